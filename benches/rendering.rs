@@ -0,0 +1,34 @@
+//! Replays every redraw recording in `tests/data` with `neovide --benchmark` and prints the
+//! results. Run with `cargo bench`.
+
+use std::{fs, path::Path, process::Command};
+
+fn main() {
+    let data_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let mut recordings = fs::read_dir(&data_dir)
+        .expect("Could not read the recordings")
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "msgpack")
+        })
+        .collect::<Vec<_>>();
+    recordings.sort();
+
+    let mut failed = false;
+    for recording in recordings {
+        let status = Command::new(env!("CARGO_BIN_EXE_neovide"))
+            .arg("--no-fork")
+            .arg("--benchmark")
+            .arg(&recording)
+            .status()
+            .expect("Could not start neovide");
+        if !status.success() {
+            eprintln!("Benchmark failed for {}", recording.display());
+            failed = true;
+        }
+    }
+    if failed {
+        std::process::exit(1);
+    }
+}
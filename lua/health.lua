@@ -0,0 +1,88 @@
+-- The health check of `:checkhealth neovide`, written to the runtimepath by the neovide module.
+
+local M = {}
+
+local health = vim.health
+-- The report_ functions were renamed in Neovim 0.10
+local start = health.start or health.report_start
+local ok = health.ok or health.report_ok
+local info = health.info or health.report_info
+local warn = health.warn or health.report_warn
+local error = health.error or health.report_error
+
+-- Above this the latency is noticeable when typing
+local SLOW_ROUNDTRIP_MS = 20
+
+local function check_environment(status)
+    start("Environment")
+    if status.server then
+        info("Connected to a Neovim server with --server")
+    end
+    if status.wayland then
+        info("Running on Wayland, which doesn't let Neovide query or set the window position")
+    end
+    if vim.fn.has("wsl") == 1 or status.wsl then
+        if type(vim.g.clipboard) == "table" and vim.g.clipboard.name == "neovide" then
+            ok("The clipboard is shared with Windows through Neovide")
+        elseif vim.fn.executable("win32yank.exe") == 1 then
+            ok("The clipboard is shared with Windows through win32yank.exe")
+        else
+            warn("The clipboard is not shared with Windows", {
+                "Remove g:neovide_no_custom_clipboard, or install win32yank.exe",
+            })
+        end
+    end
+end
+
+function M.check()
+    start("Neovide")
+    if not vim.g.neovide_channel_id then
+        error("Not running in Neovide")
+        return
+    end
+
+    local hrtime = (vim.uv or vim.loop).hrtime
+    local started = hrtime()
+    local success, status = pcall(vim.rpcrequest, vim.g.neovide_channel_id, "neovide.health")
+    local roundtrip = (hrtime() - started) / 1e6
+    if not success then
+        error("Could not query Neovide: " .. status)
+        return
+    end
+    ok("Neovide " .. status.version)
+    local latency = ("RPC roundtrip latency: %.2f ms"):format(roundtrip)
+    if roundtrip > SLOW_ROUNDTRIP_MS then
+        warn(latency, { "The connection to Neovide is slow, input will lag" })
+    else
+        ok(latency)
+    end
+
+    start("Rendering")
+    info("Renderer: " .. (status.gpu_backend ~= vim.NIL and status.gpu_backend or "unknown"))
+    info("VSync: " .. (status.vsync ~= "" and status.vsync or "unknown"))
+    if status.multigrid then
+        ok("Multigrid is enabled")
+    else
+        warn("Multigrid is disabled with --no-multigrid", {
+            "Smooth scrolling, window animations and floating window blur need multigrid",
+        })
+    end
+
+    start("Fonts")
+    if #status.fonts == 0 then
+        info("Using the default font")
+    end
+    for _, font in ipairs(status.fonts) do
+        if font.loaded then
+            ok(font.name)
+        else
+            error(font.name .. " could not be loaded", {
+                "Run :set guifont=* to list the available fonts",
+            })
+        end
+    end
+
+    check_environment(status)
+end
+
+return M
@@ -2,6 +2,8 @@
 ---@field neovide_channel_id integer
 ---@field register_clipboard boolean
 ---@field register_right_click boolean
+---@field register_primary_selection boolean
+---@field arrange_startup_files boolean
 ---@field enable_focus_command boolean
 ---@field global_variable_settings string[]
 ---@field option_settings string[]
@@ -26,7 +28,7 @@ end
 
 local function set_clipboard(register)
     return function(lines, regtype)
-        rpcrequest("neovide.set_clipboard", lines)
+        rpcrequest("neovide.set_clipboard", lines, register)
     end
 end
 
@@ -51,6 +53,262 @@ if args.register_clipboard and not vim.g.neovide_no_custom_clipboard then
     }
 end
 
+-- The files given on the command line are opened in tabs, and arranged as configured by
+-- g:neovide_open_multiple_files_in once the config has been read
+if args.arrange_startup_files then
+    vim.api.nvim_create_autocmd("VimEnter", {
+        once = true,
+        callback = function()
+            local target = vim.g.neovide_open_multiple_files_in
+            if target == "splits" or target == "hsplits" then
+                vim.cmd("silent! tabonly | all")
+            elseif target == "vsplits" then
+                vim.cmd("silent! tabonly | vertical all")
+            elseif target == "windows" then
+                -- The first file stays in this window
+                local files = {}
+                for i = 1, vim.fn.argc() - 1 do
+                    table.insert(files, vim.fn.fnamemodify(vim.fn.argv(i), ":p"))
+                end
+                vim.cmd("silent! tabonly | silent! 2,$argdelete")
+                for _, file in ipairs(files) do
+                    pcall(vim.api.nvim_buf_delete, vim.fn.bufadd(file), {})
+                end
+                rpcnotify("neovide.open_in_new_windows", files)
+            end
+        end,
+    })
+end
+
+-- Like terminals on Linux, copy visual selections to the primary selection, so that they can be
+-- pasted with the middle mouse button. Neovim pastes the "* register on middle click by default.
+if args.register_primary_selection and vim.fn.exists("*getregion") == 1
+    and not vim.g.neovide_no_custom_clipboard then
+    vim.api.nvim_create_autocmd({ "ModeChanged" }, {
+        pattern = "[vV\x16]*:*",
+        callback = function()
+            if vim.g.neovide_no_primary_selection then
+                return
+            end
+            local lines = vim.fn.getregion(vim.fn.getpos("'<"), vim.fn.getpos("'>"), {
+                type = vim.fn.visualmode(),
+            })
+            if #lines > 0 then
+                rpcrequest("neovide.set_clipboard", lines, "*")
+            end
+        end
+    })
+end
+
+-- Forward OSC 52 clipboard sequences from programs running in terminal buffers, this includes
+-- Neovim and tmux running on a remote machine through ssh. With --server the sequences go to the
+-- clipboard of the machine running Neovide. The server outlives the connection, so the autocmd
+-- replaces the one of the previous connection, and does nothing while Neovide is disconnected.
+if vim.fn.exists("##TermRequest") == 1 then
+    vim.api.nvim_create_autocmd({ "TermRequest" }, {
+        group = vim.api.nvim_create_augroup("NeovideOsc52", { clear = true }),
+        callback = function(ev)
+            local sequence = type(ev.data) == "table" and ev.data.sequence or ev.data or vim.v.termrequest
+            local payload = type(sequence) == "string" and sequence:match("^\027%]52;(.*)$")
+            local connected = not vim.tbl_isempty(vim.api.nvim_get_chan_info(vim.g.neovide_channel_id))
+            if not payload or not connected then
+                return
+            end
+            local ok, result = pcall(rpcrequest, "neovide.osc52", payload, false)
+            if ok and type(result) == "table" and result.confirm then
+                if vim.fn.confirm(result.confirm, "&Yes\n&No", 2) ~= 1 then
+                    return
+                end
+                ok, result = pcall(rpcrequest, "neovide.osc52", payload, true)
+            end
+            if not ok then
+                vim.notify(result, vim.log.levels.WARN)
+            elseif type(result) == "string" then
+                vim.api.nvim_chan_send(vim.bo[ev.buf].channel, result)
+            end
+        end
+    })
+end
+
+vim.api.nvim_create_user_command("NeovidePasteImage", function()
+    local ok, path = pcall(rpcrequest, "neovide.paste_image")
+    if not ok then
+        vim.notify(path, vim.log.levels.ERROR)
+    elseif path == vim.NIL then
+        vim.notify("The clipboard does not contain an image", vim.log.levels.WARN)
+    else
+        vim.api.nvim_put({ path }, "c", true, true)
+    end
+end, {})
+
+-- Returns the column ranges of the row colored by semantic tokens, treesitter and syntax
+-- highlighting, as lists of { start_col, end_col, group }. Later ranges have priority over earlier
+-- ones of the same kind.
+local function highlight_ranges(bufnr, row, text)
+    local semantic_tokens = {}
+    for name, ns in pairs(vim.api.nvim_get_namespaces()) do
+        if name:find("^vim_lsp_semantic_tokens") then
+            local marks = vim.api.nvim_buf_get_extmarks(bufnr, ns, { row, 0 }, { row, -1 }, {
+                details = true,
+            })
+            for _, mark in ipairs(marks) do
+                local details = mark[4]
+                if details.hl_group then
+                    local end_col = details.end_row == row and details.end_col or #text
+                    table.insert(semantic_tokens, { mark[3], end_col, details.hl_group })
+                end
+            end
+        end
+    end
+
+    local treesitter = {}
+    local ok, parser = pcall(vim.treesitter.get_parser, bufnr)
+    if ok and parser and vim.treesitter.highlighter.active[bufnr] then
+        parser:for_each_tree(function(tree, language_tree)
+            local lang = language_tree:lang()
+            local query = vim.treesitter.query.get(lang, "highlights")
+            if not query then
+                return
+            end
+            for id, node in query:iter_captures(tree:root(), bufnr, row, row + 1) do
+                local capture = query.captures[id]
+                if not capture:find("^_") then
+                    local start_row, start_col, end_row, end_col = node:range()
+                    table.insert(treesitter, {
+                        start_row < row and 0 or start_col,
+                        end_row > row and #text or end_col,
+                        "@" .. capture .. "." .. lang,
+                    })
+                end
+            end
+        end)
+    end
+
+    -- Syntax highlighting has no ranges, so it's looked up by character, and only without
+    -- treesitter, which replaces it
+    local syntax = {}
+    if not vim.treesitter.highlighter.active[bufnr] and vim.bo[bufnr].syntax ~= "" then
+        local col = 0
+        for _, char in ipairs(vim.fn.split(text, "\\zs")) do
+            local id = vim.fn.synIDtrans(vim.fn.synID(row + 1, col + 1, 1))
+            if id ~= 0 then
+                table.insert(syntax, { col, col + #char, vim.fn.synIDattr(id, "name") })
+            end
+            col = col + #char
+        end
+    end
+
+    return { semantic_tokens, treesitter, syntax }
+end
+
+-- Returns the highlight group coloring the column, semantic tokens have priority over treesitter,
+-- which in turn has priority over syntax highlighting.
+local function group_at(ranges, col)
+    for _, kind in ipairs(ranges) do
+        for i = #kind, 1, -1 do
+            local range = kind[i]
+            if range[1] <= col and col < range[2] then
+                return range[3]
+            end
+        end
+    end
+end
+
+-- Returns the resolved attributes of the highlight group. Treesitter captures like
+-- `@keyword.lua` fall back to `@keyword` when there's no highlight for the language.
+local function resolve_group(name)
+    local hl = vim.api.nvim_get_hl(0, { name = name, link = false })
+    while vim.tbl_isempty(hl) and name:find("%.") do
+        name = name:match("^(.*)%.")
+        hl = vim.api.nvim_get_hl(0, { name = name, link = false })
+    end
+    return hl
+end
+
+-- Returns the end of the character starting at the byte column.
+local function char_end(text, col)
+    local char = text:sub(col + 1):match("^[%z\1-\127\194-\244][\128-\191]*") or ""
+    return math.min(col + #char, #text)
+end
+
+vim.api.nvim_create_user_command("NeovideCopyRich", function(opts)
+    local bufnr = vim.api.nvim_get_current_buf()
+    local first_row, last_row = opts.line1 - 1, opts.line2 - 1
+
+    -- Only the selected characters are copied when the range comes from a charwise or blockwise
+    -- visual selection, otherwise the whole lines. Blocks use the byte columns of the marks.
+    local mode = vim.fn.visualmode()
+    local start_mark = vim.api.nvim_buf_get_mark(bufnr, "<")
+    local end_mark = vim.api.nvim_buf_get_mark(bufnr, ">")
+    local from_selection = opts.range == 2
+        and start_mark[1] == opts.line1
+        and end_mark[1] == opts.line2
+        and (mode == "v" or mode == "\22")
+    local function selected_columns(row, text)
+        if not from_selection then
+            return 0, #text
+        elseif mode == "v" then
+            return row == first_row and math.min(start_mark[2], #text) or 0,
+                row == last_row and char_end(text, end_mark[2]) or #text
+        end
+        local left = math.min(start_mark[2], end_mark[2])
+        local right = math.max(start_mark[2], end_mark[2])
+        return math.min(left, #text), char_end(text, right)
+    end
+
+    local highlights = {}
+    local lines = {}
+    for i, text in ipairs(vim.api.nvim_buf_get_lines(bufnr, first_row, last_row + 1, true)) do
+        local row = first_row + i - 1
+        local start_col, end_col = selected_columns(row, text)
+        local ranges = highlight_ranges(bufnr, row, text)
+
+        -- Split the text into runs of the same highlight group, and resolve each group only once
+        local runs = {}
+        local col = start_col
+        for _, char in ipairs(vim.fn.split(text:sub(start_col + 1, end_col), "\\zs")) do
+            local group = group_at(ranges, col) or ""
+            local last = runs[#runs]
+            if last and last.group == group then
+                last.text = last.text .. char
+            else
+                runs[#runs + 1] = { group = group, text = char }
+            end
+            col = col + #char
+        end
+
+        local spans = {}
+        for _, run in ipairs(runs) do
+            if not highlights[run.group] then
+                highlights[run.group] = run.group == "" and {} or resolve_group(run.group)
+            end
+            local hl = highlights[run.group]
+            local span = {
+                text = run.text,
+                fg = hl.fg,
+                bg = hl.bg,
+                bold = hl.bold or false,
+                italic = hl.italic or false,
+                underline = hl.underline or false,
+            }
+            local last = spans[#spans]
+            if last and last.fg == span.fg and last.bg == span.bg and last.bold == span.bold
+                and last.italic == span.italic and last.underline == span.underline then
+                last.text = last.text .. run.text
+            else
+                spans[#spans + 1] = span
+            end
+        end
+        lines[#lines + 1] = spans
+    end
+
+    local normal = vim.api.nvim_get_hl(0, { name = "Normal", link = false })
+    local ok, err = pcall(rpcrequest, "neovide.copy_rich", { lines = lines, fg = normal.fg, bg = normal.bg })
+    if not ok then
+        vim.notify(err, vim.log.levels.ERROR)
+    end
+end, { range = true })
+
 if args.register_right_click then
     vim.api.nvim_create_user_command("NeovideRegisterRightClick", function()
         rpcnotify("neovide.register_right_click")
@@ -64,6 +322,432 @@ vim.api.nvim_create_user_command("NeovideFocus", function()
     rpcnotify("neovide.focus_window")
 end, {})
 
+vim.api.nvim_create_user_command("NeovideLogs", function(opts)
+    rpcnotify("neovide.logs", opts.fargs[1] or "", opts.fargs[2] or "")
+end, {
+    nargs = "*",
+    complete = function(_, line)
+        -- Only the level is completed, the module is free text
+        if #vim.split(line, "%s+") > 2 then
+            return {}
+        end
+        return { "error", "warn", "info", "debug" }
+    end,
+})
+
+vim.api.nvim_create_user_command("NeovideRequestAttention", function(opts)
+    rpcnotify("neovide.request_attention", opts.args)
+end, {
+    nargs = "?",
+    complete = function()
+        return { "critical" }
+    end,
+})
+
+vim.api.nvim_create_user_command("NeovideProfile", function(opts)
+    local ok, err = pcall(rpcrequest, "neovide.set_profile", opts.args)
+    if not ok then
+        vim.notify(err, vim.log.levels.ERROR)
+    end
+end, {
+    nargs = "?",
+    complete = function()
+        return rpcrequest("neovide.profiles")
+    end,
+})
+
+vim.api.nvim_create_user_command("NeovideResizeWindow", function(opts)
+    local delta_cols = tonumber(opts.fargs[1]) or 0
+    local delta_lines = tonumber(opts.fargs[2]) or 0
+    rpcnotify("neovide.resize_window", delta_cols, delta_lines, opts.fargs[3] or "NW")
+end, { nargs = "+" })
+
+vim.api.nvim_create_user_command("NeovideProjects", function()
+    if not require("neovide").project_launcher() then
+        vim.notify("No recent projects", vim.log.levels.INFO)
+    end
+end, {})
+
+vim.api.nvim_create_user_command("NeovideResetZoom", function()
+    vim.g.neovide_scale_factor = 1.0
+end, {})
+
+-- The options zen mode changes, and their values before entering it
+local zen_mode_saved_options = nil
+
+vim.api.nvim_create_user_command("NeovideZenMode", function(opts)
+    local enable = zen_mode_saved_options == nil
+    if opts.args == "on" or opts.args == "off" then
+        enable = opts.args == "on"
+    elseif opts.args ~= "" then
+        vim.notify("NeovideZenMode: expected on or off, got " .. opts.args, vim.log.levels.ERROR)
+        return
+    end
+    if enable and zen_mode_saved_options == nil then
+        zen_mode_saved_options = { showtabline = vim.o.showtabline, laststatus = vim.o.laststatus }
+        vim.o.showtabline = 0
+        vim.o.laststatus = 0
+    elseif not enable and zen_mode_saved_options ~= nil then
+        vim.o.showtabline = zen_mode_saved_options.showtabline
+        vim.o.laststatus = zen_mode_saved_options.laststatus
+        zen_mode_saved_options = nil
+    end
+    rpcnotify("neovide.zen_mode", enable)
+end, {
+    nargs = "?",
+    complete = function()
+        return { "on", "off" }
+    end,
+})
+
+-- Send the ligature overrides of the buffers shown in each window, as pairs of window handle and
+-- b:neovide_ligatures. The renderer falls back to g:neovide_ligatures for the other windows.
+local function update_window_ligatures()
+    local ligatures = {}
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        local enabled = vim.b[vim.api.nvim_win_get_buf(win)].neovide_ligatures
+        if enabled ~= nil then
+            table.insert(ligatures, { win, enabled == true or enabled == 1 })
+        end
+    end
+    rpcnotify("neovide.window_ligatures", ligatures)
+end
+
+vim.api.nvim_create_autocmd({ "BufWinEnter", "WinNew", "WinClosed", "FileType", "BufEnter" }, {
+    callback = function()
+        vim.schedule(update_window_ligatures)
+    end
+})
+
+-- The width of the number and sign columns, and the horizontal scroll of the window
+local function window_text_offset(win)
+    local textoff = vim.fn.getwininfo(win)[1].textoff
+    local leftcol = vim.api.nvim_win_call(win, function()
+        return vim.fn.winsaveview().leftcol
+    end)
+    return textoff, leftcol
+end
+
+local function is_enabled(value)
+    return value == true or value == 1
+end
+
+-- Collecting the information of every window on each scroll is only worth it when some window
+-- can show indent guides
+local function indent_guides_used()
+    if is_enabled(vim.g.neovide_indent_guides) then
+        return true
+    end
+    local filetypes = vim.g.neovide_indent_guides_filetypes
+    if type(filetypes) == "table" then
+        for _, enabled in pairs(filetypes) do
+            if is_enabled(enabled) then
+                return true
+            end
+        end
+    end
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        if is_enabled(vim.b[vim.api.nvim_win_get_buf(win)].neovide_indent_guides) then
+            return true
+        end
+    end
+    return false
+end
+
+-- b:neovide_indent_guides, or the entry of the filetype in g:neovide_indent_guides_filetypes, nil
+-- when neither is set
+local function buffer_indent_guides(buf)
+    local enabled = vim.b[buf].neovide_indent_guides
+    if enabled == nil then
+        local filetypes = vim.g.neovide_indent_guides_filetypes
+        if type(filetypes) == "table" then
+            enabled = filetypes[vim.bo[buf].filetype]
+        end
+    end
+    if enabled ~= nil then
+        enabled = is_enabled(enabled)
+    end
+    return enabled
+end
+
+local window_indents_sent = false
+
+-- Send what the renderer needs to draw the indent guides of each window, as lists of window
+-- handle, shiftwidth, width of the number and sign columns, horizontal scroll and
+-- b:neovide_indent_guides, or the entry of the filetype in g:neovide_indent_guides_filetypes. The
+-- renderer falls back to g:neovide_indent_guides when it's nil.
+local function update_window_indents()
+    if not indent_guides_used() then
+        -- The guides are removed once when they are turned off
+        if window_indents_sent then
+            window_indents_sent = false
+            rpcnotify("neovide.window_indents", {})
+        end
+        return
+    end
+    window_indents_sent = true
+
+    local indents = {}
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        local buf = vim.api.nvim_win_get_buf(win)
+        local textoff, leftcol = window_text_offset(win)
+        local shiftwidth = vim.api.nvim_win_call(win, vim.fn.shiftwidth)
+        local enabled = buffer_indent_guides(buf)
+        table.insert(indents, {
+            win,
+            shiftwidth,
+            textoff,
+            leftcol,
+            enabled == nil and vim.NIL or enabled,
+        })
+    end
+    rpcnotify("neovide.window_indents", indents)
+end
+
+-- The screen cells covered by the trailing whitespace of the lines shown in the window, as
+-- [row, first column, last column] in the grid of the window. Only the lines on the screen are
+-- looked at, so this doesn't depend on the size of the buffer.
+local function trailing_whitespace_cells(win, buf)
+    local info = vim.fn.getwininfo(win)[1]
+    local text_left = info.wincol + info.textoff
+    local right = info.wincol + info.width - 1
+    local lines = vim.api.nvim_buf_get_lines(buf, info.topline - 1, info.botline, false)
+    local cells = {}
+    vim.api.nvim_win_call(win, function()
+        for i, line in ipairs(lines) do
+            local lnum = info.topline + i - 1
+            local start = line:find("%s+$")
+            if start and vim.fn.foldclosed(lnum) == -1 then
+                local first = vim.fn.screenpos(win, lnum, start)
+                local last = vim.fn.screenpos(win, lnum, #line)
+                -- Either end can be scrolled out of the window horizontally
+                if first.row == 0 then
+                    first = { row = last.row, col = text_left }
+                end
+                if last.row == 0 then
+                    last = { row = first.row, endcol = right }
+                end
+                if first.row > 0 then
+                    for row = first.row, last.row do
+                        local first_col = row == first.row and first.col or text_left
+                        local last_col = row == last.row and last.endcol or right
+                        table.insert(cells, {
+                            row - info.winrow,
+                            first_col - info.wincol,
+                            last_col - info.wincol,
+                        })
+                    end
+                end
+            end
+        end
+    end)
+    return cells
+end
+
+local window_trailing_whitespace_sent = false
+
+-- Neovim draws the trailing whitespace just like the empty end of the line, so the renderer is
+-- sent where it is in each window, as lists of window handle and cells, when
+-- g:neovide_trailing_whitespace is set. Buffers with the indent guides disabled are left out.
+local function update_window_trailing_whitespace()
+    if not is_enabled(vim.g.neovide_trailing_whitespace) then
+        if window_trailing_whitespace_sent then
+            window_trailing_whitespace_sent = false
+            rpcnotify("neovide.window_trailing_whitespace", {})
+        end
+        return
+    end
+    window_trailing_whitespace_sent = true
+
+    local windows = {}
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        local buf = vim.api.nvim_win_get_buf(win)
+        if buffer_indent_guides(buf) ~= false then
+            table.insert(windows, { win, trailing_whitespace_cells(win, buf) })
+        end
+    end
+    rpcnotify("neovide.window_trailing_whitespace", windows)
+end
+
+-- The highlight groups drawn by the renderer are hidden by a highlight namespace of Neovide, used
+-- by the windows while the overlays are enabled, which removes their background. The global
+-- definitions are left alone, so that the colorscheme and plugins still see the real colors.
+local overlay_namespace = vim.api.nvim_create_namespace("neovide_overlays")
+
+-- Hides the background of the group in the windows, and returns its color for the renderer
+local function hide_highlight(name)
+    local definition = vim.api.nvim_get_hl(0, { name = name, link = false })
+    local hidden = vim.deepcopy(definition)
+    hidden.bg = nil
+    hidden.ctermbg = nil
+    vim.api.nvim_set_hl(overlay_namespace, name, hidden)
+    return definition.bg or vim.NIL
+end
+
+-- Shows the group with its global definition again
+local function restore_highlight(name)
+    local definition = vim.api.nvim_get_hl(0, { name = name, link = false })
+    vim.api.nvim_set_hl(overlay_namespace, name, definition)
+end
+
+-- Windows that use a namespace of their own, set by a plugin, are left alone
+local function set_overlay_namespace(enabled)
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        local namespace = vim.api.nvim_get_hl_ns({ winid = win })
+        if enabled and namespace <= 0 then
+            vim.api.nvim_win_set_hl_ns(win, overlay_namespace)
+        elseif not enabled and namespace == overlay_namespace then
+            vim.api.nvim_win_set_hl_ns(win, 0)
+        end
+    end
+end
+
+-- The screen columns of 'colorcolumn', which can be relative to 'textwidth'
+local function color_columns(win, textoff, leftcol)
+    local columns = {}
+    local textwidth = vim.bo[vim.api.nvim_win_get_buf(win)].textwidth
+    local width = vim.api.nvim_win_get_width(win)
+    for _, column in ipairs(vim.split(vim.wo[win].colorcolumn, ",", { trimempty = true })) do
+        local number = tonumber(column)
+        if column:match("^[+-]") then
+            number = textwidth > 0 and textwidth + number or nil
+        end
+        if number and number > leftcol then
+            local screen_column = textoff + number - 1 - leftcol
+            if screen_column < width then
+                table.insert(columns, screen_column)
+            end
+        end
+    end
+    return columns
+end
+
+local overlays_enabled = false
+
+-- Send the 'cursorline' and 'colorcolumn' of each window, and the colors of their highlight
+-- groups, when they are drawn by the renderer with g:neovide_cursorline_overlay and
+-- g:neovide_colorcolumn_overlay
+local function update_window_highlights()
+    local cursorline = vim.g.neovide_cursorline_overlay
+    local colorcolumn = vim.g.neovide_colorcolumn_overlay
+    if not cursorline and not colorcolumn then
+        -- The windows stop using the namespace, and the renderer stops drawing, once when the
+        -- overlays are turned off
+        if overlays_enabled then
+            overlays_enabled = false
+            set_overlay_namespace(false)
+            rpcnotify("neovide.window_highlights", {}, vim.empty_dict())
+        end
+        return
+    end
+    overlays_enabled = true
+
+    -- The group of an overlay that is off is copied from the global definition again, which
+    -- may have changed since it was last hidden
+    local colors = vim.empty_dict()
+    if cursorline then
+        colors.cursorline = hide_highlight("CursorLine")
+    else
+        restore_highlight("CursorLine")
+    end
+    if colorcolumn then
+        colors.colorcolumn = hide_highlight("ColorColumn")
+    else
+        restore_highlight("ColorColumn")
+    end
+
+    set_overlay_namespace(true)
+
+    local windows = {}
+    for _, win in ipairs(vim.api.nvim_list_wins()) do
+        local textoff, leftcol = window_text_offset(win)
+        table.insert(windows, {
+            win,
+            vim.wo[win].cursorline,
+            color_columns(win, textoff, leftcol),
+        })
+    end
+    rpcnotify("neovide.window_highlights", windows, colors)
+end
+
+local window_updates_scheduled = false
+local function schedule_window_updates()
+    -- Several of the events are usually triggered together, only send the updates once
+    if not window_updates_scheduled then
+        window_updates_scheduled = true
+        vim.schedule(function()
+            window_updates_scheduled = false
+            update_window_indents()
+            update_window_trailing_whitespace()
+            update_window_highlights()
+        end)
+    end
+end
+
+local trailing_whitespace_scheduled = false
+local function schedule_trailing_whitespace_update()
+    if is_enabled(vim.g.neovide_trailing_whitespace) and not trailing_whitespace_scheduled then
+        trailing_whitespace_scheduled = true
+        vim.schedule(function()
+            trailing_whitespace_scheduled = false
+            update_window_trailing_whitespace()
+        end)
+    end
+end
+
+-- Neovim only redraws horizontally scrolled windows, so the change of leftcol is sent right away,
+-- before the redraw, for the renderer to slide the text from where it was. The skipcol of
+-- 'smoothscroll' isn't part of the viewport events either, and tells the renderer that the view
+-- scrolled within a wrapped line.
+vim.api.nvim_create_autocmd("WinScrolled", {
+    callback = function()
+        local scrolled = {}
+        local skipcols = {}
+        for win, delta in pairs(vim.v.event) do
+            local handle = tonumber(win)
+            if handle and vim.api.nvim_win_is_valid(handle) then
+                if delta.leftcol ~= 0 then
+                    local textoff = vim.fn.getwininfo(handle)[1].textoff
+                    table.insert(scrolled, { handle, delta.leftcol, textoff })
+                end
+                if (delta.skipcol or 0) ~= 0 then
+                    local skipcol = vim.api.nvim_win_call(handle, vim.fn.winsaveview).skipcol
+                    table.insert(skipcols, { handle, skipcol })
+                end
+            end
+        end
+        if #scrolled > 0 then
+            rpcnotify("neovide.window_scrolled_columns", scrolled)
+        end
+        if #skipcols > 0 then
+            rpcnotify("neovide.window_skipcols", skipcols)
+        end
+    end,
+})
+
+vim.api.nvim_create_autocmd(
+    { "BufWinEnter", "WinNew", "WinClosed", "FileType", "BufEnter", "WinScrolled", "WinResized" },
+    { callback = schedule_window_updates }
+)
+vim.api.nvim_create_autocmd("OptionSet", {
+    pattern = { "shiftwidth", "tabstop", "number", "relativenumber", "numberwidth", "signcolumn",
+        "foldcolumn", "statuscolumn", "cursorline", "colorcolumn", "textwidth" },
+    callback = schedule_window_updates,
+})
+-- Editing changes the trailing whitespace without scrolling
+vim.api.nvim_create_autocmd({ "TextChanged", "TextChangedI" }, {
+    callback = schedule_trailing_whitespace_update,
+})
+-- The hidden highlights are copied from the new colors
+vim.api.nvim_create_autocmd("ColorScheme", { callback = schedule_window_updates })
+
+-- The colorscheme may have set its own terminal colors, which Neovide replaces again
+vim.api.nvim_create_autocmd("ColorScheme", {
+    callback = function()
+        rpcnotify("neovide.colorscheme_changed")
+    end,
+})
+
 vim.api.nvim_exec([[
 function! WatchGlobal(variable, callback)
     call dictwatcheradd(g:, a:variable, a:callback)
@@ -76,6 +760,11 @@ for _,global_variable_setting in ipairs(args.global_variable_settings) do
     end
     vim.fn.WatchGlobal("neovide_" .. global_variable_setting, callback)
 end
+vim.fn.WatchGlobal("neovide_indent_guides", schedule_window_updates)
+vim.fn.WatchGlobal("neovide_indent_guides_filetypes", schedule_window_updates)
+vim.fn.WatchGlobal("neovide_trailing_whitespace", schedule_window_updates)
+vim.fn.WatchGlobal("neovide_cursorline_overlay", schedule_window_updates)
+vim.fn.WatchGlobal("neovide_colorcolumn_overlay", schedule_window_updates)
 
 for _,option_setting in ipairs(args.option_settings) do
     vim.api.nvim_create_autocmd({ "OptionSet" }, {
@@ -102,6 +791,20 @@ vim.api.nvim_create_autocmd({ "VimEnter" }, {
     end
 })
 
+-- Shows the recent projects when Neovide starts without files
+vim.api.nvim_create_autocmd({ "VimEnter" }, {
+    once = true,
+    callback = function()
+        if vim.g.neovide_project_launcher == false or vim.fn.argc() > 0 then
+            return
+        end
+        if vim.api.nvim_buf_line_count(0) > 1 or vim.api.nvim_buf_get_name(0) ~= "" or vim.bo.modified then
+            return
+        end
+        require("neovide").project_launcher()
+    end
+})
+
 -- Create auto command for retrieving exit code from neovim on quit.
 vim.api.nvim_create_autocmd({ "VimLeavePre" }, {
     pattern = "*",
@@ -155,7 +155,7 @@ local function setup_autocommand()
             end
 
             -- Don't show the intro if a buffer is loaded
-            if vim.api.nvim_buf_line_count(0) > 1 or vim.api.nvim_buf_get_name(0) ~= "" then
+            if vim.api.nvim_buf_line_count(0) > 1 or vim.api.nvim_buf_get_name(0) ~= "" or vim.bo.modified then
                 return
             end
 
@@ -0,0 +1,574 @@
+-- The `neovide` Lua module, available with `require("neovide")` from the configs and plugins
+-- running in Neovide. It wraps the RPC calls on g:neovide_channel_id.
+
+---@class ModuleArgs
+---@field version string
+---@field api_level integer
+---@field register_right_click boolean
+---@field health_check string
+
+---@type ModuleArgs
+local args = ...
+
+local M = {}
+
+--- The version of the running Neovide, like "0.12.2".
+M.version = args.version
+
+--- Increased every time functions are added to this module.
+M.api_level = args.api_level
+
+local function rpcnotify(method, ...)
+    vim.rpcnotify(vim.g.neovide_channel_id, method, ...)
+end
+
+local function rpcrequest(method, ...)
+    return vim.rpcrequest(vim.g.neovide_channel_id, method, ...)
+end
+
+-- Converts vim.NIL returned by requests to nil
+local function nil_if_vim_nil(value)
+    if value == vim.NIL then
+        return nil
+    end
+    return value
+end
+
+---@class Geometry
+---@field position { x: integer, y: integer }?
+---@field width integer
+---@field height integer
+---@field inner_width integer
+---@field inner_height integer
+---@field scale_factor number
+
+---@class MenuItem
+---@field title string
+---@field command string?
+---@field menu string?
+
+-- Window control
+
+--- Brings the window to the front and focuses it.
+function M.focus()
+    rpcnotify("neovide.focus_window")
+end
+
+--- Asks for the attention of the user without taking the focus.
+---@param critical boolean?
+function M.request_attention(critical)
+    rpcnotify("neovide.request_attention", critical and "critical" or "")
+end
+
+--- Grows or shrinks the window by grid cells, keeping the corner given by `anchor` in place.
+---@param columns integer
+---@param lines integer
+---@param anchor "NW"|"NE"|"SW"|"SE"|nil
+function M.resize_window(columns, lines, anchor)
+    rpcnotify("neovide.resize_window", columns, lines, anchor or "NW")
+end
+
+--- Moves the top left corner of the window to the screen position, in physical pixels.
+---@param x integer
+---@param y integer
+function M.set_window_position(x, y)
+    rpcnotify("neovide.set_window_position", x, y)
+end
+
+---@return Geometry
+function M.get_window_geometry()
+    local geometry = rpcrequest("neovide.get_window_geometry")
+    geometry.position = nil_if_vim_nil(geometry.position)
+    return geometry
+end
+
+--- Enters or leaves zen mode. Use `:NeovideZenMode` to also hide the tabline and statusline.
+---@param enabled boolean
+function M.set_zen_mode(enabled)
+    rpcnotify("neovide.zen_mode", enabled)
+end
+
+---@class Padding
+---@field top integer?
+---@field left integer?
+---@field right integer?
+---@field bottom integer?
+
+--- Replaces the sides of `g:neovide_padding_*` that are given, in pixels, animated with
+--- `g:neovide_padding_animation_length`. `nil` goes back to the settings.
+---@param padding Padding?
+function M.set_padding(padding)
+    rpcnotify("neovide.set_padding", padding)
+end
+
+--- Magnifies a window by the factor without changing the layout, for example while presenting.
+--- The window grows from its top left corner over the ones next to it. 1 restores it.
+---@param win integer 0 for the current window
+---@param factor number
+function M.set_window_zoom(win, factor)
+    if win == 0 then
+        win = vim.api.nvim_get_current_win()
+    end
+    rpcnotify("neovide.set_window_zoom", win, factor)
+end
+
+---@param shape string
+function M.set_mouse_shape(shape)
+    rpcnotify("neovide.set_mouse_shape", shape)
+end
+
+--- A path to an image, or base64 encoded image data. `nil` restores the Neovide icon.
+---@param icon string?
+function M.set_window_icon(icon)
+    rpcnotify("neovide.set_window_icon", icon)
+end
+
+---@param items MenuItem[]
+function M.set_menu_items(items)
+    rpcnotify("neovide.set_menu_items", items)
+end
+
+---@param items MenuItem[]
+function M.set_context_menu(items)
+    rpcnotify("neovide.set_context_menu", items)
+end
+
+--- Saves the next frame to the PNG file at the absolute path.
+---@param path string
+function M.screenshot(path)
+    rpcnotify("neovide.screenshot", path)
+end
+
+--- Saves every frame for the duration to the directory at the absolute path.
+---@param directory string
+---@param seconds number
+function M.record_frames(directory, seconds)
+    rpcnotify("neovide.record_frames", directory, seconds)
+end
+
+if args.register_right_click then
+    function M.register_right_click()
+        rpcnotify("neovide.register_right_click")
+    end
+
+    function M.unregister_right_click()
+        rpcnotify("neovide.unregister_right_click")
+    end
+end
+
+-- Notifications
+
+--- A percentage, "indeterminate" or `nil` to remove the progress.
+---@param progress number|"indeterminate"|nil
+function M.set_progress(progress)
+    rpcnotify("neovide.set_progress", progress)
+end
+
+---@param badge string|integer|nil
+function M.set_badge(badge)
+    rpcnotify("neovide.set_badge", badge)
+end
+
+--- A path to an image shown over the taskbar button on Windows, `nil` removes it.
+---@param path string?
+function M.set_icon_overlay(path)
+    rpcnotify("neovide.set_icon_overlay", path)
+end
+
+-- Settings
+
+--- The current value of every setting, keyed by the name without the neovide_ prefix.
+---@return table<string, any>
+function M.get_settings()
+    return vim.tbl_map(nil_if_vim_nil, rpcrequest("neovide.get_all_settings"))
+end
+
+--- Raises an error for unknown settings and values of the wrong type.
+---@param name string
+---@param value any
+function M.set_setting(name, value)
+    rpcrequest("neovide.set_setting", name, value)
+end
+
+---@return string[]
+function M.profiles()
+    return rpcrequest("neovide.profiles")
+end
+
+--- Switches to the profile of the config file, `nil` goes back to the plain config.
+---@param name string?
+function M.set_profile(name)
+    rpcrequest("neovide.set_profile", name or "")
+end
+
+-- Cursor effects
+
+---@class CursorVfx
+---@field name string
+---@field spawn_rate number
+---@field lifetime number
+---@field color_fn "cursor"|"foreground"|"rainbow"|string|nil
+---@field motion "static"|"drift"|"rise"|"fall"|"spiral"|nil
+---@field speed number?
+---@field size number?
+---@field curl number?
+
+--- Adds a particle effect, used by setting g:neovide_cursor_vfx_mode to its name. Raises an error
+--- for unknown fields and values out of range.
+---@param vfx CursorVfx
+function M.register_cursor_vfx(vfx)
+    rpcrequest("neovide.register_cursor_vfx", vfx)
+end
+
+-- Measurements
+
+---@class Timings
+---@field frame_times number[]
+---@field draw_latencies number[]
+---@field key_latencies number[]
+
+--- The last `count` frame times, and the latencies from receiving draw commands and from sending
+--- a key to the frame showing them, in milliseconds. Returns all the measurements kept without a
+--- count.
+---@param count integer?
+---@return Timings
+function M.get_timings(count)
+    return rpcrequest("neovide.get_timings", count)
+end
+
+---@class FontCacheUsage
+---@field glyph_bytes integer
+---@field glyph_limit integer
+---@field shaped_texts integer
+---@field shaped_text_capacity integer
+---@field loaded_fonts integer
+---@field loaded_font_capacity integer
+
+--- How full the caches of the rasterized glyphs, the shaped texts and the loaded fonts are.
+---@return FontCacheUsage
+function M.get_font_cache_usage()
+    return rpcrequest("neovide.get_font_cache_usage")
+end
+
+-- Clipboard
+
+--- Saves the image on the clipboard as a PNG file, and returns its path.
+---@return string?
+function M.paste_image()
+    return nil_if_vim_nil(rpcrequest("neovide.paste_image"))
+end
+
+-- Quitting
+
+-- Called by Neovide when the window is closed. Quits right away when nothing is modified, and
+-- otherwise asks Neovide to show its quit dialog, since the confirm prompt of Neovim is easy to
+-- miss after clicking the close button.
+function M._quit()
+    local confirm = vim.g.neovide_confirm_quit
+    if confirm == false or confirm == 0 then
+        vim.cmd("qa!")
+        return
+    end
+    local modified = {}
+    for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+        if vim.bo[buf].modified and vim.bo[buf].buftype == "" then
+            local name = vim.api.nvim_buf_get_name(buf)
+            table.insert(modified, name == "" and "[No Name]" or vim.fn.fnamemodify(name, ":~:."))
+        end
+    end
+    if #modified == 0 then
+        -- Still asks about running terminal jobs
+        vim.cmd("confirm qa")
+    else
+        rpcnotify("neovide.confirm_quit", modified)
+    end
+end
+
+-- Called by Neovide with the choice made in the quit dialog
+---@param choice "save"|"discard"
+function M._quit_with(choice)
+    if choice == "save" then
+        -- Buffers without a name can't be written, so Neovim shows the error and stays open
+        local ok, error = pcall(vim.cmd, "wall")
+        if not ok then
+            vim.notify(error, vim.log.levels.ERROR)
+            return
+        end
+        vim.cmd("confirm qa")
+    else
+        vim.cmd("qa!")
+    end
+end
+
+-- Command palette
+
+--- Opens the command palette, a picker for commands, buffers and files drawn by Neovide.
+function M.command_palette()
+    rpcnotify("neovide.command_palette")
+end
+
+-- The most files listed in the palette, from the current directory and the recent files
+local PALETTE_MAX_FILES = 500
+-- The directory entries scanned before letting Neovim handle other events
+local PALETTE_SCAN_STEP = 100
+
+-- The files found under each directory the palette was opened in, and the directory being scanned
+local palette_files = {}
+local palette_scanning = nil
+
+-- Hidden files and the files in hidden directories, like .git, are skipped
+local function is_hidden(path)
+    return path:find("^%.") ~= nil or path:find("[/\\]%.") ~= nil
+end
+
+-- Lists the files under `dir` a few at a time, so that a big directory doesn't block Neovim
+local function scan_palette_files(dir, on_done)
+    local files = {}
+    local entries = vim.fs.dir(dir, {
+        depth = math.huge,
+        skip = function(path)
+            return not is_hidden(path)
+        end,
+    })
+    local function step()
+        for _ = 1, PALETTE_SCAN_STEP do
+            local path, type = entries()
+            if path == nil or #files >= PALETTE_MAX_FILES then
+                on_done(files)
+                return
+            end
+            if type == "file" and not is_hidden(path) then
+                table.insert(files, dir .. "/" .. path)
+            end
+        end
+        vim.schedule(step)
+    end
+    step()
+end
+
+-- Called by Neovide when the palette opens, sends the commands, the listed buffers, the recent
+-- files and the files under the current directory as `{ kind, label, value }` lists. The files
+-- found the last time are sent right away, and the list is sent again when the directory has
+-- been scanned, unless `rescan` is false.
+function M._command_palette_items(rescan)
+    local items = {}
+    for _, name in ipairs(vim.fn.getcompletion("", "command")) do
+        table.insert(items, { "command", name, name })
+    end
+    for _, buf in ipairs(vim.api.nvim_list_bufs()) do
+        if vim.bo[buf].buflisted then
+            local name = vim.api.nvim_buf_get_name(buf)
+            name = name == "" and "[No Name]" or vim.fn.fnamemodify(name, ":~:.")
+            table.insert(items, { "buffer", ("%d: %s"):format(buf, name), tostring(buf) })
+        end
+    end
+    local files = {}
+    for _, file in ipairs(vim.v.oldfiles) do
+        if #files >= PALETTE_MAX_FILES then
+            break
+        end
+        if vim.fn.filereadable(file) == 1 then
+            table.insert(files, vim.fn.fnamemodify(file, ":p"))
+        end
+    end
+    local cwd = vim.fn.getcwd()
+    local seen = {}
+    local count = 0
+    for _, file in ipairs(vim.list_extend(files, palette_files[cwd] or {})) do
+        if count >= PALETTE_MAX_FILES then
+            break
+        end
+        if not seen[file] then
+            seen[file] = true
+            count = count + 1
+            table.insert(items, { "file", vim.fn.fnamemodify(file, ":~:."), file })
+        end
+    end
+    rpcnotify("neovide.command_palette_items", items)
+
+    if rescan ~= false and palette_scanning ~= cwd then
+        palette_scanning = cwd
+        scan_palette_files(cwd, function(found)
+            palette_files[cwd] = found
+            palette_scanning = nil
+            if vim.fn.getcwd() == cwd then
+                M._command_palette_items(false)
+            end
+        end)
+    end
+end
+
+-- The most projects listed by the launcher, and the recent files previewed for each
+local LAUNCHER_MAX_PROJECTS = 50
+local LAUNCHER_PREVIEW_FILES = 4
+
+-- The closest directory above the file with a repository in it
+local function project_root(file, roots)
+    local dir = vim.fs.dirname(file)
+    if roots[dir] == nil then
+        local marker = vim.fs.find({ ".git", ".hg", ".svn" }, { path = dir, upward = true })[1]
+        roots[dir] = marker and vim.fs.dirname(marker) or false
+    end
+    return roots[dir] or nil
+end
+
+-- The projects of the recent files, most recent first, with the recent files of each
+local function recent_projects()
+    local projects = {}
+    local by_root = {}
+    local roots = {}
+    for _, file in ipairs(vim.v.oldfiles) do
+        if vim.fn.filereadable(file) == 1 then
+            local root = project_root(vim.fn.fnamemodify(file, ":p"), roots)
+            if root and not by_root[root] then
+                if #projects >= LAUNCHER_MAX_PROJECTS then
+                    break
+                end
+                by_root[root] = { root = root, files = {} }
+                table.insert(projects, by_root[root])
+            end
+            if root then
+                table.insert(by_root[root].files, file)
+            end
+        end
+    end
+    return projects
+end
+
+-- The projects shown by the launcher, by root, so that they aren't searched again when one is
+-- chosen
+local launcher_projects = {}
+
+--- Shows the recent projects, and the sessions saved in them, in the palette. Choosing a project
+--- changes to its directory and opens its most recent file. Returns false when there's nothing to
+--- show.
+---@return boolean
+function M.project_launcher()
+    local items = {}
+    launcher_projects = {}
+    for _, project in ipairs(recent_projects()) do
+        launcher_projects[project.root] = project
+        local label = vim.fn.fnamemodify(project.root, ":t")
+        local preview = { vim.fn.fnamemodify(project.root, ":~") }
+        for i = 1, math.min(#project.files, LAUNCHER_PREVIEW_FILES) do
+            local file = project.files[i]:sub(#project.root + 2)
+            table.insert(preview, "  " .. file)
+        end
+        preview = table.concat(preview, "\n")
+        table.insert(items, { "project", label, project.root, preview })
+        local session = project.root .. "/Session.vim"
+        if vim.fn.filereadable(session) == 1 then
+            table.insert(items, { "session", label .. " (session)", session, preview })
+        end
+    end
+    if #items == 0 then
+        return false
+    end
+    rpcnotify("neovide.project_launcher", items)
+    return true
+end
+
+-- Called by Neovide when an item of the palette is chosen
+function M._command_palette_run(kind, value)
+    local ok, error = pcall(function()
+        if kind == "command" then
+            vim.cmd(value)
+        elseif kind == "buffer" then
+            vim.cmd.buffer(tonumber(value))
+        elseif kind == "project" then
+            vim.cmd.cd(vim.fn.fnameescape(value))
+            local project = launcher_projects[value]
+            vim.cmd.edit(project and vim.fn.fnameescape(project.files[1]) or ".")
+        elseif kind == "session" then
+            -- Sessions can run any command, so they need to be trusted like exrc files
+            if vim.secure.read(value) then
+                vim.cmd.source(vim.fn.fnameescape(value))
+            end
+        else
+            vim.cmd.edit(vim.fn.fnameescape(value))
+        end
+    end)
+    if not ok then
+        vim.notify(error, vim.log.levels.ERROR)
+    end
+end
+
+--- Returns true when the function exists in this version of Neovide, or the API level is at
+--- least the given one.
+---@param feature string|integer
+---@return boolean
+function M.has(feature)
+    if type(feature) == "number" then
+        return M.api_level >= feature
+    end
+    return type(rawget(M, feature)) == "function"
+end
+
+-- The functions only available on other platforms do nothing and warn once, so that configs shared
+-- between machines keep working. Other unknown names are nil like in any table, use M.has() to
+-- check for functions added in newer versions.
+local PLATFORM_FUNCTIONS = {
+    register_right_click = true,
+    unregister_right_click = true,
+}
+local warned = {}
+setmetatable(M, {
+    __index = function(_, name)
+        if not PLATFORM_FUNCTIONS[name] then
+            return nil
+        end
+        return function()
+            if not warned[name] then
+                warned[name] = true
+                vim.notify(
+                    ("neovide.%s() is not supported by Neovide on this platform"):format(name),
+                    vim.log.levels.WARN
+                )
+            end
+        end
+    end,
+})
+
+package.loaded["neovide"] = M
+
+-- :checkhealth only finds health checks in the files on the runtimepath, so the one of this version
+-- of Neovide is written to the cache directory, when it's missing or a different version
+local runtime = vim.fn.stdpath("cache") .. "/neovide/runtime"
+local health_path = runtime .. "/lua/neovide/health.lua"
+local function read_file(path)
+    local file = io.open(path, "r")
+    if not file then
+        return nil
+    end
+    local content = file:read("*a")
+    file:close()
+    return content
+end
+local function write_health_check()
+    if read_file(health_path) == args.health_check then
+        return true
+    end
+    vim.fn.mkdir(runtime .. "/lua/neovide", "p")
+    -- Renamed into place, so that other instances starting at the same time never read a partial
+    -- file
+    local temporary_path = health_path .. "." .. vim.fn.getpid()
+    local file = io.open(temporary_path, "w")
+    if not file then
+        return false
+    end
+    file:write(args.health_check)
+    file:close()
+    local renamed = os.rename(temporary_path, health_path)
+    if not renamed then
+        -- Renaming over an existing file fails on Windows
+        os.remove(health_path)
+        renamed = os.rename(temporary_path, health_path)
+    end
+    if not renamed then
+        os.remove(temporary_path)
+    end
+    return renamed ~= nil
+end
+if write_health_check() then
+    vim.opt.runtimepath:append(runtime)
+end
@@ -0,0 +1,19 @@
+-- Shows the text piped to `neovide -` when Neovim has started, like `nvim -` does
+local lines = ...
+
+vim.api.nvim_create_autocmd({ "VimEnter" }, {
+    group = vim.api.nvim_create_augroup("neovide_stdin", { clear = true }),
+    once = true,
+    callback = function()
+        local buf = vim.api.nvim_get_current_buf()
+        -- Files given on the command line keep their buffers
+        if vim.api.nvim_buf_get_name(buf) ~= "" or vim.bo[buf].modified then
+            vim.cmd("tabnew")
+            buf = vim.api.nvim_get_current_buf()
+        end
+        vim.api.nvim_buf_set_lines(buf, 0, -1, false, lines)
+        vim.api.nvim_buf_call(buf, function()
+            vim.cmd("filetype detect")
+        end)
+    end,
+})
@@ -3,6 +3,9 @@
 //! This macro will generate a `SettingGroup` implementation for the struct it is applied to.
 //! It will also generate an enum with the name `{StructName}Changed` that contains a variant for
 //! each field in the struct. The enum will be used to send events when a setting is changed.
+//!
+//! Numeric fields can be limited with `#[range = "0.0..=1.0"]`, which is checked before the
+//! setting is changed through the settings API or the config file.
 
 use convert_case::{Case, Casing};
 use proc_macro::TokenStream;
@@ -11,7 +14,7 @@ use syn::{
     parse_macro_input, Attribute, Data, DataStruct, DeriveInput, Error, Field, Ident, Lit, Meta,
 };
 
-#[proc_macro_derive(SettingGroup, attributes(setting_prefix, option))]
+#[proc_macro_derive(SettingGroup, attributes(setting_prefix, option, range))]
 pub fn setting_group(item: TokenStream) -> TokenStream {
     let input = parse_macro_input!(item as DeriveInput);
     let prefix = setting_prefix(input.attrs.as_ref())
@@ -48,6 +51,23 @@ fn struct_stream(name: Ident, prefix: String, data: &DataStruct) -> TokenStream
                 }
             };
 
+            let range = match range(field) {
+                Ok(range) => range,
+                Err(error) => {
+                    return error.to_compile_error();
+                }
+            };
+            let range_check = range.map(|range| {
+                quote! {
+                    let range = #range;
+                    if let Some(number) = crate::settings::number_value(value) {
+                        if !range.contains(&number) {
+                            return Err(format!("expected a value in {range:?}, got {value}"));
+                        }
+                    }
+                }
+            });
+
             let location = match &option_name {
                 Some(option_name) => quote! {{ crate::settings::SettingLocation::NeovimOption(#option_name.to_owned()) }},
                 None => quote! {{ crate::settings::SettingLocation::NeovideGlobal(#vim_setting_name.to_owned()) }},
@@ -73,6 +93,25 @@ fn struct_stream(name: Ident, prefix: String, data: &DataStruct) -> TokenStream
                 }
             };
 
+            // Only global neovide variables can be changed through the settings API
+            let validator = if option_name.is_none() {
+                quote! {
+                    fn validate(settings: &crate::settings::Settings, value: &rmpv::Value) -> Result<(), String> {
+                        let mut parsed = settings.get::<#name>().#ident;
+                        parsed.parse_from_value(value.clone());
+                        crate::settings::check_parsed_value(value, &parsed.into())?;
+                        #range_check
+                        Ok(())
+                    }
+                }
+            } else {
+                quote! {
+                    fn validate(_settings: &crate::settings::Settings, _value: &rmpv::Value) -> Result<(), String> {
+                        Ok(())
+                    }
+                }
+            };
+
             quote! {{
                 fn update(settings: &crate::settings::Settings, value: rmpv::Value) -> crate::settings::SettingsChanged {
                     let mut s = settings.get::<#name>();
@@ -83,10 +122,13 @@ fn struct_stream(name: Ident, prefix: String, data: &DataStruct) -> TokenStream
 
                 #reader
 
+                #validator
+
                 settings.set_setting_handlers(
                     #location,
                     update,
                     reader,
+                    validate,
                 );
             }}
         }
@@ -141,6 +183,26 @@ fn setting_prefix(attrs: &[Attribute]) -> Option<String> {
     None
 }
 
+fn range(field: &Field) -> Result<Option<proc_macro2::TokenStream>, Error> {
+    for attr in field.attrs.iter() {
+        if !attr.path.is_ident("range") {
+            continue;
+        }
+
+        if let Ok(Meta::NameValue(name_value)) = attr.parse_meta() {
+            if let Lit::Str(literal) = name_value.lit {
+                return literal.parse().map(Some);
+            }
+        }
+        return Err(Error::new_spanned(
+            attr,
+            "Expected a string literal with a range of floats for range attribute",
+        ));
+    }
+
+    Ok(None)
+}
+
 fn option(field: &Field) -> Result<Option<String>, Error> {
     for attr in field.attrs.iter() {
         if !attr.path.is_ident("option") {
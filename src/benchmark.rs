@@ -0,0 +1,159 @@
+//! Headless benchmark mode, started with `--benchmark <file>`.
+//!
+//! The file is a redraw recording made with `--record-session`. The notifications are fed through
+//! the editor as fast as possible, and every resulting draw command batch is rendered to an
+//! offscreen raster surface, so no Neovim instance or window is needed.
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Context, Result};
+use skia_safe::{surfaces, Rect, Surface};
+use winit::{
+    event::Event,
+    event_loop::{ControlFlow, EventLoop},
+};
+
+use crate::{
+    bridge::read_redraw_recording, editor::Editor, renderer::Renderer, settings::FontSettings,
+    window::UserEvent,
+};
+
+/// The time step used for the animations, corresponding to a 60 Hz display.
+const FRAME_DT: f32 = 1.0 / 60.0;
+
+#[derive(Default)]
+struct Measurements {
+    event_count: usize,
+    editor_time: Duration,
+    frame_times: Vec<Duration>,
+}
+
+impl Measurements {
+    fn report(&mut self, path: &str, total_time: Duration) {
+        self.frame_times.sort();
+        let frame_count = self.frame_times.len();
+        let percentile = |p: usize| {
+            self.frame_times
+                .get((frame_count * p / 100).min(frame_count.saturating_sub(1)))
+                .copied()
+                .unwrap_or_default()
+        };
+        let average = self.frame_times.iter().sum::<Duration>() / frame_count.max(1) as u32;
+        let per_second = |duration: Duration| self.event_count as f64 / duration.as_secs_f64();
+
+        println!("Benchmark: {path}");
+        println!("  redraw events:  {}", self.event_count);
+        println!(
+            "  editor:         {:.0} events/s ({:.2} ms)",
+            per_second(self.editor_time),
+            self.editor_time.as_secs_f64() * 1000.0
+        );
+        println!(
+            "  end to end:     {:.0} events/s ({:.2} ms)",
+            per_second(total_time),
+            total_time.as_secs_f64() * 1000.0
+        );
+        println!("  frames:         {frame_count}");
+        println!(
+            "  frame time:     avg {:.3} ms, median {:.3} ms, p95 {:.3} ms, max {:.3} ms",
+            average.as_secs_f64() * 1000.0,
+            percentile(50).as_secs_f64() * 1000.0,
+            percentile(95).as_secs_f64() * 1000.0,
+            percentile(100).as_secs_f64() * 1000.0,
+        );
+    }
+}
+
+fn create_surface(renderer: &Renderer) -> Result<Surface> {
+    let grid_size = renderer.get_grid_size();
+    let font_dimensions = renderer.grid_renderer.font_dimensions;
+    let size = grid_size * font_dimensions;
+    surfaces::raster_n32_premul((size.width.max(1) as i32, size.height.max(1) as i32))
+        .context("Could not create the offscreen surface")
+}
+
+/// Replays the recording at `path` and prints the measured performance to stdout.
+///
+/// The event loop is only used for delivering the draw commands from the editor thread, no
+/// window is created.
+pub fn run_benchmark(
+    path: &str,
+    font_settings: Option<FontSettings>,
+    event_loop: EventLoop<UserEvent>,
+) -> Result<()> {
+    let notifications = read_redraw_recording(path)?;
+    let event_count = notifications
+        .iter()
+        .map(|notification| notification.events.len())
+        .sum();
+
+    let proxy = event_loop.create_proxy();
+    let editor_thread = thread::spawn(move || {
+        let mut editor = Editor::new(proxy.clone());
+        let start = Instant::now();
+        for event in notifications
+            .into_iter()
+            .flat_map(|notification| notification.events)
+        {
+            editor.handle_redraw_event(event);
+        }
+        let editor_time = start.elapsed();
+        // The user events are delivered in order, so all draw commands have been rendered when
+        // this one is received
+        let _ = proxy.send_event(UserEvent::RedrawRequested);
+        editor_time
+    });
+
+    let mut renderer = Renderer::new(1.0, font_settings);
+    let mut surface = create_surface(&renderer)?;
+    let mut measurements = Measurements {
+        event_count,
+        ..Default::default()
+    };
+    let start = Instant::now();
+    let mut result = Ok(());
+
+    event_loop.run(|event, target| {
+        target.set_control_flow(ControlFlow::Wait);
+        match event {
+            Event::UserEvent(UserEvent::DrawCommandBatch(batch)) => {
+                let frame_start = Instant::now();
+                renderer.handle_draw_commands(batch);
+                let grid_size = renderer.get_grid_size();
+                let font_dimensions = renderer.grid_renderer.font_dimensions;
+                let size = grid_size * font_dimensions;
+                if (surface.width(), surface.height()) != (size.width as i32, size.height as i32) {
+                    match create_surface(&renderer) {
+                        Ok(new_surface) => surface = new_surface,
+                        Err(error) => {
+                            result = Err(error);
+                            target.exit();
+                            return;
+                        }
+                    }
+                }
+                // Measure whole frames, produced from all of the draw commands
+                renderer.receive_frame(true);
+                renderer.animate_frame(&grid_size, &Rect::default(), FRAME_DT);
+                renderer.draw_frame(surface.canvas(), FRAME_DT);
+                measurements.frame_times.push(frame_start.elapsed());
+            }
+            event @ Event::UserEvent(UserEvent::SettingsChanged(_)) => {
+                renderer.handle_event(&event);
+            }
+            Event::UserEvent(UserEvent::RedrawRequested) => target.exit(),
+            _ => {}
+        }
+    })?;
+    result?;
+
+    let total_time = start.elapsed();
+    measurements.editor_time = editor_thread
+        .join()
+        .map_err(|_| anyhow::anyhow!("The editor thread panicked"))?;
+    measurements.report(path, total_time);
+    Ok(())
+}
@@ -1,11 +1,21 @@
-use std::error::Error;
+use std::{
+    env,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{ErrorKind, Write},
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
 
 use rmpv::Value;
 
-use crate::clipboard;
+use crate::{bridge::rich_text::RichText, clipboard};
 
-pub fn get_clipboard_contents(format: Option<&str>) -> Result<Value, Box<dyn Error + Send + Sync>> {
-    let clipboard_raw = clipboard::get_contents()?.replace('\r', "");
+pub fn get_clipboard_contents(
+    format: Option<&str>,
+    register: &str,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let clipboard_raw = clipboard::get_contents(register)?.replace('\r', "");
     let is_line_paste = clipboard_raw.ends_with('\n');
 
     let lines = if let Some("dos") = format {
@@ -29,7 +39,10 @@ pub fn get_clipboard_contents(format: Option<&str>) -> Result<Value, Box<dyn Err
     Ok(Value::from(vec![lines, paste_mode]))
 }
 
-pub fn set_clipboard_contents(value: &Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+pub fn set_clipboard_contents(
+    value: &Value,
+    register: &str,
+) -> Result<Value, Box<dyn Error + Send + Sync>> {
     #[cfg(not(windows))]
     let endline = "\n";
     #[cfg(windows)]
@@ -46,7 +59,71 @@ pub fn set_clipboard_contents(value: &Value) -> Result<Value, Box<dyn Error + Se
         })
         .ok_or("can't build string from provided text")?;
 
-    clipboard::set_contents(lines)?;
+    clipboard::set_contents(lines, register)?;
 
     Ok(Value::Nil)
 }
+
+/// Saves the image on the clipboard to a temporary file, and returns the path, or nil when there's
+/// no image on the clipboard.
+pub fn paste_clipboard_image() -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let Some(image) = clipboard::get_image()? else {
+        return Ok(Value::Nil);
+    };
+
+    let directory = image_directory()?;
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    for attempt in 0..100 {
+        let path = directory.join(format!("neovide-paste-{timestamp}-{attempt}.png"));
+        // Never follow or reuse an existing file
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => {
+                file.write_all(&image)?;
+                return Ok(Value::from(path.to_string_lossy().into_owned()));
+            }
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Err(format!("no free file name in {}", directory.display()).into())
+}
+
+/// The directory for pasted images, which only the current user can access, so that other users
+/// can't read the images or plant links in their place.
+#[cfg(not(windows))]
+fn image_directory() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    let directory = env::temp_dir().join(format!("neovide-paste-{uid}"));
+    match fs::DirBuilder::new().mode(0o700).create(&directory) {
+        Err(error) if error.kind() != ErrorKind::AlreadyExists => return Err(error.into()),
+        _ => {}
+    }
+    let metadata = fs::symlink_metadata(&directory)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.permissions().mode() & 0o077 != 0 {
+        return Err(format!(
+            "{} is not a directory that only the current user can access",
+            directory.display()
+        )
+        .into());
+    }
+    Ok(directory)
+}
+
+/// The temporary directory is already private to the user on Windows.
+#[cfg(windows)]
+fn image_directory() -> Result<PathBuf, Box<dyn Error + Send + Sync>> {
+    Ok(env::temp_dir())
+}
+
+pub fn set_clipboard_rich_contents(value: &Value) -> Result<Value, Box<dyn Error + Send + Sync>> {
+    let rich_text = RichText::parse(value).ok_or("can't parse the highlighted text")?;
+    clipboard::set_rich_contents(
+        &rich_text.to_plain_text(),
+        &rich_text.to_html(),
+        &rich_text.to_rtf(),
+    )?;
+    Ok(Value::Nil)
+}
@@ -1,6 +1,8 @@
 #[cfg(windows)]
 use std::os::windows::process::CommandExt;
 
+#[cfg(not(target_os = "macos"))]
+use std::iter;
 use std::process::{Command as StdCommand, Stdio};
 
 use anyhow::{bail, Result};
@@ -8,11 +10,72 @@ use log::debug;
 use regex::Regex;
 use tokio::process::Command as TokioCommand;
 
+#[cfg(target_os = "macos")]
+use super::shell_env::shell_environment;
+#[cfg(not(target_os = "macos"))]
+use super::wsl::{windows_to_wsl_path, wsl_distro_args};
+
 use crate::{cmd_line::CmdLineSettings, settings::*};
 
 pub fn create_nvim_command() -> Result<TokioCommand> {
-    let mut cmd = build_nvim_cmd()?;
+    create_nvim_command_impl(false)
+}
+
+/// Creates the command with `-r` before the files to open, so that they are recovered from their
+/// swap files after Neovim crashed.
+pub fn create_recovery_nvim_command() -> Result<TokioCommand> {
+    create_nvim_command_impl(true)
+}
+
+fn create_nvim_command_impl(recover: bool) -> Result<TokioCommand> {
+    let cmd = build_nvim_cmd(recover)?;
+    Ok(configure_nvim_command(cmd))
+}
+
+/// Creates the command that runs `nvim --embed` in a new container of `image`, with the current
+/// directory mounted at the same path, so that the files are opened with the toolchain of the
+/// container. The `nvim` inside the container can be changed with `--neovim-bin`.
+pub fn create_container_nvim_command(engine: &str, image: &str) -> Result<TokioCommand> {
+    let directory = std::env::current_dir()?;
+    // Windows paths can't be used inside Linux containers
+    let mount_point = if cfg!(windows) {
+        "/workspace".to_string()
+    } else {
+        directory.to_string_lossy().into_owned()
+    };
+    let mut cmd = TokioCommand::new(engine);
+    cmd.args(["run", "--rm", "--interactive", "--init"])
+        .arg("--volume")
+        .arg(format!("{}:{mount_point}", directory.to_string_lossy()))
+        .args(["--workdir", &mount_point]);
+    if engine == "podman" {
+        // Rootless Podman maps the user to root otherwise
+        cmd.arg("--userns=keep-id");
+    } else {
+        #[cfg(unix)]
+        {
+            // Files are written as the owner of the directory, instead of root
+            use std::os::unix::fs::MetadataExt;
+            let metadata = directory.metadata()?;
+            cmd.arg("--user")
+                .arg(format!("{}:{}", metadata.uid(), metadata.gid()));
+            // The user usually has no entry in the passwd file of the image, so HOME would be `/`,
+            // where Neovim can't write its state and shada files
+            cmd.args(["--env", "HOME=/tmp"]);
+        }
+    }
+    cmd.arg(image);
+
+    let settings = SETTINGS.get::<CmdLineSettings>();
+    match settings.neovim_bin.as_deref().map(shlex::split) {
+        Some(Some(bin)) if !bin.is_empty() => cmd.args(bin),
+        _ => cmd.arg("nvim"),
+    };
+    cmd.arg("--embed").args(settings.neovim_args);
+    Ok(configure_nvim_command(cmd))
+}
 
+fn configure_nvim_command(mut cmd: TokioCommand) -> TokioCommand {
     debug!("Starting neovim with: {:?}", cmd);
 
     #[cfg(not(debug_assertions))]
@@ -24,25 +87,57 @@ pub fn create_nvim_command() -> Result<TokioCommand> {
     #[cfg(windows)]
     cmd.creation_flags(winapi::um::winbase::CREATE_NO_WINDOW);
 
-    Ok(cmd)
+    // The startup notification belongs to the Neovide window, and would otherwise be claimed by
+    // GUI programs started from Neovim
+    cmd.env_remove("DESKTOP_STARTUP_ID")
+        .env_remove("XDG_ACTIVATION_TOKEN");
+
+    cmd
 }
 
-fn build_nvim_cmd() -> Result<TokioCommand> {
+fn build_nvim_cmd(recover: bool) -> Result<TokioCommand> {
+    let (bin, mut args) = find_nvim_binary()?;
+    if recover {
+        args.push("-r".to_string());
+    }
+    Ok(build_nvim_cmd_with_args(bin, args))
+}
+
+/// Finds the nvim binary and the extra arguments given with `--neovim-bin`, and checks that it
+/// can be started.
+pub fn find_nvim_binary() -> Result<(String, Vec<String>)> {
     if let Some(cmdline) = SETTINGS.get::<CmdLineSettings>().neovim_bin {
         if let Some((bin, args)) = lex_nvim_cmdline(&cmdline)? {
-            return Ok(build_nvim_cmd_with_args(bin, args));
+            return Ok((bin, args));
         }
 
         bail!("ERROR: NEOVIM_BIN='{}' was not found.", cmdline);
     } else if let Some(path) = platform_which("nvim") {
         if neovim_ok(&path, &[])? {
-            return Ok(build_nvim_cmd_with_args(path, vec![]));
+            return Ok((path, vec![]));
         }
     }
 
     bail!("ERROR: nvim not found!")
 }
 
+/// Starts nvim headlessly with the user configuration, and returns what the lua `chunk` writes
+/// to stdout.
+pub fn run_nvim_lua(bin: &str, args: &[String], chunk: &str) -> Result<String> {
+    let lua_command = format!("lua {chunk}");
+    let mut args = args.iter().map(String::as_str).collect::<Vec<_>>();
+    args.extend(["--headless", "-c", &lua_command, "-c", "qa!"]);
+    let output = create_platform_shell_command(bin, &args).output()?;
+    if !output.status.success() {
+        bail!(
+            "nvim exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
 #[cfg(target_os = "macos")]
 fn build_login_cmd_args(command: &str, args: &[&str]) -> (String, Vec<String>) {
     use std::env;
@@ -85,6 +180,7 @@ fn create_platform_shell_command(command: &str, args: &[&str]) -> StdCommand {
     {
         if SETTINGS.get::<CmdLineSettings>().wsl {
             let mut result = StdCommand::new("wsl");
+            result.args(wsl_distro_args());
             result.args(["$SHELL", "-lc"]);
             result.arg(format!("{} {}", command, args.join(" ")));
 
@@ -103,6 +199,12 @@ fn create_platform_shell_command(command: &str, args: &[&str]) -> StdCommand {
 
     #[cfg(target_os = "macos")]
     {
+        if let Some(environment) = shell_environment() {
+            let mut result = StdCommand::new(command);
+            result.args(args).envs(environment);
+            return result;
+        }
+
         let (cmd, cmd_args) = build_login_cmd_args(command, args);
 
         let mut result = StdCommand::new(cmd);
@@ -234,6 +336,13 @@ fn platform_which(bin: &str) -> Option<String> {
 
 #[cfg(target_os = "macos")]
 fn nvim_cmd_impl(bin: String, args: Vec<String>) -> TokioCommand {
+    // The captured environment makes the login shell unnecessary
+    if let Some(environment) = shell_environment() {
+        let mut cmd = TokioCommand::new(bin);
+        cmd.args(args).envs(environment);
+        return cmd;
+    }
+
     let (cmd, cmd_args) = build_login_cmd_args(
         &bin,
         &args.iter().map(|s| s.as_str()).collect::<Vec<&str>>(),
@@ -246,11 +355,18 @@ fn nvim_cmd_impl(bin: String, args: Vec<String>) -> TokioCommand {
 }
 
 #[cfg(not(target_os = "macos"))]
-fn nvim_cmd_impl(bin: String, mut args: Vec<String>) -> TokioCommand {
+fn nvim_cmd_impl(bin: String, args: Vec<String>) -> TokioCommand {
     if cfg!(target_os = "windows") && SETTINGS.get::<CmdLineSettings>().wsl {
-        args.insert(0, bin);
+        // The files given on the command line are opened from inside WSL
+        let args = args
+            .into_iter()
+            .map(|arg| windows_to_wsl_path(&arg).unwrap_or(arg));
+        let args = iter::once(bin).chain(args).collect::<Vec<_>>();
+        let command =
+            shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "));
         let mut cmd = TokioCommand::new("wsl");
-        cmd.args(["$SHELL", "-lc", &args.join(" ")]);
+        cmd.args(wsl_distro_args());
+        cmd.args(["$SHELL", "-lc", &command]);
         cmd
     } else {
         let mut cmd = TokioCommand::new(bin);
@@ -23,6 +23,7 @@ pub enum ParseError {
     F64(Value),
     Bool(Value),
     WindowAnchor(Value),
+    WindowHandle(Value),
     Format(String),
 }
 type Result<T> = std::result::Result<T, ParseError>;
@@ -40,6 +41,9 @@ impl fmt::Display for ParseError {
             ParseError::WindowAnchor(value) => {
                 write!(f, "invalid window anchor format {value}")
             }
+            ParseError::WindowHandle(value) => {
+                write!(f, "invalid window handle format {value}")
+            }
             ParseError::Format(debug_text) => {
                 write!(f, "invalid event format {debug_text}")
             }
@@ -156,6 +160,9 @@ pub enum RedrawEvent {
     MouseOff,
     BusyStart,
     BusyStop,
+    Bell,
+    /// Rung instead of `Bell` when `visualbell` is set.
+    VisualBell,
     Flush,
     Resize {
         grid: u64,
@@ -197,6 +204,7 @@ pub enum RedrawEvent {
     },
     WindowPosition {
         grid: u64,
+        window: u64,
         start_row: u64,
         start_column: u64,
         width: u64,
@@ -204,6 +212,7 @@ pub enum RedrawEvent {
     },
     WindowFloatPosition {
         grid: u64,
+        window: u64,
         anchor: WindowAnchor,
         anchor_grid: u64,
         anchor_row: f64,
@@ -506,6 +515,7 @@ fn parse_style(style_map: Value, info_array: Value) -> Result<Style> {
                     style.strikethrough = strikethrough
                 }
                 ("blend", Value::Integer(blend)) => style.blend = blend.as_u64().unwrap() as u8,
+                ("url", Value::String(url)) => style.url = url.into_str(),
 
                 ("underline", Value::Boolean(true)) => {
                     style.underline = Some(UnderlineStyle::Underline)
@@ -694,11 +704,11 @@ fn parse_grid_scroll(grid_scroll_arguments: Vec<Value>) -> Result<RedrawEvent> {
 }
 
 fn parse_win_pos(win_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let [grid, _window, start_row, start_column, width, height] =
-        extract_values(win_pos_arguments)?;
+    let [grid, window, start_row, start_column, width, height] = extract_values(win_pos_arguments)?;
 
     Ok(RedrawEvent::WindowPosition {
         grid: parse_u64(grid)?,
+        window: parse_window_handle(window)?,
         start_row: parse_u64(start_row)?,
         start_column: parse_u64(start_column)?,
         width: parse_u64(width)?,
@@ -706,6 +716,17 @@ fn parse_win_pos(win_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
     })
 }
 
+/// Window handles are sent as a msgpack extension containing the handle as an integer.
+fn parse_window_handle(window_value: Value) -> Result<u64> {
+    match &window_value {
+        Value::Ext(_, data) => rmpv::decode::read_value(&mut data.as_slice())
+            .ok()
+            .and_then(|value| value.as_u64()),
+        _ => None,
+    }
+    .ok_or(ParseError::WindowHandle(window_value))
+}
+
 fn parse_window_anchor(value: Value) -> Result<WindowAnchor> {
     let value_str = parse_string(value)?;
     match value_str.as_str() {
@@ -718,11 +739,12 @@ fn parse_window_anchor(value: Value) -> Result<WindowAnchor> {
 }
 
 fn parse_win_float_pos(win_float_pos_arguments: Vec<Value>) -> Result<RedrawEvent> {
-    let ([grid, _window, anchor, anchor_grid, anchor_row, anchor_column, focusable], [sort_order]) =
+    let ([grid, window, anchor, anchor_grid, anchor_row, anchor_column, focusable], [sort_order]) =
         extract_values_with_optional(win_float_pos_arguments)?;
 
     Ok(RedrawEvent::WindowFloatPosition {
         grid: parse_u64(grid)?,
+        window: parse_window_handle(window)?,
         anchor: parse_window_anchor(anchor)?,
         anchor_grid: parse_u64(anchor_grid)?,
         anchor_row: parse_f64(anchor_row)?,
@@ -936,6 +958,8 @@ pub fn parse_redraw_event(event_value: Value) -> Result<Vec<RedrawEvent>> {
             "mouse_off" => Some(Ok(RedrawEvent::MouseOff)),
             "busy_start" => Some(Ok(RedrawEvent::BusyStart)),
             "busy_stop" => Some(Ok(RedrawEvent::BusyStop)),
+            "bell" => Some(Ok(RedrawEvent::Bell)),
+            "visual_bell" => Some(Ok(RedrawEvent::VisualBell)),
             "flush" => Some(Ok(RedrawEvent::Flush)),
             "grid_resize" => Some(parse_grid_resize(event_parameters)),
             "default_colors_set" => Some(parse_default_colors(event_parameters)),
@@ -1,19 +1,36 @@
 use async_trait::async_trait;
-use log::trace;
+use log::{debug, trace};
 use nvim_rs::{Handler, Neovim};
 use rmpv::Value;
+use std::path::Path;
 use std::sync::Arc;
 use std::sync::Mutex;
+use std::time::Duration;
 use tokio::sync::mpsc::UnboundedSender;
-use winit::event_loop::EventLoopProxy;
+use winit::{dpi::PhysicalPosition, event_loop::EventLoopProxy, window::UserAttentionType};
 
 use crate::{
-    bridge::clipboard::{get_clipboard_contents, set_clipboard_contents},
-    bridge::{events::parse_redraw_event, NeovimWriter, RedrawEvent},
+    bridge::clipboard::{
+        get_clipboard_contents, paste_clipboard_image, set_clipboard_contents,
+        set_clipboard_rich_contents,
+    },
+    bridge::{
+        events::parse_redraw_event, health::health_status, osc52::Osc52Request, NeovimWriter,
+        Osc52Access, RedrawEvent, RedrawRecorder,
+    },
     error_handling::ResultPanicExplanation,
+    error_msg,
+    renderer::{
+        parse_trailing_whitespace, register_cursor_vfx, PaletteItem, WindowHighlights,
+        WindowIndent, RENDERER_STATUS,
+    },
     running_tracker::*,
-    settings::SETTINGS,
-    window::{UserEvent, WindowCommand},
+    settings::{profile_names, set_profile, SETTINGS},
+    window::{
+        load_icon_overlay, load_window_icon, parse_menu_items, parse_mouse_shape, PaddingOverride,
+        Progress, ResizeAnchor, UserEvent, WindowCommand, WindowSettings, FRAME_TIMINGS,
+        WINDOW_GEOMETRY,
+    },
     LoggingSender,
 };
 
@@ -22,13 +39,19 @@ pub struct NeovimHandler {
     // The EventLoopProxy is not sync on all platforms, so wrap it in a mutex
     proxy: Arc<Mutex<EventLoopProxy<UserEvent>>>,
     sender: LoggingSender<RedrawEvent>,
+    recorder: Option<Arc<RedrawRecorder>>,
 }
 
 impl NeovimHandler {
-    pub fn new(sender: UnboundedSender<RedrawEvent>, proxy: EventLoopProxy<UserEvent>) -> Self {
+    pub fn new(
+        sender: UnboundedSender<RedrawEvent>,
+        proxy: EventLoopProxy<UserEvent>,
+        recorder: Option<RedrawRecorder>,
+    ) -> Self {
         Self {
             proxy: Arc::new(Mutex::new(proxy)),
             sender: LoggingSender::attach(sender, "neovim_handler"),
+            recorder: recorder.map(Arc::new),
         }
     }
 }
@@ -43,7 +66,7 @@ impl Handler for NeovimHandler {
         arguments: Vec<Value>,
         neovim: Neovim<Self::Writer>,
     ) -> Result<Value, Value> {
-        trace!("Neovim request: {:?}", &event_name);
+        debug!("Neovim request: {:?}", &event_name);
 
         match event_name.as_ref() {
             "neovide.get_clipboard" => {
@@ -57,11 +80,48 @@ impl Handler for NeovimHandler {
                         s.next().map(String::from)
                     });
 
-                get_clipboard_contents(endline_type.as_deref())
+                let register = arguments.first().and_then(Value::as_str).unwrap_or("+");
+                get_clipboard_contents(endline_type.as_deref(), register)
                     .map_err(|_| Value::from("cannot get clipboard contents"))
             }
-            "neovide.set_clipboard" => set_clipboard_contents(&arguments[0])
-                .map_err(|_| Value::from("cannot set clipboard contents")),
+            "neovide.set_clipboard" => {
+                let register = arguments.get(1).and_then(Value::as_str).unwrap_or("+");
+                set_clipboard_contents(&arguments[0], register)
+                    .map_err(|_| Value::from("cannot set clipboard contents"))
+            }
+            "neovide.copy_rich" => {
+                let contents = arguments
+                    .first()
+                    .ok_or_else(|| Value::from("neovide.copy_rich expects the text to copy"))?;
+                set_clipboard_rich_contents(contents)
+                    .map_err(|error| Value::from(format!("cannot copy rich text: {error}")))
+            }
+            "neovide.paste_image" => paste_clipboard_image()
+                .map_err(|error| Value::from(format!("cannot paste clipboard image: {error}"))),
+            "neovide.osc52" => {
+                let payload = arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .unwrap_or_default();
+                let confirmed = arguments.get(1).and_then(Value::as_bool).unwrap_or(false);
+                let settings = SETTINGS.get::<WindowSettings>();
+                let request =
+                    Osc52Request::parse(payload, settings.clipboard_osc52_max_size as usize)?;
+                match request.access(
+                    settings.clipboard_osc52_read,
+                    settings.clipboard_osc52_write,
+                ) {
+                    Osc52Access::Deny => Err(Value::from("OSC 52 clipboard access is disabled")),
+                    Osc52Access::Confirm if !confirmed => Ok(Value::from(vec![(
+                        Value::from("confirm"),
+                        Value::from(request.confirmation_message()),
+                    )])),
+                    _ => request
+                        .execute()
+                        .map(|reply| reply.map_or(Value::Nil, Value::from))
+                        .map_err(|error| Value::from(format!("cannot access clipboard: {error}"))),
+                }
+            }
             "neovide.quit" => {
                 let error_code = arguments[0]
                     .as_i64()
@@ -69,6 +129,65 @@ impl Handler for NeovimHandler {
                 RUNNING_TRACKER.quit_with_code(error_code as i32, "Quit from neovim");
                 Ok(Value::Nil)
             }
+            "neovide.get_window_geometry" => Ok((*WINDOW_GEOMETRY.read().unwrap()).into()),
+            "neovide.health" => Ok(health_status()),
+            "neovide.get_timings" => {
+                let count = arguments
+                    .first()
+                    .and_then(Value::as_u64)
+                    .unwrap_or(u64::MAX);
+                Ok(FRAME_TIMINGS.lock().unwrap().to_value(count as usize))
+            }
+            "neovide.get_font_cache_usage" => {
+                Ok(RENDERER_STATUS.read().unwrap().font_caches.into())
+            }
+            "neovide.get_all_settings" => Ok(Value::Map(
+                SETTINGS
+                    .snapshot()
+                    .into_iter()
+                    .filter_map(|(name, value)| {
+                        Some((Value::from(name.strip_prefix("g:neovide_")?), value))
+                    })
+                    .collect(),
+            )),
+            "neovide.set_setting" => {
+                let name = arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| Value::from("neovide.set_setting expects a setting name"))?;
+                let name = name.strip_prefix("neovide_").unwrap_or(name);
+                let value = arguments.get(1).cloned().unwrap_or(Value::Nil);
+                SETTINGS
+                    .validate_global_value(name, &value)
+                    .map_err(Value::from)?;
+                // Setting the variable notifies Neovide through the variable watcher, like any
+                // other change
+                neovim
+                    .set_var(&format!("neovide_{name}"), value)
+                    .await
+                    .map(|_| Value::Nil)
+                    .map_err(|error| Value::from(format!("cannot set {name}: {error}")))
+            }
+            "neovide.profiles" => Ok(Value::from(
+                profile_names()
+                    .into_iter()
+                    .map(Value::from)
+                    .collect::<Vec<_>>(),
+            )),
+            "neovide.set_profile" => {
+                let name = arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .filter(|name| !name.is_empty());
+                set_profile(name, &self.proxy.lock().unwrap())
+                    .map(|_| Value::Nil)
+                    .map_err(Value::from)
+            }
+            "neovide.register_cursor_vfx" => {
+                register_cursor_vfx(arguments.first().unwrap_or(&Value::Nil))
+                    .map(|_| Value::Nil)
+                    .map_err(|error| Value::from(format!("neovide.register_cursor_vfx: {error}")))
+            }
             _ => Ok(Value::from("rpcrequest not handled")),
         }
     }
@@ -79,10 +198,18 @@ impl Handler for NeovimHandler {
         arguments: Vec<Value>,
         _neovim: Neovim<Self::Writer>,
     ) {
-        trace!("Neovim notification: {:?}", &event_name);
+        // Redraws are too frequent to keep in the log viewer
+        if event_name == "redraw" {
+            trace!("Neovim notification: {:?}", &event_name);
+        } else {
+            debug!("Neovim notification: {:?}", &event_name);
+        }
 
         match event_name.as_ref() {
             "redraw" => {
+                if let Some(recorder) = &self.recorder {
+                    recorder.record(&arguments);
+                }
                 for events in arguments {
                     let parsed_events = parse_redraw_event(events)
                         .unwrap_or_explained_panic("Could not parse event from neovim");
@@ -115,6 +242,342 @@ impl Handler for NeovimHandler {
                     .unwrap()
                     .send_event(WindowCommand::UnregisterRightClick.into());
             }
+            "neovide.set_menu_items" => {
+                match parse_menu_items(arguments.first().unwrap_or(&Value::Nil)) {
+                    Ok(items) => {
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetMenuItems(items).into());
+                    }
+                    Err(error) => {
+                        error_msg!("neovide.set_menu_items: {error}");
+                    }
+                }
+            }
+            "neovide.set_context_menu" => {
+                match parse_menu_items(arguments.first().unwrap_or(&Value::Nil)) {
+                    Ok(items) => {
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetContextMenu(items).into());
+                    }
+                    Err(error) => {
+                        error_msg!("neovide.set_context_menu: {error}");
+                    }
+                }
+            }
+            "neovide.set_window_zoom" => {
+                let window = arguments.first().and_then(Value::as_u64);
+                let factor = arguments.get(1).and_then(Value::as_f64);
+                match (window, factor) {
+                    (Some(window), Some(factor)) if factor > 0.0 => {
+                        let _ =
+                            self.proxy.lock().unwrap().send_event(
+                                WindowCommand::SetWindowZoom(window, factor as f32).into(),
+                            );
+                    }
+                    _ => {
+                        error_msg!(
+                            "neovide.set_window_zoom: expected a window and a positive factor"
+                        );
+                    }
+                }
+            }
+            "neovide.window_scrolled_columns" => {
+                // Lists of window handle, change of leftcol and width of the number and sign columns
+                let scrolled = arguments.first().and_then(Value::as_array);
+                for window in scrolled.into_iter().flatten() {
+                    let window = window.as_array().map(Vec::as_slice);
+                    if let Some([window, cols, text_left]) = window {
+                        if let (Some(window), Some(cols), Some(text_left)) =
+                            (window.as_u64(), cols.as_i64(), text_left.as_u64())
+                        {
+                            let _ = self.proxy.lock().unwrap().send_event(
+                                WindowCommand::ScrollWindowColumns {
+                                    window,
+                                    cols,
+                                    text_left,
+                                }
+                                .into(),
+                            );
+                        }
+                    }
+                }
+            }
+            "neovide.window_skipcols" => {
+                // Lists of window handle and the columns of the top line skipped by smoothscroll
+                let skipcols = arguments.first().and_then(Value::as_array);
+                for window in skipcols.into_iter().flatten() {
+                    let window = window.as_array().map(Vec::as_slice);
+                    if let Some([window, skipcol]) = window {
+                        if let (Some(window), Some(skipcol)) = (window.as_u64(), skipcol.as_u64()) {
+                            let _ = self.proxy.lock().unwrap().send_event(
+                                WindowCommand::SetWindowSkipcol { window, skipcol }.into(),
+                            );
+                        }
+                    }
+                }
+            }
+            "neovide.set_padding" => {
+                match PaddingOverride::parse(arguments.first().unwrap_or(&Value::Nil)) {
+                    Ok(padding) => {
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetPadding(padding).into());
+                    }
+                    Err(error) => {
+                        error_msg!("neovide.set_padding: {error}");
+                    }
+                }
+            }
+            "neovide.zen_mode" => {
+                if let Some(enabled) = arguments.first().and_then(Value::as_bool) {
+                    let _ = self
+                        .proxy
+                        .lock()
+                        .unwrap()
+                        .send_event(WindowCommand::SetZenMode(enabled).into());
+                }
+            }
+            "neovide.set_progress" => {
+                match Progress::parse(arguments.first().unwrap_or(&Value::Nil)) {
+                    Ok(progress) => {
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetProgress(progress).into());
+                    }
+                    Err(error) => {
+                        error_msg!("neovide.set_progress: {error}");
+                    }
+                }
+            }
+            "neovide.set_badge" => {
+                // Numbers are accepted too, since badges usually show a count
+                let badge = match arguments.first() {
+                    Some(Value::Nil) | None => None,
+                    Some(value) => {
+                        Some(value.as_str().map_or_else(|| value.to_string(), Into::into))
+                    }
+                };
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetBadge(badge).into());
+            }
+            "neovide.set_icon_overlay" => {
+                let overlay = match arguments.first().and_then(Value::as_str) {
+                    Some(path) => match load_icon_overlay(Path::new(path)) {
+                        Ok(overlay) => Some(overlay),
+                        Err(error) => {
+                            error_msg!("neovide.set_icon_overlay: {error:?}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetIconOverlay(overlay).into());
+            }
+            "neovide.set_window_icon" => {
+                let icon = match arguments.first().and_then(Value::as_str) {
+                    Some(source) => match load_window_icon(source) {
+                        Ok(icon) => Some(icon),
+                        Err(error) => {
+                            error_msg!("neovide.set_window_icon: {error:?}");
+                            return;
+                        }
+                    },
+                    None => None,
+                };
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetWindowIcon(icon).into());
+            }
+            "neovide.window_ligatures" => {
+                // Sent as a list of [window, ligatures] pairs, since a Lua table keyed by window
+                // handles could be serialized as an array
+                let ligatures = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|pairs| {
+                        pairs
+                            .iter()
+                            .filter_map(|pair| match pair.as_array()?.as_slice() {
+                                [window, enabled] => Some((window.as_u64()?, enabled.as_bool()?)),
+                                _ => None,
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetWindowLigatures(ligatures).into());
+            }
+            "neovide.window_highlights" => {
+                let highlights = WindowHighlights::parse(
+                    arguments.first().unwrap_or(&Value::Nil),
+                    arguments.get(1).unwrap_or(&Value::Nil),
+                );
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetWindowHighlights(highlights).into());
+            }
+            "neovide.colorscheme_changed" => {
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::ColorschemeChanged.into());
+            }
+            "neovide.open_in_new_windows" => {
+                let files = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|files| {
+                        files
+                            .iter()
+                            .filter_map(|file| file.as_str().map(str::to_string))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::OpenFilesInNewWindows(files).into());
+            }
+            "neovide.logs" => {
+                let mut arguments = arguments.iter().map(|value| value.as_str().unwrap_or(""));
+                let level = arguments.next().unwrap_or("").to_string();
+                let module = arguments.next().unwrap_or("").to_string();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::ShowLogs { level, module }.into());
+            }
+            "neovide.command_palette" => {
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::OpenCommandPalette.into());
+            }
+            "neovide.confirm_quit" => {
+                let buffers = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|buffers| {
+                        buffers
+                            .iter()
+                            .filter_map(|name| name.as_str().map(String::from))
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::ConfirmQuit(buffers).into());
+            }
+            "neovide.command_palette_items" => {
+                let items = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|items| items.iter().filter_map(PaletteItem::parse).collect())
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetCommandPaletteItems(items).into());
+            }
+            "neovide.project_launcher" => {
+                let items = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|items| items.iter().filter_map(PaletteItem::parse).collect())
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::OpenProjectLauncher(items).into());
+            }
+            "neovide.window_indents" => {
+                let indents = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|windows| windows.iter().filter_map(WindowIndent::parse).collect())
+                    .unwrap_or_default();
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::SetWindowIndents(indents).into());
+            }
+            "neovide.window_trailing_whitespace" => {
+                let trailing_whitespace = arguments
+                    .first()
+                    .and_then(Value::as_array)
+                    .map(|windows| {
+                        windows
+                            .iter()
+                            .filter_map(parse_trailing_whitespace)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+                let _ = self.proxy.lock().unwrap().send_event(
+                    WindowCommand::SetWindowTrailingWhitespace(trailing_whitespace).into(),
+                );
+            }
+            "neovide.screenshot" => {
+                if let Some(path) = arguments.first().and_then(Value::as_str) {
+                    let _ = self
+                        .proxy
+                        .lock()
+                        .unwrap()
+                        .send_event(WindowCommand::Screenshot(path.into()).into());
+                }
+            }
+            "neovide.record_frames" => {
+                let directory = arguments.first().and_then(Value::as_str);
+                let seconds = arguments.get(1).and_then(Value::as_f64);
+                match (directory, seconds) {
+                    (Some(directory), Some(seconds)) if seconds > 0.0 => {
+                        let _ = self.proxy.lock().unwrap().send_event(
+                            WindowCommand::RecordFrames {
+                                directory: directory.into(),
+                                duration: Duration::from_secs_f64(seconds),
+                            }
+                            .into(),
+                        );
+                    }
+                    _ => {
+                        error_msg!(
+                            "neovide.record_frames expects a directory and a duration in seconds"
+                        );
+                    }
+                }
+            }
             "neovide.focus_window" => {
                 let _ = self
                     .proxy
@@ -122,6 +585,77 @@ impl Handler for NeovimHandler {
                     .unwrap()
                     .send_event(WindowCommand::FocusWindow.into());
             }
+            "neovide.request_attention" => {
+                let attention_type = match arguments.first().and_then(Value::as_str) {
+                    Some("critical") => UserAttentionType::Critical,
+                    _ => UserAttentionType::Informational,
+                };
+                let _ = self
+                    .proxy
+                    .lock()
+                    .unwrap()
+                    .send_event(WindowCommand::RequestAttention(attention_type).into());
+            }
+            "neovide.set_window_position" => {
+                let x = arguments.first().and_then(Value::as_i64);
+                let y = arguments.get(1).and_then(Value::as_i64);
+                if let (Some(x), Some(y)) = (x, y) {
+                    if let (Ok(x), Ok(y)) = (i32::try_from(x), i32::try_from(y)) {
+                        let position = PhysicalPosition::new(x, y);
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetPosition(position).into());
+                    } else {
+                        error_msg!("neovide.set_window_position position {x}, {y} is out of range");
+                    }
+                } else {
+                    error_msg!("neovide.set_window_position expects integer x and y arguments");
+                }
+            }
+            "neovide.set_mouse_shape" => {
+                let name = arguments
+                    .first()
+                    .and_then(Value::as_str)
+                    .unwrap_or("default");
+                match parse_mouse_shape(name) {
+                    Some(shape) => {
+                        let _ = self
+                            .proxy
+                            .lock()
+                            .unwrap()
+                            .send_event(WindowCommand::SetMouseShape(shape).into());
+                    }
+                    None => {
+                        error_msg!("Unknown mouse shape {name:?}");
+                    }
+                }
+            }
+            "neovide.resize_window" => {
+                let delta_cols = arguments.first().and_then(Value::as_i64).unwrap_or(0);
+                let delta_lines = arguments.get(1).and_then(Value::as_i64).unwrap_or(0);
+                let anchor = match arguments.get(2).and_then(Value::as_str) {
+                    Some(anchor) => match ResizeAnchor::parse(anchor) {
+                        Some(anchor) => anchor,
+                        None => {
+                            error_msg!(
+                                "Invalid resize anchor {anchor:?}, expected NW, NE, SW or SE"
+                            );
+                            return;
+                        }
+                    },
+                    None => ResizeAnchor::default(),
+                };
+                let _ = self.proxy.lock().unwrap().send_event(
+                    WindowCommand::ResizeWindow {
+                        delta_cols,
+                        delta_lines,
+                        anchor,
+                    }
+                    .into(),
+                );
+            }
             _ => {}
         }
     }
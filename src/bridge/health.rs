@@ -0,0 +1,51 @@
+//! The status reported by `:checkhealth neovide`, which is implemented in `lua/health.lua`.
+
+use std::env;
+
+use rmpv::Value;
+
+use crate::{
+    cmd_line::CmdLineSettings, crash_report::gpu_backend, renderer::RENDERER_STATUS,
+    settings::SETTINGS,
+};
+
+/// Answers the `neovide.health` request. Neovim measures the roundtrip latency around it.
+pub fn health_status() -> Value {
+    let cmd_line = SETTINGS.get::<CmdLineSettings>();
+    let status = RENDERER_STATUS.read().unwrap().clone();
+    let fonts = status
+        .fonts
+        .into_iter()
+        .map(|(name, loaded)| {
+            Value::from(vec![
+                (Value::from("name"), Value::from(name)),
+                (Value::from("loaded"), Value::from(loaded)),
+            ])
+        })
+        .collect::<Vec<_>>();
+    Value::from(vec![
+        (
+            Value::from("version"),
+            Value::from(env!("CARGO_PKG_VERSION")),
+        ),
+        (
+            Value::from("gpu_backend"),
+            gpu_backend().map_or(Value::Nil, Value::from),
+        ),
+        (Value::from("vsync"), Value::from(status.vsync)),
+        (Value::from("fonts"), Value::from(fonts)),
+        (
+            Value::from("multigrid"),
+            Value::from(!cmd_line.no_multi_grid),
+        ),
+        (
+            Value::from("wayland"),
+            Value::from(cfg!(target_os = "linux") && env::var("WAYLAND_DISPLAY").is_ok()),
+        ),
+        (Value::from("wsl"), Value::from(cmd_line.wsl)),
+        (
+            Value::from("server"),
+            Value::from(cmd_line.server.is_some()),
+        ),
+    ])
+}
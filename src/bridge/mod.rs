@@ -3,9 +3,17 @@ mod clipboard;
 mod command;
 mod events;
 mod handler;
+mod health;
+mod osc52;
+mod redraw_recording;
+mod rich_text;
 pub mod session;
 mod setup;
+mod shell_env;
+mod single_instance;
 mod ui_commands;
+mod watchdog;
+mod wsl;
 
 use anyhow::{bail, Context, Result};
 use itertools::Itertools;
@@ -13,39 +21,83 @@ use log::{error, info};
 use nvim_rs::{error::CallError, Neovim, UiAttachOptions, Value};
 use rmpv::Utf8String;
 use std::{io::Error, ops::Add};
-use tokio::runtime::{Builder, Runtime};
+use tokio::{
+    runtime::{Builder, Runtime},
+    sync::Notify,
+};
 use winit::event_loop::EventLoopProxy;
 
 use crate::{
-    cmd_line::CmdLineSettings, dimensions::Dimensions, editor::start_editor, running_tracker::*,
-    settings::*, window::UserEvent,
+    cmd_line::CmdLineSettings,
+    dimensions::Dimensions,
+    editor::start_editor,
+    renderer::apply_preferred_high_contrast,
+    running_tracker::*,
+    settings::*,
+    window::{UserEvent, WindowCommand, WindowSettings},
 };
 pub use handler::NeovimHandler;
+pub use osc52::Osc52Access;
+pub use redraw_recording::{read_redraw_recording, start_replay, RedrawRecorder};
+pub use session::kill_embedded_neovim;
+use session::{is_crash, neovim_stderr_tail, wait_embedded_neovim};
 use session::{NeovimInstance, NeovimSession};
 use setup::{get_api_information, setup_neovide_specific_state};
+pub use single_instance::{
+    daemon_is_waiting, forward_to_running_instance, hand_to_daemon, send_command,
+};
+use single_instance::{listen_for_instances, wait_to_be_claimed};
+use watchdog::watch_responsiveness;
+use wsl::set_neovim_in_wsl;
 
 pub use api_info::*;
-pub use command::create_nvim_command;
+use command::create_recovery_nvim_command;
+pub use command::{create_nvim_command, find_nvim_binary, run_nvim_lua};
 pub use events::*;
 pub use session::NeovimWriter;
-pub use ui_commands::{send_ui, start_ui_command_handler, ParallelCommand, SerialCommand};
+use ui_commands::discard_pending_ui_commands;
+pub use ui_commands::{
+    send_ui, start_ui_command_handler, FileOpenTarget, ParallelCommand, SerialCommand,
+};
 
 const INTRO_MESSAGE_LUA: &str = include_str!("../../lua/intro.lua");
-const NEOVIM_REQUIRED_VERSION: &str = "0.9.2";
+const STDIN_LUA: &str = include_str!("../../lua/stdin.lua");
+pub const NEOVIM_REQUIRED_VERSION: &str = "0.9.2";
 
 pub struct NeovimRuntime {
     runtime: Option<Runtime>,
 }
 
-fn neovim_instance() -> Result<NeovimInstance> {
-    if let Some(address) = SETTINGS.get::<CmdLineSettings>().server {
-        Ok(NeovimInstance::Server { address })
+lazy_static! {
+    static ref RESTART_REQUESTED: Notify = Notify::new();
+}
+
+fn neovim_instance(recover: bool) -> Result<NeovimInstance> {
+    let settings = SETTINGS.get::<CmdLineSettings>();
+    if let Some(address) = &settings.server {
+        Ok(NeovimInstance::Server {
+            address: address.clone(),
+        })
+    } else if let Some((engine, image)) = settings.container() {
+        // The swap files are gone with the container, so there's nothing to recover
+        Ok(NeovimInstance::Container {
+            engine,
+            image: image.to_string(),
+        })
+    } else if recover && settings.neovim_args.iter().any(|arg| is_valid_path(arg)) {
+        // Without any files, `-r` only lists the swap files and exits
+        Ok(NeovimInstance::Embedded(create_recovery_nvim_command()?))
     } else {
         let cmd = create_nvim_command()?;
         Ok(NeovimInstance::Embedded(cmd))
     }
 }
 
+/// Starts Neovim again in the same window, after it crashed.
+pub fn restart_neovim() {
+    RESTART_REQUESTED.notify_one();
+}
+
 /// Takes the --cmd or -c argument and returns the command to be executed.
 fn handle_command_arg(position: usize, args: Vec<String>) -> String {
     args.get(position + 1).cloned().unwrap_or_default()
@@ -145,6 +197,29 @@ pub async fn setup_intro_message_autocommand(
     nvim.exec_lua(INTRO_MESSAGE_LUA, args).await
 }
 
+/// Splits the text read from stdin into lines like `str::lines`, but keeps the bytes as they are,
+/// since the text doesn't have to be UTF-8. They are sent as binary, which Lua receives as strings
+/// with the same bytes.
+fn stdin_lines(text: &[u8]) -> Vec<Value> {
+    let text = text.strip_suffix(b"\n").unwrap_or(text);
+    if text.is_empty() {
+        return Vec::new();
+    }
+    text.split(|&byte| byte == b'\n')
+        .map(|line| Value::Binary(line.strip_suffix(b"\r").unwrap_or(line).to_vec()))
+        .collect()
+}
+
+/// Shows the text read from stdin in a buffer, when Neovim has started.
+async fn setup_stdin_buffer(
+    nvim: &Neovim<NeovimWriter>,
+    text: &[u8],
+) -> Result<(), Box<CallError>> {
+    nvim.exec_lua(STDIN_LUA, vec![Value::Array(stdin_lines(text))])
+        .await
+        .map(|_| ())
+}
+
 pub async fn show_error_message(
     nvim: &Neovim<NeovimWriter>,
     lines: &[String],
@@ -169,10 +244,15 @@ pub async fn show_error_message(
     nvim.echo(prepared_lines, true, vec![]).await
 }
 
-async fn launch(handler: NeovimHandler, grid_size: Option<Dimensions>) -> Result<NeovimSession> {
-    let neovim_instance = neovim_instance()?;
+async fn launch(
+    handler: NeovimHandler,
+    grid_size: Option<Dimensions>,
+    stdin: Option<Vec<u8>>,
+    recover: bool,
+) -> Result<NeovimSession> {
+    let neovim_instance = neovim_instance(recover)?;
 
-    let session = NeovimSession::new(neovim_instance, handler)
+    let mut session = NeovimSession::new(neovim_instance, handler)
         .await
         .context("Could not locate or start neovim process")?;
 
@@ -191,18 +271,41 @@ async fn launch(handler: NeovimHandler, grid_size: Option<Dimensions>) -> Result
 
     let settings = SETTINGS.get::<CmdLineSettings>();
 
-    let should_handle_clipboard = settings.wsl || settings.server.is_some();
+    let should_handle_clipboard =
+        settings.wsl || settings.server.is_some() || settings.container().is_some();
     let api_information = get_api_information(&session.neovim).await?;
+    // A server can also run in WSL
+    let in_wsl = settings.wsl
+        || (cfg!(target_os = "windows")
+            && session
+                .neovim
+                .call_function("has", vec![Value::from("wsl")])
+                .await
+                .ok()
+                .and_then(|value| value.as_i64())
+                == Some(1));
+    set_neovim_in_wsl(in_wsl);
     info!(
         "Neovide registered to nvim with channel id {}",
         api_information.channel
     );
     // This is too verbose to keep enabled all the time
     // log::info!("Api information {:#?}", api_information);
+    // Set up before the intro message, which is not shown when the buffer has text
+    if let Some(text) = stdin {
+        setup_stdin_buffer(&session.neovim, &text)
+            .await
+            .context("Error setting up the stdin buffer")?;
+    }
     setup_neovide_specific_state(&session.neovim, should_handle_clipboard, &api_information)
         .await?;
 
-    start_ui_command_handler(session.neovim.clone(), &api_information);
+    session.stop_ui_commands = Some(start_ui_command_handler(
+        session.neovim.clone(),
+        &api_information,
+    ));
+    // Reading the desktop settings can still be in progress on Linux
+    tokio::task::spawn_blocking(apply_preferred_high_contrast).await?;
     SETTINGS.read_initial_values(&session.neovim).await?;
 
     let mut options = UiAttachOptions::new();
@@ -226,16 +329,57 @@ async fn launch(handler: NeovimHandler, grid_size: Option<Dimensions>) -> Result
     res.map(|()| session)
 }
 
-async fn run(session: NeovimSession) {
-    match session.io_handle.await {
-        Err(join_error) => error!("Error joining IO loop: '{}'", join_error),
-        Ok(Err(error)) => {
-            if !error.is_channel_closed() {
-                error!("Error: '{}'", error);
+/// Runs until Neovim exits. With `g:neovide_recover_on_crash`, a crash of the embedded Neovim, but
+/// not an exit with an error code, is shown in the window instead, and Neovim is started again
+/// with `-r` when the window asks for it.
+async fn run(mut session: NeovimSession, proxy: EventLoopProxy<UserEvent>) {
+    loop {
+        tokio::spawn(watch_responsiveness(session.neovim.clone(), proxy.clone()));
+        match (&mut session.io_handle).await {
+            Err(join_error) => error!("Error joining IO loop: '{}'", join_error),
+            Ok(Err(error)) => {
+                if !error.is_channel_closed() {
+                    error!("Error: '{}'", error);
+                }
             }
+            Ok(Ok(())) => {}
+        };
+        if let Some(stop_ui_commands) = session.stop_ui_commands.take() {
+            let _ = stop_ui_commands.send(());
+        }
+
+        let Some(status) = wait_embedded_neovim().await else {
+            break;
+        };
+        if !is_crash(&status)
+            || !RUNNING_TRACKER.is_running()
+            || !SETTINGS.get::<WindowSettings>().recover_on_crash
+        {
+            break;
         }
-        Ok(Ok(())) => {}
-    };
+        error!("Neovim exited unexpectedly with {status}");
+        let _ = proxy.send_event(
+            WindowCommand::NeovimCrashed {
+                status: status.to_string(),
+                output: neovim_stderr_tail(),
+            }
+            .into(),
+        );
+
+        RESTART_REQUESTED.notified().await;
+        discard_pending_ui_commands();
+        let handler = start_editor(proxy.clone(), None);
+        match launch(handler, None, None, true).await {
+            Ok(new_session) => {
+                session = new_session;
+                let _ = proxy.send_event(WindowCommand::NeovimRestarted.into());
+            }
+            Err(error) => {
+                error!("Could not restart Neovim: {error:?}");
+                break;
+            }
+        }
+    }
     RUNNING_TRACKER.quit("neovim processed failed");
 }
 
@@ -252,13 +396,31 @@ impl NeovimRuntime {
         &mut self,
         event_loop_proxy: EventLoopProxy<UserEvent>,
         grid_size: Option<Dimensions>,
+        stdin: Option<Vec<u8>>,
     ) -> Result<()> {
-        let handler = start_editor(event_loop_proxy);
+        let recorder = SETTINGS
+            .get::<CmdLineSettings>()
+            .record_session
+            .map(|path| RedrawRecorder::create(&path))
+            .transpose()?;
+        let handler = start_editor(event_loop_proxy.clone(), recorder);
         let runtime = self.runtime.as_ref().unwrap();
-        let session = runtime.block_on(launch(handler, grid_size))?;
-        runtime.spawn(run(session));
+        let session = runtime.block_on(launch(handler, grid_size, stdin, false))?;
+        runtime.spawn(run(session, event_loop_proxy));
         Ok(())
     }
+
+    /// Opens the files of new `--single-instance` invocations in this instance.
+    pub fn listen_for_instances(&self, event_loop_proxy: EventLoopProxy<UserEvent>) {
+        let runtime = self.runtime.as_ref().unwrap();
+        runtime.spawn(listen_for_instances(event_loop_proxy));
+    }
+
+    /// Keeps this `--daemon` hidden until a new invocation claims it.
+    pub fn wait_to_be_claimed(&self, event_loop_proxy: EventLoopProxy<UserEvent>) {
+        let runtime = self.runtime.as_ref().unwrap();
+        runtime.spawn(wait_to_be_claimed(event_loop_proxy));
+    }
 }
 
 impl Drop for NeovimRuntime {
@@ -266,3 +428,21 @@ impl Drop for NeovimRuntime {
         self.runtime.take().unwrap().shutdown_background();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stdin_lines_keep_the_bytes() {
+        assert_eq!(
+            stdin_lines(b"caf\xe9\r\n\nend\n"),
+            vec![
+                Value::Binary(b"caf\xe9".to_vec()),
+                Value::Binary(Vec::new()),
+                Value::Binary(b"end".to_vec()),
+            ]
+        );
+        assert!(stdin_lines(b"").is_empty());
+    }
+}
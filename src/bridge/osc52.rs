@@ -0,0 +1,182 @@
+use std::error::Error;
+
+use log::error;
+use rmpv::Value;
+
+use crate::{
+    clipboard,
+    settings::ParseFromValue,
+    utils::{decode_base64, encode_base64},
+};
+
+/// Whether programs in `:terminal` buffers are allowed to access the clipboard through OSC 52.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Osc52Access {
+    #[default]
+    Allow,
+    Confirm,
+    Deny,
+}
+
+impl ParseFromValue for Osc52Access {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "allow" => Osc52Access::Allow,
+                "confirm" => Osc52Access::Confirm,
+                "deny" => Osc52Access::Deny,
+                value => {
+                    error!("Expected an OSC 52 access mode, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!("Expected an OSC 52 access string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<Osc52Access> for Value {
+    fn from(access: Osc52Access) -> Self {
+        match access {
+            Osc52Access::Allow => Value::from("allow"),
+            Osc52Access::Confirm => Value::from("confirm"),
+            Osc52Access::Deny => Value::from("deny"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Osc52Request {
+    Set {
+        registers: Vec<&'static str>,
+        text: String,
+    },
+    Query {
+        register: &'static str,
+    },
+}
+
+impl Osc52Request {
+    /// Parses the payload of an OSC 52 sequence, that is everything after `ESC ] 52 ;` without
+    /// the terminator. Decoded text larger than `max_size` bytes is rejected.
+    pub fn parse(payload: &str, max_size: usize) -> Result<Self, String> {
+        let (selection, data) = payload.split_once(';').ok_or("malformed OSC 52 sequence")?;
+
+        // The clipboard is the default when no selection is given, and cut buffers are treated
+        // like the primary selection
+        let mut registers = selection
+            .chars()
+            .map(|c| match c {
+                'c' => "+",
+                _ => "*",
+            })
+            .collect::<Vec<_>>();
+        registers.dedup();
+        if registers.is_empty() {
+            registers.push("+");
+        }
+
+        if data == "?" {
+            return Ok(Osc52Request::Query {
+                register: registers[0],
+            });
+        }
+
+        // Reject huge payloads before decoding, base64 encodes 3 bytes as 4 characters
+        let too_large = || format!("OSC 52 clipboard data exceeds the limit of {max_size} bytes");
+        if data.len() / 4 > max_size / 3 + 1 {
+            return Err(too_large());
+        }
+        let bytes = decode_base64(data).ok_or("invalid base64 in OSC 52 sequence")?;
+        if bytes.len() > max_size {
+            return Err(too_large());
+        }
+        let text = String::from_utf8(bytes).map_err(|_| "OSC 52 clipboard data is not UTF-8")?;
+        Ok(Osc52Request::Set { registers, text })
+    }
+
+    pub fn access(&self, read: Osc52Access, write: Osc52Access) -> Osc52Access {
+        match self {
+            Osc52Request::Set { .. } => write,
+            Osc52Request::Query { .. } => read,
+        }
+    }
+
+    pub fn confirmation_message(&self) -> String {
+        match self {
+            Osc52Request::Set { text, .. } => format!(
+                "A terminal program wants to copy {} bytes to the clipboard. Allow?",
+                text.len()
+            ),
+            Osc52Request::Query { .. } => {
+                "A terminal program wants to read the clipboard. Allow?".to_string()
+            }
+        }
+    }
+
+    /// Performs the request, returns the reply to send to the terminal for queries.
+    pub fn execute(&self) -> Result<Option<String>, Box<dyn Error + Send + Sync>> {
+        match self {
+            Osc52Request::Set { registers, text } => {
+                for register in registers {
+                    clipboard::set_contents(text.clone(), register)?;
+                }
+                Ok(None)
+            }
+            Osc52Request::Query { register } => {
+                let contents = clipboard::get_contents(register)?;
+                let selection = if *register == "+" { "c" } else { "p" };
+                Ok(Some(format!(
+                    "\x1b]52;{selection};{}\x1b\\",
+                    encode_base64(contents.as_bytes())
+                )))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_set() {
+        assert_eq!(
+            Osc52Request::parse("c;aGVsbG8=", 100),
+            Ok(Osc52Request::Set {
+                registers: vec!["+"],
+                text: "hello".to_string()
+            })
+        );
+        assert_eq!(
+            Osc52Request::parse(";aGVsbG8=", 100),
+            Ok(Osc52Request::Set {
+                registers: vec!["+"],
+                text: "hello".to_string()
+            })
+        );
+        assert_eq!(
+            Osc52Request::parse("ps;aGVsbG8=", 100),
+            Ok(Osc52Request::Set {
+                registers: vec!["*"],
+                text: "hello".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            Osc52Request::parse("p;?", 100),
+            Ok(Osc52Request::Query { register: "*" })
+        );
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(Osc52Request::parse("aGVsbG8=", 100).is_err());
+        assert!(Osc52Request::parse("c;aGVsbG8=", 3).is_err());
+        assert!(Osc52Request::parse("c;//79", 100).is_err());
+    }
+}
@@ -0,0 +1,135 @@
+//! Recording and replaying of the redraw notifications sent by Neovim, which makes it possible to
+//! reproduce rendering bugs without the user's Neovim configuration.
+//!
+//! A recording consists of consecutive msgpack values, one for each notification, of the form
+//! `[microseconds since the start, redraw arguments]`.
+
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Write},
+    sync::Mutex,
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use log::{error, info};
+use rmpv::{
+    decode::{read_value, Error as DecodeError},
+    encode::write_value,
+    Value,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    bridge::{parse_redraw_event, RedrawEvent},
+    editor::Editor,
+    window::UserEvent,
+};
+
+pub struct RedrawRecorder {
+    writer: Mutex<BufWriter<File>>,
+    start: Instant,
+}
+
+impl RedrawRecorder {
+    pub fn create(path: &str) -> Result<Self> {
+        let file = File::create(path).with_context(|| format!("Could not create {path}"))?;
+        Ok(Self {
+            writer: Mutex::new(BufWriter::new(file)),
+            start: Instant::now(),
+        })
+    }
+
+    /// Appends the arguments of a redraw notification to the recording. The file is flushed
+    /// immediately, so that the recording is usable even if Neovide crashes.
+    pub fn record(&self, arguments: &[Value]) {
+        let entry = Value::Array(vec![
+            Value::from(self.start.elapsed().as_micros() as u64),
+            Value::Array(arguments.to_vec()),
+        ]);
+        let mut writer = self.writer.lock().unwrap();
+        if let Err(error) = write_value(&mut *writer, &entry) {
+            error!("Could not record the redraw notification: {error}");
+        }
+        if let Err(error) = writer.flush() {
+            error!("Could not write the redraw recording: {error}");
+        }
+    }
+}
+
+pub struct RecordedNotification {
+    /// The time since the start of the recording.
+    pub time: Duration,
+    pub events: Vec<RedrawEvent>,
+}
+
+/// Reads and parses all notifications of a recording.
+pub fn read_redraw_recording(path: &str) -> Result<Vec<RecordedNotification>> {
+    let file = File::open(path).with_context(|| format!("Could not open {path}"))?;
+    let mut reader = BufReader::new(file);
+    let mut notifications = Vec::new();
+    loop {
+        let entry = match read_value(&mut reader) {
+            Ok(entry) => entry,
+            Err(DecodeError::InvalidMarkerRead(error))
+                if error.kind() == ErrorKind::UnexpectedEof =>
+            {
+                break;
+            }
+            Err(error) => return Err(error).with_context(|| format!("Could not read {path}")),
+        };
+        let Value::Array(entry) = entry else {
+            bail!("Expected a recorded notification in {path}, got {entry}");
+        };
+        let [Value::Integer(time), Value::Array(arguments)] = entry.as_slice() else {
+            bail!("Expected a timestamp and redraw arguments in {path}");
+        };
+        let mut events = Vec::new();
+        for event in arguments {
+            events.extend(parse_redraw_event(event.clone())?);
+        }
+        notifications.push(RecordedNotification {
+            time: Duration::from_micros(time.as_u64().unwrap_or_default()),
+            events,
+        });
+    }
+    Ok(notifications)
+}
+
+/// Plays back a recording with the original timing, instead of connecting to Neovim.
+pub fn start_replay(path: &str, event_loop_proxy: EventLoopProxy<UserEvent>) -> Result<()> {
+    let notifications = read_redraw_recording(path)?;
+    thread::spawn(move || {
+        let mut editor = Editor::new(event_loop_proxy);
+        let start = Instant::now();
+        for notification in notifications {
+            if let Some(delay) = notification.time.checked_sub(start.elapsed()) {
+                thread::sleep(delay);
+            }
+            for event in notification.events {
+                editor.handle_redraw_event(event);
+            }
+        }
+        info!("Replay finished");
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_redraw_recording() {
+        let notifications = read_redraw_recording("tests/data/scrolling.msgpack").unwrap();
+        assert!(!notifications.is_empty());
+        assert!(notifications
+            .windows(2)
+            .all(|pair| pair[0].time <= pair[1].time));
+        assert!(notifications
+            .iter()
+            .flat_map(|notification| &notification.events)
+            .any(|event| matches!(event, RedrawEvent::Flush)));
+    }
+}
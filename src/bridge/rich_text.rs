@@ -0,0 +1,296 @@
+use std::fmt::Write;
+
+use rmpv::Value;
+
+/// A run of text sharing the same highlight, as resolved by `NeovideCopyRich`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RichSpan {
+    pub text: String,
+    pub foreground: Option<u32>,
+    pub background: Option<u32>,
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RichText {
+    pub lines: Vec<Vec<RichSpan>>,
+    pub foreground: u32,
+    pub background: u32,
+}
+
+fn map_get<'a>(value: &'a Value, key: &str) -> Option<&'a Value> {
+    value
+        .as_map()?
+        .iter()
+        .find(|(k, _)| k.as_str() == Some(key))
+        .map(|(_, v)| v)
+}
+
+fn parse_color(value: Option<&Value>) -> Option<u32> {
+    value.and_then(Value::as_u64).map(|color| color as u32)
+}
+
+fn parse_flag(value: Option<&Value>) -> bool {
+    value.and_then(Value::as_bool).unwrap_or(false)
+}
+
+impl RichText {
+    pub fn parse(value: &Value) -> Option<Self> {
+        let lines = map_get(value, "lines")?
+            .as_array()?
+            .iter()
+            .map(|line| {
+                line.as_array()
+                    .map(|spans| {
+                        spans
+                            .iter()
+                            .map(|span| RichSpan {
+                                text: map_get(span, "text")
+                                    .and_then(Value::as_str)
+                                    .unwrap_or_default()
+                                    .to_string(),
+                                foreground: parse_color(map_get(span, "fg")),
+                                background: parse_color(map_get(span, "bg")),
+                                bold: parse_flag(map_get(span, "bold")),
+                                italic: parse_flag(map_get(span, "italic")),
+                                underline: parse_flag(map_get(span, "underline")),
+                            })
+                            .collect()
+                    })
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        Some(Self {
+            lines,
+            foreground: parse_color(map_get(value, "fg")).unwrap_or(0x000000),
+            background: parse_color(map_get(value, "bg")).unwrap_or(0xffffff),
+        })
+    }
+
+    pub fn to_plain_text(&self) -> String {
+        self.lines
+            .iter()
+            .map(|line| {
+                line.iter()
+                    .map(|span| span.text.as_str())
+                    .collect::<String>()
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut html = format!(
+            "<pre style=\"color: #{:06x}; background-color: #{:06x}; font-family: monospace;\">",
+            self.foreground, self.background
+        );
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                html.push('\n');
+            }
+            for span in line {
+                let mut style = String::new();
+                if let Some(foreground) = span.foreground {
+                    let _ = write!(style, "color: #{foreground:06x}; ");
+                }
+                if let Some(background) = span.background {
+                    let _ = write!(style, "background-color: #{background:06x}; ");
+                }
+                if span.bold {
+                    style.push_str("font-weight: bold; ");
+                }
+                if span.italic {
+                    style.push_str("font-style: italic; ");
+                }
+                if span.underline {
+                    style.push_str("text-decoration: underline; ");
+                }
+
+                let text = escape_html(&span.text);
+                if style.is_empty() {
+                    html.push_str(&text);
+                } else {
+                    let _ = write!(html, "<span style=\"{}\">{text}</span>", style.trim_end());
+                }
+            }
+        }
+        html.push_str("</pre>");
+        html
+    }
+
+    pub fn to_rtf(&self) -> String {
+        // The color table is 1-based, index 0 means the default color
+        let mut colors = vec![self.foreground, self.background];
+        let mut color_index = |color: u32| match colors.iter().position(|&c| c == color) {
+            Some(index) => index + 1,
+            None => {
+                colors.push(color);
+                colors.len()
+            }
+        };
+
+        let mut body = String::new();
+        for (i, line) in self.lines.iter().enumerate() {
+            if i > 0 {
+                body.push_str("\\par\n");
+            }
+            for span in line {
+                let foreground = color_index(span.foreground.unwrap_or(self.foreground));
+                let background = color_index(span.background.unwrap_or(self.background));
+                let _ = write!(body, "{{\\cf{foreground}\\highlight{background}");
+                if span.bold {
+                    body.push_str("\\b");
+                }
+                if span.italic {
+                    body.push_str("\\i");
+                }
+                if span.underline {
+                    body.push_str("\\ul");
+                }
+                body.push(' ');
+                body.push_str(&escape_rtf(&span.text));
+                body.push('}');
+            }
+        }
+
+        let mut rtf = String::from("{\\rtf1\\ansi\\deff0{\\fonttbl{\\f0\\fmodern Courier New;}}");
+        rtf.push_str("{\\colortbl;");
+        for color in colors {
+            let _ = write!(
+                rtf,
+                "\\red{}\\green{}\\blue{};",
+                (color >> 16) & 0xff,
+                (color >> 8) & 0xff,
+                color & 0xff
+            );
+        }
+        rtf.push_str("}\\f0\n");
+        rtf.push_str(&body);
+        rtf.push('}');
+        rtf
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+fn escape_rtf(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '{' | '}' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\t' => escaped.push_str("\\tab "),
+            c if c.is_ascii() => escaped.push(c),
+            // RTF only supports 16 bit signed unicode escapes, characters outside of the BMP are
+            // written as surrogate pairs. The ? is the fallback for readers without unicode support.
+            c => {
+                let mut buffer = [0u16; 2];
+                for unit in c.encode_utf16(&mut buffer) {
+                    let _ = write!(escaped, "\\u{}?", *unit as i16);
+                }
+            }
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> RichText {
+        RichText {
+            lines: vec![
+                vec![
+                    RichSpan {
+                        text: "if".to_string(),
+                        foreground: Some(0xff0000),
+                        bold: true,
+                        ..Default::default()
+                    },
+                    RichSpan {
+                        text: " a < b {".to_string(),
+                        ..Default::default()
+                    },
+                ],
+                vec![RichSpan {
+                    text: "é".to_string(),
+                    italic: true,
+                    ..Default::default()
+                }],
+            ],
+            foreground: 0x000000,
+            background: 0xffffff,
+        }
+    }
+
+    #[test]
+    fn test_parse() {
+        let value = Value::from(vec![
+            (Value::from("fg"), Value::from(0x112233)),
+            (Value::from("bg"), Value::from(0x445566)),
+            (
+                Value::from("lines"),
+                Value::from(vec![Value::from(vec![Value::from(vec![
+                    (Value::from("text"), Value::from("foo")),
+                    (Value::from("fg"), Value::from(0xff0000)),
+                    (Value::from("underline"), Value::from(true)),
+                ])])]),
+            ),
+        ]);
+        let rich_text = RichText::parse(&value).unwrap();
+        assert_eq!(rich_text.foreground, 0x112233);
+        assert_eq!(rich_text.background, 0x445566);
+        assert_eq!(
+            rich_text.lines,
+            vec![vec![RichSpan {
+                text: "foo".to_string(),
+                foreground: Some(0xff0000),
+                underline: true,
+                ..Default::default()
+            }]]
+        );
+    }
+
+    #[test]
+    fn test_plain_text() {
+        assert_eq!(sample().to_plain_text(), "if a < b {\né");
+    }
+
+    #[test]
+    fn test_html() {
+        assert_eq!(
+            sample().to_html(),
+            "<pre style=\"color: #000000; background-color: #ffffff; font-family: monospace;\">\
+             <span style=\"color: #ff0000; font-weight: bold;\">if</span> a &lt; b {\n\
+             <span style=\"font-style: italic;\">é</span></pre>"
+        );
+    }
+
+    #[test]
+    fn test_rtf() {
+        let rtf = sample().to_rtf();
+        assert!(rtf.starts_with("{\\rtf1\\ansi"));
+        assert!(rtf.contains("{\\colortbl;\\red0\\green0\\blue0;\\red255\\green255\\blue255;\\red255\\green0\\blue0;}"));
+        assert!(rtf.contains("{\\cf3\\highlight2\\b if}{\\cf1\\highlight2  a < b \\{}\\par\n"));
+        assert!(rtf.contains("{\\cf1\\highlight2\\i \\u233?}"));
+        assert!(rtf.ends_with('}'));
+    }
+}
@@ -4,28 +4,99 @@
 #[cfg(debug_assertions)]
 use core::fmt;
 use std::{
+    collections::VecDeque,
     io::{Error, ErrorKind, Result},
-    process::Stdio,
+    process::{ExitStatus, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Mutex,
+    },
 };
 
 use nvim_rs::{error::LoopError, neovim::Neovim, Handler};
 use tokio::{
-    io::{split, AsyncRead, AsyncWrite},
+    io::{split, AsyncBufReadExt, AsyncRead, AsyncWrite, BufReader},
     net::TcpStream,
-    process::Command,
+    process::{Child, Command},
     spawn,
+    sync::oneshot,
     task::JoinHandle,
 };
 use tokio_util::compat::{TokioAsyncReadCompatExt, TokioAsyncWriteCompatExt};
 
+use super::command::create_container_nvim_command;
+
 pub type NeovimWriter = Box<dyn futures::AsyncWrite + Send + Unpin + 'static>;
 
 type BoxedReader = Box<dyn AsyncRead + Send + Unpin + 'static>;
 type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin + 'static>;
 
+/// The embedded Neovim process, kept so that it can be killed when it stops responding.
+static EMBEDDED_PROCESS: Mutex<Option<Child>> = Mutex::new(None);
+/// Set when the embedded Neovim is killed because it stopped responding.
+static EMBEDDED_PROCESS_KILLED: AtomicBool = AtomicBool::new(false);
+
+/// The last lines Neovim wrote to stderr, shown when it crashes.
+static STDERR_TAIL: Mutex<VecDeque<String>> = Mutex::new(VecDeque::new());
+const STDERR_TAIL_LINES: usize = 20;
+
+/// Waits for the embedded Neovim process to exit, returns `None` when Neovide is connected to a
+/// server instead.
+pub async fn wait_embedded_neovim() -> Option<ExitStatus> {
+    let mut child = EMBEDDED_PROCESS.lock().unwrap().take()?;
+    child.wait().await.ok()
+}
+
+/// True when Neovim was killed by a signal, dumped core or was killed by Neovide, but not when it
+/// exited by itself with an error code, like with `:cquit`.
+pub fn is_crash(status: &ExitStatus) -> bool {
+    if EMBEDDED_PROCESS_KILLED.load(Ordering::Relaxed) {
+        return true;
+    }
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        status.signal().is_some() || status.core_dumped()
+    }
+    #[cfg(windows)]
+    {
+        // Unhandled exceptions exit with their NTSTATUS code, like 0xC0000005 for an access
+        // violation, which has the error severity bits set
+        status
+            .code()
+            .is_some_and(|code| (code as u32) & 0xC000_0000 == 0xC000_0000)
+    }
+    #[cfg(not(any(unix, windows)))]
+    {
+        !status.success()
+    }
+}
+
+/// The last lines Neovim wrote to stderr. It's only captured in release builds, since debug builds
+/// show it in the terminal.
+pub fn neovim_stderr_tail() -> Vec<String> {
+    STDERR_TAIL.lock().unwrap().iter().cloned().collect()
+}
+
+/// Kills the embedded Neovim process, returns false when Neovide is connected to a server instead.
+pub fn kill_embedded_neovim() -> bool {
+    match EMBEDDED_PROCESS.lock().unwrap().as_mut() {
+        Some(child) => {
+            EMBEDDED_PROCESS_KILLED.store(true, Ordering::Relaxed);
+            if let Err(error) = child.start_kill() {
+                log::error!("Could not kill the Neovim process: {error}");
+            }
+            true
+        }
+        None => false,
+    }
+}
+
 pub struct NeovimSession {
     pub neovim: Neovim<NeovimWriter>,
     pub io_handle: JoinHandle<std::result::Result<(), Box<LoopError>>>,
+    /// Stops sending the UI commands to this Neovim.
+    pub stop_ui_commands: Option<oneshot::Sender<()>>,
 }
 
 #[cfg(debug_assertions)]
@@ -47,7 +118,11 @@ impl NeovimSession {
             Neovim::<NeovimWriter>::new(reader.compat(), Box::new(writer.compat_write()), handler);
         let io_handle = spawn(io);
 
-        Ok(Self { neovim, io_handle })
+        Ok(Self {
+            neovim,
+            io_handle,
+            stop_ui_commands: None,
+        })
     }
 }
 
@@ -63,6 +138,10 @@ pub enum NeovimInstance {
     /// interpreted as a TCP/IPv4/IPv6 address. Otherwise it's interpreted as a named pipe or Unix
     /// domain socket path. Spawns and connects to an embedded Neovim instance.
     Server { address: String },
+
+    /// A new instance started in a container of `image`, by the container `engine`, `docker` or
+    /// `podman`. It's connected through stdio like an embedded instance.
+    Container { engine: &'static str, image: String },
 }
 
 impl NeovimInstance {
@@ -70,6 +149,11 @@ impl NeovimInstance {
         match self {
             NeovimInstance::Embedded(cmd) => Self::spawn_process(cmd).await,
             NeovimInstance::Server { address } => Self::connect_to_server(address).await,
+            NeovimInstance::Container { engine, image } => {
+                let cmd = create_container_nvim_command(engine, &image)
+                    .map_err(|error| Error::other(error.to_string()))?;
+                Self::spawn_process(cmd).await
+            }
         }
     }
 
@@ -87,6 +171,22 @@ impl NeovimInstance {
                 .take()
                 .ok_or_else(|| Error::new(ErrorKind::Other, "Can't open stdin"))?,
         );
+        STDERR_TAIL.lock().unwrap().clear();
+        if let Some(stderr) = child.stderr.take() {
+            spawn(async move {
+                let mut lines = BufReader::new(stderr).lines();
+                while let Ok(Some(line)) = lines.next_line().await {
+                    log::debug!("Neovim stderr: {line}");
+                    let mut tail = STDERR_TAIL.lock().unwrap();
+                    if tail.len() == STDERR_TAIL_LINES {
+                        tail.pop_front();
+                    }
+                    tail.push_back(line);
+                }
+            });
+        }
+        EMBEDDED_PROCESS_KILLED.store(false, Ordering::Relaxed);
+        *EMBEDDED_PROCESS.lock().unwrap() = Some(child);
 
         Ok((reader, writer))
     }
@@ -6,6 +6,7 @@ use super::api_info::{parse_api_info, ApiInformation};
 use super::setup_intro_message_autocommand;
 use crate::{
     bridge::NeovimWriter,
+    cmd_line::CmdLineSettings,
     settings::{SettingLocation, SETTINGS},
 };
 
@@ -13,6 +14,11 @@ use crate::bridge::setup_tty_startup_directory;
 use crate::utils::is_tty;
 
 const INIT_LUA: &str = include_str!("../../lua/init.lua");
+const MODULE_LUA: &str = include_str!("../../lua/neovide.lua");
+const HEALTH_LUA: &str = include_str!("../../lua/health.lua");
+
+/// The version of the `neovide` Lua module, increased when functions are added to it.
+const LUA_API_LEVEL: u64 = 8;
 
 pub async fn get_api_information(nvim: &Neovim<NeovimWriter>) -> Result<ApiInformation> {
     // Retrieve the channel number for communicating with neovide.
@@ -64,6 +70,17 @@ pub async fn setup_neovide_specific_state(
 
     let register_clipboard = should_handle_clipboard;
     let register_right_click = cfg!(target_os = "windows");
+    let register_primary_selection = register_clipboard && cfg!(target_os = "linux");
+    let cmdline = SETTINGS.get::<CmdLineSettings>();
+    // Only the tabs opened with `-p` are arranged
+    let arrange_startup_files = cmdline.tabs
+        && !cmdline.neovim_flags.diff
+        && cmdline
+            .files_to_open
+            .iter()
+            .filter(|file| *file != "-")
+            .count()
+            > 1;
 
     let settings = SETTINGS.setting_locations();
     let global_variable_settings = settings
@@ -94,6 +111,14 @@ pub async fn setup_neovide_specific_state(
             Value::from("register_right_click"),
             Value::from(register_right_click),
         ),
+        (
+            Value::from("register_primary_selection"),
+            Value::from(register_primary_selection),
+        ),
+        (
+            Value::from("arrange_startup_files"),
+            Value::from(arrange_startup_files),
+        ),
         (
             Value::from("global_variable_settings"),
             Value::from(global_variable_settings),
@@ -105,6 +130,22 @@ pub async fn setup_neovide_specific_state(
         .await
         .context("Error when running Neovide init.lua")?;
 
+    let module_args = Value::from(vec![
+        (
+            Value::from("version"),
+            Value::from(env!("CARGO_PKG_VERSION")),
+        ),
+        (Value::from("api_level"), Value::from(LUA_API_LEVEL)),
+        (
+            Value::from("register_right_click"),
+            Value::from(register_right_click),
+        ),
+        (Value::from("health_check"), Value::from(HEALTH_LUA)),
+    ]);
+    nvim.execute_lua(MODULE_LUA, vec![module_args])
+        .await
+        .context("Error when loading the neovide Lua module")?;
+
     setup_tty_startup_directory(nvim)
         .await
         .context("Error setting up TTY startup directory")?;
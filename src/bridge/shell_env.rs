@@ -0,0 +1,141 @@
+// Apps launched from Finder or the Dock on macOS get the minimal environment of launchd, without
+// the PATH set up by the shell startup files, so nvim wouldn't find the language servers and
+// other tools the user installed.
+use std::collections::HashMap;
+
+#[cfg(target_os = "macos")]
+use std::{
+    env,
+    io::Read,
+    process::{Command as StdCommand, Stdio},
+    sync::OnceLock,
+    thread,
+    time::{Duration, Instant},
+};
+
+#[cfg(target_os = "macos")]
+use crate::{cmd_line::CmdLineSettings, settings::SETTINGS};
+
+/// Surrounds the output of `env`, to tell it apart from what the startup files print.
+const MARKER: &str = "_NEOVIDE_SHELL_ENV_";
+/// Shells that take longer than this to start are given up on.
+#[cfg(target_os = "macos")]
+const TIMEOUT: Duration = Duration::from_secs(5);
+/// These belong to the shell that printed them, not to nvim.
+const IGNORED_VARIABLES: &[&str] = &["_", "SHLVL", "PWD", "OLDPWD"];
+
+/// The environment of an interactive login shell of the user, captured once and used for all the
+/// commands Neovide starts. None when disabled with `--no-shell-env` or when the shell failed.
+#[cfg(target_os = "macos")]
+pub fn shell_environment() -> Option<&'static HashMap<String, String>> {
+    static ENVIRONMENT: OnceLock<Option<HashMap<String, String>>> = OnceLock::new();
+    if SETTINGS.get::<CmdLineSettings>().no_shell_env {
+        return None;
+    }
+    ENVIRONMENT.get_or_init(capture_shell_environment).as_ref()
+}
+
+#[cfg(target_os = "macos")]
+fn capture_shell_environment() -> Option<HashMap<String, String>> {
+    let shell = env::var("SHELL").unwrap_or_else(|_| "/bin/zsh".to_string());
+    let script = format!("printf '{MARKER}'; /usr/bin/env; printf '{MARKER}'");
+    let spawned = StdCommand::new(&shell)
+        .args(["-l", "-i", "-c", &script])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn();
+    let mut child = match spawned {
+        Ok(child) => child,
+        Err(error) => {
+            log::warn!("Could not start {shell}: {error}");
+            return None;
+        }
+    };
+
+    // Read on another thread, so that a large environment can't fill the pipe and block the shell
+    let mut stdout = child.stdout.take()?;
+    let reader = thread::spawn(move || {
+        let mut output = Vec::new();
+        stdout.read_to_end(&mut output).map(|_| output)
+    });
+    let start = Instant::now();
+    while child.try_wait().ok()?.is_none() {
+        if start.elapsed() > TIMEOUT {
+            log::warn!("{shell} took too long to start, using the environment of Neovide");
+            let _ = child.kill();
+            return None;
+        }
+        thread::sleep(Duration::from_millis(10));
+    }
+    let output = reader.join().ok()?.ok()?;
+    let environment = parse_environment(&String::from_utf8_lossy(&output));
+    if environment.is_none() {
+        log::warn!("Could not read the environment of {shell}");
+    }
+    environment
+}
+
+/// Parses the output of `env` between the markers. Lines that don't start a new variable are
+/// continuations of multi-line values.
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn parse_environment(output: &str) -> Option<HashMap<String, String>> {
+    let mut parts = output.split(MARKER);
+    let env_output = parts.nth(1)?;
+    // Without the closing marker, the output could be cut short
+    parts.next()?;
+
+    let mut variables: Vec<(String, String)> = Vec::new();
+    for line in env_output.lines() {
+        match line.split_once('=') {
+            Some((name, value)) if is_variable_name(name) => {
+                variables.push((name.to_string(), value.to_string()));
+            }
+            _ => {
+                if let Some((_, value)) = variables.last_mut() {
+                    value.push('\n');
+                    value.push_str(line);
+                }
+            }
+        }
+    }
+    Some(
+        variables
+            .into_iter()
+            .filter(|(name, _)| !IGNORED_VARIABLES.contains(&name.as_str()))
+            .collect(),
+    )
+}
+
+fn is_variable_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_environment() {
+        let output = format!(
+            "Welcome back!\n{MARKER}PATH=/opt/homebrew/bin:/usr/bin\nSHLVL=2\n\
+             MULTI=first\nsecond line\nEMPTY=\n{MARKER}"
+        );
+        let environment = parse_environment(&output).unwrap();
+        assert_eq!(environment["PATH"], "/opt/homebrew/bin:/usr/bin");
+        assert_eq!(environment["MULTI"], "first\nsecond line");
+        assert_eq!(environment["EMPTY"], "");
+        assert!(!environment.contains_key("SHLVL"));
+        assert_eq!(environment.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_environment_without_markers() {
+        assert_eq!(parse_environment("PATH=/usr/bin\n"), None);
+        assert_eq!(parse_environment(&format!("{MARKER}PATH=/usr/bin\n")), None);
+    }
+}
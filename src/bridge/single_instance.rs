@@ -0,0 +1,664 @@
+//! Lets new invocations of `neovide --single-instance` open their files in an already running
+//! Neovide, and `neovide --send-command` control it, by sending requests over a Unix domain socket
+//! or a named pipe. New invocations also hand their files to a hidden `neovide --daemon` the same
+//! way.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{BufRead, BufReader as StdBufReader, Write},
+    process::{Command as StdCommand, Stdio},
+    time::Duration,
+};
+
+use anyhow::{bail, Context, Result};
+use log::{error, info, warn};
+use rmpv::Value;
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader},
+    sync::mpsc::unbounded_channel,
+    time::timeout,
+};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    bridge::{send_ui, ParallelCommand},
+    cmd_line::CmdLineSettings,
+    settings::SETTINGS,
+    window::{UserEvent, WindowCommand},
+};
+
+/// Sent by a new invocation as a single line of JSON.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+enum Request {
+    /// Opens the files and focuses the window. The paths are absolute, since the working directory
+    /// of the invocation can be different.
+    Open {
+        files: Vec<String>,
+    },
+    Focus,
+    Lua {
+        code: String,
+    },
+    SetFont {
+        font: String,
+    },
+    /// Takes over a hidden daemon, which shows its window with the files. The daemon answers with
+    /// a line, so that the invocation knows that it doesn't need to start itself. The handed
+    /// variables of the invocation's environment are given to Neovim.
+    Claim {
+        files: Vec<String>,
+        cwd: String,
+        env: BTreeMap<String, String>,
+    },
+}
+
+/// How long a new connection has to send its request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// The answer of a daemon that was claimed.
+const CLAIMED: &str = "claimed";
+
+/// The answer to a `lua` request, as a single line of JSON.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+enum Response {
+    /// The value returned by the code, empty when it returned nothing.
+    Result(String),
+    Error(String),
+}
+
+impl Request {
+    /// Parses the values of `--send-command`, like `open foo.txt` or `set-font "Fira Code:h14"`.
+    fn parse(args: &[String]) -> Result<Self> {
+        let (command, args) = args.split_first().context("No command given")?;
+        let request = match (command.as_str(), args) {
+            ("open", files) if !files.is_empty() => open_request(files),
+            ("focus", []) => Request::Focus,
+            ("lua", [code]) => Request::Lua { code: code.clone() },
+            ("set-font", [font]) => Request::SetFont { font: font.clone() },
+            ("open" | "focus" | "lua" | "set-font", _) => {
+                bail!("Wrong number of arguments for {command}")
+            }
+            _ => bail!("Unknown command {command}, expected open, focus, lua or set-font"),
+        };
+        Ok(request)
+    }
+}
+
+/// The address the running instance listens on, one per user.
+fn instance_address() -> Result<String> {
+    address("instance")
+}
+
+/// The address the waiting daemon listens on, one per user.
+fn daemon_address() -> Result<String> {
+    address("daemon")
+}
+
+fn address(kind: &str) -> Result<String> {
+    // The pipe names are shared by all the users, so they are made unique with the SID, which
+    // can't be changed like the user name in the environment, and the login session
+    #[cfg(windows)]
+    {
+        let sid = crate::windows_utils::current_user_sid()?;
+        let session = crate::windows_utils::current_session_id()?;
+        Ok(format!("\\\\.\\pipe\\neovide-{kind}-{sid}-{session}"))
+    }
+
+    #[cfg(not(windows))]
+    {
+        let user = env::var("USER")
+            .or_else(|_| env::var("USERNAME"))
+            .unwrap_or_default();
+        Ok(socket_directory(&user)?
+            .join(format!("{kind}.sock"))
+            .to_string_lossy()
+            .into_owned())
+    }
+}
+
+/// The directory of the sockets, which only the user can access. Without a runtime directory it's
+/// in the temporary directory shared with the other users, so one that was created by someone else,
+/// or that others can access, is refused.
+#[cfg(not(windows))]
+fn socket_directory(user: &str) -> Result<std::path::PathBuf> {
+    use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+
+    let directory = dirs::runtime_dir()
+        .unwrap_or_else(env::temp_dir)
+        .join(format!("neovide-{user}"));
+    match std::fs::DirBuilder::new().mode(0o700).create(&directory) {
+        Err(error) if error.kind() != std::io::ErrorKind::AlreadyExists => {
+            return Err(error).with_context(|| format!("Could not create {}", directory.display()));
+        }
+        _ => {}
+    }
+    let metadata = std::fs::symlink_metadata(&directory)
+        .with_context(|| format!("Could not read {}", directory.display()))?;
+    // SAFETY: getuid has no preconditions and can't fail
+    let uid = unsafe { libc::getuid() };
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.permissions().mode() & 0o077 != 0 {
+        bail!(
+            "{} is not a directory that only the current user can access",
+            directory.display()
+        );
+    }
+    Ok(directory)
+}
+
+fn open_request(files: &[String]) -> Request {
+    Request::Open {
+        files: files.iter().map(|file| absolute_path(file)).collect(),
+    }
+}
+
+fn absolute_path(file: &str) -> String {
+    env::current_dir()
+        .unwrap_or_default()
+        .join(file)
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Sends the request to the running instance, and returns the connection for reading the answer.
+/// Returns `Ok(None)` when no instance is running.
+fn send_request(request: &Request) -> Result<Option<Stream>> {
+    let Some(mut stream) = connect(&instance_address()?) else {
+        return Ok(None);
+    };
+    let line = serde_json::to_string(request).context("Could not serialize the request")?;
+    writeln!(stream, "{line}").context("Could not send the request to the running instance")?;
+    Ok(Some(stream))
+}
+
+#[cfg(unix)]
+type Stream = std::os::unix::net::UnixStream;
+
+// The client end of a named pipe can be opened like a file
+#[cfg(windows)]
+type Stream = std::fs::File;
+
+#[cfg(unix)]
+fn connect(address: &str) -> Option<Stream> {
+    Stream::connect(address).ok()
+}
+
+/// Connects to the pipe, when its server is a process of the current user. The server is only
+/// allowed to identify the client, not to act as it.
+#[cfg(windows)]
+fn connect(address: &str) -> Option<Stream> {
+    use std::os::windows::fs::OpenOptionsExt;
+
+    use winapi::um::winbase::SECURITY_IDENTIFICATION;
+
+    let stream = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .security_qos_flags(SECURITY_IDENTIFICATION)
+        .open(address)
+        .ok()?;
+    match crate::windows_utils::verify_pipe_server(&stream) {
+        Ok(()) => Some(stream),
+        Err(error) => {
+            warn!("Not connecting to {address}: {error:?}");
+            None
+        }
+    }
+}
+
+/// Sends the files to the running instance, which opens them and focuses its window. Returns
+/// false when no instance is running, so that this one should start normally.
+pub fn forward_to_running_instance(files: &[String]) -> bool {
+    let request = if files.is_empty() {
+        Request::Focus
+    } else {
+        open_request(files)
+    };
+    match send_request(&request) {
+        Ok(stream) => stream.is_some(),
+        Err(error) => {
+            // Starting normally is better than losing the files
+            warn!("{error:?}");
+            false
+        }
+    }
+}
+
+/// The variables handed to a daemon, besides the `NEOVIDE_` ones, which affect how the programs
+/// started from Neovim behave. The rest of the environment can hold tokens and credentials, so it's
+/// never sent.
+const HANDED_VARIABLES: &[&str] = &[
+    "PATH",
+    "SHELL",
+    "EDITOR",
+    "VISUAL",
+    "TERM",
+    "COLORTERM",
+    "TZ",
+    "NVIM_APPNAME",
+    "LANG",
+    "LANGUAGE",
+];
+
+fn is_handed_variable(name: &str) -> bool {
+    HANDED_VARIABLES.contains(&name) || name.starts_with("NEOVIDE_") || name.starts_with("LC_")
+}
+
+/// The variables of this process that are handed to a daemon, without the ones that aren't valid
+/// Unicode.
+fn environment() -> BTreeMap<String, String> {
+    env::vars_os()
+        .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)))
+        .filter(|(name, _)| is_handed_variable(name))
+        .collect()
+}
+
+/// True when the `NEOVIDE_` variables, which set options like the command line does, are the same
+/// for the invocation and the daemon.
+fn same_options(invocation: &BTreeMap<String, String>, daemon: &BTreeMap<String, String>) -> bool {
+    let options = |env: &BTreeMap<String, String>| {
+        env.iter()
+            .filter(|(name, _)| name.starts_with("NEOVIDE_"))
+            .map(|(name, value)| (name.clone(), value.clone()))
+            .collect::<Vec<_>>()
+    };
+    options(invocation) == options(daemon)
+}
+
+/// Hands the files to a waiting `neovide --daemon`, which shows its window with them. Returns false
+/// when no daemon is waiting, it didn't answer, or it was started with other options, so that this
+/// one should start normally. Only invocations without options are handed over, since the window
+/// of the daemon is already set up with its own.
+///
+/// Nothing is sent until the daemon is known to be the user's: the Unix socket is in a directory
+/// only the user can access, and on Windows `connect` checks the server of the pipe.
+pub fn hand_to_daemon(files: &[String]) -> bool {
+    let Some(stream) = daemon_address().ok().and_then(|address| connect(&address)) else {
+        return false;
+    };
+    let request = Request::Claim {
+        files: files.iter().map(|file| absolute_path(file)).collect(),
+        cwd: env::current_dir()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .into_owned(),
+        env: environment(),
+    };
+    let claimed = (|| -> Result<bool> {
+        let mut writer = &stream;
+        writeln!(writer, "{}", serde_json::to_string(&request)?)?;
+        let mut answer = String::new();
+        StdBufReader::new(&stream).read_line(&mut answer)?;
+        Ok(answer.trim() == CLAIMED)
+    })();
+    match claimed {
+        Ok(claimed) => claimed,
+        Err(error) => {
+            // Another invocation claimed the daemon first
+            warn!("Could not hand the files to the daemon: {error:?}");
+            false
+        }
+    }
+}
+
+/// True when a daemon is already waiting, so that another one isn't started.
+pub fn daemon_is_waiting() -> bool {
+    daemon_address()
+        .ok()
+        .and_then(|address| connect(&address))
+        .is_some()
+}
+
+/// Starts the next daemon in the background, with the same arguments as this one.
+fn spawn_spare_daemon() {
+    let spawned = env::current_exe().and_then(|current_exe| {
+        StdCommand::new(current_exe)
+            .args(env::args().skip(1))
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+    });
+    match spawned {
+        Ok(_) => info!("Started the next daemon"),
+        Err(error) => error!("Could not start the next daemon: {error}"),
+    }
+}
+
+/// Sends the command given with `--send-command` to the running instance. The result of `lua` is
+/// printed, and its error returned.
+pub fn send_command(args: &[String]) -> Result<()> {
+    let request = Request::parse(args)?;
+    let Some(stream) = send_request(&request)? else {
+        bail!("No running Neovide started with --single-instance was found");
+    };
+    if matches!(request, Request::Lua { .. }) {
+        let mut line = String::new();
+        StdBufReader::new(&stream)
+            .read_line(&mut line)
+            .context("Could not read the result from the running instance")?;
+        match serde_json::from_str(&line).context("Invalid response")? {
+            Response::Result(result) if result.is_empty() => {}
+            Response::Result(result) => println!("{result}"),
+            Response::Error(error) => bail!("{error}"),
+        }
+    }
+    Ok(())
+}
+
+/// Reads the request of a connection. None when it was closed without one, like by the check of
+/// a starting daemon.
+async fn read_request(stream: &mut (impl AsyncRead + Unpin)) -> Result<Option<Request>> {
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line).await?;
+    if line.is_empty() {
+        return Ok(None);
+    }
+    serde_json::from_str(&line)
+        .context("Invalid request")
+        .map(Some)
+}
+
+/// Handles the request of a connection. Returns true when it claimed the daemon.
+async fn handle_connection(
+    mut stream: impl AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    proxy: &EventLoopProxy<UserEvent>,
+) -> bool {
+    // The connections are handled one at a time, so a client that never sends its request
+    // mustn't keep the others waiting
+    let request = match timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await {
+        Ok(request) => match request.transpose() {
+            Some(request) => request,
+            None => return false,
+        },
+        Err(_) => {
+            warn!("A new invocation didn't send its request in time");
+            return false;
+        }
+    };
+    if let Ok(Request::Lua { code }) = request {
+        // Neovim can be busy for a while, so the other connections are not kept waiting
+        tokio::spawn(answer_lua(stream, code));
+        return false;
+    }
+    if let Ok(Request::Claim { env, .. }) = &request {
+        // Without an answer the invocation starts normally, with its own options
+        if !same_options(env, &environment()) {
+            info!("Not claimed by an invocation with other NEOVIDE_ variables");
+            return false;
+        }
+    }
+    let claimed = matches!(request, Ok(Request::Claim { .. }));
+    if claimed {
+        if let Err(error) = stream.write_all(format!("{CLAIMED}\n").as_bytes()).await {
+            warn!("Could not answer the invocation that claimed the daemon: {error}");
+        }
+    }
+    handle_request(request, proxy);
+    claimed
+}
+
+/// Runs the code of a `lua` request, and answers with its result.
+async fn answer_lua(mut stream: impl AsyncWrite + Unpin, code: String) {
+    let (reply, mut result) = unbounded_channel();
+    send_ui(ParallelCommand::EvaluateLua { code, reply });
+    let response = match result.recv().await {
+        Some(Ok(result)) => Response::Result(result),
+        Some(Err(error)) => Response::Error(error),
+        None => Response::Error("Neovim exited".to_string()),
+    };
+    let line = serde_json::to_string(&response).unwrap_or_default();
+    if let Err(error) = stream.write_all(format!("{line}\n").as_bytes()).await {
+        warn!("Could not answer a lua request: {error}");
+    }
+}
+
+// The proxy is not sync on all platforms, so it can't be borrowed across awaits
+fn handle_request(request: Result<Request>, proxy: &EventLoopProxy<UserEvent>) {
+    match request {
+        Ok(Request::Claim { files, cwd, env }) => {
+            info!("Claimed by a new invocation in {cwd} with {files:?}");
+            let env = env
+                .into_iter()
+                .map(|(name, value)| (name.into(), value.into()))
+                .collect();
+            send_ui(ParallelCommand::ExecLua {
+                code: r#"
+                    local cwd, env = ...
+                    for name, value in pairs(env) do
+                        vim.env[name] = value
+                    end
+                    vim.fn.chdir(cwd)
+                "#
+                .to_string(),
+                args: vec![cwd.into(), Value::Map(env)],
+            });
+            if !files.is_empty() {
+                let _ = proxy.send_event(UserEvent::OpenFiles(files));
+            }
+            let _ = proxy.send_event(WindowCommand::ShowDaemonWindow.into());
+        }
+        Ok(Request::Open { files }) => {
+            info!("Opening {files:?} from a new invocation");
+            let _ = proxy.send_event(UserEvent::OpenFiles(files));
+            let _ = proxy.send_event(WindowCommand::FocusWindow.into());
+        }
+        Ok(Request::Focus) => {
+            let _ = proxy.send_event(WindowCommand::FocusWindow.into());
+        }
+        // Answered by the connection
+        Ok(Request::Lua { .. }) => {}
+        Ok(Request::SetFont { font }) => send_ui(ParallelCommand::ExecLua {
+            code: "vim.o.guifont = ...".to_string(),
+            args: vec![font.into()],
+        }),
+        Err(error) => warn!("Could not handle a new invocation: {error:?}"),
+    }
+}
+
+/// Accepts the requests of new invocations until Neovide exits.
+pub async fn listen_for_instances(proxy: EventLoopProxy<UserEvent>) {
+    match instance_address() {
+        Ok(address) => {
+            listen(address, proxy, false).await;
+        }
+        Err(error) => error!("Could not listen for other instances: {error:?}"),
+    }
+}
+
+/// Waits hidden until a new invocation claims the daemon, and then starts the next one, which
+/// takes over the address. With `--single-instance`, the claimed window is the one that opens the
+/// files of later invocations.
+pub async fn wait_to_be_claimed(proxy: EventLoopProxy<UserEvent>) {
+    let address = match daemon_address() {
+        Ok(address) => address,
+        Err(error) => {
+            error!("Could not wait for an invocation: {error:?}");
+            return;
+        }
+    };
+    if !listen(address.clone(), proxy.clone(), true).await {
+        return;
+    }
+    #[cfg(unix)]
+    let _ = std::fs::remove_file(&address);
+    spawn_spare_daemon();
+    if SETTINGS.get::<CmdLineSettings>().single_instance {
+        listen_for_instances(proxy).await;
+    }
+}
+
+/// Accepts the requests of new invocations until Neovide exits, or until the daemon is claimed
+/// with `until_claimed`. Returns true when it was claimed.
+#[cfg(unix)]
+async fn listen(address: String, proxy: EventLoopProxy<UserEvent>, until_claimed: bool) -> bool {
+    use tokio::net::UnixListener;
+
+    // Nothing answered on the socket, so it was left behind by an instance that crashed
+    let _ = std::fs::remove_file(&address);
+    let listener = match UnixListener::bind(&address) {
+        Ok(listener) => listener,
+        Err(error) => {
+            error!("Could not listen for other instances on {address}: {error}");
+            return false;
+        }
+    };
+    loop {
+        match listener.accept().await {
+            Ok((stream, _)) => {
+                if handle_connection(stream, &proxy).await && until_claimed {
+                    return true;
+                }
+            }
+            Err(error) => {
+                error!("Could not accept a new invocation: {error}");
+                return false;
+            }
+        }
+    }
+}
+
+/// Accepts the requests of new invocations until Neovide exits, or until the daemon is claimed
+/// with `until_claimed`. Returns true when it was claimed.
+#[cfg(windows)]
+async fn listen(address: String, proxy: EventLoopProxy<UserEvent>, until_claimed: bool) -> bool {
+    use tokio::net::windows::named_pipe::ServerOptions;
+
+    let mut options = ServerOptions::new();
+    let mut server = match create_pipe(&mut options, &address, true) {
+        Ok(server) => server,
+        Err(error) => {
+            error!("Could not listen for other instances on {address}: {error:?}");
+            return false;
+        }
+    };
+    loop {
+        if let Err(error) = server.connect().await {
+            error!("Could not accept a new invocation: {error}");
+            return false;
+        }
+        // A new pipe instance needs to exist before the connected one is handled
+        let connected = server;
+        server = match create_pipe(&mut options, &address, false) {
+            Ok(server) => server,
+            Err(error) => {
+                error!("Could not listen for other instances on {address}: {error:?}");
+                return false;
+            }
+        };
+        if handle_connection(connected, &proxy).await && until_claimed {
+            return true;
+        }
+    }
+}
+
+/// Creates an instance of the pipe that only the current user can connect to. The first one fails
+/// when the pipe already exists, so one created by someone else isn't joined.
+#[cfg(windows)]
+fn create_pipe(
+    options: &mut tokio::net::windows::named_pipe::ServerOptions,
+    address: &str,
+    first: bool,
+) -> Result<tokio::net::windows::named_pipe::NamedPipeServer> {
+    let security = crate::windows_utils::UserOnlySecurity::new()?;
+    let mut attributes = security.attributes();
+    // SAFETY: The attributes and the descriptor they point to outlive the call
+    let server = unsafe {
+        options
+            .first_pipe_instance(first)
+            .create_with_security_attributes_raw(
+                address,
+                &mut attributes as *mut _ as *mut std::ffi::c_void,
+            )?
+    };
+    Ok(server)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_request_has_absolute_paths() {
+        let request = open_request(&args(&["foo.txt"]));
+        assert_eq!(
+            request,
+            Request::Open {
+                files: vec![env::current_dir()
+                    .unwrap()
+                    .join("foo.txt")
+                    .to_string_lossy()
+                    .into_owned()]
+            }
+        );
+        let line = serde_json::to_string(&request).unwrap();
+        assert_eq!(serde_json::from_str::<Request>(&line).unwrap(), request);
+    }
+
+    #[test]
+    fn test_claim_needs_same_options() {
+        let env = |vars: &[(&str, &str)]| {
+            vars.iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect::<BTreeMap<_, _>>()
+        };
+        let daemon = env(&[("HOME", "/home/a"), ("NEOVIDE_FRAME", "none")]);
+        assert!(same_options(
+            &env(&[("HOME", "/home/b"), ("NEOVIDE_FRAME", "none")]),
+            &daemon
+        ));
+        assert!(!same_options(
+            &env(&[("NEOVIDE_FRAME", "none"), ("NEOVIDE_WSL", "1")]),
+            &daemon
+        ));
+        assert!(!same_options(&env(&[("HOME", "/home/a")]), &daemon));
+    }
+
+    #[test]
+    fn test_only_the_handed_variables_are_sent() {
+        assert!(is_handed_variable("PATH"));
+        assert!(is_handed_variable("NEOVIDE_FRAME"));
+        assert!(is_handed_variable("LC_ALL"));
+        assert!(!is_handed_variable("SSH_AUTH_SOCK"));
+        assert!(!is_handed_variable("GITHUB_TOKEN"));
+        assert!(!is_handed_variable("AWS_SECRET_ACCESS_KEY"));
+        assert!(!is_handed_variable("PATHEXT_EXTRA"));
+    }
+
+    #[test]
+    fn test_parse_command() {
+        assert_eq!(Request::parse(&args(&["focus"])).unwrap(), Request::Focus);
+        assert_eq!(
+            Request::parse(&args(&["set-font", "Fira Code:h14"])).unwrap(),
+            Request::SetFont {
+                font: "Fira Code:h14".to_string()
+            }
+        );
+        assert_eq!(
+            serde_json::to_string(&Request::parse(&args(&["lua", "print(1)"])).unwrap()).unwrap(),
+            r#"{"command":"lua","code":"print(1)"}"#
+        );
+        assert!(Request::parse(&args(&["open"])).is_err());
+        assert!(Request::parse(&args(&["focus", "now"])).is_err());
+        assert!(Request::parse(&args(&["quit"])).is_err());
+    }
+
+    #[test]
+    fn test_response_is_a_json_line() {
+        assert_eq!(
+            serde_json::to_string(&Response::Result("{ 1, 2 }".to_string())).unwrap(),
+            r#"{"result":"{ 1, 2 }"}"#
+        );
+        assert_eq!(
+            serde_json::from_str::<Response>(r#"{"error":"E5108"}"#).unwrap(),
+            Response::Error("E5108".to_string())
+        );
+    }
+}
@@ -1,17 +1,21 @@
 use std::sync::Mutex;
 
-use log::trace;
+use log::{error, trace};
 
 use anyhow::{Context, Result};
 use nvim_rs::{call_args, error::CallError, rpc::model::IntoVal, Neovim, Value};
 use strum::AsRefStr;
-use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    oneshot,
+};
 
-use super::show_error_message;
+use super::{show_error_message, wsl::neovim_path};
 use crate::{
     bridge::{ApiInformation, NeovimWriter},
     profiling::{tracy_dynamic_zone, tracy_fiber_enter, tracy_fiber_leave},
     running_tracker::RUNNING_TRACKER,
+    settings::ParseFromValue,
     LoggingSender,
 };
 
@@ -22,6 +26,7 @@ use crate::{
 #[derive(Clone, Debug, AsRefStr)]
 pub enum SerialCommand {
     Keyboard(String),
+    Paste(String),
     MouseButton {
         button: String,
         action: String,
@@ -41,6 +46,19 @@ pub enum SerialCommand {
         position: (u32, u32),
         modifier_string: String,
     },
+    /// Moves the vertical separator to the right of a window by a number of columns.
+    MoveSeparator {
+        window: u64,
+        columns: i64,
+    },
+    KeyEvent {
+        code: String,
+        key: String,
+        location: String,
+        pressed: bool,
+        repeat: bool,
+        modifier_string: String,
+    },
 }
 
 impl SerialCommand {
@@ -58,6 +76,11 @@ impl SerialCommand {
                     .map(|_| ())
                     .context("Input failed")
             }
+            SerialCommand::Paste(text) => nvim
+                .paste(&text, true, -1)
+                .await
+                .map(|_| ())
+                .context("Paste failed"),
             SerialCommand::MouseButton {
                 button,
                 action,
@@ -117,6 +140,46 @@ impl SerialCommand {
                     .await
                     .context("Mouse Drag Failed"),
             },
+            SerialCommand::MoveSeparator { window, columns } => nvim
+                .call_function(
+                    "win_move_separator",
+                    vec![Value::from(window), Value::from(columns)],
+                )
+                .await
+                .map(|_| ())
+                .context("Moving the separator failed"),
+            // Raw key events are delivered through an autocommand instead of nvim_input, so they
+            // never end up in the typeahead buffer or in recorded macros.
+            SerialCommand::KeyEvent {
+                code,
+                key,
+                location,
+                pressed,
+                repeat,
+                modifier_string,
+            } => {
+                let data = Value::from(vec![
+                    (Value::from("code"), Value::from(code)),
+                    (Value::from("key"), Value::from(key)),
+                    (Value::from("location"), Value::from(location)),
+                    (
+                        Value::from("state"),
+                        Value::from(if pressed { "press" } else { "release" }),
+                    ),
+                    (Value::from("repeat"), Value::from(repeat)),
+                    (Value::from("modifiers"), Value::from(modifier_string)),
+                ]);
+                nvim.exec_autocmds(
+                    Value::from("User"),
+                    vec![
+                        (Value::from("pattern"), Value::from("NeovideKeyEvent")),
+                        (Value::from("modeline"), Value::from(false)),
+                        (Value::from("data"), data),
+                    ],
+                )
+                .await
+                .context("Key Event Failed")
+            }
         };
 
         if let Err(error) = result {
@@ -125,18 +188,121 @@ impl SerialCommand {
     }
 }
 
+/// Where files opened from outside of Neovim are shown.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FileOpenTarget {
+    #[default]
+    Tabs,
+    Splits,
+    VSplits,
+    /// A new Neovide window, which the window opens itself.
+    Windows,
+}
+
+impl FileOpenTarget {
+    fn command(self) -> &'static str {
+        match self {
+            // New windows are started before the files reach Neovim, but tabs are the closest
+            FileOpenTarget::Tabs | FileOpenTarget::Windows => "tabnew",
+            FileOpenTarget::Splits => "split",
+            FileOpenTarget::VSplits => "vsplit",
+        }
+    }
+}
+
+impl ParseFromValue for FileOpenTarget {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(target) = value.as_str() {
+            *self = match target {
+                "tabs" => FileOpenTarget::Tabs,
+                "splits" | "hsplits" => FileOpenTarget::Splits,
+                "vsplits" => FileOpenTarget::VSplits,
+                "windows" => FileOpenTarget::Windows,
+                value => {
+                    error!(
+                        "Expected tabs, hsplits, vsplits or windows, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Expected a file open target string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<FileOpenTarget> for Value {
+    fn from(target: FileOpenTarget) -> Self {
+        match target {
+            FileOpenTarget::Tabs => Value::from("tabs"),
+            FileOpenTarget::Splits => Value::from("splits"),
+            FileOpenTarget::VSplits => Value::from("vsplits"),
+            FileOpenTarget::Windows => Value::from("windows"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, AsRefStr)]
 pub enum ParallelCommand {
     Quit,
-    Resize { width: u64, height: u64 },
-    FileDrop(String),
+    Resize {
+        width: u64,
+        height: u64,
+    },
+    FileDrop {
+        path: String,
+        target: FileOpenTarget,
+    },
     FocusLost,
     FocusGained,
     DisplayAvailableFonts(Vec<String>),
-    SetBackground(String),
-    ShowError { lines: Vec<String> },
+    /// Follows the theme of the system, `light` or `dark`, by setting `background` and the
+    /// colorscheme, and firing the `NeovideThemeChanged` autocommand when it changed.
+    ApplyTheme {
+        theme: String,
+        set_background: bool,
+        colorscheme: Option<String>,
+        notify: bool,
+    },
+    ShowError {
+        lines: Vec<String>,
+    },
+    SetVariable {
+        name: String,
+        value: Value,
+    },
+    ExecLua {
+        code: String,
+        args: Vec<Value>,
+    },
+    /// Runs the Lua code and sends back its result, formatted with `vim.inspect`, or the error.
+    EvaluateLua {
+        code: String,
+        reply: UnboundedSender<std::result::Result<String, String>>,
+    },
+    /// Sends Ctrl-C right away, instead of after the keys that are waiting to be sent.
+    Interrupt,
+    PowerChanged {
+        on_battery: bool,
+        power_saving: bool,
+    },
 }
 
+/// Like `:lua =expr`, the code is evaluated as an expression when it can be parsed as one.
+const EVALUATE_LUA: &str = r#"
+    local code = ...
+    local chunk = loadstring("return " .. code) or assert(loadstring(code))
+    local result = chunk()
+    if result == nil then
+        return ""
+    end
+    return type(result) == "string" and result or vim.inspect(result)
+"#;
+
 async fn display_available_fonts(
     nvim: &Neovim<NeovimWriter>,
     fonts: Vec<String>,
@@ -182,6 +348,37 @@ async fn display_available_fonts(
     Ok(())
 }
 
+async fn apply_theme(
+    nvim: &Neovim<NeovimWriter>,
+    theme: String,
+    set_background: bool,
+    colorscheme: Option<String>,
+    notify: bool,
+) -> Result<(), Box<CallError>> {
+    if set_background {
+        nvim.command(&format!("set background={theme}")).await?;
+    }
+    if let Some(colorscheme) = &colorscheme {
+        nvim.command(&format!("colorscheme {colorscheme}")).await?;
+    }
+    if notify {
+        let mut data = vec![(Value::from("theme"), Value::from(theme))];
+        if let Some(colorscheme) = colorscheme {
+            data.push((Value::from("colorscheme"), Value::from(colorscheme)));
+        }
+        nvim.exec_autocmds(
+            Value::from("User"),
+            vec![
+                (Value::from("pattern"), Value::from("NeovideThemeChanged")),
+                (Value::from("modeline"), Value::from(false)),
+                (Value::from("data"), Value::from(data)),
+            ],
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 impl ParallelCommand {
     async fn execute(self, nvim: &Neovim<NeovimWriter>) {
         // Don't panic here unless there's absolutely no chance of continuing the program, Instead
@@ -189,11 +386,11 @@ impl ParallelCommand {
         // for failure is when neovim has already quit, and a command, for example mouse move is
         // being sent
         let result = match self {
+            // With modified buffers, Neovim asks the window to show the quit dialog
             ParallelCommand::Quit => nvim
-                .command(
-                    "if get(g:, 'neovide_confirm_quit', 0) == 1 | confirm qa | else | qa! | endif",
-                )
+                .exec_lua("require('neovide')._quit()", vec![])
                 .await
+                .map(|_| ())
                 // Ignore all errors, since neovim exits immediately before the response is sent.
                 // We could an RPC notify instead of request, but nvim-rs does currently not support it.
                 .or(Ok(())),
@@ -207,22 +404,26 @@ impl ParallelCommand {
             ParallelCommand::FocusGained => {
                 nvim.ui_set_focus(true).await.context("FocusGained failed")
             }
-            ParallelCommand::FileDrop(path) => nvim
+            ParallelCommand::FileDrop { path, target } => nvim
                 .cmd(
                     vec![
-                        ("cmd".into(), "tabnew".into()),
+                        ("cmd".into(), target.command().into()),
                         ("magic".into(), vec![("file".into(), false.into())].into()),
-                        ("args".into(), vec![Value::from(path)].into()),
+                        ("args".into(), vec![Value::from(neovim_path(path))].into()),
                     ],
                     vec![],
                 )
                 .await
                 .map(|_| ()) // We don't care about the result
                 .context("FileDrop failed"),
-            ParallelCommand::SetBackground(background) => nvim
-                .command(format!("set background={}", background).as_str())
+            ParallelCommand::ApplyTheme {
+                theme,
+                set_background,
+                colorscheme,
+                notify,
+            } => apply_theme(nvim, theme, set_background, colorscheme, notify)
                 .await
-                .context("SetBackground failed"),
+                .context("ApplyTheme failed"),
             ParallelCommand::DisplayAvailableFonts(fonts) => display_available_fonts(nvim, fonts)
                 .await
                 .context("DisplayAvailableFonts failed"),
@@ -236,6 +437,49 @@ impl ParallelCommand {
                     .await
                     .context("ShowError failed")
             }
+            ParallelCommand::SetVariable { name, value } => nvim
+                .set_var(&name, value)
+                .await
+                .context("SetVariable failed"),
+            ParallelCommand::ExecLua { code, args } => nvim
+                .exec_lua(&code, args)
+                .await
+                .map(|_| ())
+                .context("ExecLua failed"),
+            ParallelCommand::EvaluateLua { code, reply } => {
+                let result = nvim
+                    .exec_lua(EVALUATE_LUA, vec![Value::from(code)])
+                    .await
+                    .map_err(|error| error.to_string())
+                    .map(|value| value.as_str().unwrap_or_default().to_string());
+                let _ = reply.send(result);
+                Ok(())
+            }
+            ParallelCommand::Interrupt => nvim
+                .input("<C-c>")
+                .await
+                .map(|_| ())
+                .context("Interrupt failed"),
+            ParallelCommand::PowerChanged {
+                on_battery,
+                power_saving,
+            } => nvim
+                .exec_autocmds(
+                    Value::from("User"),
+                    vec![
+                        (Value::from("pattern"), Value::from("NeovidePowerChanged")),
+                        (Value::from("modeline"), Value::from(false)),
+                        (
+                            Value::from("data"),
+                            Value::from(vec![
+                                (Value::from("on_battery"), Value::from(on_battery)),
+                                (Value::from("power_saving"), Value::from(power_saving)),
+                            ]),
+                        ),
+                    ],
+                )
+                .await
+                .context("PowerChanged failed"),
         };
 
         if let Err(error) = result {
@@ -290,13 +534,23 @@ lazy_static! {
     static ref UI_CHANNELS: UIChannels = UIChannels::new();
 }
 
-pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>, api_information: &ApiInformation) {
+/// Sends the UI commands to Neovim until the returned sender is used or dropped, which gives the
+/// commands back for the next Neovim, when it's restarted after a crash.
+pub fn start_ui_command_handler(
+    nvim: Neovim<NeovimWriter>,
+    api_information: &ApiInformation,
+) -> oneshot::Sender<()> {
     let (serial_tx, mut serial_rx) = unbounded_channel::<SerialCommand>();
+    let (stop_tx, mut stop_rx) = oneshot::channel();
     let ui_command_nvim = nvim.clone();
     tokio::spawn(async move {
         let mut ui_command_receiver = UI_CHANNELS.receiver.lock().unwrap().take().unwrap();
         while RUNNING_TRACKER.is_running() {
-            match ui_command_receiver.recv().await {
+            let command = tokio::select! {
+                command = ui_command_receiver.recv() => command,
+                _ = &mut stop_rx => break,
+            };
+            match command {
                 Some(UiCommand::Serial(serial_command)) => {
                     tracy_dynamic_zone!(serial_command.as_ref());
                     // This can fail if the serial_rx loop exits before this one, so ignore the errors
@@ -314,6 +568,7 @@ pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>, api_information: &Ap
                 }
             }
         }
+        *UI_CHANNELS.receiver.lock().unwrap() = Some(ui_command_receiver);
     });
 
     let has_x_buttons = api_information.version.has_version(0, 10, 0);
@@ -331,12 +586,19 @@ pub fn start_ui_command_handler(nvim: Neovim<NeovimWriter>, api_information: &Ap
                     serial_command.execute(&nvim, has_x_buttons).await;
                     tracy_fiber_enter!("Serial command");
                 }
-                None => {
-                    RUNNING_TRACKER.quit("serial ui command channel failed");
-                }
+                // The handler above was stopped
+                None => break,
             }
         }
     });
+    stop_tx
+}
+
+/// Drops the commands sent while Neovim wasn't running, like the keys typed after it crashed.
+pub fn discard_pending_ui_commands() {
+    if let Some(receiver) = UI_CHANNELS.receiver.lock().unwrap().as_mut() {
+        while receiver.try_recv().is_ok() {}
+    }
 }
 
 pub fn send_ui<T>(command: T)
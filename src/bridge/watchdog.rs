@@ -0,0 +1,49 @@
+//! Detects when Neovim stops answering requests, for example when it's stuck in an endless loop or
+//! waiting for a slow synchronous call, so that the window can show it instead of looking frozen.
+
+use std::time::Duration;
+
+use nvim_rs::Neovim;
+use tokio::time::{sleep, timeout};
+use winit::event_loop::EventLoopProxy;
+
+use crate::{
+    bridge::NeovimWriter,
+    running_tracker::RUNNING_TRACKER,
+    settings::SETTINGS,
+    window::{UserEvent, WindowCommand, WindowSettings},
+};
+
+/// The time between the heartbeat requests.
+const HEARTBEAT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Sends a heartbeat request every second, and tells the window that Neovim is busy when it isn't
+/// answered within `g:neovide_busy_timeout` seconds, and when it's answered again. The request is
+/// `nvim_get_mode`, which Neovim answers while it waits for input, like in the prompt of `input()`
+/// or a pending operator, so that only Neovim being stuck is reported.
+pub async fn watch_responsiveness(nvim: Neovim<NeovimWriter>, proxy: EventLoopProxy<UserEvent>) {
+    while RUNNING_TRACKER.is_running() {
+        let busy_timeout = SETTINGS.get::<WindowSettings>().busy_timeout;
+        if busy_timeout > 0.0 {
+            let heartbeat = nvim.get_mode();
+            tokio::pin!(heartbeat);
+            let limit = Duration::from_secs_f32(busy_timeout);
+            let result = match timeout(limit, &mut heartbeat).await {
+                Ok(result) => result,
+                Err(_) => {
+                    log::info!("Neovim has not responded for {busy_timeout} seconds");
+                    let _ = proxy.send_event(WindowCommand::SetNeovimBusy(true).into());
+                    let result = heartbeat.await;
+                    log::info!("Neovim is responding again");
+                    let _ = proxy.send_event(WindowCommand::SetNeovimBusy(false).into());
+                    result
+                }
+            };
+            // Neovim has exited
+            if result.is_err() {
+                return;
+            }
+        }
+        sleep(HEARTBEAT_INTERVAL).await;
+    }
+}
@@ -0,0 +1,86 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use crate::{cmd_line::CmdLineSettings, settings::SETTINGS};
+
+/// Where Windows drives are mounted in WSL, unless changed in `/etc/wsl.conf`.
+const AUTOMOUNT_ROOT: &str = "/mnt/";
+
+static NEOVIM_IN_WSL: AtomicBool = AtomicBool::new(false);
+
+/// Remembers that Neovim runs in WSL, either through `--wsl` or a server started in WSL, so that
+/// the paths from Windows are translated.
+pub fn set_neovim_in_wsl(in_wsl: bool) {
+    NEOVIM_IN_WSL.store(in_wsl, Ordering::Relaxed);
+}
+
+/// Translates a path from Windows, like a dropped file, to the one Neovim sees.
+pub fn neovim_path(path: String) -> String {
+    if cfg!(target_os = "windows") && NEOVIM_IN_WSL.load(Ordering::Relaxed) {
+        windows_to_wsl_path(&path).unwrap_or(path)
+    } else {
+        path
+    }
+}
+
+/// The arguments that select the distribution for the `wsl` command, none for the default one.
+#[cfg_attr(target_os = "macos", allow(dead_code))]
+pub fn wsl_distro_args() -> Vec<String> {
+    SETTINGS
+        .get::<CmdLineSettings>()
+        .wsl_distro
+        .map(|distro| vec!["-d".to_string(), distro])
+        .unwrap_or_default()
+}
+
+/// Translates an absolute Windows path, like `C:\Users\me` or `\\wsl$\Ubuntu\home\me`, to the
+/// path inside WSL. Returns None for other paths, which are left as they are.
+pub fn windows_to_wsl_path(path: &str) -> Option<String> {
+    let path = path.replace('\\', "/");
+    // The files of the distributions themselves, through the network share
+    for prefix in ["//wsl$/", "//wsl.localhost/"] {
+        if let Some(rest) = path.strip_prefix(prefix) {
+            return Some(match rest.split_once('/') {
+                Some((_distro, rest)) => format!("/{rest}"),
+                None => "/".to_string(),
+            });
+        }
+    }
+
+    let mut chars = path.chars();
+    let drive = chars.next().filter(char::is_ascii_alphabetic)?;
+    let rest = chars.as_str().strip_prefix(':')?;
+    if !rest.is_empty() && !rest.starts_with('/') {
+        // A path relative to the current directory of the drive, like `C:file.txt`
+        return None;
+    }
+    Some(format!(
+        "{AUTOMOUNT_ROOT}{}{rest}",
+        drive.to_ascii_lowercase()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_windows_to_wsl_path() {
+        assert_eq!(
+            windows_to_wsl_path(r"C:\Users\me\My File.txt").as_deref(),
+            Some("/mnt/c/Users/me/My File.txt")
+        );
+        assert_eq!(windows_to_wsl_path("d:/src").as_deref(), Some("/mnt/d/src"));
+        assert_eq!(windows_to_wsl_path("E:").as_deref(), Some("/mnt/e"));
+        assert_eq!(
+            windows_to_wsl_path(r"\\wsl$\Ubuntu\home\me\init.lua").as_deref(),
+            Some("/home/me/init.lua")
+        );
+        assert_eq!(
+            windows_to_wsl_path(r"\\wsl.localhost\Debian").as_deref(),
+            Some("/")
+        );
+        assert_eq!(windows_to_wsl_path("src/main.rs"), None);
+        assert_eq!(windows_to_wsl_path("/home/me"), None);
+        assert_eq!(windows_to_wsl_path("C:file.txt"), None);
+    }
+}
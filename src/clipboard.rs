@@ -1,8 +1,13 @@
 use std::error::Error;
 use std::sync::OnceLock;
+#[cfg(target_os = "linux")]
+use std::time::Duration;
 
 #[cfg(target_os = "linux")]
-use copypasta::wayland_clipboard;
+use copypasta::{
+    wayland_clipboard,
+    x11_clipboard::{Primary as X11Primary, X11ClipboardContext},
+};
 use copypasta::{ClipboardContext, ClipboardProvider};
 use parking_lot::Mutex;
 use raw_window_handle::HasRawDisplayHandle;
@@ -14,24 +19,391 @@ use crate::window::UserEvent;
 
 type Result<T> = std::result::Result<T, Box<dyn Error + Send + Sync + 'static>>;
 
-static CLIPBOARD: OnceLock<Mutex<Box<dyn ClipboardProvider>>> = OnceLock::new();
+struct Clipboards {
+    clipboard: Box<dyn ClipboardProvider>,
+    // The primary selection, only available on Linux
+    selection: Option<Box<dyn ClipboardProvider>>,
+    // Images are only supported through X11 on Linux
+    #[cfg(target_os = "linux")]
+    is_x11: bool,
+}
+
+static CLIPBOARD: OnceLock<Mutex<Clipboards>> = OnceLock::new();
 
 pub fn init(event_loop: &EventLoop<UserEvent>) {
     CLIPBOARD
         .set(Mutex::new(match event_loop.raw_display_handle() {
             #[cfg(target_os = "linux")]
             RawDisplayHandle::Wayland(WaylandDisplayHandle { display, .. }) => unsafe {
-                Box::new(wayland_clipboard::create_clipboards_from_external(display).1)
+                let (selection, clipboard) =
+                    wayland_clipboard::create_clipboards_from_external(display);
+                Clipboards {
+                    clipboard: Box::new(clipboard),
+                    selection: Some(Box::new(selection)),
+                    is_x11: false,
+                }
+            },
+            #[cfg(target_os = "linux")]
+            RawDisplayHandle::Xlib(..) | RawDisplayHandle::Xcb(..) => Clipboards {
+                clipboard: Box::new(ClipboardContext::new().unwrap()),
+                selection: X11ClipboardContext::<X11Primary>::new()
+                    .ok()
+                    .map(|selection| Box::new(selection) as Box<dyn ClipboardProvider>),
+                is_x11: true,
+            },
+            _ => Clipboards {
+                clipboard: Box::new(ClipboardContext::new().unwrap()),
+                selection: None,
+                #[cfg(target_os = "linux")]
+                is_x11: false,
             },
-            _ => Box::new(ClipboardContext::new().unwrap()),
         }))
         .ok();
 }
 
-pub fn get_contents() -> Result<String> {
-    CLIPBOARD.get().unwrap().lock().get_contents()
+fn with_provider<T>(
+    register: &str,
+    f: impl FnOnce(&mut Box<dyn ClipboardProvider>) -> Result<T>,
+) -> Result<T> {
+    let mut clipboards = CLIPBOARD.get().unwrap().lock();
+    let clipboards = &mut *clipboards;
+    match (register, clipboards.selection.as_mut()) {
+        ("*", Some(selection)) => f(selection),
+        _ => f(&mut clipboards.clipboard),
+    }
+}
+
+/// Gets the contents of the given register, "*" is the primary selection when available,
+/// everything else is the clipboard.
+pub fn get_contents(register: &str) -> Result<String> {
+    with_provider(register, |provider| provider.get_contents())
+}
+
+pub fn set_contents(lines: String, register: &str) -> Result<()> {
+    with_provider(register, |provider| provider.set_contents(lines))
+}
+
+/// Returns the image on the clipboard encoded as PNG, or None if the clipboard doesn't contain an
+/// image in a supported format.
+pub fn get_image() -> Result<Option<Vec<u8>>> {
+    #[cfg(target_os = "linux")]
+    {
+        if CLIPBOARD.get().unwrap().lock().is_x11 {
+            get_x11_image()
+        } else {
+            Err("clipboard images are not supported on Wayland".into())
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        get_windows_image()
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows")))]
+    {
+        Err("clipboard images are not supported on this platform".into())
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn get_x11_image() -> Result<Option<Vec<u8>>> {
+    let clipboard = x11_clipboard::Clipboard::new()?;
+    let png = clipboard.getter.get_atom("image/png")?;
+    // The owner refuses the conversion when there's no image, which shows up as an error
+    let image = clipboard
+        .load(
+            clipboard.getter.atoms.clipboard,
+            png,
+            clipboard.getter.atoms.property,
+            Duration::from_millis(500),
+        )
+        .ok();
+    Ok(image.filter(|image| !image.is_empty()))
+}
+
+#[cfg(target_os = "windows")]
+fn get_windows_image() -> Result<Option<Vec<u8>>> {
+    use std::{ffi::CString, ptr::null_mut, slice};
+
+    use winapi::um::{
+        winbase::{GlobalLock, GlobalSize, GlobalUnlock},
+        winuser::{
+            CloseClipboard, GetClipboardData, IsClipboardFormatAvailable, OpenClipboard,
+            RegisterClipboardFormatA,
+        },
+    };
+
+    // Browsers, Office and most screenshot tools put the image on the clipboard in the
+    // registered "PNG" format in addition to the bitmap formats.
+    let format_name = CString::new("PNG").unwrap();
+    unsafe {
+        let format = RegisterClipboardFormatA(format_name.as_ptr());
+        if format == 0 || IsClipboardFormatAvailable(format) == 0 {
+            return Ok(None);
+        }
+        if OpenClipboard(null_mut()) == 0 {
+            return Err("could not open the clipboard".into());
+        }
+        let handle = GetClipboardData(format);
+        let mut image = None;
+        if !handle.is_null() {
+            let data = GlobalLock(handle);
+            if !data.is_null() {
+                image = Some(slice::from_raw_parts(data as *const u8, GlobalSize(handle)).to_vec());
+                GlobalUnlock(handle);
+            }
+        }
+        CloseClipboard();
+        Ok(image)
+    }
 }
 
-pub fn set_contents(lines: String) -> Result<()> {
-    CLIPBOARD.get().unwrap().lock().set_contents(lines)
+/// Puts formatted text on the clipboard, together with a plain text version for applications that
+/// don't support formatting.
+pub fn set_rich_contents(text: &str, html: &str, rtf: &str) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        if CLIPBOARD.get().unwrap().lock().is_x11 {
+            set_x11_rich_contents(text, html, rtf)
+        } else {
+            Err("copying rich text is not supported on Wayland".into())
+        }
+    }
+    #[cfg(target_os = "windows")]
+    {
+        set_windows_rich_contents(text, html, rtf)
+    }
+    #[cfg(target_os = "macos")]
+    {
+        set_macos_rich_contents(text, html, rtf)
+    }
+    #[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+    {
+        let _ = (text, html, rtf);
+        Err("copying rich text is not supported on this platform".into())
+    }
+}
+
+/// Owns the X11 clipboard with the text in all the formats until another application takes it.
+/// x11-clipboard only offers a single format, which would leave applications that paste plain
+/// text with nothing.
+#[cfg(target_os = "linux")]
+fn set_x11_rich_contents(text: &str, html: &str, rtf: &str) -> Result<()> {
+    use std::thread;
+
+    use x11rb::{
+        connection::{Connection, RequestConnection},
+        protocol::{
+            xproto::{
+                AtomEnum, ConnectionExt, CreateWindowAux, EventMask, PropMode,
+                SelectionNotifyEvent, WindowClass, SELECTION_NOTIFY_EVENT,
+            },
+            Event,
+        },
+        wrapper::ConnectionExt as _,
+        COPY_DEPTH_FROM_PARENT, CURRENT_TIME, NONE,
+    };
+
+    let (connection, screen) = x11rb::connect(None)?;
+    let root = connection.setup().roots[screen].root;
+    let window = connection.generate_id()?;
+    connection.create_window(
+        COPY_DEPTH_FROM_PARENT,
+        window,
+        root,
+        0,
+        0,
+        1,
+        1,
+        0,
+        WindowClass::INPUT_OUTPUT,
+        0,
+        &CreateWindowAux::new(),
+    )?;
+    let atom = |name: &str| -> Result<u32> {
+        Ok(connection
+            .intern_atom(false, name.as_bytes())?
+            .reply()?
+            .atom)
+    };
+    let clipboard = atom("CLIPBOARD")?;
+    let targets = atom("TARGETS")?;
+    let formats = [
+        (atom("UTF8_STRING")?, text.as_bytes().to_vec()),
+        (atom("text/plain;charset=utf-8")?, text.as_bytes().to_vec()),
+        (atom("text/html")?, html.as_bytes().to_vec()),
+        (atom("text/rtf")?, rtf.as_bytes().to_vec()),
+    ];
+    // Larger contents would need the INCR protocol
+    let max_size = connection.maximum_request_bytes().saturating_sub(64);
+    if formats.iter().any(|(_, data)| data.len() > max_size) {
+        return Err("the text is too large for the clipboard".into());
+    }
+
+    connection.set_selection_owner(window, clipboard, CURRENT_TIME)?;
+    if connection.get_selection_owner(clipboard)?.reply()?.owner != window {
+        return Err("could not take the clipboard".into());
+    }
+
+    thread::Builder::new()
+        .name("rich_clipboard".to_owned())
+        .spawn(move || {
+            while let Ok(event) = connection.wait_for_event() {
+                match event {
+                    Event::SelectionRequest(request) => {
+                        // Obsolete clients don't give a property
+                        let property = if request.property == NONE {
+                            request.target
+                        } else {
+                            request.property
+                        };
+                        let stored = if request.target == targets {
+                            let mut atoms = vec![targets];
+                            atoms.extend(formats.iter().map(|(format, _)| *format));
+                            connection
+                                .change_property32(
+                                    PropMode::REPLACE,
+                                    request.requestor,
+                                    property,
+                                    AtomEnum::ATOM,
+                                    &atoms,
+                                )
+                                .is_ok()
+                        } else if let Some((format, data)) =
+                            formats.iter().find(|(format, _)| *format == request.target)
+                        {
+                            connection
+                                .change_property8(
+                                    PropMode::REPLACE,
+                                    request.requestor,
+                                    property,
+                                    *format,
+                                    data,
+                                )
+                                .is_ok()
+                        } else {
+                            false
+                        };
+                        let _ = connection.send_event(
+                            false,
+                            request.requestor,
+                            EventMask::NO_EVENT,
+                            SelectionNotifyEvent {
+                                response_type: SELECTION_NOTIFY_EVENT,
+                                sequence: 0,
+                                time: request.time,
+                                requestor: request.requestor,
+                                selection: request.selection,
+                                target: request.target,
+                                property: if stored { property } else { NONE },
+                            },
+                        );
+                        let _ = connection.flush();
+                    }
+                    // Another application owns the clipboard now
+                    Event::SelectionClear(_) => break,
+                    _ => {}
+                }
+            }
+        })?;
+    Ok(())
+}
+
+#[cfg(target_os = "macos")]
+fn set_macos_rich_contents(text: &str, html: &str, rtf: &str) -> Result<()> {
+    use icrate::{
+        AppKit::{NSPasteboard, NSPasteboardTypeHTML, NSPasteboardTypeRTF, NSPasteboardTypeString},
+        Foundation::NSString,
+    };
+
+    unsafe {
+        let pasteboard = NSPasteboard::generalPasteboard();
+        pasteboard.clearContents();
+        for (contents, format) in [
+            (text, NSPasteboardTypeString),
+            (html, NSPasteboardTypeHTML),
+            (rtf, NSPasteboardTypeRTF),
+        ] {
+            if !pasteboard.setString_forType(&NSString::from_str(contents), format) {
+                return Err("could not write to the pasteboard".into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Wraps an html fragment in the header required by the Windows "HTML Format" clipboard format.
+#[cfg(target_os = "windows")]
+fn windows_html_format(html: &str) -> String {
+    const HEADER_LENGTH: usize = 105;
+    let prefix = "<html><body><!--StartFragment-->";
+    let suffix = "<!--EndFragment--></body></html>";
+    let start_html = HEADER_LENGTH;
+    let start_fragment = start_html + prefix.len();
+    let end_fragment = start_fragment + html.len();
+    let end_html = end_fragment + suffix.len();
+    let header = format!(
+        "Version:0.9\r\nStartHTML:{start_html:010}\r\nEndHTML:{end_html:010}\r\n\
+         StartFragment:{start_fragment:010}\r\nEndFragment:{end_fragment:010}\r\n"
+    );
+    debug_assert_eq!(header.len(), HEADER_LENGTH);
+    format!("{header}{prefix}{html}{suffix}")
+}
+
+#[cfg(target_os = "windows")]
+fn set_windows_rich_contents(text: &str, html: &str, rtf: &str) -> Result<()> {
+    use std::{ffi::CString, ptr::copy_nonoverlapping, ptr::null_mut};
+
+    use winapi::um::{
+        winbase::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE},
+        winuser::{
+            CloseClipboard, EmptyClipboard, OpenClipboard, RegisterClipboardFormatA,
+            SetClipboardData, CF_UNICODETEXT,
+        },
+    };
+
+    let text = text
+        .replace('\n', "\r\n")
+        .encode_utf16()
+        .chain(Some(0))
+        .flat_map(u16::to_ne_bytes)
+        .collect::<Vec<u8>>();
+    let html = windows_html_format(html).into_bytes();
+    let rtf = rtf.as_bytes().to_vec();
+
+    let html_format = CString::new("HTML Format").unwrap();
+    let rtf_format = CString::new("Rich Text Format").unwrap();
+    unsafe {
+        let formats = [
+            (CF_UNICODETEXT, text),
+            (RegisterClipboardFormatA(html_format.as_ptr()), html),
+            (RegisterClipboardFormatA(rtf_format.as_ptr()), rtf),
+        ];
+
+        if OpenClipboard(null_mut()) == 0 {
+            return Err("could not open the clipboard".into());
+        }
+        EmptyClipboard();
+        for (format, mut data) in formats {
+            if format == 0 {
+                continue;
+            }
+            // The html and rtf formats are expected to be null terminated as well
+            if format != CF_UNICODETEXT {
+                data.push(0);
+            }
+            let handle = GlobalAlloc(GMEM_MOVEABLE, data.len());
+            if handle.is_null() {
+                continue;
+            }
+            let target = GlobalLock(handle);
+            if !target.is_null() {
+                copy_nonoverlapping(data.as_ptr(), target as *mut u8, data.len());
+                GlobalUnlock(handle);
+                // The clipboard takes ownership of the memory
+                SetClipboardData(format, handle);
+            }
+        }
+        CloseClipboard();
+    }
+    Ok(())
 }
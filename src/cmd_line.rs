@@ -1,13 +1,20 @@
 use std::{iter, mem};
 
 use crate::utils::is_tty;
-use crate::{dimensions::Dimensions, frame::Frame, settings::*};
+use crate::{
+    dimensions::{Extent, GeometrySize},
+    frame::Frame,
+    settings::*,
+    window::{parse_cascade_from, InitialPosition},
+};
 
 use anyhow::Result;
 use clap::{
     builder::{styling, FalseyValueParser, Styles},
-    ArgAction, Parser,
+    error::{Error as ClapError, ErrorKind},
+    ArgAction, CommandFactory, Parser,
 };
+use winit::dpi::PhysicalPosition;
 
 #[cfg(target_os = "windows")]
 pub const SRGB_DEFAULT: &str = "1";
@@ -26,10 +33,92 @@ fn is_tty_str() -> &'static str {
     is_tty().then_some("1").unwrap_or("0")
 }
 
+/// The Nvim flags Neovide knows about. They can also be given before `--`, since they don't clash
+/// with the flags of Neovide.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NeovimFlags {
+    /// `-d`, the files are shown side by side.
+    pub diff: bool,
+    /// `-R`
+    pub read_only: bool,
+    /// `+{command}`, `+{line}` or `+`, run after the first file is loaded.
+    pub commands: Vec<String>,
+    /// `-q {errorfile}`
+    pub error_file: Option<String>,
+}
+
+impl NeovimFlags {
+    fn is_flag(arg: &str) -> bool {
+        matches!(arg, "-d" | "-R" | "-q") || arg.starts_with('+')
+    }
+
+    /// Finds the flags among the arguments passed to Nvim.
+    fn parse(args: &[String]) -> Result<Self, ClapError> {
+        let mut flags = Self::default();
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-d" => flags.diff = true,
+                "-R" => flags.read_only = true,
+                "-q" => {
+                    let Some(file) = args.next() else {
+                        return Err(CmdLineSettings::command().error(
+                            ErrorKind::InvalidValue,
+                            "`-q` needs the error file to read the quickfix list from",
+                        ));
+                    };
+                    flags.error_file = Some(file.clone());
+                }
+                "--embed" | "--headless" => {
+                    return Err(CmdLineSettings::command().error(
+                        ErrorKind::ArgumentConflict,
+                        format!(
+                            "Neovide starts Nvim embedded itself, `{arg}` can't be passed to it"
+                        ),
+                    ));
+                }
+                _ if arg.starts_with('+') => flags.commands.push(arg[1..].to_string()),
+                _ => {}
+            }
+        }
+        Ok(flags)
+    }
+
+    /// True when none of the flags were given.
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+/// Moves the Nvim flags given before `--` to their own list, since clap would reject them as
+/// unknown arguments or take them for files.
+fn split_neovim_flags(args: Vec<String>) -> (Vec<String>, Vec<String>) {
+    let mut neovide_args = Vec::new();
+    let mut neovim_flags = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            neovide_args.push(arg);
+            neovide_args.extend(args);
+            break;
+        }
+        if !NeovimFlags::is_flag(&arg) {
+            neovide_args.push(arg);
+            continue;
+        }
+        let takes_value = arg == "-q";
+        neovim_flags.push(arg);
+        if takes_value {
+            neovim_flags.extend(args.next());
+        }
+    }
+    (neovide_args, neovim_flags)
+}
+
 #[derive(Clone, Debug, Parser)]
 #[command(version, about, long_about = None, styles = get_styles())]
 pub struct CmdLineSettings {
-    /// Files to open (plainly appended to NeoVim args)
+    /// Files to open (plainly appended to NeoVim args), `-` reads the text to edit from stdin
     #[arg(
         num_args = ..,
         action = ArgAction::Append,
@@ -57,6 +146,18 @@ pub struct CmdLineSettings {
     #[arg(long, env = "NEOVIDE_WSL")]
     pub wsl: bool,
 
+    /// Run NeoVim in a Docker container of IMAGE, with the current directory mounted
+    #[arg(long, value_name = "IMAGE", conflicts_with_all = ["podman", "server", "wsl"])]
+    pub docker: Option<String>,
+
+    /// Run NeoVim in a Podman container of IMAGE, with the current directory mounted
+    #[arg(long, value_name = "IMAGE", conflicts_with_all = ["server", "wsl"])]
+    pub podman: Option<String>,
+
+    /// The WSL distribution to run NeoVim in instead of the default one, implies --wsl
+    #[arg(long = "wsl-distro", env = "NEOVIDE_WSL_DISTRO", value_name = "NAME")]
+    pub wsl_distro: Option<String>,
+
     /// Which window decorations to use (do note that the window might not be resizable
     /// if this is "none")
     #[arg(long, env = "NEOVIDE_FRAME", default_value_t)]
@@ -109,6 +210,11 @@ pub struct CmdLineSettings {
     #[arg(long = "no-vsync", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
     _no_vsync: bool,
 
+    /// Start NeoVim with the environment of Neovide, instead of the one of an interactive login
+    /// shell (macOS only)
+    #[arg(long = "no-shell-env", env = "NEOVIDE_NO_SHELL_ENV", value_parser = FalseyValueParser::new())]
+    pub no_shell_env: bool,
+
     /// Which NeoVim binary to invoke headlessly instead of `nvim` found on $PATH
     #[arg(long = "neovim-bin", env = "NEOVIM_BIN")]
     pub neovim_bin: Option<String>,
@@ -140,10 +246,83 @@ pub struct CmdLineSettings {
     #[command(flatten)]
     pub geometry: GeometryArgs,
 
+    /// Open the window on the monitor with this number, starting from 1, or with a name
+    /// containing NAME
+    #[arg(long, env = "NEOVIDE_MONITOR", value_name = "NUMBER|NAME")]
+    pub monitor: Option<String>,
+
+    /// Place the window centered on the monitor, at the mouse, cascaded from the previous window,
+    /// or at the position X,Y in pixels
+    #[arg(
+        long,
+        env = "NEOVIDE_POSITION",
+        value_name = "center|mouse|cascade|X,Y"
+    )]
+    pub position: Option<InitialPosition>,
+
+    /// The position of the Neovide window that started this one, which `--position cascade`
+    /// continues from
+    #[arg(long = "cascade-from", hide = true, value_name = "X,Y", value_parser = parse_cascade_from)]
+    pub cascade_from: Option<PhysicalPosition<i32>>,
+
+    /// Apply the overrides of the profile NAME from the config file
+    #[arg(long, env = "NEOVIDE_PROFILE", value_name = "NAME")]
+    pub profile: Option<String>,
+
+    /// Open the files in an already running Neovide started with this flag, and focus its window
+    #[arg(long = "single-instance", env = "NEOVIDE_SINGLE_INSTANCE", value_parser = FalseyValueParser::new())]
+    pub single_instance: bool,
+
+    /// Wait hidden in the background with Neovim started, until the next `neovide` shows the
+    /// window with its files, and then start another one for the time after that
+    #[arg(long)]
+    pub daemon: bool,
+
+    /// Send a command to a running Neovide started with --single-instance, and exit. The commands
+    /// are `open FILES...`, `focus`, `lua CODE` and `set-font FONT`
+    #[arg(long = "send-command", value_name = "COMMAND", num_args = 1..)]
+    pub send_command: Option<Vec<String>>,
+
+    /// Check the Neovim binary, fonts, display, rendering and config file, and print a report
+    #[arg(long)]
+    pub doctor: bool,
+
+    /// Add Neovide to the "Open With" menus of the file manager, and exit
+    #[arg(
+        long = "register-file-associations",
+        conflicts_with = "unregister_file_associations"
+    )]
+    pub register_file_associations: bool,
+
+    /// Remove Neovide from the "Open With" menus of the file manager, and exit
+    #[arg(long = "unregister-file-associations")]
+    pub unregister_file_associations: bool,
+
+    /// Record all redraw events received from Neovim to FILE, for reproducing rendering bugs
+    #[arg(
+        long = "record-session",
+        value_name = "FILE",
+        conflicts_with = "replay"
+    )]
+    pub record_session: Option<String>,
+
+    /// Play back the redraw events recorded in FILE instead of starting Neovim
+    #[arg(long, value_name = "FILE")]
+    pub replay: Option<String>,
+
+    /// Replay the redraw events recorded in FILE without Neovim or a window, and report the
+    /// rendering performance
+    #[arg(long, value_name = "FILE")]
+    pub benchmark: Option<String>,
+
     /// Force opengl on Windows
     #[cfg(target_os = "windows")]
     #[arg(long = "opengl", env = "NEOVIDE_OPENGL", action = ArgAction::SetTrue, value_parser = FalseyValueParser::new())]
     pub opengl: bool,
+
+    /// The Nvim flags found among the arguments passed to Nvim
+    #[arg(skip)]
+    pub neovim_flags: NeovimFlags,
 }
 
 // geometry, size and maximized are mutually exclusive
@@ -151,19 +330,36 @@ pub struct CmdLineSettings {
 #[group(required = false, multiple = false)]
 pub struct GeometryArgs {
     /// The initial grid size of the window [<columns>x<lines>]. Defaults to columns/lines from init.vim/lua if no value is given.
-    /// If --grid is not set then it's inferred from the window size
-    #[arg(long)]
-    pub grid: Option<Option<Dimensions>>,
+    /// If --grid is not set then it's inferred from the window size. Fractions of the monitor,
+    /// like 0.6x0.8, size the window to that part of it instead
+    #[arg(long, value_parser = parse_grid)]
+    pub grid: Option<Option<GeometrySize>>,
 
-    /// The size of the window in pixels.
+    /// The size of the window in pixels. Each side can also be a fraction of the monitor, like
+    /// 80%x100% or 0.8x1.0
     #[arg(long)]
-    pub size: Option<Dimensions>,
+    pub size: Option<GeometrySize>,
 
     /// Maximize the window on startup (not equivalent to fullscreen)
     #[arg(long, env = "NEOVIDE_MAXIMIZED", value_parser = FalseyValueParser::new())]
     pub maximized: bool,
 }
 
+/// A grid is either counted in cells or sized by the monitor, since the cells can't be measured
+/// before the font is loaded.
+fn parse_grid(s: &str) -> Result<GeometrySize, String> {
+    let grid: GeometrySize = s.parse()?;
+    match (grid.width, grid.height) {
+        (Extent::Absolute(_), Extent::Fraction(_)) | (Extent::Fraction(_), Extent::Absolute(_)) => {
+            Err(format!(
+                "Invalid grid: {s}\nThe columns and lines are either both counts or both \
+                fractions of the monitor"
+            ))
+        }
+        _ => Ok(grid),
+    }
+}
+
 impl Default for CmdLineSettings {
     fn default() -> Self {
         Self::parse_from(iter::empty::<String>())
@@ -171,6 +367,7 @@ impl Default for CmdLineSettings {
 }
 
 pub fn handle_command_line_arguments(args: Vec<String>) -> Result<()> {
+    let (args, neovim_flags) = split_neovim_flags(args);
     let mut cmdline = CmdLineSettings::try_parse_from(args)?;
 
     if cmdline._no_tabs {
@@ -189,25 +386,87 @@ pub fn handle_command_line_arguments(args: Vec<String>) -> Result<()> {
         cmdline.vsync = false;
     }
 
-    cmdline.neovim_args = cmdline
-        .tabs
+    if cmdline.wsl_distro.is_some() {
+        cmdline.wsl = true;
+    }
+
+    let passthrough_args = neovim_flags
+        .into_iter()
+        .chain(mem::take(&mut cmdline.neovim_args))
+        .collect::<Vec<_>>();
+    cmdline.neovim_flags = NeovimFlags::parse(&passthrough_args)?;
+
+    if cmdline.server.is_some()
+        && (!cmdline.files_to_open.is_empty() || !passthrough_args.is_empty())
+    {
+        return Err(CmdLineSettings::command()
+            .error(
+                ErrorKind::ArgumentConflict,
+                "`--server` connects to a running Nvim, which can't be given files or Nvim arguments",
+            )
+            .into());
+    }
+
+    // Diffs show the files side by side in a single tab
+    let tabs = cmdline.tabs && !cmdline.neovim_flags.diff;
+    cmdline.neovim_args = tabs
         .then(|| "-p".to_string())
         .into_iter()
-        .chain(mem::take(&mut cmdline.files_to_open))
-        .chain(cmdline.neovim_args)
+        .chain(
+            cmdline
+                .files_to_open
+                .iter()
+                .filter(|file| *file != "-")
+                .cloned(),
+        )
+        .chain(passthrough_args)
         .collect();
 
     SETTINGS.set::<CmdLineSettings>(&cmdline);
     Ok(())
 }
 
+impl CmdLineSettings {
+    /// True when started as `neovide -`, at the end of a pipeline.
+    pub fn reads_stdin(&self) -> bool {
+        self.files_to_open.iter().any(|file| file == "-")
+    }
+
+    /// The container engine and the image given with `--docker` or `--podman`.
+    pub fn container(&self) -> Option<(&'static str, &str)> {
+        match (&self.docker, &self.podman) {
+            (Some(image), _) => Some(("docker", image)),
+            (_, Some(image)) => Some(("podman", image)),
+            _ => None,
+        }
+    }
+}
+
+/// Finds the profile before the command line is parsed, since the config file, which contains
+/// the profiles, provides the defaults for the other arguments. `env_profile` is the value of
+/// `NEOVIDE_PROFILE`, used when the argument is not given.
+pub fn find_profile_argument(args: &[String], env_profile: Option<String>) -> Option<String> {
+    let mut args = args.iter().skip(1).take_while(|arg| *arg != "--");
+    while let Some(arg) = args.next() {
+        if arg == "--profile" {
+            return args.next().cloned();
+        }
+        if let Some(name) = arg.strip_prefix("--profile=") {
+            return Some(name.to_string());
+        }
+    }
+    env_profile
+}
+
 #[cfg(test)]
 #[allow(clippy::bool_assert_comparison)] // useful here since the explicit true/false comparison matters
 #[serial_test::serial]
 mod tests {
     use scoped_env::ScopedEnv;
+    use winit::dpi::PhysicalSize;
 
     use super::*;
+    use crate::dimensions::Dimensions;
 
     #[test]
     fn test_neovim_passthrough() {
@@ -273,10 +532,10 @@ mod tests {
 
         assert_eq!(
             SETTINGS.get::<CmdLineSettings>().geometry.grid,
-            Some(Some(Dimensions {
+            Some(Some(GeometrySize::from(Dimensions {
                 width: 42,
                 height: 24
-            })),
+            }))),
         );
     }
 
@@ -290,10 +549,10 @@ mod tests {
         handle_command_line_arguments(args).expect("Could not parse arguments");
         assert_eq!(
             SETTINGS.get::<CmdLineSettings>().geometry.grid,
-            Some(Some(Dimensions {
+            Some(Some(GeometrySize::from(Dimensions {
                 width: 42,
                 height: 24
-            })),
+            }))),
         );
     }
 
@@ -307,11 +566,48 @@ mod tests {
         handle_command_line_arguments(args).expect("Could not parse arguments");
         assert_eq!(
             SETTINGS.get::<CmdLineSettings>().geometry.size,
-            Some(Dimensions {
+            Some(GeometrySize::from(Dimensions {
                 width: 420,
                 height: 240,
+            })),
+        );
+    }
+
+    #[test]
+    fn test_relative_geometry() {
+        let args: Vec<String> = ["neovide", "--size=80%x100%"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(
+            SETTINGS.get::<CmdLineSettings>().geometry.size,
+            Some(GeometrySize {
+                width: Extent::Fraction(0.8),
+                height: Extent::Fraction(1.0),
+            }),
+        );
+
+        let args: Vec<String> = ["neovide", "--grid=0.5x0.25"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let grid = SETTINGS.get::<CmdLineSettings>().geometry.grid.flatten();
+        assert_eq!(grid.and_then(|grid| grid.absolute()), None);
+        assert_eq!(
+            grid.map(|grid| grid.resolve(PhysicalSize::new(2560, 1440))),
+            Some(Dimensions {
+                width: 1280,
+                height: 360,
             }),
         );
+
+        // Cells can't be mixed with fractions, and the fractions can't be larger than the monitor
+        for geometry in ["--grid=80x0.5", "--size=150%x100%", "--size=0x100"] {
+            let args = vec!["neovide".to_string(), geometry.to_string()];
+            assert!(handle_command_line_arguments(args).is_err(), "{geometry}");
+        }
     }
 
     #[test]
@@ -322,6 +618,220 @@ mod tests {
         assert!(SETTINGS.get::<CmdLineSettings>().log_to_file);
     }
 
+    #[test]
+    fn test_doctor() {
+        let args: Vec<String> = ["neovide", "--doctor"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert!(SETTINGS.get::<CmdLineSettings>().doctor);
+    }
+
+    #[test]
+    fn test_record_session() {
+        let args: Vec<String> = ["neovide", "--record-session", "session.msgpack"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(
+            SETTINGS.get::<CmdLineSettings>().record_session,
+            Some("session.msgpack".to_string())
+        );
+        assert_eq!(SETTINGS.get::<CmdLineSettings>().replay, None);
+    }
+
+    #[test]
+    fn test_file_associations() {
+        let args: Vec<String> = ["neovide", "--register-file-associations"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert!(settings.register_file_associations);
+        assert!(!settings.unregister_file_associations);
+
+        let args: Vec<String> = [
+            "neovide",
+            "--register-file-associations",
+            "--unregister-file-associations",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+        assert!(handle_command_line_arguments(args).is_err());
+    }
+
+    #[test]
+    fn test_record_session_conflicts_with_replay() {
+        let args: Vec<String> = [
+            "neovide",
+            "--record-session",
+            "a.msgpack",
+            "--replay",
+            "b.msgpack",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        assert!(handle_command_line_arguments(args).is_err());
+    }
+
+    #[test]
+    fn test_single_instance() {
+        let args: Vec<String> = ["neovide", "--single-instance", "./foo.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert!(settings.single_instance);
+        assert_eq!(settings.files_to_open, vec!["./foo.txt"]);
+        assert_eq!(settings.neovim_args, vec!["-p", "./foo.txt"]);
+    }
+
+    #[test]
+    fn test_daemon() {
+        let args: Vec<String> = ["neovide", "--daemon", "--single-instance"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert!(settings.daemon);
+        assert!(settings.single_instance);
+        assert!(settings.files_to_open.is_empty());
+    }
+
+    #[test]
+    fn test_cascade_from() {
+        let args: Vec<String> = [
+            "neovide",
+            "--position=cascade",
+            "--cascade-from=10,-20",
+            "file",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert_eq!(settings.cascade_from, Some(PhysicalPosition::new(10, -20)));
+        assert_eq!(settings.files_to_open, vec!["file"]);
+    }
+
+    #[test]
+    fn test_send_command() {
+        let args: Vec<String> = ["neovide", "--send-command", "open", "a.txt", "b.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert_eq!(
+            settings.send_command,
+            Some(vec![
+                "open".to_string(),
+                "a.txt".to_string(),
+                "b.txt".to_string()
+            ])
+        );
+        assert!(settings.files_to_open.is_empty());
+    }
+
+    #[test]
+    fn test_stdin() {
+        let args: Vec<String> = ["neovide", "--no-tabs", "-", "./foo.txt"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert!(settings.reads_stdin());
+        assert_eq!(settings.neovim_args, vec!["./foo.txt"]);
+    }
+
+    #[test]
+    fn test_neovim_flags_before_passthrough() {
+        let args: Vec<String> = [
+            "neovide",
+            "-d",
+            "./foo.txt",
+            "./bar.txt",
+            "+42",
+            "-q",
+            "errors.txt",
+            "--",
+            "-R",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert_eq!(settings.files_to_open, vec!["./foo.txt", "./bar.txt"]);
+        assert_eq!(
+            settings.neovim_args,
+            vec![
+                "./foo.txt",
+                "./bar.txt",
+                "-d",
+                "+42",
+                "-q",
+                "errors.txt",
+                "-R"
+            ]
+        );
+        assert_eq!(
+            settings.neovim_flags,
+            NeovimFlags {
+                diff: true,
+                read_only: true,
+                commands: vec!["42".to_string()],
+                error_file: Some("errors.txt".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_conflicting_neovim_args() {
+        for args in [
+            vec!["neovide", "--", "--embed"],
+            vec!["neovide", "--server", "localhost:6666", "./foo.txt"],
+            vec!["neovide", "--server", "localhost:6666", "-R"],
+            vec!["neovide", "./foo.txt", "-q"],
+        ] {
+            let args = args.iter().map(|s| s.to_string()).collect();
+            assert!(handle_command_line_arguments(args).is_err());
+        }
+    }
+
+    #[test]
+    fn test_benchmark() {
+        let args: Vec<String> = ["neovide", "--benchmark", "tests/data/scrolling.msgpack"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(
+            SETTINGS.get::<CmdLineSettings>().benchmark,
+            Some("tests/data/scrolling.msgpack".to_string())
+        );
+    }
+
     #[test]
     fn test_frameless_flag() {
         let args: Vec<String> = ["neovide", "--frame=full"]
@@ -342,6 +852,64 @@ mod tests {
         assert_eq!(SETTINGS.get::<CmdLineSettings>().frame, Frame::None);
     }
 
+    #[test]
+    fn test_container() {
+        let args: Vec<String> = ["neovide", "--podman", "rust:latest", "src/main.rs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(
+            SETTINGS.get::<CmdLineSettings>().container(),
+            Some(("podman", "rust:latest"))
+        );
+
+        let args: Vec<String> = ["neovide", "--docker", "rust", "--server", "localhost:6666"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        assert!(handle_command_line_arguments(args).is_err());
+    }
+
+    #[test]
+    fn test_wsl_distro() {
+        let args: Vec<String> = ["neovide", "--wsl-distro", "Ubuntu-22.04"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        assert_eq!(settings.wsl_distro.as_deref(), Some("Ubuntu-22.04"));
+        assert!(settings.wsl);
+    }
+
+    #[test]
+    fn test_monitor() {
+        let args: Vec<String> = ["neovide", "--monitor", "DELL"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert_eq!(
+            SETTINGS.get::<CmdLineSettings>().monitor.as_deref(),
+            Some("DELL")
+        );
+    }
+
+    #[test]
+    fn test_no_shell_env() {
+        let args: Vec<String> = ["neovide", "--no-shell-env"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+
+        handle_command_line_arguments(args).expect("Could not parse arguments");
+        assert!(SETTINGS.get::<CmdLineSettings>().no_shell_env);
+    }
+
     #[test]
     fn test_neovim_bin_arg() {
         let args: Vec<String> = ["neovide", "--neovim-bin", "foo"]
@@ -497,4 +1065,29 @@ mod tests {
         handle_command_line_arguments(args).expect("Could not parse arguments");
         assert_eq!(SETTINGS.get::<CmdLineSettings>().vsync, true,);
     }
+
+    #[test]
+    fn test_find_profile_argument() {
+        let args = |args: &[&str]| args.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        assert_eq!(
+            find_profile_argument(
+                &args(&["neovide", "--profile", "presentation", "file"]),
+                Some("battery".to_string())
+            ),
+            Some("presentation".to_string())
+        );
+        assert_eq!(
+            find_profile_argument(&args(&["neovide", "--profile=battery"]), None),
+            Some("battery".to_string())
+        );
+        assert_eq!(
+            find_profile_argument(&args(&["neovide", "--", "--profile", "nvim"]), None),
+            None
+        );
+        assert_eq!(
+            find_profile_argument(&args(&["neovide", "file"]), Some("battery".to_string())),
+            Some("battery".to_string())
+        );
+    }
 }
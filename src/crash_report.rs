@@ -0,0 +1,378 @@
+//! Crash reports, written when Neovide panics or is killed by a fatal signal.
+//!
+//! Besides the panic message and backtrace, the report contains the GPU backend, a snapshot of
+//! the settings and a summary of the last draw commands that were rendered, which is often
+//! enough to reproduce rendering related crashes.
+
+use std::{
+    collections::VecDeque,
+    fmt::{self, Write as _},
+    fs,
+    path::{Path, PathBuf},
+};
+
+use backtrace::Backtrace;
+use parking_lot::Mutex;
+use time::{macros::format_description, OffsetDateTime};
+
+use crate::{
+    renderer::{open_url, DrawCommand, WindowDrawCommand},
+    settings::{neovide_std_datapath, SETTINGS},
+};
+
+/// The number of draw commands kept for the report.
+const DRAW_COMMAND_HISTORY_SIZE: usize = 64;
+
+/// What the report needs to know about a draw command. Recording it never allocates, so it's
+/// cheap enough to do for every command and safe to read from a signal handler.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct DrawCommandSummary {
+    name: &'static str,
+    grid: Option<u64>,
+    value: Option<i64>,
+}
+
+impl DrawCommandSummary {
+    fn new(command: &DrawCommand) -> Self {
+        let (name, grid, value) = match command {
+            DrawCommand::CloseWindow(grid) => ("CloseWindow", Some(*grid), None),
+            DrawCommand::Window { grid_id, command } => {
+                let (name, value) = match command {
+                    WindowDrawCommand::Position { .. } => ("Position", None),
+                    WindowDrawCommand::DrawLine { row, .. } => ("DrawLine", Some(*row as i64)),
+                    WindowDrawCommand::Scroll { rows, .. } => ("Scroll", Some(*rows)),
+                    WindowDrawCommand::Clear => ("Clear", None),
+                    WindowDrawCommand::Show => ("Show", None),
+                    WindowDrawCommand::Hide => ("Hide", None),
+                    WindowDrawCommand::Close => ("Close", None),
+                    WindowDrawCommand::Viewport { .. } => ("Viewport", None),
+                    WindowDrawCommand::ViewportMargins { .. } => ("ViewportMargins", None),
+                    WindowDrawCommand::WindowHandle(handle) => {
+                        ("WindowHandle", Some(*handle as i64))
+                    }
+                };
+                (name, Some(*grid_id), value)
+            }
+            DrawCommand::UpdateCursor(_) => ("UpdateCursor", None, None),
+            DrawCommand::FontChanged(_) => ("FontChanged", None, None),
+            DrawCommand::LineSpaceChanged(space) => ("LineSpaceChanged", None, Some(*space)),
+            DrawCommand::DefaultStyleChanged(_) => ("DefaultStyleChanged", None, None),
+            DrawCommand::ModeChanged(_) => ("ModeChanged", None, None),
+            DrawCommand::UIReady => ("UIReady", None, None),
+        };
+        Self { name, grid, value }
+    }
+}
+
+impl fmt::Display for DrawCommandSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(grid) = self.grid {
+            write!(f, "grid {grid}: ")?;
+        }
+        f.write_str(self.name)?;
+        if let Some(value) = self.value {
+            write!(f, " {value}")?;
+        }
+        Ok(())
+    }
+}
+
+struct CrashContext {
+    gpu_backend: Option<String>,
+    draw_commands: VecDeque<DrawCommandSummary>,
+}
+
+impl Default for CrashContext {
+    fn default() -> Self {
+        Self {
+            gpu_backend: None,
+            draw_commands: VecDeque::with_capacity(DRAW_COMMAND_HISTORY_SIZE),
+        }
+    }
+}
+
+lazy_static! {
+    static ref CRASH_CONTEXT: Mutex<CrashContext> = Mutex::new(CrashContext::default());
+}
+
+/// Remembers a summary of the last draw commands of the batch.
+pub fn record_draw_commands(batch: &[DrawCommand]) {
+    let mut context = CRASH_CONTEXT.lock();
+    let history = &mut context.draw_commands;
+    let new_commands = &batch[batch.len().saturating_sub(DRAW_COMMAND_HISTORY_SIZE)..];
+    let overflow = (history.len() + new_commands.len()).saturating_sub(DRAW_COMMAND_HISTORY_SIZE);
+    history.drain(..overflow);
+    history.extend(new_commands.iter().map(DrawCommandSummary::new));
+}
+
+pub fn set_gpu_backend(description: String) {
+    CRASH_CONTEXT.lock().gpu_backend = Some(description);
+}
+
+pub fn gpu_backend() -> Option<String> {
+    CRASH_CONTEXT.lock().gpu_backend.clone()
+}
+
+fn generate_report(timestamp: &str, panic_message: &str, backtrace: &Backtrace) -> String {
+    let mut report = String::new();
+    let _ = writeln!(report, "Neovide {} crash report", crate_version!());
+    let _ = writeln!(report, "Time: {timestamp}");
+    let _ = writeln!(
+        report,
+        "OS: {} {}",
+        std::env::consts::OS,
+        std::env::consts::ARCH
+    );
+
+    // The panic can happen while the context is locked, so don't wait for it
+    let context = CRASH_CONTEXT.try_lock();
+    let gpu_backend = context
+        .as_ref()
+        .and_then(|context| context.gpu_backend.clone());
+    let _ = writeln!(
+        report,
+        "GPU backend: {}",
+        gpu_backend.as_deref().unwrap_or("unknown")
+    );
+
+    let _ = writeln!(report, "\n## Panic\n\n{panic_message}\n\n{backtrace:?}");
+
+    let _ = writeln!(report, "## Settings\n");
+    let arguments = std::env::args().skip(1).collect::<Vec<_>>();
+    let _ = writeln!(report, "Arguments: {}", arguments.join(" "));
+    for (name, value) in SETTINGS.snapshot() {
+        let _ = writeln!(report, "{name} = {value}");
+    }
+
+    let _ = writeln!(report, "\n## Last draw commands\n");
+    match &context {
+        Some(context) => {
+            for command in &context.draw_commands {
+                let _ = writeln!(report, "{command}");
+            }
+        }
+        None => {
+            let _ = writeln!(report, "(not available)");
+        }
+    }
+    report
+}
+
+/// Writes a crash report to the Neovide data directory and returns its path.
+pub fn write_crash_report(panic_message: &str, backtrace: &Backtrace) -> Option<PathBuf> {
+    let now: OffsetDateTime = std::time::SystemTime::now().into();
+    let timestamp = now
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second]"
+        ))
+        .ok()?;
+    let file_name = now
+        .format(format_description!(
+            "crash-[year][month][day]-[hour][minute][second].txt"
+        ))
+        .ok()?;
+
+    let report = generate_report(&timestamp, panic_message, backtrace);
+    let directory = neovide_std_datapath();
+    let path = directory.join(file_name);
+    match fs::create_dir_all(&directory).and_then(|_| fs::write(&path, report)) {
+        Ok(()) => Some(path),
+        Err(error) => {
+            eprintln!(
+                "Could not write the crash report to {}: {error}",
+                path.display()
+            );
+            None
+        }
+    }
+}
+
+/// The signals that kill Neovide without running the panic hook.
+#[cfg(not(windows))]
+const FATAL_SIGNALS: [libc::c_int; 5] = [
+    libc::SIGSEGV,
+    libc::SIGBUS,
+    libc::SIGILL,
+    libc::SIGFPE,
+    libc::SIGABRT,
+];
+
+#[cfg(not(windows))]
+static SIGNAL_REPORT_PATH: std::sync::OnceLock<std::ffi::CString> = std::sync::OnceLock::new();
+
+/// Writes a short crash report when Neovide is killed by a fatal signal, such as a segfault in
+/// the graphics driver, which the panic hook never sees. The handler can only make
+/// async-signal-safe calls, so the report is limited to the signal and the draw command summary.
+/// Windows is not covered yet.
+#[cfg(not(windows))]
+pub fn install_signal_handler() {
+    use std::os::unix::ffi::OsStringExt;
+
+    let directory = neovide_std_datapath();
+    if fs::create_dir_all(&directory).is_err() {
+        return;
+    }
+    let path = directory
+        .join("crash-signal.txt")
+        .into_os_string()
+        .into_vec();
+    let Ok(path) = std::ffi::CString::new(path) else {
+        return;
+    };
+    let _ = SIGNAL_REPORT_PATH.set(path);
+    // The handler must not run the lazy initializer
+    lazy_static::initialize(&CRASH_CONTEXT);
+
+    for signal in FATAL_SIGNALS {
+        unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction =
+                handle_fatal_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+            // Run on the alternate stack so that stack overflows are reported too, and restore
+            // the default action so that re-raising the signal terminates as before
+            action.sa_flags = libc::SA_ONSTACK | libc::SA_RESETHAND;
+            libc::sigemptyset(&mut action.sa_mask);
+            libc::sigaction(signal, &action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(not(windows))]
+extern "C" fn handle_fatal_signal(signal: libc::c_int) {
+    if let Some(path) = SIGNAL_REPORT_PATH.get() {
+        let fd = unsafe {
+            libc::open(
+                path.as_ptr(),
+                libc::O_WRONLY | libc::O_CREAT | libc::O_TRUNC,
+                0o644,
+            )
+        };
+        if fd >= 0 {
+            let mut writer = SignalSafeWriter(fd);
+            let _ = writeln!(
+                writer,
+                "Neovide {} was killed by signal {signal}",
+                crate_version!()
+            );
+            let _ = writeln!(writer, "\n## Last draw commands\n");
+            // The signal can arrive while the context is locked, so don't wait for it
+            match CRASH_CONTEXT.try_lock() {
+                Some(context) => {
+                    for command in &context.draw_commands {
+                        let _ = writeln!(writer, "{command}");
+                    }
+                }
+                None => {
+                    let _ = writeln!(writer, "(not available)");
+                }
+            }
+            unsafe { libc::close(fd) };
+        }
+    }
+    unsafe { libc::raise(signal) };
+}
+
+/// Writes directly to a file descriptor without allocating, for use in signal handlers.
+#[cfg(not(windows))]
+struct SignalSafeWriter(libc::c_int);
+
+#[cfg(not(windows))]
+impl fmt::Write for SignalSafeWriter {
+    fn write_str(&mut self, text: &str) -> fmt::Result {
+        let mut bytes = text.as_bytes();
+        while !bytes.is_empty() {
+            let written = unsafe { libc::write(self.0, bytes.as_ptr().cast(), bytes.len()) };
+            if written <= 0 {
+                return Err(fmt::Error);
+            }
+            bytes = &bytes[written as usize..];
+        }
+        Ok(())
+    }
+}
+
+/// Tells the user about the crash with a native dialog, offering to open the report.
+pub fn show_crash_dialog(report: &Path) {
+    let message = format!(
+        "Neovide crashed. A crash report was saved to {}.\n\nPlease consider attaching it to an \
+         issue at https://github.com/neovide/neovide/issues",
+        report.display()
+    );
+    if ask_to_open_report(&message) {
+        open_url(&report.to_string_lossy());
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn ask_to_open_report(message: &str) -> bool {
+    use winapi::um::winuser::{MessageBoxW, IDYES, MB_ICONERROR, MB_YESNO};
+
+    let wide = |text: &str| text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let text = wide(&format!("{message}\n\nOpen the report?"));
+    let caption = wide("Neovide");
+    let result = unsafe {
+        MessageBoxW(
+            std::ptr::null_mut(),
+            text.as_ptr(),
+            caption.as_ptr(),
+            MB_YESNO | MB_ICONERROR,
+        )
+    };
+    result == IDYES
+}
+
+#[cfg(target_os = "macos")]
+fn ask_to_open_report(message: &str) -> bool {
+    use std::process::Command;
+
+    let script = format!(
+        "display dialog {:?} with title \"Neovide\" with icon stop buttons {{\"Close\", \"Open \
+         Report\"}} default button \"Open Report\"",
+        message
+    );
+    Command::new("osascript")
+        .args(["-e", &script])
+        .output()
+        .map(|output| String::from_utf8_lossy(&output.stdout).contains("Open Report"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn ask_to_open_report(message: &str) -> bool {
+    use std::process::Command;
+
+    let zenity = Command::new("zenity")
+        .args([
+            "--question",
+            "--title=Neovide",
+            "--ok-label=Open Report",
+            "--cancel-label=Close",
+            "--text",
+            message,
+        ])
+        .status();
+    let status = match zenity {
+        Ok(status) => Ok(status),
+        Err(_) => Command::new("kdialog")
+            .args(["--title", "Neovide", "--yesno", message])
+            .status(),
+    };
+    status.map(|status| status.success()).unwrap_or(false)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_draw_command_history() {
+        record_draw_commands(&vec![DrawCommand::UIReady; DRAW_COMMAND_HISTORY_SIZE + 10]);
+        record_draw_commands(&[DrawCommand::LineSpaceChanged(3)]);
+        let context = CRASH_CONTEXT.lock();
+        assert_eq!(context.draw_commands.len(), DRAW_COMMAND_HISTORY_SIZE);
+        assert_eq!(
+            context.draw_commands.back().map(ToString::to_string),
+            Some("LineSpaceChanged 3".to_string())
+        );
+    }
+}
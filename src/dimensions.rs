@@ -63,6 +63,101 @@ impl FromStr for Dimensions {
     }
 }
 
+/// One side of a size given on the command line.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Extent {
+    Absolute(u64),
+    /// A fraction of the work area of the monitor, from `0.6` or `60%`.
+    Fraction(f64),
+}
+
+impl Extent {
+    fn resolve(self, available: u32) -> u64 {
+        match self {
+            Extent::Absolute(extent) => extent,
+            Extent::Fraction(fraction) => (available as f64 * fraction).round() as u64,
+        }
+    }
+}
+
+impl FromStr for Extent {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let fraction = if let Some(percent) = s.strip_suffix('%') {
+            percent.parse::<f64>().ok().map(|percent| percent / 100.0)
+        } else if s.contains('.') {
+            s.parse::<f64>().ok()
+        } else {
+            return match s.parse::<u64>() {
+                Ok(0) => {
+                    Err("Invalid Dimensions: Window dimensions should be greater than 0.".into())
+                }
+                Ok(extent) => Ok(Extent::Absolute(extent)),
+                Err(_) => Err(format!("Invalid dimension: {s}")),
+            };
+        };
+        match fraction {
+            Some(fraction) if fraction > 0.0 && fraction <= 1.0 => Ok(Extent::Fraction(fraction)),
+            _ => Err(format!(
+                "Invalid fraction of the monitor: {s}, expected more than 0 and at most 1 or 100%"
+            )),
+        }
+    }
+}
+
+/// A `<width>x<height>` size given on the command line, where each side can also be a fraction of
+/// the work area of the monitor, like `0.6x0.8` or `80%x100%`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GeometrySize {
+    pub width: Extent,
+    pub height: Extent,
+}
+
+impl GeometrySize {
+    /// The size, when neither side is relative to the monitor.
+    pub fn absolute(&self) -> Option<Dimensions> {
+        match (self.width, self.height) {
+            (Extent::Absolute(width), Extent::Absolute(height)) => {
+                Some(Dimensions { width, height })
+            }
+            _ => None,
+        }
+    }
+
+    /// The size, with the fractions taken of the available area.
+    pub fn resolve(&self, available: PhysicalSize<u32>) -> Dimensions {
+        Dimensions {
+            width: self.width.resolve(available.width),
+            height: self.height.resolve(available.height),
+        }
+    }
+}
+
+impl From<Dimensions> for GeometrySize {
+    fn from(Dimensions { width, height }: Dimensions) -> Self {
+        GeometrySize {
+            width: Extent::Absolute(width),
+            height: Extent::Absolute(height),
+        }
+    }
+}
+
+impl FromStr for GeometrySize {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let Some((width, height)) = s.split_once('x') else {
+            return Err(format!(
+                "Invalid geometry: {s}\nValid format: <width>x<height>, where each side can also \
+                be a fraction of the monitor like 0.6 or 60%"
+            ));
+        };
+        Ok(GeometrySize {
+            width: width.parse()?,
+            height: height.parse()?,
+        })
+    }
+}
+
 impl Display for Dimensions {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}x{}", self.width, self.height)
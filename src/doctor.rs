@@ -0,0 +1,351 @@
+//! Startup diagnostics, printed with `--doctor`.
+
+use std::{
+    env,
+    fmt::{self, Display},
+    panic::{self, AssertUnwindSafe},
+};
+
+use serde::Deserialize;
+use skia_safe::FontMgr;
+use winit::{event_loop::EventLoop, window::WindowBuilder};
+
+use crate::{
+    bridge::{find_nvim_binary, run_nvim_lua, NEOVIM_REQUIRED_VERSION},
+    cmd_line::CmdLineSettings,
+    crash_report::gpu_backend,
+    renderer::{
+        build_window_config, create_skia_renderer, fonts::font_options::FontOptions, SurfaceOptions,
+    },
+    settings::{config_path, Config, SETTINGS},
+    window::UserEvent,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Status {
+    Ok,
+    Warning,
+    Error,
+}
+
+struct Check {
+    status: Status,
+    message: String,
+}
+
+struct Section {
+    title: &'static str,
+    checks: Vec<Check>,
+}
+
+impl Section {
+    fn new(title: &'static str) -> Self {
+        Self {
+            title,
+            checks: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, status: Status, message: impl Into<String>) {
+        self.checks.push(Check {
+            status,
+            message: message.into(),
+        });
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{}", self.title)?;
+        for check in &self.checks {
+            let status = match check.status {
+                Status::Ok => "ok",
+                Status::Warning => "warning",
+                Status::Error => "error",
+            };
+            // Indent continuation lines, so that multi-line errors stay readable
+            let message = check.message.trim_end().replace('\n', "\n    ");
+            writeln!(f, "  [{status}] {message}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns true if the dotted `version` is at least `required`.
+fn version_at_least(version: &str, required: &str) -> bool {
+    let parse = |version: &str| {
+        version
+            .split(|c: char| !c.is_ascii_digit())
+            .take(3)
+            .map(|part| part.parse::<u64>().unwrap_or(0))
+            .collect::<Vec<_>>()
+    };
+    parse(version) >= parse(required)
+}
+
+/// What nvim reports about itself, printed as JSON after anything the configuration prints.
+#[derive(Debug, Deserialize, PartialEq)]
+struct NeovimInfo {
+    version: String,
+    guifont: String,
+}
+
+/// Finds the info printed by nvim in its output, which can contain other lines printed by the
+/// user configuration.
+fn parse_neovim_info(output: &str) -> Option<NeovimInfo> {
+    output
+        .lines()
+        .rev()
+        .find_map(|line| serde_json::from_str(line.trim()).ok())
+}
+
+/// Checks the nvim binary, and returns the guifont set by the user configuration.
+fn check_neovim(section: &mut Section) -> Option<String> {
+    let (bin, args) = match find_nvim_binary() {
+        Ok(binary) => binary,
+        Err(error) => {
+            section.add(Status::Error, error.to_string());
+            return None;
+        }
+    };
+    section.add(Status::Ok, format!("Binary: {bin} {}", args.join(" ")));
+
+    let output = match run_nvim_lua(
+        &bin,
+        &args,
+        concat!(
+            "io.stdout:write('\\n', vim.json.encode(",
+            "{ version = tostring(vim.version()), guifont = vim.o.guifont }), '\\n')"
+        ),
+    ) {
+        Ok(output) => output,
+        Err(error) => {
+            section.add(
+                Status::Error,
+                format!("Could not start nvim with your configuration: {error}"),
+            );
+            return None;
+        }
+    };
+    let Some(NeovimInfo { version, guifont }) = parse_neovim_info(&output) else {
+        section.add(
+            Status::Error,
+            format!("Could not read the version from the output of nvim:\n{output}"),
+        );
+        return None;
+    };
+    if version_at_least(&version, NEOVIM_REQUIRED_VERSION) {
+        section.add(Status::Ok, format!("Version: {version}"));
+    } else {
+        section.add(
+            Status::Error,
+            format!("Version: {version}, but {NEOVIM_REQUIRED_VERSION} or newer is required"),
+        );
+    }
+    Some(guifont)
+}
+
+fn check_rendering(section: &mut Section, event_loop: &EventLoop<UserEvent>) {
+    let settings = SETTINGS.get::<CmdLineSettings>();
+    // Creating the window and the GPU context panics when it fails, which is reported here
+    // instead of as a crash
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        let window_builder = WindowBuilder::new()
+            .with_title("Neovide")
+            .with_visible(false);
+        let window = build_window_config(window_builder, event_loop);
+        create_skia_renderer(
+            window,
+            settings.srgb,
+            settings.vsync,
+            &SurfaceOptions::default(),
+        );
+    }));
+    panic::set_hook(hook);
+    if let Err(payload) = result {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|message| message.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown error".to_string());
+        section.add(
+            Status::Error,
+            format!("Could not create the window and the GPU context: {message}"),
+        );
+        return;
+    }
+    match gpu_backend() {
+        Some(backend) => section.add(Status::Ok, backend),
+        None => section.add(Status::Warning, "Could not determine the GPU backend"),
+    }
+    section.add(
+        Status::Ok,
+        format!(
+            "sRGB: {}, VSync: {}",
+            if settings.srgb { "on" } else { "off" },
+            if settings.vsync { "on" } else { "off" }
+        ),
+    );
+}
+
+#[cfg(not(any(target_os = "windows", target_os = "macos")))]
+fn check_display(section: &mut Section, event_loop: &EventLoop<UserEvent>) {
+    use winit::platform::wayland::EventLoopWindowTargetExtWayland;
+
+    let variable = |name: &str| env::var(name).unwrap_or_else(|_| "unset".to_string());
+    let backend = if event_loop.is_wayland() {
+        "Wayland"
+    } else {
+        "X11"
+    };
+    section.add(Status::Ok, format!("Using {backend}"));
+    section.add(
+        Status::Ok,
+        format!(
+            "XDG_SESSION_TYPE={}, WAYLAND_DISPLAY={}, DISPLAY={}",
+            variable("XDG_SESSION_TYPE"),
+            variable("WAYLAND_DISPLAY"),
+            variable("DISPLAY")
+        ),
+    );
+}
+
+#[cfg(any(target_os = "windows", target_os = "macos"))]
+fn check_display(section: &mut Section, _event_loop: &EventLoop<UserEvent>) {
+    section.add(Status::Ok, env::consts::OS);
+}
+
+fn check_fonts(section: &mut Section, guifont: Option<&str>, config: Option<&Config>) {
+    let options = match (guifont, config.and_then(|config| config.font.clone())) {
+        (Some(guifont), _) if !guifont.is_empty() => {
+            section.add(Status::Ok, format!("guifont: {guifont}"));
+            match FontOptions::parse(guifont) {
+                Ok(options) => options,
+                Err(error) => {
+                    section.add(Status::Error, format!("Invalid guifont: {error}"));
+                    return;
+                }
+            }
+        }
+        (_, Some(font)) => {
+            section.add(Status::Ok, "Using the font from the config file");
+            font.into()
+        }
+        _ => {
+            section.add(Status::Ok, "No guifont set, using the default font");
+            return;
+        }
+    };
+
+    let font_manager = FontMgr::new();
+    let mut fonts = options.possible_fonts();
+    fonts.dedup();
+    for font in fonts {
+        let (family, style) = font.as_family_and_font_style();
+        let name = match &font.style {
+            Some(style) => format!("{family} {style}"),
+            None => family.to_string(),
+        };
+        if font_manager.match_family_style(family, style).is_some() {
+            section.add(Status::Ok, format!("Found {name}"));
+        } else {
+            section.add(
+                Status::Warning,
+                format!("{name} was not found, a fallback font will be used"),
+            );
+        }
+    }
+}
+
+fn check_config(section: &mut Section) -> Option<Config> {
+    let path = config_path();
+    match Config::load_from_path(&path) {
+        Ok(config) => {
+            section.add(Status::Ok, format!("{} is valid", path.display()));
+            Some(config)
+        }
+        Err(None) => {
+            section.add(Status::Ok, format!("{} does not exist", path.display()));
+            None
+        }
+        Err(Some(error)) => {
+            section.add(Status::Error, error);
+            None
+        }
+    }
+}
+
+/// Runs all checks and prints the report. Returns false if any of the checks failed.
+pub fn run_doctor(event_loop: &EventLoop<UserEvent>) -> bool {
+    let mut config_section = Section::new("Config file");
+    let config = check_config(&mut config_section);
+
+    let mut neovim_section = Section::new("Neovim");
+    let guifont = check_neovim(&mut neovim_section);
+
+    let mut font_section = Section::new("Fonts");
+    check_fonts(&mut font_section, guifont.as_deref(), config.as_ref());
+
+    let mut display_section = Section::new("Display");
+    check_display(&mut display_section, event_loop);
+
+    let mut rendering_section = Section::new("Rendering");
+    check_rendering(&mut rendering_section, event_loop);
+
+    let sections = [
+        neovim_section,
+        config_section,
+        font_section,
+        display_section,
+        rendering_section,
+    ];
+    println!("Neovide {}\n", crate_version!());
+    for section in &sections {
+        println!("{section}");
+    }
+    sections
+        .iter()
+        .flat_map(|section| &section.checks)
+        .all(|check| check.status != Status::Error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_version_at_least() {
+        assert!(version_at_least("0.10.0", "0.9.2"));
+        assert!(version_at_least("0.9.2", "0.9.2"));
+        assert!(version_at_least("0.10.0-dev-2735+g7b1ef48", "0.9.2"));
+        assert!(!version_at_least("0.9.1", "0.9.2"));
+        assert!(!version_at_least("", "0.9.2"));
+    }
+
+    #[test]
+    fn test_parse_neovim_info() {
+        let output =
+            "printed by init.lua\n\n{\"version\":\"0.10.0\",\"guifont\":\"Fira Code:h14\"}\n";
+        assert_eq!(
+            parse_neovim_info(output),
+            Some(NeovimInfo {
+                version: "0.10.0".to_string(),
+                guifont: "Fira Code:h14".to_string(),
+            })
+        );
+        assert_eq!(parse_neovim_info("E5108: Error executing lua\n"), None);
+    }
+
+    #[test]
+    fn test_section_display() {
+        let mut section = Section::new("Neovim");
+        section.add(Status::Ok, "Version: 0.10.0");
+        section.add(Status::Error, "first\nsecond\n");
+        assert_eq!(
+            section.to_string(),
+            "Neovim\n  [ok] Version: 0.10.0\n  [error] first\n    second\n"
+        );
+    }
+}
@@ -62,7 +62,7 @@ impl Cursor {
             blinkoff: None,
             enabled: true,
             double_width: false,
-            grid_cell: (" ".to_string(), None),
+            grid_cell: crate::default_cell!(),
         }
     }
 
@@ -1,13 +1,16 @@
 use std::sync::Arc;
 
+use smol_str::SmolStr;
+
 use crate::{editor::style::Style, utils::RingBuffer};
 
-pub type GridCell = (String, Option<Arc<Style>>);
+/// The text of a cell is stored inline, so that the cells don't need their own allocations.
+pub type GridCell = (SmolStr, Option<Arc<Style>>);
 
 #[macro_export]
 macro_rules! default_cell {
     () => {
-        (" ".to_owned(), None)
+        (smol_str::SmolStr::new_inline(" "), None)
     };
 }
 
@@ -179,7 +182,7 @@ mod tests {
     fn set_grid_line_to_chars(grid: &mut CharacterGrid, row: usize, value: &str) {
         assert_eq!(value.len(), grid.width);
         for (col_nr, chr) in value.chars().enumerate() {
-            *grid.get_cell_mut(col_nr, row).unwrap() = (chr.to_string(), None);
+            *grid.get_cell_mut(col_nr, row).unwrap() = (chr.to_string().into(), None);
         }
     }
 
@@ -192,8 +195,7 @@ mod tests {
     }
 
     fn assert_grid_cell_contents(grid: &CharacterGrid, x: usize, y: usize, char: &str) {
-        let char = char.to_string();
-        let value = (char, None);
+        let value = (SmolStr::new(char), None);
         let cell = Some(&value);
         assert_eq!(grid.get_cell(x, y), cell);
     }
@@ -207,7 +209,7 @@ mod tests {
         let mut grid = CharacterGrid::new((num_columns, num_lines));
         for (row_nr, line) in lines.iter().enumerate() {
             for (col_nr, chr) in line.chars().enumerate() {
-                *grid.get_cell_mut(col_nr, row_nr).unwrap() = (chr.to_string(), None);
+                *grid.get_cell_mut(col_nr, row_nr).unwrap() = (chr.to_string().into(), None);
             }
         }
         grid
@@ -230,11 +232,11 @@ mod tests {
         let mut character_grid = CharacterGrid::new(context.size);
 
         character_grid.lines[context.y].characters[context.x] = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
         let result = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
 
@@ -251,18 +253,18 @@ mod tests {
         let mut character_grid = CharacterGrid::new(context.size);
 
         character_grid.lines[context.y].characters[context.x] = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
         let result = (
-            "bar".to_string(),
+            SmolStr::new("bar"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
 
         // RUN FUNCTION
         let cell = character_grid.get_cell_mut(context.x, context.y).unwrap();
         *cell = (
-            "bar".to_string(),
+            SmolStr::new("bar"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
 
@@ -276,7 +278,7 @@ mod tests {
     fn set_all_characters_sets_all_cells_to_given_character() {
         let context = Context::new();
         let grid_cell = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
         let mut character_grid = CharacterGrid::new(context.size);
@@ -292,7 +294,7 @@ mod tests {
         let mut character_grid = CharacterGrid::new(context.size);
 
         let grid_cell = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors.clone()))),
         );
         character_grid.set_all_characters(grid_cell.clone());
@@ -315,7 +317,7 @@ mod tests {
         );
 
         let grid_cell = (
-            "foo".to_string(),
+            SmolStr::new("foo"),
             Some(Arc::new(Style::new(context.none_colors))),
         );
         character_grid.set_all_characters(grid_cell.clone());
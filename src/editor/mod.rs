@@ -18,7 +18,7 @@ use winit::window::Theme;
 use skia_safe::Color4f;
 
 use crate::{
-    bridge::{GuiOption, NeovimHandler, RedrawEvent, WindowAnchor},
+    bridge::{GuiOption, NeovimHandler, RedrawEvent, RedrawRecorder, WindowAnchor},
     profiling::{tracy_named_frame, tracy_zone},
     renderer::DrawCommand,
     window::{UserEvent, WindowCommand},
@@ -29,7 +29,7 @@ use crate::{cmd_line::CmdLineSettings, frame::Frame, settings::SETTINGS};
 
 pub use cursor::{Cursor, CursorMode, CursorShape};
 pub use draw_command_batcher::DrawCommandBatcher;
-pub use style::{Colors, HighlightInfo, HighlightKind, Style, UnderlineStyle};
+pub use style::{Colors, HighlightInfo, HighlightKind, Style, StyleInterner, UnderlineStyle};
 pub use window::*;
 
 const MODE_CMDLINE: u64 = 4;
@@ -64,6 +64,7 @@ pub struct Editor {
     pub windows: HashMap<u64, Window>,
     pub cursor: Cursor,
     pub defined_styles: HashMap<u64, Arc<Style>>,
+    style_interner: StyleInterner,
     pub mode_list: Vec<CursorMode>,
     pub draw_command_batcher: Rc<DrawCommandBatcher>,
     pub current_mode_index: Option<u64>,
@@ -77,6 +78,7 @@ impl Editor {
             windows: HashMap::new(),
             cursor: Cursor::new(),
             defined_styles: HashMap::new(),
+            style_interner: StyleInterner::default(),
             mode_list: Vec::new(),
             draw_command_batcher: Rc::new(DrawCommandBatcher::new()),
             current_mode_index: None,
@@ -132,6 +134,16 @@ impl Editor {
                     .event_loop_proxy
                     .send_event(WindowCommand::SetMouseEnabled(false).into());
             }
+            RedrawEvent::Bell => {
+                tracy_zone!("EditorBell");
+                let _ = self.event_loop_proxy.send_event(WindowCommand::Bell.into());
+            }
+            RedrawEvent::VisualBell => {
+                tracy_zone!("EditorVisualBell");
+                let _ = self
+                    .event_loop_proxy
+                    .send_event(WindowCommand::VisualBell.into());
+            }
             RedrawEvent::BusyStart => {
                 tracy_zone!("EditorBusyStart");
                 trace!("Cursor off");
@@ -171,7 +183,8 @@ impl Editor {
             }
             RedrawEvent::HighlightAttributesDefine { id, style } => {
                 tracy_zone!("EditorHighlightAttributesDefine");
-                self.defined_styles.insert(id, Arc::new(style));
+                let style = self.style_interner.intern(style);
+                self.defined_styles.insert(id, style);
             }
             RedrawEvent::CursorGoto {
                 grid,
@@ -231,16 +244,19 @@ impl Editor {
             }
             RedrawEvent::WindowPosition {
                 grid,
+                window,
                 start_row,
                 start_column,
                 width,
                 height,
             } => {
                 tracy_zone!("EditorWindowPosition");
-                self.set_window_position(grid, start_column, start_row, width, height)
+                self.set_window_position(grid, start_column, start_row, width, height);
+                self.set_window_handle(grid, window);
             }
             RedrawEvent::WindowFloatPosition {
                 grid,
+                window,
                 anchor,
                 anchor_grid,
                 anchor_column: anchor_left,
@@ -256,7 +272,8 @@ impl Editor {
                     anchor_left,
                     anchor_top,
                     sort_order,
-                )
+                );
+                self.set_window_handle(grid, window);
             }
             RedrawEvent::WindowHide { grid } => {
                 tracy_zone!("EditorWindowHide");
@@ -280,13 +297,14 @@ impl Editor {
             }
             RedrawEvent::WindowViewport {
                 grid,
+                top_line,
                 // Don't send viewport events if they don't have a scroll delta
                 scroll_delta: Some(scroll_delta),
                 ..
             } => {
                 tracy_zone!("EditorWindowViewport");
                 self.set_ui_ready();
-                self.send_updated_viewport(grid, scroll_delta)
+                self.send_updated_viewport(grid, top_line, scroll_delta)
             }
             RedrawEvent::WindowViewportMargins {
                 grid,
@@ -371,6 +389,12 @@ impl Editor {
         }
     }
 
+    fn set_window_handle(&mut self, grid: u64, window_handle: u64) {
+        if let Some(window) = self.windows.get_mut(&grid) {
+            window.set_window_handle(window_handle);
+        }
+    }
+
     fn set_window_float_position(
         &mut self,
         grid: u64,
@@ -519,7 +543,7 @@ impl Editor {
             self.cursor.double_width = double_width;
         } else {
             self.cursor.double_width = false;
-            self.cursor.grid_cell = (" ".to_string(), None);
+            self.cursor.grid_cell = crate::default_cell!();
         }
         self.draw_command_batcher
             .queue(DrawCommand::UpdateCursor(self.cursor.clone()));
@@ -551,9 +575,9 @@ impl Editor {
         }
     }
 
-    fn send_updated_viewport(&mut self, grid: u64, scroll_delta: f64) {
+    fn send_updated_viewport(&mut self, grid: u64, top_line: f64, scroll_delta: f64) {
         if let Some(window) = self.windows.get_mut(&grid) {
-            window.update_viewport(scroll_delta);
+            window.update_viewport(top_line, scroll_delta);
         } else {
             trace!("viewport event received before window initialized");
         }
@@ -588,9 +612,12 @@ impl Editor {
     }
 }
 
-pub fn start_editor(event_loop_proxy: EventLoopProxy<UserEvent>) -> NeovimHandler {
+pub fn start_editor(
+    event_loop_proxy: EventLoopProxy<UserEvent>,
+    recorder: Option<RedrawRecorder>,
+) -> NeovimHandler {
     let (sender, mut receiver) = unbounded_channel();
-    let handler = NeovimHandler::new(sender, event_loop_proxy.clone());
+    let handler = NeovimHandler::new(sender, event_loop_proxy.clone(), recorder);
     thread::spawn(move || {
         let mut editor = Editor::new(event_loop_proxy);
 
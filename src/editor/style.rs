@@ -1,5 +1,32 @@
+use std::{
+    collections::HashSet,
+    hash::{Hash, Hasher},
+    sync::Arc,
+};
+
 use skia_safe::Color4f;
 
+/// The contrast of the text in the high contrast mode, which is the enhanced level of WCAG.
+const HIGH_CONTRAST_RATIO: f32 = 7.0;
+
+/// The relative luminance of WCAG, which is how bright the color appears.
+fn relative_luminance(color: Color4f) -> f32 {
+    let linear = |channel: f32| {
+        if channel <= 0.03928 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    0.2126 * linear(color.r) + 0.7152 * linear(color.g) + 0.0722 * linear(color.b)
+}
+
+/// The contrast ratio of WCAG, from 1 for the same colors to 21 for black and white.
+fn contrast_ratio(a: Color4f, b: Color4f) -> f32 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
 #[derive(new, Debug, Clone, PartialEq)]
 pub struct Colors {
     pub foreground: Option<Color4f>,
@@ -7,7 +34,20 @@ pub struct Colors {
     pub special: Option<Color4f>,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+// The colors come from Neovim as integers, so they are never NaN
+impl Eq for Colors {}
+
+impl Hash for Colors {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for color in [self.foreground, self.background, self.special] {
+            color
+                .map(|color| [color.r, color.g, color.b, color.a].map(f32::to_bits))
+                .hash(state);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum UnderlineStyle {
     Underline,
     UnderDouble,
@@ -16,7 +56,7 @@ pub enum UnderlineStyle {
     UnderCurl,
 }
 
-#[derive(new, Debug, Clone, PartialEq)]
+#[derive(new, Debug, Clone, PartialEq, Eq, Hash)]
 pub struct Style {
     pub colors: Colors,
     #[new(default)]
@@ -32,17 +72,19 @@ pub struct Style {
     #[new(default)]
     pub underline: Option<UnderlineStyle>,
     #[new(default)]
+    pub url: Option<String>,
+    #[new(default)]
     pub infos: Vec<HighlightInfo>,
 }
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
 pub enum HighlightKind {
     Ui,
     Syntax,
     Terminal,
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct HighlightInfo {
     pub kind: HighlightKind,
     pub ui_name: String,
@@ -80,6 +122,54 @@ impl Style {
             .special
             .unwrap_or_else(|| self.foreground(default_colors))
     }
+
+    /// The foreground, replaced with black or white when it doesn't stand out enough from the
+    /// background, for the high contrast mode.
+    pub fn high_contrast_foreground(&self, default_colors: &Colors) -> Color4f {
+        let foreground = self.foreground(default_colors);
+        let background = self.background(default_colors);
+        if contrast_ratio(foreground, background) >= HIGH_CONTRAST_RATIO {
+            return foreground;
+        }
+        let black = Color4f::new(0.0, 0.0, 0.0, 1.0);
+        let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+        if contrast_ratio(white, background) >= contrast_ratio(black, background) {
+            white
+        } else {
+            black
+        }
+    }
+
+    /// The opacity of the background, from `winblend` and `pumblend`.
+    pub fn background_alpha(&self) -> f32 {
+        1.0 - self.blend.min(100) as f32 / 100.0
+    }
+
+    /// The opacity of the text. Like the Neovim compositor used by terminals, the text is blended
+    /// half as much as the background, so that it stays readable.
+    pub fn foreground_alpha(&self) -> f32 {
+        1.0 - self.blend.min(100) as f32 / 200.0
+    }
+}
+
+/// Shares one allocation between the highlight ids with the same attributes. Neovim defines a new
+/// id for every combination of highlight groups, most of which look the same, so the grid cells
+/// and line fragments only point to the few distinct styles, and comparing them is mostly a
+/// pointer comparison.
+#[derive(Default)]
+pub struct StyleInterner {
+    styles: HashSet<Arc<Style>>,
+}
+
+impl StyleInterner {
+    pub fn intern(&mut self, style: Style) -> Arc<Style> {
+        if let Some(existing) = self.styles.get(&style) {
+            return existing.clone();
+        }
+        let style = Arc::new(style);
+        self.styles.insert(style.clone());
+        style
+    }
 }
 
 #[cfg(test)]
@@ -171,4 +261,52 @@ mod tests {
             style.foreground(&DEFAULT_COLORS),
         );
     }
+
+    #[test]
+    fn test_blend_alpha() {
+        let mut style = Style::new(COLORS);
+        assert_eq!(style.background_alpha(), 1.0);
+        assert_eq!(style.foreground_alpha(), 1.0);
+
+        style.blend = 50;
+        assert_eq!(style.background_alpha(), 0.5);
+        assert_eq!(style.foreground_alpha(), 0.75);
+    }
+
+    #[test]
+    fn test_high_contrast_foreground() {
+        let gray = Color4f::new(0.5, 0.5, 0.5, 1.0);
+        let dark_gray = Color4f::new(0.2, 0.2, 0.2, 1.0);
+        let mut style = Style::new(Colors::new(Some(gray), Some(dark_gray), None));
+        assert_eq!(
+            style.high_contrast_foreground(&DEFAULT_COLORS),
+            Color4f::new(1.0, 1.0, 1.0, 1.0)
+        );
+
+        style.colors.background = Some(Color4f::new(0.9, 0.9, 0.9, 1.0));
+        assert_eq!(
+            style.high_contrast_foreground(&DEFAULT_COLORS),
+            Color4f::new(0.0, 0.0, 0.0, 1.0)
+        );
+
+        style.colors.foreground = Some(Color4f::new(0.0, 0.0, 0.5, 1.0));
+        assert_eq!(
+            style.high_contrast_foreground(&DEFAULT_COLORS),
+            Color4f::new(0.0, 0.0, 0.5, 1.0)
+        );
+    }
+
+    #[test]
+    fn test_intern_shares_equal_styles() {
+        let mut interner = StyleInterner::default();
+        let mut bold = Style::new(COLORS);
+        bold.bold = true;
+
+        let first = interner.intern(bold.clone());
+        let second = interner.intern(bold);
+        let plain = interner.intern(Style::new(COLORS));
+        assert!(Arc::ptr_eq(&first, &second));
+        assert!(!Arc::ptr_eq(&first, &plain));
+        assert_eq!(interner.styles.len(), 2);
+    }
 }
@@ -1,6 +1,7 @@
 use std::{collections::HashMap, rc::Rc, sync::Arc};
 
 use log::warn;
+use smol_str::SmolStr;
 use unicode_segmentation::UnicodeSegmentation;
 
 use crate::{
@@ -22,6 +23,7 @@ pub struct Window {
 
     pub anchor_info: Option<AnchorInfo>,
     grid_position: (f64, f64),
+    window_handle: Option<u64>,
 
     draw_command_batcher: Rc<DrawCommandBatcher>,
 }
@@ -41,6 +43,7 @@ impl Window {
             window_type,
             anchor_info,
             grid_position,
+            window_handle: None,
             draw_command_batcher,
         };
         window.send_updated_position();
@@ -67,11 +70,11 @@ impl Window {
         &self,
         window_left: u64,
         window_top: u64,
-    ) -> (String, Option<Arc<Style>>, bool) {
+    ) -> (SmolStr, Option<Arc<Style>>, bool) {
         let grid_cell = self
             .grid
             .get_cell(window_left as usize, window_top as usize)
-            .map_or((" ".to_string(), None), |(character, style)| {
+            .map_or(crate::default_cell!(), |(character, style)| {
                 (character.clone(), style.clone())
             });
 
@@ -143,13 +146,13 @@ impl Window {
         // Insert the contents of the cell into the grid.
         if text.is_empty() {
             if let Some(cell) = self.grid.get_cell_mut(*column_pos, row_index) {
-                *cell = (text, style.clone());
+                *cell = (text.into(), style.clone());
             }
             *column_pos += 1;
         } else {
             for character in text.graphemes(true) {
                 if let Some(cell) = self.grid.get_cell_mut(*column_pos, row_index) {
-                    *cell = (character.to_string(), style.clone());
+                    *cell = (character.into(), style.clone());
                 }
                 *column_pos += 1;
             }
@@ -185,7 +188,7 @@ impl Window {
         }
 
         let line_fragment = LineFragment {
-            text,
+            text: text.into(),
             window_left: start as u64,
             width: width as u64,
             style: style.clone(),
@@ -310,6 +313,13 @@ impl Window {
         }
     }
 
+    pub fn set_window_handle(&mut self, window_handle: u64) {
+        if self.window_handle != Some(window_handle) {
+            self.window_handle = Some(window_handle);
+            self.send_command(WindowDrawCommand::WindowHandle(window_handle));
+        }
+    }
+
     pub fn hide(&self) {
         self.send_command(WindowDrawCommand::Hide);
     }
@@ -322,8 +332,11 @@ impl Window {
         self.send_command(WindowDrawCommand::Close);
     }
 
-    pub fn update_viewport(&self, scroll_delta: f64) {
-        self.send_command(WindowDrawCommand::Viewport { scroll_delta });
+    pub fn update_viewport(&self, top_line: f64, scroll_delta: f64) {
+        self.send_command(WindowDrawCommand::Viewport {
+            top_line,
+            scroll_delta,
+        });
     }
 
     pub fn update_viewport_margins(&self, top: u64, bottom: u64, left: u64, right: u64) {
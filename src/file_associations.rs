@@ -0,0 +1,180 @@
+//! Registers Neovide with the desktop, so that it's listed in the "Open With" menus of the file
+//! managers, with `--register-file-associations`.
+
+#[cfg(not(windows))]
+use std::process::Command;
+use std::{env, path::PathBuf};
+
+use anyhow::{Context, Result};
+
+#[cfg(windows)]
+use crate::windows_utils::{register_open_with, unregister_open_with};
+
+/// The path of the running binary, which the registered entries launch.
+fn current_exe() -> Result<PathBuf> {
+    env::current_exe().context("Could not find the path of the Neovide binary")
+}
+
+/// The desktop entry installed for the current user, which overrides a system wide one.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn desktop_entry_path() -> Result<PathBuf> {
+    let data_dir = dirs::data_dir().context("Could not find the data directory")?;
+    Ok(data_dir.join("applications").join("neovide.desktop"))
+}
+
+/// Quotes an argument of the `Exec` key, following the Desktop Entry specification. Inside the
+/// quotes `"`, `` ` ``, `$` and `\` are escaped with a backslash, and since the backslash is also
+/// the escape character of string values, which are unescaped first, a literal backslash is
+/// written as four. A literal `%` is written as `%%`, since it starts the field codes.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn quote_exec_argument(argument: &str) -> String {
+    let mut quoted = String::from('"');
+    for character in argument.chars() {
+        match character {
+            '"' | '`' | '$' => {
+                quoted.push('\\');
+                quoted.push(character);
+            }
+            '\\' => quoted.push_str("\\\\\\\\"),
+            '%' => quoted.push_str("%%"),
+            _ => quoted.push(character),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// The bundled desktop entry, launching the binary at `exe`. `%F` passes all selected files to a
+/// single activation.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn desktop_entry(exe: &str) -> String {
+    include_str!("../assets/neovide.desktop")
+        .lines()
+        .map(|line| {
+            if line.starts_with("Exec=") {
+                format!("Exec={} %F", quote_exec_argument(exe))
+            } else {
+                line.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+        + "\n"
+}
+
+/// Rebuilds the MIME type cache, so that the new entry is offered for its types immediately.
+/// Desktops without the tool read the entries directly, so failures are ignored.
+#[cfg(not(any(windows, target_os = "macos")))]
+fn update_desktop_database(entry: &std::path::Path) {
+    if let Some(directory) = entry.parent() {
+        let _ = Command::new("update-desktop-database")
+            .arg(directory)
+            .output();
+    }
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn register() -> Result<String> {
+    let exe = current_exe()?;
+    let path = desktop_entry_path()?;
+    if let Some(directory) = path.parent() {
+        std::fs::create_dir_all(directory)
+            .with_context(|| format!("Could not create {directory:?}"))?;
+    }
+    std::fs::write(&path, desktop_entry(&exe.to_string_lossy()))
+        .with_context(|| format!("Could not write {path:?}"))?;
+    update_desktop_database(&path);
+    Ok(path.display().to_string())
+}
+
+#[cfg(not(any(windows, target_os = "macos")))]
+fn unregister() -> Result<String> {
+    let path = desktop_entry_path()?;
+    if path.exists() {
+        std::fs::remove_file(&path).with_context(|| format!("Could not remove {path:?}"))?;
+        update_desktop_database(&path);
+    }
+    Ok(path.display().to_string())
+}
+
+/// Launch Services keeps the document types declared by the Info.plist of the bundle.
+#[cfg(target_os = "macos")]
+const LSREGISTER: &str = "/System/Library/Frameworks/CoreServices.framework/Frameworks/LaunchServices.framework/Support/lsregister";
+
+/// The `Neovide.app` bundle containing the binary.
+#[cfg(target_os = "macos")]
+fn app_bundle() -> Result<PathBuf> {
+    let exe = current_exe()?;
+    exe.ancestors()
+        .find(|path| path.extension().is_some_and(|extension| extension == "app"))
+        .map(PathBuf::from)
+        .with_context(|| format!("{exe:?} is not inside an application bundle"))
+}
+
+#[cfg(target_os = "macos")]
+fn lsregister(flag: &str) -> Result<String> {
+    let bundle = app_bundle()?;
+    let status = Command::new(LSREGISTER)
+        .arg(flag)
+        .arg(&bundle)
+        .status()
+        .context("Could not run lsregister")?;
+    anyhow::ensure!(status.success(), "lsregister failed with {status}");
+    Ok(bundle.display().to_string())
+}
+
+#[cfg(target_os = "macos")]
+fn register() -> Result<String> {
+    lsregister("-f")
+}
+
+#[cfg(target_os = "macos")]
+fn unregister() -> Result<String> {
+    lsregister("-u")
+}
+
+#[cfg(windows)]
+fn register() -> Result<String> {
+    let exe = current_exe()?;
+    register_open_with(&exe)
+}
+
+#[cfg(windows)]
+fn unregister() -> Result<String> {
+    unregister_open_with()
+}
+
+/// Registers or unregisters Neovide, and prints where.
+pub fn update_file_associations(register_associations: bool) -> Result<()> {
+    if register_associations {
+        let location = register()?;
+        println!("Registered Neovide in {location}");
+    } else {
+        let location = unregister()?;
+        println!("Unregistered Neovide from {location}");
+    }
+    Ok(())
+}
+
+#[cfg(all(test, not(any(windows, target_os = "macos"))))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_desktop_entry_launches_current_binary() {
+        let entry = desktop_entry("/opt/neovide/neovide");
+        assert!(entry
+            .lines()
+            .any(|line| line == "Exec=\"/opt/neovide/neovide\" %F"));
+        assert!(entry.lines().any(|line| line.starts_with("MimeType=")));
+        assert!(entry.ends_with('\n'));
+    }
+
+    #[test]
+    fn test_exec_argument_is_quoted() {
+        assert_eq!(
+            quote_exec_argument("/home/a b/$HOME/\"x\"/`y`/c\\d/100%"),
+            r#""/home/a b/\$HOME/\"x\"/\`y\`/c\\\\d/100%%""#
+        );
+    }
+}
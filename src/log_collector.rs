@@ -0,0 +1,283 @@
+use std::{collections::VecDeque, fmt, fmt::Write, sync::Mutex, sync::OnceLock};
+
+use log::{Level, LevelFilter};
+#[cfg(not(test))]
+use log::{Log, Metadata, Record};
+use time::{OffsetDateTime, UtcOffset};
+use tracing::{
+    field::{Field, Visit},
+    Event, Subscriber,
+};
+use tracing_log::{AsLog, NormalizeEvent};
+use tracing_subscriber::{layer::Context, Layer};
+
+/// The most records kept in memory, the oldest are dropped first.
+const MAX_ENTRIES: usize = 5000;
+/// The records of Neovide at this level or above are kept, even when the logger writes out less.
+const RETAINED_LEVEL: LevelFilter = LevelFilter::Debug;
+/// The records of the dependencies at this level or above are kept.
+const RETAINED_DEPENDENCY_LEVEL: LevelFilter = LevelFilter::Warn;
+
+static ENTRIES: Mutex<LogBuffer> = Mutex::new(LogBuffer::new(MAX_ENTRIES));
+static LOCAL_OFFSET: OnceLock<UtcOffset> = OnceLock::new();
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogEntry {
+    /// In the local time zone.
+    pub time: OffsetDateTime,
+    pub level: Level,
+    /// The module path without the `neovide::` prefix, like `bridge::handler`.
+    pub module: String,
+    /// The message followed by the other fields of the event, as `key=value`.
+    pub message: String,
+}
+
+/// A ring buffer of log entries.
+pub struct LogBuffer {
+    entries: VecDeque<LogEntry>,
+    capacity: usize,
+}
+
+impl LogBuffer {
+    pub const fn new(capacity: usize) -> Self {
+        Self {
+            entries: VecDeque::new(),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, entry: LogEntry) {
+        if self.entries.len() == self.capacity {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(entry);
+    }
+
+    pub fn filtered(&self, filter: &LogFilter) -> Vec<LogEntry> {
+        self.entries
+            .iter()
+            .filter(|entry| filter.matches(entry))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Which entries the log viewer shows.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LogFilter {
+    pub level: LevelFilter,
+    /// Matched anywhere in the module path, ignoring the case.
+    pub module: String,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            module: String::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn matches(&self, entry: &LogEntry) -> bool {
+        entry.level <= self.level
+            && entry
+                .module
+                .to_lowercase()
+                .contains(&self.module.to_lowercase())
+    }
+}
+
+/// The offset of the local time zone, which can only be read reliably before other threads are
+/// started, so it's read once at startup. UTC is used when it can't be read.
+fn local_offset() -> UtcOffset {
+    *LOCAL_OFFSET.get_or_init(|| UtcOffset::current_local_offset().unwrap_or(UtcOffset::UTC))
+}
+
+/// A `tracing` layer that keeps the events of Neovide and the warnings of its dependencies in
+/// memory for `:NeovideLogs`.
+pub struct LogCollector;
+
+impl<S: Subscriber> Layer<S> for LogCollector {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>, _ctx: Context<'_, S>) -> bool {
+        is_retained(metadata.level().as_log(), metadata.target())
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        // The events from the `log` bridge carry the real target in their fields
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let level = metadata.level().as_log();
+        let target = metadata.target();
+        if !is_retained(level, target) {
+            return;
+        }
+        let mut visitor = MessageVisitor::default();
+        event.record(&mut visitor);
+        ENTRIES.lock().unwrap().push(LogEntry {
+            time: OffsetDateTime::now_utc().to_offset(local_offset()),
+            level,
+            module: target
+                .strip_prefix("neovide::")
+                .unwrap_or(target)
+                .to_string(),
+            message: visitor.finish(),
+        });
+    }
+}
+
+fn is_retained(level: Level, target: &str) -> bool {
+    if target.starts_with("neovide") {
+        level <= RETAINED_LEVEL
+    } else {
+        level <= RETAINED_DEPENDENCY_LEVEL
+    }
+}
+
+/// Formats the message of an event, followed by its other fields.
+#[derive(Default)]
+struct MessageVisitor {
+    message: String,
+    fields: String,
+}
+
+impl MessageVisitor {
+    fn finish(self) -> String {
+        match (self.message.is_empty(), self.fields.is_empty()) {
+            (_, true) => self.message,
+            (true, false) => self.fields,
+            (false, false) => format!("{} {}", self.message, self.fields),
+        }
+    }
+}
+
+impl Visit for MessageVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => write!(self.message, "{value:?}"),
+            // Added by the `log` bridge
+            name if name.starts_with("log.") => Ok(()),
+            name if self.fields.is_empty() => write!(self.fields, "{name}={value:?}"),
+            name => write!(self.fields, " {name}={value:?}"),
+        }
+        .unwrap();
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        // Without the quotes of the debug format
+        self.record_debug(field, &format_args!("{value}"));
+    }
+}
+
+/// Passes the records of the `log` crate both to the logger that writes them out, which does its
+/// own filtering, and on to `tracing`, where the collector picks up the ones it keeps.
+#[cfg(not(test))]
+struct LogBridge {
+    logger: Box<dyn Log>,
+}
+
+#[cfg(not(test))]
+impl Log for LogBridge {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        is_retained(metadata.level(), metadata.target()) || self.logger.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        tracing_log::format_trace(record).ok();
+        self.logger.log(record);
+    }
+
+    fn flush(&self) {
+        self.logger.flush();
+    }
+}
+
+/// Installs the collector as the `tracing` subscriber, and the bridge in front of the logger,
+/// which only sees the records it's enabled for.
+#[cfg(not(test))]
+pub fn install_log_collector(logger: Box<dyn Log>) {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    local_offset();
+    let subscriber = tracing_subscriber::registry().with(LogCollector);
+    tracing::subscriber::set_global_default(subscriber).expect("Could not start log collector");
+    let max_level = log::max_level().max(RETAINED_LEVEL);
+    log::set_boxed_logger(Box::new(LogBridge { logger })).expect("Could not start logger");
+    log::set_max_level(max_level);
+}
+
+/// The kept entries that pass the filter, oldest first.
+pub fn log_entries(filter: &LogFilter) -> Vec<LogEntry> {
+    ENTRIES.lock().unwrap().filtered(filter)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(level: Level, module: &str) -> LogEntry {
+        LogEntry {
+            time: OffsetDateTime::UNIX_EPOCH,
+            level,
+            module: module.to_string(),
+            message: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_buffer_drops_oldest() {
+        let mut buffer = LogBuffer::new(2);
+        buffer.push(entry(Level::Info, "first"));
+        buffer.push(entry(Level::Info, "second"));
+        buffer.push(entry(Level::Info, "third"));
+        let modules: Vec<_> = buffer
+            .filtered(&LogFilter::default())
+            .into_iter()
+            .map(|entry| entry.module)
+            .collect();
+        assert_eq!(modules, vec!["second", "third"]);
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = LogFilter {
+            level: LevelFilter::Warn,
+            module: "Bridge".to_string(),
+        };
+        assert!(filter.matches(&entry(Level::Error, "bridge::handler")));
+        assert!(filter.matches(&entry(Level::Warn, "bridge")));
+        assert!(!filter.matches(&entry(Level::Info, "bridge::handler")));
+        assert!(!filter.matches(&entry(Level::Error, "renderer")));
+    }
+
+    #[test]
+    fn test_collects_events_with_fields() {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let subscriber = tracing_subscriber::registry().with(LogCollector);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::warn!(target: "zbus::connection", name = "portal", "No reply");
+            tracing::info!(target: "zbus::connection", "Dropped");
+            tracing::debug!(target: "neovide::bridge", "Kept");
+        });
+        let filter = LogFilter {
+            level: LevelFilter::Trace,
+            module: String::new(),
+        };
+        let entries: Vec<_> = log_entries(&filter)
+            .into_iter()
+            .map(|entry| (entry.module, entry.message))
+            .collect();
+        assert_eq!(
+            entries,
+            vec![
+                (
+                    "zbus::connection".to_string(),
+                    "No reply name=portal".to_string()
+                ),
+                ("bridge".to_string(), "Kept".to_string()),
+            ]
+        );
+    }
+}
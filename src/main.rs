@@ -12,14 +12,19 @@ extern crate approx;
 #[macro_use]
 extern crate clap;
 
+mod benchmark;
 mod bridge;
 mod channel_utils;
 mod clipboard;
 mod cmd_line;
+mod crash_report;
 mod dimensions;
+mod doctor;
 mod editor;
 mod error_handling;
+mod file_associations;
 mod frame;
+mod log_collector;
 mod profiling;
 mod renderer;
 mod running_tracker;
@@ -35,30 +40,39 @@ extern crate derive_new;
 #[macro_use]
 extern crate lazy_static;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use log::trace;
 use std::env::{self, args};
 use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::io::{self, Read, Write};
 use std::panic::{set_hook, PanicInfo};
 use std::time::SystemTime;
 use time::macros::format_description;
 use time::OffsetDateTime;
-use winit::event_loop::EventLoopProxy;
+use winit::event_loop::EventLoop;
 
 #[cfg(not(test))]
 use flexi_logger::{Cleanup, Criterion, Duplicate, FileSpec, Logger, Naming};
 
 use backtrace::Backtrace;
-use bridge::NeovimRuntime;
+use benchmark::run_benchmark;
+use bridge::{
+    daemon_is_waiting, forward_to_running_instance, hand_to_daemon, send_command, start_replay,
+    NeovimRuntime,
+};
 use cmd_line::CmdLineSettings;
+use crash_report::{show_crash_dialog, write_crash_report};
+use doctor::run_doctor;
 use error_handling::{handle_startup_errors, NeovideExitCode};
+use file_associations::update_file_associations;
+#[cfg(not(test))]
+use log_collector::install_log_collector;
 use renderer::{cursor_renderer::CursorSettings, RendererSettings};
 #[cfg_attr(target_os = "windows", allow(unused_imports))]
 use settings::SETTINGS;
 use window::{
-    create_event_loop, create_window, determine_window_size, main_loop, UserEvent, WindowSettings,
-    WindowSize,
+    create_event_loop, create_window, determine_window_size, main_loop,
+    start_reading_desktop_settings, startup_monitor, UserEvent, WindowSettings, WindowSize,
 };
 
 pub use channel_utils::*;
@@ -66,7 +80,9 @@ pub use running_tracker::*;
 #[cfg(target_os = "windows")]
 pub use windows_utils::*;
 
-use crate::settings::{load_last_window_settings, Config, FontSettings, PersistentWindowSettings};
+use crate::settings::{
+    load_last_window_settings, restore_scale_factor, Config, FontSettings, PersistentWindowSettings,
+};
 
 pub use profiling::startup_profiler;
 
@@ -81,8 +97,19 @@ fn main() -> NeovideExitCode {
         eprintln!("{stderr_msg}");
 
         log_panic_to_file(panic_info, &backtrace);
+
+        let panic_msg = generate_panic_message(panic_info);
+        if let Some(report) = write_crash_report(&panic_msg, &backtrace) {
+            eprintln!("Crash report saved to {}", report.display());
+            if !cfg!(debug_assertions) {
+                show_crash_dialog(&report);
+            }
+        }
     }));
 
+    #[cfg(not(windows))]
+    crash_report::install_signal_handler();
+
     #[cfg(target_os = "windows")]
     {
         windows_fix_dpi();
@@ -90,9 +117,28 @@ fn main() -> NeovideExitCode {
 
     let event_loop = create_event_loop();
 
-    match setup(event_loop.create_proxy()) {
+    match setup(&event_loop) {
         Err(err) => handle_startup_errors(err, event_loop).into(),
         Ok((window_size, font_settings, _runtime)) => {
+            #[cfg(target_os = "windows")]
+            {
+                let settings = SETTINGS.get::<CmdLineSettings>();
+                if settings.doctor || settings.benchmark.is_some() {
+                    windows_attach_to_console();
+                }
+            }
+            if SETTINGS.get::<CmdLineSettings>().doctor {
+                return i32::from(!run_doctor(&event_loop)).into();
+            }
+            if let Some(path) = SETTINGS.get::<CmdLineSettings>().benchmark {
+                return match run_benchmark(&path, font_settings, event_loop) {
+                    Ok(()) => 0.into(),
+                    Err(error) => {
+                        eprintln!("{error:?}");
+                        1.into()
+                    }
+                };
+            }
             clipboard::init(&event_loop);
             let window = create_window(&event_loop, &window_size);
             main_loop(window, window_size, font_settings, event_loop).into()
@@ -101,7 +147,7 @@ fn main() -> NeovideExitCode {
 }
 
 fn setup(
-    proxy: EventLoopProxy<UserEvent>,
+    event_loop: &EventLoop<UserEvent>,
 ) -> Result<(WindowSize, Option<FontSettings>, NeovimRuntime)> {
     //  --------------
     // | Architecture |
@@ -172,13 +218,56 @@ fn setup(
     //
     // The Window event loop sends UICommand to the bridge, which forwards them to Neovim. It also
     // reads `DrawCommand`, `SettingChanged`, and `WindowCommand` from the other components.
-    let config = Config::init();
-    Config::watch_config_file(config.clone(), proxy.clone());
+    let proxy = event_loop.create_proxy();
+    let args: Vec<String> = args().collect();
+    let config = Config::init(cmd_line::find_profile_argument(
+        &args,
+        env::var("NEOVIDE_PROFILE").ok(),
+    ));
 
     //Will exit if -h or -v
-    cmd_line::handle_command_line_arguments(args().collect())?;
+    cmd_line::handle_command_line_arguments(args)?;
+    let cmdline_settings = SETTINGS.get::<CmdLineSettings>();
+    if cmdline_settings.register_file_associations || cmdline_settings.unregister_file_associations
+    {
+        #[cfg(target_os = "windows")]
+        windows_attach_to_console();
+        update_file_associations(cmdline_settings.register_file_associations)?;
+        std::process::exit(0);
+    }
+    if let Some(command) = &cmdline_settings.send_command {
+        #[cfg(target_os = "windows")]
+        windows_attach_to_console();
+        send_command(command)?;
+        std::process::exit(0);
+    }
+    // Piped text and Nvim flags can't be forwarded, so they are always opened in a new instance
+    let forwardable = !cmdline_settings.reads_stdin()
+        && cmdline_settings.neovim_flags.is_empty()
+        && !cmdline_settings.doctor
+        && cmdline_settings.benchmark.is_none()
+        && cmdline_settings.replay.is_none();
+    if forwardable
+        && cmdline_settings.single_instance
+        && forward_to_running_instance(&cmdline_settings.files_to_open)
+    {
+        std::process::exit(0);
+    }
+    if cmdline_settings.daemon {
+        if daemon_is_waiting() {
+            eprintln!("A Neovide daemon is already waiting");
+            std::process::exit(0);
+        }
+    } else if forwardable && only_files_given() && hand_to_daemon(&cmdline_settings.files_to_open) {
+        std::process::exit(0);
+    }
+    start_reading_desktop_settings();
+    let stdin = cmdline_settings
+        .reads_stdin()
+        .then(read_stdin)
+        .transpose()?;
     #[cfg(not(target_os = "windows"))]
-    maybe_disown();
+    maybe_disown(stdin.as_deref());
 
     startup_profiler();
 
@@ -190,19 +279,35 @@ fn setup(
     SETTINGS.register::<WindowSettings>();
     SETTINGS.register::<RendererSettings>();
     SETTINGS.register::<CursorSettings>();
+    restore_scale_factor();
+    config.apply_settings(&proxy);
+    Config::watch_config_file(config.clone(), proxy.clone());
     let window_settings = load_last_window_settings().ok();
-    let window_size = determine_window_size(window_settings.as_ref());
+    let monitor = startup_monitor(event_loop);
+    let window_size = determine_window_size(window_settings.as_ref(), monitor.as_ref());
     let grid_size = match window_size {
         WindowSize::Grid(grid_size) => Some(grid_size),
-        _ => match window_settings {
-            Some(PersistentWindowSettings::Windowed { grid_size, .. }) => grid_size,
+        _ => match window_settings
+            .as_ref()
+            .and_then(PersistentWindowSettings::windowed)
+        {
+            Some(PersistentWindowSettings::Windowed { grid_size, .. }) => *grid_size,
             _ => None,
         },
     };
 
     let mut runtime = NeovimRuntime::new()?;
-    runtime.launch(proxy, grid_size)?;
-    Ok((window_size, config.font, runtime))
+    if let Some(path) = cmdline_settings.replay {
+        start_replay(&path, proxy)?;
+    } else if cmdline_settings.benchmark.is_none() && !cmdline_settings.doctor {
+        runtime.launch(proxy.clone(), grid_size, stdin)?;
+        if cmdline_settings.daemon {
+            runtime.wait_to_be_claimed(proxy);
+        } else if cmdline_settings.single_instance {
+            runtime.listen_for_instances(proxy);
+        }
+    }
+    Ok((window_size, config.active().font, runtime))
 }
 
 #[cfg(not(test))]
@@ -223,28 +328,53 @@ pub fn init_logger() {
         Logger::try_with_env_or_str("neovide = error").expect("Could not init logger")
     };
 
-    logger.start().expect("Could not start logger");
+    let (logger, _handle) = logger.build().expect("Could not start logger");
+    install_log_collector(logger);
+}
+
+/// True when only files were given on the command line, without any options.
+fn only_files_given() -> bool {
+    args().skip(1).all(|arg| !arg.starts_with('-'))
+}
+
+/// Reads the text piped to `neovide -`, in whatever encoding it is.
+fn read_stdin() -> Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    io::stdin()
+        .read_to_end(&mut bytes)
+        .context("Could not read stdin")?;
+    Ok(bytes)
 }
 
 #[cfg(not(target_os = "windows"))]
-fn maybe_disown() {
+fn maybe_disown(stdin: Option<&[u8]>) {
     use std::process;
 
     let settings = SETTINGS.get::<CmdLineSettings>();
 
-    if cfg!(debug_assertions) || !settings.fork {
+    // The diagnostic modes print to the terminal, so they can't be detached from it
+    let prints_report = settings.doctor || settings.benchmark.is_some();
+    if cfg!(debug_assertions) || !settings.fork || prints_report {
         return;
     }
 
     if let Ok(current_exe) = env::current_exe() {
-        assert!(process::Command::new(current_exe)
-            .stdin(process::Stdio::null())
+        // The stdin of the terminal has already been read, so it's passed on through a pipe
+        let mut child = process::Command::new(current_exe)
+            .stdin(if stdin.is_some() {
+                process::Stdio::piped()
+            } else {
+                process::Stdio::null()
+            })
             .stdout(process::Stdio::null())
             .stderr(process::Stdio::null())
             .arg("--no-fork")
             .args(env::args().skip(1))
             .spawn()
-            .is_ok());
+            .expect("Could not spawn the detached process");
+        if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            let _ = pipe.write_all(text);
+        }
         process::exit(0);
     } else {
         eprintln!("error in disowning process, cannot obtain the path for the current executable, continuing without disowning...");
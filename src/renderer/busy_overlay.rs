@@ -0,0 +1,42 @@
+use skia_safe::Canvas;
+
+use crate::{
+    profiling::tracy_zone,
+    renderer::{dialog::draw_dialog, GridRenderer},
+};
+
+/// The opacity of the black drawn over the grids while Neovim is busy.
+const DIM_ALPHA: u8 = 0x99;
+
+/// Dims the window and explains how to get Neovim back, while it doesn't answer the heartbeat
+/// requests.
+#[derive(Default)]
+pub struct BusyOverlay {
+    busy: bool,
+    // Only the embedded Neovim can be killed, not a server Neovide is connected to
+    can_kill: bool,
+}
+
+impl BusyOverlay {
+    pub fn set_busy(&mut self, busy: bool, can_kill: bool) {
+        self.busy = busy;
+        self.can_kill = can_kill;
+    }
+
+    fn lines(&self) -> Vec<&'static str> {
+        let mut lines = vec!["Neovim is not responding", "", "<C-c>   interrupt it"];
+        if self.can_kill {
+            lines.push("<C-S-k> kill it");
+        }
+        lines
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.busy {
+            return;
+        }
+        tracy_zone!("busy_overlay_draw");
+        let lines: Vec<String> = self.lines().into_iter().map(String::from).collect();
+        draw_dialog(canvas, grid_renderer, &lines, DIM_ALPHA);
+    }
+}
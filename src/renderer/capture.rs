@@ -0,0 +1,185 @@
+use std::{
+    fs::{self, File},
+    io::BufWriter,
+    path::{Path, PathBuf},
+    sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::{bail, Context, Result};
+use image::{
+    codecs::gif::{GifEncoder, Repeat},
+    Delay, Frame, RgbaImage,
+};
+use skia_safe::{png_encoder, AlphaType, Canvas, ColorType, ImageInfo, Pixmap};
+
+use crate::error_msg;
+
+/// A rendered frame read back from the canvas, in unpremultiplied RGBA.
+#[derive(Clone)]
+pub struct CapturedFrame {
+    width: i32,
+    height: i32,
+    pixels: Vec<u8>,
+}
+
+impl CapturedFrame {
+    /// Reads the pixels of the whole canvas. This works the same way for all the backends, since
+    /// skia takes care of copying the surface back from the GPU.
+    pub fn read(canvas: &Canvas) -> Option<Self> {
+        let size = canvas.base_layer_size();
+        let info = Self::image_info(size.width, size.height);
+        let mut pixels = vec![0; info.compute_min_byte_size()];
+        canvas
+            .read_pixels(&info, &mut pixels, info.min_row_bytes(), (0, 0))
+            .then_some(Self {
+                width: size.width,
+                height: size.height,
+                pixels,
+            })
+    }
+
+    fn image_info(width: i32, height: i32) -> ImageInfo {
+        ImageInfo::new(
+            (width, height),
+            ColorType::RGBA8888,
+            AlphaType::Unpremul,
+            None,
+        )
+    }
+
+    pub fn save_png(&mut self, path: &Path) -> Result<()> {
+        let info = Self::image_info(self.width, self.height);
+        let pixmap = Pixmap::new(&info, &mut self.pixels, info.min_row_bytes())
+            .context("Invalid frame size")?;
+        let file = File::create(path).with_context(|| format!("Could not create {path:?}"))?;
+        if !png_encoder::encode(&pixmap, &mut BufWriter::new(file), &Default::default()) {
+            bail!("Could not encode {path:?}");
+        }
+        Ok(())
+    }
+
+    /// Turns the frame into a GIF frame that is shown for `delay`.
+    fn into_gif_frame(self, delay: Duration) -> Result<Frame> {
+        let image = RgbaImage::from_raw(self.width as u32, self.height as u32, self.pixels)
+            .context("Invalid frame size")?;
+        Ok(Frame::from_parts(
+            image,
+            0,
+            0,
+            Delay::from_saturating_duration(delay),
+        ))
+    }
+
+    /// Encodes and saves the frame on another thread, so that rendering is not blocked.
+    pub fn save_png_in_background(mut self, path: PathBuf) {
+        thread::spawn(move || {
+            if let Err(error) = self.save_png(&path) {
+                error_msg!("Could not save the screenshot: {error:?}");
+            }
+        });
+    }
+}
+
+/// How many frames can wait for being encoded, before new ones are dropped. Each one is a full
+/// uncompressed copy of the window.
+const MAX_QUEUED_FRAMES: usize = 8;
+
+/// The GIF quantization speed from 1 to 30, the fastest one keeps up best with the rendering.
+const GIF_SPEED: i32 = 30;
+
+/// How long the last frame of the GIF is shown, since there's no next frame to tell.
+const LAST_GIF_FRAME_DELAY: Duration = Duration::from_millis(100);
+
+/// A recorded frame, the path of its PNG image and when it was rendered.
+type RecordedFrame = (PathBuf, CapturedFrame, Instant);
+
+/// Saves every rendered frame into a directory for a given time, as numbered PNG images, and
+/// assembles them into an animated `recording.gif` in the same directory.
+pub struct FrameRecorder {
+    directory: PathBuf,
+    end: Instant,
+    frame_count: u32,
+    dropped_count: u32,
+    sender: SyncSender<RecordedFrame>,
+}
+
+impl FrameRecorder {
+    pub fn new(directory: PathBuf, duration: Duration) -> Result<Self> {
+        fs::create_dir_all(&directory)
+            .with_context(|| format!("Could not create {directory:?}"))?;
+        let gif_path = directory.join("recording.gif");
+        let gif_file =
+            File::create(&gif_path).with_context(|| format!("Could not create {gif_path:?}"))?;
+
+        // A single thread writes the frames in order, and finishes the GIF when the recorder is
+        // dropped
+        let (sender, receiver) = sync_channel::<RecordedFrame>(MAX_QUEUED_FRAMES);
+        thread::spawn(move || {
+            if let Err(error) = write_recording(receiver, gif_file) {
+                error_msg!("Could not save the recording: {error:?}");
+            }
+        });
+
+        Ok(Self {
+            directory,
+            end: Instant::now() + duration,
+            frame_count: 0,
+            dropped_count: 0,
+            sender,
+        })
+    }
+
+    /// Adds the frame to the recording, returns false when the recording has ended. The frame is
+    /// dropped instead of blocking the rendering when the encoding can't keep up.
+    pub fn add_frame(&mut self, frame: CapturedFrame) -> bool {
+        if Instant::now() >= self.end {
+            log::info!(
+                "Recorded {} frames to {:?}, dropped {}",
+                self.frame_count,
+                self.directory,
+                self.dropped_count
+            );
+            return false;
+        }
+        let path = self
+            .directory
+            .join(format!("frame_{:05}.png", self.frame_count));
+        match self.sender.try_send((path, frame, Instant::now())) {
+            Ok(()) => {
+                self.frame_count += 1;
+                true
+            }
+            Err(TrySendError::Full(_)) => {
+                self.dropped_count += 1;
+                true
+            }
+            Err(TrySendError::Disconnected(_)) => false,
+        }
+    }
+}
+
+fn write_recording(receiver: Receiver<RecordedFrame>, gif_file: File) -> Result<()> {
+    let mut encoder = GifEncoder::new_with_speed(BufWriter::new(gif_file), GIF_SPEED);
+    encoder.set_repeat(Repeat::Infinite)?;
+    // A GIF frame is shown until the next one, so it's only encoded when the next one arrives.
+    // The delays come from the render times, which keeps the speed right when frames are dropped.
+    let mut previous: Option<(CapturedFrame, Instant)> = None;
+    let mut size = None;
+    for (path, mut frame, time) in receiver {
+        frame.save_png(&path)?;
+        // The GIF has the size of the first frame, so the frames rendered while the window has
+        // another size are left out of it
+        if *size.get_or_insert((frame.width, frame.height)) != (frame.width, frame.height) {
+            continue;
+        }
+        if let Some((previous_frame, previous_time)) = previous.replace((frame, time)) {
+            encoder.encode_frame(previous_frame.into_gif_frame(time - previous_time)?)?;
+        }
+    }
+    if let Some((frame, _)) = previous {
+        encoder.encode_frame(frame.into_gif_frame(LAST_GIF_FRAME_DELAY)?)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,583 @@
+use rmpv::Value;
+use skia_safe::{Canvas, Color, Paint, Rect};
+
+use crate::{dimensions::Dimensions, profiling::tracy_zone, renderer::GridRenderer};
+
+/// The most results shown at once, the list scrolls to keep the selection visible.
+const MAX_ROWS: usize = 12;
+/// The most lines of the preview shown below the results.
+const MAX_PREVIEW_ROWS: usize = 5;
+/// The widest the palette gets, in grid cells.
+const MAX_COLUMNS: usize = 80;
+const PROMPT: &str = "> ";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PaletteItemKind {
+    Command,
+    Buffer,
+    File,
+    Project,
+    Session,
+}
+
+impl PaletteItemKind {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "command" => Some(PaletteItemKind::Command),
+            "buffer" => Some(PaletteItemKind::Buffer),
+            "file" => Some(PaletteItemKind::File),
+            "project" => Some(PaletteItemKind::Project),
+            "session" => Some(PaletteItemKind::Session),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PaletteItemKind::Command => "command",
+            PaletteItemKind::Buffer => "buffer",
+            PaletteItemKind::File => "file",
+            PaletteItemKind::Project => "project",
+            PaletteItemKind::Session => "session",
+        }
+    }
+}
+
+/// An entry of the palette, sent by Neovim, where the value is what's used to run it, like the
+/// buffer number of a buffer. The preview is shown below the results while it's selected.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PaletteItem {
+    pub kind: PaletteItemKind,
+    pub label: String,
+    pub value: String,
+    pub preview: String,
+}
+
+impl PaletteItem {
+    /// Parses a `[kind, label, value]` list, optionally followed by the preview.
+    pub fn parse(value: &Value) -> Option<Self> {
+        let (kind, label, value, preview) = match value.as_array()?.as_slice() {
+            [kind, label, value] => (kind, label, value, ""),
+            [kind, label, value, preview] => (kind, label, value, preview.as_str()?),
+            _ => return None,
+        };
+        Some(PaletteItem {
+            kind: PaletteItemKind::parse(kind.as_str()?)?,
+            label: label.as_str()?.to_string(),
+            value: value.as_str()?.to_string(),
+            preview: preview.to_string(),
+        })
+    }
+}
+
+fn contains(rect: &Rect, (x, y): (f32, f32)) -> bool {
+    (rect.left..rect.right).contains(&x) && (rect.top..rect.bottom).contains(&y)
+}
+
+/// Where the palette is drawn, in pixels, and how many rows it has.
+struct Layout {
+    left: f32,
+    top: f32,
+    columns: usize,
+    rows: usize,
+    preview_rows: usize,
+}
+
+/// What the window does after a key was handled by the palette.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PaletteAction {
+    None,
+    Close,
+    Run(PaletteItem),
+}
+
+/// A fuzzy picker drawn by Neovide over the grids. It handles the keyboard input by itself while
+/// it's open, so that it can be used even when Neovim is waiting at a prompt. The items of the
+/// last time it was opened are shown until Neovim sends the new ones.
+#[derive(Default)]
+pub struct CommandPalette {
+    open: bool,
+    loading: bool,
+    query: String,
+    items: Vec<PaletteItem>,
+    // The indices of the matching items, with the character positions of the matches
+    matches: Vec<(usize, Vec<usize>)>,
+    selected: usize,
+    // The first match shown, when the list is scrolled
+    first: usize,
+}
+
+impl CommandPalette {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self) {
+        self.open = true;
+        self.loading = true;
+        self.query.clear();
+        self.update_matches();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    pub fn set_items(&mut self, items: Vec<PaletteItem>) {
+        self.items = items;
+        self.loading = false;
+        self.update_matches();
+    }
+
+    /// Opens the palette with items that are already known, like the projects of the launcher.
+    pub fn open_with_items(&mut self, items: Vec<PaletteItem>) {
+        self.open();
+        self.set_items(items);
+    }
+
+    fn select(&mut self, selected: usize) {
+        self.selected = selected;
+        if selected < self.first {
+            self.first = selected;
+        } else if selected >= self.first + MAX_ROWS {
+            self.first = selected + 1 - MAX_ROWS;
+        }
+    }
+
+    fn run_selected(&self) -> PaletteAction {
+        self.matches
+            .get(self.selected)
+            .map_or(PaletteAction::None, |(index, _)| {
+                PaletteAction::Run(self.items[*index].clone())
+            })
+    }
+
+    /// Handles a key in the Neovim notation, like `<Down>` or `a`.
+    pub fn handle_key(&mut self, key: &str) -> PaletteAction {
+        let count = self.matches.len();
+        match key {
+            "<Esc>" | "<C-c>" | "<C-[>" => return PaletteAction::Close,
+            "<Enter>" | "<CR>" | "<C-m>" => return self.run_selected(),
+            "<Up>" | "<C-p>" | "<S-Tab>" if count > 0 => {
+                self.select((self.selected + count - 1) % count);
+            }
+            "<Down>" | "<C-n>" | "<Tab>" if count > 0 => {
+                self.select((self.selected + 1) % count);
+            }
+            "<BS>" | "<C-h>" => {
+                self.query.pop();
+                self.update_matches();
+            }
+            "<C-u>" => {
+                self.query.clear();
+                self.update_matches();
+            }
+            "<C-w>" => {
+                let trimmed = self.query.trim_end();
+                let end = trimmed.rfind(' ').map_or(0, |index| index + 1);
+                self.query.truncate(end);
+                self.update_matches();
+            }
+            "<lt>" => {
+                self.query.push('<');
+                self.update_matches();
+            }
+            "<Space>" => {
+                self.query.push(' ');
+                self.update_matches();
+            }
+            text if !text.starts_with('<') => {
+                self.query.push_str(text);
+                self.update_matches();
+            }
+            _ => {}
+        }
+        PaletteAction::None
+    }
+
+    fn update_matches(&mut self) {
+        let mut matches: Vec<_> = self
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(index, item)| {
+                fuzzy_match(&self.query, &item.label)
+                    .map(|(score, positions)| (score, index, positions))
+            })
+            .collect();
+        // Stable, so that equal matches keep the order Neovim sent them in
+        matches.sort_by_key(|(score, ..)| -score);
+        self.matches = matches
+            .into_iter()
+            .map(|(_, index, positions)| (index, positions))
+            .collect();
+        self.selected = 0;
+        self.first = 0;
+    }
+
+    fn preview_lines(&self) -> Vec<&str> {
+        self.matches
+            .get(self.selected)
+            .map(|(index, _)| {
+                let preview = &self.items[*index].preview;
+                preview.lines().take(MAX_PREVIEW_ROWS).collect()
+            })
+            .unwrap_or_default()
+    }
+
+    fn layout(&self, (width, height): (f32, f32), font_width: f32) -> Option<Layout> {
+        let columns = ((width / font_width) as usize)
+            .saturating_sub(4)
+            .min(MAX_COLUMNS);
+        if columns < PROMPT.len() + 1 {
+            return None;
+        }
+        Some(Layout {
+            left: ((width - columns as f32 * font_width) / 2.0).round(),
+            top: (height / 6.0).round(),
+            columns,
+            rows: self.matches.len().clamp(1, MAX_ROWS),
+            preview_rows: self.preview_lines().len(),
+        })
+    }
+
+    /// The palette in a window of the size, and the index of the match at the position, if any.
+    fn hit_test(
+        &self,
+        (x, y): (f32, f32),
+        size: (f32, f32),
+        font_dimensions: Dimensions,
+    ) -> Option<(Rect, Option<usize>)> {
+        let font_width = font_dimensions.width as f32;
+        let font_height = font_dimensions.height as f32;
+        let layout = self.layout(size, font_width)?;
+        let rect = Rect::from_xywh(
+            layout.left,
+            layout.top,
+            layout.columns as f32 * font_width,
+            (layout.rows + 1 + layout.preview_rows) as f32 * font_height,
+        );
+        if !contains(&rect, (x, y)) {
+            return Some((rect, None));
+        }
+        let row = ((y - layout.top) / font_height) as usize;
+        let index = (1..=layout.rows)
+            .contains(&row)
+            .then(|| self.first + row - 1)
+            .filter(|index| *index < self.matches.len());
+        Some((rect, index))
+    }
+
+    /// Selects the result under the mouse. Returns true when the selection changed.
+    pub fn hover(
+        &mut self,
+        position: (f32, f32),
+        size: (f32, f32),
+        font_dimensions: Dimensions,
+    ) -> bool {
+        match self.hit_test(position, size, font_dimensions) {
+            Some((_, Some(index))) if index != self.selected => {
+                self.select(index);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs the result that was clicked, or closes the palette when clicked outside of it.
+    pub fn click(
+        &mut self,
+        position: (f32, f32),
+        size: (f32, f32),
+        font_dimensions: Dimensions,
+    ) -> PaletteAction {
+        match self.hit_test(position, size, font_dimensions) {
+            Some((_, Some(index))) => {
+                self.select(index);
+                self.run_selected()
+            }
+            Some((rect, None)) if !contains(&rect, position) => PaletteAction::Close,
+            _ => PaletteAction::None,
+        }
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.open {
+            return;
+        }
+        tracy_zone!("command_palette_draw");
+
+        let font_width = grid_renderer.font_dimensions.width as f32;
+        let font_height = grid_renderer.font_dimensions.height as f32;
+        let y_adjustment = grid_renderer.shaper.lock().y_adjustment() as f32;
+        let colors = &grid_renderer.default_style.colors;
+        let background = colors.background.unwrap().to_color();
+        let foreground = colors.foreground.unwrap().to_color();
+
+        let size = canvas.base_layer_size();
+        let Some(Layout {
+            left,
+            top,
+            columns,
+            rows,
+            preview_rows,
+        }) = self.layout((size.width as f32, size.height as f32), font_width)
+        else {
+            return;
+        };
+        let palette_rect = Rect::from_xywh(
+            left,
+            top,
+            columns as f32 * font_width,
+            (rows + 1 + preview_rows) as f32 * font_height,
+        );
+
+        let mut paint = Paint::default();
+        paint.set_anti_alias(false);
+        paint.set_color(background);
+        canvas.draw_rect(palette_rect, &paint);
+        paint.set_color(foreground.with_a(0x60));
+        paint.set_style(skia_safe::PaintStyle::Stroke);
+        canvas.draw_rect(palette_rect.with_outset((1.0, 1.0)), &paint);
+        paint.set_style(skia_safe::PaintStyle::Fill);
+
+        let mut draw_text = |text: &str, x: f32, row: usize, color: Color| {
+            paint.set_color(color);
+            let y = top + row as f32 * font_height + y_adjustment;
+            for blob in grid_renderer
+                .shaper
+                .lock()
+                .shape_cached(text, Default::default(), false)
+                .iter()
+            {
+                canvas.draw_text_blob(blob, (x, y), &paint);
+            }
+        };
+
+        let query: String = format!("{PROMPT}{}", self.query)
+            .chars()
+            .take(columns)
+            .collect();
+        draw_text(&query, left, 0, foreground);
+
+        let dimmed = foreground.with_a(0x90);
+        if self.matches.is_empty() {
+            let message = if self.loading {
+                "Loading..."
+            } else {
+                "No matches"
+            };
+            draw_text(message, left + font_width, 1, dimmed);
+            return;
+        }
+
+        let first = self.first;
+        let mut underlines = Vec::new();
+        for (row, (index, positions)) in self
+            .matches
+            .iter()
+            .enumerate()
+            .skip(first)
+            .take(MAX_ROWS)
+            .map(|(match_index, item)| (match_index - first + 1, item))
+        {
+            let item = &self.items[*index];
+            let row_top = top + row as f32 * font_height;
+            let kind = item.kind.name();
+            let label_columns = columns.saturating_sub(kind.len() + 3);
+            let label: String = item.label.chars().take(label_columns).collect();
+            draw_text(&label, left + font_width, row, foreground);
+            draw_text(
+                kind,
+                left + (columns - kind.len() - 1) as f32 * font_width,
+                row,
+                dimmed,
+            );
+            // The matched characters are underlined
+            for position in positions
+                .iter()
+                .filter(|position| **position < label_columns)
+            {
+                let x = left + (position + 1) as f32 * font_width;
+                underlines.push(Rect::from_xywh(
+                    x,
+                    row_top + font_height - 2.0,
+                    font_width,
+                    1.0,
+                ));
+            }
+        }
+        for (row, line) in self.preview_lines().into_iter().enumerate() {
+            let line: String = line.chars().take(columns.saturating_sub(2)).collect();
+            draw_text(&line, left + font_width, rows + 1 + row, dimmed);
+        }
+        let mut paint = Paint::default();
+        paint.set_color(foreground);
+        for rect in &underlines {
+            canvas.draw_rect(rect, &paint);
+        }
+        // The selected row is tinted with the foreground color
+        let selected_top = top + (self.selected - first + 1) as f32 * font_height;
+        paint.set_color(foreground.with_a(0x30));
+        canvas.draw_rect(
+            Rect::from_xywh(left, selected_top, palette_rect.width(), font_height),
+            &paint,
+        );
+        if preview_rows > 0 {
+            let preview_top = top + (rows + 1) as f32 * font_height;
+            canvas.draw_rect(
+                Rect::from_xywh(left, preview_top, palette_rect.width(), 1.0),
+                &paint,
+            );
+        }
+    }
+}
+
+/// Matches the characters of the query in order, ignoring the case. Consecutive characters and
+/// characters at the start of words score higher. Returns the score and the character positions
+/// of the matches.
+fn fuzzy_match(query: &str, text: &str) -> Option<(i64, Vec<usize>)> {
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut query_chars = query.chars().filter(|c| !c.is_whitespace()).peekable();
+    let mut previous: Option<char> = None;
+    for (position, character) in text.chars().enumerate() {
+        let Some(query_char) = query_chars.peek() else {
+            break;
+        };
+        if character.to_lowercase().eq(query_char.to_lowercase()) {
+            score += 1;
+            if positions.last().is_some_and(|last| last + 1 == position) {
+                score += 5;
+            }
+            let word_start = match previous {
+                Some(previous) => {
+                    !previous.is_alphanumeric()
+                        || (previous.is_lowercase() && character.is_uppercase())
+                }
+                None => true,
+            };
+            if word_start {
+                score += 8;
+            }
+            positions.push(position);
+            query_chars.next();
+        }
+        previous = Some(character);
+    }
+    if query_chars.peek().is_some() {
+        return None;
+    }
+    // Shorter texts are closer matches
+    score -= text.chars().count() as i64 / 8;
+    Some((score, positions))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(kind: PaletteItemKind, label: &str) -> PaletteItem {
+        PaletteItem {
+            kind,
+            label: label.to_string(),
+            value: label.to_string(),
+            preview: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_fuzzy_match() {
+        assert_eq!(fuzzy_match("", "anything").map(|(_, p)| p), Some(vec![]));
+        assert_eq!(fuzzy_match("gs", "Git status").unwrap().1, vec![0, 4]);
+        assert_eq!(fuzzy_match("xyz", "Git status"), None);
+        // Word starts and consecutive characters rank higher than scattered ones
+        let word_starts = fuzzy_match("ls", "LspStart").unwrap().0;
+        let scattered = fuzzy_match("ls", "lines").unwrap().0;
+        assert!(word_starts > scattered);
+    }
+
+    #[test]
+    fn test_keys_filter_and_run() {
+        let mut palette = CommandPalette::default();
+        palette.open();
+        palette.set_items(vec![
+            item(PaletteItemKind::Command, "write"),
+            item(PaletteItemKind::Command, "Lexplore"),
+            item(PaletteItemKind::File, "src/lib.rs"),
+        ]);
+        assert_eq!(palette.matches.len(), 3);
+
+        palette.handle_key("l");
+        assert_eq!(palette.matches.len(), 2);
+        assert_eq!(palette.items[palette.matches[0].0].label, "Lexplore");
+        palette.handle_key("<Down>");
+        assert_eq!(
+            palette.handle_key("<CR>"),
+            PaletteAction::Run(item(PaletteItemKind::File, "src/lib.rs"))
+        );
+
+        palette.handle_key("<BS>");
+        assert_eq!(palette.matches.len(), 3);
+        assert_eq!(palette.handle_key("<Esc>"), PaletteAction::Close);
+    }
+
+    #[test]
+    fn test_parse_item() {
+        let value = Value::from(vec![
+            Value::from("buffer"),
+            Value::from("3: init.lua"),
+            Value::from("3"),
+        ]);
+        assert_eq!(
+            PaletteItem::parse(&value),
+            Some(PaletteItem {
+                kind: PaletteItemKind::Buffer,
+                label: "3: init.lua".to_string(),
+                value: "3".to_string(),
+                preview: String::new(),
+            })
+        );
+        assert_eq!(PaletteItem::parse(&Value::from("write")), None);
+
+        let value = Value::from(vec![
+            Value::from("project"),
+            Value::from("neovide"),
+            Value::from("/src/neovide"),
+            Value::from("~/src/neovide\n  src/main.rs"),
+        ]);
+        let item = PaletteItem::parse(&value).unwrap();
+        assert_eq!(item.kind, PaletteItemKind::Project);
+        assert_eq!(item.preview.lines().count(), 2);
+    }
+
+    #[test]
+    fn test_mouse() {
+        let mut palette = CommandPalette::default();
+        palette.open_with_items(vec![
+            item(PaletteItemKind::Project, "neovide"),
+            item(PaletteItemKind::Project, "neovim"),
+        ]);
+        let font_dimensions = Dimensions {
+            width: 10,
+            height: 20,
+        };
+        // A 100 columns wide window, the palette starts at the 10th column and the 100th pixel
+        let size = (1000.0, 600.0);
+        assert!(palette.hover((500.0, 145.0), size, font_dimensions));
+        assert_eq!(palette.selected, 1);
+        // The prompt doesn't run anything, and clicking outside closes the palette
+        assert_eq!(
+            palette.click((500.0, 105.0), size, font_dimensions),
+            PaletteAction::None
+        );
+        assert_eq!(
+            palette.click((500.0, 125.0), size, font_dimensions),
+            PaletteAction::Run(item(PaletteItemKind::Project, "neovide"))
+        );
+        assert_eq!(
+            palette.click((20.0, 125.0), size, font_dimensions),
+            PaletteAction::Close
+        );
+    }
+}
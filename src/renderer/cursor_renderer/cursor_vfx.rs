@@ -4,7 +4,7 @@ use skia_safe::{paint::Style, BlendMode, Canvas, Color, Paint, Point, Rect};
 
 use crate::{
     editor::Cursor,
-    renderer::cursor_renderer::CursorSettings,
+    renderer::cursor_renderer::{custom_vfx::CustomTrail, CursorSettings},
     renderer::{animation_utils::*, grid_renderer::GridRenderer},
     settings::*,
 };
@@ -42,10 +42,13 @@ pub enum TrailMode {
     PixieDust,
 }
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
 pub enum VfxMode {
     Highlight(HighlightMode),
     Trail(TrailMode),
+    /// An effect registered from Lua with `neovide.register_cursor_vfx`.
+    Custom(String),
+    #[default]
     Disabled,
 }
 
@@ -60,10 +63,8 @@ impl ParseFromValue for VfxMode {
                 "torpedo" => VfxMode::Trail(TrailMode::Torpedo),
                 "pixiedust" => VfxMode::Trail(TrailMode::PixieDust),
                 "" => VfxMode::Disabled,
-                value => {
-                    error!("Expected a VfxMode name, but received {:?}", value);
-                    return;
-                }
+                // The effect may be registered after the mode is set
+                name => VfxMode::Custom(name.to_string()),
             };
         } else {
             error!("Expected a VfxMode string, but received {:?}", value);
@@ -80,6 +81,7 @@ impl From<VfxMode> for Value {
             VfxMode::Trail(TrailMode::Railgun) => Value::from("railgun"),
             VfxMode::Trail(TrailMode::Torpedo) => Value::from("torpedo"),
             VfxMode::Trail(TrailMode::PixieDust) => Value::from("pixiedust"),
+            VfxMode::Custom(name) => Value::from(name),
             VfxMode::Disabled => Value::from(""),
         }
     }
@@ -89,6 +91,7 @@ pub fn new_cursor_vfx(mode: &VfxMode) -> Option<Box<dyn CursorVfx>> {
     match mode {
         VfxMode::Highlight(mode) => Some(Box::new(PointHighlight::new(mode))),
         VfxMode::Trail(mode) => Some(Box::new(ParticleTrail::new(mode))),
+        VfxMode::Custom(name) => Some(Box::new(CustomTrail::new(name))),
         VfxMode::Disabled => None,
     }
 }
@@ -372,13 +375,13 @@ impl CursorVfx for ParticleTrail {
 }
 
 // Random number generator based on http://www.pcg-random.org/
-struct RngState {
+pub(super) struct RngState {
     state: u64,
     inc: u64,
 }
 
 impl RngState {
-    fn new() -> RngState {
+    pub(super) fn new() -> RngState {
         RngState {
             state: 0x853C_49E6_748F_EA9Bu64,
             inc: (0xDA3E_39CB_94B9_5BDBu64 << 1) | 1,
@@ -404,7 +407,7 @@ impl RngState {
         xsh.rotate_right(rot)
     }
 
-    fn next_f32(&mut self) -> f32 {
+    pub(super) fn next_f32(&mut self) -> f32 {
         let v = self.next();
 
         // In C we'd do ldexp(v, -32) to bring a number in the range [0,2^32) down to [0,1) range.
@@ -433,14 +436,14 @@ impl RngState {
         Point::new(x * 2.0 - 1.0, y * 2.0 - 1.0)
     }
 
-    fn rand_dir_normalized(&mut self) -> Point {
+    pub(super) fn rand_dir_normalized(&mut self) -> Point {
         let mut v = self.rand_dir();
         v.normalize();
         v
     }
 }
 
-fn rotate_vec(v: Point, rot: f32) -> Point {
+pub(super) fn rotate_vec(v: Point, rot: f32) -> Point {
     let sin = rot.sin();
     let cos = rot.cos();
 
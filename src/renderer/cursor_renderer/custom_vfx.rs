@@ -0,0 +1,433 @@
+use std::{
+    collections::HashMap,
+    f32::consts::PI,
+    sync::{Arc, RwLock},
+};
+
+use log::warn;
+use rmpv::Value;
+use skia_safe::{BlendMode, Canvas, Color, Paint, Point, Rect, HSV};
+
+use super::{
+    cursor_vfx::{rotate_vec, CursorVfx, RngState},
+    CursorSettings,
+};
+use crate::{editor::Cursor, renderer::grid_renderer::GridRenderer};
+
+/// The names of the built in effects, which can't be replaced.
+const BUILTIN_NAMES: &[&str] = &[
+    "sonicboom",
+    "ripple",
+    "wireframe",
+    "railgun",
+    "torpedo",
+    "pixiedust",
+];
+
+const MAX_SPAWN_RATE: f32 = 50.0;
+const MAX_LIFETIME: f32 = 5.0;
+const MAX_SPEED: f32 = 50.0;
+const MAX_SIZE: f32 = 4.0;
+const MAX_CURL: f32 = 10.0;
+/// The most particles alive at once, the oldest are dropped first. A long jump with a high spawn
+/// rate and lifetime would otherwise create tens of thousands of them.
+const MAX_PARTICLES: usize = 2000;
+
+lazy_static! {
+    static ref CUSTOM_VFX: RwLock<HashMap<String, Arc<CustomVfxDescriptor>>> =
+        RwLock::new(HashMap::new());
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VfxColor {
+    /// The color of the cursor, like the built in effects.
+    Cursor,
+    /// The default foreground color.
+    Foreground,
+    /// Cycles through the hues over the lifetime of the particle.
+    Rainbow,
+    Fixed(Color),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VfxMotion {
+    /// The particles stay where they were spawned.
+    Static,
+    /// The particles move in a random direction.
+    Drift,
+    Rise,
+    Fall,
+    /// The particles move in a random direction, curling around.
+    Spiral,
+}
+
+/// A particle system registered from Lua with `neovide.register_cursor_vfx`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CustomVfxDescriptor {
+    /// The particles spawned per cell the cursor travels.
+    pub spawn_rate: f32,
+    /// The seconds the particles live.
+    pub lifetime: f32,
+    pub color: VfxColor,
+    pub motion: VfxMotion,
+    /// The speed of the particles in cells per second.
+    pub speed: f32,
+    /// The size of the particles relative to the cell width.
+    pub size: f32,
+    /// The turns per second of spiraling particles.
+    pub curl: f32,
+}
+
+impl CustomVfxDescriptor {
+    /// Parses the table passed to `neovide.register_cursor_vfx`, returning the name and the
+    /// descriptor. Unknown fields and out of range values are rejected, so that mistakes are
+    /// reported to the caller instead of drawing something unexpected.
+    pub fn parse(value: &Value) -> Result<(String, Self), String> {
+        let fields = value
+            .as_map()
+            .ok_or("expected a table with the descriptor of the effect")?;
+
+        let mut name = None;
+        let mut descriptor = CustomVfxDescriptor {
+            spawn_rate: 0.0,
+            lifetime: 0.0,
+            color: VfxColor::Cursor,
+            motion: VfxMotion::Drift,
+            speed: 1.0,
+            size: 0.3,
+            curl: 1.0,
+        };
+        let (mut has_spawn_rate, mut has_lifetime) = (false, false);
+
+        for (key, value) in fields {
+            let key = key.as_str().ok_or("expected string keys")?;
+            match key {
+                "name" => {
+                    let value = value.as_str().ok_or("name must be a string")?;
+                    if value.is_empty() || BUILTIN_NAMES.contains(&value) {
+                        return Err(format!("{value:?} is not a valid name"));
+                    }
+                    name = Some(value.to_string());
+                }
+                "spawn_rate" => {
+                    descriptor.spawn_rate = parse_number(key, value, 0.0, MAX_SPAWN_RATE)?;
+                    has_spawn_rate = true;
+                }
+                "lifetime" => {
+                    descriptor.lifetime = parse_number(key, value, 0.01, MAX_LIFETIME)?;
+                    has_lifetime = true;
+                }
+                "speed" => descriptor.speed = parse_number(key, value, 0.0, MAX_SPEED)?,
+                "size" => descriptor.size = parse_number(key, value, 0.01, MAX_SIZE)?,
+                "curl" => descriptor.curl = parse_number(key, value, -MAX_CURL, MAX_CURL)?,
+                "color_fn" => {
+                    let value = value.as_str().ok_or("color_fn must be a string")?;
+                    descriptor.color = parse_color(value)?;
+                }
+                "motion" => {
+                    descriptor.motion = match value.as_str() {
+                        Some("static") => VfxMotion::Static,
+                        Some("drift") => VfxMotion::Drift,
+                        Some("rise") => VfxMotion::Rise,
+                        Some("fall") => VfxMotion::Fall,
+                        Some("spiral") => VfxMotion::Spiral,
+                        _ => {
+                            return Err(format!(
+                                "motion must be static, drift, rise, fall or spiral, but is {value}"
+                            ))
+                        }
+                    }
+                }
+                _ => return Err(format!("unknown field {key:?}")),
+            }
+        }
+
+        let name = name.ok_or("name is missing")?;
+        if !has_spawn_rate {
+            return Err("spawn_rate is missing".to_string());
+        }
+        if !has_lifetime {
+            return Err("lifetime is missing".to_string());
+        }
+        Ok((name, descriptor))
+    }
+}
+
+fn parse_number(key: &str, value: &Value, min: f32, max: f32) -> Result<f32, String> {
+    let number = value
+        .as_f64()
+        .or_else(|| value.as_i64().map(|value| value as f64))
+        .ok_or_else(|| format!("{key} must be a number"))? as f32;
+    if !(min..=max).contains(&number) {
+        return Err(format!(
+            "{key} must be between {min} and {max}, but is {number}"
+        ));
+    }
+    Ok(number)
+}
+
+fn parse_color(value: &str) -> Result<VfxColor, String> {
+    match value {
+        "cursor" => Ok(VfxColor::Cursor),
+        "foreground" => Ok(VfxColor::Foreground),
+        "rainbow" => Ok(VfxColor::Rainbow),
+        _ => value
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6)
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .map(|rgb| VfxColor::Fixed(Color::from(0xFF00_0000 | rgb)))
+            .ok_or_else(|| {
+                format!("color_fn must be cursor, foreground, rainbow or #rrggbb, but is {value:?}")
+            }),
+    }
+}
+
+/// Adds or replaces the effect, which is used when `g:neovide_cursor_vfx_mode` is set to its
+/// name.
+pub fn register_cursor_vfx(value: &Value) -> Result<(), String> {
+    let (name, descriptor) = CustomVfxDescriptor::parse(value)?;
+    CUSTOM_VFX
+        .write()
+        .unwrap()
+        .insert(name, Arc::new(descriptor));
+    Ok(())
+}
+
+fn custom_vfx(name: &str) -> Option<Arc<CustomVfxDescriptor>> {
+    CUSTOM_VFX.read().unwrap().get(name).cloned()
+}
+
+#[derive(Clone)]
+struct CustomParticle {
+    pos: Point,
+    speed: Point,
+    rotation_speed: f32,
+    lifetime: f32,
+}
+
+/// Draws the particles of a registered descriptor. The descriptor is looked up on every update,
+/// so that effects registered after the mode is set, or registered again, are picked up.
+pub struct CustomTrail {
+    name: String,
+    descriptor: Option<Arc<CustomVfxDescriptor>>,
+    particles: Vec<CustomParticle>,
+    previous_cursor_dest: Point,
+    // Fractions of particles carried over to the next movement, so that low spawn rates work
+    pending_particles: f32,
+    rng: RngState,
+}
+
+impl CustomTrail {
+    pub fn new(name: &str) -> CustomTrail {
+        let descriptor = custom_vfx(name);
+        if descriptor.is_none() {
+            warn!("The cursor vfx {name:?} is not registered");
+        }
+        CustomTrail {
+            name: name.to_string(),
+            descriptor,
+            particles: vec![],
+            previous_cursor_dest: Point::new(0.0, 0.0),
+            pending_particles: 0.0,
+            rng: RngState::new(),
+        }
+    }
+
+    fn particle_speed(&mut self, descriptor: &CustomVfxDescriptor, cell_height: f32) -> Point {
+        let speed = descriptor.speed * cell_height;
+        let spread = self.rng.next_f32() - 0.5;
+        match descriptor.motion {
+            VfxMotion::Static => Point::new(0.0, 0.0),
+            VfxMotion::Drift | VfxMotion::Spiral => self.rng.rand_dir_normalized() * speed,
+            VfxMotion::Rise => Point::new(spread * 0.6, -1.0) * speed,
+            VfxMotion::Fall => Point::new(spread * 0.6, 1.0) * speed,
+        }
+    }
+}
+
+/// The particles are kept in the order they were spawned.
+fn drop_oldest_particles(particles: &mut Vec<CustomParticle>) {
+    if particles.len() > MAX_PARTICLES {
+        particles.drain(..particles.len() - MAX_PARTICLES);
+    }
+}
+
+impl CursorVfx for CustomTrail {
+    fn update(
+        &mut self,
+        _settings: &CursorSettings,
+        current_cursor_dest: Point,
+        cursor_dimensions: Point,
+        immediate_movement: bool,
+        dt: f32,
+    ) -> bool {
+        self.descriptor = custom_vfx(&self.name);
+        let Some(descriptor) = self.descriptor.clone() else {
+            self.particles.clear();
+            return false;
+        };
+
+        self.particles.retain_mut(|particle| {
+            particle.lifetime -= dt;
+            particle.pos += particle.speed * dt;
+            particle.speed = rotate_vec(particle.speed, dt * particle.rotation_speed);
+            particle.lifetime > 0.0
+        });
+
+        if current_cursor_dest != self.previous_cursor_dest {
+            if !immediate_movement {
+                let travel = current_cursor_dest - self.previous_cursor_dest;
+                let cells = travel.length() / cursor_dimensions.y;
+                self.pending_particles += cells * descriptor.spawn_rate;
+                let particle_count = self.pending_particles as usize;
+                self.pending_particles -= particle_count as f32;
+                // Only the newest ones would be kept
+                let particle_count = particle_count.min(MAX_PARTICLES);
+
+                let rotation_speed = match descriptor.motion {
+                    VfxMotion::Spiral => 2.0 * PI * descriptor.curl,
+                    _ => 0.0,
+                };
+                for _ in 0..particle_count {
+                    let pos = self.previous_cursor_dest + travel * self.rng.next_f32();
+                    let speed = self.particle_speed(&descriptor, cursor_dimensions.y);
+                    self.particles.push(CustomParticle {
+                        pos,
+                        speed,
+                        rotation_speed,
+                        lifetime: descriptor.lifetime,
+                    });
+                }
+            }
+            self.previous_cursor_dest = current_cursor_dest;
+        }
+        drop_oldest_particles(&mut self.particles);
+
+        !self.particles.is_empty()
+    }
+
+    fn restart(&mut self, _position: Point) {}
+
+    fn render(
+        &self,
+        settings: &CursorSettings,
+        canvas: &Canvas,
+        grid_renderer: &mut GridRenderer,
+        cursor: &Cursor,
+    ) {
+        let Some(descriptor) = self.descriptor.as_ref() else {
+            return;
+        };
+        let colors = &grid_renderer.default_style.colors;
+        let base_color = match descriptor.color {
+            VfxColor::Cursor => cursor.background(colors).to_color(),
+            VfxColor::Foreground => colors
+                .foreground
+                .map_or(Color::WHITE, |color| color.to_color()),
+            VfxColor::Rainbow | VfxColor::Fixed(_) => Color::WHITE,
+        };
+        let size = grid_renderer.font_dimensions.width as f32 * descriptor.size;
+
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::SrcOver);
+        paint.set_anti_alias(true);
+
+        for particle in &self.particles {
+            let remaining = particle.lifetime / descriptor.lifetime;
+            let alpha = (remaining * settings.vfx_opacity).clamp(0.0, 255.0) as u8;
+            let color = match descriptor.color {
+                VfxColor::Rainbow => {
+                    HSV::from((360.0 * (1.0 - remaining), 1.0, 1.0)).to_color(alpha)
+                }
+                VfxColor::Fixed(color) => color.with_a(alpha),
+                _ => base_color.with_a(alpha),
+            };
+            paint.set_color(color);
+
+            let radius = size * remaining.sqrt();
+            let rect = Rect::from_xywh(
+                particle.pos.x - radius * 0.5,
+                particle.pos.y - radius * 0.5,
+                radius,
+                radius,
+            );
+            canvas.draw_oval(rect, &paint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(fields: &[(&str, Value)]) -> Value {
+        Value::Map(
+            fields
+                .iter()
+                .map(|(key, value)| (Value::from(*key), value.clone()))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_custom_vfx_descriptor() {
+        let (name, descriptor) = CustomVfxDescriptor::parse(&table(&[
+            ("name", Value::from("embers")),
+            ("spawn_rate", Value::from(4)),
+            ("lifetime", Value::from(0.8)),
+            ("color_fn", Value::from("#ff8000")),
+            ("motion", Value::from("rise")),
+        ]))
+        .unwrap();
+        assert_eq!(name, "embers");
+        assert_eq!(descriptor.spawn_rate, 4.0);
+        assert_eq!(descriptor.lifetime, 0.8);
+        assert_eq!(
+            descriptor.color,
+            VfxColor::Fixed(Color::from_rgb(0xFF, 0x80, 0x00))
+        );
+        assert_eq!(descriptor.motion, VfxMotion::Rise);
+        assert_eq!(descriptor.speed, 1.0);
+    }
+
+    #[test]
+    fn test_oldest_particles_are_dropped() {
+        let mut particles: Vec<_> = (0..MAX_PARTICLES + 10)
+            .map(|i| CustomParticle {
+                pos: Point::new(i as f32, 0.0),
+                speed: Point::new(0.0, 0.0),
+                rotation_speed: 0.0,
+                lifetime: 1.0,
+            })
+            .collect();
+        drop_oldest_particles(&mut particles);
+        assert_eq!(particles.len(), MAX_PARTICLES);
+        assert_eq!(particles[0].pos.x, 10.0);
+    }
+
+    #[test]
+    fn test_invalid_custom_vfx_descriptors_are_rejected() {
+        let valid = [
+            ("name", Value::from("embers")),
+            ("spawn_rate", Value::from(4)),
+            ("lifetime", Value::from(0.8)),
+        ];
+        assert!(CustomVfxDescriptor::parse(&table(&valid)).is_ok());
+        assert!(CustomVfxDescriptor::parse(&Value::from("embers")).is_err());
+        assert!(CustomVfxDescriptor::parse(&table(&valid[1..])).is_err());
+        assert!(CustomVfxDescriptor::parse(&table(&valid[..2])).is_err());
+
+        let with = |field: (&'static str, Value)| {
+            let mut fields = valid.to_vec();
+            fields.push(field);
+            CustomVfxDescriptor::parse(&table(&fields))
+        };
+        assert!(with(("name", Value::from("railgun"))).is_err());
+        assert!(with(("spawn_rate", Value::from(1000))).is_err());
+        assert!(with(("lifetime", Value::from(-1))).is_err());
+        assert!(with(("speed", Value::from("fast"))).is_err());
+        assert!(with(("color_fn", Value::from("#ff80"))).is_err());
+        assert!(with(("motion", Value::from("teleport"))).is_err());
+        assert!(with(("shader", Value::from("evil"))).is_err());
+    }
+}
@@ -1,22 +1,26 @@
 mod blink;
 mod cursor_vfx;
+mod custom_vfx;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
+use log::error;
+use rmpv::Value;
 use skia_safe::{op, Canvas, Paint, Path, Point};
 use winit::event::{Event, WindowEvent};
 
 use crate::{
     bridge::EditorMode,
-    editor::{Cursor, CursorShape},
+    editor::{Cursor, CursorShape, Style},
     profiling::{tracy_plot, tracy_zone},
     renderer::animation_utils::*,
-    renderer::{GridRenderer, RenderedWindow},
+    renderer::{parse_color, GridRenderer, RenderedWindow},
     settings::{ParseFromValue, SETTINGS},
     window::{ShouldRender, UserEvent},
 };
 
 use blink::*;
+pub use custom_vfx::register_cursor_vfx;
 
 const DEFAULT_CELL_PERCENTAGE: f32 = 1.0 / 8.0;
 
@@ -32,7 +36,10 @@ pub struct CursorSettings {
     animate_in_insert_mode: bool,
     animate_command_line: bool,
     trail_size: f32,
+    shape_animation_length: f32,
+    unfocused_style: UnfocusedCursorStyle,
     unfocused_outline_width: f32,
+    unfocused_outline_color: String,
     smooth_blink: bool,
 
     vfx_mode: cursor_vfx::VfxMode,
@@ -42,6 +49,16 @@ pub struct CursorSettings {
     vfx_particle_speed: f32,
     vfx_particle_phase: f32,
     vfx_particle_curl: f32,
+
+    animation_length_insert: ModeOverride<f32>,
+    animation_length_normal: ModeOverride<f32>,
+    animation_length_visual: ModeOverride<f32>,
+    trail_size_insert: ModeOverride<f32>,
+    trail_size_normal: ModeOverride<f32>,
+    trail_size_visual: ModeOverride<f32>,
+    vfx_mode_insert: ModeOverride<cursor_vfx::VfxMode>,
+    vfx_mode_normal: ModeOverride<cursor_vfx::VfxMode>,
+    vfx_mode_visual: ModeOverride<cursor_vfx::VfxMode>,
 }
 
 impl Default for CursorSettings {
@@ -53,7 +70,10 @@ impl Default for CursorSettings {
             animate_in_insert_mode: true,
             animate_command_line: true,
             trail_size: 0.7,
+            shape_animation_length: 0.08,
+            unfocused_style: UnfocusedCursorStyle::Outline,
             unfocused_outline_width: 1.0 / 8.0,
+            unfocused_outline_color: String::new(),
             smooth_blink: false,
             vfx_mode: cursor_vfx::VfxMode::Disabled,
             vfx_opacity: 200.0,
@@ -62,10 +82,125 @@ impl Default for CursorSettings {
             vfx_particle_speed: 10.0,
             vfx_particle_phase: 1.5,
             vfx_particle_curl: 1.0,
+            animation_length_insert: ModeOverride::default(),
+            animation_length_normal: ModeOverride::default(),
+            animation_length_visual: ModeOverride::default(),
+            trail_size_insert: ModeOverride::default(),
+            trail_size_normal: ModeOverride::default(),
+            trail_size_visual: ModeOverride::default(),
+            vfx_mode_insert: ModeOverride::default(),
+            vfx_mode_normal: ModeOverride::default(),
+            vfx_mode_visual: ModeOverride::default(),
+        }
+    }
+}
+
+impl CursorSettings {
+    /// The settings with the overrides of the mode applied. Replace mode uses the insert mode
+    /// settings, and the other modes use the settings for all modes.
+    fn for_mode(&self, mode: &EditorMode) -> CursorSettings {
+        let (animation_length, trail_size, vfx_mode) = match mode {
+            EditorMode::Insert | EditorMode::Replace => (
+                &self.animation_length_insert,
+                &self.trail_size_insert,
+                &self.vfx_mode_insert,
+            ),
+            EditorMode::Normal => (
+                &self.animation_length_normal,
+                &self.trail_size_normal,
+                &self.vfx_mode_normal,
+            ),
+            EditorMode::Visual => (
+                &self.animation_length_visual,
+                &self.trail_size_visual,
+                &self.vfx_mode_visual,
+            ),
+            _ => return self.clone(),
+        };
+        CursorSettings {
+            animation_length: animation_length.get_or(&self.animation_length),
+            trail_size: trail_size.get_or(&self.trail_size),
+            vfx_mode: vfx_mode.get_or(&self.vfx_mode),
+            ..self.clone()
         }
     }
 }
 
+/// How the cursor is drawn while the window doesn't have focus.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum UnfocusedCursorStyle {
+    /// The block cursor is drawn as an outline, the other shapes are unchanged.
+    #[default]
+    Outline,
+    /// Every shape turns into an outlined block, like in gvim.
+    Hollow,
+    /// The cursor is drawn like when the window has focus.
+    Filled,
+    Hidden,
+}
+
+impl ParseFromValue for UnfocusedCursorStyle {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(style) = value.as_str() {
+            *self = match style {
+                "outline" => UnfocusedCursorStyle::Outline,
+                "hollow" => UnfocusedCursorStyle::Hollow,
+                "filled" => UnfocusedCursorStyle::Filled,
+                "hidden" => UnfocusedCursorStyle::Hidden,
+                value => {
+                    error!(
+                        "Expected outline, hollow, filled or hidden, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Expected an unfocused cursor style string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<UnfocusedCursorStyle> for Value {
+    fn from(style: UnfocusedCursorStyle) -> Self {
+        match style {
+            UnfocusedCursorStyle::Outline => Value::from("outline"),
+            UnfocusedCursorStyle::Hollow => Value::from("hollow"),
+            UnfocusedCursorStyle::Filled => Value::from("filled"),
+            UnfocusedCursorStyle::Hidden => Value::from("hidden"),
+        }
+    }
+}
+
+/// A cursor setting for one mode, which uses the setting for all modes while it's nil.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ModeOverride<T>(Option<T>);
+
+impl<T: Clone> ModeOverride<T> {
+    fn get_or(&self, default: &T) -> T {
+        self.0.as_ref().unwrap_or(default).clone()
+    }
+}
+
+impl<T: ParseFromValue + Default> ParseFromValue for ModeOverride<T> {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_nil() {
+            self.0 = None;
+        } else {
+            self.0.parse_from_value(value);
+        }
+    }
+}
+
+impl<T: Into<Value>> From<ModeOverride<T>> for Value {
+    fn from(setting: ModeOverride<T>) -> Self {
+        setting.0.map_or(Value::Nil, Into::into)
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct Corner {
     start_position: Point,
@@ -74,6 +209,10 @@ pub struct Corner {
     previous_destination: Point,
     length_multiplier: f32,
     t: f32,
+    // The relative positions of the shape change animation, which is separate from the movement
+    shape_start: Point,
+    shape_destination: Point,
+    shape_t: f32,
 }
 
 impl Corner {
@@ -85,7 +224,40 @@ impl Corner {
             previous_destination: Point::new(-1000.0, -1000.0),
             length_multiplier: 1.0,
             t: 0.0,
+            shape_start: Point::new(0.0, 0.0),
+            shape_destination: Point::new(0.0, 0.0),
+            shape_t: 1.0,
+        }
+    }
+
+    /// Moves the corner to its position in the new cursor shape, animated or immediately.
+    fn set_shape_destination(&mut self, relative_destination: Point, animate: bool) {
+        self.shape_start = if animate {
+            self.relative_position
+        } else {
+            relative_destination
+        };
+        self.shape_destination = relative_destination;
+        self.shape_t = 0.0;
+    }
+
+    /// Interpolates the relative position between the shapes, returning true while animating.
+    fn update_shape(&mut self, animation_length: f32, dt: f32, immediate: bool) -> bool {
+        if self.shape_t >= 1.0 {
+            return false;
         }
+        self.shape_t = if immediate || animation_length <= 0.0 {
+            1.0
+        } else {
+            (self.shape_t + dt / animation_length).min(1.0)
+        };
+        self.relative_position = ease_point(
+            ease_out_quad,
+            self.shape_start,
+            self.shape_destination,
+            self.shape_t,
+        );
+        true
     }
 
     pub fn update(
@@ -95,7 +267,11 @@ impl Corner {
         destination: Point,
         dt: f32,
         immediate_movement: bool,
+        immediate_shape: bool,
     ) -> bool {
+        let shape_animating =
+            self.update_shape(settings.shape_animation_length, dt, immediate_shape);
+
         if destination != self.previous_destination {
             self.t = 0.0;
             self.start_position = self.current_position;
@@ -110,11 +286,6 @@ impl Corner {
             }
         }
 
-        // Check first if animation's over
-        if (self.t - 1.0).abs() < std::f32::EPSILON {
-            return false;
-        }
-
         // Calculate window-space destination for corner
         let relative_scaled_position: Point = (
             self.relative_position.x * font_dimensions.x,
@@ -124,6 +295,14 @@ impl Corner {
 
         let corner_destination = destination + relative_scaled_position;
 
+        // Check first if animation's over, the shape may still be changing in place
+        if (self.t - 1.0).abs() < f32::EPSILON {
+            if shape_animating {
+                self.current_position = corner_destination;
+            }
+            return shape_animating;
+        }
+
         if immediate_movement {
             self.t = 1.0;
             self.current_position = corner_destination;
@@ -197,7 +376,7 @@ impl CursorRenderer {
             previous_vfx_mode: cursor_vfx::VfxMode::Disabled,
             window_has_focus: true,
         };
-        renderer.set_cursor_shape(&CursorShape::Block, DEFAULT_CELL_PERCENTAGE);
+        renderer.set_cursor_shape(&CursorShape::Block, DEFAULT_CELL_PERCENTAGE, false);
         renderer
     }
 
@@ -218,42 +397,38 @@ impl CursorRenderer {
         self.cursor = new_cursor;
     }
 
-    fn set_cursor_shape(&mut self, cursor_shape: &CursorShape, cell_percentage: f32) {
-        self.corners = self
-            .corners
-            .clone()
-            .into_iter()
-            .enumerate()
-            .map(|(i, corner)| {
-                let (x, y) = STANDARD_CORNERS[i];
-
-                Corner {
-                    relative_position: match cursor_shape {
-                        CursorShape::Block => (x, y).into(),
-                        // Transform the x position so that the right side is translated over to
-                        // the BAR_WIDTH position
-                        CursorShape::Vertical => ((x + 0.5) * cell_percentage - 0.5, y).into(),
-                        // Do the same as above, but flip the y coordinate and then flip the result
-                        // so that the horizontal bar is at the bottom of the character space
-                        // instead of the top.
-                        CursorShape::Horizontal => {
-                            (x, -((-y + 0.5) * cell_percentage - 0.5)).into()
-                        }
-                    },
-                    t: 0.0,
-                    start_position: corner.current_position,
-                    ..corner
-                }
-            })
-            .collect::<Vec<Corner>>();
+    fn set_cursor_shape(
+        &mut self,
+        cursor_shape: &CursorShape,
+        cell_percentage: f32,
+        animate: bool,
+    ) {
+        for (corner, &(x, y)) in self.corners.iter_mut().zip(STANDARD_CORNERS) {
+            corner.set_shape_destination(
+                match cursor_shape {
+                    CursorShape::Block => (x, y).into(),
+                    // Transform the x position so that the right side is translated over to
+                    // the BAR_WIDTH position
+                    CursorShape::Vertical => ((x + 0.5) * cell_percentage - 0.5, y).into(),
+                    // Do the same as above, but flip the y coordinate and then flip the result
+                    // so that the horizontal bar is at the bottom of the character space
+                    // instead of the top.
+                    CursorShape::Horizontal => (x, -((-y + 0.5) * cell_percentage - 0.5)).into(),
+                },
+                animate,
+            );
+        }
     }
 
     pub fn update_cursor_destination(
         &mut self,
         (font_width, font_height): (u64, u64),
         windows: &HashMap<u64, RenderedWindow>,
+        local_echo_width: u64,
     ) {
         let (cursor_grid_x, cursor_grid_y) = self.cursor.grid_position;
+        // Move the cursor past any locally echoed text, which Neovim has not confirmed yet
+        let cursor_grid_x = cursor_grid_x + local_echo_width;
 
         if let Some(window) = windows.get(&self.cursor.parent_window_id) {
             let grid_x = cursor_grid_x as f32 + window.grid_current_position.x;
@@ -286,9 +461,23 @@ impl CursorRenderer {
         self.blink_status.update_status(&self.cursor)
     }
 
+    /// The style of the cursor while the window doesn't have focus, or `None` when it has.
+    fn unfocused_style(&self, settings: &CursorSettings) -> Option<UnfocusedCursorStyle> {
+        (!self.window_has_focus).then_some(settings.unfocused_style)
+    }
+
+    /// The shape drawn, which is a block while unfocused with the hollow style.
+    fn displayed_shape(&self, settings: &CursorSettings) -> CursorShape {
+        match self.unfocused_style(settings) {
+            Some(UnfocusedCursorStyle::Hollow) => CursorShape::Block,
+            _ => self.cursor.shape.clone(),
+        }
+    }
+
     pub fn draw(&mut self, grid_renderer: &mut GridRenderer, canvas: &Canvas) {
         tracy_zone!("cursor_draw");
         let settings = SETTINGS.get::<CursorSettings>();
+        let unfocused_style = self.unfocused_style(&settings);
         let render = self.blink_status.should_render() || settings.smooth_blink;
         let opacity = match settings.smooth_blink {
             true => self.blink_status.opacity(),
@@ -301,7 +490,8 @@ impl CursorRenderer {
 
         let character = self.cursor.grid_cell.0.clone();
 
-        if !(self.cursor.enabled && render) {
+        if !(self.cursor.enabled && render) || unfocused_style == Some(UnfocusedCursorStyle::Hidden)
+        {
             return;
         }
         // Draw Background
@@ -312,11 +502,19 @@ impl CursorRenderer {
             .with_a((opacity * alpha) as u8);
         paint.set_color(background_color);
 
-        let path = if self.window_has_focus || self.cursor.shape != CursorShape::Block {
-            self.draw_rectangle(canvas, &paint)
-        } else {
+        let outlined = match unfocused_style {
+            Some(UnfocusedCursorStyle::Outline) => self.cursor.shape == CursorShape::Block,
+            Some(UnfocusedCursorStyle::Hollow) => true,
+            _ => false,
+        };
+        let path = if outlined {
+            if let Some(color) = parse_color(&settings.unfocused_outline_color) {
+                paint.set_color(color.with_a((opacity * alpha) as u8));
+            }
             let outline_width = settings.unfocused_outline_width * grid_renderer.em_size;
             self.draw_rectangular_outline(canvas, &paint, outline_width)
+        } else {
+            self.draw_rectangle(canvas, &paint)
         };
 
         // Draw foreground
@@ -330,13 +528,17 @@ impl CursorRenderer {
         canvas.save();
         canvas.clip_path(&path, None, Some(false));
 
-        let y_adjustment = grid_renderer.shaper.y_adjustment();
+        let y_adjustment = grid_renderer.shaper.lock().y_adjustment();
         let style = &self.cursor.grid_cell.1;
         let coarse_style = style.as_ref().map(|style| style.into()).unwrap_or_default();
 
-        let blobs = &grid_renderer.shaper.shape_cached(character, coarse_style);
-
-        for blob in blobs.iter() {
+        // A single character never forms a ligature
+        for blob in grid_renderer
+            .shaper
+            .lock()
+            .shape_cached(character, coarse_style, true)
+            .iter()
+        {
             canvas.draw_text_blob(
                 blob,
                 (self.destination.x, self.destination.y + y_adjustment as f32),
@@ -356,13 +558,19 @@ impl CursorRenderer {
         current_mode: &EditorMode,
         grid_renderer: &GridRenderer,
         dt: f32,
+        animations_enabled: bool,
     ) -> bool {
         tracy_zone!("cursor_animate");
-        let settings = SETTINGS.get::<CursorSettings>();
+        let settings = SETTINGS.get::<CursorSettings>().for_mode(current_mode);
 
-        if settings.vfx_mode != self.previous_vfx_mode {
-            self.cursor_vfx = cursor_vfx::new_cursor_vfx(&settings.vfx_mode);
-            self.previous_vfx_mode = settings.vfx_mode.clone();
+        let vfx_mode = if animations_enabled {
+            settings.vfx_mode.clone()
+        } else {
+            cursor_vfx::VfxMode::Disabled
+        };
+        if vfx_mode != self.previous_vfx_mode {
+            self.cursor_vfx = cursor_vfx::new_cursor_vfx(&vfx_mode);
+            self.previous_vfx_mode = vfx_mode;
         }
 
         let mut cursor_width = grid_renderer.font_dimensions.width;
@@ -383,13 +591,17 @@ impl CursorRenderer {
 
         let center_destination = self.destination + cursor_dimensions * 0.5;
 
-        if self.previous_cursor_shape.as_ref() != Some(&self.cursor.shape) {
-            self.previous_cursor_shape = Some(self.cursor.shape.clone());
+        let shape = self.displayed_shape(&settings);
+        if self.previous_cursor_shape.as_ref() != Some(&shape) {
+            // The first shape isn't animated, since there's nothing to animate from
+            let animate_shape = self.previous_cursor_shape.is_some();
+            self.previous_cursor_shape = Some(shape.clone());
             self.set_cursor_shape(
-                &self.cursor.shape.clone(),
+                &shape,
                 self.cursor
                     .cell_percentage
                     .unwrap_or(DEFAULT_CELL_PERCENTAGE),
+                animate_shape,
             );
 
             if let Some(vfx) = self.cursor_vfx.as_mut() {
@@ -400,7 +612,8 @@ impl CursorRenderer {
         let mut animating = false;
 
         if !center_destination.is_zero() {
-            let immediate_movement = !settings.animate_in_insert_mode && in_insert_mode
+            let immediate_movement = !animations_enabled
+                || !settings.animate_in_insert_mode && in_insert_mode
                 || !settings.animate_command_line && !changed_to_from_cmdline;
             for corner in self.corners.iter_mut() {
                 let corner_animating = corner.update(
@@ -409,6 +622,7 @@ impl CursorRenderer {
                     center_destination,
                     dt,
                     immediate_movement,
+                    !animations_enabled,
                 );
 
                 animating |= corner_animating;
@@ -485,7 +699,77 @@ impl CursorRenderer {
         path
     }
 
+    pub fn parent_window_id(&self) -> u64 {
+        self.cursor.parent_window_id
+    }
+
     pub fn get_current_position(&self) -> Point {
         self.destination
     }
+
+    /// The highlight of the cell under the cursor.
+    pub fn cell_style(&self) -> Option<Arc<Style>> {
+        self.cursor.grid_cell.1.clone()
+    }
+
+    /// The center of the cursor as it's currently drawn, following the animation.
+    pub fn get_animated_center(&self) -> Point {
+        let sum = self.corners.iter().fold(Point::default(), |sum, corner| {
+            sum + corner.current_position
+        });
+        sum * (1.0 / self.corners.len() as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mode_overrides_fall_back_to_the_setting_for_all_modes() {
+        let mut settings = CursorSettings::default();
+        settings
+            .animation_length_insert
+            .parse_from_value(Value::from(0.0));
+        settings
+            .trail_size_visual
+            .parse_from_value(Value::from(0.2));
+
+        let insert = settings.for_mode(&EditorMode::Insert);
+        assert_eq!(insert.animation_length, 0.0);
+        assert_eq!(insert.trail_size, settings.trail_size);
+        assert_eq!(
+            settings.for_mode(&EditorMode::Replace).animation_length,
+            0.0
+        );
+        assert_eq!(settings.for_mode(&EditorMode::Visual).trail_size, 0.2);
+        assert_eq!(
+            settings.for_mode(&EditorMode::Normal).animation_length,
+            settings.animation_length
+        );
+
+        settings
+            .animation_length_insert
+            .parse_from_value(Value::Nil);
+        assert_eq!(
+            settings.for_mode(&EditorMode::Insert).animation_length,
+            settings.animation_length
+        );
+    }
+
+    #[test]
+    fn test_corner_interpolates_between_shapes() {
+        let mut corner = Corner::new();
+        corner.set_shape_destination(Point::new(0.5, 0.5), false);
+        assert!(corner.update_shape(0.1, 0.0, false));
+        assert_eq!(corner.relative_position, Point::new(0.5, 0.5));
+
+        corner.set_shape_destination(Point::new(-0.375, 0.5), true);
+        assert!(corner.update_shape(0.1, 0.05, false));
+        let x = corner.relative_position.x;
+        assert!(x < 0.5 && x > -0.375);
+        assert!(corner.update_shape(0.1, 0.05, false));
+        assert_eq!(corner.relative_position, Point::new(-0.375, 0.5));
+        assert!(!corner.update_shape(0.1, 0.05, false));
+    }
 }
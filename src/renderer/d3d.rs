@@ -8,7 +8,7 @@ use skia_safe::{
         BackendRenderTarget, DirectContext, FlushInfo, Protected, SurfaceOrigin, SyncCpu,
     },
     surface::BackendSurfaceAccess,
-    Canvas, ColorType, Surface,
+    Canvas, ColorSpace, ColorType, Surface,
 };
 use winapi::{
     shared::{
@@ -16,12 +16,21 @@ use winapi::{
             IDXGIAdapter1, DXGI_ADAPTER_DESC1, DXGI_ADAPTER_FLAG_SOFTWARE,
             DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT, DXGI_SWAP_EFFECT_FLIP_DISCARD,
         },
-        dxgi1_2::{DXGI_ALPHA_MODE_UNSPECIFIED, DXGI_SCALING_NONE, DXGI_SWAP_CHAIN_DESC1},
+        dxgi1_2::{
+            DXGI_ALPHA_MODE_PREMULTIPLIED, DXGI_ALPHA_MODE_UNSPECIFIED, DXGI_SCALING_NONE,
+            DXGI_SCALING_STRETCH, DXGI_SWAP_CHAIN_DESC1,
+        },
         dxgi1_3::{CreateDXGIFactory2, DXGI_CREATE_FACTORY_DEBUG},
         dxgi1_4::{IDXGIFactory4, IDXGISwapChain3},
         dxgi1_6::{IDXGIFactory6, DXGI_GPU_PREFERENCE_HIGH_PERFORMANCE},
-        dxgiformat::DXGI_FORMAT_R8G8B8A8_UNORM,
-        dxgitype::{DXGI_SAMPLE_DESC, DXGI_USAGE_RENDER_TARGET_OUTPUT},
+        dxgiformat::{
+            DXGI_FORMAT_R16G16B16A16_FLOAT, DXGI_FORMAT_R8G8B8A8_UNORM,
+            DXGI_FORMAT_R8G8B8A8_UNORM_SRGB,
+        },
+        dxgitype::{
+            DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709, DXGI_SAMPLE_DESC,
+            DXGI_USAGE_RENDER_TARGET_OUTPUT,
+        },
         guiddef::REFIID,
         windef::HWND,
         winerror::SUCCEEDED,
@@ -34,6 +43,9 @@ use winapi::{
         },
         d3d12sdklayers::ID3D12Debug,
         d3dcommon::{D3D_FEATURE_LEVEL, D3D_FEATURE_LEVEL_11_0},
+        dcomp::{
+            DCompositionCreateDevice, IDCompositionDevice, IDCompositionTarget, IDCompositionVisual,
+        },
         handleapi::CloseHandle,
         synchapi::{CreateEventA as CreateEvent, WaitForSingleObjectEx},
         unknwnbase::IUnknown,
@@ -42,13 +54,17 @@ use winapi::{
     },
     Interface,
 };
-use winit::{event_loop::EventLoopProxy, window::Window};
+use winit::{dpi::PhysicalSize, event_loop::EventLoopProxy, window::Window};
 use wio::com::ComPtr;
 
-use super::{vsync::VSyncWinSwapChain, SkiaRenderer, VSync};
+use super::{vsync::VSyncWinSwapChain, OutputColorSpace, SkiaRenderer, SurfaceOptions, VSync};
 #[cfg(feature = "gpu_profiling")]
 use crate::profiling::{d3d::create_d3d_gpu_context, GpuCtx};
-use crate::{profiling::tracy_gpu_zone, window::UserEvent};
+use crate::{
+    profiling::tracy_gpu_zone,
+    settings::SETTINGS,
+    window::{UserEvent, WindowSettings, WindowsBackdrop},
+};
 
 const D3D_FEATUREL_LEVEL: D3D_FEATURE_LEVEL = D3D_FEATURE_LEVEL_11_0;
 
@@ -132,11 +148,46 @@ fn get_hardware_adapter(factory: &ComPtr<IDXGIFactory4>) -> Result<ComPtr<IDXGIA
     }
 }
 
+/// Presents the swap chain through DirectComposition, which is the only way for a flip model swap
+/// chain to have an alpha channel, so that the backdrop material shows through.
+struct Composition {
+    _device: ComPtr<IDCompositionDevice>,
+    _target: ComPtr<IDCompositionTarget>,
+    _visual: ComPtr<IDCompositionVisual>,
+}
+
+impl Composition {
+    fn new(hwnd: HWND, swap_chain: &ComPtr<IDXGISwapChain3>) -> Self {
+        let device: ComPtr<IDCompositionDevice> =
+            call_com_fn(|device, id| unsafe { DCompositionCreateDevice(null(), id, device) })
+                .expect("Failed to create the DirectComposition device");
+        let target: ComPtr<IDCompositionTarget> = call_com_fn(|target, _| unsafe {
+            device.CreateTargetForHwnd(hwnd, true.into(), target)
+        })
+        .expect("Failed to create the DirectComposition target");
+        let visual: ComPtr<IDCompositionVisual> =
+            call_com_fn(|visual, _| unsafe { device.CreateVisual(visual) })
+                .expect("Failed to create the DirectComposition visual");
+        unsafe {
+            visual.SetContent(swap_chain.as_raw() as *mut IUnknown);
+            target.SetRoot(visual.as_raw());
+            device.Commit();
+        }
+        Self {
+            _device: device,
+            _target: target,
+            _visual: visual,
+        }
+    }
+}
+
 pub struct D3DSkiaRenderer {
     gr_context: DirectContext,
     swap_chain: ComPtr<IDXGISwapChain3>,
     swap_chain_desc: DXGI_SWAP_CHAIN_DESC1,
     swap_chain_waitable: HANDLE,
+    scrgb: bool,
+    surface_options: SurfaceOptions,
     pub command_queue: ComPtr<ID3D12CommandQueue>,
     buffers: Vec<ComPtr<ID3D12Resource>>,
     surfaces: Vec<Surface>,
@@ -148,11 +199,21 @@ pub struct D3DSkiaRenderer {
     _backend_context: BackendContext,
     pub device: ComPtr<ID3D12Device>,
     _adapter: ComPtr<IDXGIAdapter1>,
+    composition: Option<Composition>,
     window: Window,
 }
 
 impl D3DSkiaRenderer {
-    pub fn new(window: Window) -> Self {
+    pub fn new(window: Window, surface_options: &SurfaceOptions) -> Self {
+        // 8 bit swap chains are already treated as sRGB, and Display P3 is covered by scRGB
+        let scrgb = match surface_options.color_space {
+            OutputColorSpace::Default | OutputColorSpace::Srgb => false,
+            OutputColorSpace::DisplayP3 => {
+                log::warn!("Display P3 is not supported with Direct3D, using scRGB instead");
+                true
+            }
+            OutputColorSpace::ScRgb => true,
+        };
         let mut factory_flags = 0;
 
         let debug_controller: ComPtr<ID3D12Debug> = call_com_fn(|debug_controller, id| unsafe {
@@ -191,11 +252,22 @@ impl D3DSkiaRenderer {
             call_com_fn(|queue, id| unsafe { device.CreateCommandQueue(&queue_desc, id, queue) })
                 .expect("Failed to create the Direct3D command queue");
 
+        // The backdrop is only read when the window is created, since it needs a swap chain with an
+        // alpha channel. It's not possible with subpixel antialiasing, which needs an opaque one.
+        let composited = SETTINGS.get::<WindowSettings>().windows_backdrop != WindowsBackdrop::None
+            && !surface_options.opaque();
+        // Composition swap chains don't take the size from the window
+        let size = window.inner_size();
+
         // Describe and create the swap chain.
         let swap_chain_desc = DXGI_SWAP_CHAIN_DESC1 {
-            Width: 0,
-            Height: 0,
-            Format: DXGI_FORMAT_R8G8B8A8_UNORM,
+            Width: if composited { size.width.max(1) } else { 0 },
+            Height: if composited { size.height.max(1) } else { 0 },
+            Format: if scrgb {
+                DXGI_FORMAT_R16G16B16A16_FLOAT
+            } else {
+                DXGI_FORMAT_R8G8B8A8_UNORM
+            },
             Stereo: false.into(),
             SampleDesc: DXGI_SAMPLE_DESC {
                 Count: 1,
@@ -203,9 +275,17 @@ impl D3DSkiaRenderer {
             },
             BufferUsage: DXGI_USAGE_RENDER_TARGET_OUTPUT,
             BufferCount: 2,
-            Scaling: DXGI_SCALING_NONE,
+            Scaling: if composited {
+                DXGI_SCALING_STRETCH
+            } else {
+                DXGI_SCALING_NONE
+            },
             SwapEffect: DXGI_SWAP_EFFECT_FLIP_DISCARD,
-            AlphaMode: DXGI_ALPHA_MODE_UNSPECIFIED,
+            AlphaMode: if composited {
+                DXGI_ALPHA_MODE_PREMULTIPLIED
+            } else {
+                DXGI_ALPHA_MODE_UNSPECIFIED
+            },
             Flags: DXGI_SWAP_CHAIN_FLAG_FRAME_LATENCY_WAITABLE_OBJECT,
         };
 
@@ -216,21 +296,39 @@ impl D3DSkiaRenderer {
         };
 
         let swap_chain: ComPtr<IDXGISwapChain3> = call_com_fn(|swap_chain, _| unsafe {
-            dxgi_factory.CreateSwapChainForHwnd(
-                command_queue.as_raw() as *mut IUnknown,
-                hwnd as HWND,
-                &swap_chain_desc,
-                null(),
-                null_mut(),
-                swap_chain,
-            )
+            if composited {
+                dxgi_factory.CreateSwapChainForComposition(
+                    command_queue.as_raw() as *mut IUnknown,
+                    &swap_chain_desc,
+                    null_mut(),
+                    swap_chain,
+                )
+            } else {
+                dxgi_factory.CreateSwapChainForHwnd(
+                    command_queue.as_raw() as *mut IUnknown,
+                    hwnd as HWND,
+                    &swap_chain_desc,
+                    null(),
+                    null_mut(),
+                    swap_chain,
+                )
+            }
         })
         .expect("Failed to create the Direct3D swap chain");
+        let composition = composited.then(|| Composition::new(hwnd as HWND, &swap_chain));
 
         unsafe {
             swap_chain.SetMaximumFrameLatency(1);
         };
 
+        // Linear colors with sRGB primaries, which can go outside of the 0-1 range for HDR and
+        // wide gamut displays
+        if scrgb
+            && unsafe { swap_chain.SetColorSpace1(DXGI_COLOR_SPACE_RGB_FULL_G10_NONE_P709) } < 0
+        {
+            log::warn!("Could not set the scRGB color space of the swap chain");
+        }
+
         let swap_chain_waitable = unsafe { swap_chain.GetFrameLatencyWaitableObject() };
         if swap_chain_waitable.is_null() {
             panic!("Failed to get swapchain waitable object");
@@ -264,8 +362,11 @@ impl D3DSkiaRenderer {
             swap_chain,
             swap_chain_desc,
             swap_chain_waitable,
+            scrgb,
+            surface_options: surface_options.clone(),
             gr_context,
             _backend_context: backend_context,
+            composition,
             buffers: Vec::new(),
             surfaces: Vec::new(),
             fence_values,
@@ -327,8 +428,18 @@ impl D3DSkiaRenderer {
         }
     }
 
-    fn setup_surfaces(&mut self) {
+    fn buffer_size(&self) -> PhysicalSize<u32> {
         let size = self.window.inner_size();
+        // Composition swap chains can't have a zero size, which minimized windows report
+        if self.composition.is_some() {
+            PhysicalSize::new(size.width.max(1), size.height.max(1))
+        } else {
+            size
+        }
+    }
+
+    fn setup_surfaces(&mut self) {
+        let size = self.buffer_size();
         let size = (
             size.width.try_into().expect("Could not convert width"),
             size.height.try_into().expect("Could not convert height"),
@@ -342,11 +453,18 @@ impl D3DSkiaRenderer {
                     .expect("Could not get swapchain buffer");
             self.buffers.push(buffer.clone());
 
+            // Flip model swap chains can't use sRGB formats, but the buffers can be rendered to
+            // through an sRGB view, which converts the colors to linear before blending
+            let gamma_correct = self.surface_options.gamma_correct && !self.scrgb;
             let info = TextureResourceInfo {
                 resource: buffer,
                 alloc: None,
                 resource_state: D3D12_RESOURCE_STATE_PRESENT,
-                format: self.swap_chain_desc.Format,
+                format: if gamma_correct {
+                    DXGI_FORMAT_R8G8B8A8_UNORM_SRGB
+                } else {
+                    self.swap_chain_desc.Format
+                },
                 sample_count: self.swap_chain_desc.SampleDesc.Count,
                 level_count: 1,
                 sample_quality_pattern: 0,
@@ -355,13 +473,20 @@ impl D3DSkiaRenderer {
 
             let backend_render_target = BackendRenderTarget::new_d3d(size, &info);
 
+            let (color_type, color_space) = if self.scrgb {
+                (ColorType::RGBAF16, Some(ColorSpace::new_srgb_linear()))
+            } else if gamma_correct {
+                (ColorType::SRGBA8888, Some(ColorSpace::new_srgb()))
+            } else {
+                (ColorType::RGBA8888, None)
+            };
             let surface = wrap_backend_render_target(
                 &mut self.gr_context,
                 &backend_render_target,
                 SurfaceOrigin::TopLeft,
-                ColorType::RGBA8888,
-                None,
-                None,
+                color_type,
+                color_space,
+                self.surface_options.surface_props().as_ref(),
             )
             .expect("Could not create backend render target");
             self.surfaces.push(surface);
@@ -422,7 +547,7 @@ impl SkiaRenderer for D3DSkiaRenderer {
         self.surfaces.clear();
         self.buffers.clear();
 
-        let size = self.window.inner_size();
+        let size = self.buffer_size();
 
         unsafe {
             self.swap_chain.ResizeBuffers(
@@ -436,6 +561,16 @@ impl SkiaRenderer for D3DSkiaRenderer {
         self.setup_surfaces();
     }
 
+    fn set_max_frame_latency(&mut self, latency: Option<u32>) {
+        // The vsync waits for the frame latency waitable object, so this also paces the frames
+        if unsafe { self.swap_chain.SetMaximumFrameLatency(latency.unwrap_or(1)) } < 0 {
+            log::warn!("Could not set the maximum frame latency of the swap chain");
+        }
+    }
+
+    // The swap chain always presents at the vertical blank, only the pacing changes
+    fn set_vsync(&mut self, _enabled: bool) {}
+
     fn create_vsync(&self, proxy: EventLoopProxy<UserEvent>) -> VSync {
         VSync::WindowsSwapChain(VSyncWinSwapChain::new(proxy, self.swap_chain_waitable))
     }
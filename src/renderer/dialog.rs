@@ -0,0 +1,321 @@
+use skia_safe::{Canvas, Color, Paint, Rect};
+
+use crate::{profiling::tracy_zone, renderer::GridRenderer};
+
+/// The most modified buffers listed in the quit dialog.
+const MAX_LISTED_BUFFERS: usize = 8;
+/// The widest line of Neovim output shown in the crash dialog, in characters.
+const MAX_OUTPUT_COLUMNS: usize = 100;
+
+/// Dims the whole window and draws the lines in a box at its center, with the default colors.
+pub fn draw_dialog(canvas: &Canvas, grid_renderer: &mut GridRenderer, lines: &[String], dim: u8) {
+    let size = canvas.base_layer_size();
+    let mut paint = Paint::default();
+    paint.set_color(Color::BLACK.with_a(dim));
+    canvas.draw_rect(Rect::from_wh(size.width as f32, size.height as f32), &paint);
+
+    let font_width = grid_renderer.font_dimensions.width as f32;
+    let font_height = grid_renderer.font_dimensions.height as f32;
+    let y_adjustment = grid_renderer.shaper.lock().y_adjustment() as f32;
+    let colors = &grid_renderer.default_style.colors;
+    let background = colors.background.unwrap().to_color();
+    let foreground = colors.foreground.unwrap().to_color();
+
+    let columns = lines
+        .iter()
+        .map(|line| line.chars().count())
+        .max()
+        .unwrap_or(0)
+        + 4;
+    let box_rect = Rect::from_xywh(
+        ((size.width as f32 - columns as f32 * font_width) / 2.0).round(),
+        ((size.height as f32 - (lines.len() + 2) as f32 * font_height) / 2.0).round(),
+        columns as f32 * font_width,
+        (lines.len() + 2) as f32 * font_height,
+    );
+    paint.set_color(background);
+    canvas.draw_rect(box_rect, &paint);
+
+    paint.set_color(foreground);
+    for (row, line) in lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| !line.is_empty())
+    {
+        let x = box_rect.left + 2.0 * font_width;
+        let y = box_rect.top + (row + 1) as f32 * font_height + y_adjustment;
+        for blob in grid_renderer
+            .shaper
+            .lock()
+            .shape_cached(line.as_str(), Default::default(), false)
+            .iter()
+        {
+            canvas.draw_text_blob(blob, (x, y), &paint);
+        }
+    }
+}
+
+/// What to do with the modified buffers when the window is closed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum QuitChoice {
+    SaveAll,
+    Discard,
+    Cancel,
+}
+
+impl QuitChoice {
+    pub fn name(self) -> &'static str {
+        match self {
+            QuitChoice::SaveAll => "save",
+            QuitChoice::Discard => "discard",
+            QuitChoice::Cancel => "cancel",
+        }
+    }
+}
+
+/// Asks what to do with the modified buffers when the window is closed, since the confirm prompt
+/// of Neovim is easy to miss after clicking the close button.
+#[derive(Default)]
+pub struct QuitDialog {
+    open: bool,
+    modified_buffers: Vec<String>,
+}
+
+impl QuitDialog {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, modified_buffers: Vec<String>) {
+        self.open = true;
+        self.modified_buffers = modified_buffers;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Handles a key in the Neovim notation, returning the choice it makes.
+    pub fn handle_key(&self, key: &str) -> Option<QuitChoice> {
+        match key {
+            "s" | "S" | "<Enter>" | "<CR>" => Some(QuitChoice::SaveAll),
+            "d" | "D" => Some(QuitChoice::Discard),
+            "c" | "C" | "<Esc>" | "<C-c>" => Some(QuitChoice::Cancel),
+            _ => None,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let count = self.modified_buffers.len();
+        let mut lines = vec![
+            if count == 1 {
+                "1 buffer has unsaved changes:".to_string()
+            } else {
+                format!("{count} buffers have unsaved changes:")
+            },
+            String::new(),
+        ];
+        lines.extend(
+            self.modified_buffers
+                .iter()
+                .take(MAX_LISTED_BUFFERS)
+                .map(|name| format!("  {name}")),
+        );
+        if count > MAX_LISTED_BUFFERS {
+            lines.push(format!("  and {} more", count - MAX_LISTED_BUFFERS));
+        }
+        lines.push(String::new());
+        lines.push("[S]ave All   [D]iscard   [C]ancel".to_string());
+        lines
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.open {
+            return;
+        }
+        tracy_zone!("quit_dialog_draw");
+        draw_dialog(canvas, grid_renderer, &self.lines(), 0x66);
+    }
+}
+
+/// What to do after the embedded Neovim crashed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CrashChoice {
+    Restart,
+    Quit,
+}
+
+/// Shown instead of closing the window when the embedded Neovim exits with an error, with the
+/// last lines Neovim wrote to stderr.
+#[derive(Default)]
+pub struct CrashDialog {
+    open: bool,
+    status: String,
+    output: Vec<String>,
+}
+
+impl CrashDialog {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    pub fn open(&mut self, status: String, output: Vec<String>) {
+        self.open = true;
+        self.status = status;
+        self.output = output;
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+    }
+
+    /// Handles a key in the Neovim notation, returning the choice it makes.
+    pub fn handle_key(&self, key: &str) -> Option<CrashChoice> {
+        match key {
+            "r" | "R" | "<Enter>" | "<CR>" => Some(CrashChoice::Restart),
+            "q" | "Q" | "<Esc>" => Some(CrashChoice::Quit),
+            _ => None,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Neovim exited unexpectedly ({})", self.status),
+            String::new(),
+        ];
+        if !self.output.is_empty() {
+            lines.extend(
+                self.output
+                    .iter()
+                    .map(|line| line.chars().take(MAX_OUTPUT_COLUMNS).collect()),
+            );
+            lines.push(String::new());
+        }
+        lines.push("[R]estart and recover   [Q]uit".to_string());
+        lines
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.open {
+            return;
+        }
+        tracy_zone!("crash_dialog_draw");
+        draw_dialog(canvas, grid_renderer, &self.lines(), 0x99);
+    }
+}
+
+/// Whether to open the link of a `LinkDialog`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkChoice {
+    Open,
+    Cancel,
+}
+
+/// Asks before opening a `file://` link, since the links come from the text in the buffers and
+/// terminals, and opening a file can run it.
+#[derive(Default)]
+pub struct LinkDialog {
+    url: Option<String>,
+}
+
+impl LinkDialog {
+    pub fn is_open(&self) -> bool {
+        self.url.is_some()
+    }
+
+    pub fn open(&mut self, url: String) {
+        self.url = Some(url);
+    }
+
+    /// Closes the dialog, returning the link it asked about.
+    pub fn close(&mut self) -> Option<String> {
+        self.url.take()
+    }
+
+    /// Handles a key in the Neovim notation, returning the choice it makes.
+    pub fn handle_key(&self, key: &str) -> Option<LinkChoice> {
+        match key {
+            "o" | "O" => Some(LinkChoice::Open),
+            "c" | "C" | "<Esc>" | "<C-c>" => Some(LinkChoice::Cancel),
+            _ => None,
+        }
+    }
+
+    fn lines(&self) -> Vec<String> {
+        let url = self.url.as_deref().unwrap_or_default();
+        vec![
+            "Open this file link?".to_string(),
+            String::new(),
+            format!(
+                "  {}",
+                url.chars().take(MAX_OUTPUT_COLUMNS).collect::<String>()
+            ),
+            String::new(),
+            "[O]pen   [C]ancel".to_string(),
+        ]
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.is_open() {
+            return;
+        }
+        tracy_zone!("link_dialog_draw");
+        draw_dialog(canvas, grid_renderer, &self.lines(), 0x66);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quit_dialog_lines_and_keys() {
+        let mut dialog = QuitDialog::default();
+        dialog.open((0..10).map(|i| format!("file{i}.rs")).collect());
+        let lines = dialog.lines();
+        assert_eq!(lines[0], "10 buffers have unsaved changes:");
+        assert_eq!(lines[2 + MAX_LISTED_BUFFERS], "  and 2 more");
+
+        assert_eq!(dialog.handle_key("<CR>"), Some(QuitChoice::SaveAll));
+        assert_eq!(dialog.handle_key("D"), Some(QuitChoice::Discard));
+        assert_eq!(dialog.handle_key("<Esc>"), Some(QuitChoice::Cancel));
+        assert_eq!(dialog.handle_key("x"), None);
+    }
+
+    #[test]
+    fn test_link_dialog_keys() {
+        let mut dialog = LinkDialog::default();
+        dialog.open("file:///tmp/run.sh".to_string());
+        assert!(dialog.is_open());
+        assert_eq!(dialog.lines()[2], "  file:///tmp/run.sh");
+        // Enter doesn't open the link, so that it isn't confirmed by accident
+        assert_eq!(dialog.handle_key("<CR>"), None);
+        assert_eq!(dialog.handle_key("o"), Some(LinkChoice::Open));
+        assert_eq!(dialog.handle_key("<Esc>"), Some(LinkChoice::Cancel));
+        assert_eq!(dialog.close(), Some("file:///tmp/run.sh".to_string()));
+        assert!(!dialog.is_open());
+    }
+
+    #[test]
+    fn test_crash_dialog_lines_and_keys() {
+        let mut dialog = CrashDialog::default();
+        dialog.open("signal: 11 (SIGSEGV)".to_string(), Vec::new());
+        assert_eq!(
+            dialog.lines(),
+            vec![
+                "Neovim exited unexpectedly (signal: 11 (SIGSEGV))".to_string(),
+                String::new(),
+                "[R]estart and recover   [Q]uit".to_string(),
+            ]
+        );
+
+        dialog.open("exit status: 1".to_string(), vec!["x".repeat(200)]);
+        let lines = dialog.lines();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2].len(), MAX_OUTPUT_COLUMNS);
+
+        assert_eq!(dialog.handle_key("<CR>"), Some(CrashChoice::Restart));
+        assert_eq!(dialog.handle_key("q"), Some(CrashChoice::Quit));
+        assert_eq!(dialog.handle_key("x"), None);
+    }
+}
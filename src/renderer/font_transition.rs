@@ -0,0 +1,54 @@
+use skia_safe::{Canvas, Image, Paint, Point};
+
+use crate::{
+    dimensions::Dimensions,
+    renderer::animation_utils::{ease, ease_out_quad, lerp},
+};
+
+/// Cross-fades from an image of the last frame rendered with the previous font to the frames
+/// rendered with the new one. The old image is scaled towards the new cell size while it fades out,
+/// so that the text appears to grow or shrink into place.
+pub struct FontTransition {
+    snapshot: Image,
+    origin: Point,
+    old_font_dimensions: Dimensions,
+    t: f32,
+}
+
+impl FontTransition {
+    pub fn new(snapshot: Image, origin: Point, old_font_dimensions: Dimensions) -> Self {
+        Self {
+            snapshot,
+            origin,
+            old_font_dimensions,
+            t: 0.0,
+        }
+    }
+
+    /// Steps the transition and returns true while it's still in progress.
+    pub fn animate(&mut self, dt: f32, length: f32) -> bool {
+        self.t = if length > 0.0 {
+            (self.t + dt / length).min(1.0)
+        } else {
+            1.0
+        };
+        self.t < 1.0
+    }
+
+    pub fn draw(&self, root_canvas: &Canvas, font_dimensions: Dimensions) {
+        let progress = ease(ease_out_quad, 0.0, 1.0, self.t);
+        let scale = |new: u64, old: u64| lerp(1.0, new as f32 / old.max(1) as f32, progress);
+        let scale_x = scale(font_dimensions.width, self.old_font_dimensions.width);
+        let scale_y = scale(font_dimensions.height, self.old_font_dimensions.height);
+
+        let mut paint = Paint::default();
+        paint.set_alpha_f(1.0 - progress);
+
+        root_canvas.save();
+        root_canvas.translate(self.origin);
+        root_canvas.scale((scale_x, scale_y));
+        root_canvas.translate(-self.origin);
+        root_canvas.draw_image(&self.snapshot, (0.0, 0.0), Some(&paint));
+        root_canvas.restore();
+    }
+}
@@ -1,12 +1,14 @@
 use std::{num::NonZeroUsize, sync::Arc};
 
 use itertools::Itertools;
-use log::{debug, error, trace, warn};
+use log::{debug, error, trace};
 use lru::LruCache;
+use parking_lot::{Mutex, MutexGuard};
 use skia_safe::{
     graphics::{font_cache_limit, font_cache_used, set_font_cache_limit},
     TextBlob, TextBlobBuilder,
 };
+use smol_str::SmolStr;
 use swash::{
     shape::ShapeContext,
     text::{
@@ -20,19 +22,42 @@ use unicode_segmentation::UnicodeSegmentation;
 use crate::{
     error_msg,
     profiling::tracy_zone,
-    renderer::fonts::{font_loader::*, font_options::*},
+    renderer::{
+        fonts::{font_loader::*, font_options::*},
+        FontCacheUsage, RendererSettings, SubpixelOrder, RENDERER_STATUS,
+    },
+    settings::SETTINGS,
 };
 
+fn cache_capacity(size: u32) -> NonZeroUsize {
+    NonZeroUsize::new(size as usize).unwrap_or(NonZeroUsize::MIN)
+}
+
 #[derive(new, Clone, Hash, PartialEq, Eq, Debug)]
 struct ShapeKey {
-    pub text: String,
+    pub text: SmolStr,
     pub style: CoarseStyle,
+    pub ligatures: bool,
+}
+
+type BlobCache = LruCache<(Arc<str>, ShapeKey), Arc<Vec<TextBlob>>>;
+
+lazy_static! {
+    // The shaped text of the process, keyed by the fonts and the size it was shaped with. It's
+    // the only cache of the shapers, so a shaper with the same fonts as another one, like the one
+    // of another window, neither shapes nor stores the text again.
+    static ref BLOB_CACHE: Mutex<BlobCache> =
+        Mutex::new(LruCache::new(NonZeroUsize::new(10000).unwrap()));
 }
 
+/// The OpenType features that form ligatures, disabled when ligatures are turned off.
+const LIGATURE_FEATURES: [&str; 2] = ["calt", "liga"];
+
 pub struct CachingShaper {
     options: FontOptions,
     font_loader: FontLoader,
-    blob_cache: LruCache<ShapeKey, Vec<TextBlob>>,
+    // Identifies the fonts and the size in the blob cache
+    fonts_identity: Arc<str>,
     shape_context: ShapeContext,
     scale_factor: f32,
     fudge_factor: f32,
@@ -40,14 +65,35 @@ pub struct CachingShaper {
     font_info: Option<(Metrics, f32)>,
 }
 
+/// The shaper of the process, shared by the renderers of the window thread and the frame producer.
+/// It's only reachable through its lock, so the shaper itself never moves to another thread.
+pub struct SharedShaper(Mutex<CachingShaper>);
+
+// SAFETY: skia-safe doesn't mark `FontMgr` as `Send`, but it's reference counted atomically like
+// the other Skia objects, and Skia's font managers are thread safe. The shaper can only be created
+// here, so it can't be moved out of the mutex, which makes sure that it's never used from two
+// threads at once.
+unsafe impl Send for SharedShaper {}
+unsafe impl Sync for SharedShaper {}
+
+impl SharedShaper {
+    pub fn new(scale_factor: f32) -> Self {
+        Self(Mutex::new(CachingShaper::new(scale_factor)))
+    }
+
+    pub fn lock(&self) -> MutexGuard<'_, CachingShaper> {
+        self.0.lock()
+    }
+}
+
 impl CachingShaper {
-    pub fn new(scale_factor: f32) -> CachingShaper {
+    fn new(scale_factor: f32) -> CachingShaper {
         let options = FontOptions::default();
         let font_size = options.size * scale_factor;
         let mut shaper = CachingShaper {
             options,
             font_loader: FontLoader::new(font_size),
-            blob_cache: LruCache::new(NonZeroUsize::new(10000).unwrap()),
+            fonts_identity: Arc::from(""),
             shape_context: ShapeContext::new(),
             scale_factor,
             fudge_factor: 1.0,
@@ -97,9 +143,16 @@ impl CachingShaper {
         self.update_font_options(options);
     }
 
-    pub fn update_font_options(&mut self, options: FontOptions) {
+    pub fn update_font_options(&mut self, mut options: FontOptions) {
         debug!("Updating font options: {:?}", options);
 
+        // The subpixel antialiasing mode renders the text with the normal edging as LCD text
+        if options.edging == FontEdging::AntiAlias
+            && SETTINGS.get::<RendererSettings>().subpixel_antialiasing != SubpixelOrder::None
+        {
+            options.edging = FontEdging::SubpixelAntiAlias;
+        }
+
         let keys = options
             .possible_fonts()
             .iter()
@@ -111,10 +164,21 @@ impl CachingShaper {
             .unique()
             .collect::<Vec<_>>();
 
+        let loaded = keys
+            .iter()
+            .map(|key| self.font_loader.get_or_load(key).is_some())
+            .collect_vec();
         let failed_fonts = keys
             .iter()
-            .filter(|key| self.font_loader.get_or_load(key).is_none())
+            .zip(&loaded)
+            .filter(|(_, loaded)| !**loaded)
+            .map(|(key, _)| key)
             .collect_vec();
+        RENDERER_STATUS.write().unwrap().fonts = keys
+            .iter()
+            .zip(loaded)
+            .filter_map(|(key, loaded)| Some((key.font_desc.as_ref()?.to_string(), loaded)))
+            .collect();
 
         if !failed_fonts.is_empty() {
             error_msg!(
@@ -178,7 +242,31 @@ impl CachingShaper {
             debug!("Fudged font size: {:.2}px", font_size);
             debug!("Fudged font width: {:.2}px", self.info().1);
         }
-        self.blob_cache.clear();
+        // The text shaped with the old fonts ages out of the cache
+        self.fonts_identity = self.identify_fonts();
+    }
+
+    /// Everything the shaped text depends on, which is the same for shapers with the same fonts.
+    fn identify_fonts(&self) -> Arc<str> {
+        let options = &self.options;
+        let features = options
+            .features
+            .iter()
+            .sorted_by(|(a, _), (b, _)| a.cmp(b))
+            .collect_vec();
+        format!(
+            "{:?} {:?} {:?} {:?} {:?} {:?} {:?} {} {}",
+            options.normal,
+            options.italic,
+            options.bold,
+            options.bold_italic,
+            features,
+            options.hinting,
+            options.edging,
+            self.current_size(),
+            options.width,
+        )
+        .into()
     }
 
     pub fn font_names(&self) -> Vec<String> {
@@ -371,19 +459,47 @@ impl CachingShaper {
         grouped_results
     }
 
-    pub fn adjust_font_cache_size(&self) {
-        let current_font_cache_size = font_cache_limit() as f32;
-        let percent_font_cache_used = font_cache_used() as f32 / current_font_cache_size;
-        if percent_font_cache_used > 0.9 {
-            warn!(
-                "Font cache is {}% full, increasing cache size",
-                percent_font_cache_used * 100.0
-            );
-            set_font_cache_limit((percent_font_cache_used * 1.5) as usize);
+    /// Keeps the caches within the budgets of the settings, evicting the least recently used
+    /// entries, and reports how full they are.
+    fn apply_cache_budgets(&mut self) {
+        let settings = SETTINGS.get::<RendererSettings>();
+
+        // Skia evicts the least recently used glyphs itself when the limit is reached, so the
+        // limit starts small and grows when needed, until it reaches the budget
+        let budget = settings.font_cache_budget as usize * 1024 * 1024;
+        let limit = font_cache_limit();
+        if budget != 0 && limit > budget {
+            set_font_cache_limit(budget);
+        } else if font_cache_used() as f32 > limit as f32 * 0.9 && (budget == 0 || limit < budget) {
+            let new_limit = limit * 3 / 2;
+            let new_limit = if budget == 0 {
+                new_limit
+            } else {
+                new_limit.min(budget)
+            };
+            debug!("Font cache is almost full, increasing its limit to {new_limit} bytes");
+            set_font_cache_limit(new_limit);
+        }
+
+        let shaped_text_capacity = cache_capacity(settings.shaped_text_cache_size);
+        let mut blob_cache = BLOB_CACHE.lock();
+        if blob_cache.cap() != shaped_text_capacity {
+            blob_cache.resize(shaped_text_capacity);
         }
+        self.font_loader
+            .set_capacity(cache_capacity(settings.loaded_fonts_cache_size));
+
+        RENDERER_STATUS.write().unwrap().font_caches = FontCacheUsage {
+            glyph_bytes: font_cache_used(),
+            glyph_limit: font_cache_limit(),
+            shaped_texts: blob_cache.len(),
+            shaped_text_capacity: blob_cache.cap().get(),
+            loaded_fonts: self.font_loader.len(),
+            loaded_font_capacity: self.font_loader.capacity(),
+        };
     }
 
-    pub fn shape(&mut self, text: String, style: CoarseStyle) -> Vec<TextBlob> {
+    pub fn shape(&mut self, text: &str, style: CoarseStyle, ligatures: bool) -> Vec<TextBlob> {
         let current_size = self.current_size();
         let (glyph_width, ..) = self.font_base_dimensions();
 
@@ -391,8 +507,8 @@ impl CachingShaper {
 
         trace!("Shaping text: {:?}", text);
 
-        for (cluster_group, font_pair) in self.build_clusters(&text, style) {
-            let features = self.get_font_features(
+        for (cluster_group, font_pair) in self.build_clusters(text, style) {
+            let mut features = self.get_font_features(
                 font_pair
                     .as_ref()
                     .key
@@ -400,6 +516,11 @@ impl CachingShaper {
                     .as_ref()
                     .map(|desc| desc.family.as_str()),
             );
+            if !ligatures {
+                // This overrides the features from guifont, since the setting is more specific
+                features.retain(|(name, _)| !LIGATURE_FEATURES.contains(&name.as_str()));
+                features.extend(LIGATURE_FEATURES.map(|name| (name.to_string(), 0)));
+            }
 
             let mut shaper = self
                 .shape_context
@@ -439,21 +560,31 @@ impl CachingShaper {
             resulting_blobs.push(blob.expect("Could not create textblob"));
         }
 
-        self.adjust_font_cache_size();
-
         resulting_blobs
     }
 
-    pub fn shape_cached(&mut self, text: String, style: CoarseStyle) -> &Vec<TextBlob> {
+    /// The text is kept as a `SmolStr` in the cache keys, so that the short text of cells isn't
+    /// allocated.
+    pub fn shape_cached(
+        &mut self,
+        text: impl Into<SmolStr>,
+        style: CoarseStyle,
+        ligatures: bool,
+    ) -> Arc<Vec<TextBlob>> {
         tracy_zone!("shape_cached");
-        let key = ShapeKey::new(text.clone(), style);
-
-        if !self.blob_cache.contains(&key) {
-            let blobs = self.shape(text, style);
-            self.blob_cache.put(key.clone(), blobs);
+        let key = (
+            self.fonts_identity.clone(),
+            ShapeKey::new(text.into(), style, ligatures),
+        );
+        if let Some(blobs) = BLOB_CACHE.lock().get(&key) {
+            return blobs.clone();
         }
 
-        self.blob_cache.get(&key).unwrap()
+        // The lock isn't held while shaping, so that the other shapers don't wait for it
+        let blobs = Arc::new(self.shape(&key.1.text, style, ligatures));
+        BLOB_CACHE.lock().put(key, blobs.clone());
+        self.apply_cache_budgets();
+        blobs
     }
 
     fn get_font_features(&self, name: Option<&str>) -> Vec<(String, u16)> {
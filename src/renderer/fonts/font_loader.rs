@@ -100,6 +100,21 @@ impl FontLoader {
         }
     }
 
+    /// Keeps at most this many fonts, dropping the least recently used ones.
+    pub fn set_capacity(&mut self, capacity: NonZeroUsize) {
+        if self.cache.cap() != capacity {
+            self.cache.resize(capacity);
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.cache.cap().get()
+    }
+
+    pub fn len(&self) -> usize {
+        self.cache.len()
+    }
+
     pub fn get_or_load(&mut self, font_key: &FontKey) -> Option<Arc<FontPair>> {
         if let Some(cached) = self.cache.get(font_key) {
             return Some(cached.clone());
@@ -8,7 +8,7 @@ use skia_safe::{
     FontStyle,
 };
 
-use crate::editor;
+use crate::{editor, window::text_scale_factor};
 
 const DEFAULT_FONT_SIZE: f32 = 14.0;
 const FONT_OPTS_SEPARATOR: char = ':';
@@ -24,6 +24,12 @@ const FONT_ITALIC_OPT: &str = "i";
 const INVALID_SIZE_ERR: &str = "Invalid size";
 const INVALID_WIDTH_ERR: &str = "Invalid width";
 
+lazy_static! {
+    // The size used when guifont doesn't set one, following the text size of the OS. It's only
+    // queried once, since that may start a process.
+    static ref DEFAULT_FONT_PIXELS: f32 = points_to_pixels(DEFAULT_FONT_SIZE * text_scale_factor());
+}
+
 /// Description of the normal font.
 #[derive(Clone, Debug, Deserialize, PartialEq, Hash, Eq, Default)]
 pub struct FontDescription {
@@ -239,7 +245,7 @@ impl Default for FontOptions {
             bold_italic: None,
             features: HashMap::new(),
             allow_float_size: false,
-            size: points_to_pixels(DEFAULT_FONT_SIZE),
+            size: *DEFAULT_FONT_PIXELS,
             width: 0.0,
             hinting: FontHinting::default(),
             edging: FontEdging::default(),
@@ -0,0 +1,464 @@
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    iter,
+    ops::Range,
+    sync::{
+        mpsc::{channel, Receiver, Sender},
+        Arc,
+    },
+    thread,
+};
+
+use log::error;
+use skia_safe::{Color, Picture, PictureRecorder, Rect};
+
+use crate::{
+    profiling::{tracy_named_frame, tracy_zone},
+    renderer::{
+        indent_guides::{
+            draw_indent_guides, draw_trailing_whitespace, IndentGuides, TrailingWhitespace,
+            WindowIndent,
+        },
+        window_state::{Line, WindowSnapshot, WindowState},
+        GridRenderer, LineFragment, RendererSettings, WindowDrawCommand,
+    },
+    settings::SETTINGS,
+    utils::{triple_buffer, TripleBufferReader, TripleBufferWriter},
+};
+
+/// The foreground of a single fragment, kept so that it can be reused when the line is redrawn
+/// but the fragment is unchanged.
+#[derive(Clone)]
+pub struct FragmentPicture {
+    pub generation: u64,
+    pub picture: Option<Picture>,
+}
+
+/// The windows produced from the draw commands, with all of their lines ready to be drawn.
+#[derive(Clone, Default)]
+pub struct FrameSnapshot {
+    /// The last message sent to the producer that's included in the frame.
+    pub sequence: u64,
+    pub windows: HashMap<u64, Arc<WindowSnapshot>>,
+}
+
+enum Message {
+    DrawCommands(Vec<(u64, WindowDrawCommand)>),
+    UpdateGridRenderer(GridRenderer),
+    SetWindowLigatures(HashMap<u64, bool>),
+    SetWindowIndents(HashMap<u64, WindowIndent>),
+    SetWindowTrailingWhitespace(HashMap<u64, HashMap<u64, Range<u64>>>),
+    ScrollWindowColumns {
+        window_handle: u64,
+        cols: i64,
+        text_left: u64,
+    },
+    SetWindowSkipcol {
+        window_handle: u64,
+        skipcol: u64,
+    },
+    Reset,
+}
+
+/// Applies the draw commands of the windows and prepares their lines on a worker thread, which
+/// produces an immutable snapshot of the windows whenever it's done with the queued commands.
+/// The snapshots are passed back through a triple buffer, so the window thread animates and draws
+/// the latest one without waiting for the worker, and the worker never waits for a frame to be
+/// drawn.
+///
+/// The worker draws with a copy of the window thread's `GridRenderer` that shares its shaper, and
+/// is sent a new copy whenever the fonts or the default style change.
+pub struct FramePipeline {
+    sender: Sender<(u64, Message)>,
+    frames: TripleBufferReader<FrameSnapshot>,
+    // The last message sent to the producer
+    sequence: u64,
+    // The frames older than this are dropped, since they have the windows of a previous Neovim
+    discard_before: u64,
+}
+
+impl FramePipeline {
+    pub fn new(grid_renderer: &GridRenderer) -> Self {
+        let (sender, receiver) = channel();
+        let (writer, frames) = triple_buffer(FrameSnapshot::default());
+
+        let producer = FrameProducer::new(grid_renderer.share());
+        thread::Builder::new()
+            .name("frame_producer".to_owned())
+            .spawn(move || producer.run(receiver, writer))
+            .expect("Could not start the frame producer thread");
+
+        Self {
+            sender,
+            frames,
+            sequence: 0,
+            discard_before: 0,
+        }
+    }
+
+    fn send(&mut self, message: Message) -> u64 {
+        self.sequence += 1;
+        if self.sender.send((self.sequence, message)).is_err() {
+            error!("The frame producer thread has stopped");
+        }
+        self.sequence
+    }
+
+    /// Queues the draw commands of the windows, which are flushed together. Returns the sequence
+    /// number of the frames that include them.
+    pub fn draw_commands(&mut self, commands: Vec<(u64, WindowDrawCommand)>) -> u64 {
+        self.send(Message::DrawCommands(commands))
+    }
+
+    /// Makes the lines prepared from now on use the current state of `grid_renderer`, and prepares
+    /// all the lines again with it.
+    pub fn update(&mut self, grid_renderer: &GridRenderer) {
+        self.send(Message::UpdateGridRenderer(grid_renderer.share()));
+    }
+
+    pub fn set_window_ligatures(&mut self, window_ligatures: HashMap<u64, bool>) {
+        self.send(Message::SetWindowLigatures(window_ligatures));
+    }
+
+    pub fn set_window_indents(&mut self, window_indents: HashMap<u64, WindowIndent>) {
+        self.send(Message::SetWindowIndents(window_indents));
+    }
+
+    pub fn set_window_trailing_whitespace(
+        &mut self,
+        window_trailing_whitespace: HashMap<u64, HashMap<u64, Range<u64>>>,
+    ) {
+        self.send(Message::SetWindowTrailingWhitespace(
+            window_trailing_whitespace,
+        ));
+    }
+
+    pub fn scroll_window_columns(&mut self, window_handle: u64, cols: i64, text_left: u64) {
+        self.send(Message::ScrollWindowColumns {
+            window_handle,
+            cols,
+            text_left,
+        });
+    }
+
+    pub fn set_window_skipcol(&mut self, window_handle: u64, skipcol: u64) {
+        self.send(Message::SetWindowSkipcol {
+            window_handle,
+            skipcol,
+        });
+    }
+
+    /// Forgets all the windows, the frames produced before aren't received anymore.
+    pub fn reset(&mut self) {
+        self.discard_before = self.send(Message::Reset);
+    }
+
+    /// Takes the latest frame, or waits for the one that includes everything sent when `wait` is
+    /// set. Returns the frame when it's new.
+    pub fn receive(&mut self, wait: bool) -> Option<&FrameSnapshot> {
+        tracy_zone!("receive_frame");
+        let updated = if wait {
+            let sequence = self.sequence;
+            self.frames.update_until(|frame| frame.sequence >= sequence)
+        } else {
+            self.frames.update()
+        };
+        let frame = self.frames.front();
+        (updated && frame.sequence >= self.discard_before).then_some(frame)
+    }
+
+    /// The sequence number of the last message sent to the producer.
+    pub fn sent_sequence(&self) -> u64 {
+        self.sequence
+    }
+
+    /// The sequence number of the last message included in the frame received last.
+    pub fn received_sequence(&self) -> u64 {
+        self.frames.front().sequence
+    }
+
+    /// Whether the last frame received includes everything sent to the producer.
+    pub fn is_current(&self) -> bool {
+        self.received_sequence() == self.sequence
+    }
+}
+
+/// The state of the windows on the frame producer thread.
+struct FrameProducer {
+    grid_renderer: GridRenderer,
+    windows: HashMap<u64, WindowState>,
+    // The ligature overrides of the buffers, by the handle of the window showing them
+    window_ligatures: HashMap<u64, bool>,
+    window_indents: HashMap<u64, WindowIndent>,
+    window_trailing_whitespace: HashMap<u64, HashMap<u64, Range<u64>>>,
+}
+
+impl FrameProducer {
+    fn new(grid_renderer: GridRenderer) -> Self {
+        Self {
+            grid_renderer,
+            windows: HashMap::new(),
+            window_ligatures: HashMap::new(),
+            window_indents: HashMap::new(),
+            window_trailing_whitespace: HashMap::new(),
+        }
+    }
+
+    /// Handles the messages until the window thread is gone. All the queued messages are handled
+    /// before a frame is produced, since only the latest one would be drawn.
+    fn run(
+        mut self,
+        receiver: Receiver<(u64, Message)>,
+        mut writer: TripleBufferWriter<FrameSnapshot>,
+    ) {
+        while let Ok(first) = receiver.recv() {
+            let mut sequence = 0;
+            for (message_sequence, message) in iter::once(first).chain(receiver.try_iter()) {
+                self.handle_message(message);
+                sequence = message_sequence;
+            }
+            self.produce(writer.back(), sequence);
+            writer.publish();
+        }
+    }
+
+    fn handle_message(&mut self, message: Message) {
+        match message {
+            Message::DrawCommands(commands) => {
+                for (grid_id, command) in commands {
+                    self.handle_window_draw_command(grid_id, command);
+                }
+                tracy_named_frame!("neovim draw batch processed");
+                let settings = SETTINGS.get::<RendererSettings>();
+                for window in self.windows.values_mut() {
+                    window.flush(&settings);
+                }
+            }
+            Message::UpdateGridRenderer(grid_renderer) => self.grid_renderer = grid_renderer,
+            Message::SetWindowLigatures(window_ligatures) => {
+                for window in self.windows.values_mut() {
+                    let ligatures = window
+                        .window_handle()
+                        .and_then(|handle| window_ligatures.get(&handle).copied());
+                    window.set_ligatures(ligatures);
+                }
+                self.window_ligatures = window_ligatures;
+            }
+            Message::SetWindowIndents(window_indents) => {
+                for window in self.windows.values_mut() {
+                    let indent = window
+                        .window_handle()
+                        .and_then(|handle| window_indents.get(&handle).copied());
+                    window.set_indent(indent);
+                }
+                self.window_indents = window_indents;
+            }
+            Message::SetWindowTrailingWhitespace(window_trailing_whitespace) => {
+                for window in self.windows.values_mut() {
+                    let trailing_whitespace = window
+                        .window_handle()
+                        .and_then(|handle| window_trailing_whitespace.get(&handle).cloned());
+                    window.set_trailing_whitespace(trailing_whitespace.unwrap_or_default());
+                }
+                self.window_trailing_whitespace = window_trailing_whitespace;
+            }
+            Message::ScrollWindowColumns {
+                window_handle,
+                cols,
+                text_left,
+            } => {
+                for window in self.windows.values_mut() {
+                    if window.window_handle() == Some(window_handle) {
+                        window.scroll_columns(cols, text_left);
+                    }
+                }
+            }
+            Message::SetWindowSkipcol {
+                window_handle,
+                skipcol,
+            } => {
+                for window in self.windows.values_mut() {
+                    if window.window_handle() == Some(window_handle) {
+                        window.set_skipcol(skipcol);
+                    }
+                }
+            }
+            Message::Reset => self.windows.clear(),
+        }
+    }
+
+    fn handle_window_draw_command(&mut self, grid_id: u64, command: WindowDrawCommand) {
+        match command {
+            WindowDrawCommand::Close => {
+                self.windows.remove(&grid_id);
+            }
+            WindowDrawCommand::WindowHandle(handle) => {
+                if let Some(window) = self.windows.get_mut(&grid_id) {
+                    window.handle_window_draw_command(command);
+                    window.set_ligatures(self.window_ligatures.get(&handle).copied());
+                    window.set_indent(self.window_indents.get(&handle).copied());
+                    window.set_trailing_whitespace(
+                        self.window_trailing_whitespace
+                            .get(&handle)
+                            .cloned()
+                            .unwrap_or_default(),
+                    );
+                }
+            }
+            command => match self.windows.entry(grid_id) {
+                Entry::Occupied(mut occupied_entry) => {
+                    occupied_entry.get_mut().handle_window_draw_command(command);
+                }
+                Entry::Vacant(vacant_entry) => {
+                    if let WindowDrawCommand::Position {
+                        grid_position: (grid_left, grid_top),
+                        grid_size: (width, height),
+                        ..
+                    } = command
+                    {
+                        let new_window = WindowState::new(
+                            grid_id,
+                            (grid_left as f32, grid_top as f32).into(),
+                            (width, height).into(),
+                        );
+                        vacant_entry.insert(new_window);
+                    } else {
+                        error!("WindowDrawCommand sent for uninitialized grid {}", grid_id);
+                    }
+                }
+            },
+        }
+    }
+
+    /// Prepares the lines of the windows, and makes the frame a snapshot of them.
+    fn produce(&mut self, frame: &mut FrameSnapshot, sequence: u64) {
+        tracy_zone!("produce_frame");
+        let settings = SETTINGS.get::<RendererSettings>();
+        frame.sequence = sequence;
+        frame.windows.clear();
+        for (id, window) in &mut self.windows {
+            window.prepare_lines(&mut self.grid_renderer, &settings);
+            frame.windows.insert(*id, window.snapshot());
+        }
+    }
+}
+
+/// Shapes the line and records its pictures. The pictures of the fragments that were already
+/// recorded with the current fonts are reused, unless `reuse_fragments` is false.
+pub(super) fn prepare_line(
+    grid_renderer: &mut GridRenderer,
+    line: &Line,
+    grid_width: u64,
+    ligatures: bool,
+    indent_guides: Option<IndentGuides>,
+    trailing_whitespace: Option<TrailingWhitespace>,
+    reuse_fragments: bool,
+) -> Line {
+    tracy_zone!("prepare_line");
+    let line_fragments = &line.line_fragments;
+    let mut fragment_pictures = if reuse_fragments {
+        line.fragment_pictures.clone()
+    } else {
+        vec![None; line_fragments.len()]
+    };
+    let font_dimensions = grid_renderer.font_dimensions;
+    let grid_rect = Rect::from_wh(
+        (grid_width * font_dimensions.width) as f32,
+        font_dimensions.height as f32,
+    );
+
+    let mut recorder = PictureRecorder::new();
+    let canvas = recorder.begin_recording(grid_rect, None);
+
+    let mut has_transparency = false;
+    let mut custom_background = false;
+
+    for line_fragment in line_fragments.iter() {
+        let LineFragment {
+            window_left,
+            width,
+            style,
+            ..
+        } = line_fragment;
+        let grid_position = (*window_left, 0);
+        let background_info = grid_renderer.draw_background(canvas, grid_position, *width, style);
+        custom_background |= background_info.custom_color;
+        has_transparency |= background_info.transparent;
+    }
+    let background_picture =
+        custom_background.then_some(recorder.finish_recording_as_picture(None).unwrap());
+
+    let canvas = recorder.begin_recording(grid_rect, None);
+    let mut foreground_drawn = false;
+    let generation = grid_renderer.generation;
+    for (line_fragment, fragment_picture) in line_fragments.iter().zip(fragment_pictures.iter_mut())
+    {
+        let fragment_picture = match fragment_picture {
+            Some(fragment_picture) if fragment_picture.generation == generation => fragment_picture,
+            _ => {
+                let LineFragment {
+                    text,
+                    window_left,
+                    width,
+                    style,
+                } = line_fragment;
+                let grid_position = (*window_left, 0);
+
+                let mut fragment_recorder = PictureRecorder::new();
+                let fragment_canvas = fragment_recorder.begin_recording(grid_rect, None);
+                let drawn = grid_renderer.draw_foreground(
+                    fragment_canvas,
+                    text,
+                    grid_position,
+                    *width,
+                    style,
+                    ligatures,
+                );
+                fragment_picture.insert(FragmentPicture {
+                    generation,
+                    picture: drawn
+                        .then(|| fragment_recorder.finish_recording_as_picture(None).unwrap()),
+                })
+            }
+        };
+        if let Some(picture) = &fragment_picture.picture {
+            canvas.draw_picture(picture, None, None);
+            foreground_drawn = true;
+        }
+    }
+    let default_foreground = grid_renderer
+        .default_style
+        .colors
+        .foreground
+        .map_or(Color::WHITE, |color| color.to_color());
+    if let Some(indent_guides) = &indent_guides {
+        foreground_drawn |= draw_indent_guides(
+            canvas,
+            line_fragments,
+            indent_guides,
+            font_dimensions,
+            default_foreground,
+        );
+    }
+    if let Some(trailing_whitespace) = &trailing_whitespace {
+        foreground_drawn |= draw_trailing_whitespace(
+            canvas,
+            trailing_whitespace,
+            font_dimensions,
+            default_foreground,
+        );
+    }
+    let foreground_picture =
+        foreground_drawn.then_some(recorder.finish_recording_as_picture(None).unwrap());
+
+    Line {
+        line_fragments: line_fragments.clone(),
+        fragment_pictures,
+        background_picture,
+        foreground_picture,
+        has_transparency,
+        is_inferred_border: line.is_inferred_border,
+        trailing_whitespace,
+        prepared_generation: Some(generation),
+    }
+}
@@ -10,19 +10,23 @@ use crate::{
     dimensions::Dimensions,
     editor::{Colors, Style, UnderlineStyle},
     profiling::tracy_zone,
-    renderer::{CachingShaper, RendererSettings},
+    renderer::{RendererSettings, SharedShaper},
     settings::*,
 };
 
 use super::fonts::font_options::FontOptions;
 
 pub struct GridRenderer {
-    pub shaper: CachingShaper,
+    /// Shared with the frame producer thread, which shapes the lines with it.
+    pub shaper: Arc<SharedShaper>,
     pub default_style: Arc<Style>,
     pub em_size: f32,
     pub font_dimensions: Dimensions,
     pub scale_factor: f64,
     pub is_ready: bool,
+    /// Incremented whenever the font or the default style changes, which invalidates everything
+    /// drawn before.
+    pub generation: u64,
 }
 
 /// Struct with named fields to be returned from draw_background
@@ -33,27 +37,42 @@ pub struct BackgroundInfo {
 
 impl GridRenderer {
     pub fn new(scale_factor: f64) -> Self {
-        let mut shaper = CachingShaper::new(scale_factor as f32);
+        let shaper = SharedShaper::new(scale_factor as f32);
         let default_style = Arc::new(Style::new(Colors::new(
             Some(colors::WHITE),
             Some(colors::BLACK),
             Some(colors::GREY),
         )));
-        let em_size = shaper.current_size();
-        let font_dimensions: Dimensions = shaper.font_base_dimensions().into();
+        let em_size = shaper.lock().current_size();
+        let font_dimensions: Dimensions = shaper.lock().font_base_dimensions().into();
 
         GridRenderer {
-            shaper,
+            shaper: Arc::new(shaper),
             default_style,
             em_size,
             font_dimensions,
             scale_factor,
             is_ready: false,
+            generation: 0,
+        }
+    }
+
+    /// Returns a renderer with the same state that uses the same shaper, for drawing on another
+    /// thread.
+    pub fn share(&self) -> Self {
+        GridRenderer {
+            shaper: self.shaper.clone(),
+            default_style: self.default_style.clone(),
+            em_size: self.em_size,
+            font_dimensions: self.font_dimensions,
+            scale_factor: self.scale_factor,
+            is_ready: self.is_ready,
+            generation: self.generation,
         }
     }
 
     pub fn font_names(&self) -> Vec<String> {
-        self.shaper.font_names()
+        self.shaper.lock().font_names()
     }
 
     /// Convert PhysicalSize to grid size.
@@ -67,32 +86,38 @@ impl GridRenderer {
     }
 
     pub fn handle_scale_factor_update(&mut self, scale_factor: f64) {
-        self.shaper.update_scale_factor(scale_factor as f32);
+        self.shaper.lock().update_scale_factor(scale_factor as f32);
         self.update_font_dimensions();
     }
 
     pub fn update_font(&mut self, guifont_setting: &str) {
-        self.shaper.update_font(guifont_setting);
+        self.shaper.lock().update_font(guifont_setting);
         self.update_font_dimensions();
     }
 
     pub fn update_font_options(&mut self, options: FontOptions) {
-        self.shaper.update_font_options(options);
+        self.shaper.lock().update_font_options(options);
         self.update_font_dimensions();
     }
 
     pub fn update_linespace(&mut self, linespace_setting: i64) {
-        self.shaper.update_linespace(linespace_setting);
+        self.shaper.lock().update_linespace(linespace_setting);
         self.update_font_dimensions();
     }
 
     fn update_font_dimensions(&mut self) {
-        self.em_size = self.shaper.current_size();
-        self.font_dimensions = self.shaper.font_base_dimensions().into();
+        self.em_size = self.shaper.lock().current_size();
+        self.font_dimensions = self.shaper.lock().font_base_dimensions().into();
         self.is_ready = true;
+        self.generation += 1;
         trace!("Updated font dimensions: {:?}", self.font_dimensions,);
     }
 
+    pub fn set_default_style(&mut self, style: Style) {
+        self.default_style = Arc::new(style);
+        self.generation += 1;
+    }
+
     fn compute_text_region(&self, grid_position: (u64, u64), cell_width: u64) -> Rect {
         let (x, y) = grid_position * self.font_dimensions;
         let width = cell_width * self.font_dimensions.width;
@@ -117,7 +142,8 @@ impl GridRenderer {
         style: &Option<Arc<Style>>,
     ) -> BackgroundInfo {
         tracy_zone!("draw_background");
-        let debug = SETTINGS.get::<RendererSettings>().debug_renderer;
+        let settings = SETTINGS.get::<RendererSettings>();
+        let debug = settings.debug_renderer;
         if style.is_none() && !debug {
             return BackgroundInfo {
                 custom_color: false,
@@ -139,10 +165,9 @@ impl GridRenderer {
         } else {
             paint.set_color(style.background(&self.default_style.colors).to_color());
         }
-        if style.blend > 0 {
-            paint.set_alpha_f((100 - style.blend) as f32 / 100.0);
-        } else {
-            paint.set_alpha_f(1.0);
+        // The high contrast mode ignores `winblend` and `pumblend`
+        if !settings.high_contrast {
+            paint.set_alpha_f(style.background_alpha());
         }
 
         let custom_color = paint.color4f() != self.default_style.colors.background.unwrap();
@@ -152,7 +177,7 @@ impl GridRenderer {
 
         BackgroundInfo {
             custom_color,
-            transparent: style.blend > 0,
+            transparent: style.blend > 0 && !settings.high_contrast,
         }
     }
 
@@ -165,6 +190,7 @@ impl GridRenderer {
         grid_position: (u64, u64),
         cell_width: u64,
         style: &Option<Arc<Style>>,
+        ligatures: bool,
     ) -> bool {
         tracy_zone!("draw_foreground");
         let (x, y) = grid_position * self.font_dimensions;
@@ -180,7 +206,7 @@ impl GridRenderer {
         let region = self.compute_text_region(clip_position, cell_width + 2);
 
         if let Some(underline_style) = style.underline {
-            let line_position = self.shaper.underline_position();
+            let line_position = self.shaper.lock().underline_position();
             let p1 = (
                 x as f32,
                 (y - line_position + self.font_dimensions.height) as f32,
@@ -197,18 +223,26 @@ impl GridRenderer {
         canvas.save();
         canvas.clip_rect(region, None, Some(false));
 
-        let y_adjustment = self.shaper.y_adjustment();
+        let y_adjustment = self.shaper.lock().y_adjustment();
 
         let mut paint = Paint::default();
         paint.set_anti_alias(false);
         paint.set_blend_mode(BlendMode::SrcOver);
 
-        if SETTINGS.get::<RendererSettings>().debug_renderer {
+        let settings = SETTINGS.get::<RendererSettings>();
+        if settings.debug_renderer {
             let random_hsv: HSV = (rand::random::<f32>() * 360.0, 1.0, 1.0).into();
             let random_color = random_hsv.to_color(255);
             paint.set_color(random_color);
+        } else if settings.high_contrast {
+            paint.set_color(
+                style
+                    .high_contrast_foreground(&self.default_style.colors)
+                    .to_color(),
+            );
         } else {
             paint.set_color(style.foreground(&self.default_style.colors).to_color());
+            paint.set_alpha_f(style.foreground_alpha());
         }
         paint.set_anti_alias(false);
 
@@ -223,7 +257,8 @@ impl GridRenderer {
         if !trimmed.is_empty() {
             for blob in self
                 .shaper
-                .shape_cached(trimmed.to_string(), style.into())
+                .lock()
+                .shape_cached(trimmed, style.into(), ligatures)
                 .iter()
             {
                 tracy_zone!("draw_text_blob");
@@ -251,6 +286,27 @@ impl GridRenderer {
         drawn
     }
 
+    pub fn draw_hyperlink_underline(
+        &mut self,
+        canvas: &Canvas,
+        grid_position: (u64, u64),
+        cell_width: u64,
+        style: &Option<Arc<Style>>,
+    ) {
+        let (x, y) = grid_position * self.font_dimensions;
+        let width = cell_width * self.font_dimensions.width;
+        let line_position =
+            (y - self.shaper.lock().underline_position() + self.font_dimensions.height) as f32;
+        let style = style.as_ref().unwrap_or(&self.default_style);
+        self.draw_underline(
+            canvas,
+            style,
+            UnderlineStyle::Underline,
+            (x as f32, line_position).into(),
+            ((x + width) as f32, line_position).into(),
+        );
+    }
+
     fn draw_underline(
         &self,
         canvas: &Canvas,
@@ -265,14 +321,19 @@ impl GridRenderer {
         let mut underline_paint = Paint::default();
         underline_paint.set_anti_alias(false);
         underline_paint.set_blend_mode(BlendMode::SrcOver);
-        let underline_stroke_scale = SETTINGS.get::<RendererSettings>().underline_stroke_scale;
+        let settings = SETTINGS.get::<RendererSettings>();
+        let underline_stroke_scale = settings.underline_stroke_scale;
         // If the stroke width is less than one, clamp it to one otherwise we get nasty aliasing
         // issues
-        let stroke_width = (self.shaper.current_size() * underline_stroke_scale / 10.).max(1.);
+        let stroke_width =
+            (self.shaper.lock().current_size() * underline_stroke_scale / 10.).max(1.);
 
         underline_paint
             .set_color(style.special(&self.default_style.colors).to_color())
             .set_stroke_width(stroke_width);
+        if !settings.high_contrast {
+            underline_paint.set_alpha_f(style.foreground_alpha());
+        }
 
         match underline_style {
             UnderlineStyle::Underline => {
@@ -0,0 +1,281 @@
+#[cfg(not(target_os = "windows"))]
+use std::process::Command;
+use std::{ops::Range, sync::Arc};
+
+use log::error;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{editor::Style, renderer::LineFragment};
+
+// Only schemes that link_safety lets the user open, so that no link is shown as clickable
+// without doing anything
+const URL_SCHEMES: &[&str] = &["https://", "http://", "file://", "mailto:"];
+
+/// A link under the mouse, either set explicitly by Neovim through the `url` highlight attribute
+/// (OSC 8 in terminal buffers), or detected from text that looks like an URL.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Hyperlink {
+    pub url: String,
+    pub row: u64,
+    pub columns: Range<u64>,
+    pub style: Option<Arc<Style>>,
+}
+
+/// Finds the hyperlink covering `column` of the line made up of `line_fragments`.
+pub fn hyperlink_at(line_fragments: &[LineFragment], row: u64, column: u64) -> Option<Hyperlink> {
+    let index = line_fragments.iter().position(|fragment| {
+        (fragment.window_left..fragment.window_left + fragment.width).contains(&column)
+    })?;
+    let fragment = &line_fragments[index];
+
+    if let Some(url) = fragment.style.as_ref().and_then(|style| style.url.as_ref()) {
+        // The link can be split into several fragments, for example by double width characters
+        let same_url = |fragment: &&LineFragment| {
+            fragment.style.as_ref().and_then(|style| style.url.as_ref()) == Some(url)
+        };
+        let start = line_fragments[..index]
+            .iter()
+            .rev()
+            .take_while(same_url)
+            .last()
+            .unwrap_or(fragment)
+            .window_left;
+        let end = line_fragments[index..]
+            .iter()
+            .take_while(same_url)
+            .last()
+            .map(|fragment| fragment.window_left + fragment.width)
+            .unwrap();
+        return Some(Hyperlink {
+            url: url.clone(),
+            row,
+            columns: start..end,
+            style: fragment.style.clone(),
+        });
+    }
+
+    // Each grapheme occupies one cell, since double width characters end a fragment
+    let cells = line_fragments
+        .iter()
+        .flat_map(|fragment| {
+            fragment
+                .text
+                .graphemes(true)
+                .zip(fragment.window_left..)
+                .map(|(grapheme, column)| (column, grapheme))
+        })
+        .collect::<Vec<_>>();
+    let text = cells
+        .iter()
+        .map(|(_, grapheme)| *grapheme)
+        .collect::<String>();
+    let graphemes = text.grapheme_indices(true).collect::<Vec<_>>();
+
+    find_urls(&text).into_iter().find_map(|range| {
+        let first = graphemes.iter().position(|(i, _)| *i == range.start)?;
+        let last = graphemes.iter().rposition(|(i, _)| *i < range.end)?;
+        let columns = cells[first].0..cells[last].0 + 1;
+        columns.contains(&column).then(|| Hyperlink {
+            url: text[range].to_string(),
+            row,
+            columns,
+            style: fragment.style.clone(),
+        })
+    })
+}
+
+/// Returns the byte ranges of everything that looks like an URL in `text`.
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+    let mut urls = Vec::new();
+    let mut search_start = 0;
+    while let Some((start, scheme)) = URL_SCHEMES
+        .iter()
+        .filter_map(|scheme| {
+            text[search_start..]
+                .find(scheme)
+                .map(|i| (search_start + i, scheme))
+        })
+        .min_by_key(|(start, _)| *start)
+    {
+        let rest = &text[start..];
+        let mut end = start
+            + rest
+                .find(|c: char| c.is_whitespace() || "<>\"'`".contains(c))
+                .unwrap_or(rest.len());
+
+        // Trailing punctuation and unbalanced closing brackets most likely belong to the
+        // surrounding text
+        loop {
+            let url = &text[start..end];
+            let Some(last) = url.chars().last() else {
+                break;
+            };
+            let unbalanced = |open: char| url.matches(open).count() < url.matches(last).count();
+            let trim = match last {
+                '.' | ',' | ':' | ';' | '!' | '?' => true,
+                ')' => unbalanced('('),
+                ']' => unbalanced('['),
+                '}' => unbalanced('{'),
+                _ => false,
+            };
+            if !trim {
+                break;
+            }
+            end -= last.len_utf8();
+        }
+
+        if end > start + scheme.len() {
+            urls.push(start..end);
+        }
+        search_start = end.max(start + scheme.len());
+    }
+    urls
+}
+
+/// How a clicked hyperlink is opened. The links come from the text in the buffers and terminals,
+/// so only web and mail links are opened right away.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinkSafety {
+    Trusted,
+    NeedsConfirmation,
+    Refused,
+}
+
+pub fn link_safety(url: &str) -> LinkSafety {
+    let scheme = url
+        .split_once(':')
+        .map(|(scheme, _)| scheme.to_ascii_lowercase());
+    match scheme.as_deref() {
+        Some("http" | "https" | "mailto") => LinkSafety::Trusted,
+        Some("file") => LinkSafety::NeedsConfirmation,
+        _ => LinkSafety::Refused,
+    }
+}
+
+/// Opens the url or path with the default handler of the system. It's passed as a single
+/// argument and never through a shell, so it can't run other commands.
+pub fn open_url(url: &str) {
+    #[cfg(target_os = "windows")]
+    let result = shell_execute(url);
+    #[cfg(target_os = "macos")]
+    let result = Command::new("open").arg(url).spawn().map(|_| ());
+    #[cfg(not(any(target_os = "windows", target_os = "macos")))]
+    let result = Command::new("xdg-open").arg(url).spawn().map(|_| ());
+
+    if let Err(error) = result {
+        error!("Failed to open {url}: {error}");
+    }
+}
+
+#[cfg(target_os = "windows")]
+fn shell_execute(url: &str) -> std::io::Result<()> {
+    use winapi::um::{shellapi::ShellExecuteW, winuser::SW_SHOWNORMAL};
+
+    let wide = |text: &str| text.encode_utf16().chain(Some(0)).collect::<Vec<u16>>();
+    let operation = wide("open");
+    let file = wide(url);
+    let result = unsafe {
+        ShellExecuteW(
+            std::ptr::null_mut(),
+            operation.as_ptr(),
+            file.as_ptr(),
+            std::ptr::null(),
+            std::ptr::null(),
+            SW_SHOWNORMAL,
+        )
+    };
+    // Values above 32 mean success
+    if result as usize > 32 {
+        Ok(())
+    } else {
+        Err(std::io::Error::last_os_error())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::editor::Colors;
+
+    fn fragment(text: &str, window_left: u64, url: Option<&str>) -> LineFragment {
+        let mut style = Style::new(Colors::new(None, None, None));
+        style.url = url.map(String::from);
+        LineFragment {
+            text: text.into(),
+            window_left,
+            width: text.graphemes(true).count() as u64,
+            style: Some(Arc::new(style)),
+        }
+    }
+
+    #[test]
+    fn test_find_urls() {
+        let text = "see https://neovide.dev/faq.html, or (http://example.com/a_(b)).";
+        let urls = find_urls(text)
+            .into_iter()
+            .map(|range| &text[range])
+            .collect::<Vec<_>>();
+        assert_eq!(
+            urls,
+            vec!["https://neovide.dev/faq.html", "http://example.com/a_(b)"]
+        );
+        assert!(find_urls("https:// nothing here").is_empty());
+    }
+
+    #[test]
+    fn test_link_safety() {
+        assert_eq!(link_safety("https://neovide.dev"), LinkSafety::Trusted);
+        assert_eq!(link_safety("HTTP://neovide.dev"), LinkSafety::Trusted);
+        assert_eq!(
+            link_safety("mailto:someone@example.com"),
+            LinkSafety::Trusted
+        );
+        assert_eq!(
+            link_safety("file:///tmp/run.sh"),
+            LinkSafety::NeedsConfirmation
+        );
+        assert_eq!(link_safety("ftp://example.com"), LinkSafety::Refused);
+        assert_eq!(link_safety("calc.exe"), LinkSafety::Refused);
+    }
+
+    #[test]
+    fn test_detected_schemes_can_be_opened() {
+        for scheme in URL_SCHEMES {
+            let url = format!("{scheme}example.com");
+            assert_eq!(find_urls(&url), vec![0..url.len()]);
+            assert_ne!(link_safety(&url), LinkSafety::Refused, "{scheme}");
+        }
+        assert!(find_urls("ftp://example.com").is_empty());
+    }
+
+    #[test]
+    fn test_detected_url() {
+        let fragments = vec![
+            fragment("åh ", 0, None),
+            fragment("https://neovide.dev", 3, None),
+            fragment(" x", 22, None),
+        ];
+        let link = hyperlink_at(&fragments, 4, 10).unwrap();
+        assert_eq!(link.url, "https://neovide.dev");
+        assert_eq!(link.columns, 3..22);
+        assert_eq!(link.row, 4);
+        assert_eq!(hyperlink_at(&fragments, 4, 1), None);
+        assert_eq!(hyperlink_at(&fragments, 4, 23), None);
+    }
+
+    #[test]
+    fn test_explicit_url() {
+        let fragments = vec![
+            fragment("click ", 0, None),
+            fragment("here", 6, Some("https://neovide.dev")),
+            LineFragment {
+                width: 2,
+                ..fragment("漢", 10, Some("https://neovide.dev"))
+            },
+            fragment("!", 12, None),
+        ];
+        let link = hyperlink_at(&fragments, 0, 7).unwrap();
+        assert_eq!(link.url, "https://neovide.dev");
+        assert_eq!(link.columns, 6..12);
+    }
+}
@@ -0,0 +1,127 @@
+use skia_safe::{Canvas, Paint, Point, Rect};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+use crate::{profiling::tracy_zone, renderer::GridRenderer};
+
+/// The width of the caret drawn inside the composition, relative to the cell width.
+const CARET_WIDTH: f32 = 0.1;
+
+/// The text being composed with an input method, like the reading of a Japanese word before it's
+/// converted. It's drawn inline over the grid at the cursor, instead of in a separate window of
+/// the input method, with the clause that is being converted highlighted.
+#[derive(Default)]
+pub struct ImePreedit {
+    text: String,
+    /// The byte range of the clause being converted, or the caret when it's empty.
+    selection: Option<(usize, usize)>,
+}
+
+impl ImePreedit {
+    /// Replaces the composition, returns true if it changed.
+    pub fn set(&mut self, text: &str, selection: Option<(usize, usize)>) -> bool {
+        if self.text == text && self.selection == selection {
+            return false;
+        }
+        self.text = text.to_string();
+        self.selection = selection;
+        true
+    }
+
+    /// The number of grid cells before the selection, where the candidate window is placed.
+    pub fn selection_offset(&self) -> usize {
+        let start = self.selection.map_or(0, |(start, _)| start);
+        self.text
+            .get(..start)
+            .map_or(0, |text| text.graphemes(true).map(cell_width).sum())
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer, origin: Point) {
+        if self.text.is_empty() {
+            return;
+        }
+        tracy_zone!("ime_preedit_draw");
+
+        let font_width = grid_renderer.font_dimensions.width as f32;
+        let font_height = grid_renderer.font_dimensions.height as f32;
+        let y_adjustment = grid_renderer.shaper.lock().y_adjustment() as f32;
+        let colors = &grid_renderer.default_style.colors;
+        let (background, foreground) = (colors.background.unwrap(), colors.foreground.unwrap());
+        let mut paint = Paint::default();
+        paint.set_anti_alias(false);
+
+        let mut x = origin.x;
+        for (index, grapheme) in self.text.grapheme_indices(true) {
+            let width = cell_width(grapheme) as f32 * font_width;
+            // The clause being converted is shown in reverse video
+            let selected = self
+                .selection
+                .is_some_and(|(start, end)| index >= start && index < end);
+            let (cell_background, cell_foreground) = if selected {
+                (foreground, background)
+            } else {
+                (background, foreground)
+            };
+            paint.set_color4f(cell_background, None);
+            canvas.draw_rect(Rect::from_xywh(x, origin.y, width, font_height), &paint);
+            paint.set_color4f(cell_foreground, None);
+            for blob in grid_renderer
+                .shaper
+                .lock()
+                .shape_cached(grapheme.to_string(), Default::default(), false)
+                .iter()
+            {
+                canvas.draw_text_blob(blob, (x, origin.y + y_adjustment), &paint);
+            }
+            x += width;
+        }
+
+        // The whole composition is underlined, like in the system composition window
+        paint.set_color4f(foreground, None);
+        let underline_y = origin.y + font_height - 1.0;
+        canvas.draw_rect(
+            Rect::from_ltrb(origin.x, underline_y, x, underline_y + 1.0),
+            &paint,
+        );
+
+        // Without a clause to convert, the selection is the caret
+        if matches!(self.selection, Some((start, end)) if start == end) {
+            let caret_x = origin.x + self.selection_offset() as f32 * font_width;
+            canvas.draw_rect(
+                Rect::from_xywh(
+                    caret_x,
+                    origin.y,
+                    (font_width * CARET_WIDTH).max(1.0),
+                    font_height,
+                ),
+                &paint,
+            );
+        }
+    }
+}
+
+/// The number of grid cells taken by the grapheme cluster, two for the wide East Asian characters
+/// and emoji, like Neovim. Combining characters are part of the cluster, so it takes at least one.
+fn cell_width(grapheme: &str) -> usize {
+    grapheme.width().clamp(1, 2)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_selection_offset_counts_wide_characters() {
+        let mut preedit = ImePreedit::default();
+        assert!(preedit.set("にほんご", Some((6, 12))));
+        assert!(!preedit.set("にほんご", Some((6, 12))));
+        assert_eq!(preedit.selection_offset(), 4);
+
+        preedit.set("abc", Some((1, 1)));
+        assert_eq!(preedit.selection_offset(), 1);
+
+        // The combining accent is part of the e, and the emoji is wide
+        preedit.set("e\u{301}😀x", Some((7, 7)));
+        assert_eq!(preedit.selection_offset(), 3);
+    }
+}
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, ops::Range};
+
+use rmpv::Value;
+use skia_safe::{Canvas, Color, Paint, Point, Rect};
+
+use crate::{dimensions::Dimensions, renderer::LineFragment};
+
+/// The alpha of the default foreground color used for the guides.
+const DEFAULT_ALPHA: u8 = 0x40;
+
+/// The indentation settings of the buffer shown in a Neovim window, sent by the Lua side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct WindowIndent {
+    pub shiftwidth: u64,
+    /// The width of the number, sign and fold columns before the text.
+    pub textoff: u64,
+    /// The first buffer column shown, when the window is scrolled horizontally.
+    pub leftcol: u64,
+    /// `b:neovide_indent_guides`, overriding `g:neovide_indent_guides` for the buffer.
+    pub enabled: Option<bool>,
+}
+
+impl WindowIndent {
+    /// Parses a `[window, shiftwidth, textoff, leftcol, enabled]` list, where enabled may be nil.
+    pub fn parse(value: &Value) -> Option<(u64, Self)> {
+        match value.as_array()?.as_slice() {
+            [window, shiftwidth, textoff, leftcol, enabled] => Some((
+                window.as_u64()?,
+                WindowIndent {
+                    shiftwidth: shiftwidth.as_u64()?,
+                    textoff: textoff.as_u64()?,
+                    leftcol: leftcol.as_u64()?,
+                    enabled: enabled.as_bool(),
+                },
+            )),
+            _ => None,
+        }
+    }
+}
+
+/// Parses a `[window, [[row, first column, last column], ...]]` list of the trailing whitespace
+/// shown in a window, into the columns of each row.
+pub fn parse_trailing_whitespace(value: &Value) -> Option<(u64, HashMap<u64, Range<u64>>)> {
+    match value.as_array()?.as_slice() {
+        [window, rows] => Some((
+            window.as_u64()?,
+            rows.as_array()?
+                .iter()
+                .filter_map(|row| match row.as_array()?.as_slice() {
+                    [row, first, last] => {
+                        Some((row.as_u64()?, first.as_u64()?..last.as_u64()? + 1))
+                    }
+                    _ => None,
+                })
+                .collect(),
+        )),
+        _ => None,
+    }
+}
+
+/// What is drawn over the indentation of the lines of a window.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct IndentGuides {
+    pub indent: WindowIndent,
+    pub guides: bool,
+    pub whitespace: bool,
+    /// The default foreground at a low alpha is used without a color.
+    pub color: Option<Color>,
+}
+
+/// The trailing whitespace of a line, which is marked with dots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TrailingWhitespace {
+    pub columns: Range<u64>,
+    /// The default foreground at a low alpha is used without a color.
+    pub color: Option<Color>,
+}
+
+/// Checks that all the cells of the columns are spaces. The columns come from Neovim separately
+/// from the redraws, so they may not match the text on the screen yet.
+pub fn is_blank(line_fragments: &[LineFragment], columns: &Range<u64>) -> bool {
+    let mut covered = columns.start;
+    for fragment in line_fragments {
+        let fragment_columns = fragment.window_left..fragment.window_left + fragment.width;
+        if fragment_columns.end <= columns.start || fragment_columns.start >= columns.end {
+            continue;
+        }
+        // Double width characters are never whitespace
+        if fragment.text.chars().count() as u64 != fragment.width {
+            return false;
+        }
+        let skip = columns.start.saturating_sub(fragment.window_left) as usize;
+        let take = (columns.end.min(fragment_columns.end) - fragment.window_left) as usize - skip;
+        if fragment
+            .text
+            .chars()
+            .skip(skip)
+            .take(take)
+            .any(|c| c != ' ')
+        {
+            return false;
+        }
+        covered = covered.max(fragment_columns.end);
+    }
+    covered >= columns.end
+}
+
+/// Counts the spaces at the start of the text of the line, after the number and sign columns.
+/// Lines with only spaces have no indentation, so that the guides don't cover empty lines.
+fn indentation(line_fragments: &[LineFragment], textoff: u64) -> u64 {
+    let mut column = textoff;
+    for fragment in line_fragments {
+        if fragment.window_left + fragment.width <= column {
+            continue;
+        }
+        let skip = column.saturating_sub(fragment.window_left);
+        for character in fragment.text.chars().skip(skip as usize) {
+            if character != ' ' {
+                return column - textoff;
+            }
+            column += 1;
+        }
+        // Double width characters make the text shorter than the fragment
+        if fragment.text.chars().count() as u64 != fragment.width {
+            return column - textoff;
+        }
+    }
+    0
+}
+
+/// Draws the guides of the line on the canvas of its foreground, returning true when something
+/// was drawn.
+pub fn draw_indent_guides(
+    canvas: &Canvas,
+    line_fragments: &[LineFragment],
+    guides: &IndentGuides,
+    font_dimensions: Dimensions,
+    default_foreground: Color,
+) -> bool {
+    let IndentGuides {
+        indent,
+        color,
+        guides: draw_guides,
+        whitespace: draw_whitespace,
+    } = *guides;
+    let cells = indentation(line_fragments, indent.textoff);
+    if cells == 0 || indent.shiftwidth == 0 {
+        return false;
+    }
+
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(color.unwrap_or_else(|| default_foreground.with_a(DEFAULT_ALPHA)));
+
+    let (width, height) = (font_dimensions.width as f32, font_dimensions.height as f32);
+    let line_width = (width / 8.0).max(1.0);
+    let mut drawn = false;
+    for cell in 0..cells {
+        let left = (indent.textoff + cell) as f32 * width;
+        if (indent.leftcol + cell) % indent.shiftwidth == 0 {
+            if draw_guides {
+                canvas.draw_rect(Rect::from_xywh(left, 0.0, line_width, height), &paint);
+                drawn = true;
+            }
+        } else if draw_whitespace {
+            let center = Point::new(left + width / 2.0, height / 2.0);
+            canvas.draw_circle(center, line_width, &paint);
+            drawn = true;
+        }
+    }
+    drawn
+}
+
+/// Draws a dot on every cell of the trailing whitespace, returning true when something was drawn.
+pub fn draw_trailing_whitespace(
+    canvas: &Canvas,
+    trailing_whitespace: &TrailingWhitespace,
+    font_dimensions: Dimensions,
+    default_foreground: Color,
+) -> bool {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(true);
+    paint.set_color(
+        trailing_whitespace
+            .color
+            .unwrap_or_else(|| default_foreground.with_a(DEFAULT_ALPHA)),
+    );
+
+    let (width, height) = (font_dimensions.width as f32, font_dimensions.height as f32);
+    let radius = (width / 8.0).max(1.0);
+    for column in trailing_whitespace.columns.clone() {
+        let center = Point::new((column as f32 + 0.5) * width, height / 2.0);
+        canvas.draw_circle(center, radius, &paint);
+    }
+    !trailing_whitespace.columns.is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(text: &str, window_left: u64) -> LineFragment {
+        LineFragment {
+            text: text.into(),
+            window_left,
+            width: text.chars().count() as u64,
+            style: None,
+        }
+    }
+
+    #[test]
+    fn test_indentation_starts_after_the_text_offset() {
+        let line = [fragment(" 12 ", 0), fragment("    let", 4)];
+        assert_eq!(indentation(&line, 4), 4);
+        assert_eq!(indentation(&line, 0), 1);
+
+        let split = [fragment("  ", 0), fragment("  ", 2), fragment("x  ", 4)];
+        assert_eq!(indentation(&split, 0), 4);
+
+        let blank = [fragment(" 12 ", 0), fragment("        ", 4)];
+        assert_eq!(indentation(&blank, 4), 0);
+    }
+
+    #[test]
+    fn test_trailing_whitespace_must_be_blank() {
+        let line = [fragment("let x ", 0), fragment("   ", 6), fragment("y", 9)];
+        assert!(is_blank(&line, &(5..9)));
+        assert!(!is_blank(&line, &(5..10)));
+        assert!(!is_blank(&line, &(2..6)));
+        // Columns past the end of the line are not on the screen
+        assert!(!is_blank(&line, &(6..12)));
+    }
+}
@@ -0,0 +1,159 @@
+use std::sync::Arc;
+
+use skia_safe::{Canvas, Paint, Point, Rect};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{editor::Style, profiling::tracy_zone, renderer::GridRenderer};
+
+/// Characters typed in insert mode that have been sent to Neovim, but not yet confirmed by a
+/// redraw. They are drawn locally at the cursor position to hide the latency of the roundtrip,
+/// which is mostly noticeable when connected to a remote server.
+pub struct LocalEcho {
+    pending: Vec<String>,
+    origin: Point,
+    style: Option<Arc<Style>>,
+}
+
+impl LocalEcho {
+    pub fn new() -> Self {
+        Self {
+            pending: Vec::new(),
+            origin: Point::default(),
+            style: None,
+        }
+    }
+
+    /// Records typed keys to be echoed. `cursor_position` and `style`, the highlight of the cell
+    /// under the cursor, are only used when nothing is pending, since the cursor does not move
+    /// until Neovim responds.
+    pub fn push(&mut self, text: &str, cursor_position: Point, style: Option<Arc<Style>>) {
+        // Special and modified keys, which are enclosed in <>, can edit or move the text that is
+        // already echoed, and wide and combining characters would require knowing the width of
+        // the glyph. So drop the whole prediction and let Neovim draw the rest.
+        if text.starts_with('<') || !text.chars().all(is_predictable_char) {
+            self.pending.clear();
+            return;
+        }
+
+        if self.pending.is_empty() {
+            self.origin = cursor_position;
+            self.style = style;
+        }
+        self.pending.extend(text.chars().map(String::from));
+    }
+
+    /// Discards the prediction, returns true if there was anything pending.
+    pub fn clear(&mut self) -> bool {
+        let had_pending = !self.pending.is_empty();
+        self.pending.clear();
+        had_pending
+    }
+
+    /// The number of grid cells occupied by the echoed text.
+    pub fn width(&self) -> u64 {
+        self.pending.len() as u64
+    }
+
+    pub fn draw(&self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if self.pending.is_empty() {
+            return;
+        }
+        tracy_zone!("local_echo_draw");
+
+        let font_width = grid_renderer.font_dimensions.width as f32;
+        let font_height = grid_renderer.font_dimensions.height as f32;
+        let y_adjustment = grid_renderer.shaper.lock().y_adjustment() as f32;
+        let default_colors = &grid_renderer.default_style.colors;
+        let style = self.style.as_deref();
+        let background_color = style.map_or(default_colors.background.unwrap(), |style| {
+            style.background(default_colors)
+        });
+        let foreground_color = style.map_or(default_colors.foreground.unwrap(), |style| {
+            style.foreground(default_colors)
+        });
+        let coarse_style = style.map(|style| style.into()).unwrap_or_default();
+        let mut background = Paint::new(background_color, None);
+        background.set_anti_alias(false);
+        let mut foreground = Paint::new(foreground_color, None);
+        foreground.set_anti_alias(false);
+
+        for (i, text) in self.pending.iter().enumerate() {
+            let x = self.origin.x + i as f32 * font_width;
+            canvas.draw_rect(
+                Rect::from_xywh(x, self.origin.y, font_width, font_height),
+                &background,
+            );
+            for blob in grid_renderer
+                .shaper
+                .lock()
+                .shape_cached(text.as_str(), coarse_style, true)
+                .iter()
+            {
+                canvas.draw_text_blob(blob, (x, self.origin.y + y_adjustment), &foreground);
+            }
+        }
+    }
+}
+
+fn is_predictable_char(c: char) -> bool {
+    // Latin, Greek and Cyrillic letters don't need any shaping context. The width check excludes
+    // the control characters and the combining marks in those blocks.
+    let simple_script = matches!(
+        c,
+        '\u{20}'..='\u{24F}' | '\u{370}'..='\u{52F}' | '\u{1E00}'..='\u{1FFF}'
+    );
+    simple_script && c.width() == Some(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_push_records_origin_of_first_character() {
+        let mut echo = LocalEcho::new();
+        echo.push("a", Point::new(10.0, 20.0), None);
+        echo.push("b", Point::new(99.0, 99.0), None);
+        assert_eq!(echo.width(), 2);
+        assert_eq!(echo.origin, Point::new(10.0, 20.0));
+    }
+
+    #[test]
+    fn test_wide_characters_are_not_predicted() {
+        let mut echo = LocalEcho::new();
+        echo.push("漢", Point::default(), None);
+        echo.push("\t", Point::default(), None);
+        assert_eq!(echo.width(), 0);
+        echo.push("é", Point::default(), None);
+        assert_eq!(echo.width(), 1);
+        echo.push("漢", Point::default(), None);
+        assert_eq!(echo.width(), 0);
+        echo.push("e\u{301}", Point::default(), None);
+        echo.push("\u{301}", Point::default(), None);
+        echo.push("א", Point::default(), None);
+        echo.push("ب", Point::default(), None);
+        assert_eq!(echo.width(), 0);
+        echo.push("Жω", Point::default(), None);
+        assert_eq!(echo.width(), 2);
+    }
+
+    #[test]
+    fn test_special_keys_drop_the_prediction() {
+        let mut echo = LocalEcho::new();
+        echo.push("ab", Point::default(), None);
+        echo.push("<BS>", Point::default(), None);
+        assert_eq!(echo.width(), 0);
+        echo.push("c", Point::default(), None);
+        echo.push("<C-w>", Point::default(), None);
+        assert_eq!(echo.width(), 0);
+    }
+
+    #[test]
+    fn test_clear() {
+        let mut echo = LocalEcho::new();
+        assert!(!echo.clear());
+        echo.push("x", Point::default(), None);
+        assert!(echo.clear());
+        assert_eq!(echo.width(), 0);
+    }
+}
@@ -0,0 +1,221 @@
+use log::{Level, LevelFilter};
+use skia_safe::{Canvas, Color, Paint, Rect};
+use time::macros::format_description;
+
+use crate::{
+    log_collector::{log_entries, LogEntry, LogFilter},
+    profiling::tracy_zone,
+    renderer::GridRenderer,
+};
+
+/// The widest the module column gets, in characters.
+const MODULE_COLUMNS: usize = 24;
+
+/// Shows the log records Neovide kept in memory, filtered by level and module, over the grids.
+/// The entries are read when it's opened and after every key, so the newest ones show up by
+/// pressing any key.
+#[derive(Default)]
+pub struct LogViewer {
+    open: bool,
+    filter: LogFilter,
+    entries: Vec<LogEntry>,
+    // How many entries the view is scrolled up from the newest one
+    scroll: usize,
+    // The number of entries that fit, from the last draw
+    page_rows: usize,
+}
+
+impl LogViewer {
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Opens the viewer, the level and module filters are used when they are not empty.
+    pub fn open(&mut self, level: &str, module: &str) {
+        self.open = true;
+        if let Ok(level) = level.parse() {
+            self.filter.level = level;
+        }
+        if !module.is_empty() {
+            self.filter.module = module.to_string();
+        }
+        self.scroll = 0;
+        self.refresh();
+    }
+
+    pub fn close(&mut self) {
+        self.open = false;
+        self.entries.clear();
+    }
+
+    fn refresh(&mut self) {
+        self.entries = log_entries(&self.filter);
+        self.scroll = self.scroll.min(self.entries.len().saturating_sub(1));
+    }
+
+    /// Handles a key in the Neovim notation, returns false when the viewer should close.
+    pub fn handle_key(&mut self, key: &str) -> bool {
+        let page = self.page_rows.max(1);
+        match key {
+            "<Esc>" | "<C-c>" | "<C-[>" => return false,
+            "<Tab>" => self.filter.level = next_level(self.filter.level),
+            "<S-Tab>" => {
+                for _ in 0..3 {
+                    self.filter.level = next_level(self.filter.level);
+                }
+            }
+            "<Up>" | "<C-p>" => self.scroll += 1,
+            "<Down>" | "<C-n>" => self.scroll = self.scroll.saturating_sub(1),
+            "<PageUp>" | "<C-b>" => self.scroll += page,
+            "<PageDown>" | "<C-f>" => self.scroll = self.scroll.saturating_sub(page),
+            "<Home>" => self.scroll = usize::MAX,
+            "<End>" => self.scroll = 0,
+            "<BS>" | "<C-h>" => {
+                self.filter.module.pop();
+            }
+            "<C-u>" => self.filter.module.clear(),
+            "<lt>" => self.filter.module.push('<'),
+            text if !text.starts_with('<') => self.filter.module.push_str(text),
+            _ => {}
+        }
+        self.refresh();
+        true
+    }
+
+    pub fn draw(&mut self, canvas: &Canvas, grid_renderer: &mut GridRenderer) {
+        if !self.open {
+            return;
+        }
+        tracy_zone!("log_viewer_draw");
+
+        let font_width = grid_renderer.font_dimensions.width as f32;
+        let font_height = grid_renderer.font_dimensions.height as f32;
+        let y_adjustment = grid_renderer.shaper.lock().y_adjustment() as f32;
+        let colors = &grid_renderer.default_style.colors;
+        let background = colors.background.unwrap().to_color();
+        let foreground = colors.foreground.unwrap().to_color();
+
+        let size = canvas.base_layer_size();
+        let columns = ((size.width as f32 / font_width) as usize).saturating_sub(4);
+        let rows = ((size.height as f32 / font_height) as usize).saturating_sub(2);
+        // The header and the footer take a row each
+        if columns < 20 || rows < 3 {
+            return;
+        }
+        self.page_rows = rows - 2;
+        let left = ((size.width as f32 - columns as f32 * font_width) / 2.0).round();
+        let top = ((size.height as f32 - rows as f32 * font_height) / 2.0).round();
+        let viewer_rect = Rect::from_xywh(
+            left,
+            top,
+            columns as f32 * font_width,
+            rows as f32 * font_height,
+        );
+
+        let mut paint = Paint::default();
+        paint.set_anti_alias(false);
+        paint.set_color(background);
+        canvas.draw_rect(viewer_rect, &paint);
+        paint.set_color(foreground.with_a(0x60));
+        paint.set_style(skia_safe::PaintStyle::Stroke);
+        canvas.draw_rect(viewer_rect.with_outset((1.0, 1.0)), &paint);
+        paint.set_style(skia_safe::PaintStyle::Fill);
+
+        let mut draw_text = |text: &str, column: usize, row: usize, color: Color| {
+            let text: String = text.chars().take(columns.saturating_sub(column)).collect();
+            if text.is_empty() {
+                return;
+            }
+            paint.set_color(color);
+            let x = left + column as f32 * font_width;
+            let y = top + row as f32 * font_height + y_adjustment;
+            for blob in grid_renderer
+                .shaper
+                .lock()
+                .shape_cached(text, Default::default(), false)
+                .iter()
+            {
+                canvas.draw_text_blob(blob, (x, y), &paint);
+            }
+        };
+
+        let dimmed = foreground.with_a(0x90);
+        let header = format!(
+            " Neovide logs   level: {}   module: {}_   {} entries",
+            self.filter.level.as_str().to_lowercase(),
+            self.filter.module,
+            self.entries.len()
+        );
+        draw_text(&header, 0, 0, foreground);
+        draw_text(
+            " <Tab> level   type to filter modules   <Up>/<Down> scroll   <Esc> close",
+            0,
+            rows - 1,
+            dimmed,
+        );
+
+        let visible = self.page_rows;
+        let scroll = self.scroll.min(self.entries.len().saturating_sub(visible));
+        let end = self.entries.len() - scroll;
+        let start = end.saturating_sub(visible);
+        let time_format = format_description!("[hour]:[minute]:[second].[subsecond digits:3]");
+        for (row, entry) in self.entries[start..end].iter().enumerate() {
+            let row = row + 1;
+            let time = entry.time.format(time_format).unwrap_or_default();
+            let module: String = entry.module.chars().take(MODULE_COLUMNS).collect();
+            draw_text(&time, 1, row, dimmed);
+            draw_text(
+                entry.level.as_str(),
+                14,
+                row,
+                level_color(entry.level, foreground),
+            );
+            draw_text(&module, 20, row, dimmed);
+            // Only the first line of multi-line messages fits
+            let message = entry.message.lines().next().unwrap_or_default();
+            draw_text(message, 21 + MODULE_COLUMNS, row, foreground);
+        }
+    }
+}
+
+/// Cycles through the levels that are kept, from the least to the most verbose.
+fn next_level(level: LevelFilter) -> LevelFilter {
+    match level {
+        LevelFilter::Error => LevelFilter::Warn,
+        LevelFilter::Warn => LevelFilter::Info,
+        LevelFilter::Info => LevelFilter::Debug,
+        _ => LevelFilter::Error,
+    }
+}
+
+fn level_color(level: Level, foreground: Color) -> Color {
+    match level {
+        Level::Error => Color::from_rgb(0xe0, 0x5a, 0x5a),
+        Level::Warn => Color::from_rgb(0xd8, 0xa6, 0x3c),
+        Level::Info => foreground,
+        Level::Debug | Level::Trace => foreground.with_a(0x90),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_keys_change_filter() {
+        let mut viewer = LogViewer::default();
+        viewer.open("warn", "bridge");
+        assert_eq!(viewer.filter.level, LevelFilter::Warn);
+        assert_eq!(viewer.filter.module, "bridge");
+
+        viewer.handle_key("<Tab>");
+        assert_eq!(viewer.filter.level, LevelFilter::Info);
+        viewer.handle_key("<S-Tab>");
+        assert_eq!(viewer.filter.level, LevelFilter::Warn);
+
+        viewer.handle_key("<C-u>");
+        viewer.handle_key("r");
+        assert_eq!(viewer.filter.module, "r");
+        assert!(!viewer.handle_key("<Esc>"));
+    }
+}
@@ -1,11 +1,30 @@
 pub mod animation_utils;
+mod busy_overlay;
+mod capture;
+mod command_palette;
 pub mod cursor_renderer;
+mod dialog;
+mod font_transition;
 pub mod fonts;
+mod frame_producer;
 pub mod grid_renderer;
+mod hyperlink;
+mod ime_preedit;
+mod indent_guides;
+mod local_echo;
+mod log_viewer;
 pub mod opengl;
 pub mod profiler;
+mod progress_bar;
 mod rendered_window;
+mod resize_preview;
+mod separators;
+mod startup_fade;
+mod surface_options;
+mod visual_bell;
 mod vsync;
+mod window_highlights;
+mod window_state;
 
 #[cfg(target_os = "windows")]
 pub mod d3d;
@@ -13,12 +32,14 @@ pub mod d3d;
 use std::{
     cmp::Ordering,
     collections::{hash_map::Entry, HashMap},
-    sync::Arc,
+    ops::Range,
+    sync::RwLock,
 };
 
-use log::error;
-use skia_safe::{Canvas, Point, Rect};
+use rmpv::Value;
+use skia_safe::{BlendMode, Canvas, ClipOp, Color, Contains, Paint, Point, Rect, Shader, TileMode};
 use winit::{
+    dpi::PhysicalSize,
     event::Event,
     event_loop::{EventLoop, EventLoopProxy},
     window::{Window, WindowBuilder},
@@ -26,11 +47,15 @@ use winit::{
 
 use crate::{
     bridge::EditorMode,
+    crash_report::record_draw_commands,
     dimensions::Dimensions,
     editor::{Cursor, Style},
-    profiling::{tracy_create_gpu_context, tracy_named_frame, tracy_zone},
+    profiling::{tracy_create_gpu_context, tracy_zone},
     settings::*,
-    window::{ShouldRender, UserEvent},
+    window::{
+        power_saving, prefers_high_contrast, prefers_reduced_motion, wait_for_desktop_settings,
+        Progress, ShouldRender, UserEvent, WindowSettingsChanged,
+    },
     WindowSettings,
 };
 
@@ -38,19 +63,89 @@ use crate::{
 use crate::profiling::GpuCtx;
 
 #[cfg(target_os = "windows")]
-use crate::CmdLineSettings;
+use crate::{crash_report::set_gpu_backend, CmdLineSettings};
 
+use busy_overlay::BusyOverlay;
+pub use capture::{CapturedFrame, FrameRecorder};
+pub use command_palette::{CommandPalette, PaletteAction, PaletteItem, PaletteItemKind};
+pub use cursor_renderer::register_cursor_vfx;
 use cursor_renderer::CursorRenderer;
-pub use fonts::caching_shaper::CachingShaper;
+pub use dialog::{CrashChoice, CrashDialog, LinkChoice, LinkDialog, QuitChoice, QuitDialog};
+use font_transition::FontTransition;
+pub use fonts::caching_shaper::SharedShaper;
+use frame_producer::FramePipeline;
 pub use grid_renderer::GridRenderer;
-pub use rendered_window::{LineFragment, RenderedWindow, WindowDrawCommand, WindowDrawDetails};
+pub use hyperlink::{find_urls, link_safety, open_url, Hyperlink, LinkSafety};
+use ime_preedit::ImePreedit;
+pub use indent_guides::{parse_trailing_whitespace, WindowIndent};
+use local_echo::LocalEcho;
+pub use log_viewer::LogViewer;
+use progress_bar::ProgressBar;
+pub use rendered_window::{
+    LineFragment, RenderedWindow, ViewportMargins, WindowDrawCommand, WindowDrawDetails,
+};
+use resize_preview::ResizePreview;
+use separators::Separators;
+use startup_fade::StartupFade;
+pub use surface_options::{OutputColorSpace, SubpixelOrder, SurfaceOptions};
+use visual_bell::{VisualBell, VisualBellEasing, VisualBellEffect};
+pub use window_highlights::WindowHighlights;
+use window_highlights::WindowUnderlay;
 
 pub use vsync::VSync;
 
+/// What the renderer ended up using, reported by `:checkhealth neovide`.
+#[derive(Clone, Debug, Default)]
+pub struct RendererStatus {
+    pub vsync: String,
+    /// The fonts of `guifont`, and whether they could be loaded.
+    pub fonts: Vec<(String, bool)>,
+    pub font_caches: FontCacheUsage,
+}
+
+/// How full the font caches are, returned by `neovide.get_font_cache_usage`.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FontCacheUsage {
+    /// The bytes of the glyphs rasterized by Skia, and its limit.
+    pub glyph_bytes: usize,
+    pub glyph_limit: usize,
+    /// The shaped texts, shared by all the shapers of the process, and how many are kept.
+    pub shaped_texts: usize,
+    pub shaped_text_capacity: usize,
+    /// The loaded fonts, and how many are kept.
+    pub loaded_fonts: usize,
+    pub loaded_font_capacity: usize,
+}
+
+impl From<FontCacheUsage> for Value {
+    fn from(usage: FontCacheUsage) -> Self {
+        Value::Map(
+            [
+                ("glyph_bytes", usage.glyph_bytes),
+                ("glyph_limit", usage.glyph_limit),
+                ("shaped_texts", usage.shaped_texts),
+                ("shaped_text_capacity", usage.shaped_text_capacity),
+                ("loaded_fonts", usage.loaded_fonts),
+                ("loaded_font_capacity", usage.loaded_font_capacity),
+            ]
+            .into_iter()
+            .map(|(name, value)| (Value::from(name), Value::from(value as u64)))
+            .collect(),
+        )
+    }
+}
+
+lazy_static! {
+    // Written by the window thread, and read when Neovim requests the health status
+    pub static ref RENDERER_STATUS: RwLock<RendererStatus> = RwLock::new(RendererStatus::default());
+}
+
 use self::fonts::font_options::FontOptions;
 
 #[derive(SettingGroup, Clone)]
 pub struct RendererSettings {
+    pub animations_enabled: bool,
+    high_contrast: bool,
     position_animation_length: f32,
     scroll_animation_length: f32,
     scroll_animation_far_lines: u32,
@@ -64,11 +159,37 @@ pub struct RendererSettings {
     debug_renderer: bool,
     profiler: bool,
     underline_stroke_scale: f32,
+    font_transition_length: f32,
+    startup_fade_length: f32,
+    font_cache_budget: u32,
+    shaped_text_cache_size: u32,
+    loaded_fonts_cache_size: u32,
+    ligatures: bool,
+    indent_guides: bool,
+    indent_whitespace: bool,
+    trailing_whitespace: bool,
+    indent_guides_color: String,
+    #[range = "0.0..=1.0"]
+    inactive_window_dim: f32,
+    #[range = "0.0..=1.0"]
+    crosshair_opacity: f32,
+    crosshair_column: bool,
+    cursorline_overlay: bool,
+    colorcolumn_overlay: bool,
+    native_separators: bool,
+    pub color_space: OutputColorSpace,
+    pub gamma_correct_blending: bool,
+    subpixel_antialiasing: SubpixelOrder,
+    visual_bell_effect: VisualBellEffect,
+    visual_bell_duration: f32,
+    visual_bell_easing: VisualBellEasing,
 }
 
 impl Default for RendererSettings {
     fn default() -> Self {
         Self {
+            animations_enabled: !prefers_reduced_motion(),
+            high_contrast: prefers_high_contrast(),
             position_animation_length: 0.15,
             scroll_animation_length: 0.3,
             scroll_animation_far_lines: 1,
@@ -82,10 +203,44 @@ impl Default for RendererSettings {
             debug_renderer: false,
             profiler: false,
             underline_stroke_scale: 1.,
+            font_transition_length: 0.15,
+            startup_fade_length: 0.2,
+            font_cache_budget: 64,
+            shaped_text_cache_size: 10000,
+            loaded_fonts_cache_size: 20,
+            ligatures: true,
+            indent_guides: false,
+            indent_whitespace: false,
+            trailing_whitespace: false,
+            indent_guides_color: String::new(),
+            inactive_window_dim: 0.0,
+            crosshair_opacity: 0.0,
+            crosshair_column: true,
+            cursorline_overlay: false,
+            colorcolumn_overlay: false,
+            native_separators: false,
+            color_space: OutputColorSpace::default(),
+            gamma_correct_blending: false,
+            subpixel_antialiasing: SubpixelOrder::None,
+            visual_bell_effect: VisualBellEffect::default(),
+            visual_bell_duration: 0.2,
+            visual_bell_easing: VisualBellEasing::default(),
         }
     }
 }
 
+/// Turns the high contrast mode on when the desktop prefers it, after waiting for its settings to
+/// be read. It's called before the settings are read from Neovim, so that a
+/// `g:neovide_high_contrast` set in the config still takes precedence.
+pub fn apply_preferred_high_contrast() {
+    wait_for_desktop_settings();
+    let mut settings = SETTINGS.get::<RendererSettings>();
+    if prefers_high_contrast() && !settings.high_contrast {
+        settings.high_contrast = true;
+        SETTINGS.set(&settings);
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum DrawCommand {
     CloseWindow(u64),
@@ -105,6 +260,21 @@ pub struct Renderer {
     cursor_renderer: CursorRenderer,
     pub grid_renderer: GridRenderer,
     current_mode: EditorMode,
+    local_echo: LocalEcho,
+    ime_preedit: ImePreedit,
+    pub command_palette: CommandPalette,
+    pub log_viewer: LogViewer,
+    busy_overlay: BusyOverlay,
+    pub quit_dialog: QuitDialog,
+    pub crash_dialog: CrashDialog,
+    pub link_dialog: LinkDialog,
+    frame_pipeline: FramePipeline,
+    // The cursor updates and local echo clears of the draw commands, applied once the frame with
+    // the lines of the same batch is drawn, by the sequence number of the batch
+    pending_updates: Vec<(u64, PendingUpdate)>,
+    // The size of the main grid, known before the frame with it is produced
+    main_grid_size: Option<Dimensions>,
+    hovered_cell: Option<(u64, u64, u64)>,
 
     rendered_windows: HashMap<u64, RenderedWindow>,
     pub window_regions: Vec<WindowDrawDetails>,
@@ -112,6 +282,38 @@ pub struct Renderer {
     profiler: profiler::Profiler,
     os_scale_factor: f64,
     user_scale_factor: f64,
+    zoom: f64,
+    // The scale the grids are drawn with while the zoom animates, relative to `zoom`
+    zoom_preview: f32,
+    font_transition: Option<FontTransition>,
+    startup_fade: Option<StartupFade>,
+    resize_preview: Option<ResizePreview>,
+    // The magnification of the windows set with `neovide.set_window_zoom`, by window handle
+    window_zooms: HashMap<u64, f32>,
+    window_highlights: WindowHighlights,
+    separators: Separators,
+    progress_bar: ProgressBar,
+    visual_bell: VisualBell,
+    // `g:neovide_padding_color`, parsed when it changes
+    padding_color: Option<Color>,
+    // The last `guifont`, and the size of the active profile that overrides it
+    guifont: Option<String>,
+    guifont_size: Option<f32>,
+    // The window surface can't be transparent in the subpixel antialiasing mode
+    pub opaque_surface: bool,
+}
+
+/// Parses a CSS color, like `#1e1e2e` or `darkblue`.
+fn parse_color(color: &str) -> Option<Color> {
+    let [r, g, b, a] = color.parse::<csscolorparser::Color>().ok()?.to_rgba8();
+    Some(Color::from_argb(a, r, g, b))
+}
+
+/// An update of the draw commands that has to wait for the frame of its batch, so that the cursor
+/// doesn't move before the text under it.
+enum PendingUpdate {
+    Cursor(Cursor),
+    ClearLocalEcho,
 }
 
 /// Results of processing the draw commands from the command channel.
@@ -123,12 +325,16 @@ pub struct DrawCommandResult {
 impl Renderer {
     pub fn new(os_scale_factor: f64, init_font_settings: Option<FontSettings>) -> Self {
         let window_settings = SETTINGS.get::<WindowSettings>();
+        let opaque_surface =
+            SurfaceOptions::from_settings(&SETTINGS.get::<RendererSettings>()).opaque();
 
         let user_scale_factor = window_settings.scale_factor.into();
         let scale_factor = user_scale_factor * os_scale_factor;
         let cursor_renderer = CursorRenderer::new();
         let mut grid_renderer = GridRenderer::new(scale_factor);
-        grid_renderer.update_font_options(init_font_settings.map(|x| x.into()).unwrap_or_default());
+        let font_options: FontOptions = init_font_settings.map(|x| x.into()).unwrap_or_default();
+        grid_renderer.update_font_options(font_options);
+        let frame_pipeline = FramePipeline::new(&grid_renderer);
         let current_mode = EditorMode::Unknown(String::from(""));
 
         let rendered_windows = HashMap::new();
@@ -141,14 +347,53 @@ impl Renderer {
             cursor_renderer,
             grid_renderer,
             current_mode,
+            local_echo: LocalEcho::new(),
+            ime_preedit: ImePreedit::default(),
+            command_palette: CommandPalette::default(),
+            log_viewer: LogViewer::default(),
+            busy_overlay: BusyOverlay::default(),
+            quit_dialog: QuitDialog::default(),
+            crash_dialog: CrashDialog::default(),
+            link_dialog: LinkDialog::default(),
+            frame_pipeline,
+            pending_updates: Vec::new(),
+            main_grid_size: None,
+            hovered_cell: None,
             window_regions,
             profiler,
             os_scale_factor,
             user_scale_factor,
+            zoom: 1.0,
+            zoom_preview: 1.0,
+            font_transition: None,
+            startup_fade: None,
+            resize_preview: None,
+            window_zooms: HashMap::new(),
+            window_highlights: WindowHighlights::default(),
+            separators: Separators::default(),
+            progress_bar: ProgressBar::default(),
+            visual_bell: VisualBell::default(),
+            padding_color: parse_color(&window_settings.padding_color),
+            guifont: None,
+            guifont_size: None,
+            opaque_surface,
         }
     }
 
     pub fn handle_event(&mut self, event: &Event<UserEvent>) -> bool {
+        // Settings like the underline scale affect how the cached line pictures are drawn
+        match event {
+            Event::UserEvent(UserEvent::SettingsChanged(SettingsChanged::Renderer(_))) => {
+                self.grid_renderer.generation += 1;
+                self.update_frame_pipeline();
+            }
+            Event::UserEvent(UserEvent::SettingsChanged(SettingsChanged::Window(
+                WindowSettingsChanged::PaddingColor(color),
+            ))) => {
+                self.padding_color = parse_color(color);
+            }
+            _ => {}
+        }
         self.cursor_renderer.handle_event(event)
     }
 
@@ -162,20 +407,127 @@ impl Renderer {
 
     pub fn draw_frame(&mut self, root_canvas: &Canvas, dt: f32) {
         tracy_zone!("renderer_draw_frame");
+        root_canvas.save();
+        root_canvas.reset_matrix();
+        if let Some(resize_preview) = &self.resize_preview {
+            resize_preview.draw(root_canvas);
+        } else {
+            self.draw_grids(root_canvas);
+        }
+        if let Some(font_transition) = &self.font_transition {
+            font_transition.draw(root_canvas, self.grid_renderer.font_dimensions);
+        }
+        if let Some(startup_fade) = &self.startup_fade {
+            startup_fade.draw(root_canvas);
+        }
+        self.draw_progress_bar(root_canvas);
+        self.draw_visual_bell(root_canvas);
+        self.busy_overlay.draw(root_canvas, &mut self.grid_renderer);
+        self.log_viewer.draw(root_canvas, &mut self.grid_renderer);
+        self.quit_dialog.draw(root_canvas, &mut self.grid_renderer);
+        self.link_dialog.draw(root_canvas, &mut self.grid_renderer);
+        self.crash_dialog.draw(root_canvas, &mut self.grid_renderer);
+        self.command_palette
+            .draw(root_canvas, &mut self.grid_renderer);
+        self.profiler.draw(root_canvas, dt);
+        root_canvas.restore();
+    }
+
+    /// Draws the splash shown while Neovim starts, which is only the background for now.
+    pub fn draw_splash(&self, root_canvas: &Canvas, color: Color) {
+        root_canvas.clear(color);
+    }
+
+    /// Fades the first frame in from the splash.
+    pub fn begin_startup_fade(&mut self, splash_color: Color) {
+        self.startup_fade = Some(StartupFade::new(splash_color));
+    }
+
+    /// Renders the current frame into an image before the font changes, so that it can be faded
+    /// out while the frames with the new font fade in.
+    pub fn begin_font_transition(&mut self, root_canvas: &Canvas) {
+        if SETTINGS.get::<RendererSettings>().font_transition_length <= 0.0 {
+            return;
+        }
+        let Some(mut surface) = root_canvas.new_surface(&root_canvas.image_info(), None) else {
+            return;
+        };
+        self.draw_grids(surface.canvas());
+
+        let font_dimensions = self.grid_renderer.font_dimensions;
+        let origin = self
+            .rendered_windows
+            .get(&1)
+            .map(|root_window| root_window.pixel_region(font_dimensions))
+            .map_or(Point::default(), |region| (region.left, region.top).into());
+        self.font_transition = Some(FontTransition::new(
+            surface.image_snapshot(),
+            origin,
+            font_dimensions,
+        ));
+    }
+
+    /// Renders the current frame, at the size of the window before the resize, into an image that
+    /// is stretched over the window until the resize ends.
+    pub fn begin_resize_preview(&mut self, root_canvas: &Canvas, size: PhysicalSize<u32>) {
+        if self.resize_preview.is_some() {
+            return;
+        }
+        let info = root_canvas
+            .image_info()
+            .with_dimensions((size.width as i32, size.height as i32));
+        let Some(mut surface) = root_canvas.new_surface(&info, None) else {
+            return;
+        };
+        self.draw_grids(surface.canvas());
+        self.resize_preview = Some(ResizePreview::new(surface.image_snapshot()));
+    }
+
+    pub fn end_resize_preview(&mut self) {
+        self.resize_preview = None;
+    }
+
+    fn draw_grids(&mut self, root_canvas: &Canvas) {
         let default_background = self.grid_renderer.get_default_background();
         let font_dimensions = self.grid_renderer.font_dimensions;
 
-        let transparency = { SETTINGS.get::<WindowSettings>().transparency };
-        root_canvas.clear(default_background.with_a((255.0 * transparency) as u8));
+        let window_settings = SETTINGS.get::<WindowSettings>();
+        let settings = SETTINGS.get::<RendererSettings>();
+        let transparency = if self.opaque_surface || settings.high_contrast {
+            1.0
+        } else {
+            window_settings.transparency
+        };
+        let alpha = (255.0 * transparency) as u8;
+        let padding_color = self.padding_color.unwrap_or(default_background);
+        root_canvas.clear(padding_color.with_a(alpha));
         root_canvas.save();
         root_canvas.reset_matrix();
+        if self.zoom_preview != 1.0 {
+            if let Some(root_window) = self.rendered_windows.get(&1) {
+                let origin = root_window.pixel_region(font_dimensions);
+                root_canvas.translate((origin.left, origin.top));
+                root_canvas.scale((self.zoom_preview, self.zoom_preview));
+                root_canvas.translate((-origin.left, -origin.top));
+            }
+        }
+
+        if window_settings.padding_extend_background {
+            self.extend_window_backgrounds(root_canvas, alpha);
+        }
 
         if let Some(root_window) = self.rendered_windows.get(&1) {
             let clip_rect = root_window.pixel_region(font_dimensions);
             root_canvas.clip_rect(clip_rect, None, Some(false));
         }
 
-        let windows: Vec<&mut RenderedWindow> = {
+        if settings.native_separators {
+            self.update_separators();
+        } else {
+            self.separators.clear();
+        }
+
+        let order: Vec<u64> = {
             let (mut root_windows, mut floating_windows): (
                 Vec<&mut RenderedWindow>,
                 Vec<&mut RenderedWindow>,
@@ -185,35 +537,447 @@ impl Renderer {
                 .filter(|window| !window.hidden)
                 .partition(|window| window.anchor_info.is_none());
 
-            root_windows
-                .sort_by(|window_a, window_b| window_a.id.partial_cmp(&window_b.id).unwrap());
+            // Zoomed windows are drawn over the windows next to them, which they cover
+            root_windows.sort_by_key(|window| (window.is_zoomed(), window.id));
 
             floating_windows.sort_by(floating_sort);
+            floating_windows.sort_by_key(|window| window.is_zoomed());
 
-            root_windows.into_iter().chain(floating_windows).collect()
+            root_windows
+                .into_iter()
+                .chain(floating_windows)
+                .map(|window| window.id)
+                .collect()
+        };
+        // The high contrast mode ignores `winblend` and `pumblend`
+        let blend_underlays = if settings.high_contrast {
+            HashMap::new()
+        } else {
+            self.blend_underlays(&order, font_dimensions)
         };
 
-        let settings = SETTINGS.get::<RendererSettings>();
         let mut floating_rects = Vec::new();
 
+        let default_colors = &self.grid_renderer.default_style.colors;
+        let window_highlights = &self.window_highlights;
+        let cursor_window = self.cursor_renderer.parent_window_id();
+        let cursor_center = self.cursor_renderer.get_animated_center().y;
+        let mut windows: Vec<&mut RenderedWindow> = self
+            .rendered_windows
+            .values_mut()
+            .filter(|window| !window.hidden)
+            .collect();
+        windows.sort_by_key(|window| order.iter().position(|id| *id == window.id));
         self.window_regions = windows
             .into_iter()
             .map(|window| {
+                let background = window
+                    .background_color(default_colors)
+                    .unwrap_or(default_background);
+                if let Some(cells) = blend_underlays.get(&window.id) {
+                    draw_blend_underlay(root_canvas, cells, alpha);
+                }
+                let underlay = window
+                    .window_handle
+                    .and_then(|handle| window_highlights.windows.get(&handle))
+                    .map(|highlight| WindowUnderlay {
+                        highlight,
+                        color_column_color: window_highlights
+                            .color_column_color
+                            .filter(|_| settings.colorcolumn_overlay),
+                        cursorline: window_highlights
+                            .cursorline_color
+                            .filter(|_| settings.cursorline_overlay && window.id == cursor_window)
+                            .map(|color| (cursor_center, color)),
+                    });
                 window.draw(
                     root_canvas,
                     &settings,
-                    default_background.with_a((255.0 * transparency) as u8),
+                    background.with_a((255.0 * transparency) as u8),
                     font_dimensions,
                     &mut floating_rects,
+                    underlay.as_ref(),
                 )
             })
             .collect();
 
+        self.draw_separators(
+            root_canvas,
+            window_settings.padding_extend_background,
+            alpha,
+        );
+        self.draw_inactive_window_dim(root_canvas, &settings);
+        self.draw_crosshair(root_canvas, &settings);
+        self.draw_hovered_hyperlink(root_canvas);
+        // The text at the cursor is magnified with the window it's in
+        root_canvas.save();
+        if let Some(window) = self.rendered_windows.get(&cursor_window) {
+            window.apply_zoom(root_canvas, font_dimensions);
+        }
+        self.local_echo.draw(root_canvas, &mut self.grid_renderer);
         self.cursor_renderer
             .draw(&mut self.grid_renderer, root_canvas);
+        // Covers the cursor, since the composition has its own caret
+        self.ime_preedit.draw(
+            root_canvas,
+            &mut self.grid_renderer,
+            self.cursor_renderer.get_current_position(),
+        );
+        root_canvas.restore();
 
-        self.profiler.draw(root_canvas, dt);
+        root_canvas.restore();
+    }
+
+    /// The backgrounds of the cells under the text of the blended floating windows, by the id of
+    /// the floating window. Like in Neovim, a cell with text hides what is under it, and its
+    /// background is blended with the background of the cell under it. The blank cells are blended
+    /// with everything under them, which the alpha of their background already does.
+    fn blend_underlays(
+        &self,
+        order: &[u64],
+        font_dimensions: Dimensions,
+    ) -> HashMap<u64, Vec<(Rect, Color)>> {
+        let default_colors = &self.grid_renderer.default_style.colors;
+        let windows: Vec<&RenderedWindow> = order
+            .iter()
+            .filter_map(|id| self.rendered_windows.get(id))
+            .collect();
+        let (width, height) = (font_dimensions.width as f32, font_dimensions.height as f32);
+        let mut underlays = HashMap::new();
+        for (index, window) in windows.iter().enumerate() {
+            if window.anchor_info.is_none() {
+                continue;
+            }
+            let runs = window.blended_text_cells();
+            if runs.is_empty() {
+                continue;
+            }
+            let region = window.pixel_region(font_dimensions);
+            let mut cells: Vec<(Rect, Color)> = Vec::new();
+            for (start, row, run_width) in runs {
+                for column in start..start + run_width {
+                    let cell = Rect::from_xywh(
+                        region.left + column as f32 * width,
+                        region.top + row as f32 * height,
+                        width,
+                        height,
+                    );
+                    let center = cell.center();
+                    let below = windows[..index].iter().rev().find_map(|below| {
+                        let below_region = below.pixel_region(font_dimensions);
+                        below_region.contains(center).then_some(())?;
+                        below.cell_background(
+                            ((center.x - below_region.left) / width) as u64,
+                            ((center.y - below_region.top) / height) as u64,
+                            default_colors,
+                        )
+                    });
+                    let Some(color) = below else {
+                        continue;
+                    };
+                    match cells.last_mut() {
+                        Some((rect, last_color))
+                            if *last_color == color
+                                && rect.right == cell.left
+                                && rect.top == cell.top =>
+                        {
+                            rect.right = cell.right;
+                        }
+                        _ => cells.push((cell, color)),
+                    }
+                }
+            }
+            underlays.insert(window.id, cells);
+        }
+        underlays
+    }
+
+    fn draw_progress_bar(&self, root_canvas: &Canvas) {
+        let default_colors = &self.grid_renderer.default_style.colors;
+        self.progress_bar.draw(
+            root_canvas,
+            default_colors
+                .foreground
+                .unwrap_or(skia_safe::colors::WHITE)
+                .to_color(),
+            self.grid_renderer.scale_factor as f32,
+        );
+    }
+
+    fn draw_visual_bell(&self, root_canvas: &Canvas) {
+        let default_colors = &self.grid_renderer.default_style.colors;
+        self.visual_bell.draw(
+            root_canvas,
+            SETTINGS.get::<RendererSettings>().visual_bell_effect,
+            default_colors
+                .foreground
+                .unwrap_or(skia_safe::colors::WHITE)
+                .to_color(),
+            self.grid_renderer.scale_factor as f32,
+        );
+    }
+
+    fn update_separators(&mut self) {
+        let font_dimensions = self.grid_renderer.font_dimensions;
+        let windows: Vec<_> = self
+            .rendered_windows
+            .values()
+            .filter(|window| window.id != 1 && !window.hidden && window.anchor_info.is_none())
+            .map(|window| (window.pixel_region(font_dimensions), window.window_handle))
+            .collect();
+        self.separators.update(&windows, font_dimensions);
+    }
+
+    /// Draws the native separators, over the background of the window on their left when the
+    /// backgrounds are extended, or the default background otherwise.
+    fn draw_separators(&self, root_canvas: &Canvas, extend_background: bool, alpha: u8) {
+        let floating_rects: Vec<_> = self
+            .window_regions
+            .iter()
+            .filter(|details| details.floating_order.is_some())
+            .map(|details| details.region)
+            .collect();
+        let default_colors = &self.grid_renderer.default_style.colors;
+        let default_background = self.grid_renderer.get_default_background();
+        let background = |window_handle: u64| {
+            self.rendered_windows
+                .values()
+                .find(|window| extend_background && window.window_handle == Some(window_handle))
+                .and_then(|window| window.background_color(default_colors))
+                .unwrap_or(default_background)
+                .with_a(alpha)
+        };
+        self.separators.draw(
+            root_canvas,
+            background,
+            default_colors
+                .foreground
+                .unwrap_or(skia_safe::colors::WHITE)
+                .to_color(),
+            self.grid_renderer.scale_factor as f32,
+            &floating_rects,
+        );
+    }
+
+    /// Returns the handle of the Neovim window owning the separator at the position, if native
+    /// separators are enabled.
+    pub fn separator_at(&self, position: Point) -> Option<u64> {
+        self.separators.separator_at(position)
+    }
+
+    pub fn set_hovered_separator(&mut self, window_handle: Option<u64>) {
+        self.separators.set_hovered(window_handle);
+    }
+
+    /// Fills the padding next to the windows at the edges of the editor with their background, so
+    /// that it looks like the windows extend to the border.
+    fn extend_window_backgrounds(&self, root_canvas: &Canvas, alpha: u8) {
+        let font_dimensions = self.grid_renderer.font_dimensions;
+        let Some(root_region) = self
+            .rendered_windows
+            .get(&1)
+            .map(|window| window.pixel_region(font_dimensions))
+        else {
+            return;
+        };
+        let canvas_size = root_canvas.base_layer_size();
+        let default_colors = &self.grid_renderer.default_style.colors;
+        let near = |a: f32, b: f32| (a - b).abs() < font_dimensions.width.max(1) as f32;
+
+        // Replace the padding color instead of blending over it, when it's translucent
+        let mut paint = Paint::default();
+        paint.set_blend_mode(BlendMode::Src);
+        for window in self
+            .rendered_windows
+            .values()
+            .filter(|window| window.id != 1 && !window.hidden && window.anchor_info.is_none())
+        {
+            let background = window
+                .background_color(default_colors)
+                .unwrap_or_else(|| self.grid_renderer.get_default_background());
+            let region = window.pixel_region(font_dimensions);
+            let extended = Rect::from_ltrb(
+                if near(region.left, root_region.left) {
+                    0.0
+                } else {
+                    region.left
+                },
+                if near(region.top, root_region.top) {
+                    0.0
+                } else {
+                    region.top
+                },
+                if near(region.right, root_region.right) {
+                    canvas_size.width as f32
+                } else {
+                    region.right
+                },
+                if near(region.bottom, root_region.bottom) {
+                    canvas_size.height as f32
+                } else {
+                    region.bottom
+                },
+            );
+            paint.set_color(background.with_a(alpha));
+            root_canvas.draw_rect(extended, &paint);
+        }
+    }
+
+    /// Covers the editor windows without the cursor with the background color, so that the active
+    /// one stands out. Floating windows drawn on top are left as they are.
+    fn draw_inactive_window_dim(&self, root_canvas: &Canvas, settings: &RendererSettings) {
+        if settings.inactive_window_dim <= 0.0 || settings.high_contrast {
+            return;
+        }
+        let active_grid = self.cursor_renderer.parent_window_id();
+        // The cursor is in the command line, or another grid that's not an editor window
+        if !self
+            .window_regions
+            .iter()
+            .any(|details| details.id == active_grid && details.floating_order.is_none())
+        {
+            return;
+        }
+
+        let mut paint = Paint::default();
+        paint.set_color(self.grid_renderer.get_default_background());
+        paint.set_alpha_f(settings.inactive_window_dim.min(1.0));
 
+        for details in &self.window_regions {
+            // Grid 1 is behind all the other windows
+            if details.id == 1 || details.id == active_grid || details.floating_order.is_some() {
+                continue;
+            }
+            root_canvas.save();
+            for floating in self
+                .window_regions
+                .iter()
+                .filter(|details| details.floating_order.is_some())
+            {
+                root_canvas.clip_rect(floating.region, ClipOp::Difference, Some(false));
+            }
+            root_canvas.draw_rect(details.region, &paint);
+            root_canvas.restore();
+        }
+    }
+
+    /// Draws a glow along the row, and optionally the column, of the cursor. It follows the
+    /// animated cursor rather than the grid, and fades out towards the edges of the window.
+    fn draw_crosshair(&self, root_canvas: &Canvas, settings: &RendererSettings) {
+        if settings.crosshair_opacity <= 0.0 {
+            return;
+        }
+        let Some(details) = self
+            .window_regions
+            .iter()
+            .find(|details| details.id == self.cursor_renderer.parent_window_id())
+        else {
+            return;
+        };
+
+        let region = details.region;
+        let center = self.zoom_cursor_point(self.cursor_renderer.get_animated_center());
+        let font_dimensions = self.grid_renderer.font_dimensions;
+        let foreground = self.grid_renderer.default_style.colors.foreground;
+        let color = foreground.unwrap_or(skia_safe::colors::WHITE).to_color();
+        let glow_color = color.with_a((255.0 * settings.crosshair_opacity.min(1.0)) as u8);
+        let colors = [color.with_a(0), glow_color, color.with_a(0)];
+
+        root_canvas.save();
+        root_canvas.clip_rect(region, None, Some(false));
+
+        let mut paint = Paint::default();
+        paint.set_anti_alias(true);
+
+        let half_height = font_dimensions.height as f32 / 2.0;
+        let position = ((center.x - region.left) / region.width()).clamp(0.0, 1.0);
+        paint.set_shader(Shader::linear_gradient(
+            ((region.left, center.y), (region.right, center.y)),
+            colors.as_slice(),
+            [0.0, position, 1.0].as_slice(),
+            TileMode::Clamp,
+            None,
+            None,
+        ));
+        root_canvas.draw_rect(
+            Rect::from_ltrb(
+                region.left,
+                center.y - half_height,
+                region.right,
+                center.y + half_height,
+            ),
+            &paint,
+        );
+
+        if settings.crosshair_column {
+            let half_width = font_dimensions.width as f32 / 2.0;
+            let position = ((center.y - region.top) / region.height()).clamp(0.0, 1.0);
+            paint.set_shader(Shader::linear_gradient(
+                ((center.x, region.top), (center.x, region.bottom)),
+                colors.as_slice(),
+                [0.0, position, 1.0].as_slice(),
+                TileMode::Clamp,
+                None,
+                None,
+            ));
+            root_canvas.draw_rect(
+                Rect::from_ltrb(
+                    center.x - half_width,
+                    region.top,
+                    center.x + half_width,
+                    region.bottom,
+                ),
+                &paint,
+            );
+        }
+
+        root_canvas.restore();
+    }
+
+    /// Returns the hyperlink at the given cell of a grid, if any.
+    pub fn hyperlink_at(&self, grid_id: u64, column: u64, row: u64) -> Option<Hyperlink> {
+        self.rendered_windows
+            .get(&grid_id)
+            .and_then(|window| window.hyperlink_at(column, row))
+    }
+
+    /// Sets the grid cell under the mouse, returns true if the hovered hyperlink changed and a
+    /// new frame should be rendered. The link is looked up again when drawing, so that the
+    /// underline follows changes to the grid.
+    pub fn set_hovered_cell(&mut self, cell: Option<(u64, u64, u64)>) -> bool {
+        let link_at = |cell: Option<(u64, u64, u64)>| {
+            cell.and_then(|(grid_id, column, row)| self.hyperlink_at(grid_id, column, row))
+        };
+        let changed = link_at(self.hovered_cell) != link_at(cell);
+        self.hovered_cell = cell;
+        changed
+    }
+
+    fn draw_hovered_hyperlink(&mut self, root_canvas: &Canvas) {
+        let Some((grid_id, column, row)) = self.hovered_cell else {
+            return;
+        };
+        let Some(link) = self.hyperlink_at(grid_id, column, row) else {
+            return;
+        };
+        let Some(details) = self
+            .window_regions
+            .iter()
+            .find(|details| details.id == grid_id)
+        else {
+            return;
+        };
+
+        root_canvas.save();
+        root_canvas.clip_rect(details.region, None, Some(false));
+        root_canvas.translate((details.region.left, details.region.top));
+        root_canvas.scale((details.zoom, details.zoom));
+        self.grid_renderer.draw_hyperlink_underline(
+            root_canvas,
+            (link.columns.start, link.row),
+            link.columns.end - link.columns.start,
+            &link.style,
+        );
         root_canvas.restore();
     }
 
@@ -241,7 +1005,15 @@ impl Renderer {
             root_windows.into_iter().chain(floating_windows)
         };
 
-        let settings = SETTINGS.get::<RendererSettings>();
+        let mut settings = SETTINGS.get::<RendererSettings>();
+        // Without animations everything moves to its destination in one frame
+        settings.animations_enabled &= !power_saving();
+        if !settings.animations_enabled {
+            settings.position_animation_length = 0.0;
+            settings.scroll_animation_length = 0.0;
+            settings.font_transition_length = 0.0;
+            settings.startup_fade_length = 0.0;
+        }
         // Clippy recommends short-circuiting with any which is not what we want
         #[allow(clippy::unnecessary_fold)]
         let mut animating = windows.fold(false, |acc, window| {
@@ -250,48 +1022,186 @@ impl Renderer {
 
         let windows = &self.rendered_windows;
         let font_dimensions = self.grid_renderer.font_dimensions;
-        self.cursor_renderer
-            .update_cursor_destination(font_dimensions.into(), windows);
+        self.cursor_renderer.update_cursor_destination(
+            font_dimensions.into(),
+            windows,
+            self.local_echo.width(),
+        );
+
+        animating |= self.cursor_renderer.animate(
+            &self.current_mode,
+            &self.grid_renderer,
+            dt,
+            settings.animations_enabled,
+        );
 
-        animating |= self
-            .cursor_renderer
-            .animate(&self.current_mode, &self.grid_renderer, dt);
+        animating |= self.separators.animate(dt);
+        animating |= self.progress_bar.animate(dt);
+        animating |= self.visual_bell.animate(
+            dt,
+            settings.visual_bell_duration,
+            settings.visual_bell_easing,
+            settings.animations_enabled,
+        );
+
+        if let Some(font_transition) = &mut self.font_transition {
+            if font_transition.animate(dt, settings.font_transition_length) {
+                animating = true;
+            } else {
+                self.font_transition = None;
+            }
+        }
+
+        if let Some(startup_fade) = &mut self.startup_fade {
+            if startup_fade.animate(dt, settings.startup_fade_length) {
+                animating = true;
+            } else {
+                self.startup_fade = None;
+            }
+        }
 
         animating
     }
 
+    /// Echoes keys typed in insert mode locally until Neovim redraws the line. Returns true if
+    /// the echo changed and a new frame should be rendered.
+    pub fn handle_typed_text(&mut self, text: &str) -> bool {
+        if !SETTINGS.get::<WindowSettings>().input_instant_echo
+            || self.current_mode != EditorMode::Insert
+        {
+            return false;
+        }
+        let width = self.local_echo.width();
+        self.local_echo.push(
+            text,
+            self.cursor_renderer.get_current_position(),
+            self.cursor_renderer.cell_style(),
+        );
+        self.local_echo.width() != width
+    }
+
+    /// Dims the window while Neovim doesn't respond, `can_kill` tells if the kill chord is shown.
+    pub fn set_neovim_busy(&mut self, busy: bool, can_kill: bool) {
+        self.busy_overlay.set_busy(busy, can_kill);
+    }
+
+    /// Forgets the windows of a Neovim instance that has exited, before a new one is started.
+    pub fn reset_windows(&mut self) {
+        self.frame_pipeline.reset();
+        self.rendered_windows.clear();
+        self.main_grid_size = None;
+        // The handles of the new instance start over
+        self.window_zooms.clear();
+        self.busy_overlay.set_busy(false, false);
+    }
+
+    /// Shows the text composed with an input method at the cursor, returns true if it changed.
+    pub fn set_ime_preedit(&mut self, text: &str, selection: Option<(usize, usize)>) -> bool {
+        self.ime_preedit.set(text, selection)
+    }
+
+    /// The number of grid cells from the cursor to the clause being composed.
+    pub fn ime_selection_offset(&self) -> usize {
+        self.ime_preedit.selection_offset()
+    }
+
     pub fn handle_config_changed(&mut self, config: HotReloadConfigs) {
         match config {
             HotReloadConfigs::Font(font) => match font {
                 Some(font) => {
-                    self.grid_renderer.update_font_options(font.into());
+                    self.grid_renderer.update_font_options((*font).into());
+                    self.update_frame_pipeline();
                 }
                 None => {
                     self.grid_renderer
                         .update_font_options(FontOptions::default());
+                    self.update_frame_pipeline();
                 }
             },
+            HotReloadConfigs::FontSize(size) => {
+                self.guifont_size = size;
+                self.update_guifont();
+            }
+            // Handled by the window
+            HotReloadConfigs::VSync(..) => {}
         }
     }
 
+    /// Loads the fonts of `guifont`, with the size of the active profile when it has one.
+    fn update_guifont(&mut self) {
+        let Some(guifont) = &self.guifont else {
+            return;
+        };
+        let options = match (FontOptions::parse(guifont), self.guifont_size) {
+            (Ok(mut options), Some(size)) => {
+                options.size = size;
+                options
+            }
+            // The errors are reported when the fonts are updated from the setting
+            _ => {
+                self.grid_renderer.update_font(guifont);
+                self.update_frame_pipeline();
+                return;
+            }
+        };
+        self.grid_renderer.update_font_options(options);
+        self.update_frame_pipeline();
+    }
+
+    /// Sends the new fonts and default style to the frame producer, which prepares all the lines
+    /// again with them.
+    fn update_frame_pipeline(&mut self) {
+        self.frame_pipeline.update(&self.grid_renderer);
+    }
+
     pub fn handle_draw_commands(&mut self, batch: Vec<DrawCommand>) -> DrawCommandResult {
-        let settings = SETTINGS.get::<RendererSettings>();
+        record_draw_commands(&batch);
         let mut result = DrawCommandResult {
             font_changed: false,
             should_show: false,
         };
 
+        let mut window_commands = Vec::new();
+        let mut updates = Vec::new();
         for draw_command in batch {
-            self.handle_draw_command(draw_command, &mut result);
-            tracy_named_frame!("neovim draw batch processed");
+            match draw_command {
+                DrawCommand::Window { grid_id, command } => {
+                    // Any update from Neovim to the line or the cursor replaces the local
+                    // prediction
+                    if matches!(command, WindowDrawCommand::DrawLine { .. })
+                        && grid_id == self.cursor_renderer.parent_window_id()
+                    {
+                        updates.push(PendingUpdate::ClearLocalEcho);
+                    }
+                    if grid_id == 1 {
+                        self.track_main_grid_size(&command);
+                    }
+                    window_commands.push((grid_id, command));
+                }
+                DrawCommand::UpdateCursor(new_cursor) => {
+                    updates.push(PendingUpdate::ClearLocalEcho);
+                    updates.push(PendingUpdate::Cursor(new_cursor));
+                }
+                DrawCommand::ModeChanged(new_mode) => {
+                    updates.push(PendingUpdate::ClearLocalEcho);
+                    self.current_mode = new_mode;
+                }
+                draw_command => self.handle_draw_command(draw_command, &mut result),
+            }
         }
-        self.flush(&settings);
+        let sequence = if window_commands.is_empty() {
+            self.frame_pipeline.sent_sequence()
+        } else {
+            self.frame_pipeline.draw_commands(window_commands)
+        };
+        self.pending_updates
+            .extend(updates.into_iter().map(|update| (sequence, update)));
+        self.apply_pending_updates();
 
         let user_scale_factor = SETTINGS.get::<WindowSettings>().scale_factor.into();
         if user_scale_factor != self.user_scale_factor {
             self.user_scale_factor = user_scale_factor;
-            self.grid_renderer
-                .handle_scale_factor_update(self.os_scale_factor * self.user_scale_factor);
+            self.update_scale_factor();
             result.font_changed = true;
         }
 
@@ -300,65 +1210,166 @@ impl Renderer {
 
     pub fn handle_os_scale_factor_change(&mut self, os_scale_factor: f64) {
         self.os_scale_factor = os_scale_factor;
-        self.grid_renderer
-            .handle_scale_factor_update(self.os_scale_factor * self.user_scale_factor);
+        self.update_scale_factor();
     }
 
-    pub fn prepare_lines(&mut self) {
-        self.rendered_windows
-            .iter_mut()
-            .for_each(|(_, w)| w.prepare_lines(&mut self.grid_renderer));
+    /// Sets a temporary factor on top of `neovide_scale_factor`, used by zen mode. Returns true
+    /// if the font changed.
+    pub fn set_zoom(&mut self, zoom: f64) -> bool {
+        self.zoom_preview = 1.0;
+        if zoom == self.zoom {
+            return false;
+        }
+        self.zoom = zoom;
+        self.update_scale_factor();
+        true
     }
 
-    fn handle_draw_command(&mut self, draw_command: DrawCommand, result: &mut DrawCommandResult) {
-        match draw_command {
-            DrawCommand::Window {
-                grid_id,
-                command: WindowDrawCommand::Close,
-            } => {
-                self.rendered_windows.remove(&grid_id);
+    /// Draws the grids scaled to the zoom while it animates, so that the fonts are only reloaded
+    /// with `set_zoom` when it ends.
+    pub fn preview_zoom(&mut self, zoom: f64) {
+        self.zoom_preview = (zoom / self.zoom) as f32;
+    }
+
+    fn update_scale_factor(&mut self) {
+        let scale_factor = self.os_scale_factor * self.user_scale_factor * self.zoom;
+        self.grid_renderer.handle_scale_factor_update(scale_factor);
+        self.update_frame_pipeline();
+    }
+
+    /// Shows the progress as a line at the top of the window, on platforms that can't show it in
+    /// the taskbar or the dock.
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress_bar.set_progress(progress);
+    }
+
+    pub fn ring_visual_bell(&mut self) {
+        self.visual_bell.ring();
+    }
+
+    /// Sets the windows that override `g:neovide_ligatures`, replacing the previous overrides.
+    pub fn set_window_ligatures(&mut self, window_ligatures: HashMap<u64, bool>) {
+        self.frame_pipeline.set_window_ligatures(window_ligatures);
+    }
+
+    /// Sets the indentation of the buffers shown in the windows, replacing the previous ones.
+    pub fn set_window_indents(&mut self, window_indents: HashMap<u64, WindowIndent>) {
+        self.frame_pipeline.set_window_indents(window_indents);
+    }
+
+    /// Sets the columns of the trailing whitespace on the rows of the windows, replacing the
+    /// previous ones.
+    pub fn set_window_trailing_whitespace(
+        &mut self,
+        window_trailing_whitespace: HashMap<u64, HashMap<u64, Range<u64>>>,
+    ) {
+        self.frame_pipeline
+            .set_window_trailing_whitespace(window_trailing_whitespace);
+    }
+
+    /// Magnifies a window by the factor when it's drawn, 1.0 restores it. The layout isn't changed,
+    /// so the window covers the ones next to it.
+    pub fn set_window_zoom(&mut self, window_handle: u64, zoom: f32) {
+        let zoom = (zoom != 1.0).then_some(zoom);
+        for window in self.rendered_windows.values_mut() {
+            if window.window_handle == Some(window_handle) {
+                window.set_zoom(zoom);
             }
-            DrawCommand::Window { grid_id, command } => {
-                match self.rendered_windows.entry(grid_id) {
-                    Entry::Occupied(mut occupied_entry) => {
-                        let rendered_window = occupied_entry.get_mut();
-                        rendered_window.handle_window_draw_command(command);
+        }
+        match zoom {
+            Some(zoom) => self.window_zooms.insert(window_handle, zoom),
+            None => self.window_zooms.remove(&window_handle),
+        };
+    }
+
+    /// Slides the text of the window by the columns its `leftcol` changed.
+    pub fn scroll_window_columns(&mut self, window_handle: u64, cols: i64, text_left: u64) {
+        self.frame_pipeline
+            .scroll_window_columns(window_handle, cols, text_left);
+    }
+
+    pub fn set_window_skipcol(&mut self, window_handle: u64, skipcol: u64) {
+        self.frame_pipeline
+            .set_window_skipcol(window_handle, skipcol);
+    }
+
+    pub fn set_window_highlights(&mut self, window_highlights: WindowHighlights) {
+        self.window_highlights = window_highlights;
+    }
+
+    /// Switches the windows to the latest frame produced from the draw commands, or waits for the
+    /// one that includes all of them when `wait` is set. Returns true when the frame is up to date.
+    pub fn receive_frame(&mut self, wait: bool) -> bool {
+        if let Some(frame) = self.frame_pipeline.receive(wait) {
+            self.rendered_windows
+                .retain(|id, _| frame.windows.contains_key(id));
+            for (id, snapshot) in &frame.windows {
+                let window = match self.rendered_windows.entry(*id) {
+                    Entry::Occupied(occupied_entry) => {
+                        let window = occupied_entry.into_mut();
+                        window.apply_snapshot(snapshot.clone());
+                        window
                     }
                     Entry::Vacant(vacant_entry) => {
-                        if let WindowDrawCommand::Position {
-                            grid_position: (grid_left, grid_top),
-                            grid_size: (width, height),
-                            ..
-                        } = command
-                        {
-                            let new_window = RenderedWindow::new(
-                                grid_id,
-                                (grid_left as f32, grid_top as f32).into(),
-                                (width, height).into(),
-                            );
-                            vacant_entry.insert(new_window);
-                        } else {
-                            error!("WindowDrawCommand sent for uninitialized grid {}", grid_id);
-                        }
+                        vacant_entry.insert(RenderedWindow::new(snapshot.clone()))
                     }
-                }
+                };
+                let zoom = window
+                    .window_handle
+                    .and_then(|handle| self.window_zooms.get(&handle).copied());
+                window.set_zoom(zoom);
             }
-            DrawCommand::UpdateCursor(new_cursor) => {
-                self.cursor_renderer.update_cursor(new_cursor);
+        }
+        self.apply_pending_updates();
+        self.frame_pipeline.is_current()
+    }
+
+    /// Applies the updates of the batches the current frame includes.
+    fn apply_pending_updates(&mut self) {
+        let received = self.frame_pipeline.received_sequence();
+        let ready = self
+            .pending_updates
+            .iter()
+            .take_while(|(sequence, _)| *sequence <= received)
+            .count();
+        for (_, update) in self.pending_updates.drain(..ready) {
+            match update {
+                PendingUpdate::Cursor(new_cursor) => {
+                    self.cursor_renderer.update_cursor(new_cursor);
+                }
+                PendingUpdate::ClearLocalEcho => {
+                    self.local_echo.clear();
+                }
             }
+        }
+    }
+
+    fn track_main_grid_size(&mut self, command: &WindowDrawCommand) {
+        match command {
+            WindowDrawCommand::Position {
+                grid_size: (width, height),
+                ..
+            } => self.main_grid_size = Some((*width, *height).into()),
+            WindowDrawCommand::Close => self.main_grid_size = None,
+            _ => {}
+        }
+    }
+
+    fn handle_draw_command(&mut self, draw_command: DrawCommand, result: &mut DrawCommandResult) {
+        match draw_command {
             DrawCommand::FontChanged(new_font) => {
-                self.grid_renderer.update_font(&new_font);
+                self.guifont = Some(new_font);
+                self.update_guifont();
                 result.font_changed = true;
             }
             DrawCommand::LineSpaceChanged(new_linespace) => {
                 self.grid_renderer.update_linespace(new_linespace);
+                self.update_frame_pipeline();
                 result.font_changed = true;
             }
             DrawCommand::DefaultStyleChanged(new_style) => {
-                self.grid_renderer.default_style = Arc::new(new_style);
-            }
-            DrawCommand::ModeChanged(new_mode) => {
-                self.current_mode = new_mode;
+                self.grid_renderer.set_default_style(new_style);
+                self.update_frame_pipeline();
             }
             DrawCommand::UIReady => {
                 result.should_show = true;
@@ -367,26 +1378,39 @@ impl Renderer {
         }
     }
 
-    pub fn flush(&mut self, renderer_settings: &RendererSettings) {
-        self.rendered_windows
-            .iter_mut()
-            .for_each(|(_, w)| w.flush(renderer_settings));
+    pub fn get_cursor_position(&self) -> Point {
+        self.zoom_cursor_point(self.cursor_renderer.get_current_position())
     }
 
-    pub fn get_cursor_position(&self) -> Point {
-        self.cursor_renderer.get_current_position()
+    /// Where a point in the window of the cursor is drawn, when the window is zoomed.
+    fn zoom_cursor_point(&self, point: Point) -> Point {
+        match self
+            .rendered_windows
+            .get(&self.cursor_renderer.parent_window_id())
+        {
+            Some(window) => window.zoom_point(point, self.grid_renderer.font_dimensions),
+            None => point,
+        }
     }
 
     pub fn get_grid_size(&self) -> Dimensions {
-        if let Some(main_grid) = self.rendered_windows.get(&1) {
-            main_grid.grid_size
-        } else {
-            DEFAULT_GRID_SIZE
-        }
+        self.main_grid_size.unwrap_or(DEFAULT_GRID_SIZE)
     }
 }
 
 /// Defines how floating windows are sorted.
+/// Covers what is under the text of a blended floating window with the backgrounds of the cells
+/// there, before the window is drawn over them.
+fn draw_blend_underlay(canvas: &Canvas, cells: &[(Rect, Color)], alpha: u8) {
+    let mut paint = Paint::default();
+    paint.set_anti_alias(false);
+    paint.set_blend_mode(BlendMode::Src);
+    for (rect, color) in cells {
+        paint.set_color(color.with_a(alpha));
+        canvas.draw_rect(rect, &paint);
+    }
+}
+
 fn floating_sort(window_a: &&mut RenderedWindow, window_b: &&mut RenderedWindow) -> Ordering {
     // First, compare floating order
     let mut ord = window_a
@@ -448,12 +1472,22 @@ pub fn build_window_config<TE>(
     }
 }
 
+/// The most frames that can be queued for the GPU, set with `g:neovide_max_frame_latency`. None
+/// leaves it to the backend.
+pub fn max_frame_latency(latency: u32) -> Option<u32> {
+    (latency > 0).then(|| latency.min(3))
+}
+
 pub trait SkiaRenderer {
     fn window(&self) -> &Window;
     fn flush(&mut self);
     fn swap_buffers(&mut self);
     fn canvas(&mut self) -> &Canvas;
     fn resize(&mut self);
+    /// Limits how many frames can be queued before the rendering waits for the GPU.
+    fn set_max_frame_latency(&mut self, latency: Option<u32>);
+    /// Switches the vsync of the presentation itself, the pacing is done by `VSync`.
+    fn set_vsync(&mut self, enabled: bool);
     fn create_vsync(&self, proxy: EventLoopProxy<UserEvent>) -> VSync;
     #[cfg(feature = "gpu_profiling")]
     fn tracy_create_gpu_context(&self, name: &str) -> Box<dyn GpuCtx>;
@@ -463,13 +1497,20 @@ pub fn create_skia_renderer(
     window: WindowConfig,
     srgb: bool,
     vsync: bool,
+    surface_options: &SurfaceOptions,
 ) -> Box<dyn SkiaRenderer> {
     let renderer: Box<dyn SkiaRenderer> = match &window.config {
-        WindowConfigType::OpenGL(..) => {
-            Box::new(opengl::OpenGLSkiaRenderer::new(window, srgb, vsync))
-        }
+        WindowConfigType::OpenGL(..) => Box::new(opengl::OpenGLSkiaRenderer::new(
+            window,
+            srgb,
+            vsync,
+            surface_options,
+        )),
         #[cfg(target_os = "windows")]
-        WindowConfigType::Direct3D => Box::new(d3d::D3DSkiaRenderer::new(window.window)),
+        WindowConfigType::Direct3D => {
+            set_gpu_backend("Direct3D 12".to_string());
+            Box::new(d3d::D3DSkiaRenderer::new(window.window, surface_options))
+        }
     };
     tracy_create_gpu_context("main_render_context", renderer.as_ref());
     renderer
@@ -1,8 +1,9 @@
 use std::{
+    collections::VecDeque,
     convert::TryInto,
     env,
     env::consts::OS,
-    ffi::{c_void, CStr, CString},
+    ffi::{c_char, c_void, CStr, CString},
     num::NonZeroU32,
 };
 
@@ -23,7 +24,7 @@ use skia_safe::{
         backend_render_targets::make_gl, gl::FramebufferInfo, surfaces::wrap_backend_render_target,
         DirectContext, SurfaceOrigin,
     },
-    ColorType,
+    ColorSpace, ColorType,
 };
 use winit::{
     dpi::PhysicalSize,
@@ -37,9 +38,11 @@ pub use super::vsync::VSyncWinDwm;
 #[cfg(target_os = "macos")]
 pub use super::vsync::VSyncMacos;
 
-use super::{SkiaRenderer, VSync, WindowConfig, WindowConfigType};
+use super::{
+    OutputColorSpace, SkiaRenderer, SurfaceOptions, VSync, WindowConfig, WindowConfigType,
+};
 
-use crate::{profiling::tracy_gpu_zone, window::UserEvent};
+use crate::{crash_report::set_gpu_backend, profiling::tracy_gpu_zone, window::UserEvent};
 
 #[cfg(feature = "gpu_profiling")]
 use crate::profiling::{opengl::create_opengl_gpu_context, GpuCtx};
@@ -49,13 +52,20 @@ pub struct OpenGLSkiaRenderer {
     // If possible keep it the reverse of the initialization order
     skia_surface: skia_safe::Surface,
     fb_info: FramebufferInfo,
+    surface_options: SurfaceOptions,
     pub gr_context: DirectContext,
     context: PossiblyCurrentContext,
     window_surface: Surface<WindowSurface>,
     config: Config,
     window: Window,
+    max_frame_latency: Option<u32>,
+    /// Signaled when the GPU has finished the frames that are still in flight, oldest first.
+    frame_fences: VecDeque<GLsync>,
 }
 
+/// Don't wait forever for a frame if the driver loses it.
+const FENCE_TIMEOUT_NS: u64 = 1_000_000_000;
+
 fn clamp_render_buffer_size(size: &PhysicalSize<u32>) -> PhysicalSize<u32> {
     PhysicalSize::new(
         size.width.clamp(1, MAX_RENDERBUFFER_SIZE),
@@ -63,12 +73,37 @@ fn clamp_render_buffer_size(size: &PhysicalSize<u32>) -> PhysicalSize<u32> {
     )
 }
 
+fn gl_string(name: gl::types::GLenum) -> String {
+    let string = unsafe { gl::GetString(name) };
+    if string.is_null() {
+        return "unknown".to_string();
+    }
+    unsafe { CStr::from_ptr(string as *const c_char) }
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// The OpenGL VSync is always disabled on Wayland and Windows, since they have their own
+/// implementation
+fn swap_interval(vsync: bool) -> SwapInterval {
+    if vsync && env::var("WAYLAND_DISPLAY").is_err() && OS != "windows" && OS != "macos" {
+        SwapInterval::Wait(NonZeroU32::new(1).unwrap())
+    } else {
+        SwapInterval::DontWait
+    }
+}
+
 fn get_proc_address(surface: &Surface<WindowSurface>, addr: &CStr) -> *const c_void {
     GlDisplay::get_proc_address(&surface.display(), addr)
 }
 
 impl OpenGLSkiaRenderer {
-    pub fn new(window: WindowConfig, srgb: bool, vsync: bool) -> Self {
+    pub fn new(
+        window: WindowConfig,
+        srgb: bool,
+        vsync: bool,
+        surface_options: &SurfaceOptions,
+    ) -> Self {
         #[allow(irrefutable_let_patterns)] // This can only be something else than OpenGL on Windows
         let config = if let WindowConfigType::OpenGL(config) = window.config {
             config
@@ -76,13 +111,23 @@ impl OpenGLSkiaRenderer {
             panic!("Not an opengl window");
         };
         let window = window.window;
+        let color_space = surface_options.color_space;
+        let gamma_correct = surface_options.gamma_correct;
+        match color_space {
+            OutputColorSpace::Default => {}
+            #[cfg(target_os = "macos")]
+            OutputColorSpace::Srgb | OutputColorSpace::DisplayP3 => {
+                crate::window::set_window_color_space(&window, color_space)
+            }
+            _ => log::warn!("The {color_space:?} color space is not supported with OpenGL"),
+        }
         let gl_display = config.display();
         let raw_window_handle = window.raw_window_handle();
 
         let size = clamp_render_buffer_size(&window.inner_size());
 
         let surface_attributes = SurfaceAttributesBuilder::<WindowSurface>::new()
-            .with_srgb(Some(srgb))
+            .with_srgb(Some(srgb || gamma_correct))
             .build(
                 raw_window_handle,
                 NonZeroU32::new(size.width).unwrap(),
@@ -101,17 +146,15 @@ impl OpenGLSkiaRenderer {
             .unwrap();
 
         // NOTE: We don't care if these fails, the driver can override the SwapInterval in any case, so it needs to work in all cases
-        // The OpenGL VSync is always disabled on Wayland and Windows, since they have their own
-        // implementation
-        let _ = if vsync && env::var("WAYLAND_DISPLAY").is_err() && OS != "windows" && OS != "macos"
-        {
-            window_surface
-                .set_swap_interval(&context, SwapInterval::Wait(NonZeroU32::new(1).unwrap()))
-        } else {
-            window_surface.set_swap_interval(&context, SwapInterval::DontWait)
-        };
+        let _ = window_surface.set_swap_interval(&context, swap_interval(vsync));
 
         gl::load_with(|s| get_proc_address(&window_surface, CString::new(s).unwrap().as_c_str()));
+        set_gpu_backend(format!(
+            "OpenGL {} ({} {})",
+            gl_string(gl::VERSION),
+            gl_string(gl::VENDOR),
+            gl_string(gl::RENDERER)
+        ));
 
         let interface = skia_safe::gpu::gl::Interface::new_load_with(|name| {
             if name == "eglGetCurrentDisplay" {
@@ -129,7 +172,11 @@ impl OpenGLSkiaRenderer {
 
             FramebufferInfo {
                 fboid: fboid.try_into().expect("Could not create frame buffer id"),
-                format: skia_safe::gpu::gl::Format::RGBA8.into(),
+                format: if gamma_correct {
+                    skia_safe::gpu::gl::Format::SRGB8_ALPHA8.into()
+                } else {
+                    skia_safe::gpu::gl::Format::RGBA8.into()
+                },
                 ..Default::default()
             }
         };
@@ -140,6 +187,7 @@ impl OpenGLSkiaRenderer {
             &window_surface,
             &mut gr_context,
             &fb_info,
+            surface_options,
         );
 
         Self {
@@ -149,7 +197,27 @@ impl OpenGLSkiaRenderer {
             config,
             gr_context,
             fb_info,
+            surface_options: surface_options.clone(),
             skia_surface,
+            max_frame_latency: None,
+            frame_fences: VecDeque::new(),
+        }
+    }
+
+    /// Waits until fewer frames than the maximum latency are in flight.
+    fn wait_for_frame_fences(&mut self) {
+        let Some(latency) = self.max_frame_latency else {
+            return;
+        };
+        tracy_gpu_zone!("wait for frame fences");
+        let fence = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+        self.frame_fences.push_back(fence);
+        while self.frame_fences.len() >= latency as usize {
+            let fence = self.frame_fences.pop_front().unwrap();
+            unsafe {
+                gl::ClientWaitSync(fence, gl::SYNC_FLUSH_COMMANDS_BIT, FENCE_TIMEOUT_NS);
+                gl::DeleteSync(fence);
+            }
         }
     }
 }
@@ -172,6 +240,7 @@ impl SkiaRenderer for OpenGLSkiaRenderer {
             self.window().pre_present_notify();
             let _ = self.window_surface.swap_buffers(&self.context);
         }
+        self.wait_for_frame_fences();
     }
 
     fn canvas(&mut self) -> &Canvas {
@@ -186,9 +255,23 @@ impl SkiaRenderer for OpenGLSkiaRenderer {
             &self.window_surface,
             &mut self.gr_context,
             &self.fb_info,
+            &self.surface_options,
         );
     }
 
+    fn set_max_frame_latency(&mut self, latency: Option<u32>) {
+        self.max_frame_latency = latency;
+        for fence in self.frame_fences.drain(..) {
+            unsafe { gl::DeleteSync(fence) };
+        }
+    }
+
+    fn set_vsync(&mut self, enabled: bool) {
+        let _ = self
+            .window_surface
+            .set_swap_interval(&self.context, swap_interval(enabled));
+    }
+
     #[allow(unused_variables)]
     fn create_vsync(&self, proxy: EventLoopProxy<UserEvent>) -> VSync {
         #[cfg(target_os = "linux")]
@@ -223,9 +306,10 @@ pub fn build_window<TE>(
     winit_window_builder: WindowBuilder,
     event_loop: &EventLoop<TE>,
 ) -> WindowConfig {
+    let transparent = winit_window_builder.window_attributes().transparent;
     let template_builder = ConfigTemplateBuilder::new()
         .with_stencil_size(8)
-        .with_transparency(true);
+        .with_transparency(transparent);
     let (window, config) = DisplayBuilder::new()
         .with_window_builder(Some(winit_window_builder))
         .build(event_loop, template_builder, gen_config)
@@ -242,6 +326,7 @@ fn create_surface(
     window_surface: &Surface<WindowSurface>,
     gr_context: &mut DirectContext,
     fb_info: &FramebufferInfo,
+    surface_options: &SurfaceOptions,
 ) -> skia_safe::Surface {
     let size = clamp_render_buffer_size(size);
     let backend_render_target = make_gl(
@@ -254,13 +339,19 @@ fn create_surface(
     let width = NonZeroU32::new(size.width).unwrap();
     let height = NonZeroU32::new(size.height).unwrap();
     GlSurface::resize(window_surface, context, width, height);
+    // With an sRGB framebuffer the GPU converts the colors to linear before blending them
+    let (color_type, color_space) = if surface_options.gamma_correct {
+        (ColorType::SRGBA8888, Some(ColorSpace::new_srgb()))
+    } else {
+        (ColorType::RGBA8888, None)
+    };
     wrap_backend_render_target(
         gr_context,
         &backend_render_target,
         SurfaceOrigin::BottomLeft,
-        ColorType::RGBA8888,
-        None,
-        None,
+        color_type,
+        color_space,
+        surface_options.surface_props().as_ref(),
     )
     .expect("Could not create skia surface")
 }
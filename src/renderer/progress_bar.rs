@@ -0,0 +1,51 @@
+use skia_safe::{Canvas, Color, Paint, Rect};
+
+use crate::window::Progress;
+
+/// The height of the line in logical pixels.
+const HEIGHT: f32 = 2.0;
+/// The time in seconds it takes the indeterminate segment to cross the window.
+const SWEEP_LENGTH: f32 = 1.5;
+/// The width of the indeterminate segment, relative to the width of the window.
+const SEGMENT_WIDTH: f32 = 0.25;
+
+/// A thin line along the top edge of the window showing the progress set by
+/// `neovide.set_progress`, for platforms without a taskbar or dock progress.
+#[derive(Default)]
+pub struct ProgressBar {
+    progress: Option<Progress>,
+    t: f32,
+}
+
+impl ProgressBar {
+    pub fn set_progress(&mut self, progress: Option<Progress>) {
+        self.progress = progress;
+    }
+
+    /// Moves the indeterminate segment, returns true while it's animating.
+    pub fn animate(&mut self, dt: f32) -> bool {
+        if self.progress != Some(Progress::Indeterminate) {
+            self.t = 0.0;
+            return false;
+        }
+        self.t = (self.t + dt / SWEEP_LENGTH).fract();
+        true
+    }
+
+    pub fn draw(&self, root_canvas: &Canvas, color: Color, scale: f32) {
+        let Some(progress) = self.progress else {
+            return;
+        };
+        let width = root_canvas.base_layer_size().width as f32;
+        let (left, right) = match progress {
+            Progress::Value(value) => (0.0, value * width),
+            Progress::Indeterminate => {
+                // The segment enters from the left edge and leaves at the right edge
+                let left = (self.t * (1.0 + SEGMENT_WIDTH) - SEGMENT_WIDTH) * width;
+                (left.max(0.0), (left + SEGMENT_WIDTH * width).min(width))
+            }
+        };
+        let paint = Paint::default().set_color(color).to_owned();
+        root_canvas.draw_rect(Rect::from_ltrb(left, 0.0, right, HEIGHT * scale), &paint);
+    }
+}
@@ -1,27 +1,36 @@
-use std::{cell::RefCell, rc::Rc, sync::Arc};
+use std::sync::Arc;
 
 use skia_safe::{
-    canvas::SaveLayerRec,
+    canvas::{SaveLayerFlags, SaveLayerRec},
     image_filters::blur,
     scalar,
     utils::shadow_utils::{draw_shadow, ShadowFlags},
-    BlendMode, Canvas, ClipOp, Color, Contains, Matrix, Paint, Path, Picture, PictureRecorder,
-    Point, Point3, Rect,
+    BlendMode, Canvas, ClipOp, Color, Contains, Matrix, Paint, Path, Point, Point3, Rect,
 };
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+use winit::dpi::PhysicalPosition;
 
 use crate::{
     cmd_line::CmdLineSettings,
     dimensions::Dimensions,
-    editor::{AnchorInfo, Style, WindowType},
-    profiling::{tracy_plot, tracy_zone},
-    renderer::{animation_utils::*, GridRenderer, RendererSettings},
+    editor::{AnchorInfo, Colors, Style, WindowType},
+    profiling::tracy_plot,
+    renderer::{
+        animation_utils::*,
+        hyperlink,
+        window_highlights::WindowUnderlay,
+        window_state::{Line, ScrollShift, WindowSnapshot},
+        Hyperlink, RendererSettings, SubpixelOrder,
+    },
     settings::SETTINGS,
-    utils::RingBuffer,
 };
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct LineFragment {
-    pub text: String,
+    /// Short fragments are stored inline, and longer ones are shared when the line is cloned.
+    pub text: SmolStr,
     pub window_left: u64,
     pub width: u64,
     pub style: Option<Arc<Style>>,
@@ -59,6 +68,7 @@ pub enum WindowDrawCommand {
     Hide,
     Close,
     Viewport {
+        top_line: f64,
         scroll_delta: f64,
     },
     ViewportMargins {
@@ -67,16 +77,8 @@ pub enum WindowDrawCommand {
         left: u64,
         right: u64,
     },
-}
-
-#[derive(Clone)]
-struct Line {
-    line_fragments: Vec<LineFragment>,
-    background_picture: Option<Picture>,
-    foreground_picture: Option<Picture>,
-    has_transparency: bool,
-    is_inferred_border: bool,
-    is_valid: bool,
+    /// The handle of the Neovim window displayed in the grid.
+    WindowHandle(u64),
 }
 
 pub struct RenderedWindow {
@@ -88,11 +90,13 @@ pub struct RenderedWindow {
     window_type: WindowType,
 
     pub grid_size: Dimensions,
-
-    scrollback_lines: RingBuffer<Option<Rc<RefCell<Line>>>>,
-    actual_lines: RingBuffer<Option<Rc<RefCell<Line>>>>,
-    scroll_delta: isize,
     pub viewport_margins: ViewportMargins,
+    pub window_handle: Option<u64>,
+    // The latest state of the window produced from the draw commands
+    snapshot: Arc<WindowSnapshot>,
+    shown: u64,
+    scroll_shift: ScrollShift,
+    horizontal_scroll_shift: ScrollShift,
 
     grid_start_position: Point,
     pub grid_current_position: Point,
@@ -100,8 +104,11 @@ pub struct RenderedWindow {
     position_t: f32,
 
     pub scroll_animation: CriticallyDampedSpringAnimation,
+    pub horizontal_scroll_animation: CriticallyDampedSpringAnimation,
 
     has_transparency: bool,
+    // Magnifies the window around its top left corner, without changing the layout
+    zoom: f32,
 }
 
 #[derive(Clone, Debug)]
@@ -109,6 +116,8 @@ pub struct WindowDrawDetails {
     pub id: u64,
     pub region: Rect,
     pub floating_order: Option<u64>,
+    /// The magnification the window is drawn with, the region is the zoomed one.
+    pub zoom: f32,
 }
 
 impl WindowDrawDetails {
@@ -119,27 +128,33 @@ impl WindowDrawDetails {
             self.id
         }
     }
+
+    /// Maps a position on the zoomed window back to where it is on the grid.
+    pub fn unzoom(&self, position: PhysicalPosition<f32>) -> PhysicalPosition<f32> {
+        PhysicalPosition::new(
+            self.region.left + (position.x - self.region.left) / self.zoom,
+            self.region.top + (position.y - self.region.top) / self.zoom,
+        )
+    }
 }
 
 impl RenderedWindow {
-    pub fn new(id: u64, grid_position: Point, grid_size: Dimensions) -> RenderedWindow {
-        RenderedWindow {
+    pub fn new(snapshot: Arc<WindowSnapshot>) -> RenderedWindow {
+        let grid_position = snapshot.grid_destination;
+        let mut window = RenderedWindow {
             vertical_position: 0.0,
-            id,
-            hidden: false,
+            id: snapshot.id,
+            hidden: snapshot.hidden,
             anchor_info: None,
             window_type: WindowType::Editor,
 
-            grid_size,
-
-            actual_lines: RingBuffer::new(grid_size.height as usize, None),
-            scrollback_lines: RingBuffer::new(2 * grid_size.height as usize, None),
-            scroll_delta: 0,
-            viewport_margins: ViewportMargins {
-                top: 0,
-                bottom: 0,
-                inferred: true,
-            },
+            grid_size: snapshot.grid_size,
+            viewport_margins: snapshot.viewport_margins.clone(),
+            window_handle: None,
+            shown: snapshot.shown,
+            scroll_shift: snapshot.scroll_shift,
+            horizontal_scroll_shift: snapshot.horizontal_scroll_shift,
+            snapshot: snapshot.clone(),
 
             grid_start_position: grid_position,
             grid_current_position: grid_position,
@@ -147,9 +162,74 @@ impl RenderedWindow {
             position_t: 2.0, // 2.0 is out of the 0.0 to 1.0 range and stops animation.
 
             scroll_animation: CriticallyDampedSpringAnimation::new(),
+            horizontal_scroll_animation: CriticallyDampedSpringAnimation::new(),
 
             has_transparency: false,
+            zoom: 1.0,
+        };
+        window.apply_snapshot(snapshot);
+        window
+    }
+
+    /// Switches to a newer state of the window. The snapshots in between might have been skipped,
+    /// so the animations are moved by everything that changed since the previous one.
+    pub fn apply_snapshot(&mut self, snapshot: Arc<WindowSnapshot>) {
+        if self.grid_destination != snapshot.grid_destination {
+            if self.grid_start_position.x.abs() > f32::EPSILON
+                || self.grid_start_position.y.abs() > f32::EPSILON
+            {
+                self.position_t = 0.0; // Reset animation as we have a new destination.
+                self.grid_start_position = self.grid_current_position;
+            } else {
+                // We don't want to animate since the window is animating out of the start location,
+                // so we set t to 2.0 to stop animations.
+                self.position_t = 2.0;
+                self.grid_start_position = snapshot.grid_destination;
+            }
+            self.grid_destination = snapshot.grid_destination;
+        }
+        if self.shown != snapshot.shown {
+            self.shown = snapshot.shown;
+            self.position_t = 2.0; // We don't want to animate since the window is becoming visible,
+                                   // so we set t to 2.0 to stop animations.
+            self.grid_start_position = self.grid_destination;
         }
+
+        apply_scroll_shift(
+            &mut self.scroll_animation,
+            &mut self.scroll_shift,
+            snapshot.scroll_shift,
+        );
+        // The animation can't drift further than the scrollback
+        let max_delta = snapshot
+            .scrollback_lines
+            .len()
+            .saturating_sub(snapshot.grid_size.height as usize) as f32;
+        self.scroll_animation.position =
+            self.scroll_animation.position.clamp(-max_delta, max_delta);
+
+        apply_scroll_shift(
+            &mut self.horizontal_scroll_animation,
+            &mut self.horizontal_scroll_shift,
+            snapshot.horizontal_scroll_shift,
+        );
+        // Nor further than the width of the text
+        let max_offset = snapshot
+            .grid_size
+            .width
+            .saturating_sub(snapshot.scroll_text_left) as f32;
+        self.horizontal_scroll_animation.position = self
+            .horizontal_scroll_animation
+            .position
+            .clamp(-max_offset, max_offset);
+
+        self.hidden = snapshot.hidden;
+        self.anchor_info = snapshot.anchor_info.clone();
+        self.window_type = snapshot.window_type;
+        self.grid_size = snapshot.grid_size;
+        self.viewport_margins = snapshot.viewport_margins.clone();
+        self.window_handle = snapshot.window_handle;
+        self.snapshot = snapshot;
     }
 
     pub fn pixel_region(&self, font_dimensions: Dimensions) -> Rect {
@@ -241,6 +321,10 @@ impl RenderedWindow {
             tracy_plot!("Scroll position {}", self.scroll_animation.position.into());
         }
 
+        animating |= self
+            .horizontal_scroll_animation
+            .update(dt, settings.scroll_animation_length);
+
         animating
     }
 
@@ -249,7 +333,9 @@ impl RenderedWindow {
         canvas: &Canvas,
         pixel_region: &Rect,
         font_dimensions: Dimensions,
-        default_background: Color,
+        background: Color,
+        layer_flags: SaveLayerFlags,
+        underlay: Option<&WindowUnderlay>,
     ) {
         let scroll_offset_lines = self.scroll_animation.position.floor();
         let scroll_offset = scroll_offset_lines - self.scroll_animation.position;
@@ -258,10 +344,10 @@ impl RenderedWindow {
         let line_height = font_dimensions.height as f32;
         let mut has_transparency = false;
 
-        let lines: Vec<(Matrix, &Rc<RefCell<Line>>)> = if !self.scrollback_lines.is_empty() {
+        let lines: Vec<(Matrix, &Arc<Line>)> = if !self.snapshot.scrollback_lines.is_empty() {
             (0..self.grid_size.height as isize + 1)
                 .filter_map(|i| {
-                    self.scrollback_lines[scroll_offset_lines + i]
+                    self.snapshot.scrollback_lines[scroll_offset_lines + i]
                         .as_ref()
                         .map(|line| (i, line))
                 })
@@ -283,7 +369,7 @@ impl RenderedWindow {
         };
 
         let top_border_indices = 0..self.viewport_margins.top as isize;
-        let actual_line_count = self.actual_lines.len() as isize;
+        let actual_line_count = self.snapshot.actual_lines.len() as isize;
         let bottom_border_indices =
             actual_line_count - self.viewport_margins.bottom as isize..actual_line_count;
         let margins_inferred = self.viewport_margins.inferred;
@@ -291,8 +377,8 @@ impl RenderedWindow {
         let border_lines: Vec<_> = top_border_indices
             .chain(bottom_border_indices)
             .filter_map(|i| {
-                self.actual_lines[i].as_ref().and_then(|line| {
-                    if !margins_inferred || line.borrow().is_inferred_border {
+                self.snapshot.actual_lines[i].as_ref().and_then(|line| {
+                    if !margins_inferred || line.is_inferred_border {
                         Some((i, line))
                     } else {
                         None
@@ -317,86 +403,196 @@ impl RenderedWindow {
                 - (self.viewport_margins.top + self.viewport_margins.bottom) as f32 * line_height,
         );
 
+        // While scrolling horizontally, the text is drawn shifted, but the number and sign columns
+        // before it stay in place
+        let horizontal_offset =
+            (-self.horizontal_scroll_animation.position * font_dimensions.width as f32).round();
+        let inner_passes = if horizontal_offset == 0.0 {
+            vec![(inner_region, 0.0)]
+        } else {
+            let text_left = (inner_region.left
+                + self.snapshot.scroll_text_left as f32 * font_dimensions.width as f32)
+                .min(inner_region.right);
+            vec![
+                (
+                    Rect::from_ltrb(
+                        inner_region.left,
+                        inner_region.top,
+                        text_left,
+                        inner_region.bottom,
+                    ),
+                    0.0,
+                ),
+                (
+                    Rect::from_ltrb(
+                        text_left,
+                        inner_region.top,
+                        inner_region.right,
+                        inner_region.bottom,
+                    ),
+                    horizontal_offset,
+                ),
+            ]
+        };
+
         let mut background_paint = Paint::default();
         background_paint.set_blend_mode(BlendMode::Src);
-        background_paint.set_alpha(default_background.a());
+        background_paint.set_alpha(background.a());
 
         let save_layer_rec = SaveLayerRec::default()
             .bounds(pixel_region)
-            .paint(&background_paint);
+            .paint(&background_paint)
+            .flags(layer_flags);
         canvas.save_layer(&save_layer_rec);
-        canvas.clear(default_background.with_a(255));
+        canvas.clear(background.with_a(255));
+        // The cells with a background of their own, like the visual selection, cover the underlay
+        if let Some(underlay) = underlay {
+            canvas.save();
+            canvas.clip_rect(inner_region, None, false);
+            underlay.draw(canvas, pixel_region, font_dimensions);
+            canvas.restore();
+        }
         for (matrix, line) in &border_lines {
-            let line = line.borrow();
             if let Some(background_picture) = &line.background_picture {
                 has_transparency |= line.has_transparency;
                 canvas.draw_picture(background_picture, Some(matrix), None);
             }
         }
-        canvas.save();
-        canvas.clip_rect(inner_region, None, false);
-        for (matrix, line) in &lines {
-            let line = line.borrow();
-            if let Some(background_picture) = &line.background_picture {
-                has_transparency |= line.has_transparency;
-                canvas.draw_picture(background_picture, Some(matrix), None);
+        for (clip, offset) in &inner_passes {
+            canvas.save();
+            canvas.clip_rect(clip, None, false);
+            for (matrix, line) in &lines {
+                if let Some(background_picture) = &line.background_picture {
+                    has_transparency |= line.has_transparency;
+                    let mut matrix = *matrix;
+                    matrix.post_translate((*offset, 0.0));
+                    canvas.draw_picture(background_picture, Some(&matrix), None);
+                }
             }
+            canvas.restore();
         }
         canvas.restore();
-        canvas.restore();
 
         for (matrix, line) in &border_lines {
-            let line = line.borrow();
             if let Some(foreground_picture) = &line.foreground_picture {
                 canvas.draw_picture(foreground_picture, Some(matrix), None);
             }
         }
-        canvas.save();
-        canvas.clip_rect(inner_region, None, false);
-        for (matrix, line) in &lines {
-            let line = line.borrow();
-            if let Some(foreground_picture) = &line.foreground_picture {
-                canvas.draw_picture(foreground_picture, Some(matrix), None);
+        for (clip, offset) in &inner_passes {
+            canvas.save();
+            canvas.clip_rect(clip, None, false);
+            for (matrix, line) in &lines {
+                if let Some(foreground_picture) = &line.foreground_picture {
+                    let mut matrix = *matrix;
+                    matrix.post_translate((*offset, 0.0));
+                    canvas.draw_picture(foreground_picture, Some(&matrix), None);
+                }
             }
+            canvas.restore();
         }
-        canvas.restore();
         self.has_transparency = has_transparency;
     }
 
+    pub fn hyperlink_at(&self, column: u64, row: u64) -> Option<Hyperlink> {
+        if row >= self.snapshot.actual_lines.len() as u64 {
+            return None;
+        }
+        let line = self.snapshot.actual_lines[row as isize].as_ref()?;
+        hyperlink::hyperlink_at(&line.line_fragments, row, column)
+    }
+
+    /// The background color of the cell, `None` outside of the grid or when the line isn't drawn.
+    pub fn cell_background(&self, column: u64, row: u64, default_colors: &Colors) -> Option<Color> {
+        if row >= self.snapshot.actual_lines.len() as u64 {
+            return None;
+        }
+        let line = self.snapshot.actual_lines[row as isize].as_ref()?;
+        let style = line
+            .line_fragments
+            .iter()
+            .find(|fragment| {
+                column >= fragment.window_left && column < fragment.window_left + fragment.width
+            })
+            .map_or(self.snapshot.background_style.as_ref(), |fragment| {
+                fragment.style.as_ref()
+            });
+        match style {
+            Some(style) => Some(style.background(default_colors).to_color()),
+            None => default_colors.background.map(|color| color.to_color()),
+        }
+    }
+
+    /// The runs of cells with text, blended with `winblend` or `pumblend`, as the column, row and
+    /// width. Neovim hides what is under them, while what is under the blank cells shows through.
+    pub fn blended_text_cells(&self) -> Vec<(u64, u64, u64)> {
+        let mut runs: Vec<(u64, u64, u64)> = Vec::new();
+        for row in 0..self.snapshot.actual_lines.len() {
+            let Some(line) = &self.snapshot.actual_lines[row as isize] else {
+                continue;
+            };
+            let blended = line
+                .line_fragments
+                .iter()
+                .filter(|fragment| fragment.style.as_ref().is_some_and(|style| style.blend > 0));
+            for fragment in blended {
+                let mut column = fragment.window_left;
+                for grapheme in fragment.text.graphemes(true) {
+                    let width = (grapheme.width() as u64).max(1);
+                    if grapheme != " " {
+                        match runs.last_mut() {
+                            Some((start, run_row, run_width))
+                                if *run_row == row as u64 && *start + *run_width == column =>
+                            {
+                                *run_width += width;
+                            }
+                            _ => runs.push((column, row as u64, width)),
+                        }
+                    }
+                    column += width;
+                }
+            }
+        }
+        runs
+    }
+
     fn has_transparency(&self) -> bool {
         let scroll_offset_lines = self.scroll_animation.position.floor() as isize;
-        if self.scrollback_lines.is_empty() {
+        if self.snapshot.scrollback_lines.is_empty() {
             return false;
         }
-        self.scrollback_lines
+        self.snapshot
+            .scrollback_lines
             .iter_range(
                 scroll_offset_lines..scroll_offset_lines + self.grid_size.height as isize + 1,
             )
             .flatten()
-            .any(|line| line.borrow().has_transparency)
+            .any(|line| line.has_transparency)
     }
 
     pub fn draw(
         &mut self,
         root_canvas: &Canvas,
         settings: &RendererSettings,
-        default_background: Color,
+        background: Color,
         font_dimensions: Dimensions,
         previous_floating_rects: &mut Vec<Rect>,
+        underlay: Option<&WindowUnderlay>,
     ) -> WindowDrawDetails {
-        let has_transparency = default_background.a() != 255 || self.has_transparency();
+        let has_transparency = background.a() != 255 || self.has_transparency();
 
         let pixel_region = self.pixel_region(font_dimensions);
+        let zoomed_region = self.zoomed_region(pixel_region);
         let transparent_floating = self.anchor_info.is_some() && has_transparency;
 
         if self.anchor_info.is_some()
             && settings.floating_shadow
+            && !settings.high_contrast
             && !previous_floating_rects
                 .iter()
-                .any(|rect| rect.contains(pixel_region))
+                .any(|rect| rect.contains(zoomed_region))
         {
             root_canvas.save();
-            let shadow_path = Path::rect(pixel_region, None);
+            let shadow_path = Path::rect(zoomed_region, None);
             // We clip using the Difference op to make sure that the shadow isn't rendered inside
             // the window itself.
             root_canvas.clip_path(&shadow_path, Some(ClipOp::Difference), None);
@@ -422,11 +618,12 @@ impl RenderedWindow {
                 Some(ShadowFlags::DIRECTIONAL_LIGHT),
             );
             root_canvas.restore();
-            previous_floating_rects.push(pixel_region);
+            previous_floating_rects.push(zoomed_region);
         }
 
         root_canvas.save();
-        root_canvas.clip_rect(pixel_region, None, Some(false));
+        root_canvas.clip_rect(zoomed_region, None, Some(false));
+        self.apply_zoom(root_canvas, font_dimensions);
         let need_blur = transparent_floating && settings.floating_blur;
 
         if need_blur {
@@ -454,7 +651,7 @@ impl RenderedWindow {
 
         let paint = Paint::default()
             .set_anti_alias(false)
-            .set_color(Color::from_argb(255, 255, 255, default_background.a()))
+            .set_color(Color::from_argb(255, 255, 255, background.a()))
             .set_blend_mode(if self.anchor_info.is_some() {
                 BlendMode::SrcOver
             } else {
@@ -462,13 +659,27 @@ impl RenderedWindow {
             })
             .to_owned();
 
-        let save_layer_rec = SaveLayerRec::default().bounds(&pixel_region).paint(&paint);
+        // LCD text can only be kept in an opaque layer, since it's blended per color channel.
+        // Floating windows with winblend, or transparent lines, fall back to grayscale text, which
+        // is decided every frame, since the blending changes at any time.
+        let layer_flags =
+            if settings.subpixel_antialiasing != SubpixelOrder::None && !has_transparency {
+                SaveLayerFlags::PRESERVE_LCD_TEXT
+            } else {
+                SaveLayerFlags::empty()
+            };
+        let save_layer_rec = SaveLayerRec::default()
+            .bounds(&pixel_region)
+            .paint(&paint)
+            .flags(layer_flags);
         root_canvas.save_layer(&save_layer_rec);
         self.draw_surface(
             root_canvas,
             &pixel_region,
             font_dimensions,
-            default_background,
+            background,
+            layer_flags,
+            underlay,
         );
         root_canvas.restore();
 
@@ -476,341 +687,126 @@ impl RenderedWindow {
 
         WindowDrawDetails {
             id: self.id,
-            region: pixel_region,
+            region: zoomed_region,
             floating_order: self.anchor_info.as_ref().map(|v| v.sort_order),
+            zoom: self.zoom,
         }
     }
 
-    pub fn handle_window_draw_command(&mut self, draw_command: WindowDrawCommand) {
-        match draw_command {
-            WindowDrawCommand::Position {
-                grid_position: (grid_left, grid_top),
-                grid_size,
-                anchor_info,
-                window_type,
-            } => {
-                tracy_zone!("position_cmd", 0);
-
-                let grid_left = grid_left.max(0.0) as f32;
-                let grid_top = grid_top.max(0.0) as f32;
-                let new_destination: Point = (grid_left, grid_top).into();
-                let new_grid_size: Dimensions = grid_size.into();
-
-                if self.grid_destination != new_destination {
-                    if self.grid_start_position.x.abs() > f32::EPSILON
-                        || self.grid_start_position.y.abs() > f32::EPSILON
-                    {
-                        self.position_t = 0.0; // Reset animation as we have a new destination.
-                        self.grid_start_position = self.grid_current_position;
-                    } else {
-                        // We don't want to animate since the window is animating out of the start location,
-                        // so we set t to 2.0 to stop animations.
-                        self.position_t = 2.0;
-                        self.grid_start_position = new_destination;
-                    }
-                    self.grid_destination = new_destination;
-                }
-
-                let height = new_grid_size.height as usize;
-                self.actual_lines.resize(height, None);
-                self.grid_size = new_grid_size;
-
-                self.scrollback_lines.resize(2 * height, None);
-                self.scrollback_lines.clone_from_iter(&self.actual_lines);
-                self.scroll_delta = 0;
-
-                if height != self.actual_lines.len() {
-                    self.scroll_animation.reset();
-                }
-
-                self.anchor_info = anchor_info;
-                self.window_type = window_type;
-
-                if self.hidden {
-                    self.hidden = false;
-                    self.position_t = 2.0; // We don't want to animate since the window is becoming visible,
-                                           // so we set t to 2.0 to stop animations.
-                    self.grid_start_position = new_destination;
-                    self.grid_destination = new_destination;
-                }
-            }
-            WindowDrawCommand::DrawLine {
-                row,
-                line_fragments,
-            } => {
-                tracy_zone!("draw_line_cmd", 0);
-
-                let mut line = Line {
-                    line_fragments,
-                    background_picture: None,
-                    foreground_picture: None,
-                    has_transparency: false,
-                    is_inferred_border: false,
-                    is_valid: false,
-                };
-
-                if self.viewport_margins.inferred {
-                    let check_border = |fragment: &LineFragment, check: &dyn Fn(&str) -> bool| {
-                        fragment.style.as_ref().map_or(false, |style| {
-                            style.infos.last().map_or(false, |info| {
-                                // The specification seems to indicate that kind should be UI and
-                                // then we only need to test ui_name. But at least for FloatTitle,
-                                // that is not the case, the kind is set to syntax and hi_name is
-                                // set.
-                                check(&info.ui_name) || check(&info.hi_name)
-                            })
-                        })
-                    };
-
-                    let float_border =
-                        |s: &str| matches!(s, "FloatBorder" | "FloatTitle" | "FloatFooter");
-                    let winbar = |s: &str| matches!(s, "WinBar" | "WinBarNC");
-
-                    // Lines with purly border highlight groups are considered borders.
-                    line.is_inferred_border = line
-                        .line_fragments
-                        .iter()
-                        .map(|fragment| check_border(fragment, &float_border))
-                        .all(|v| v);
-
-                    // And also lines with a winbar highlight anywhere
-                    line.is_inferred_border |= line
-                        .line_fragments
-                        .iter()
-                        .map(|fragment| check_border(fragment, &winbar))
-                        .any(|v| v)
-                }
+    /// The background color of the window, `None` when it uses the default background.
+    pub fn background_color(&self, default_colors: &Colors) -> Option<Color> {
+        self.snapshot
+            .background_style
+            .as_ref()
+            .map(|style| style.background(default_colors).to_color())
+    }
 
-                self.actual_lines[row] = Some(Rc::new(RefCell::new(line)));
-            }
-            WindowDrawCommand::Scroll {
-                top,
-                bottom,
-                left,
-                right,
-                rows,
-                cols,
-            } => {
-                tracy_zone!("scroll_cmd", 0);
-                if top == 0
-                    && bottom == self.grid_size.height
-                    && left == 0
-                    && right == self.grid_size.width
-                    && cols == 0
-                {
-                    self.actual_lines.rotate(rows as isize);
-                }
-            }
-            WindowDrawCommand::Clear => {
-                tracy_zone!("clear_cmd", 0);
-                self.scroll_delta = 0;
-                self.scrollback_lines
-                    .iter_mut()
-                    .for_each(|line| *line = None);
-                self.scroll_animation.reset();
-            }
-            WindowDrawCommand::Show => {
-                tracy_zone!("show_cmd", 0);
-                if self.hidden {
-                    self.hidden = false;
-                    self.position_t = 2.0; // We don't want to animate since the window is becoming visible,
-                                           // so we set t to 2.0 to stop animations.
-                    self.grid_start_position = self.grid_destination;
-                    self.scroll_animation.reset();
-                }
-            }
-            WindowDrawCommand::Hide => {
-                tracy_zone!("hide_cmd", 0);
-                self.hidden = true;
-            }
-            WindowDrawCommand::Viewport { scroll_delta } => {
-                log::trace!("Handling Viewport {}", self.id);
-                self.scroll_delta = scroll_delta.round() as isize;
-            }
-            WindowDrawCommand::ViewportMargins { top, bottom, .. } => {
-                self.viewport_margins = ViewportMargins {
-                    top,
-                    bottom,
-                    inferred: false,
-                }
-            }
-            _ => {}
-        };
+    /// Sets the factor the window is magnified by when drawn, 1.0 draws it normally.
+    pub fn set_zoom(&mut self, zoom: Option<f32>) {
+        self.zoom = zoom.unwrap_or(1.0);
     }
 
-    fn infer_viewport_margins(&mut self) {
-        if self.viewport_margins.inferred {
-            self.viewport_margins.top = self
-                .actual_lines
-                .iter()
-                .take_while(|line| {
-                    if let Some(line) = line {
-                        line.borrow().is_inferred_border
-                    } else {
-                        false
-                    }
-                })
-                .count() as u64;
-            self.viewport_margins.bottom = (self.viewport_margins.top as usize
-                ..self.actual_lines.len())
-                .rev()
-                .map(|i| self.actual_lines[i].as_ref())
-                .take_while(|line| {
-                    if let Some(line) = line {
-                        line.borrow().is_inferred_border
-                    } else {
-                        false
-                    }
-                })
-                .count() as u64;
-        }
+    pub fn is_zoomed(&self) -> bool {
+        self.zoom != 1.0
     }
 
-    pub fn flush(&mut self, renderer_settings: &RendererSettings) {
-        self.infer_viewport_margins();
-
-        // If the borders are changed, reset the scrollback to only fit the inner view
-        let inner_range = self.viewport_margins.top as isize
-            ..(self.actual_lines.len() - self.viewport_margins.bottom as usize) as isize;
-        let inner_size = inner_range.len();
-        let inner_view = self.actual_lines.iter_range(inner_range);
-        if inner_size != self.scrollback_lines.len() / 2 {
-            self.scrollback_lines.resize(2 * inner_size, None);
-            self.scrollback_lines.clone_from_iter(inner_view);
-            self.scroll_delta = 0;
-            self.scroll_animation.reset();
-            return;
+    /// Scales the canvas around the top left corner of the window when it's zoomed, so that
+    /// everything drawn afterwards in the unzoomed coordinates, like the cursor, is magnified too.
+    pub fn apply_zoom(&self, canvas: &Canvas, font_dimensions: Dimensions) {
+        if self.is_zoomed() {
+            let pixel_region = self.pixel_region(font_dimensions);
+            canvas.translate((pixel_region.left, pixel_region.top));
+            canvas.scale((self.zoom, self.zoom));
+            canvas.translate((-pixel_region.left, -pixel_region.top));
         }
+    }
 
-        let scroll_delta = self.scroll_delta;
-        self.scrollback_lines.rotate(scroll_delta);
-
-        self.scrollback_lines.clone_from_iter(inner_view);
-
-        if scroll_delta != 0 {
-            let mut scroll_offset = self.scroll_animation.position;
+    /// Where a point on the unzoomed window is drawn with the zoom.
+    pub fn zoom_point(&self, point: Point, font_dimensions: Dimensions) -> Point {
+        let pixel_region = self.pixel_region(font_dimensions);
+        let origin = Point::new(pixel_region.left, pixel_region.top);
+        origin + (point - origin) * self.zoom
+    }
 
-            let max_delta = self.scrollback_lines.len() - self.grid_size.height as usize;
-            log::trace!(
-                "Scroll offset {scroll_offset}, delta {scroll_delta}, max_delta {max_delta}"
-            );
-            // Do a limited scroll with empty lines when scrolling far
-            if scroll_delta.unsigned_abs() > max_delta {
-                let far_lines = renderer_settings
-                    .scroll_animation_far_lines
-                    .min(self.actual_lines.len() as u32) as isize;
-
-                scroll_offset = -(far_lines * scroll_delta.signum()) as f32;
-                let empty_lines = if scroll_delta > 0 {
-                    -far_lines..0
-                } else {
-                    self.actual_lines.len() as isize..self.actual_lines.len() as isize + far_lines
-                };
-                for i in empty_lines {
-                    self.scrollback_lines[i] = None;
-                }
-            // And even when scrolling in steps, we can't let it drift too far, since the
-            // buffer size is limited
-            } else {
-                scroll_offset -= scroll_delta as f32;
-                scroll_offset = scroll_offset.clamp(-(max_delta as f32), max_delta as f32);
-            }
-            self.scroll_animation.position = scroll_offset;
-            log::trace!("Current scroll {scroll_offset}");
-        }
-        self.scroll_delta = 0;
+    /// The region the window covers when it's drawn with the zoom, which grows from the top left
+    /// corner and can cover the windows next to it.
+    fn zoomed_region(&self, pixel_region: Rect) -> Rect {
+        Rect::from_xywh(
+            pixel_region.left,
+            pixel_region.top,
+            pixel_region.width() * self.zoom,
+            pixel_region.height() * self.zoom,
+        )
     }
+}
 
-    pub fn prepare_lines(&mut self, grid_renderer: &mut GridRenderer) {
-        let scroll_offset_lines = self.scroll_animation.position.floor() as isize;
-        let height = self.grid_size.height as isize;
-        if height == 0 {
-            return;
+fn apply_scroll_shift(
+    animation: &mut CriticallyDampedSpringAnimation,
+    seen: &mut ScrollShift,
+    shift: ScrollShift,
+) {
+    if shift.sets != seen.sets {
+        if shift.stopped {
+            animation.reset();
         }
-        let font_dimensions = grid_renderer.font_dimensions;
-
-        let mut prepare_line = |line: &Rc<RefCell<Line>>| {
-            let mut line = line.borrow_mut();
-            if line.is_valid {
-                return;
-            }
-
-            let mut recorder = PictureRecorder::new();
-
-            let grid_rect = Rect::from_wh(
-                (self.grid_size.width * font_dimensions.width) as f32,
-                font_dimensions.height as f32,
-            );
-            let canvas = recorder.begin_recording(grid_rect, None);
-
-            let mut has_transparency = false;
-            let mut custom_background = false;
-
-            for line_fragment in line.line_fragments.iter() {
-                let LineFragment {
-                    window_left,
-                    width,
-                    style,
-                    ..
-                } = line_fragment;
-                let grid_position = (*window_left, 0);
-                let background_info =
-                    grid_renderer.draw_background(canvas, grid_position, *width, style);
-                custom_background |= background_info.custom_color;
-                has_transparency |= background_info.transparent;
-            }
-            let background_picture =
-                custom_background.then_some(recorder.finish_recording_as_picture(None).unwrap());
-
-            let canvas = recorder.begin_recording(grid_rect, None);
-            let mut foreground_drawn = false;
-            for line_fragment in &line.line_fragments {
-                let LineFragment {
-                    text,
-                    window_left,
-                    width,
-                    style,
-                } = line_fragment;
-                let grid_position = (*window_left, 0);
-
-                foreground_drawn |=
-                    grid_renderer.draw_foreground(canvas, text, grid_position, *width, style);
-            }
-            let foreground_picture =
-                foreground_drawn.then_some(recorder.finish_recording_as_picture(None).unwrap());
-
-            line.background_picture = background_picture;
-            line.foreground_picture = foreground_picture;
-            line.has_transparency = has_transparency;
-            line.is_valid = true;
-        };
+        animation.position = shift.position + shift.moved as f32;
+    } else {
+        animation.position += (shift.moved - seen.moved) as f32;
+    }
+    *seen = shift;
+}
 
-        if !self.scrollback_lines.is_empty() {
-            for line in self
-                .scrollback_lines
-                .iter_range_mut(scroll_offset_lines..scroll_offset_lines + height + 1)
-                .flatten()
-            {
-                prepare_line(line)
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::window_state::{tests::line, WindowState};
+
+    #[test]
+    fn test_blended_text_cells_skip_the_blank_ones() {
+        let mut style = Style::new(Colors {
+            foreground: None,
+            background: None,
+            special: None,
+        });
+        style.blend = 30;
+        let style = Some(Arc::new(style));
+        let mut snapshot = WindowState::new(2, Point::default(), (10, 1).into()).snapshot();
+        let mut text = line("").unwrap();
+        Arc::get_mut(&mut text).unwrap().line_fragments = vec![
+            LineFragment {
+                text: "ab 日".into(),
+                window_left: 0,
+                width: 5,
+                style: style.clone(),
+            },
+            LineFragment {
+                text: "cd".into(),
+                window_left: 5,
+                width: 2,
+                style: None,
+            },
+            LineFragment {
+                text: "e".into(),
+                window_left: 7,
+                width: 1,
+                style,
+            },
+        ]
+        .into();
+        Arc::get_mut(&mut snapshot).unwrap().actual_lines[0] = Some(text);
+        let window = RenderedWindow::new(snapshot);
+        assert_eq!(
+            window.blended_text_cells(),
+            vec![(0, 0, 2), (3, 0, 2), (7, 0, 1)]
+        );
+    }
 
-        for line in self
-            .actual_lines
-            .iter_range_mut(0..self.viewport_margins.top as isize)
-            .flatten()
-        {
-            prepare_line(line)
-        }
-        let actual_line_count = self.actual_lines.len() as isize;
-        for line in self
-            .actual_lines
-            .iter_range_mut(
-                actual_line_count - self.viewport_margins.bottom as isize..actual_line_count,
-            )
-            .flatten()
-        {
-            prepare_line(line)
-        }
+    #[test]
+    fn test_scroll_position_stays_in_the_scrollback() {
+        let state = WindowState::new(1, Point::default(), (10, 2).into());
+        let mut window = RenderedWindow::new(state.snapshot());
+        window.scroll_animation.position = -5.0;
+        window.apply_snapshot(state.snapshot());
+        assert_eq!(window.scroll_animation.position, -2.0);
     }
 }
@@ -0,0 +1,24 @@
+use skia_safe::{Canvas, Image};
+
+/// The last frame from before an interactive resize, stretched over the window while it's being
+/// dragged, so that the grid is only reflowed once the size settles.
+pub struct ResizePreview {
+    snapshot: Image,
+}
+
+impl ResizePreview {
+    pub fn new(snapshot: Image) -> Self {
+        Self { snapshot }
+    }
+
+    pub fn draw(&self, root_canvas: &Canvas) {
+        let size = root_canvas.base_layer_size();
+        let scale_x = size.width as f32 / self.snapshot.width().max(1) as f32;
+        let scale_y = size.height as f32 / self.snapshot.height().max(1) as f32;
+
+        root_canvas.save();
+        root_canvas.scale((scale_x, scale_y));
+        root_canvas.draw_image(&self.snapshot, (0.0, 0.0), None);
+        root_canvas.restore();
+    }
+}
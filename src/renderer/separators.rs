@@ -0,0 +1,174 @@
+use skia_safe::{BlendMode, Canvas, ClipOp, Color, Paint, Point, Rect};
+
+use crate::{dimensions::Dimensions, renderer::animation_utils::lerp};
+
+/// How far from the line, in pixels, the mouse still hovers a separator.
+const HOVER_TOLERANCE: f32 = 3.0;
+/// The time in seconds it takes to highlight a hovered separator.
+const HIGHLIGHT_LENGTH: f32 = 0.1;
+
+/// The gap between two side by side windows, where Neovim draws the vertical separator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Separator {
+    /// The handle of the Neovim window to the left, which owns the separator.
+    pub window_handle: u64,
+    pub region: Rect,
+}
+
+impl Separator {
+    fn line_x(&self) -> f32 {
+        self.region.center_x().round()
+    }
+}
+
+/// Draws the vertical separators as thin lines instead of characters, and highlights the one that
+/// is hovered or being dragged.
+#[derive(Default)]
+pub struct Separators {
+    separators: Vec<Separator>,
+    hovered: Option<u64>,
+    highlight: f32,
+}
+
+impl Separators {
+    /// Finds the separators between the given editor windows, given as their pixel regions and
+    /// Neovim window handles.
+    pub fn update(&mut self, windows: &[(Rect, Option<u64>)], font_dimensions: Dimensions) {
+        let cell_width = font_dimensions.width as f32;
+        self.separators = windows
+            .iter()
+            .filter_map(|(left, handle)| Some((left, (*handle)?)))
+            .flat_map(|(left, window_handle)| {
+                windows.iter().filter_map(move |(right, _)| {
+                    let gap = right.left - left.right;
+                    let top = left.top.max(right.top);
+                    let bottom = left.bottom.min(right.bottom);
+                    (gap > cell_width * 0.5 && gap < cell_width * 1.5 && bottom > top).then(|| {
+                        Separator {
+                            window_handle,
+                            region: Rect::from_ltrb(left.right, top, right.left, bottom),
+                        }
+                    })
+                })
+            })
+            .collect();
+    }
+
+    pub fn clear(&mut self) {
+        self.separators.clear();
+        self.hovered = None;
+    }
+
+    /// Returns the handle of the window owning the separator at the given position, if any.
+    pub fn separator_at(&self, position: Point) -> Option<u64> {
+        self.separators
+            .iter()
+            .find(|separator| {
+                let region = separator.region;
+                position.y >= region.top
+                    && position.y < region.bottom
+                    && (position.x - separator.line_x()).abs()
+                        <= (region.width() / 2.0).max(HOVER_TOLERANCE)
+            })
+            .map(|separator| separator.window_handle)
+    }
+
+    pub fn set_hovered(&mut self, window_handle: Option<u64>) {
+        self.hovered = window_handle;
+    }
+
+    /// Fades the highlight in or out, returns true while it's animating.
+    pub fn animate(&mut self, dt: f32) -> bool {
+        let target = if self.hovered.is_some() { 1.0 } else { 0.0 };
+        if self.highlight == target {
+            return false;
+        }
+        let step = dt / HIGHLIGHT_LENGTH;
+        self.highlight = if target > self.highlight {
+            (self.highlight + step).min(1.0)
+        } else {
+            (self.highlight - step).max(0.0)
+        };
+        true
+    }
+
+    /// Covers the separator characters with the background and draws the lines over them.
+    /// Floating windows on top of the separators are left untouched.
+    pub fn draw(
+        &self,
+        root_canvas: &Canvas,
+        background: impl Fn(u64) -> Color,
+        foreground: Color,
+        scale: f32,
+        floating_rects: &[Rect],
+    ) {
+        if self.separators.is_empty() {
+            return;
+        }
+
+        root_canvas.save();
+        for rect in floating_rects {
+            root_canvas.clip_rect(rect, ClipOp::Difference, Some(false));
+        }
+
+        for separator in &self.separators {
+            // Replace the cells under the separator, so translucent backgrounds don't add up
+            let mut paint = Paint::default();
+            paint.set_blend_mode(BlendMode::Src);
+            paint.set_color(background(separator.window_handle));
+            root_canvas.draw_rect(separator.region, &paint);
+            paint.set_blend_mode(BlendMode::SrcOver);
+
+            let highlight = if self.hovered == Some(separator.window_handle) {
+                self.highlight
+            } else {
+                0.0
+            };
+            let width = lerp(1.0, 3.0, highlight) * scale;
+            let x = separator.line_x();
+            paint.set_color(foreground);
+            paint.set_alpha_f(lerp(0.3, 0.8, highlight));
+            root_canvas.draw_rect(
+                Rect::from_ltrb(
+                    x - width / 2.0,
+                    separator.region.top,
+                    x + width / 2.0,
+                    separator.region.bottom,
+                ),
+                &paint,
+            );
+        }
+
+        root_canvas.restore();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_separators_between_side_by_side_windows() {
+        let font_dimensions = Dimensions {
+            width: 10,
+            height: 20,
+        };
+        let windows = [
+            (Rect::from_ltrb(0.0, 0.0, 100.0, 200.0), Some(1000)),
+            (Rect::from_ltrb(110.0, 0.0, 200.0, 100.0), Some(1001)),
+            (Rect::from_ltrb(110.0, 120.0, 200.0, 200.0), Some(1002)),
+            (Rect::from_ltrb(0.0, 220.0, 200.0, 300.0), Some(1003)),
+        ];
+        let mut separators = Separators::default();
+        separators.update(&windows, font_dimensions);
+
+        assert_eq!(separators.separators.len(), 2);
+        assert_eq!(separators.separator_at(Point::new(105.0, 50.0)), Some(1000));
+        assert_eq!(
+            separators.separator_at(Point::new(104.0, 150.0)),
+            Some(1000)
+        );
+        assert_eq!(separators.separator_at(Point::new(105.0, 110.0)), None);
+        assert_eq!(separators.separator_at(Point::new(50.0, 50.0)), None);
+    }
+}
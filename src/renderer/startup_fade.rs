@@ -0,0 +1,52 @@
+use skia_safe::{Canvas, Color, Paint};
+
+use crate::renderer::animation_utils::{ease, ease_out_quad};
+
+/// Fades the first frames of Neovim in from the splash, the background shown while Neovim starts.
+pub struct StartupFade {
+    splash_color: Color,
+    t: f32,
+}
+
+impl StartupFade {
+    pub fn new(splash_color: Color) -> Self {
+        Self {
+            splash_color,
+            t: 0.0,
+        }
+    }
+
+    /// Steps the fade and returns true while it's still in progress.
+    pub fn animate(&mut self, dt: f32, length: f32) -> bool {
+        self.t = if length > 0.0 {
+            (self.t + dt / length).min(1.0)
+        } else {
+            1.0
+        };
+        self.t < 1.0
+    }
+
+    /// Covers the frame with the splash, which gets more transparent as the fade progresses.
+    pub fn draw(&self, root_canvas: &Canvas) {
+        let progress = ease(ease_out_quad, 0.0, 1.0, self.t);
+        let mut paint = Paint::default();
+        paint.set_color(self.splash_color);
+        paint.set_alpha_f(self.splash_color.a() as f32 / 255.0 * (1.0 - progress));
+        root_canvas.draw_paint(&paint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fade_finishes() {
+        let mut fade = StartupFade::new(Color::BLACK);
+        assert!(fade.animate(0.1, 0.3));
+        assert!(!fade.animate(0.3, 0.3));
+        // Without a length, the grids show up right away
+        let mut fade = StartupFade::new(Color::BLACK);
+        assert!(!fade.animate(0.01, 0.0));
+    }
+}
@@ -0,0 +1,163 @@
+use log::error;
+use rmpv::Value;
+use skia_safe::{PixelGeometry, SurfaceProps, SurfacePropsFlags};
+
+use crate::settings::ParseFromValue;
+
+use super::RendererSettings;
+
+/// The color space the window contents are presented in. It's only read when the window is
+/// created, since it changes the format of the window surface.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OutputColorSpace {
+    /// Let the platform decide how the colors are interpreted.
+    #[default]
+    Default,
+    /// Tag the window as sRGB, so that the colors are converted to the gamut of the display.
+    Srgb,
+    /// Interpret the colors in the Display P3 gamut, which makes them more saturated on wide
+    /// gamut displays. Only supported on macOS.
+    DisplayP3,
+    /// Render into a linear 16 bit floating point surface, that Windows composites in HDR and
+    /// wide gamut. Only supported with Direct3D.
+    ScRgb,
+}
+
+impl ParseFromValue for OutputColorSpace {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(color_space) = value.as_str() {
+            *self = match color_space {
+                "default" => OutputColorSpace::Default,
+                "srgb" => OutputColorSpace::Srgb,
+                "display-p3" => OutputColorSpace::DisplayP3,
+                "scrgb" => OutputColorSpace::ScRgb,
+                value => {
+                    error!(
+                        "Expected default, srgb, display-p3 or scrgb, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!("Expected a color space string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<OutputColorSpace> for Value {
+    fn from(color_space: OutputColorSpace) -> Self {
+        match color_space {
+            OutputColorSpace::Default => Value::from("default"),
+            OutputColorSpace::Srgb => Value::from("srgb"),
+            OutputColorSpace::DisplayP3 => Value::from("display-p3"),
+            OutputColorSpace::ScRgb => Value::from("scrgb"),
+        }
+    }
+}
+
+/// The order of the color elements of the display pixels, used for subpixel antialiased text.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SubpixelOrder {
+    /// Subpixel antialiasing is disabled, and the window can be transparent.
+    #[default]
+    None,
+    Rgb,
+    Bgr,
+    Vrgb,
+    Vbgr,
+}
+
+impl SubpixelOrder {
+    pub fn pixel_geometry(self) -> Option<PixelGeometry> {
+        match self {
+            SubpixelOrder::None => None,
+            SubpixelOrder::Rgb => Some(PixelGeometry::RGBH),
+            SubpixelOrder::Bgr => Some(PixelGeometry::BGRH),
+            SubpixelOrder::Vrgb => Some(PixelGeometry::RGBV),
+            SubpixelOrder::Vbgr => Some(PixelGeometry::BGRV),
+        }
+    }
+}
+
+impl ParseFromValue for SubpixelOrder {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(order) = value.as_str() {
+            *self = match order {
+                "none" => SubpixelOrder::None,
+                "rgb" => SubpixelOrder::Rgb,
+                "bgr" => SubpixelOrder::Bgr,
+                "vrgb" => SubpixelOrder::Vrgb,
+                "vbgr" => SubpixelOrder::Vbgr,
+                value => {
+                    error!(
+                        "Expected none, rgb, bgr, vrgb or vbgr, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!("Expected a subpixel order string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<SubpixelOrder> for Value {
+    fn from(order: SubpixelOrder) -> Self {
+        match order {
+            SubpixelOrder::None => Value::from("none"),
+            SubpixelOrder::Rgb => Value::from("rgb"),
+            SubpixelOrder::Bgr => Value::from("bgr"),
+            SubpixelOrder::Vrgb => Value::from("vrgb"),
+            SubpixelOrder::Vbgr => Value::from("vbgr"),
+        }
+    }
+}
+
+/// The settings that decide the format of the window surface. They are only read when the window
+/// is created.
+#[derive(Clone, Debug, Default)]
+pub struct SurfaceOptions {
+    pub color_space: OutputColorSpace,
+    pub gamma_correct: bool,
+    pub subpixel_order: SubpixelOrder,
+}
+
+impl SurfaceOptions {
+    pub fn from_settings(settings: &RendererSettings) -> Self {
+        Self {
+            color_space: settings.color_space,
+            gamma_correct: settings.gamma_correct_blending,
+            subpixel_order: settings.subpixel_antialiasing,
+        }
+    }
+
+    /// Subpixel antialiasing needs an opaque surface, since the text is blended per color channel.
+    pub fn opaque(&self) -> bool {
+        self.subpixel_order != SubpixelOrder::None
+    }
+
+    /// The surface properties that tell skia the subpixel layout, `None` for the defaults.
+    pub fn surface_props(&self) -> Option<SurfaceProps> {
+        self.subpixel_order
+            .pixel_geometry()
+            .map(|geometry| SurfaceProps::new(SurfacePropsFlags::default(), geometry))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_subpixel_order() {
+        let mut order = SubpixelOrder::default();
+        order.parse_from_value(Value::from("bgr"));
+        assert_eq!(order, SubpixelOrder::Bgr);
+        order.parse_from_value(Value::from("diagonal"));
+        assert_eq!(order, SubpixelOrder::Bgr);
+        assert_eq!(order.pixel_geometry(), Some(PixelGeometry::BGRH));
+        assert_eq!(Value::from(SubpixelOrder::Vrgb), Value::from("vrgb"));
+    }
+}
@@ -0,0 +1,212 @@
+use log::error;
+use rmpv::Value;
+use skia_safe::{paint::Style, Canvas, Color, Paint, Rect};
+
+use crate::{
+    renderer::animation_utils::{ease_linear, ease_out_cubic, ease_out_expo, ease_out_quad},
+    settings::ParseFromValue,
+};
+
+/// The opacity of the flash and the dimming at the start of the bell.
+const MAX_ALPHA: f32 = 0.3;
+/// The width of the border in logical pixels.
+const BORDER_WIDTH: f32 = 4.0;
+
+/// How the window shows the bell when `visualbell` is set.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VisualBellEffect {
+    /// The window is covered with the foreground color, which fades out.
+    #[default]
+    Flash,
+    /// The window is darkened.
+    Dim,
+    /// Only the edges of the window flash.
+    Border,
+}
+
+impl ParseFromValue for VisualBellEffect {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(effect) = value.as_str() {
+            *self = match effect {
+                "flash" => VisualBellEffect::Flash,
+                "dim" => VisualBellEffect::Dim,
+                "border" => VisualBellEffect::Border,
+                value => {
+                    error!("Expected flash, dim or border, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Expected a visual bell effect string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<VisualBellEffect> for Value {
+    fn from(effect: VisualBellEffect) -> Self {
+        match effect {
+            VisualBellEffect::Flash => Value::from("flash"),
+            VisualBellEffect::Dim => Value::from("dim"),
+            VisualBellEffect::Border => Value::from("border"),
+        }
+    }
+}
+
+/// How the visual bell fades out.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum VisualBellEasing {
+    Linear,
+    Quad,
+    #[default]
+    Cubic,
+    Expo,
+}
+
+impl VisualBellEasing {
+    fn function(self) -> fn(f32) -> f32 {
+        match self {
+            VisualBellEasing::Linear => ease_linear,
+            VisualBellEasing::Quad => ease_out_quad,
+            VisualBellEasing::Cubic => ease_out_cubic,
+            VisualBellEasing::Expo => ease_out_expo,
+        }
+    }
+}
+
+impl ParseFromValue for VisualBellEasing {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(easing) = value.as_str() {
+            *self = match easing {
+                "linear" => VisualBellEasing::Linear,
+                "quad" => VisualBellEasing::Quad,
+                "cubic" => VisualBellEasing::Cubic,
+                "expo" => VisualBellEasing::Expo,
+                value => {
+                    error!(
+                        "Expected linear, quad, cubic or expo, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!("Expected an easing string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<VisualBellEasing> for Value {
+    fn from(easing: VisualBellEasing) -> Self {
+        match easing {
+            VisualBellEasing::Linear => Value::from("linear"),
+            VisualBellEasing::Quad => Value::from("quad"),
+            VisualBellEasing::Cubic => Value::from("cubic"),
+            VisualBellEasing::Expo => Value::from("expo"),
+        }
+    }
+}
+
+/// The effect drawn over the window when Neovim rings the bell with `visualbell` set.
+#[derive(Default)]
+pub struct VisualBell {
+    /// The progress of the fade out, `None` when the bell isn't shown.
+    t: Option<f32>,
+    intensity: f32,
+}
+
+impl VisualBell {
+    /// Starts the effect again, if it's still fading out from the previous bell.
+    pub fn ring(&mut self) {
+        self.t = Some(0.0);
+        self.intensity = 1.0;
+    }
+
+    /// Returns true while the effect is shown. Without `fade`, when the animations are disabled,
+    /// it's shown unchanged for the duration and then removed at once.
+    pub fn animate(
+        &mut self,
+        dt: f32,
+        duration: f32,
+        easing: VisualBellEasing,
+        fade: bool,
+    ) -> bool {
+        let Some(t) = self.t.as_mut() else {
+            return false;
+        };
+        *t = if duration > 0.0 {
+            (*t + dt / duration).min(1.0)
+        } else {
+            1.0
+        };
+        self.intensity = if fade {
+            1.0 - easing.function()(*t)
+        } else {
+            1.0
+        };
+        if *t >= 1.0 {
+            self.t = None;
+        }
+        true
+    }
+
+    pub fn draw(&self, root_canvas: &Canvas, effect: VisualBellEffect, color: Color, scale: f32) {
+        if self.t.is_none() || self.intensity <= 0.0 {
+            return;
+        }
+        let size = root_canvas.base_layer_size();
+        let rect = Rect::from_wh(size.width as f32, size.height as f32);
+        let mut paint = Paint::default();
+        match effect {
+            VisualBellEffect::Flash => {
+                paint.set_color(color);
+                paint.set_alpha_f(MAX_ALPHA * self.intensity);
+            }
+            VisualBellEffect::Dim => {
+                paint.set_color(Color::BLACK);
+                paint.set_alpha_f(MAX_ALPHA * self.intensity);
+            }
+            VisualBellEffect::Border => {
+                let width = BORDER_WIDTH * scale;
+                paint.set_color(color);
+                paint.set_alpha_f(self.intensity);
+                paint.set_style(Style::Stroke);
+                paint.set_stroke_width(width);
+                // The stroke is centered on the rect, so it's moved inside the window
+                root_canvas.draw_rect(rect.with_inset((width / 2.0, width / 2.0)), &paint);
+                return;
+            }
+        }
+        root_canvas.draw_rect(rect, &paint);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visual_bell_fades_out_over_the_duration() {
+        let mut bell = VisualBell::default();
+        assert!(!bell.animate(0.1, 0.2, VisualBellEasing::Linear, true));
+
+        bell.ring();
+        assert!(bell.animate(0.1, 0.2, VisualBellEasing::Linear, true));
+        assert!((bell.intensity - 0.5).abs() < 1e-6);
+        assert!(bell.animate(0.1, 0.2, VisualBellEasing::Linear, true));
+        assert_eq!(bell.intensity, 0.0);
+        assert!(!bell.animate(0.1, 0.2, VisualBellEasing::Linear, true));
+    }
+
+    #[test]
+    fn test_visual_bell_without_animations_does_not_fade() {
+        let mut bell = VisualBell::default();
+        bell.ring();
+        assert!(bell.animate(0.1, 0.2, VisualBellEasing::Linear, false));
+        assert_eq!(bell.intensity, 1.0);
+        assert!(bell.animate(0.1, 0.2, VisualBellEasing::Linear, false));
+        assert!(!bell.animate(0.1, 0.2, VisualBellEasing::Linear, false));
+    }
+}
@@ -11,7 +11,10 @@ mod vsync_win_swap_chain;
 use vsync_timer::VSyncTimer;
 
 use crate::{
-    renderer::SkiaRenderer, settings::SETTINGS, window::UserEvent, window::WindowSettings,
+    renderer::SkiaRenderer,
+    settings::SETTINGS,
+    window::UserEvent,
+    window::{limit_refresh_rate, WindowSettings},
 };
 use winit::{event_loop::EventLoopProxy, window::Window};
 
@@ -49,6 +52,21 @@ impl VSync {
         }
     }
 
+    /// A short description for `:checkhealth neovide`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            VSync::Opengl() => "OpenGL swap interval",
+            VSync::WinitThrottling() => "Winit throttling",
+            VSync::Timer(_) => "Timer, vsync disabled",
+            #[cfg(target_os = "windows")]
+            VSync::WindowsDwm(_) => "DWM flush",
+            #[cfg(target_os = "windows")]
+            VSync::WindowsSwapChain(_) => "Swap chain waitable object",
+            #[cfg(target_os = "macos")]
+            VSync::Macos(_) => "CVDisplayLink",
+        }
+    }
+
     pub fn wait_for_vsync(&mut self) {
         match self {
             VSync::Timer(vsync) => vsync.wait_for_vsync(),
@@ -85,7 +103,8 @@ impl VSync {
     }
 
     pub fn get_refresh_rate(&self, window: &Window) -> f32 {
-        let settings_refresh_rate = 1.0 / SETTINGS.get::<WindowSettings>().refresh_rate as f32;
+        let settings_refresh_rate =
+            1.0 / limit_refresh_rate(SETTINGS.get::<WindowSettings>().refresh_rate) as f32;
 
         match self {
             VSync::Timer(_) => settings_refresh_rate,
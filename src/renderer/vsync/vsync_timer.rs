@@ -1,5 +1,5 @@
 use crate::settings::SETTINGS;
-use crate::window::WindowSettings;
+use crate::window::{limit_refresh_rate, WindowSettings};
 use spin_sleep::SpinSleeper;
 use std::time::{Duration, Instant};
 
@@ -17,8 +17,8 @@ impl VSyncTimer {
     }
 
     pub fn wait_for_vsync(&mut self) {
-        let refresh_duration =
-            Duration::from_secs_f64(1.0 / SETTINGS.get::<WindowSettings>().refresh_rate as f64);
+        let refresh_rate = limit_refresh_rate(SETTINGS.get::<WindowSettings>().refresh_rate);
+        let refresh_duration = Duration::from_secs_f64(1.0 / refresh_rate as f64);
         let next_refresh = self.last_refresh + refresh_duration;
         self.last_refresh = next_refresh;
         let sleep_duration = next_refresh.saturating_duration_since(Instant::now());
@@ -0,0 +1,135 @@
+use std::collections::HashMap;
+
+use rmpv::Value;
+use skia_safe::{Canvas, Color, Paint, Rect};
+
+use crate::dimensions::Dimensions;
+
+/// The `cursorline` and `colorcolumn` of a Neovim window, drawn by the renderer instead of Neovim
+/// so that they follow the animations.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct WindowHighlight {
+    pub cursorline: bool,
+    /// The grid columns of the `colorcolumn`, after the number and sign columns and the horizontal
+    /// scroll are taken into account.
+    pub color_columns: Vec<u64>,
+}
+
+/// The highlights of all the windows, with the colors of the `CursorLine` and `ColorColumn`
+/// highlight groups, which are hidden in Neovim while the overlays are enabled.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct WindowHighlights {
+    pub windows: HashMap<u64, WindowHighlight>,
+    pub cursorline_color: Option<Color>,
+    pub color_column_color: Option<Color>,
+}
+
+impl WindowHighlights {
+    /// Parses a list of `[window, cursorline, columns]` and a table of the colors as RGB integers.
+    pub fn parse(windows: &Value, colors: &Value) -> Self {
+        let windows = windows
+            .as_array()
+            .map(|windows| {
+                windows
+                    .iter()
+                    .filter_map(|window| match window.as_array()?.as_slice() {
+                        [handle, cursorline, columns] => Some((
+                            handle.as_u64()?,
+                            WindowHighlight {
+                                cursorline: cursorline.as_bool()?,
+                                color_columns: columns
+                                    .as_array()?
+                                    .iter()
+                                    .filter_map(Value::as_u64)
+                                    .collect(),
+                            },
+                        )),
+                        _ => None,
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+        let color = |name: &str| {
+            colors
+                .as_map()?
+                .iter()
+                .find(|(key, _)| key.as_str() == Some(name))?
+                .1
+                .as_u64()
+                .map(|rgb| Color::from(0xFF00_0000 | rgb as u32))
+        };
+        Self {
+            windows,
+            cursorline_color: color("cursorline"),
+            color_column_color: color("colorcolumn"),
+        }
+    }
+}
+
+/// What is drawn under the text of a window.
+pub struct WindowUnderlay<'a> {
+    pub highlight: &'a WindowHighlight,
+    pub color_column_color: Option<Color>,
+    /// The vertical center of the animated cursor and the `CursorLine` color, for the window with
+    /// the cursor.
+    pub cursorline: Option<(f32, Color)>,
+}
+
+impl WindowUnderlay<'_> {
+    /// Draws the highlights over the window background, before the backgrounds of the cells.
+    pub fn draw(&self, canvas: &Canvas, region: &Rect, font_dimensions: Dimensions) {
+        let mut paint = Paint::default();
+        if let Some(color) = self.color_column_color {
+            paint.set_color(color);
+            let width = font_dimensions.width as f32;
+            for column in &self.highlight.color_columns {
+                let left = region.left + *column as f32 * width;
+                canvas.draw_rect(
+                    Rect::from_ltrb(left, region.top, left + width, region.bottom),
+                    &paint,
+                );
+            }
+        }
+        if let Some((center, color)) = self.cursorline.filter(|_| self.highlight.cursorline) {
+            paint.set_color(color);
+            let half_height = font_dimensions.height as f32 / 2.0;
+            canvas.draw_rect(
+                Rect::from_ltrb(
+                    region.left,
+                    center - half_height,
+                    region.right,
+                    center + half_height,
+                ),
+                &paint,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_window_highlights() {
+        let windows = Value::from(vec![
+            Value::from(vec![
+                Value::from(1000),
+                Value::from(true),
+                Value::from(vec![Value::from(84), Value::from(124)]),
+            ]),
+            Value::from("invalid"),
+        ]);
+        let colors = Value::Map(vec![(Value::from("cursorline"), Value::from(0x313244))]);
+        let highlights = WindowHighlights::parse(&windows, &colors);
+
+        assert_eq!(highlights.windows.len(), 1);
+        assert_eq!(highlights.windows[&1000].color_columns, vec![84, 124]);
+        assert!(highlights.windows[&1000].cursorline);
+        assert_eq!(
+            highlights.cursorline_color,
+            Some(Color::from_rgb(0x31, 0x32, 0x44))
+        );
+        assert_eq!(highlights.color_column_color, None);
+    }
+}
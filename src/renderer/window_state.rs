@@ -0,0 +1,986 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    ops::Range,
+    sync::Arc,
+};
+
+use skia_safe::{Picture, Point};
+
+use crate::{
+    dimensions::Dimensions,
+    editor::{AnchorInfo, Style, WindowType},
+    profiling::tracy_zone,
+    renderer::{
+        frame_producer::{prepare_line, FragmentPicture},
+        indent_guides::{is_blank, IndentGuides, TrailingWhitespace, WindowIndent},
+        parse_color, GridRenderer, LineFragment, RendererSettings, ViewportMargins,
+        WindowDrawCommand,
+    },
+    utils::RingBuffer,
+};
+
+/// A row of a grid, with the pictures it's drawn with. The lines are immutable once they are in a
+/// snapshot, a line that changes is replaced.
+#[derive(Clone)]
+pub(super) struct Line {
+    pub(super) line_fragments: Arc<[LineFragment]>,
+    pub(super) fragment_pictures: Vec<Option<FragmentPicture>>,
+    pub(super) background_picture: Option<Picture>,
+    pub(super) foreground_picture: Option<Picture>,
+    pub(super) has_transparency: bool,
+    pub(super) is_inferred_border: bool,
+    pub(super) trailing_whitespace: Option<TrailingWhitespace>,
+    // The generation of the grid renderer the pictures were recorded with, `None` until they are
+    pub(super) prepared_generation: Option<u64>,
+}
+
+/// How the flushes moved a scroll animation. The window thread only draws the latest snapshot, so
+/// the lines are counted from the last time the position was set, instead of per flush.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct ScrollShift {
+    /// Incremented whenever the position is set.
+    pub sets: u64,
+    pub position: f32,
+    /// Whether the animation was stopped when the position was set.
+    pub stopped: bool,
+    /// The lines the animation moved by since the position was set.
+    pub moved: isize,
+}
+
+impl ScrollShift {
+    fn set(&mut self, position: f32, stop: bool) {
+        self.sets += 1;
+        self.position = position;
+        self.stopped = stop;
+        self.moved = 0;
+    }
+
+    fn shift(&mut self, lines: isize) {
+        self.moved += lines;
+    }
+}
+
+/// The state of a window after a flush, which the window thread draws and animates.
+pub struct WindowSnapshot {
+    pub id: u64,
+    pub hidden: bool,
+    /// Incremented whenever the window becomes visible, so that it appears without animating.
+    pub shown: u64,
+    pub anchor_info: Option<AnchorInfo>,
+    pub window_type: WindowType,
+    pub grid_size: Dimensions,
+    pub grid_destination: Point,
+    pub viewport_margins: ViewportMargins,
+    pub(super) scrollback_lines: RingBuffer<Option<Arc<Line>>>,
+    pub(super) actual_lines: RingBuffer<Option<Arc<Line>>>,
+    pub scroll_shift: ScrollShift,
+    pub horizontal_scroll_shift: ScrollShift,
+    pub scroll_text_left: u64,
+    // The style of the empty cells after the lines, which use the Normal highlight of the window
+    pub background_style: Option<Arc<Style>>,
+    pub window_handle: Option<u64>,
+}
+
+/// The grid of a window, which the frame producer applies the draw commands to.
+pub(super) struct WindowState {
+    id: u64,
+    hidden: bool,
+    shown: u64,
+    anchor_info: Option<AnchorInfo>,
+    window_type: WindowType,
+
+    grid_size: Dimensions,
+    grid_destination: Point,
+
+    scrollback_lines: RingBuffer<Option<Arc<Line>>>,
+    actual_lines: RingBuffer<Option<Arc<Line>>>,
+    scroll_delta: isize,
+    // The screen lines scrolled by the grid scroll events since the last flush
+    grid_scroll_rows: isize,
+    // The change of `leftcol` since the last flush, and the first column of the text, which is
+    // after the number and sign columns
+    grid_scroll_cols: isize,
+    scroll_text_left: u64,
+    top_line: Option<f64>,
+    // The columns of the top line skipped by `smoothscroll`
+    skipcol: u64,
+    flushed_view_top: Option<(f64, u64)>,
+    // Set when the grid was cleared, which is only handled in the flush, since the viewport events
+    // after it tell if the view jumped or the content was only refreshed
+    pending_clear: bool,
+    viewport_margins: ViewportMargins,
+
+    scroll_shift: ScrollShift,
+    horizontal_scroll_shift: ScrollShift,
+
+    background_style: Option<Arc<Style>>,
+
+    window_handle: Option<u64>,
+    // Overrides `g:neovide_ligatures` for the buffer shown in the window
+    ligatures: Option<bool>,
+    // Whether the prepared lines were shaped with ligatures
+    prepared_ligatures: bool,
+    indent: Option<WindowIndent>,
+    // The indent guides drawn on the prepared lines
+    prepared_indent_guides: Option<IndentGuides>,
+    // The columns of the trailing whitespace on the rows of the grid
+    trailing_whitespace: HashMap<u64, Range<u64>>,
+}
+
+impl WindowState {
+    pub fn new(id: u64, grid_position: Point, grid_size: Dimensions) -> Self {
+        WindowState {
+            id,
+            hidden: false,
+            shown: 0,
+            anchor_info: None,
+            window_type: WindowType::Editor,
+
+            grid_size,
+            grid_destination: grid_position,
+
+            actual_lines: RingBuffer::new(grid_size.height as usize, None),
+            scrollback_lines: RingBuffer::new(2 * grid_size.height as usize, None),
+            scroll_delta: 0,
+            grid_scroll_rows: 0,
+            grid_scroll_cols: 0,
+            scroll_text_left: 0,
+            top_line: None,
+            skipcol: 0,
+            flushed_view_top: None,
+            pending_clear: false,
+            viewport_margins: ViewportMargins {
+                top: 0,
+                bottom: 0,
+                inferred: true,
+            },
+
+            scroll_shift: ScrollShift::default(),
+            horizontal_scroll_shift: ScrollShift::default(),
+
+            background_style: None,
+            window_handle: None,
+            ligatures: None,
+            prepared_ligatures: true,
+            indent: None,
+            prepared_indent_guides: None,
+            trailing_whitespace: HashMap::new(),
+        }
+    }
+
+    pub fn window_handle(&self) -> Option<u64> {
+        self.window_handle
+    }
+
+    /// The immutable copy of the window that's drawn. The lines are shared with it.
+    pub fn snapshot(&self) -> Arc<WindowSnapshot> {
+        Arc::new(WindowSnapshot {
+            id: self.id,
+            hidden: self.hidden,
+            shown: self.shown,
+            anchor_info: self.anchor_info.clone(),
+            window_type: self.window_type,
+            grid_size: self.grid_size,
+            grid_destination: self.grid_destination,
+            viewport_margins: self.viewport_margins.clone(),
+            scrollback_lines: self.scrollback_lines.clone(),
+            actual_lines: self.actual_lines.clone(),
+            scroll_shift: self.scroll_shift,
+            horizontal_scroll_shift: self.horizontal_scroll_shift,
+            scroll_text_left: self.scroll_text_left,
+            background_style: self.background_style.clone(),
+            window_handle: self.window_handle,
+        })
+    }
+
+    /// Makes the window visible, it appears at its destination without animating.
+    fn show(&mut self) {
+        self.hidden = false;
+        self.shown += 1;
+    }
+
+    pub fn handle_window_draw_command(&mut self, draw_command: WindowDrawCommand) {
+        match draw_command {
+            WindowDrawCommand::Position {
+                grid_position: (grid_left, grid_top),
+                grid_size,
+                anchor_info,
+                window_type,
+            } => {
+                tracy_zone!("position_cmd", 0);
+
+                let grid_left = grid_left.max(0.0) as f32;
+                let grid_top = grid_top.max(0.0) as f32;
+                self.grid_destination = (grid_left, grid_top).into();
+                let new_grid_size: Dimensions = grid_size.into();
+
+                let height = new_grid_size.height as usize;
+                self.actual_lines.resize(height, None);
+                self.grid_size = new_grid_size;
+
+                self.resize_scrollback(height);
+                self.scrollback_lines.clone_from_iter(&self.actual_lines);
+                self.scroll_delta = 0;
+                self.grid_scroll_rows = 0;
+                self.grid_scroll_cols = 0;
+                self.flushed_view_top = self.view_top();
+
+                self.anchor_info = anchor_info;
+                self.window_type = window_type;
+
+                if self.hidden {
+                    self.show();
+                }
+            }
+            WindowDrawCommand::DrawLine {
+                row,
+                line_fragments,
+            } => {
+                tracy_zone!("draw_line_cmd", 0);
+
+                // Neovim redraws whole lines even when only a few cells change, for example when
+                // moving the cursorline, so keep the pictures of the unchanged fragments
+                let fragment_pictures = match &self.actual_lines[row] {
+                    Some(old_line) => reuse_fragment_pictures(
+                        &old_line.line_fragments,
+                        &old_line.fragment_pictures,
+                        &line_fragments,
+                    ),
+                    None => vec![None; line_fragments.len()],
+                };
+
+                let mut line = Line {
+                    line_fragments: line_fragments.into(),
+                    fragment_pictures,
+                    background_picture: None,
+                    foreground_picture: None,
+                    has_transparency: false,
+                    is_inferred_border: false,
+                    trailing_whitespace: None,
+                    prepared_generation: None,
+                };
+
+                if self.viewport_margins.inferred {
+                    let check_border = |fragment: &LineFragment, check: &dyn Fn(&str) -> bool| {
+                        fragment.style.as_ref().map_or(false, |style| {
+                            style.infos.last().map_or(false, |info| {
+                                // The specification seems to indicate that kind should be UI and
+                                // then we only need to test ui_name. But at least for FloatTitle,
+                                // that is not the case, the kind is set to syntax and hi_name is
+                                // set.
+                                check(&info.ui_name) || check(&info.hi_name)
+                            })
+                        })
+                    };
+
+                    let float_border =
+                        |s: &str| matches!(s, "FloatBorder" | "FloatTitle" | "FloatFooter");
+                    let winbar = |s: &str| matches!(s, "WinBar" | "WinBarNC");
+
+                    // Lines with purly border highlight groups are considered borders.
+                    line.is_inferred_border = line
+                        .line_fragments
+                        .iter()
+                        .map(|fragment| check_border(fragment, &float_border))
+                        .all(|v| v);
+
+                    // And also lines with a winbar highlight anywhere
+                    line.is_inferred_border |= line
+                        .line_fragments
+                        .iter()
+                        .map(|fragment| check_border(fragment, &winbar))
+                        .any(|v| v)
+                }
+
+                self.actual_lines[row] = Some(Arc::new(line));
+            }
+            WindowDrawCommand::Scroll {
+                top,
+                bottom,
+                left,
+                right,
+                rows,
+                cols,
+            } => {
+                tracy_zone!("scroll_cmd", 0);
+                let full_width = left == 0 && right == self.grid_size.width && cols == 0;
+                if full_width && top == 0 && bottom == self.grid_size.height {
+                    self.actual_lines.rotate(rows as isize);
+                }
+                // Scrolls of the whole text area count the screen lines the view moved, which
+                // differ from the buffer lines of the viewport when lines wrap
+                if full_width
+                    && top <= self.viewport_margins.top
+                    && bottom + self.viewport_margins.bottom >= self.grid_size.height
+                {
+                    self.grid_scroll_rows += rows as isize;
+                }
+            }
+            WindowDrawCommand::Clear => {
+                tracy_zone!("clear_cmd", 0);
+                self.pending_clear = true;
+            }
+            WindowDrawCommand::Show => {
+                tracy_zone!("show_cmd", 0);
+                if self.hidden {
+                    self.show();
+                    self.scroll_shift.set(0.0, true);
+                    self.horizontal_scroll_shift.set(0.0, true);
+                }
+            }
+            WindowDrawCommand::Hide => {
+                tracy_zone!("hide_cmd", 0);
+                self.hidden = true;
+            }
+            WindowDrawCommand::Viewport {
+                top_line,
+                scroll_delta,
+            } => {
+                log::trace!("Handling Viewport {}", self.id);
+                // Several viewport events can arrive before the next flush when plugins scroll
+                // programmatically, so accumulate them instead of only keeping the last one
+                self.scroll_delta += scroll_delta.round() as isize;
+                self.top_line = Some(top_line);
+            }
+            WindowDrawCommand::ViewportMargins { top, bottom, .. } => {
+                self.viewport_margins = ViewportMargins {
+                    top,
+                    bottom,
+                    inferred: false,
+                }
+            }
+            WindowDrawCommand::WindowHandle(handle) => {
+                self.window_handle = Some(handle);
+            }
+            _ => {}
+        };
+    }
+
+    fn infer_viewport_margins(&mut self) {
+        if self.viewport_margins.inferred {
+            self.viewport_margins.top = self
+                .actual_lines
+                .iter()
+                .take_while(|line| {
+                    if let Some(line) = line {
+                        line.is_inferred_border
+                    } else {
+                        false
+                    }
+                })
+                .count() as u64;
+            self.viewport_margins.bottom = (self.viewport_margins.top as usize
+                ..self.actual_lines.len())
+                .rev()
+                .map(|i| self.actual_lines[i].as_ref())
+                .take_while(|line| {
+                    if let Some(line) = line {
+                        line.is_inferred_border
+                    } else {
+                        false
+                    }
+                })
+                .count() as u64;
+        }
+    }
+
+    /// Resizes the scrollback to fit `height` lines on each side of the view, keeping the lines
+    /// around the view, so that an ongoing scroll animation continues without jumping. The window
+    /// thread keeps the animation within the new scrollback.
+    fn resize_scrollback(&mut self, height: usize) {
+        let old_height = self.scrollback_lines.len() as isize / 2;
+        let new_height = height as isize;
+        let mut scrollback_lines = RingBuffer::new(2 * height, None);
+        for i in -new_height.min(old_height)..new_height.min(old_height) {
+            scrollback_lines[i] = self.scrollback_lines[i].clone();
+        }
+        self.scrollback_lines = scrollback_lines;
+    }
+
+    pub fn flush(&mut self, renderer_settings: &RendererSettings) {
+        self.infer_viewport_margins();
+        self.update_background_style();
+
+        // If the borders are changed, reset the scrollback to only fit the inner view
+        let inner_range = self.viewport_margins.top as isize
+            ..(self.actual_lines.len() - self.viewport_margins.bottom as usize) as isize;
+        let inner_size = inner_range.len();
+        let view_top = self.view_top();
+        let scroll_delta =
+            reconcile_scroll_delta(self.scroll_delta, self.flushed_view_top, view_top);
+        self.flushed_view_top = view_top;
+        // Reloading the buffer or formatting it clears the grid, but when the view stays at the
+        // same line, the scrollback and an ongoing animation are kept, so that nothing jumps
+        let scroll_delta = if std::mem::take(&mut self.pending_clear) {
+            if scroll_delta != 0 {
+                self.scrollback_lines
+                    .iter_mut()
+                    .for_each(|line| *line = None);
+                self.scroll_shift.set(0.0, true);
+                self.horizontal_scroll_shift.set(0.0, true);
+            }
+            self.grid_scroll_rows = 0;
+            self.grid_scroll_cols = 0;
+            0
+        } else {
+            scroll_delta
+        };
+        if inner_size != self.scrollback_lines.len() / 2 {
+            self.resize_scrollback(inner_size);
+            self.scrollback_lines
+                .clone_from_iter(self.actual_lines.iter_range(inner_range));
+            self.scroll_delta = 0;
+            self.grid_scroll_rows = 0;
+            self.grid_scroll_cols = 0;
+            return;
+        }
+        let scroll_delta = self.screen_scroll_delta(scroll_delta, inner_range.clone());
+        let inner_view = self.actual_lines.iter_range(inner_range);
+
+        self.scrollback_lines.rotate(scroll_delta);
+
+        self.scrollback_lines.clone_from_iter(inner_view);
+
+        if scroll_delta != 0 {
+            let max_delta = self.scrollback_lines.len() - self.grid_size.height as usize;
+            log::trace!("Scroll delta {scroll_delta}, max_delta {max_delta}");
+            // Do a limited scroll with empty lines when scrolling far
+            if scroll_delta.unsigned_abs() > max_delta {
+                let far_lines = renderer_settings
+                    .scroll_animation_far_lines
+                    .min(self.actual_lines.len() as u32) as isize;
+
+                self.scroll_shift
+                    .set(-(far_lines * scroll_delta.signum()) as f32, false);
+                let empty_lines = if scroll_delta > 0 {
+                    -far_lines..0
+                } else {
+                    self.actual_lines.len() as isize..self.actual_lines.len() as isize + far_lines
+                };
+                for i in empty_lines {
+                    self.scrollback_lines[i] = None;
+                }
+            // And even when scrolling in steps, the window thread doesn't let it drift too far,
+            // since the buffer size is limited
+            } else {
+                self.scroll_shift.shift(-scroll_delta);
+            }
+        }
+        self.scroll_delta = 0;
+        self.grid_scroll_rows = 0;
+        self.flush_horizontal_scroll();
+    }
+
+    /// Records the columns of the top line that `smoothscroll` skips. Neovim sends it before
+    /// redrawing the window, so it's used to reconcile the scroll delta on the next flush.
+    pub fn set_skipcol(&mut self, skipcol: u64) {
+        self.skipcol = skipcol;
+    }
+
+    fn view_top(&self) -> Option<(f64, u64)> {
+        self.top_line.map(|top_line| (top_line, self.skipcol))
+    }
+
+    /// Records that the window was scrolled horizontally by `cols`, with the text starting at
+    /// `text_left`. Neovim sends it before redrawing the window, so it's applied on the next flush.
+    pub fn scroll_columns(&mut self, cols: i64, text_left: u64) {
+        self.grid_scroll_cols += cols as isize;
+        self.scroll_text_left = text_left;
+    }
+
+    /// Moves the horizontal scroll animation back by the scrolled columns, so that the text slides
+    /// from where it was to its new place.
+    fn flush_horizontal_scroll(&mut self) {
+        self.horizontal_scroll_shift.shift(-self.grid_scroll_cols);
+        self.grid_scroll_cols = 0;
+    }
+
+    /// Converts the scroll delta of the viewport to screen lines. With `wrap` a buffer line can
+    /// take several screen lines, so the rows of the grid scroll events are used when Neovim sent
+    /// them, and otherwise the shift is found by comparing the new lines with the previous ones.
+    fn screen_scroll_delta(&self, scroll_delta: isize, inner_range: Range<isize>) -> isize {
+        if scroll_delta == 0 {
+            return 0;
+        }
+        if self.grid_scroll_rows.signum() == scroll_delta.signum() {
+            return self.grid_scroll_rows;
+        }
+        let previous: Vec<_> = self
+            .scrollback_lines
+            .iter_range(0..inner_range.len() as isize)
+            .cloned()
+            .collect();
+        let current: Vec<_> = self.actual_lines.iter_range(inner_range).cloned().collect();
+        matching_scroll_delta(&previous, &current, scroll_delta)
+    }
+
+    /// Neovim fills the cells after the end of the lines with the Normal highlight of the window,
+    /// which can be changed with `winhighlight`. So the most common style of the last cell of each
+    /// line is used as the background of the whole window.
+    fn update_background_style(&mut self) {
+        let mut counts: Vec<(Option<Arc<Style>>, usize)> = Vec::new();
+        for line in self.actual_lines.iter().flatten() {
+            let Some(fragment) = line.line_fragments.last() else {
+                continue;
+            };
+            match counts
+                .iter_mut()
+                .find(|(style, _)| *style == fragment.style)
+            {
+                Some((_, count)) => *count += 1,
+                None => counts.push((fragment.style.clone(), 1)),
+            }
+        }
+        if let Some((style, _)) = counts.into_iter().max_by_key(|(_, count)| *count) {
+            self.background_style = style;
+        }
+    }
+
+    /// Sets the ligature override of the window, the lines are reshaped when they are prepared.
+    pub fn set_ligatures(&mut self, ligatures: Option<bool>) {
+        self.ligatures = ligatures;
+    }
+
+    /// Sets the indentation of the buffer shown in the window, which is used for the guides.
+    pub fn set_indent(&mut self, indent: Option<WindowIndent>) {
+        self.indent = indent;
+    }
+
+    /// Sets the columns of the trailing whitespace on each row of the grid.
+    pub fn set_trailing_whitespace(&mut self, trailing_whitespace: HashMap<u64, Range<u64>>) {
+        self.trailing_whitespace = trailing_whitespace;
+    }
+
+    /// The guides drawn over the indentation, when enabled for the buffer.
+    fn indent_guides(&self, settings: &RendererSettings) -> Option<IndentGuides> {
+        let indent = self.indent?;
+        let guides = indent.enabled.unwrap_or(settings.indent_guides);
+        let whitespace = indent.enabled != Some(false) && settings.indent_whitespace;
+        (guides || whitespace).then(|| IndentGuides {
+            indent,
+            guides,
+            whitespace,
+            color: parse_color(&settings.indent_guides_color),
+        })
+    }
+
+    /// Records the pictures of the lines that changed, and of all of them when the fonts, the
+    /// ligatures or the guides changed, so that every line of the snapshot is ready to be drawn.
+    /// `g:neovide_ligatures` is used unless the window overrides it.
+    pub fn prepare_lines(&mut self, grid_renderer: &mut GridRenderer, settings: &RendererSettings) {
+        tracy_zone!("prepare_lines");
+        let grid_width = self.grid_size.width;
+        let ligatures = self.ligatures.unwrap_or(settings.ligatures);
+        let indent_guides = self.indent_guides(settings);
+        // The fragments are shaped again with the ligatures changed
+        let reshape =
+            ligatures != self.prepared_ligatures || indent_guides != self.prepared_indent_guides;
+        self.prepared_ligatures = ligatures;
+        self.prepared_indent_guides = indent_guides;
+        let generation = grid_renderer.generation;
+
+        // The scrollback shares most of its lines with the grid, so the lines are only prepared
+        // once, and the replaced ones are kept alive until both are updated
+        let mut prepared: HashMap<*const Line, (Arc<Line>, Arc<Line>)> = HashMap::new();
+        let mut prepare =
+            |line: &mut Arc<Line>, trailing_whitespace: Option<TrailingWhitespace>| {
+                if let Some((_, new_line)) = prepared.get(&Arc::as_ptr(line)) {
+                    *line = new_line.clone();
+                    return;
+                }
+                if !reshape
+                    && line.prepared_generation == Some(generation)
+                    && line.trailing_whitespace == trailing_whitespace
+                {
+                    return;
+                }
+                let new_line = Arc::new(prepare_line(
+                    grid_renderer,
+                    line,
+                    grid_width,
+                    ligatures,
+                    indent_guides,
+                    trailing_whitespace,
+                    !reshape,
+                ));
+                let old_line = std::mem::replace(line, new_line.clone());
+                prepared.insert(Arc::as_ptr(&old_line), (old_line, new_line));
+            };
+
+        let whitespace_color = parse_color(&settings.indent_guides_color);
+        for row in 0..self.actual_lines.len() {
+            let Some(line) = &mut self.actual_lines[row as isize] else {
+                continue;
+            };
+            let trailing_whitespace = self
+                .trailing_whitespace
+                .get(&(row as u64))
+                .filter(|columns| {
+                    settings.trailing_whitespace && is_blank(&line.line_fragments, columns)
+                })
+                .map(|columns| TrailingWhitespace {
+                    columns: columns.clone(),
+                    color: whitespace_color,
+                });
+            prepare(line, trailing_whitespace);
+        }
+        // The lines that scrolled out of the grid keep their marks
+        for line in self.scrollback_lines.iter_mut().flatten() {
+            let trailing_whitespace = line.trailing_whitespace.clone();
+            prepare(line, trailing_whitespace);
+        }
+    }
+}
+
+/// Returns the cached pictures of `old_fragments` that can be used for `new_fragments`. Both are
+/// sorted by column, so the fragments are matched by walking through them in parallel.
+fn reuse_fragment_pictures<T: Clone>(
+    old_fragments: &[LineFragment],
+    old_pictures: &[Option<T>],
+    new_fragments: &[LineFragment],
+) -> Vec<Option<T>> {
+    let mut old = old_fragments.iter().zip(old_pictures).peekable();
+    new_fragments
+        .iter()
+        .map(|fragment| {
+            while old
+                .next_if(|(old_fragment, _)| old_fragment.window_left < fragment.window_left)
+                .is_some()
+            {}
+            old.next_if(|(old_fragment, _)| *old_fragment == fragment)
+                .and_then(|(_, picture)| picture.clone())
+        })
+        .collect()
+}
+
+/// Checks the accumulated scroll delta against the change of the top of the view, which is the
+/// absolute top line and the columns of it skipped by `smoothscroll`. The delta counts screen
+/// lines and skips over closed folds, so it differs from the top line change, but the direction
+/// has to agree. When the top line is unchanged, only a changed `skipcol` scrolls the view, by
+/// part of a wrapped line.
+fn reconcile_scroll_delta(
+    scroll_delta: isize,
+    previous_view_top: Option<(f64, u64)>,
+    view_top: Option<(f64, u64)>,
+) -> isize {
+    let (Some((previous_top_line, previous_skipcol)), Some((top_line, skipcol))) =
+        (previous_view_top, view_top)
+    else {
+        return scroll_delta;
+    };
+    let top_line_delta = (top_line - previous_top_line).round() as isize;
+    if top_line_delta == 0 {
+        if skipcol != previous_skipcol {
+            scroll_delta
+        } else {
+            0
+        }
+    } else if top_line_delta.signum() != scroll_delta.signum() {
+        top_line_delta
+    } else {
+        scroll_delta
+    }
+}
+
+/// Lines that occur more often, like closing braces, match at too many shifts to tell them apart.
+const MAX_REPEATED_LINES: usize = 8;
+
+/// Finds the shift of the lines that makes the most of the current lines match the previous ones,
+/// in the direction of the hint. Blank lines are ignored since they match anywhere, and the hint is
+/// kept unless another shift matches more lines. The previous lines are looked up by the hash of
+/// their text, so only the shifts of lines that match are counted, and lines repeated more than
+/// `MAX_REPEATED_LINES` times are ignored like the blank ones.
+fn matching_scroll_delta(
+    previous: &[Option<Arc<Line>>],
+    current: &[Option<Arc<Line>>],
+    hint: isize,
+) -> isize {
+    if hint == 0 {
+        return hint;
+    }
+    let is_blank = |line: &Line| line.line_fragments.iter().all(|f| f.text.trim().is_empty());
+    let text_hash = |line: &Line| {
+        let mut hasher = DefaultHasher::new();
+        for fragment in line.line_fragments.iter() {
+            fragment.text.hash(&mut hasher);
+        }
+        hasher.finish()
+    };
+
+    let mut previous_by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+    for (i, line) in previous.iter().enumerate() {
+        if let Some(line) = line {
+            if !is_blank(line) {
+                previous_by_hash.entry(text_hash(line)).or_default().push(i);
+            }
+        }
+    }
+
+    let mut matches: HashMap<isize, usize> = HashMap::new();
+    for (i, line) in current.iter().enumerate() {
+        let Some(line) = line else {
+            continue;
+        };
+        let Some(candidates) = previous_by_hash
+            .get(&text_hash(line))
+            .filter(|candidates| candidates.len() <= MAX_REPEATED_LINES)
+        else {
+            continue;
+        };
+        for &old_index in candidates {
+            let delta = old_index as isize - i as isize;
+            if delta.signum() != hint.signum() {
+                continue;
+            }
+            let old = previous[old_index].as_ref().unwrap();
+            if line.line_fragments == old.line_fragments {
+                *matches.entry(delta).or_default() += 1;
+            }
+        }
+    }
+
+    let hint_matches = matches.get(&hint).copied().unwrap_or(0);
+    matches
+        .into_iter()
+        .filter(|(_, count)| *count > hint_matches)
+        .max_by_key(|(delta, count)| (*count, -delta.abs()))
+        .map_or(hint, |(delta, _)| delta)
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    pub fn line(text: &str) -> Option<Arc<Line>> {
+        Some(Arc::new(Line {
+            line_fragments: Arc::new([LineFragment {
+                text: text.into(),
+                window_left: 0,
+                width: text.len() as u64,
+                style: None,
+            }]),
+            fragment_pictures: vec![None],
+            background_picture: None,
+            foreground_picture: None,
+            has_transparency: false,
+            is_inferred_border: false,
+            trailing_whitespace: None,
+            prepared_generation: None,
+        }))
+    }
+
+    fn line_text(window: &WindowState, index: isize) -> Option<String> {
+        window.scrollback_lines[index]
+            .as_ref()
+            .map(|line| line.line_fragments[0].text.to_string())
+    }
+
+    #[test]
+    fn test_resize_keeps_scrollback() {
+        let mut window = WindowState::new(1, Point::default(), (10, 2).into());
+        for i in -2..2 {
+            window.scrollback_lines[i] = line(&i.to_string());
+        }
+
+        window.grid_size = (10, 3).into();
+        window.resize_scrollback(3);
+        assert_eq!(window.scrollback_lines.len(), 6);
+        for i in -2..2 {
+            assert_eq!(line_text(&window, i), Some(i.to_string()));
+        }
+        assert_eq!(line_text(&window, -3), None);
+        assert_eq!(line_text(&window, 2), None);
+
+        window.grid_size = (10, 1).into();
+        window.resize_scrollback(1);
+        assert_eq!(line_text(&window, -1), Some("-1".to_string()));
+        assert_eq!(line_text(&window, 0), Some("0".to_string()));
+    }
+
+    #[test]
+    fn test_snapshot_shares_the_lines() {
+        let mut window = WindowState::new(1, Point::default(), (10, 2).into());
+        window.handle_window_draw_command(WindowDrawCommand::DrawLine {
+            row: 0,
+            line_fragments: vec![LineFragment {
+                text: "foo".into(),
+                window_left: 0,
+                width: 3,
+                style: None,
+            }],
+        });
+        let snapshot = window.snapshot();
+        let line = snapshot.actual_lines[0].as_ref().unwrap();
+        assert!(Arc::ptr_eq(line, window.actual_lines[0].as_ref().unwrap()));
+
+        // Changing the window afterwards doesn't change the snapshot
+        window.handle_window_draw_command(WindowDrawCommand::DrawLine {
+            row: 0,
+            line_fragments: Vec::new(),
+        });
+        window.handle_window_draw_command(WindowDrawCommand::Hide);
+        assert_eq!(line.line_fragments[0].text, "foo");
+        assert!(!snapshot.hidden);
+    }
+
+    #[test]
+    fn test_reuse_fragment_pictures() {
+        let fragment = |text: &str, window_left: u64| LineFragment {
+            text: text.into(),
+            window_left,
+            width: text.len() as u64,
+            style: None,
+        };
+        let old = vec![fragment("foo ", 0), fragment("bar", 4), fragment(" baz", 7)];
+        let pictures = vec![Some(1), None, Some(3)];
+
+        // Only the middle fragment changed
+        let new = vec![fragment("foo ", 0), fragment("BAR", 4), fragment(" baz", 7)];
+        assert_eq!(
+            reuse_fragment_pictures(&old, &pictures, &new),
+            vec![Some(1), None, Some(3)]
+        );
+
+        // The fragments are split differently, so only the first one can be reused
+        let new = vec![fragment("foo b", 0), fragment("ar baz", 5)];
+        assert_eq!(
+            reuse_fragment_pictures(&old, &pictures, &new),
+            vec![None, None]
+        );
+        let new = vec![fragment("foo ", 0), fragment("bar baz", 4)];
+        assert_eq!(
+            reuse_fragment_pictures(&old, &pictures, &new),
+            vec![Some(1), None]
+        );
+    }
+
+    #[test]
+    fn test_reconcile_scroll_delta() {
+        // Without a known top line the delta is used as is
+        assert_eq!(reconcile_scroll_delta(3, None, Some((10.0, 0))), 3);
+        // Wrapped lines make the delta larger than the top line change
+        assert_eq!(
+            reconcile_scroll_delta(5, Some((10.0, 0)), Some((13.0, 0))),
+            5
+        );
+        // A scroll followed by zz back to the same view
+        assert_eq!(
+            reconcile_scroll_delta(4, Some((10.0, 0)), Some((10.0, 0))),
+            0
+        );
+        // Missed events make the delta point in the wrong direction
+        assert_eq!(
+            reconcile_scroll_delta(-2, Some((10.0, 0)), Some((12.0, 0))),
+            2
+        );
+        assert_eq!(
+            reconcile_scroll_delta(0, Some((12.0, 0)), Some((10.0, 0))),
+            -2
+        );
+    }
+
+    #[test]
+    fn test_reconcile_smoothscroll_delta() {
+        // Scrolling through a wrapped line only changes skipcol
+        assert_eq!(
+            reconcile_scroll_delta(1, Some((10.0, 0)), Some((10.0, 80))),
+            1
+        );
+        assert_eq!(
+            reconcile_scroll_delta(-2, Some((10.0, 160)), Some((10.0, 0))),
+            -2
+        );
+        // Scrolling past the end of the wrapped line moves the top line and resets skipcol
+        assert_eq!(
+            reconcile_scroll_delta(1, Some((10.0, 160)), Some((11.0, 0))),
+            1
+        );
+    }
+
+    #[test]
+    fn test_smoothscroll_keeps_partial_line_scroll() {
+        let settings = RendererSettings::default();
+        let viewport = |top_line, scroll_delta| WindowDrawCommand::Viewport {
+            top_line,
+            scroll_delta,
+        };
+        let mut window = WindowState::new(1, Point::default(), (10, 2).into());
+        window.handle_window_draw_command(viewport(10.0, 0.0));
+        window.flush(&settings);
+
+        // <C-e> with smoothscroll scrolls one screen line into the wrapped top line
+        window.set_skipcol(10);
+        window.handle_window_draw_command(viewport(10.0, 1.0));
+        window.flush(&settings);
+        assert_eq!(window.scroll_shift.moved, -1);
+    }
+
+    #[test]
+    fn test_clear_keeps_scroll_position() {
+        let settings = RendererSettings::default();
+        let viewport = |top_line, scroll_delta| WindowDrawCommand::Viewport {
+            top_line,
+            scroll_delta,
+        };
+        let mut window = WindowState::new(1, Point::default(), (10, 2).into());
+        window.handle_window_draw_command(viewport(10.0, 0.0));
+        window.flush(&settings);
+        window.scrollback_lines[-1] = line("above");
+
+        // The content is refreshed, and the view stays at the same line
+        window.handle_window_draw_command(WindowDrawCommand::Clear);
+        window.handle_window_draw_command(viewport(10.0, 3.0));
+        window.flush(&settings);
+        assert_eq!(line_text(&window, -1), Some("above".to_string()));
+        assert_eq!(window.scroll_shift, ScrollShift::default());
+
+        // The view jumps to another line
+        window.handle_window_draw_command(WindowDrawCommand::Clear);
+        window.handle_window_draw_command(viewport(50.0, 40.0));
+        window.flush(&settings);
+        assert_eq!(line_text(&window, -1), None);
+        assert_eq!(window.scroll_shift.sets, 1);
+        assert_eq!(window.scroll_shift.position, 0.0);
+        assert!(window.scroll_shift.stopped);
+    }
+
+    #[test]
+    fn test_horizontal_scroll_shift() {
+        let mut window = WindowState::new(1, Point::default(), (20, 4).into());
+        window.scroll_columns(3, 4);
+        window.scroll_columns(2, 4);
+        window.flush_horizontal_scroll();
+        assert_eq!(window.horizontal_scroll_shift.moved, -5);
+        assert_eq!(window.scroll_text_left, 4);
+
+        window.scroll_columns(-40, 4);
+        window.flush_horizontal_scroll();
+        assert_eq!(window.horizontal_scroll_shift.moved, 35);
+    }
+
+    #[test]
+    fn test_matching_scroll_delta() {
+        let lines = |texts: &[&str]| texts.iter().map(|text| line(text)).collect::<Vec<_>>();
+        let previous = lines(&["a", "b1", "b2", "b3", "c", "d"]);
+
+        // One buffer line down, but it wrapped over three screen lines
+        let current = lines(&["c", "d", "e", "f", "g1", "g2"]);
+        assert_eq!(matching_scroll_delta(&previous, &current, 2), 4);
+        assert_eq!(matching_scroll_delta(&previous, &current, 1), 4);
+
+        // Scrolling up into the wrapped line
+        let previous = lines(&["c", "d", "e", "f", "g", "h"]);
+        let current = lines(&["b1", "b2", "b3", "c", "d", "e"]);
+        assert_eq!(matching_scroll_delta(&previous, &current, -1), -3);
+
+        // Nothing matches, or only blank lines, so the hint is kept
+        let current = lines(&["x", "", "", "", "", "y"]);
+        assert_eq!(matching_scroll_delta(&previous, &current, 1), 1);
+
+        // Lines repeated too often don't count
+        let previous = lines(&["}"; 12]);
+        let current = lines(&["}"; 12]);
+        assert_eq!(matching_scroll_delta(&previous, &current, 1), 1);
+    }
+}
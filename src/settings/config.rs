@@ -1,16 +1,23 @@
 //! Config file handling
 
-use std::{env, fs, sync::mpsc};
+use std::{collections::HashMap, env, fs, sync::mpsc};
 
 use notify::Watcher;
+use parking_lot::Mutex;
+use rmpv::Value;
 use serde::Deserialize;
 use winit::event_loop::EventLoopProxy;
 
-use crate::{error_msg, frame::Frame, window::UserEvent};
+use crate::{
+    bridge::{send_ui, ParallelCommand},
+    error_msg,
+    frame::Frame,
+    window::UserEvent,
+};
 
 use std::path::{Path, PathBuf};
 
-use super::font::FontSettings;
+use super::{font::FontSettings, SETTINGS};
 
 const CONFIG_FILE: &str = "config.toml";
 
@@ -37,6 +44,7 @@ pub fn config_path() -> PathBuf {
 #[serde(rename_all = "kebab-case")]
 pub struct Config {
     pub wsl: Option<bool>,
+    pub wsl_distro: Option<String>,
     pub no_multigrid: Option<bool>,
     pub maximized: Option<bool>,
     pub vsync: Option<bool>,
@@ -49,23 +57,121 @@ pub struct Config {
     pub font: Option<FontSettings>,
     pub title_hidden: Option<bool>,
     pub tabs: Option<bool>,
+    pub wayland_app_id: Option<String>,
+    pub x11_wm_class: Option<String>,
+    pub x11_wm_class_instance: Option<String>,
+    #[cfg(target_os = "windows")]
+    pub opengl: Option<bool>,
+    /// The `neovide_` global variables, without the prefix.
+    pub settings: Option<toml::Table>,
+    pub profile: Option<HashMap<String, Profile>>,
+    /// The `font-size` of the active profile when there's no `[font]` table, which is applied to
+    /// `guifont` instead.
+    #[serde(skip)]
+    pub font_size: Option<f32>,
+}
+
+/// A named set of overrides, selected with `--profile` or `:NeovideProfile`.
+#[derive(Debug, Deserialize, Default, Clone)]
+#[serde(rename_all = "kebab-case")]
+pub struct Profile {
+    pub vsync: Option<bool>,
+    pub font: Option<FontSettings>,
+    /// Overrides the size of `font`, of the `[font]` table, or of `guifont` when neither is set.
+    pub font_size: Option<f32>,
+    pub settings: Option<toml::Table>,
+}
+
+#[derive(Default)]
+struct ProfileState {
+    active: Option<String>,
+    /// The values the settings had before they were overridden by the active profile.
+    saved_values: HashMap<String, Value>,
+}
+
+lazy_static! {
+    static ref PROFILE_STATE: Mutex<ProfileState> = Mutex::new(ProfileState::default());
 }
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum HotReloadConfigs {
-    Font(Option<FontSettings>),
+    Font(Option<Box<FontSettings>>),
+    /// Overrides the size of `guifont`, used when there's no `[font]` table.
+    FontSize(Option<f32>),
+    VSync(bool),
 }
 
 impl Config {
-    /// Loads config from `config_path()` and writes it to env variables.
-    pub fn init() -> Config {
+    /// Loads config from `config_path()`, selects the profile and writes the result to env
+    /// variables.
+    pub fn init(profile: Option<String>) -> Config {
         let config = Config::load_from_path(&config_path());
-        match &config {
-            Ok(config) => config.write_to_env(),
-            Err(Some(err)) => eprintln!("{err}"),
-            Err(None) => {}
+        if let Err(Some(err)) = &config {
+            eprintln!("{err}");
+        }
+        let config = config.unwrap_or_default();
+        if let Some(profile) = profile {
+            if config.profile(&profile).is_some() {
+                PROFILE_STATE.lock().active = Some(profile);
+            } else {
+                eprintln!("Unknown profile {profile}, continuing without it.");
+            }
+        }
+        config.active().write_to_env();
+        config
+    }
+
+    fn profile(&self, name: &str) -> Option<&Profile> {
+        self.profile.as_ref()?.get(name)
+    }
+
+    /// Returns the config with the overrides of the active profile applied.
+    pub fn active(&self) -> Config {
+        let active = PROFILE_STATE.lock().active.clone();
+        self.with_profile(active.as_deref())
+    }
+
+    fn with_profile(&self, name: Option<&str>) -> Config {
+        let mut config = self.clone();
+        let Some(profile) = name.and_then(|name| self.profile(name)) else {
+            return config;
         };
-        config.unwrap_or_default()
+        config.vsync = profile.vsync.or(config.vsync);
+        if profile.font.is_some() {
+            config.font.clone_from(&profile.font);
+        }
+        match &mut config.font {
+            Some(font) => font.size = profile.font_size.unwrap_or(font.size),
+            None => config.font_size = profile.font_size,
+        }
+        if let Some(settings) = &profile.settings {
+            let mut merged = config.settings.take().unwrap_or_default();
+            merged.extend(settings.clone());
+            config.settings = Some(merged);
+        }
+        config
+    }
+
+    /// Applies the `[settings]` table, the settings of the active profile and its `guifont` size.
+    /// Must be called after the setting groups are registered, but before nvim is started, so
+    /// that the values set in the nvim config take precedence.
+    pub fn apply_settings(&self, event_loop_proxy: &EventLoopProxy<UserEvent>) {
+        if let Some(size) = self.active().font_size {
+            let _ = event_loop_proxy.send_event(HotReloadConfigs::FontSize(Some(size)).into());
+        }
+        for (name, value) in validated_values(self.settings.as_ref()) {
+            SETTINGS.set_global_value(&name, value);
+        }
+        let mut state = PROFILE_STATE.lock();
+        let Some(profile) = state.active.as_deref().and_then(|name| self.profile(name)) else {
+            return;
+        };
+        for (name, value) in validated_values(profile.settings.as_ref()) {
+            if let Some(current) = SETTINGS.read_global_value(&name) {
+                state.saved_values.insert(name.clone(), current);
+            }
+            SETTINGS.set_global_value(&name, value);
+        }
     }
 
     pub fn watch_config_file(init_config: Config, event_loop_proxy: EventLoopProxy<UserEvent>) {
@@ -76,6 +182,9 @@ impl Config {
         if let Some(wsl) = self.wsl {
             env::set_var("NEOVIDE_WSL", wsl.to_string());
         }
+        if let Some(wsl_distro) = &self.wsl_distro {
+            env::set_var("NEOVIDE_WSL_DISTRO", wsl_distro);
+        }
         if let Some(no_multigrid) = self.no_multigrid {
             env::set_var("NEOVIDE_NO_MULTIGRID", no_multigrid.to_string());
         }
@@ -109,10 +218,23 @@ impl Config {
         if let Some(tabs) = &self.tabs {
             env::set_var("NEOVIDE_TABS", tabs.to_string());
         }
+        if let Some(wayland_app_id) = &self.wayland_app_id {
+            env::set_var("NEOVIDE_APP_ID", wayland_app_id);
+        }
+        if let Some(x11_wm_class) = &self.x11_wm_class {
+            env::set_var("NEOVIDE_WM_CLASS", x11_wm_class);
+        }
+        if let Some(x11_wm_class_instance) = &self.x11_wm_class_instance {
+            env::set_var("NEOVIDE_WM_CLASS_INSTANCE", x11_wm_class_instance);
+        }
+        #[cfg(target_os = "windows")]
+        if let Some(opengl) = self.opengl {
+            env::set_var("NEOVIDE_OPENGL", opengl.to_string());
+        }
     }
 
     // TODO: should maybe return well-typed error?
-    fn load_from_path(path: &Path) -> Result<Self, Option<String>> {
+    pub fn load_from_path(path: &Path) -> Result<Self, Option<String>> {
         if !path.exists() {
             return Err(None);
         }
@@ -134,6 +256,159 @@ impl Config {
     }
 }
 
+fn toml_to_value(value: &toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::from(value.as_str()),
+        toml::Value::Integer(value) => Value::from(*value),
+        toml::Value::Float(value) => Value::from(*value),
+        toml::Value::Boolean(value) => Value::from(*value),
+        toml::Value::Datetime(value) => Value::from(value.to_string()),
+        toml::Value::Array(values) => Value::Array(values.iter().map(toml_to_value).collect()),
+        toml::Value::Table(table) => Value::Map(
+            table
+                .iter()
+                .map(|(key, value)| (Value::from(key.as_str()), toml_to_value(value)))
+                .collect(),
+        ),
+    }
+}
+
+/// Returns the values of a `[settings]` table, with the names normalized to the variable names.
+fn table_values(table: Option<&toml::Table>) -> Vec<(String, Value)> {
+    table
+        .into_iter()
+        .flatten()
+        .map(|(name, value)| (name.replace('-', "_"), toml_to_value(value)))
+        .collect()
+}
+
+/// Returns the valid values of a `[settings]` table, the invalid ones are reported to the user.
+fn validated_values(table: Option<&toml::Table>) -> Vec<(String, Value)> {
+    table_values(table)
+        .into_iter()
+        .filter(
+            |(name, value)| match SETTINGS.validate_global_value(name, value) {
+                Ok(()) => true,
+                Err(err) => {
+                    error_msg!("Config file {}: {err}", config_path().display());
+                    false
+                }
+            },
+        )
+        .collect()
+}
+
+/// Settings are written to the nvim globals, which notifies Neovide through the normal setting
+/// change mechanism, and keeps both sides in sync.
+fn set_global_variable(name: String, value: Value) {
+    send_ui(ParallelCommand::SetVariable {
+        name: format!("neovide_{name}"),
+        value,
+    });
+}
+
+/// Sends the font and the vsync mode when they differ between the two configs.
+fn send_hot_reload_changes(
+    previous: &Config,
+    config: &Config,
+    event_loop_proxy: &EventLoopProxy<UserEvent>,
+) {
+    let mut changes = Vec::new();
+    if config.font != previous.font {
+        changes.push(HotReloadConfigs::Font(config.font.clone().map(Box::new)));
+    }
+    if config.font_size != previous.font_size {
+        changes.push(HotReloadConfigs::FontSize(config.font_size));
+    }
+    let vsync = config.vsync.unwrap_or(true);
+    if vsync != previous.vsync.unwrap_or(true) {
+        changes.push(HotReloadConfigs::VSync(vsync));
+    }
+    for change in changes {
+        let _ = event_loop_proxy.send_event(change.into());
+    }
+}
+
+/// Sends the font, vsync and settings that differ between the two configs. Settings removed from
+/// the config keep their value.
+fn send_changes(previous: &Config, config: &Config, event_loop_proxy: &EventLoopProxy<UserEvent>) {
+    send_hot_reload_changes(previous, config, event_loop_proxy);
+
+    let previous_values: HashMap<_, _> = table_values(previous.settings.as_ref())
+        .into_iter()
+        .collect();
+    for (name, value) in validated_values(config.settings.as_ref()) {
+        if previous_values.get(&name) != Some(&value) {
+            set_global_variable(name, value);
+        }
+    }
+}
+
+/// Returns the names of the profiles in the config file, sorted.
+pub fn profile_names() -> Vec<String> {
+    let config = Config::load_from_path(&config_path()).unwrap_or_default();
+    let mut names = config
+        .profile
+        .into_iter()
+        .flat_map(|profiles| profiles.into_keys())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// Switches to the profile `name`, or back to the plain config when `None`. The settings changed
+/// by the previous profile, that the new one doesn't override, are restored.
+pub fn set_profile(
+    name: Option<&str>,
+    event_loop_proxy: &EventLoopProxy<UserEvent>,
+) -> Result<(), String> {
+    let config = match Config::load_from_path(&config_path()) {
+        Ok(config) => config,
+        Err(None) => Config::default(),
+        Err(Some(err)) => return Err(err),
+    };
+    let profile = match name {
+        Some(name) => Some(
+            config
+                .profile(name)
+                .ok_or_else(|| format!("Unknown profile {name}"))?,
+        ),
+        None => None,
+    };
+    let previous = config.active();
+
+    let profile_values = validated_values(profile.and_then(|profile| profile.settings.as_ref()));
+    let mut changes = HashMap::new();
+    let mut state = PROFILE_STATE.lock();
+    let restored = state
+        .saved_values
+        .keys()
+        .filter(|saved| !profile_values.iter().any(|(name, _)| name == *saved))
+        .cloned()
+        .collect::<Vec<_>>();
+    for name in restored {
+        if let Some(value) = state.saved_values.remove(&name) {
+            changes.insert(name, value);
+        }
+    }
+    for (name, value) in profile_values {
+        if !state.saved_values.contains_key(&name) {
+            if let Some(current) = SETTINGS.read_global_value(&name) {
+                state.saved_values.insert(name.clone(), current);
+            }
+        }
+        changes.insert(name, value);
+    }
+    state.active = name.map(str::to_string);
+    drop(state);
+
+    send_hot_reload_changes(&previous, &config.active(), event_loop_proxy);
+    for (name, value) in changes {
+        set_global_variable(name, value);
+    }
+    Ok(())
+}
+
 fn watcher_thread(init_config: Config, event_loop_proxy: EventLoopProxy<UserEvent>) {
     let (tx, rx) = mpsc::channel();
     let mut watcher =
@@ -173,14 +448,94 @@ fn watcher_thread(init_config: Config, event_loop_proxy: EventLoopProxy<UserEven
             }
         };
 
-        // notify if font changed
-        if config.font != previous_config.font {
-            event_loop_proxy
-                .send_event(UserEvent::ConfigsChanged(Box::new(HotReloadConfigs::Font(
-                    config.font.clone(),
-                ))))
-                .unwrap();
-        }
+        send_changes(
+            &previous_config.active(),
+            &config.active(),
+            &event_loop_proxy,
+        );
         previous_config = config;
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_setting_values() {
+        let config: Config = toml::from_str(
+            r#"
+            [settings]
+            transparency = 0.8
+            cursor-vfx-mode = "railgun"
+            padding_top = 5
+            "#,
+        )
+        .unwrap();
+        let mut values = table_values(config.settings.as_ref());
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            values,
+            vec![
+                ("cursor_vfx_mode".to_string(), Value::from("railgun")),
+                ("padding_top".to_string(), Value::from(5)),
+                ("transparency".to_string(), Value::from(0.8)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_with_profile() {
+        let config: Config = toml::from_str(
+            r#"
+            vsync = true
+            [font]
+            normal = "Fira Code"
+            size = 14
+
+            [settings]
+            transparency = 1.0
+            padding_top = 5
+
+            [profile.presentation]
+            vsync = false
+            font-size = 24
+            settings = { transparency = 0.8 }
+            "#,
+        )
+        .unwrap();
+
+        let presentation = config.with_profile(Some("presentation"));
+        assert_eq!(presentation.vsync, Some(false));
+        assert_eq!(presentation.font.unwrap().size, 24.0);
+        let mut values = table_values(presentation.settings.as_ref());
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        assert_eq!(
+            values,
+            vec![
+                ("padding_top".to_string(), Value::from(5)),
+                ("transparency".to_string(), Value::from(0.8)),
+            ]
+        );
+
+        let unknown = config.with_profile(Some("unknown"));
+        assert_eq!(unknown.vsync, Some(true));
+        assert_eq!(unknown.font.unwrap().size, 14.0);
+    }
+
+    #[test]
+    fn test_profile_font_size_without_font() {
+        let config: Config = toml::from_str(
+            r#"
+            [profile.presentation]
+            font-size = 24
+            "#,
+        )
+        .unwrap();
+
+        let presentation = config.with_profile(Some("presentation"));
+        assert!(presentation.font.is_none());
+        assert_eq!(presentation.font_size, Some(24.0));
+        assert_eq!(config.with_profile(None).font_size, None);
+    }
+}
@@ -3,7 +3,7 @@ mod from_value;
 mod window_size;
 
 use anyhow::{Context, Result};
-use log::trace;
+use log::{debug, trace};
 use nvim_rs::Neovim;
 use parking_lot::RwLock;
 use rmpv::Value;
@@ -12,18 +12,20 @@ use std::{
     collections::HashMap,
     convert::TryInto,
     fmt::Debug,
+    time::Duration,
 };
 use winit::event_loop::EventLoopProxy;
 
 use crate::{bridge::NeovimWriter, window::UserEvent};
 pub use from_value::ParseFromValue;
 pub use window_size::{
-    load_last_window_settings, save_window_size, PersistentWindowSettings, DEFAULT_GRID_SIZE,
+    load_last_window_settings, load_splash_color, neovide_std_datapath, restore_scale_factor,
+    save_window_size, windowed_settings, PersistentWindowSettings, DEFAULT_GRID_SIZE,
     MAX_GRID_SIZE, MIN_GRID_SIZE,
 };
 
 mod config;
-pub use config::{Config, HotReloadConfigs};
+pub use config::{config_path, profile_names, set_profile, Config, HotReloadConfigs};
 pub use font::FontSettings;
 
 lazy_static! {
@@ -38,6 +40,10 @@ pub trait SettingGroup {
 // Function types to handle settings updates
 type UpdateHandlerFunc = fn(&Settings, Value) -> SettingsChanged;
 type ReaderHandlerFunc = fn(&Settings) -> Option<Value>;
+type ValidatorFunc = fn(&Settings, &Value) -> Result<(), String>;
+
+/// How long `snapshot` waits for the settings, which can be locked when it's called after a panic.
+const SNAPSHOT_LOCK_TIMEOUT: Duration = Duration::from_millis(100);
 
 // The Settings struct acts as a global container where each of Neovide's subsystems can store
 // their own settings. It will also coordinate updates between Neovide and nvim to make sure the
@@ -50,6 +56,7 @@ pub struct Settings {
     settings: RwLock<HashMap<TypeId, Box<dyn Any + Send + Sync>>>,
     updaters: RwLock<HashMap<SettingLocation, UpdateHandlerFunc>>,
     readers: RwLock<HashMap<SettingLocation, ReaderHandlerFunc>>,
+    validators: RwLock<HashMap<SettingLocation, ValidatorFunc>>,
 }
 
 #[derive(Debug, Clone, Hash, PartialEq, Eq)]
@@ -66,6 +73,7 @@ impl Settings {
             settings: RwLock::new(HashMap::new()),
             updaters: RwLock::new(HashMap::new()),
             readers: RwLock::new(HashMap::new()),
+            validators: RwLock::new(HashMap::new()),
         }
     }
 
@@ -74,6 +82,7 @@ impl Settings {
         setting_location: SettingLocation,
         update_func: UpdateHandlerFunc,
         reader_func: ReaderHandlerFunc,
+        validator_func: ValidatorFunc,
     ) {
         self.updaters
             .write()
@@ -82,6 +91,10 @@ impl Settings {
         self.readers
             .write()
             .insert(setting_location.clone(), reader_func);
+
+        self.validators
+            .write()
+            .insert(setting_location, validator_func);
     }
 
     pub fn set<T: Clone + Send + Sync + 'static>(&self, t: &T) {
@@ -106,6 +119,57 @@ impl Settings {
         self.updaters.read().keys().cloned().collect()
     }
 
+    /// Returns the current values of all registered settings, sorted by name, with the `g:` or
+    /// `&` prefix. Nothing is returned if the settings stay locked, like when a panic happened
+    /// while changing them.
+    pub fn snapshot(&self) -> Vec<(String, Value)> {
+        let Some(readers) = self.readers.try_read_for(SNAPSHOT_LOCK_TIMEOUT) else {
+            return Vec::new();
+        };
+        if self.settings.try_read_for(SNAPSHOT_LOCK_TIMEOUT).is_none() {
+            return Vec::new();
+        }
+        let mut values = readers
+            .iter()
+            .filter_map(|(location, reader)| {
+                let name = match location {
+                    SettingLocation::NeovideGlobal(name) => format!("g:neovide_{name}"),
+                    SettingLocation::NeovimOption(name) => format!("&{name}"),
+                };
+                reader(self).map(|value| (name, value))
+            })
+            .collect::<Vec<_>>();
+        values.sort_by(|(a, _), (b, _)| a.cmp(b));
+        values
+    }
+
+    /// Checks that `name` is a Neovide setting and that `value` is valid for it, including the
+    /// enum values and the allowed range. Used for validating the settings from the config file
+    /// and the settings API.
+    pub fn validate_global_value(&self, name: &str, value: &Value) -> Result<(), String> {
+        let location = SettingLocation::NeovideGlobal(name.to_string());
+        let Some(validator) = self.validators.read().get(&location).copied() else {
+            return Err(format!("Unknown setting {name}"));
+        };
+        validator(self, value).map_err(|error| format!("Invalid value for {name}: {error}"))
+    }
+
+    /// Returns the current value of a `neovide_` global.
+    pub fn read_global_value(&self, name: &str) -> Option<Value> {
+        let location = SettingLocation::NeovideGlobal(name.to_string());
+        let reader = self.readers.read().get(&location).copied()?;
+        reader(self)
+    }
+
+    /// Updates a `neovide_` global without going through nvim. This is only done for the config
+    /// file values before nvim is started, `read_initial_values` then synchronizes them.
+    pub fn set_global_value(&self, name: &str, value: Value) {
+        let location = SettingLocation::NeovideGlobal(name.to_string());
+        if let Some(updater) = self.updaters.read().get(&location) {
+            updater(self, value);
+        }
+    }
+
     pub async fn read_initial_values(&self, nvim: &Neovim<NeovimWriter>) -> Result<()> {
         let keys: Vec<SettingLocation> = self.updaters.read().keys().cloned().collect();
 
@@ -151,6 +215,7 @@ impl Settings {
 
         let name: Result<String, _> = name.try_into();
         let name = name.unwrap();
+        debug!("Setting g:neovide_{name} changed to {value}");
 
         let event = self
             .updaters
@@ -170,6 +235,7 @@ impl Settings {
 
         let name: Result<String, _> = name.try_into();
         let name = name.unwrap();
+        debug!("Option {name} changed to {value}");
 
         let event = self
             .updaters
@@ -185,6 +251,42 @@ impl Settings {
     }
 }
 
+/// Returns the value as a number, booleans are interchangeable with numbers, since vimscript uses
+/// numbers for booleans.
+pub fn number_value(value: &Value) -> Option<f64> {
+    match value {
+        Value::Boolean(value) => Some(*value as u8 as f64),
+        Value::Integer(value) => value.as_f64(),
+        Value::F32(value) => Some(*value as f64),
+        Value::F64(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// Checks that parsing `value` into a setting gave the same value back. Invalid values, like
+/// unknown enum names, strings for numbers or numbers out of the range of the type, are left
+/// unparsed or are truncated. Lists and maps are parsed by the settings themselves, so only their
+/// type is checked.
+pub fn check_parsed_value(value: &Value, parsed: &Value) -> Result<(), String> {
+    let matches = match (value, parsed) {
+        (_, Value::Nil) => true,
+        (Value::Array(_), Value::Array(_)) | (Value::Map(_), Value::Map(_)) => true,
+        (Value::String(value), Value::String(parsed)) => value == parsed,
+        (value, parsed) => match (number_value(value), number_value(parsed)) {
+            // Floating point settings are stored as f32
+            (Some(value), Some(parsed)) => {
+                (value - parsed).abs() <= value.abs().max(1.0) * f32::EPSILON as f64
+            }
+            _ => false,
+        },
+    };
+    if matches {
+        Ok(())
+    } else {
+        Err(format!("expected a value like {parsed}, got {value}"))
+    }
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum SettingsChanged {
     Window(crate::window::WindowSettingsChanged),
@@ -202,6 +304,9 @@ mod tests {
         foo: String,
         bar: String,
         baz: String,
+        #[range = "0.0..=1.0"]
+        qux: f32,
+        quux: u32,
         #[option = "mousemoveevent"]
         mousemoveevent_option: Option<bool>,
     }
@@ -212,6 +317,8 @@ mod tests {
                 foo: "foo".to_string(),
                 bar: "bar".to_string(),
                 baz: "baz".to_string(),
+                qux: 0.5,
+                quux: 1,
                 mousemoveevent_option: None,
             }
         }
@@ -258,13 +365,71 @@ mod tests {
         fn noop_read(_settings: &Settings) -> Option<Value> {
             None
         }
+        fn noop_validate(_settings: &Settings, _value: &Value) -> Result<(), String> {
+            Ok(())
+        }
 
-        settings.set_setting_handlers(location.clone(), noop_update, noop_read);
+        settings.set_setting_handlers(location.clone(), noop_update, noop_read, noop_validate);
         let listeners = settings.updaters.read();
         let listener = listeners.get(&location).unwrap();
         assert_eq!(&(noop_update as UpdateHandlerFunc), listener);
     }
 
+    #[test]
+    fn test_validate_global_value() {
+        let settings = Settings::new();
+        settings.register::<TestSettings>();
+
+        assert!(settings
+            .validate_global_value("foo", &Value::from("value"))
+            .is_ok());
+        assert!(settings
+            .validate_global_value("foo", &Value::from(1))
+            .is_err());
+        assert!(settings
+            .validate_global_value("unknown", &Value::from("value"))
+            .is_err());
+
+        assert!(settings
+            .validate_global_value("qux", &Value::from(1))
+            .is_ok());
+        assert!(settings
+            .validate_global_value("qux", &Value::from(0.1))
+            .is_ok());
+        assert!(settings
+            .validate_global_value("qux", &Value::from(1.5))
+            .is_err());
+        assert!(settings
+            .validate_global_value("quux", &Value::from(2))
+            .is_ok());
+        assert!(settings
+            .validate_global_value("quux", &Value::from(-1))
+            .is_err());
+        assert!(settings
+            .validate_global_value("quux", &Value::from(u64::MAX))
+            .is_err());
+        assert!(settings
+            .validate_global_value("quux", &Value::from(1.5))
+            .is_err());
+    }
+
+    #[test]
+    fn test_snapshot() {
+        let settings = Settings::new();
+        settings.register::<TestSettings>();
+
+        assert_eq!(
+            settings.snapshot(),
+            vec![
+                ("g:neovide_bar".to_string(), Value::from("bar")),
+                ("g:neovide_baz".to_string(), Value::from("baz")),
+                ("g:neovide_foo".to_string(), Value::from("foo")),
+                ("g:neovide_quux".to_string(), Value::from(1)),
+                ("g:neovide_qux".to_string(), Value::from(0.5f32)),
+            ]
+        );
+    }
+
     #[test]
     fn test_set() {
         let settings = Settings::new();
@@ -1,7 +1,10 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
-use winit::dpi::{PhysicalPosition, PhysicalSize};
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    monitor::MonitorHandle,
+};
 
 use crate::{
     dimensions::Dimensions, settings::SETTINGS, window::WindowSettings, window::WinitWindowWrapper,
@@ -24,7 +27,7 @@ pub const MAX_GRID_SIZE: Dimensions = Dimensions {
     height: 1000,
 };
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
 pub enum PersistentWindowSettings {
     Maximized,
     Windowed {
@@ -34,15 +37,86 @@ pub enum PersistentWindowSettings {
         pixel_size: Option<PhysicalSize<u32>>,
         #[serde(default)]
         grid_size: Option<Dimensions>,
+        /// The scale factor the pixel size was saved with.
+        #[serde(default)]
+        scale_factor: Option<f64>,
+    },
+    Fullscreen {
+        monitor: PersistentMonitor,
+        /// What the window was before it entered fullscreen, used when it leaves fullscreen.
+        #[serde(default)]
+        windowed: Option<Box<PersistentWindowSettings>>,
     },
 }
 
+impl PersistentWindowSettings {
+    /// The settings of the window outside of fullscreen.
+    pub fn windowed(&self) -> Option<&PersistentWindowSettings> {
+        match self {
+            PersistentWindowSettings::Fullscreen { windowed, .. } => windowed.as_deref(),
+            settings => Some(settings),
+        }
+    }
+}
+
+/// Identifies the monitor a fullscreen window was on. The name alone isn't enough when there are
+/// several monitors of the same model, and the position changes when the monitors are rearranged.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct PersistentMonitor {
+    #[serde(default)]
+    pub name: Option<String>,
+    pub position: PhysicalPosition<i32>,
+    pub size: PhysicalSize<u32>,
+}
+
+impl From<&MonitorHandle> for PersistentMonitor {
+    fn from(monitor: &MonitorHandle) -> Self {
+        Self {
+            name: monitor.name(),
+            position: monitor.position(),
+            size: monitor.size(),
+        }
+    }
+}
+
+impl PersistentMonitor {
+    /// Finds the monitor among the connected ones, either exactly or by a name no other monitor
+    /// has. None when it's no longer connected.
+    pub fn find(&self, monitors: impl Iterator<Item = MonitorHandle>) -> Option<MonitorHandle> {
+        let monitors: Vec<_> = monitors.collect();
+        let candidates: Vec<_> = monitors.iter().map(PersistentMonitor::from).collect();
+        self.find_index(&candidates)
+            .map(|index| monitors[index].clone())
+    }
+
+    fn find_index(&self, candidates: &[PersistentMonitor]) -> Option<usize> {
+        if let Some(index) = candidates.iter().position(|candidate| candidate == self) {
+            return Some(index);
+        }
+        self.name.as_ref()?;
+        let mut same_name = candidates
+            .iter()
+            .enumerate()
+            .filter(|(_, candidate)| candidate.name == self.name);
+        match (same_name.next(), same_name.next()) {
+            (Some((index, _)), None) => Some(index),
+            _ => None,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct PersistentSettings {
     window: PersistentWindowSettings,
+    /// The default background of the last session as ARGB, shown while Neovim starts.
+    #[serde(default)]
+    splash_color: Option<u32>,
+    /// `g:neovide_scale_factor` at the end of the last session, when the font was zoomed.
+    #[serde(default)]
+    scale_factor: Option<f32>,
 }
 
-fn neovide_std_datapath() -> PathBuf {
+pub fn neovide_std_datapath() -> PathBuf {
     dirs::data_local_dir().unwrap().join("neovide")
 }
 
@@ -58,6 +132,26 @@ fn load_settings() -> Result<PersistentSettings, String> {
     serde_json::from_str(&json).map_err(|e| e.to_string())
 }
 
+/// The color of the splash shown while Neovim starts, the background of the last session.
+pub fn load_splash_color() -> Option<u32> {
+    load_settings().ok()?.splash_color
+}
+
+/// Restores the zoom of the last session, before the window and the grid size are calculated. The
+/// value is sent to Neovim with the rest of the settings, unless the config sets it.
+pub fn restore_scale_factor() {
+    let Some(scale_factor) = load_settings()
+        .ok()
+        .and_then(|settings| settings.scale_factor)
+    else {
+        return;
+    };
+    log::info!("Restoring the scale factor {scale_factor}");
+    let mut window_settings = SETTINGS.get::<WindowSettings>();
+    window_settings.scale_factor = scale_factor;
+    SETTINGS.set(&window_settings);
+}
+
 pub fn load_last_window_settings() -> Result<PersistentWindowSettings, String> {
     let settings = load_settings()?;
     let loaded_settings = settings.window;
@@ -66,6 +160,37 @@ pub fn load_last_window_settings() -> Result<PersistentWindowSettings, String> {
     Ok(loaded_settings)
 }
 
+/// The settings saved for the window when it's not fullscreen.
+pub fn windowed_settings(window_wrapper: &WinitWindowWrapper) -> PersistentWindowSettings {
+    let window = window_wrapper.skia_renderer.window();
+    let maximized = window.is_maximized();
+    let pixel_size = window.inner_size();
+    let grid_size = window_wrapper.get_grid_size();
+    let position = window.outer_position().ok();
+    let window_settings = SETTINGS.get::<WindowSettings>();
+
+    if maximized && window_settings.remember_window_size {
+        PersistentWindowSettings::Maximized
+    } else {
+        PersistentWindowSettings::Windowed {
+            pixel_size: { window_settings.remember_window_size.then_some(pixel_size) },
+            grid_size: { window_settings.remember_window_size.then_some(grid_size) },
+            scale_factor: {
+                window_settings
+                    .remember_window_size
+                    .then_some(window.scale_factor())
+            },
+            position: {
+                window_settings
+                    .remember_window_position
+                    .then_some(position)
+                    .flatten()
+                    .unwrap_or_default()
+            },
+        }
+    }
+}
+
 pub fn save_window_size(window_wrapper: &WinitWindowWrapper) {
     let window = window_wrapper.skia_renderer.window();
     // Don't save the window size when the window is minimized, since the size can be 0
@@ -73,28 +198,29 @@ pub fn save_window_size(window_wrapper: &WinitWindowWrapper) {
     if window.is_minimized() == Some(true) {
         return;
     }
-    let maximized = window.is_maximized();
-    let pixel_size = window.inner_size();
-    let grid_size = window_wrapper.get_grid_size();
-    let position = window.outer_position().ok();
     let window_settings = SETTINGS.get::<WindowSettings>();
+    let background = window_wrapper.default_background();
 
+    let fullscreen_monitor = window_wrapper
+        .is_fullscreen()
+        .then(|| window.current_monitor())
+        .flatten()
+        .filter(|_| window_settings.remember_window_size);
     let settings = PersistentSettings {
-        window: if maximized && window_settings.remember_window_size {
-            PersistentWindowSettings::Maximized
-        } else {
-            PersistentWindowSettings::Windowed {
-                pixel_size: { window_settings.remember_window_size.then_some(pixel_size) },
-                grid_size: { window_settings.remember_window_size.then_some(grid_size) },
-                position: {
-                    window_settings
-                        .remember_window_position
-                        .then_some(position)
-                        .flatten()
-                        .unwrap_or_default()
-                },
-            }
+        window: match fullscreen_monitor {
+            Some(monitor) => PersistentWindowSettings::Fullscreen {
+                monitor: PersistentMonitor::from(&monitor),
+                windowed: window_wrapper.settings_before_fullscreen().map(Box::new),
+            },
+            None => windowed_settings(window_wrapper),
         },
+        splash_color: Some(u32::from_be_bytes([
+            background.a(),
+            background.r(),
+            background.g(),
+            background.b(),
+        ])),
+        scale_factor: (window_settings.scale_factor != 1.0).then_some(window_settings.scale_factor),
     };
 
     let settings_path = settings_path();
@@ -104,3 +230,52 @@ pub fn save_window_size(window_wrapper: &WinitWindowWrapper) {
     std::fs::write(&settings_path, json)
         .unwrap_or_else(|_| panic!("Can't write to {settings_path:?}"));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitor(name: &str, x: i32) -> PersistentMonitor {
+        PersistentMonitor {
+            name: Some(name.to_string()),
+            position: PhysicalPosition::new(x, 0),
+            size: PhysicalSize::new(2560, 1440),
+        }
+    }
+
+    #[test]
+    fn test_find_monitor() {
+        let saved = monitor("DELL U2720Q", 2560);
+        // Moved to the other side of the main monitor
+        let candidates = vec![monitor("Built-in", 0), monitor("DELL U2720Q", -2560)];
+        assert_eq!(saved.find_index(&candidates), Some(1));
+        // Two monitors of the same model, only the exact one can be told apart
+        let candidates = vec![monitor("DELL U2720Q", 0), monitor("DELL U2720Q", 2560)];
+        assert_eq!(saved.find_index(&candidates), Some(1));
+        let candidates = vec![monitor("DELL U2720Q", 0), monitor("DELL U2720Q", -2560)];
+        assert_eq!(saved.find_index(&candidates), None);
+        // Disconnected
+        assert_eq!(saved.find_index(&[monitor("Built-in", 0)]), None);
+    }
+
+    #[test]
+    fn test_windowed_settings_of_fullscreen() {
+        let windowed = PersistentWindowSettings::Windowed {
+            position: PhysicalPosition::new(10, 20),
+            pixel_size: Some(PhysicalSize::new(800, 600)),
+            grid_size: None,
+            scale_factor: Some(1.0),
+        };
+        let fullscreen = PersistentWindowSettings::Fullscreen {
+            monitor: monitor("Built-in", 0),
+            windowed: Some(Box::new(windowed.clone())),
+        };
+        let json = serde_json::to_string(&fullscreen).unwrap();
+        let loaded: PersistentWindowSettings = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.windowed(), Some(&windowed));
+        assert_eq!(
+            PersistentWindowSettings::Maximized.windowed(),
+            Some(&PersistentWindowSettings::Maximized)
+        );
+    }
+}
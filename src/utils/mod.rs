@@ -1,6 +1,10 @@
+mod base64;
 mod ring_buffer;
+mod triple_buffer;
 
+pub use base64::*;
 pub use ring_buffer::*;
+pub use triple_buffer::*;
 
 pub fn is_tty() -> bool {
     use std::io::IsTerminal;
@@ -7,6 +7,7 @@ use std::{
 /// A simple ring buffer data structure
 /// The buffer is always full and wraps around so that the oldest elements are overwritten.
 /// It supports both negative and positive indexing and also indexing past the size.
+#[derive(Clone)]
 pub struct RingBuffer<T> {
     elements: Vec<T>,
     current_index: isize,
@@ -0,0 +1,166 @@
+use std::sync::Arc;
+
+use parking_lot::{Condvar, Mutex};
+
+struct Shared<T> {
+    middle: Mutex<Middle<T>>,
+    published: Condvar,
+}
+
+struct Middle<T> {
+    value: T,
+    fresh: bool,
+    closed: bool,
+}
+
+pub struct TripleBufferWriter<T> {
+    back: T,
+    shared: Arc<Shared<T>>,
+}
+
+pub struct TripleBufferReader<T> {
+    front: T,
+    shared: Arc<Shared<T>>,
+}
+
+/// A triple buffer, which passes the latest value from a writer thread to a reader thread without
+/// either of them waiting for the other. The writer fills the back buffer and publishes it by
+/// swapping it with the middle one, and the reader swaps the middle buffer with the front one when
+/// a new value was published, so the values published in between are skipped. The buffers are
+/// reused, the writer gets back the one the reader is done with.
+pub fn triple_buffer<T: Clone>(initial: T) -> (TripleBufferWriter<T>, TripleBufferReader<T>) {
+    let shared = Arc::new(Shared {
+        middle: Mutex::new(Middle {
+            value: initial.clone(),
+            fresh: false,
+            closed: false,
+        }),
+        published: Condvar::new(),
+    });
+    (
+        TripleBufferWriter {
+            back: initial.clone(),
+            shared: shared.clone(),
+        },
+        TripleBufferReader {
+            front: initial,
+            shared,
+        },
+    )
+}
+
+impl<T> TripleBufferWriter<T> {
+    /// The back buffer, which holds an older value that can be overwritten.
+    pub fn back(&mut self) -> &mut T {
+        &mut self.back
+    }
+
+    /// Makes the back buffer the latest value, replacing the previous one if it wasn't read.
+    pub fn publish(&mut self) {
+        let mut middle = self.shared.middle.lock();
+        std::mem::swap(&mut middle.value, &mut self.back);
+        middle.fresh = true;
+        self.shared.published.notify_all();
+    }
+}
+
+impl<T> Drop for TripleBufferWriter<T> {
+    fn drop(&mut self) {
+        self.shared.middle.lock().closed = true;
+        self.shared.published.notify_all();
+    }
+}
+
+impl<T> TripleBufferReader<T> {
+    /// The value read last.
+    pub fn front(&self) -> &T {
+        &self.front
+    }
+
+    /// Takes the latest published value, returns false if nothing was published since the last
+    /// time.
+    pub fn update(&mut self) -> bool {
+        let mut middle = self.shared.middle.lock();
+        Self::swap_fresh(&mut self.front, &mut middle)
+    }
+
+    /// Takes the latest published value, and waits for new ones until `ready` is true for it. It
+    /// stops waiting when the writer is dropped. Returns false if nothing was published since the
+    /// last time.
+    pub fn update_until(&mut self, ready: impl Fn(&T) -> bool) -> bool {
+        let mut updated = false;
+        let mut middle = self.shared.middle.lock();
+        loop {
+            updated |= Self::swap_fresh(&mut self.front, &mut middle);
+            if ready(&self.front) || middle.closed {
+                return updated;
+            }
+            self.shared.published.wait(&mut middle);
+        }
+    }
+
+    fn swap_fresh(front: &mut T, middle: &mut Middle<T>) -> bool {
+        if !middle.fresh {
+            return false;
+        }
+        std::mem::swap(&mut middle.value, front);
+        middle.fresh = false;
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+
+    use super::*;
+
+    #[test]
+    fn reads_the_latest_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        assert!(!reader.update());
+        assert_eq!(*reader.front(), 0);
+
+        *writer.back() = 1;
+        writer.publish();
+        *writer.back() = 2;
+        writer.publish();
+        assert!(reader.update());
+        assert_eq!(*reader.front(), 2);
+        assert!(!reader.update());
+        assert_eq!(*reader.front(), 2);
+    }
+
+    #[test]
+    fn reuses_the_buffers() {
+        let (mut writer, mut reader) = triple_buffer(Vec::new());
+        writer.back().push(1);
+        writer.publish();
+        assert!(reader.update());
+        writer.back().push(2);
+        writer.publish();
+        assert!(reader.update());
+        assert_eq!(*reader.front(), vec![2]);
+        writer.back().push(3);
+        writer.publish();
+        // The writer gets back the buffer the reader was done with
+        assert_eq!(*writer.back(), vec![1]);
+    }
+
+    #[test]
+    fn waits_for_the_value() {
+        let (mut writer, mut reader) = triple_buffer(0);
+        let handle = thread::spawn(move || {
+            for value in 1..=3 {
+                *writer.back() = value;
+                writer.publish();
+            }
+        });
+        assert!(reader.update_until(|value| *value == 3));
+        assert_eq!(*reader.front(), 3);
+        handle.join().unwrap();
+
+        // The writer is gone, so it doesn't wait for a value that will never come
+        assert!(!reader.update_until(|value| *value == 4));
+    }
+}
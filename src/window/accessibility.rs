@@ -0,0 +1,447 @@
+//! Exposes the text of the Neovim grids to screen readers with AccessKit. Every grid is a multiline
+//! text input, with a line of text for each row, and the Neovim cursor as its caret.
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+use accesskit::{
+    Action, ActionHandler, ActionRequest, Node, NodeBuilder, NodeClassSet, NodeId, Role,
+    TextPosition, TextSelection, Tree, TreeUpdate,
+};
+use accesskit_winit::Adapter;
+use smol_str::SmolStr;
+use unicode_segmentation::UnicodeSegmentation;
+use winit::{event::WindowEvent, event_loop::EventLoopProxy, window::Window};
+
+use super::{UserEvent, WindowCommand};
+use crate::renderer::{DrawCommand, LineFragment, WindowDrawCommand};
+
+const ROOT_ID: NodeId = NodeId(0);
+
+/// Grid ids are small, so the upper bits identify the grid and the lower bits the row.
+fn grid_node_id(grid: u64) -> NodeId {
+    NodeId(grid << 32)
+}
+
+fn line_node_id(grid: u64, row: usize) -> NodeId {
+    NodeId((grid << 32) | (row as u64 + 1))
+}
+
+#[derive(Default)]
+struct GridText {
+    /// The text of every cell, which can be several characters when it has combining characters,
+    /// `None` for the second cell of a double width character.
+    rows: Vec<Vec<Option<SmolStr>>>,
+    /// The rows changed since the last update sent to the screen reader.
+    dirty_rows: Vec<bool>,
+    position: (f64, f64),
+    floating: bool,
+    hidden: bool,
+}
+
+impl GridText {
+    /// Returns true when the size changed.
+    fn resize(&mut self, width: usize, height: usize) -> bool {
+        let resized = self.rows.len() != height || self.rows.first().map(Vec::len) != Some(width);
+        self.rows.resize_with(height, Vec::new);
+        for row in &mut self.rows {
+            row.resize(width, Some(SmolStr::new_inline(" ")));
+        }
+        self.dirty_rows.resize(height, true);
+        resized
+    }
+
+    fn draw_line(&mut self, row: usize, line_fragments: &[LineFragment]) {
+        let Some(cells) = self.rows.get_mut(row) else {
+            return;
+        };
+        for fragment in line_fragments {
+            // Fragments have one grapheme per cell, except for the empty cell of a double width
+            // character at the end
+            let mut graphemes = fragment.text.graphemes(true);
+            let start = fragment.window_left as usize;
+            let end = (start + fragment.width as usize).min(cells.len());
+            for cell in cells.iter_mut().take(end).skip(start) {
+                *cell = graphemes.next().map(SmolStr::new);
+            }
+        }
+        self.dirty_rows[row] = true;
+    }
+
+    /// Moves the rows of the region up by `rows`, or down when negative.
+    fn scroll(&mut self, top: usize, bottom: usize, left: usize, right: usize, rows: i64) {
+        let bottom = bottom.min(self.rows.len());
+        let shift = rows.unsigned_abs() as usize;
+        if top >= bottom || shift >= bottom - top {
+            return;
+        }
+        let mut copy_row = |destination: usize, source: usize| {
+            let (destination_row, source_row) = if destination < source {
+                let (before, after) = self.rows.split_at_mut(source);
+                (&mut before[destination], &after[0])
+            } else {
+                let (before, after) = self.rows.split_at_mut(destination);
+                (&mut after[0], &before[source])
+            };
+            if let (Some(source_cells), Some(destination_cells)) = (
+                source_row.get(left..right),
+                destination_row.get_mut(left..right),
+            ) {
+                destination_cells.clone_from_slice(source_cells);
+            }
+        };
+        if rows > 0 {
+            for row in top..bottom - shift {
+                copy_row(row, row + shift);
+            }
+        } else {
+            for row in (top + shift..bottom).rev() {
+                copy_row(row, row - shift);
+            }
+        }
+        self.dirty_rows[top..bottom].fill(true);
+    }
+
+    fn line(&self, row: usize) -> String {
+        let line: String = self.rows[row]
+            .iter()
+            .flatten()
+            .map(SmolStr::as_str)
+            .collect();
+        line.trim_end().to_string()
+    }
+
+    fn clear(&mut self) {
+        for cell in self.rows.iter_mut().flatten() {
+            *cell = Some(SmolStr::new_inline(" "));
+        }
+        self.dirty_rows.fill(true);
+    }
+}
+
+#[derive(Default)]
+struct ScreenText {
+    title: String,
+    grids: HashMap<u64, GridText>,
+    /// The grid, column and row of the cursor.
+    cursor: (u64, usize, usize),
+    /// The grid of the cursor in the last update, which loses the caret when it moved away.
+    previous_cursor_grid: u64,
+    /// The grids were added, removed, moved, resized or hidden, or the title changed, so the whole
+    /// tree is sent again.
+    layout_changed: bool,
+}
+
+impl ScreenText {
+    fn handle_draw_command(&mut self, command: &DrawCommand) {
+        match command {
+            DrawCommand::Window { grid_id, command } => {
+                self.handle_window_command(*grid_id, command)
+            }
+            DrawCommand::CloseWindow(grid_id) => {
+                self.layout_changed |= self.grids.remove(grid_id).is_some();
+            }
+            DrawCommand::UpdateCursor(cursor) => {
+                let (column, row) = cursor.grid_position;
+                self.cursor = (cursor.parent_window_id, column as usize, row as usize);
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_window_command(&mut self, grid_id: u64, command: &WindowDrawCommand) {
+        if !self.grids.contains_key(&grid_id) {
+            self.layout_changed = true;
+        }
+        let grid = self.grids.entry(grid_id).or_default();
+        match command {
+            WindowDrawCommand::Position {
+                grid_position,
+                grid_size: (width, height),
+                anchor_info,
+                ..
+            } => {
+                let resized = grid.resize(*width as usize, *height as usize);
+                let floating = anchor_info.is_some();
+                self.layout_changed |= resized
+                    || grid.position != *grid_position
+                    || grid.floating != floating
+                    || grid.hidden;
+                grid.position = *grid_position;
+                grid.floating = floating;
+                grid.hidden = false;
+            }
+            WindowDrawCommand::DrawLine {
+                row,
+                line_fragments,
+            } => grid.draw_line(*row, line_fragments),
+            WindowDrawCommand::Scroll {
+                top,
+                bottom,
+                left,
+                right,
+                rows,
+                ..
+            } => grid.scroll(
+                *top as usize,
+                *bottom as usize,
+                *left as usize,
+                *right as usize,
+                *rows,
+            ),
+            WindowDrawCommand::Clear => grid.clear(),
+            WindowDrawCommand::Show => {
+                self.layout_changed |= grid.hidden;
+                grid.hidden = false;
+            }
+            WindowDrawCommand::Hide => {
+                self.layout_changed |= !grid.hidden;
+                grid.hidden = true;
+            }
+            WindowDrawCommand::Close => {
+                self.grids.remove(&grid_id);
+                self.layout_changed = true;
+            }
+            _ => {}
+        }
+    }
+
+    fn set_title(&mut self, title: &str) {
+        self.title = title.to_string();
+        self.layout_changed = true;
+    }
+
+    fn grid_node(&self, grid_id: u64, grid: &GridText, classes: &mut NodeClassSet) -> Node {
+        let mut builder = NodeBuilder::new(Role::MultilineTextInput);
+        builder.set_children(
+            (0..grid.rows.len())
+                .map(|row| line_node_id(grid_id, row))
+                .collect::<Vec<_>>(),
+        );
+        let (cursor_grid, column, row) = self.cursor;
+        if cursor_grid == grid_id && row < grid.rows.len() {
+            // The caret is placed before the character of the cursor cell, ignoring the empty
+            // cells of double width characters
+            let line_length = grid.line(row).graphemes(true).count();
+            let character_index = grid.rows[row]
+                .iter()
+                .take(column)
+                .flatten()
+                .count()
+                .min(line_length);
+            let position = TextPosition {
+                node: line_node_id(grid_id, row),
+                character_index,
+            };
+            builder.set_text_selection(TextSelection {
+                anchor: position,
+                focus: position,
+            });
+        }
+        builder.build(classes)
+    }
+
+    fn line_node(line: &str, classes: &mut NodeClassSet) -> Node {
+        let mut builder = NodeBuilder::new(Role::InlineTextBox);
+        builder.set_value(line);
+        // AccessKit characters are what the user perceives as one, so a grapheme each
+        builder.set_character_lengths(
+            line.graphemes(true)
+                .map(|grapheme| grapheme.len().min(u8::MAX as usize) as u8)
+                .collect::<Vec<_>>(),
+        );
+        builder.build(classes)
+    }
+
+    /// The nodes changed since the last update, or the whole tree when `full` is set, like when a
+    /// screen reader connects, or when the layout of the grids changed. Marks everything as sent.
+    fn tree_update(&mut self, full: bool) -> TreeUpdate {
+        let full = full || self.layout_changed;
+        let mut classes = NodeClassSet::lock_global();
+        let mut visible_grids = self
+            .grids
+            .iter()
+            .filter(|(_, grid)| !grid.hidden)
+            .collect::<Vec<_>>();
+        // Read in the order they are shown, with the floating windows last
+        visible_grids.sort_by(|(_, a), (_, b)| {
+            (a.floating, a.position.1, a.position.0)
+                .partial_cmp(&(b.floating, b.position.1, b.position.0))
+                .unwrap_or(std::cmp::Ordering::Equal)
+        });
+
+        let mut nodes = Vec::new();
+        if full {
+            let mut root = NodeBuilder::new(Role::Window);
+            root.set_name(self.title.as_str());
+            root.set_children(
+                visible_grids
+                    .iter()
+                    .map(|(grid_id, _)| grid_node_id(**grid_id))
+                    .collect::<Vec<_>>(),
+            );
+            nodes.push((ROOT_ID, root.build(&mut classes)));
+        }
+        let cursor_grid = self.cursor.0;
+        for (grid_id, grid) in &visible_grids {
+            let grid_id = **grid_id;
+            // The caret moves with the cursor, and its index depends on the text of the line
+            if full || grid_id == cursor_grid || grid_id == self.previous_cursor_grid {
+                nodes.push((
+                    grid_node_id(grid_id),
+                    self.grid_node(grid_id, grid, &mut classes),
+                ));
+            }
+            for row in 0..grid.rows.len() {
+                if full || grid.dirty_rows[row] {
+                    nodes.push((
+                        line_node_id(grid_id, row),
+                        Self::line_node(&grid.line(row), &mut classes),
+                    ));
+                }
+            }
+        }
+
+        let focus = if visible_grids
+            .iter()
+            .any(|(grid_id, _)| **grid_id == cursor_grid)
+        {
+            grid_node_id(cursor_grid)
+        } else {
+            ROOT_ID
+        };
+        drop(classes);
+
+        for grid in self.grids.values_mut() {
+            grid.dirty_rows.fill(false);
+        }
+        self.layout_changed = false;
+        self.previous_cursor_grid = cursor_grid;
+        TreeUpdate {
+            nodes,
+            tree: full.then(|| Tree::new(ROOT_ID)),
+            focus,
+        }
+    }
+}
+
+/// Neovim moves the cursor itself, so focusing the window is the only action supported.
+struct FocusHandler {
+    proxy: EventLoopProxy<UserEvent>,
+}
+
+impl ActionHandler for FocusHandler {
+    fn do_action(&mut self, request: ActionRequest) {
+        if request.action == Action::Focus {
+            let _ = self.proxy.send_event(WindowCommand::FocusWindow.into());
+        }
+    }
+}
+
+pub struct Accessibility {
+    adapter: Adapter,
+    screen: Arc<Mutex<ScreenText>>,
+}
+
+impl Accessibility {
+    /// Has to be created before the window is shown for the first time.
+    pub fn new(window: &Window, proxy: EventLoopProxy<UserEvent>) -> Self {
+        let screen = Arc::new(Mutex::new(ScreenText::default()));
+        // The tree is built only once a screen reader connects, which may be long after the start
+        let initial_screen = screen.clone();
+        let adapter = Adapter::with_action_handler(
+            window,
+            move || initial_screen.lock().unwrap().tree_update(true),
+            Box::new(FocusHandler { proxy }),
+        );
+        Self { adapter, screen }
+    }
+
+    pub fn process_event(&self, window: &Window, event: &WindowEvent) {
+        self.adapter.process_event(window, event);
+    }
+
+    pub fn handle_draw_commands(&self, batch: &[DrawCommand]) {
+        let mut screen = self.screen.lock().unwrap();
+        for command in batch {
+            screen.handle_draw_command(command);
+        }
+        self.adapter.update_if_active(|| screen.tree_update(false));
+    }
+
+    pub fn set_title(&self, title: &str) {
+        let mut screen = self.screen.lock().unwrap();
+        screen.set_title(title);
+        self.adapter.update_if_active(|| screen.tree_update(false));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fragment(text: &str, window_left: u64, width: u64) -> LineFragment {
+        LineFragment {
+            text: text.into(),
+            window_left,
+            width,
+            style: None,
+        }
+    }
+
+    #[test]
+    fn test_grid_text_follows_draw_and_scroll_commands() {
+        let mut grid = GridText::default();
+        grid.resize(6, 3);
+        grid.draw_line(0, &[fragment("ab", 0, 2), fragment("日", 2, 2)]);
+        grid.draw_line(1, &[fragment("second", 0, 6)]);
+        assert_eq!(grid.line(0), "ab日");
+        assert_eq!(grid.rows[0][3], None);
+
+        grid.scroll(0, 3, 0, 6, 1);
+        assert_eq!(grid.line(0), "second");
+        assert_eq!(grid.line(1), "");
+
+        grid.scroll(0, 3, 0, 6, -2);
+        assert_eq!(grid.line(2), "second");
+
+        // Combining characters stay in the cell of their base character
+        grid.draw_line(1, &[fragment("e\u{301}xy", 0, 3), fragment("z", 3, 1)]);
+        assert_eq!(grid.line(1), "e\u{301}xyz");
+        assert_eq!(grid.rows[1][1].as_deref(), Some("x"));
+    }
+
+    #[test]
+    fn test_only_changed_lines_are_updated() {
+        let mut screen = ScreenText::default();
+        let position = WindowDrawCommand::Position {
+            grid_position: (0.0, 0.0),
+            grid_size: (6, 3),
+            anchor_info: None,
+            window_type: crate::editor::WindowType::Editor,
+        };
+        screen.handle_window_command(1, &position);
+        let update = screen.tree_update(false);
+        // The root, the grid and its three lines
+        assert_eq!(update.nodes.len(), 5);
+        assert!(update.tree.is_some());
+
+        screen.handle_window_command(
+            1,
+            &WindowDrawCommand::DrawLine {
+                row: 1,
+                line_fragments: vec![fragment("text", 0, 4)],
+            },
+        );
+        let update = screen.tree_update(false);
+        let ids: Vec<_> = update.nodes.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![line_node_id(1, 1)]);
+        assert!(update.tree.is_none());
+
+        // The same position doesn't change the layout
+        screen.handle_window_command(1, &position);
+        assert!(screen.tree_update(false).nodes.is_empty());
+    }
+}
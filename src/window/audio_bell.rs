@@ -0,0 +1,103 @@
+use std::time::{Duration, Instant};
+#[cfg(not(windows))]
+use std::{process::Command, thread};
+
+use super::WindowSettings;
+
+/// Plays a sound when Neovim rings the bell, unless `visualbell` is set.
+#[derive(Default)]
+pub struct AudioBell {
+    last_played: Option<Instant>,
+}
+
+impl AudioBell {
+    /// Returns true when the bell should be heard. Bells rung faster than the minimum interval,
+    /// like when holding down a failing motion, are dropped.
+    fn should_play(&mut self, min_interval: f32, now: Instant) -> bool {
+        let min_interval = Duration::from_secs_f32(min_interval.max(0.0));
+        if self
+            .last_played
+            .is_some_and(|last_played| now.duration_since(last_played) < min_interval)
+        {
+            return false;
+        }
+        self.last_played = Some(now);
+        true
+    }
+
+    pub fn ring(&mut self, settings: &WindowSettings, focused: bool) {
+        let enabled = if focused {
+            settings.audio_bell_focused
+        } else {
+            settings.audio_bell_unfocused
+        };
+        if enabled && self.should_play(settings.audio_bell_min_interval, Instant::now()) {
+            play(&settings.audio_bell_sound);
+        }
+    }
+}
+
+/// Plays the sound file, or the system beep when the path is empty.
+fn play(sound: &str) {
+    #[cfg(windows)]
+    crate::windows_utils::play_sound(sound);
+    #[cfg(target_os = "macos")]
+    if sound.is_empty() {
+        super::macos::beep();
+    } else {
+        spawn_player(vec![vec!["afplay".to_string(), sound.to_string()]]);
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        // The bell of the sound theme, or the file with the PulseAudio player, falling back to ALSA
+        let players = if sound.is_empty() {
+            vec![vec!["canberra-gtk-play", "--id", "bell"]]
+        } else {
+            vec![vec!["paplay", sound], vec!["aplay", "-q", sound]]
+        };
+        spawn_player(
+            players
+                .into_iter()
+                .map(|player| player.into_iter().map(str::to_string).collect())
+                .collect(),
+        );
+    }
+}
+
+/// Runs the first of the player commands that's installed, without blocking the window.
+#[cfg(not(windows))]
+fn spawn_player(players: Vec<Vec<String>>) {
+    thread::spawn(move || {
+        for player in players {
+            let Some((program, args)) = player.split_first() else {
+                continue;
+            };
+            match Command::new(program).args(args).status() {
+                Ok(status) if !status.success() => {
+                    log::warn!("{program} failed with {status}");
+                    return;
+                }
+                Ok(_) => return,
+                Err(_) => continue,
+            }
+        }
+        log::warn!("Could not find a program to play the bell");
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audio_bell_is_rate_limited() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut bell = AudioBell::default();
+
+        assert!(bell.should_play(0.25, at(0)));
+        assert!(!bell.should_play(0.25, at(100)));
+        assert!(bell.should_play(0.25, at(300)));
+        assert!(bell.should_play(0.0, at(300)));
+    }
+}
@@ -0,0 +1,238 @@
+use log::error;
+use rmpv::Value;
+
+use crate::settings::ParseFromValue;
+
+/// How dead keys, like the ones for accents on many international layouts, are handled.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ComposeMode {
+    /// Let the operating system combine the dead key with the next key
+    #[default]
+    System,
+    /// Send the dead key character immediately and don't combine it with anything
+    Off,
+    /// Combine the dead key with the next key inside Neovide
+    Manual,
+}
+
+impl ParseFromValue for ComposeMode {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "system" => ComposeMode::System,
+                "off" => ComposeMode::Off,
+                "manual" => ComposeMode::Manual,
+                value => {
+                    error!("Expected a compose mode name, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!("Expected a compose mode string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<ComposeMode> for Value {
+    fn from(mode: ComposeMode) -> Self {
+        match mode {
+            ComposeMode::System => Value::from("system"),
+            ComposeMode::Off => Value::from("off"),
+            ComposeMode::Manual => Value::from("manual"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum DeadKeyResult {
+    /// Send the text to Neovim
+    Send(String),
+    /// Wait for the next key to complete the sequence
+    Pending,
+    /// Not handled by the compose logic, format the key as usual
+    PassThrough,
+}
+
+/// The state of a dead key sequence.
+#[derive(Debug, Default)]
+pub struct ComposeState {
+    pending: Option<char>,
+}
+
+impl ComposeState {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn reset(&mut self) {
+        self.pending = None;
+    }
+
+    /// Handles a dead key press. `option_is_meta` should be set when the macOS option key is held
+    /// and configured to act as meta, in which case the key is a mapping rather than an accent.
+    pub fn handle_dead_key(
+        &mut self,
+        mode: ComposeMode,
+        dead: Option<char>,
+        option_is_meta: bool,
+    ) -> DeadKeyResult {
+        let Some(dead) = dead else {
+            return DeadKeyResult::PassThrough;
+        };
+        if option_is_meta {
+            self.reset();
+            return DeadKeyResult::PassThrough;
+        }
+
+        match mode {
+            ComposeMode::System => DeadKeyResult::PassThrough,
+            ComposeMode::Off => {
+                // The next key is still composed by the system, so remember to undo that
+                self.pending = Some(dead);
+                DeadKeyResult::Send(dead.to_string())
+            }
+            ComposeMode::Manual => match self.pending.replace(dead) {
+                // Pressing the same dead key twice produces the accent itself
+                Some(previous) if previous == dead => {
+                    self.pending = None;
+                    DeadKeyResult::Send(dead.to_string())
+                }
+                Some(previous) => DeadKeyResult::Send(previous.to_string()),
+                None => DeadKeyResult::Pending,
+            },
+        }
+    }
+
+    /// Handles a key producing text, returns the text to send. `text` is the text reported by the
+    /// system, which might already be composed, and `base` is the text of the key itself.
+    pub fn handle_text(&mut self, mode: ComposeMode, text: &str, base: &str) -> String {
+        let Some(dead) = self.pending.take() else {
+            return text.to_string();
+        };
+
+        match mode {
+            ComposeMode::System => text.to_string(),
+            ComposeMode::Off => base.to_string(),
+            ComposeMode::Manual => {
+                let mut chars = base.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(' '), None) => dead.to_string(),
+                    (Some(c), None) => compose(dead, c)
+                        .map(String::from)
+                        .unwrap_or_else(|| format!("{dead}{base}")),
+                    _ => format!("{dead}{base}"),
+                }
+            }
+        }
+    }
+}
+
+fn compose(dead: char, base: char) -> Option<char> {
+    let (bases, composed) = match dead {
+        '`' => ("aeiouAEIOU", "àèìòùÀÈÌÒÙ"),
+        '´' | '\'' => ("aeiouyAEIOUYcCnNsSzZ", "áéíóúýÁÉÍÓÚÝćĆńŃśŚźŹ"),
+        '^' => ("aeiouAEIOU", "âêîôûÂÊÎÔÛ"),
+        '~' => ("anoANO", "ãñõÃÑÕ"),
+        '¨' | '"' => ("aeiouyAEIOUY", "äëïöüÿÄËÏÖÜŸ"),
+        '¸' => ("cC", "çÇ"),
+        '°' | '˚' => ("aAuU", "åÅůŮ"),
+        _ => return None,
+    };
+    let index = bases.chars().position(|c| c == base)?;
+    composed.chars().nth(index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_system_mode_passes_through() {
+        let mut state = ComposeState::new();
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::System, Some('~'), false),
+            DeadKeyResult::PassThrough
+        );
+        assert_eq!(state.handle_text(ComposeMode::System, "ã", "a"), "ã");
+    }
+
+    #[test]
+    fn test_off_mode_sends_dead_key_and_base() {
+        let mut state = ComposeState::new();
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Off, Some('~'), false),
+            DeadKeyResult::Send("~".to_string())
+        );
+        assert_eq!(state.handle_text(ComposeMode::Off, "ã", "a"), "a");
+        // Only the key following the dead key is affected
+        assert_eq!(state.handle_text(ComposeMode::Off, "ä", "a"), "ä");
+    }
+
+    #[test]
+    fn test_manual_dead_tilde() {
+        let mut state = ComposeState::new();
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Manual, Some('~'), false),
+            DeadKeyResult::Pending
+        );
+        assert_eq!(state.handle_text(ComposeMode::Manual, "a", "a"), "ã");
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Manual, Some('~'), false),
+            DeadKeyResult::Pending
+        );
+        assert_eq!(state.handle_text(ComposeMode::Manual, "N", "N"), "Ñ");
+    }
+
+    #[test]
+    fn test_manual_dead_acute() {
+        let mut state = ComposeState::new();
+        state.handle_dead_key(ComposeMode::Manual, Some('´'), false);
+        assert_eq!(state.handle_text(ComposeMode::Manual, "e", "e"), "é");
+        state.handle_dead_key(ComposeMode::Manual, Some('´'), false);
+        assert_eq!(state.handle_text(ComposeMode::Manual, " ", " "), "´");
+        state.handle_dead_key(ComposeMode::Manual, Some('´'), false);
+        assert_eq!(state.handle_text(ComposeMode::Manual, "x", "x"), "´x");
+    }
+
+    #[test]
+    fn test_manual_repeated_dead_keys() {
+        let mut state = ComposeState::new();
+        state.handle_dead_key(ComposeMode::Manual, Some('^'), false);
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Manual, Some('^'), false),
+            DeadKeyResult::Send("^".to_string())
+        );
+        state.handle_dead_key(ComposeMode::Manual, Some('^'), false);
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Manual, Some('`'), false),
+            DeadKeyResult::Send("^".to_string())
+        );
+        assert_eq!(state.handle_text(ComposeMode::Manual, "a", "a"), "à");
+    }
+
+    #[test]
+    fn test_altgr() {
+        let mut state = ComposeState::new();
+        // Characters produced with AltGr are sent as they are
+        assert_eq!(state.handle_text(ComposeMode::Manual, "@", "@"), "@");
+        assert_eq!(state.handle_text(ComposeMode::Manual, "€", "€"), "€");
+        // Some layouts have dead keys on the AltGr level, those are composed like any other
+        state.handle_dead_key(ComposeMode::Manual, Some('~'), false);
+        assert_eq!(state.handle_text(ComposeMode::Manual, "o", "o"), "õ");
+    }
+
+    #[test]
+    fn test_macos_option_as_meta() {
+        let mut state = ComposeState::new();
+        // Option-e is a dead acute on the US layout, but a mapping when option is meta
+        assert_eq!(
+            state.handle_dead_key(ComposeMode::Manual, Some('´'), true),
+            DeadKeyResult::PassThrough
+        );
+        assert_eq!(state.handle_text(ComposeMode::Manual, "e", "e"), "e");
+        // A pending accent is cancelled by a meta combination
+        state.handle_dead_key(ComposeMode::Manual, Some('´'), false);
+        state.handle_dead_key(ComposeMode::Manual, Some('´'), true);
+        assert_eq!(state.handle_text(ComposeMode::Manual, "e", "e"), "e");
+    }
+}
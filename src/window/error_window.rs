@@ -1,31 +1,38 @@
+use std::ops::Range;
+
 use skia_safe::{
     canvas::{Canvas, SaveLayerRec},
     colors::{BLACK, WHITE},
+    paint::Style as PaintStyle,
     textlayout::{
-        FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, TextHeightBehavior, TextIndex,
-        TextStyle,
+        FontCollection, Paragraph, ParagraphBuilder, ParagraphStyle, RectHeightStyle,
+        RectWidthStyle, TextDecoration, TextHeightBehavior, TextIndex, TextStyle,
     },
-    Color4f, FontMgr, Paint, Point, Rect, Size,
+    Color4f, Contains, FontMgr, Paint, Point, RRect, Rect, Size,
 };
 use strum::IntoEnumIterator;
 use strum::{EnumCount, EnumIter};
 use winit::{
     dpi::PhysicalSize,
-    event::{ElementState, Event, KeyEvent, Modifiers, MouseScrollDelta, WindowEvent},
+    event::{ElementState, Event, KeyEvent, Modifiers, MouseButton, MouseScrollDelta, WindowEvent},
     event_loop::{EventLoop, EventLoopWindowTarget},
     keyboard::{Key, NamedKey},
-    window::WindowBuilder,
+    window::{CursorIcon, WindowBuilder},
 };
 
 use crate::{
     clipboard,
     cmd_line::SRGB_DEFAULT,
-    renderer::{build_window_config, create_skia_renderer, SkiaRenderer, WindowConfig},
+    renderer::{
+        build_window_config, create_skia_renderer, find_urls, open_url, SkiaRenderer,
+        SurfaceOptions, WindowConfig,
+    },
     window::{load_icon, UserEvent},
 };
 
 const TEXT_COLOR: Color4f = WHITE;
 const BACKGROUND_COLOR: Color4f = BLACK;
+const LINK_COLOR: Color4f = Color4f::new(0.4, 0.7, 1.0, 1.0);
 const FONT_SIZE: f32 = 12.0 * 96.0 / 72.0;
 const PADDING: f32 = 10.0;
 const MAX_LINES: i32 = 9999;
@@ -54,11 +61,34 @@ enum PossibleScrollDirection {
     Both,
 }
 
+/// The clickable parts of the help message, the ranges are in UTF-16 code units like all
+/// paragraph indices.
+#[derive(Clone, Copy)]
+enum Button {
+    Quit,
+    Copy,
+}
+
+const HELP_MESSAGE: &str = " Quit (q)   Copy (y) ";
+const BUTTONS: [(Button, Range<usize>); 2] = [(Button::Quit, 0..10), (Button::Copy, 11..21)];
+
+struct Link {
+    url: String,
+    /// The range of the link in UTF-16 code units
+    range: Range<usize>,
+}
+
 struct Paragraphs {
     message: Paragraph,
+    links: Vec<Link>,
     help_messages: [Paragraph; PossibleScrollDirection::COUNT],
 }
 
+enum Target<'a> {
+    Button(Button),
+    Link(&'a str),
+}
+
 struct ErrorWindow<'a> {
     skia_renderer: Box<dyn SkiaRenderer>,
     font_collection: FontCollection,
@@ -71,6 +101,9 @@ struct ErrorWindow<'a> {
     modifiers: Modifiers,
     visible: bool,
     mouse_scroll_accumulator: f32,
+    mouse_position: Point,
+    message_origin: Point,
+    help_message_origin: Point,
 }
 
 impl<'a> ErrorWindow<'a> {
@@ -84,7 +117,7 @@ impl<'a> ErrorWindow<'a> {
         let srgb = SRGB_DEFAULT == "1";
         let vsync = true;
         let window = create_window(event_loop);
-        let skia_renderer = create_skia_renderer(window, srgb, vsync);
+        let skia_renderer = create_skia_renderer(window, srgb, vsync, &SurfaceOptions::default());
         let scale_factor = skia_renderer.window().scale_factor();
         let size = skia_renderer.window().inner_size();
         let paragraphs = create_paragraphs(message, scale_factor as f32, &font_collection);
@@ -93,6 +126,9 @@ impl<'a> ErrorWindow<'a> {
         let modifiers = Modifiers::default();
         let visible = false;
         let mouse_scroll_accumulator = 0.0;
+        let mouse_position = Point::default();
+        let message_origin = Point::default();
+        let help_message_origin = Point::default();
 
         Self {
             skia_renderer,
@@ -106,6 +142,9 @@ impl<'a> ErrorWindow<'a> {
             modifiers,
             visible,
             mouse_scroll_accumulator,
+            mouse_position,
+            message_origin,
+            help_message_origin,
         }
     }
 
@@ -174,16 +213,64 @@ impl<'a> ErrorWindow<'a> {
                 }
             }
             WindowEvent::ModifiersChanged(modifiers) => self.modifiers = modifiers,
+            WindowEvent::CursorMoved { position, .. } => {
+                self.mouse_position = Point::new(position.x as f32, position.y as f32);
+                let icon = match self.target_at(self.mouse_position) {
+                    Some(_) => CursorIcon::Pointer,
+                    None => CursorIcon::Default,
+                };
+                self.skia_renderer.window().set_cursor_icon(icon);
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => match self.target_at(self.mouse_position) {
+                Some(Target::Button(Button::Quit)) => window_target.exit(),
+                Some(Target::Button(Button::Copy)) => self.copy_message(),
+                Some(Target::Link(url)) => open_url(url),
+                None => {}
+            },
             _ => {}
         }
     }
 
+    fn copy_message(&self) {
+        let _ = clipboard::set_contents(self.message.to_string(), "+");
+    }
+
+    /// Returns the button or link at the given window position.
+    fn target_at(&self, point: Point) -> Option<Target<'_>> {
+        let help_message = &self.paragraphs.help_messages[0];
+        BUTTONS
+            .iter()
+            .find(|(_, range)| {
+                range_rects(help_message, range, self.help_message_origin)
+                    .any(|rect| rect.contains(point))
+            })
+            .map(|(button, _)| Target::Button(*button))
+            .or_else(|| {
+                self.paragraphs
+                    .links
+                    .iter()
+                    .find(|link| {
+                        range_rects(&self.paragraphs.message, &link.range, self.message_origin)
+                            .any(|rect| rect.contains(point))
+                    })
+                    .map(|link| Target::Link(&link.url))
+            })
+    }
+
     fn render(&mut self) {
         let (message_rect, help_message_rect) = self.layout();
 
         let (offset, possible_scroll_direction) =
             self.handle_scrolling(message_rect.height() as f64);
 
+        self.message_origin = Point::new(PADDING, PADDING - offset as f32);
+        self.help_message_origin =
+            Point::new(help_message_rect.left + PADDING, help_message_rect.top);
+
         let canvas = self.skia_renderer.canvas();
         canvas.save();
 
@@ -239,7 +326,7 @@ impl<'a> ErrorWindow<'a> {
                         true
                     }
                     "y" => {
-                        let _ = clipboard::set_contents(self.message.to_string());
+                        self.copy_message();
                         true
                     }
                     _ => false,
@@ -422,6 +509,31 @@ fn render_help_message(message: &Paragraph, canvas: &Canvas, help_message_rect:
         Point::new(help_message_rect.left + PADDING, help_message_rect.top);
     canvas.draw_rect(help_message_rect, &Paint::new(TEXT_COLOR, None));
     message.paint(canvas, help_message_text_point);
+
+    let mut button_paint = Paint::new(BACKGROUND_COLOR, None);
+    button_paint.set_style(PaintStyle::Stroke);
+    button_paint.set_anti_alias(true);
+    for (_, range) in BUTTONS {
+        for rect in range_rects(message, &range, help_message_text_point) {
+            canvas.draw_rrect(RRect::new_rect_xy(rect, 3.0, 3.0), &button_paint);
+        }
+    }
+}
+
+/// Returns the rectangles covered by `range` of a paragraph painted at `origin`.
+fn range_rects(
+    paragraph: &Paragraph,
+    range: &Range<usize>,
+    origin: Point,
+) -> impl Iterator<Item = Rect> {
+    paragraph
+        .get_rects_for_range(range.clone(), RectHeightStyle::Max, RectWidthStyle::Tight)
+        .into_iter()
+        .map(move |text_box| text_box.rect.with_offset(origin))
+}
+
+fn utf16_len(text: &str) -> usize {
+    text.encode_utf16().count()
 }
 
 fn create_paragraphs(
@@ -451,22 +563,50 @@ fn create_paragraphs(
         paragraph_builder.build()
     };
 
-    let message_line = "quit (q), copy (y)";
-
     let help_messages = PossibleScrollDirection::iter()
         .map(|dir| match dir {
-            PossibleScrollDirection::None => message_line.to_owned(),
-            PossibleScrollDirection::Down => message_line.to_owned() + " ↓",
-            PossibleScrollDirection::Up => message_line.to_owned() + " ↑",
-            PossibleScrollDirection::Both => message_line.to_owned() + " ↑↓",
+            PossibleScrollDirection::None => HELP_MESSAGE.to_owned(),
+            PossibleScrollDirection::Down => HELP_MESSAGE.to_owned() + " ↓",
+            PossibleScrollDirection::Up => HELP_MESSAGE.to_owned() + " ↑",
+            PossibleScrollDirection::Both => HELP_MESSAGE.to_owned() + " ↑↓",
         })
         .map(|msg| create_message(&msg, &inverted_text))
         .collect::<Vec<Paragraph>>()
         .try_into()
         .unwrap();
 
+    let mut link_text = normal_text.clone();
+    link_text.set_foreground_paint(&Paint::new(LINK_COLOR, None));
+    link_text.set_decoration_type(TextDecoration::UNDERLINE);
+    link_text.set_decoration_color(LINK_COLOR.to_color());
+
+    // Links are styled differently, and remembered so that they can be clicked
+    let mut links = Vec::new();
+    let mut paragraph_builder = ParagraphBuilder::new(&paragraph_style, font_collection);
+    let mut position = 0;
+    for url in find_urls(message) {
+        paragraph_builder.push_style(&normal_text);
+        paragraph_builder.add_text(&message[position..url.start]);
+        paragraph_builder.pop();
+
+        paragraph_builder.push_style(&link_text);
+        paragraph_builder.add_text(&message[url.clone()]);
+        paragraph_builder.pop();
+
+        let start = utf16_len(&message[..url.start]);
+        links.push(Link {
+            url: message[url.clone()].to_string(),
+            range: start..start + utf16_len(&message[url.clone()]),
+        });
+        position = url.end;
+    }
+    paragraph_builder.push_style(&normal_text);
+    paragraph_builder.add_text(&message[position..]);
+    paragraph_builder.pop();
+
     Paragraphs {
-        message: create_message(message, &normal_text),
+        message: paragraph_builder.build(),
+        links,
         help_messages,
     }
 }
@@ -0,0 +1,131 @@
+use std::{collections::VecDeque, sync::Mutex, time::Instant};
+
+use rmpv::Value;
+
+/// The number of measurements kept of each kind.
+const HISTORY_SIZE: usize = 240;
+
+/// Frame times and latencies in milliseconds, returned by the `neovide.get_timings` request so
+/// that the effect of settings like vsync or animations can be measured.
+#[derive(Default)]
+pub struct FrameTimings {
+    frame_times: VecDeque<f32>,
+    draw_latencies: VecDeque<f32>,
+    key_latencies: VecDeque<f32>,
+    last_present: Option<Instant>,
+    /// When the first draw commands since the last present were received.
+    first_draw: Option<Instant>,
+    /// A key sent to Neovim, which hasn't been answered with draw commands yet.
+    key_sent: Option<Instant>,
+    /// A key answered with draw commands, which are shown by the next present.
+    key_drawn: Option<Instant>,
+}
+
+fn push(history: &mut VecDeque<f32>, start: Instant, end: Instant) {
+    if history.len() == HISTORY_SIZE {
+        history.pop_front();
+    }
+    history.push_back(end.duration_since(start).as_micros() as f32 / 1000.0);
+}
+
+impl FrameTimings {
+    /// Only the first key is timed until Neovim answers, so that fast typing measures the latency
+    /// of the oldest key.
+    pub fn key_sent(&mut self, now: Instant) {
+        self.key_sent.get_or_insert(now);
+    }
+
+    pub fn draw_commands_received(&mut self, now: Instant) {
+        self.first_draw.get_or_insert(now);
+        if let Some(key_sent) = self.key_sent.take() {
+            self.key_drawn.get_or_insert(key_sent);
+        }
+    }
+
+    /// The update loop stopped rendering until something changes, so the time until the next
+    /// present is idle time, not a frame time.
+    pub fn rendering_paused(&mut self) {
+        self.last_present = None;
+    }
+
+    pub fn frame_presented(&mut self, now: Instant) {
+        if let Some(last_present) = self.last_present.replace(now) {
+            push(&mut self.frame_times, last_present, now);
+        }
+        if let Some(first_draw) = self.first_draw.take() {
+            push(&mut self.draw_latencies, first_draw, now);
+        }
+        if let Some(key_drawn) = self.key_drawn.take() {
+            push(&mut self.key_latencies, key_drawn, now);
+        }
+    }
+
+    /// The last `count` measurements of each kind, oldest first.
+    pub fn to_value(&self, count: usize) -> Value {
+        let last = |history: &VecDeque<f32>| {
+            Value::from(
+                history
+                    .iter()
+                    .skip(history.len().saturating_sub(count))
+                    .map(|&time| Value::from(time))
+                    .collect::<Vec<_>>(),
+            )
+        };
+        Value::from(vec![
+            (Value::from("frame_times"), last(&self.frame_times)),
+            (Value::from("draw_latencies"), last(&self.draw_latencies)),
+            (Value::from("key_latencies"), last(&self.key_latencies)),
+        ])
+    }
+}
+
+lazy_static! {
+    // Written by the window thread, and read when Neovim requests the timings
+    pub static ref FRAME_TIMINGS: Mutex<FrameTimings> = Mutex::new(FrameTimings::default());
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn test_key_latency_is_measured_to_the_present_after_the_answer() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut timings = FrameTimings::default();
+
+        timings.frame_presented(at(0));
+        timings.key_sent(at(2));
+        timings.key_sent(at(4));
+        // A frame presented before Neovim answers doesn't end the measurement
+        timings.frame_presented(at(16));
+        timings.draw_commands_received(at(20));
+        timings.frame_presented(at(32));
+        // The wait while idle is not a frame time
+        timings.rendering_paused();
+        timings.frame_presented(at(1000));
+
+        assert_eq!(timings.frame_times, [16.0, 16.0]);
+        assert_eq!(timings.draw_latencies, [12.0]);
+        assert_eq!(timings.key_latencies, [30.0]);
+        assert_eq!(
+            timings.to_value(1),
+            Value::from(vec![
+                (
+                    Value::from("frame_times"),
+                    Value::from(vec![Value::from(16.0f32)])
+                ),
+                (
+                    Value::from("draw_latencies"),
+                    Value::from(vec![Value::from(12.0f32)])
+                ),
+                (
+                    Value::from("key_latencies"),
+                    Value::from(vec![Value::from(30.0f32)])
+                ),
+            ])
+        );
+    }
+}
@@ -0,0 +1,87 @@
+use log::error;
+use rmpv::Value;
+use winit::monitor::{MonitorHandle, VideoMode};
+
+use crate::settings::ParseFromValue;
+
+/// How the window covers the screen when it's made fullscreen.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum FullscreenMode {
+    /// The fullscreen of the platform, which on macOS animates the window into its own space.
+    #[default]
+    Native,
+    /// Covers the whole screen without an animation. On macOS, the window stays in the current
+    /// space and the menu bar and the dock are shown when the mouse reaches them.
+    Borderless,
+    /// The pre-Lion fullscreen of macOS, which hides the menu bar and the dock. The same as
+    /// borderless on the other platforms.
+    Simple,
+    /// Takes over the display with the video mode of the monitor, mostly useful on Windows,
+    /// where it bypasses the compositor.
+    Exclusive,
+}
+
+impl ParseFromValue for FullscreenMode {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(mode) = value.as_str() {
+            *self = match mode {
+                "native" => FullscreenMode::Native,
+                "borderless" => FullscreenMode::Borderless,
+                "simple" => FullscreenMode::Simple,
+                "exclusive" => FullscreenMode::Exclusive,
+                value => {
+                    error!(
+                        "Expected native, borderless, simple or exclusive, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!(
+                "Expected a fullscreen mode string, but received {:?}",
+                value
+            );
+        }
+    }
+}
+
+impl From<FullscreenMode> for Value {
+    fn from(mode: FullscreenMode) -> Self {
+        match mode {
+            FullscreenMode::Native => Value::from("native"),
+            FullscreenMode::Borderless => Value::from("borderless"),
+            FullscreenMode::Simple => Value::from("simple"),
+            FullscreenMode::Exclusive => Value::from("exclusive"),
+        }
+    }
+}
+
+/// The video mode used for exclusive fullscreen, the one with the resolution of the monitor and
+/// the highest refresh rate, so that the layout doesn't change.
+pub fn exclusive_video_mode(monitor: &MonitorHandle) -> Option<VideoMode> {
+    let size = monitor.size();
+    monitor
+        .video_modes()
+        .filter(|mode| mode.size() == size)
+        .max_by_key(|mode| (mode.refresh_rate_millihertz(), mode.bit_depth()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_fullscreen_mode() {
+        let mut mode = FullscreenMode::default();
+        mode.parse_from_value(Value::from("simple"));
+        assert_eq!(mode, FullscreenMode::Simple);
+        // Unknown modes keep the previous one
+        mode.parse_from_value(Value::from("animated"));
+        assert_eq!(mode, FullscreenMode::Simple);
+        assert_eq!(
+            Value::from(FullscreenMode::Exclusive),
+            Value::from("exclusive")
+        );
+    }
+}
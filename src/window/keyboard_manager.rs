@@ -1,24 +1,119 @@
+use std::{env, time::Instant};
+
 use crate::bridge::{send_ui, SerialCommand};
+use crate::dimensions::Dimensions;
 
-use crate::window::UserEvent;
-#[cfg(target_os = "macos")]
-use crate::{settings::SETTINGS, window::WindowSettings};
+use log::error;
+use rmpv::Value;
+use skia_safe::Point;
+
+use crate::settings::{ParseFromValue, SETTINGS};
+use crate::window::{
+    compose::{ComposeState, DeadKeyResult},
+    shortcuts, ShortcutAction, UserEvent, WindowSettings, FRAME_TIMINGS,
+};
 #[allow(unused_imports)]
 use winit::platform::modifier_supplement::KeyEventExtModifierSupplement;
 use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
     event::{ElementState, Event, Ime, KeyEvent, Modifiers, WindowEvent},
     keyboard::{Key, KeyCode, KeyLocation, NamedKey, PhysicalKey},
 };
 
+#[cfg(target_os = "macos")]
+use winit::{keyboard::ModifiersKeyState, platform::macos::OptionAsAlt};
+
 use crate::profiling::tracy_named_frame;
 
+/// Which of the macOS option keys are interpreted as meta instead of producing special characters.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum OptionAsMeta {
+    Both,
+    OnlyLeft,
+    OnlyRight,
+    #[default]
+    None,
+}
+
+impl OptionAsMeta {
+    /// The configured behavior, taking the older `input_macos_alt_is_meta` setting into account.
+    #[allow(dead_code)] // Only used on macOS
+    pub fn from_settings(settings: &WindowSettings) -> Self {
+        match settings.input_macos_option_key_is_meta {
+            OptionAsMeta::None if settings.input_macos_alt_is_meta => OptionAsMeta::Both,
+            option_as_meta => option_as_meta,
+        }
+    }
+}
+
+impl ParseFromValue for OptionAsMeta {
+    fn parse_from_value(&mut self, value: Value) {
+        if value.is_str() {
+            *self = match value.as_str().unwrap() {
+                "both" => OptionAsMeta::Both,
+                "only_left" => OptionAsMeta::OnlyLeft,
+                "only_right" => OptionAsMeta::OnlyRight,
+                "none" => OptionAsMeta::None,
+                value => {
+                    error!(
+                        "Expected both, only_left, only_right or none, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!("Expected an option key string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<OptionAsMeta> for Value {
+    fn from(option_as_meta: OptionAsMeta) -> Self {
+        match option_as_meta {
+            OptionAsMeta::Both => Value::from("both"),
+            OptionAsMeta::OnlyLeft => Value::from("only_left"),
+            OptionAsMeta::OnlyRight => Value::from("only_right"),
+            OptionAsMeta::None => Value::from("none"),
+        }
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl From<OptionAsMeta> for OptionAsAlt {
+    fn from(option_as_meta: OptionAsMeta) -> Self {
+        match option_as_meta {
+            OptionAsMeta::Both => OptionAsAlt::Both,
+            OptionAsMeta::OnlyLeft => OptionAsAlt::OnlyLeft,
+            OptionAsMeta::OnlyRight => OptionAsAlt::OnlyRight,
+            OptionAsMeta::None => OptionAsAlt::None,
+        }
+    }
+}
+
 fn is_ascii_alphabetic_char(text: &str) -> bool {
     text.len() == 1 && text.chars().next().unwrap().is_ascii_alphabetic()
 }
 
+/// The width of the area the IME candidate window is placed under, on platforms where it's
+/// positioned relative to its top left corner.
+const IME_AREA_WIDTH: u32 = 100;
+
 pub struct KeyboardManager {
     modifiers: Modifiers,
     ime_preedit: (String, Option<(usize, usize)>),
+    compose: ComposeState,
+    shortcut_action: Option<ShortcutAction>,
+    /// While the command palette is open, the keys are kept for it instead of being sent to Neovim.
+    captured: bool,
+    captured_keys: Vec<String>,
+    /// Neovim is not responding, so the interrupt and kill chords are handled by Neovide.
+    neovim_busy: bool,
+    /// The IME uses `zwp_text_input_v3`, which places the candidate window next to the cursor
+    /// rectangle. Only the cursor rectangle is sent, winit doesn't expose `set_surrounding_text`,
+    /// so input methods that read the text around the cursor, like the ones reconverting a word,
+    /// see an empty line.
+    wayland_ime: bool,
 }
 
 impl KeyboardManager {
@@ -26,10 +121,71 @@ impl KeyboardManager {
         KeyboardManager {
             modifiers: Modifiers::default(),
             ime_preedit: ("".to_string(), None),
+            compose: ComposeState::new(),
+            shortcut_action: None,
+            captured: false,
+            captured_keys: Vec::new(),
+            neovim_busy: false,
+            wayland_ime: cfg!(target_os = "linux") && env::var("WAYLAND_DISPLAY").is_ok(),
         }
     }
 
-    pub fn handle_event(&mut self, event: &Event<UserEvent>) {
+    /// The text being composed with the input method, and the byte range of the clause that is
+    /// being converted.
+    pub fn ime_preedit(&self) -> (&str, Option<(usize, usize)>) {
+        (&self.ime_preedit.0, self.ime_preedit.1)
+    }
+
+    /// The area the IME candidate window is placed at, for the cursor at the given position.
+    ///
+    /// On Wayland the area is the cursor rectangle of `zwp_text_input_v3`, which the input method,
+    /// like fcitx5, places its popup below, so it has to be the cursor cell itself. It's sent
+    /// again every frame the cursor moves, since the compositor doesn't track the text. Elsewhere
+    /// the candidate window is placed at the top left corner, so the area starts below the cursor.
+    pub fn ime_area(
+        &self,
+        cursor_position: Point,
+        font_dimensions: Dimensions,
+    ) -> (PhysicalPosition<i32>, PhysicalSize<u32>) {
+        let x = cursor_position.x.round() as i32;
+        let y = cursor_position.y.round() as i32;
+        let height = font_dimensions.height as u32;
+        if self.wayland_ime {
+            (
+                PhysicalPosition::new(x, y),
+                PhysicalSize::new(font_dimensions.width as u32, height),
+            )
+        } else {
+            (
+                PhysicalPosition::new(x, y + height as i32),
+                PhysicalSize::new(IME_AREA_WIDTH, height),
+            )
+        }
+    }
+
+    /// Keeps the keys instead of sending them to Neovim, until it's called again with false.
+    pub fn set_captured(&mut self, captured: bool) {
+        self.captured = captured;
+        self.captured_keys.clear();
+    }
+
+    pub fn set_neovim_busy(&mut self, busy: bool) {
+        self.neovim_busy = busy;
+    }
+
+    /// Returns the keys pressed while the input was captured, in the Neovim notation.
+    pub fn take_captured_keys(&mut self) -> Vec<String> {
+        std::mem::take(&mut self.captured_keys)
+    }
+
+    /// Returns the window action of the last shortcut that was pressed.
+    pub fn take_shortcut_action(&mut self) -> Option<ShortcutAction> {
+        self.shortcut_action.take()
+    }
+
+    /// Handles keyboard related events, and returns the keys that were sent to Neovim, so that
+    /// they can be echoed locally. Special and modified keys are returned in `<>` notation.
+    pub fn handle_event(&mut self, event: &Event<UserEvent>) -> Option<String> {
         match event {
             Event::WindowEvent {
                 event:
@@ -41,11 +197,32 @@ impl KeyboardManager {
                 ..
             } if self.ime_preedit.0.is_empty() => {
                 log::trace!("{:#?}", key_event);
+                let settings = SETTINGS.get::<WindowSettings>();
+                if settings.input_raw_key_events && !self.captured {
+                    self.send_raw_key_event(key_event);
+                }
                 if key_event.state == ElementState::Pressed {
                     if let Some(text) = self.format_key(key_event) {
+                        // Shortcuts are resolved before Neovim sees the keys
+                        let busy_action = || {
+                            self.neovim_busy
+                                .then(|| shortcuts::busy_shortcut(&text))
+                                .flatten()
+                        };
+                        if let Some(action) = settings.shortcuts.get(&text).or_else(busy_action) {
+                            log::trace!("Shortcut {} {:?}", text, action);
+                            self.shortcut_action = Some(action);
+                            return None;
+                        }
+                        if self.captured {
+                            self.captured_keys.push(text);
+                            return None;
+                        }
                         log::trace!("Key pressed {} {:?}", text, self.modifiers.state());
                         tracy_named_frame!("keyboard input");
-                        send_ui(SerialCommand::Keyboard(text));
+                        FRAME_TIMINGS.lock().unwrap().key_sent(Instant::now());
+                        send_ui(SerialCommand::Keyboard(text.clone()));
+                        return Some(text);
                     }
                 }
             }
@@ -54,12 +231,30 @@ impl KeyboardManager {
                 ..
             } => {
                 log::trace!("Ime commit {text}");
+                if self.captured {
+                    self.captured_keys.push(text.to_string());
+                    return None;
+                }
+                FRAME_TIMINGS.lock().unwrap().key_sent(Instant::now());
                 send_ui(SerialCommand::Keyboard(text.to_string()));
+                return Some(text.to_string());
             }
             Event::WindowEvent {
                 event: WindowEvent::Ime(Ime::Preedit(text, cursor_offset)),
                 ..
             } => self.ime_preedit = (text.to_string(), *cursor_offset),
+            // An input method that goes away in the middle of composing, for example when the
+            // window loses focus on Wayland, doesn't always clear the preedit, which would block
+            // the keyboard input
+            Event::WindowEvent {
+                event: WindowEvent::Ime(Ime::Disabled) | WindowEvent::Focused(false),
+                ..
+            } => {
+                if !self.ime_preedit.0.is_empty() {
+                    log::trace!("Ime preedit {:?} discarded", self.ime_preedit.0);
+                }
+                self.ime_preedit = ("".to_string(), None);
+            }
             Event::WindowEvent {
                 event: WindowEvent::ModifiersChanged(modifiers),
                 ..
@@ -71,6 +266,34 @@ impl KeyboardManager {
             }
             _ => {}
         }
+        None
+    }
+
+    fn send_raw_key_event(&self, key_event: &KeyEvent) {
+        // Use the W3C names of the physical keys, for example "KeyA" or "NumpadEnter"
+        let code = match key_event.physical_key {
+            PhysicalKey::Code(code) => format!("{code:?}"),
+            PhysicalKey::Unidentified(..) => "Unidentified".to_string(),
+        };
+        let key = match &key_event.logical_key {
+            Key::Character(text) => text.to_string(),
+            Key::Named(named) => format!("{named:?}"),
+            _ => String::new(),
+        };
+        let location = match key_event.location {
+            KeyLocation::Standard => "standard",
+            KeyLocation::Left => "left",
+            KeyLocation::Right => "right",
+            KeyLocation::Numpad => "numpad",
+        };
+        send_ui(SerialCommand::KeyEvent {
+            code,
+            key,
+            location: location.to_string(),
+            pressed: key_event.state == ElementState::Pressed,
+            repeat: key_event.repeat,
+            modifier_string: self.format_modifier_string("", true),
+        });
     }
 
     fn handle_numpad_numkey<'a>(
@@ -142,34 +365,51 @@ impl KeyboardManager {
         }
     }
 
-    fn format_key(&self, key_event: &KeyEvent) -> Option<String> {
+    fn format_key(&mut self, key_event: &KeyEvent) -> Option<String> {
         if let Some(text) = get_special_key(key_event) {
+            self.compose.reset();
             Some(self.format_key_text(text, true))
         } else {
             self.format_normal_key(key_event)
         }
     }
 
-    fn format_normal_key(&self, key_event: &KeyEvent) -> Option<String> {
+    fn format_normal_key(&mut self, key_event: &KeyEvent) -> Option<String> {
+        let compose_mode = SETTINGS.get::<WindowSettings>().input_compose;
+        let option_is_meta = cfg!(target_os = "macos")
+            && self.modifiers.state().alt_key()
+            && use_alt(&self.modifiers);
+        if let Key::Dead(dead) = key_event.logical_key {
+            match self
+                .compose
+                .handle_dead_key(compose_mode, dead, option_is_meta)
+            {
+                DeadKeyResult::Send(text) => return Some(self.format_key_text(&text, false)),
+                DeadKeyResult::Pending => return None,
+                DeadKeyResult::PassThrough => {}
+            }
+        }
+
         // On macOS, when alt is held and alt_is_meta is set to true, then send the base key plus
         // the whole modifier state. Otherwise send the resulting character with "S-" and "M-"
         // removed.
         #[cfg(target_os = "macos")]
-        if self.modifiers.state().alt_key() && use_alt() {
+        if option_is_meta {
             return key_event
                 .key_without_modifiers()
                 .to_text()
                 .map(|text| self.format_key_text(text, true));
         }
 
-        key_event
-            .text
-            .as_ref()
-            .or(match &key_event.logical_key {
-                Key::Character(text) => Some(text),
-                _ => None,
-            })
-            .map(|text| self.format_key_text(text.as_str(), false))
+        let base = match &key_event.logical_key {
+            Key::Character(text) => Some(text),
+            _ => None,
+        };
+        let text = key_event.text.as_ref().or(base)?;
+        let text =
+            self.compose
+                .handle_text(compose_mode, text.as_str(), base.unwrap_or(text).as_str());
+        Some(self.format_key_text(&text, false))
     }
 
     fn format_key_text(&self, text: &str, is_special: bool) -> String {
@@ -200,6 +440,15 @@ impl KeyboardManager {
         }
     }
 
+    /// Whether the modifier used for opening hyperlinks, Cmd on macOS and Ctrl elsewhere, is held.
+    pub fn is_hyperlink_modifier_pressed(&self) -> bool {
+        if cfg!(target_os = "macos") {
+            self.modifiers.state().super_key()
+        } else {
+            self.modifiers.state().control_key()
+        }
+    }
+
     pub fn format_modifier_string(&self, text: &str, is_special: bool) -> String {
         // Shift should always be sent together with special keys (Enter, Space, F keys and so on).
         // And as a special case together with CTRL and standard a-z characters.
@@ -216,7 +465,7 @@ impl KeyboardManager {
             is_special || (self.modifiers.state().control_key() && is_ascii_alphabetic_char(text));
 
         // Always send meta (alt) together with special keys, or when alt is meta on macOS
-        let include_alt = use_alt() || is_special;
+        let include_alt = use_alt(&self.modifiers) || is_special;
 
         let state = self.modifiers.state();
         let mut ret = String::new();
@@ -229,16 +478,21 @@ impl KeyboardManager {
 }
 
 #[cfg(not(target_os = "macos"))]
-fn use_alt() -> bool {
+fn use_alt(_modifiers: &Modifiers) -> bool {
     true
 }
 
 // The option or alt key is used on macOS for character set changes
 // and does not operate the same as other systems.
 #[cfg(target_os = "macos")]
-fn use_alt() -> bool {
+fn use_alt(modifiers: &Modifiers) -> bool {
     let settings = SETTINGS.get::<WindowSettings>();
-    settings.input_macos_alt_is_meta
+    match OptionAsMeta::from_settings(&settings) {
+        OptionAsMeta::Both => true,
+        OptionAsMeta::OnlyLeft => modifiers.lalt_state() == ModifiersKeyState::Pressed,
+        OptionAsMeta::OnlyRight => modifiers.ralt_state() == ModifiersKeyState::Pressed,
+        OptionAsMeta::None => false,
+    }
 }
 
 fn get_special_key(key_event: &KeyEvent) -> Option<&str> {
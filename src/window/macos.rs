@@ -1,31 +1,49 @@
 use icrate::{
     AppKit::{
-        NSApplication, NSColor, NSEvent, NSEventModifierFlagCommand, NSEventModifierFlagControl,
-        NSEventModifierFlagOption, NSMenu, NSMenuItem, NSView, NSViewMinYMargin,
-        NSViewWidthSizable, NSWindow, NSWindowStyleMaskFullScreen, NSWindowStyleMaskTitled,
+        NSApplication, NSApplicationPresentationAutoHideDock,
+        NSApplicationPresentationAutoHideMenuBar, NSApplicationPresentationDefault,
+        NSBackingStoreBuffered, NSColor, NSEvent, NSEventModifierFlagCommand,
+        NSEventModifierFlagControl, NSEventModifierFlagOption, NSMenu, NSMenuItem, NSScreen,
+        NSView, NSViewMinYMargin, NSViewWidthSizable, NSWindow, NSWindowStyleMask,
+        NSWindowStyleMaskBorderless, NSWindowStyleMaskFullScreen, NSWindowStyleMaskTitled,
         NSWindowTabbingModeDisallowed,
     },
-    Foundation::{MainThreadMarker, NSObject, NSPoint, NSProcessInfo, NSRect, NSSize, NSString},
+    Foundation::{
+        MainThreadMarker, NSArray, NSObject, NSPoint, NSProcessInfo, NSRect, NSSize, NSString,
+    },
 };
 use objc2::{
-    declare_class, msg_send_id,
+    declare_class, msg_send, msg_send_id,
     mutability::InteriorMutable,
-    rc::Id,
-    runtime::{AnyClass, AnyObject},
+    rc::{Allocated, Id},
+    runtime::{AnyClass, AnyObject, Sel},
     sel, ClassType,
 };
 
+use std::{process::Command, ptr, thread};
+
 use csscolorparser::Color;
+use parking_lot::Mutex;
 use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use winit::event::{Event, WindowEvent};
+use winit::event_loop::EventLoopProxy;
+use winit::platform::macos::WindowExtMacOS;
 use winit::window::Window;
 
-use crate::bridge::{send_ui, ParallelCommand};
+use crate::bridge::{send_ui, EditorMode, FileOpenTarget, ParallelCommand, SerialCommand};
 use crate::{
-    cmd_line::CmdLineSettings, error_msg, frame::Frame, settings::SETTINGS, window::UserEvent,
+    cmd_line::CmdLineSettings,
+    error_msg,
+    frame::Frame,
+    renderer::{DrawCommand, OutputColorSpace},
+    settings::SETTINGS,
+    window::UserEvent,
 };
 
-use super::{WindowSettings, WindowSettingsChanged};
+use super::{
+    run_menu_command, shortcuts, OptionAsMeta, Progress, UserMenuItem, WindowCommand,
+    WindowSettings, WindowSettingsChanged,
+};
 
 declare_class!(
     // A view to simulate the double-click-to-zoom effect for `--frame transparency`.
@@ -64,6 +82,11 @@ pub struct MacosWindowFeature {
     // Extra titlebar height in --frame transparency. 0 in other cases.
     extra_titlebar_height_in_pixel: u32,
     is_fullscreen: bool,
+    context_menu_handler: Id<MenuHandler>,
+    // The windows dimming the other screens in zen mode
+    screen_covers: Vec<Id<NSWindow>>,
+    // The frame and the style of the window before it entered borderless fullscreen
+    borderless_restore: Option<(NSRect, NSWindowStyleMask)>,
 }
 
 impl MacosWindowFeature {
@@ -116,13 +139,24 @@ impl MacosWindowFeature {
             titlebar_click_handler,
             extra_titlebar_height_in_pixel,
             is_fullscreen,
+            context_menu_handler: MenuHandler::new(mtm),
+            screen_covers: Vec::new(),
+            borderless_restore: None,
         };
 
         macos_window_feature.update_background(window, true);
+        Self::update_option_as_alt(window);
 
         macos_window_feature
     }
 
+    // Let winit deliver the unmodified characters for the option keys that act as meta, so that
+    // they don't start dead key sequences.
+    fn update_option_as_alt(window: &Window) {
+        let settings = SETTINGS.get::<WindowSettings>();
+        window.set_option_as_alt(OptionAsMeta::from_settings(&settings).into());
+    }
+
     // Used to calculate the value of TITLEBAR_HEIGHT, aka, titlebar height in dpi-independent length.
     fn titlebar_height() -> f64 {
         // Do a test to calculate this.
@@ -156,8 +190,9 @@ impl MacosWindowFeature {
     }
 
     pub fn handle_size_changed(&mut self) {
-        let is_fullscreen =
-            unsafe { self.ns_window.styleMask() } & NSWindowStyleMaskFullScreen != 0;
+        let is_fullscreen = unsafe { self.ns_window.styleMask() } & NSWindowStyleMaskFullScreen
+            != 0
+            || self.borderless_restore.is_some();
         if is_fullscreen != self.is_fullscreen {
             self.is_fullscreen = is_fullscreen;
             self.set_titlebar_click_handler_visible(!is_fullscreen);
@@ -173,6 +208,104 @@ impl MacosWindowFeature {
         }
     }
 
+    /// Shows the user defined context menu at the mouse position. The menu is modal, so this
+    /// returns only after it's closed.
+    pub fn show_context_menu(&self, items: &[UserMenuItem]) {
+        unsafe {
+            let menu = NSMenu::new();
+            let mut commands = CONTEXT_MENU_COMMANDS.lock();
+            commands.clear();
+            for item in items {
+                match &item.command {
+                    Some(command) => {
+                        let menu_item = menu_item(
+                            &item.title,
+                            sel!(contextItem:),
+                            "",
+                            &self.context_menu_handler,
+                        );
+                        menu_item.setTag(commands.len() as isize);
+                        commands.push(command.clone());
+                        menu.addItem(&menu_item);
+                    }
+                    None => menu.addItem(&NSMenuItem::separatorItem()),
+                }
+            }
+            // The handler needs the commands while the menu is open
+            drop(commands);
+            menu.popUpMenuPositioningItem_atLocation_inView(None, NSEvent::mouseLocation(), None);
+        }
+    }
+
+    /// Covers the screen of the window without the animation and the separate space of the native
+    /// fullscreen. The menu bar and the dock are hidden until the mouse reaches them.
+    pub fn set_borderless_fullscreen(&mut self, enabled: bool) {
+        unsafe {
+            let app = NSApplication::sharedApplication();
+            if enabled {
+                let Some(screen) = self.ns_window.screen() else {
+                    return;
+                };
+                if self.borderless_restore.is_none() {
+                    self.borderless_restore =
+                        Some((self.ns_window.frame(), self.ns_window.styleMask()));
+                }
+                let frame: NSRect = msg_send![&*screen, frame];
+                app.setPresentationOptions(
+                    NSApplicationPresentationAutoHideDock
+                        | NSApplicationPresentationAutoHideMenuBar,
+                );
+                self.ns_window.setStyleMask(NSWindowStyleMaskBorderless);
+                self.ns_window.setFrame_display(frame, true);
+            } else if let Some((frame, style_mask)) = self.borderless_restore.take() {
+                app.setPresentationOptions(NSApplicationPresentationDefault);
+                self.ns_window.setStyleMask(style_mask);
+                self.ns_window.setFrame_display(frame, true);
+            }
+        }
+        self.handle_size_changed();
+    }
+
+    /// Dims all screens except the one of the window by covering them with a dark, semi
+    /// transparent window, or removes the covers.
+    pub fn set_other_screens_dimmed(&mut self, dimmed: bool) {
+        unsafe {
+            for cover in self.screen_covers.drain(..) {
+                cover.close();
+            }
+            if !dimmed {
+                return;
+            }
+            let current_screen = self.ns_window.screen();
+            let screens: Id<NSArray<NSScreen>> = msg_send_id![NSScreen::class(), screens];
+            for screen in screens.iter() {
+                if current_screen
+                    .as_deref()
+                    .is_some_and(|current_screen| ptr::eq(current_screen, screen))
+                {
+                    continue;
+                }
+                let frame: NSRect = msg_send![screen, frame];
+                let cover: Option<Allocated<NSWindow>> = msg_send_id![NSWindow::class(), alloc];
+                let cover: Id<NSWindow> = msg_send_id![
+                    cover,
+                    initWithContentRect: frame,
+                    styleMask: NSWindowStyleMaskBorderless,
+                    backing: NSBackingStoreBuffered,
+                    defer: false,
+                ];
+                // The covers are owned by the Id, so AppKit must not release them on close
+                cover.setReleasedWhenClosed(false);
+                cover.setOpaque(false);
+                cover.setBackgroundColor(Some(&NSColor::colorWithSRGBRed_green_blue_alpha(
+                    0.0, 0.0, 0.0, 0.8,
+                )));
+                cover.orderFront(None);
+                self.screen_covers.push(cover);
+            }
+        }
+    }
+
     /// Print a deprecation warning for `neovide_background_color`
     pub fn display_deprecation_warning(&self) {
         error_msg!(concat!(
@@ -260,56 +393,246 @@ impl MacosWindowFeature {
                 log::info!("window_blurred changed to {}", window_blurred);
                 self.update_background(window, true);
             }
+            WindowSettingsChanged::InputMacosAltIsMeta(..)
+            | WindowSettingsChanged::InputMacosOptionKeyIsMeta(..) => {
+                Self::update_option_as_alt(window);
+            }
             _ => {}
         }
     }
 }
 
 declare_class!(
-    struct QuitHandler;
+    // The target of the menu bar items.
+    struct MenuHandler;
 
-    unsafe impl ClassType for QuitHandler {
+    unsafe impl ClassType for MenuHandler {
         type Super = NSObject;
         type Mutability = InteriorMutable;
-        const NAME: &'static str = "QuitHandler";
+        const NAME: &'static str = "NeovideMenuHandler";
     }
 
-    unsafe impl QuitHandler {
+    unsafe impl MenuHandler {
         #[method(quit:)]
-        unsafe fn quit(&self, _event: &NSEvent) {
+        unsafe fn quit(&self, _sender: &NSMenuItem) {
             send_ui(ParallelCommand::Quit);
         }
+
+        #[method(newWindow:)]
+        unsafe fn new_window(&self, _sender: &NSMenuItem) {
+            shortcuts::open_new_window();
+        }
+
+        #[method(openFile:)]
+        unsafe fn open_file(&self, _sender: &NSMenuItem) {
+            choose_file_to_open();
+        }
+
+        #[method(neovideUndo:)]
+        unsafe fn undo(&self, _sender: &NSMenuItem) {
+            send_keys("<Cmd>undo<CR>");
+        }
+
+        #[method(neovideRedo:)]
+        unsafe fn redo(&self, _sender: &NSMenuItem) {
+            send_keys("<Cmd>redo<CR>");
+        }
+
+        #[method(neovideCut:)]
+        unsafe fn cut(&self, _sender: &NSMenuItem) {
+            send_keys("\"+d");
+        }
+
+        #[method(neovideCopy:)]
+        unsafe fn copy(&self, _sender: &NSMenuItem) {
+            send_keys("\"+y");
+        }
+
+        #[method(neovidePaste:)]
+        unsafe fn paste(&self, _sender: &NSMenuItem) {
+            shortcuts::paste_clipboard();
+        }
+
+        #[method(neovideSelectAll:)]
+        unsafe fn select_all(&self, _sender: &NSMenuItem) {
+            send_keys("<C-\\><C-N>ggVG");
+        }
+
+        #[method(zoomIn:)]
+        unsafe fn zoom_in(&self, _sender: &NSMenuItem) {
+            shortcuts::zoom_in();
+        }
+
+        #[method(zoomOut:)]
+        unsafe fn zoom_out(&self, _sender: &NSMenuItem) {
+            shortcuts::zoom_out();
+        }
+
+        #[method(actualSize:)]
+        unsafe fn actual_size(&self, _sender: &NSMenuItem) {
+            shortcuts::zoom(None);
+        }
+
+        #[method(newTab:)]
+        unsafe fn new_tab(&self, _sender: &NSMenuItem) {
+            send_keys("<Cmd>tabnew<CR>");
+        }
+
+        #[method(nextTab:)]
+        unsafe fn next_tab(&self, _sender: &NSMenuItem) {
+            send_keys("<Cmd>tabnext<CR>");
+        }
+
+        #[method(previousTab:)]
+        unsafe fn previous_tab(&self, _sender: &NSMenuItem) {
+            send_keys("<Cmd>tabprevious<CR>");
+        }
+
+        #[method(userItem:)]
+        unsafe fn user_item(&self, sender: &NSMenuItem) {
+            let command = USER_MENU_COMMANDS.lock().get(sender.tag() as usize).cloned();
+            if let Some(command) = command {
+                run_menu_command(&command);
+            }
+        }
+
+        #[method(contextItem:)]
+        unsafe fn context_item(&self, sender: &NSMenuItem) {
+            let command = CONTEXT_MENU_COMMANDS
+                .lock()
+                .get(sender.tag() as usize)
+                .cloned();
+            if let Some(command) = command {
+                run_menu_command(&command);
+            }
+        }
     }
 );
 
-impl QuitHandler {
-    pub fn new(_mtm: MainThreadMarker) -> Id<QuitHandler> {
+impl MenuHandler {
+    pub fn new(_mtm: MainThreadMarker) -> Id<MenuHandler> {
         unsafe { msg_send_id![Self::alloc(), init] }
     }
 }
 
+lazy_static! {
+    // The commands of the user defined items, indexed by the tag of the item.
+    static ref USER_MENU_COMMANDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    // The commands of the context menu items, indexed by the tag of the item.
+    static ref CONTEXT_MENU_COMMANDS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    // The delegate method can't capture, so the proxy of the open files handler is kept here
+    static ref OPEN_FILES_PROXY: Mutex<Option<EventLoopProxy<UserEvent>>> = Mutex::new(None);
+}
+
+/// The menu user defined items without a `menu` are added to.
+const DEFAULT_USER_MENU: &str = "Tools";
+
+fn send_keys(keys: &str) {
+    send_ui(SerialCommand::Keyboard(keys.to_string()));
+}
+
+/// Shows the standard file dialog, and opens the chosen file in a new tab.
+fn choose_file_to_open() {
+    thread::spawn(|| {
+        let output = Command::new("osascript")
+            .args(["-e", "POSIX path of (choose file)"])
+            .output();
+        match output {
+            Ok(output) if output.status.success() => {
+                let path = String::from_utf8_lossy(&output.stdout).trim().to_string();
+                if !path.is_empty() {
+                    send_ui(ParallelCommand::FileDrop {
+                        path,
+                        target: FileOpenTarget::Tabs,
+                    });
+                }
+            }
+            // Cancelling the dialog is reported as a failure
+            Ok(_) => {}
+            Err(error) => log::error!("Could not show the file dialog: {error}"),
+        }
+    });
+}
+
+unsafe fn menu_item(
+    title: &str,
+    action: Sel,
+    key_equivalent: &str,
+    target: &MenuHandler,
+) -> Id<NSMenuItem> {
+    let item = NSMenuItem::new();
+    item.setTitle(&NSString::from_str(title));
+    item.setAction(Some(action));
+    item.setKeyEquivalent(&NSString::from_str(key_equivalent));
+    item.setTarget(Some(target));
+    item
+}
+
+unsafe fn submenu(title: &str) -> (Id<NSMenu>, Id<NSMenuItem>) {
+    let menu = NSMenu::new();
+    menu.setTitle(&NSString::from_str(title));
+    let item = NSMenuItem::new();
+    item.setTitle(&NSString::from_str(title));
+    item.setSubmenu(Some(&menu));
+    (menu, item)
+}
+
 pub struct Menu {
     menu_added: bool,
-    quit_handler: Id<QuitHandler>,
+    handler: Id<MenuHandler>,
+    main_menu: Option<Id<NSMenu>>,
+    // The items that are only enabled in visual mode
+    selection_items: Vec<Id<NSMenuItem>>,
+    user_items: Vec<UserMenuItem>,
+    // The added user items and the menus they were added to, and the menus created for them
+    added_user_items: Vec<(Id<NSMenu>, Id<NSMenuItem>)>,
+    added_user_menus: Vec<Id<NSMenuItem>>,
 }
 
 impl Menu {
     pub fn new(mtm: MainThreadMarker) -> Self {
         Menu {
             menu_added: false,
-            quit_handler: QuitHandler::new(mtm),
+            handler: MenuHandler::new(mtm),
+            main_menu: None,
+            selection_items: Vec::new(),
+            user_items: Vec::new(),
+            added_user_items: Vec::new(),
+            added_user_menus: Vec::new(),
         }
     }
-    pub fn ensure_menu_added(&mut self, ev: &Event<UserEvent>) {
-        if let Event::WindowEvent {
-            event: WindowEvent::Focused(_),
-            ..
-        } = ev
-        {
-            if !self.menu_added {
+
+    pub fn handle_event(&mut self, ev: &Event<UserEvent>) {
+        match ev {
+            Event::WindowEvent {
+                event: WindowEvent::Focused(_),
+                ..
+            } if !self.menu_added => {
                 self.add_menus();
+                self.update_user_items();
                 self.menu_added = true;
             }
+            Event::UserEvent(UserEvent::DrawCommandBatch(batch)) => {
+                for command in batch {
+                    if let DrawCommand::ModeChanged(mode) = command {
+                        self.update_enabled_items(mode);
+                    }
+                }
+            }
+            Event::UserEvent(UserEvent::WindowCommand(WindowCommand::SetMenuItems(items))) => {
+                self.user_items = items.clone();
+                if self.menu_added {
+                    self.update_user_items();
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn update_enabled_items(&self, mode: &EditorMode) {
+        let has_selection = *mode == EditorMode::Visual;
+        for item in &self.selection_items {
+            unsafe { item.setEnabled(has_selection) };
         }
     }
 
@@ -360,14 +683,14 @@ impl Menu {
             quit_item.setTitle(&NSString::from_str("Quit ").stringByAppendingString(&process_name));
             quit_item.setKeyEquivalent(&NSString::from_str("q"));
             quit_item.setAction(Some(sel!(quit:)));
-            quit_item.setTarget(Some(&self.quit_handler));
+            quit_item.setTarget(Some(&self.handler));
             app_menu.addItem(&quit_item);
 
             app_menu
         }
     }
 
-    fn add_menus(&self) {
+    fn add_menus(&mut self) {
         let app = unsafe { NSApplication::sharedApplication() };
 
         let main_menu = unsafe { NSMenu::new() };
@@ -381,21 +704,74 @@ impl Menu {
             }
             main_menu.addItem(&app_menu_item);
 
-            let win_menu = self.add_window_menu();
-            let win_menu_item = NSMenuItem::new();
-            win_menu_item.setSubmenu(Some(&win_menu));
+            main_menu.addItem(&self.add_file_menu());
+            main_menu.addItem(&self.add_edit_menu());
+            main_menu.addItem(&self.add_view_menu());
+
+            let (win_menu, win_menu_item) = self.add_window_menu();
             main_menu.addItem(&win_menu_item);
             app.setWindowsMenu(Some(&win_menu));
         }
 
         unsafe { app.setMainMenu(Some(&main_menu)) };
+        self.main_menu = Some(main_menu);
     }
 
-    fn add_window_menu(&self) -> Id<NSMenu> {
-        let menu_title = NSString::from_str("Window");
+    // Most items don't have key equivalents, since they would take precedence over the <D-...>
+    // mappings in the Neovim config. Use `g:neovide_shortcuts` to bind keys to them instead.
+    fn add_file_menu(&self) -> Id<NSMenuItem> {
         unsafe {
-            let menu = NSMenu::new();
-            menu.setTitle(&menu_title);
+            let (menu, item) = submenu("File");
+            menu.addItem(&menu_item(
+                "New Window",
+                sel!(newWindow:),
+                "",
+                &self.handler,
+            ));
+            menu.addItem(&menu_item("New Tab", sel!(newTab:), "", &self.handler));
+            menu.addItem(&menu_item("Open…", sel!(openFile:), "", &self.handler));
+            item
+        }
+    }
+
+    fn add_edit_menu(&mut self) -> Id<NSMenuItem> {
+        unsafe {
+            let (menu, item) = submenu("Edit");
+            // The items are enabled based on the editor mode instead
+            menu.setAutoenablesItems(false);
+            menu.addItem(&menu_item("Undo", sel!(neovideUndo:), "", &self.handler));
+            menu.addItem(&menu_item("Redo", sel!(neovideRedo:), "", &self.handler));
+            menu.addItem(&NSMenuItem::separatorItem());
+            let cut_item = menu_item("Cut", sel!(neovideCut:), "", &self.handler);
+            let copy_item = menu_item("Copy", sel!(neovideCopy:), "", &self.handler);
+            cut_item.setEnabled(false);
+            copy_item.setEnabled(false);
+            menu.addItem(&cut_item);
+            menu.addItem(&copy_item);
+            menu.addItem(&menu_item("Paste", sel!(neovidePaste:), "", &self.handler));
+            menu.addItem(&menu_item(
+                "Select All",
+                sel!(neovideSelectAll:),
+                "",
+                &self.handler,
+            ));
+            self.selection_items = vec![cut_item, copy_item];
+            item
+        }
+    }
+
+    fn add_view_menu(&self) -> Id<NSMenuItem> {
+        unsafe {
+            let (menu, item) = submenu("View");
+            menu.addItem(&menu_item("Zoom In", sel!(zoomIn:), "", &self.handler));
+            menu.addItem(&menu_item("Zoom Out", sel!(zoomOut:), "", &self.handler));
+            menu.addItem(&menu_item(
+                "Actual Size",
+                sel!(actualSize:),
+                "",
+                &self.handler,
+            ));
+            menu.addItem(&NSMenuItem::separatorItem());
 
             let full_screen_item = NSMenuItem::new();
             full_screen_item.setTitle(&NSString::from_str("Enter Full Screen"));
@@ -405,18 +781,158 @@ impl Menu {
                 NSEventModifierFlagControl | NSEventModifierFlagCommand,
             );
             menu.addItem(&full_screen_item);
+            item
+        }
+    }
+
+    fn add_window_menu(&self) -> (Id<NSMenu>, Id<NSMenuItem>) {
+        unsafe {
+            let (menu, item) = submenu("Window");
 
             let min_item = NSMenuItem::new();
             min_item.setTitle(&NSString::from_str("Minimize"));
             min_item.setKeyEquivalent(&NSString::from_str("m"));
             min_item.setAction(Some(sel!(performMiniaturize:)));
             menu.addItem(&min_item);
-            menu
+
+            let zoom_item = NSMenuItem::new();
+            zoom_item.setTitle(&NSString::from_str("Zoom"));
+            zoom_item.setAction(Some(sel!(performZoom:)));
+            menu.addItem(&zoom_item);
+
+            menu.addItem(&NSMenuItem::separatorItem());
+            menu.addItem(&menu_item(
+                "Show Previous Tab",
+                sel!(previousTab:),
+                "",
+                &self.handler,
+            ));
+            menu.addItem(&menu_item(
+                "Show Next Tab",
+                sel!(nextTab:),
+                "",
+                &self.handler,
+            ));
+            (menu, item)
         }
     }
+
+    /// Replaces the previously added user items with the current ones.
+    fn update_user_items(&mut self) {
+        let Some(main_menu) = &self.main_menu else {
+            return;
+        };
+        unsafe {
+            for (menu, item) in self.added_user_items.drain(..) {
+                menu.removeItem(&item);
+            }
+            for item in self.added_user_menus.drain(..) {
+                main_menu.removeItem(&item);
+            }
+
+            let mut commands = USER_MENU_COMMANDS.lock();
+            commands.clear();
+            for user_item in &self.user_items {
+                let title = user_item.menu.as_deref().unwrap_or(DEFAULT_USER_MENU);
+                let menu = match main_menu.itemWithTitle(&NSString::from_str(title)) {
+                    Some(item) if item.submenu().is_some() => item.submenu().unwrap(),
+                    _ => {
+                        // New menus are added before the Window menu
+                        let (menu, item) = submenu(title);
+                        main_menu.insertItem_atIndex(&item, main_menu.numberOfItems() - 1);
+                        self.added_user_menus.push(item);
+                        menu
+                    }
+                };
+                let item = match &user_item.command {
+                    Some(command) => {
+                        let item = menu_item(&user_item.title, sel!(userItem:), "", &self.handler);
+                        item.setTag(commands.len() as isize);
+                        commands.push(command.clone());
+                        item
+                    }
+                    None => NSMenuItem::separatorItem(),
+                };
+                menu.addItem(&item);
+                self.added_user_items.push((menu, item));
+            }
+        }
+    }
+}
+
+/// Whether "Reduce motion" is enabled in the accessibility settings.
+pub fn prefers_reduced_motion() -> bool {
+    let Some(class) = AnyClass::get("NSWorkspace") else {
+        return false;
+    };
+    unsafe {
+        let workspace: *mut AnyObject = msg_send![class, sharedWorkspace];
+        msg_send![workspace, accessibilityDisplayShouldReduceMotion]
+    }
+}
+
+extern "C" {
+    fn NSBeep();
+}
+
+/// Plays the alert sound chosen in the sound settings.
+pub fn beep() {
+    unsafe { NSBeep() };
+}
+
+/// Whether "Increase contrast" is enabled in the accessibility settings.
+pub fn prefers_high_contrast() -> bool {
+    let Some(class) = AnyClass::get("NSWorkspace") else {
+        return false;
+    };
+    unsafe {
+        let workspace: *mut AnyObject = msg_send![class, sharedWorkspace];
+        msg_send![workspace, accessibilityDisplayShouldIncreaseContrast]
+    }
+}
+
+/// Tags the window with a color space, so that macOS converts the colors for the display.
+pub fn set_window_color_space(window: &Window, color_space: OutputColorSpace) {
+    let RawWindowHandle::AppKit(handle) = window.raw_window_handle() else {
+        return;
+    };
+    let Some(class) = AnyClass::get("NSColorSpace") else {
+        return;
+    };
+    unsafe {
+        let ns_color_space: *mut AnyObject = match color_space {
+            OutputColorSpace::Srgb => msg_send![class, sRGBColorSpace],
+            OutputColorSpace::DisplayP3 => msg_send![class, displayP3ColorSpace],
+            OutputColorSpace::Default | OutputColorSpace::ScRgb => return,
+        };
+        let ns_window = handle.ns_window as *mut AnyObject;
+        let _: () = msg_send![ns_window, setColorSpace: ns_color_space];
+    }
+}
+
+/// Shows a short text, like a count, as a badge on the dock icon, or removes the badge.
+pub fn set_dock_badge(label: Option<&str>) {
+    let label = label.map(NSString::from_str);
+    unsafe {
+        let app = NSApplication::sharedApplication();
+        let dock_tile: *mut AnyObject = msg_send![&app, dockTile];
+        let label: *const NSString = label.as_deref().map_or(ptr::null(), |label| label);
+        let _: () = msg_send![dock_tile, setBadgeLabel: label];
+    }
+}
+
+/// Shows the progress as a badge on the dock icon, or removes the badge.
+pub fn set_dock_progress(progress: Option<Progress>) {
+    let label = progress.map(|progress| match progress {
+        Progress::Value(value) => format!("{}%", (value * 100.0).round()),
+        Progress::Indeterminate => "…".to_string(),
+    });
+    set_dock_badge(label.as_deref());
 }
 
-pub fn register_file_handler() {
+/// Opens the files of `application:openFiles:`, which is sent both for the files Neovide was
+/// launched with from Finder, and for the files dropped on the dock icon while it's running.
+pub fn register_file_handler(proxy: EventLoopProxy<UserEvent>) {
     use objc2::rc::autoreleasepool;
 
     extern "C" fn handle_open_files(
@@ -425,17 +941,21 @@ pub fn register_file_handler() {
         _sender: &objc2::runtime::AnyObject,
         files: &mut icrate::Foundation::NSArray<icrate::Foundation::NSString>,
     ) {
-        autoreleasepool(|pool| {
-            for file in files.iter() {
-                let path = file.as_str(pool).to_owned();
-                send_ui(ParallelCommand::FileDrop(path));
-            }
+        let paths = autoreleasepool(|pool| {
+            files
+                .iter()
+                .map(|file| file.as_str(pool).to_owned())
+                .collect::<Vec<_>>()
         });
+        if let Some(proxy) = OPEN_FILES_PROXY.lock().as_ref() {
+            let _ = proxy.send_event(UserEvent::OpenFiles(paths));
+        }
     }
 
+    *OPEN_FILES_PROXY.lock() = Some(proxy);
+
     unsafe {
         use objc2::declare::ClassBuilder;
-        use objc2::msg_send;
 
         let app = NSApplication::sharedApplication();
         let delegate = app.delegate().unwrap();
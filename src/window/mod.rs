@@ -1,38 +1,66 @@
+mod accessibility;
+mod audio_bell;
+mod compose;
 mod error_window;
+mod frame_timings;
+mod fullscreen;
 mod keyboard_manager;
+mod monitors;
 mod mouse_manager;
+mod padding;
+mod platform_integration;
+mod power;
+mod progress;
 mod settings;
+mod shortcuts;
+mod terminal_colors;
 mod update_loop;
+mod user_menu;
 mod window_wrapper;
+mod zen_mode;
 
 #[cfg(target_os = "macos")]
 mod macos;
 
+#[cfg(target_os = "macos")]
+pub use macos::set_window_color_space;
+
 #[cfg(target_os = "linux")]
 use std::env;
+use std::{collections::HashMap, ops::Range, path::PathBuf, sync::RwLock, time::Duration};
 
 #[cfg(target_os = "macos")]
 use icrate::Foundation::MainThreadMarker;
 
+use rmpv::Value;
 use winit::{
-    dpi::{PhysicalSize, Size},
+    dpi::{PhysicalPosition, PhysicalSize, Size},
     error::EventLoopError,
     event::Event,
     event_loop::{EventLoop, EventLoopBuilder},
-    window::{Icon, Theme, WindowBuilder},
+    monitor::MonitorHandle,
+    window::{CursorIcon, Icon, Theme, UserAttentionType, Window, WindowBuilder},
 };
 
 #[cfg(target_os = "macos")]
 use winit::platform::macos::WindowBuilderExtMacOS;
 
 #[cfg(target_os = "linux")]
-use winit::platform::{wayland::WindowBuilderExtWayland, x11::WindowBuilderExtX11};
+use winit::platform::{
+    startup_notify::{
+        reset_activation_token_env, EventLoopExtStartupNotify, WindowBuilderExtStartupNotify,
+    },
+    wayland::WindowBuilderExtWayland,
+    x11::WindowBuilderExtX11,
+};
 
 #[cfg(target_os = "macos")]
 use winit::platform::macos::EventLoopBuilderExtMacOS;
 
-use image::{load_from_memory, GenericImageView, Pixel};
+use image::{load_from_memory, GenericImageView, Pixel, RgbaImage};
 use keyboard_manager::KeyboardManager;
+use monitors::{centered_position, find_monitor, initial_position, work_area};
+pub use monitors::{parse_cascade_from, startup_monitor, InitialPosition};
 use mouse_manager::MouseManager;
 use update_loop::UpdateLoop;
 
@@ -40,16 +68,36 @@ use crate::{
     cmd_line::{CmdLineSettings, GeometryArgs},
     dimensions::Dimensions,
     frame::Frame,
-    renderer::{build_window_config, DrawCommand, WindowConfig},
+    renderer::{
+        build_window_config, DrawCommand, PaletteItem, RendererSettings, SurfaceOptions,
+        WindowConfig, WindowHighlights, WindowIndent,
+    },
     running_tracker::*,
     settings::{
         load_last_window_settings, save_window_size, FontSettings, HotReloadConfigs,
         PersistentWindowSettings, SettingsChanged, SETTINGS,
     },
 };
+pub use compose::ComposeMode;
 pub use error_window::show_error_window;
+pub use frame_timings::FRAME_TIMINGS;
+pub use fullscreen::FullscreenMode;
+pub use keyboard_manager::OptionAsMeta;
+pub use mouse_manager::parse_mouse_shape;
+pub use padding::PaddingOverride;
+pub use platform_integration::{
+    load_icon_overlay, load_window_icon, prefers_high_contrast, prefers_reduced_motion,
+    start_reading_desktop_settings, text_scale_factor, wait_for_desktop_settings, WindowsBackdrop,
+};
+pub use power::{limit_refresh_rate, power_saving, PowerSaving};
+pub use progress::Progress;
 pub use settings::{WindowSettings, WindowSettingsChanged};
+pub use shortcuts::{ShortcutAction, Shortcuts};
+pub use terminal_colors::TerminalColors;
 pub use update_loop::ShouldRender;
+#[cfg(any(windows, target_os = "macos"))]
+pub use user_menu::run_menu_command;
+pub use user_menu::{parse_menu_items, UserMenuItem};
 pub use window_wrapper::WinitWindowWrapper;
 
 static ICON: &[u8] = include_bytes!("../../assets/neovide.ico");
@@ -66,6 +114,87 @@ const MAX_PERSISTENT_WINDOW_SIZE: PhysicalSize<u32> = PhysicalSize {
     width: 8192,
     height: 8192,
 };
+/// The minimum width given to each file of a diff, when the size is not given explicitly.
+const DIFF_WIDTH_PER_FILE: u32 = 500;
+
+/// The last known geometry of the platform window, in physical pixels.
+/// The position is not available on all platforms, notably Wayland.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct WindowGeometry {
+    pub position: Option<PhysicalPosition<i32>>,
+    pub outer_size: PhysicalSize<u32>,
+    pub inner_size: PhysicalSize<u32>,
+    pub scale_factor: f64,
+}
+
+impl From<WindowGeometry> for Value {
+    fn from(geometry: WindowGeometry) -> Self {
+        let position = geometry.position.map_or(Value::Nil, |position| {
+            Value::from(vec![
+                (Value::from("x"), Value::from(position.x)),
+                (Value::from("y"), Value::from(position.y)),
+            ])
+        });
+        Value::from(vec![
+            (Value::from("position"), position),
+            (Value::from("width"), Value::from(geometry.outer_size.width)),
+            (
+                Value::from("height"),
+                Value::from(geometry.outer_size.height),
+            ),
+            (
+                Value::from("inner_width"),
+                Value::from(geometry.inner_size.width),
+            ),
+            (
+                Value::from("inner_height"),
+                Value::from(geometry.inner_size.height),
+            ),
+            (
+                Value::from("scale_factor"),
+                Value::from(geometry.scale_factor),
+            ),
+        ])
+    }
+}
+
+lazy_static! {
+    // Written by the window thread, and read when Neovim requests the window geometry
+    pub static ref WINDOW_GEOMETRY: RwLock<WindowGeometry> = RwLock::new(WindowGeometry::default());
+}
+
+/// The corner of the window that stays in place when the window is resized from Neovim.
+/// Uses the same naming as the `anchor` field of `nvim_open_win`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ResizeAnchor {
+    #[default]
+    NorthWest,
+    NorthEast,
+    SouthWest,
+    SouthEast,
+}
+
+impl ResizeAnchor {
+    pub fn parse(anchor: &str) -> Option<Self> {
+        match anchor.to_uppercase().as_str() {
+            "NW" => Some(Self::NorthWest),
+            "NE" => Some(Self::NorthEast),
+            "SW" => Some(Self::SouthWest),
+            "SE" => Some(Self::SouthEast),
+            _ => None,
+        }
+    }
+
+    /// True when the right edge is fixed, so the window grows towards the left.
+    pub fn is_east(&self) -> bool {
+        matches!(self, Self::NorthEast | Self::SouthEast)
+    }
+
+    /// True when the bottom edge is fixed, so the window grows upwards.
+    pub fn is_south(&self) -> bool {
+        matches!(self, Self::SouthWest | Self::SouthEast)
+    }
+}
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum WindowCommand {
@@ -73,9 +202,87 @@ pub enum WindowCommand {
     SetMouseEnabled(bool),
     ListAvailableFonts,
     FocusWindow,
+    /// Shows the window of a `--daemon` that a new invocation claimed.
+    ShowDaemonWindow,
+    /// Sets the urgency hint on X11, flashes the taskbar button on Windows and bounces the dock
+    /// icon on macOS, until the window is focused.
+    RequestAttention(UserAttentionType),
     Minimize,
+    SetPosition(PhysicalPosition<i32>),
+    SetMouseShape(CursorIcon),
+    ResizeWindow {
+        delta_cols: i64,
+        delta_lines: i64,
+        anchor: ResizeAnchor,
+    },
     #[allow(dead_code)] // Theme change is only used on macOS right now
     ThemeChanged(Option<Theme>),
+    /// Items added to the menu bar, only used on macOS.
+    SetMenuItems(Vec<UserMenuItem>),
+    /// Items of the right click menu, not supported on Linux.
+    SetContextMenu(Vec<UserMenuItem>),
+    SetZenMode(bool),
+    /// Replaces the sides of the padding settings, or goes back to them with None.
+    SetPadding(Option<PaddingOverride>),
+    Bell,
+    VisualBell,
+    SetProgress(Option<Progress>),
+    SetBadge(Option<String>),
+    SetIconOverlay(Option<RgbaImage>),
+    /// Replaces the window icon, `None` restores the Neovide icon.
+    SetWindowIcon(Option<RgbaImage>),
+    /// Ligature overrides of the buffers displayed in Neovim windows, keyed by window handle.
+    SetWindowLigatures(HashMap<u64, bool>),
+    /// Indentation of the buffers displayed in Neovim windows, keyed by window handle.
+    SetWindowIndents(HashMap<u64, WindowIndent>),
+    /// The columns of the trailing whitespace on the rows of Neovim windows, keyed by window
+    /// handle and row.
+    SetWindowTrailingWhitespace(HashMap<u64, HashMap<u64, Range<u64>>>),
+    /// Magnifies the Neovim window with the handle by the factor, without changing the layout.
+    SetWindowZoom(u64, f32),
+    /// The Neovim window with the handle was scrolled horizontally by the columns, and its text
+    /// starts after the number and sign columns of the given width.
+    ScrollWindowColumns {
+        window: u64,
+        cols: i64,
+        text_left: u64,
+    },
+    /// The columns of the top line of the Neovim window with the handle that `smoothscroll` skips.
+    SetWindowSkipcol {
+        window: u64,
+        skipcol: u64,
+    },
+    /// The `cursorline` and `colorcolumn` drawn by the renderer.
+    SetWindowHighlights(WindowHighlights),
+    /// A colorscheme was loaded, which may have replaced the terminal colors.
+    ColorschemeChanged,
+    OpenCommandPalette,
+    /// Opens each of the files in its own Neovide window.
+    OpenFilesInNewWindows(Vec<String>),
+    /// Opens the log viewer with the level and module filters, which are ignored when empty.
+    ShowLogs {
+        level: String,
+        module: String,
+    },
+    /// The commands, buffers and files listed by Neovim for the command palette.
+    SetCommandPaletteItems(Vec<PaletteItem>),
+    /// Opens the palette with the recent projects and sessions listed by Neovim.
+    OpenProjectLauncher(Vec<PaletteItem>),
+    /// Neovim stopped answering the heartbeat requests, or answers them again.
+    SetNeovimBusy(bool),
+    /// The window was closed with modified buffers, which are listed in the quit dialog.
+    ConfirmQuit(Vec<String>),
+    /// The embedded Neovim exited with an error, with the last lines it wrote to stderr.
+    NeovimCrashed {
+        status: String,
+        output: Vec<String>,
+    },
+    NeovimRestarted,
+    Screenshot(PathBuf),
+    RecordFrames {
+        directory: PathBuf,
+        duration: Duration,
+    },
     #[cfg(windows)]
     RegisterRightClick,
     #[cfg(windows)]
@@ -88,7 +295,9 @@ pub enum UserEvent {
     WindowCommand(WindowCommand),
     SettingsChanged(SettingsChanged),
     ConfigsChanged(Box<HotReloadConfigs>),
-    #[allow(dead_code)]
+    /// Files opened while running, by a new `--single-instance` invocation or from the dock on
+    /// macOS.
+    OpenFiles(Vec<String>),
     RedrawRequested,
 }
 
@@ -122,7 +331,7 @@ pub fn create_event_loop() -> EventLoop<UserEvent> {
     builder.with_default_menu(false);
     let event_loop = builder.build().expect("Failed to create winit event loop");
     #[cfg(target_os = "macos")]
-    crate::window::macos::register_file_handler();
+    crate::window::macos::register_file_handler(event_loop.create_proxy());
     event_loop
 }
 
@@ -136,8 +345,11 @@ pub fn create_window(
 
     let window_settings = load_last_window_settings().ok();
 
-    let previous_position = match window_settings {
-        Some(PersistentWindowSettings::Windowed { position, .. }) => Some(position),
+    let previous_position = match window_settings
+        .as_ref()
+        .and_then(PersistentWindowSettings::windowed)
+    {
+        Some(PersistentWindowSettings::Windowed { position, .. }) => Some(*position),
         _ => None,
     };
 
@@ -145,11 +357,29 @@ pub fn create_window(
 
     // NOTE: For Geometry, the window is resized when it's shown based on the font and other
     // settings.
+    let surface_options = SurfaceOptions::from_settings(&SETTINGS.get::<RendererSettings>());
     let inner_size = match *initial_window_size {
         WindowSize::Size(size) => size,
         _ => DEFAULT_WINDOW_SIZE,
     };
 
+    // A monitor chosen with `--monitor`, or a `--position`, takes priority over the previous
+    // position
+    let previous_position = match (cmd_line_settings.position, &cmd_line_settings.monitor) {
+        (Some(position), _) => initial_position(
+            position,
+            startup_monitor(event_loop).as_ref(),
+            inner_size,
+            cmd_line_settings.cascade_from,
+            previous_position,
+        )
+        .or(previous_position),
+        (None, Some(selector)) => find_monitor(event_loop.available_monitors(), selector)
+            .map(|monitor| centered_position(&monitor, inner_size))
+            .or(previous_position),
+        (None, None) => previous_position,
+    };
+
     let winit_window_builder = WindowBuilder::new()
         .with_title("Neovide")
         .with_window_icon(Some(icon))
@@ -157,7 +387,7 @@ pub fn create_window(
         // Unfortunately we can't maximize here, because winit shows the window momentarily causing
         // flickering
         .with_maximized(false)
-        .with_transparent(true)
+        .with_transparent(!surface_options.opaque())
         .with_visible(false);
 
     let frame_decoration = cmd_line_settings.frame;
@@ -201,6 +431,18 @@ pub fn create_window(
         }
     };
 
+    // The startup notification of the launcher, or the activation token of another Wayland
+    // window, lets the window manager focus the new window and stop the busy cursor. It's
+    // removed from the environment, so that it's not inherited by the terminals of Neovim.
+    #[cfg(target_os = "linux")]
+    let winit_window_builder = match event_loop.read_token_from_env() {
+        Some(token) => {
+            reset_activation_token_env();
+            winit_window_builder.with_activation_token(token)
+        }
+        None => winit_window_builder,
+    };
+
     #[cfg(target_os = "macos")]
     let winit_window_builder = winit_window_builder.with_accepts_first_mouse(false);
 
@@ -247,31 +489,63 @@ pub enum WindowSize {
     NeovimGrid, // The geometry is read from init.vim/lua
 }
 
-pub fn determine_window_size(window_settings: Option<&PersistentWindowSettings>) -> WindowSize {
+/// Wayland only tells the scale factor after the window is shown, and until then the size of the
+/// window is taken as logical. So the saved physical size is converted back to the logical size it
+/// had, to keep it the same with fractional scaling.
+#[cfg(target_os = "linux")]
+fn saved_inner_size(pixel_size: PhysicalSize<u32>, scale_factor: Option<f64>) -> PhysicalSize<u32> {
+    match scale_factor {
+        Some(scale_factor) if env::var("WAYLAND_DISPLAY").is_ok() => {
+            let size = pixel_size.to_logical::<u32>(scale_factor);
+            PhysicalSize::new(size.width, size.height)
+        }
+        _ => pixel_size,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn saved_inner_size(
+    pixel_size: PhysicalSize<u32>,
+    _scale_factor: Option<f64>,
+) -> PhysicalSize<u32> {
+    pixel_size
+}
+
+/// The size the window opens with. Sizes given as fractions of the monitor are taken of the work
+/// area of `monitor`.
+pub fn determine_window_size(
+    window_settings: Option<&PersistentWindowSettings>,
+    monitor: Option<&MonitorHandle>,
+) -> WindowSize {
     let cmd_line = SETTINGS.get::<CmdLineSettings>();
+    let work_area = monitor.map_or(DEFAULT_WINDOW_SIZE, |monitor| work_area(monitor).1);
 
-    match cmd_line.geometry {
+    let window_size = match cmd_line.geometry {
         GeometryArgs {
-            grid: Some(Some(dimensions)),
+            grid: Some(Some(grid)),
             ..
-        } => WindowSize::Grid(dimensions.clamped_grid_size()),
+        } => match grid.absolute() {
+            Some(dimensions) => WindowSize::Grid(dimensions.clamped_grid_size()),
+            // The cells can't be measured yet, so the window is sized by the monitor instead
+            None => WindowSize::Size(grid.resolve(work_area).into()),
+        },
         GeometryArgs {
             grid: Some(None), ..
         } => WindowSize::NeovimGrid,
         GeometryArgs {
-            size: Some(dimensions),
-            ..
-        } => WindowSize::Size(dimensions.into()),
+            size: Some(size), ..
+        } => WindowSize::Size(size.resolve(work_area).into()),
         GeometryArgs {
             maximized: true, ..
         } => WindowSize::Maximized,
-        _ => match window_settings {
+        _ => match window_settings.and_then(PersistentWindowSettings::windowed) {
             Some(PersistentWindowSettings::Maximized) => WindowSize::Maximized,
             Some(PersistentWindowSettings::Windowed {
                 pixel_size: Some(pixel_size),
+                scale_factor,
                 ..
             }) => {
-                let size = Size::new(*pixel_size);
+                let size = Size::new(saved_inner_size(*pixel_size, *scale_factor));
                 let scale = 1.0;
                 WindowSize::Size(
                     Size::clamp(
@@ -285,7 +559,50 @@ pub fn determine_window_size(window_settings: Option<&PersistentWindowSettings>)
             }
             _ => WindowSize::Size(DEFAULT_WINDOW_SIZE),
         },
+    };
+
+    // `nvim -d` shows the files side by side, so the window is widened to fit them, unless the
+    // size was given explicitly
+    match window_size {
+        WindowSize::Size(size)
+            if cmd_line.neovim_flags.diff && cmd_line.geometry.size.is_none() =>
+        {
+            let files = cmd_line.files_to_open.len().max(2) as u32;
+            WindowSize::Size(PhysicalSize::new(
+                size.width
+                    .max(DIFF_WIDTH_PER_FILE * files)
+                    .min(MAX_PERSISTENT_WINDOW_SIZE.width),
+                size.height,
+            ))
+        }
+        _ => window_size,
+    }
+}
+
+/// The monitor to make the window fullscreen on at startup, when it was fullscreen the last time
+/// and no other size was given on the command line. None when that monitor is disconnected, so
+/// that the window isn't restored onto a screen that can't be seen.
+pub fn fullscreen_to_restore(
+    window: &Window,
+    window_settings: Option<&PersistentWindowSettings>,
+) -> Option<MonitorHandle> {
+    let cmd_line = SETTINGS.get::<CmdLineSettings>();
+    let geometry = &cmd_line.geometry;
+    if geometry.grid.is_some() || geometry.size.is_some() || geometry.maximized {
+        return None;
+    }
+    let Some(PersistentWindowSettings::Fullscreen { monitor, .. }) = window_settings else {
+        return None;
+    };
+    // The window is already on the monitor chosen with `--monitor`
+    if cmd_line.monitor.is_some() {
+        return window.current_monitor();
+    }
+    let found = monitor.find(window.available_monitors());
+    if found.is_none() {
+        log::info!("The fullscreen monitor {monitor:?} is not connected, opening a window instead");
     }
+    found
 }
 
 pub fn main_loop(
@@ -303,6 +620,7 @@ pub fn main_loop(
     );
 
     let mut update_loop = UpdateLoop::new(cmd_line_settings.idle);
+    power::watch_power_source();
 
     #[cfg(target_os = "macos")]
     let mut menu = {
@@ -311,7 +629,7 @@ pub fn main_loop(
     };
     event_loop.run(move |e, window_target| {
         #[cfg(target_os = "macos")]
-        menu.ensure_menu_added(&e);
+        menu.handle_event(&e);
         if e == Event::LoopExiting {
             return;
         }
@@ -0,0 +1,278 @@
+use std::str::FromStr;
+
+use winit::{
+    dpi::{PhysicalPosition, PhysicalSize},
+    event_loop::EventLoop,
+    monitor::MonitorHandle,
+};
+
+use crate::{cmd_line::CmdLineSettings, settings::SETTINGS, window::UserEvent};
+
+/// Finds the monitor given by `--monitor` or `g:neovide_fullscreen_monitor`, either by its number
+/// in the order the platform lists them, starting from 1, or by a part of its name.
+pub fn find_monitor(
+    monitors: impl Iterator<Item = MonitorHandle>,
+    selector: &str,
+) -> Option<MonitorHandle> {
+    let monitors: Vec<_> = monitors.collect();
+    let names: Vec<_> = monitors.iter().map(MonitorHandle::name).collect();
+    let monitor = select_monitor(&names, selector).map(|index| monitors[index].clone());
+    if monitor.is_none() {
+        log::warn!(
+            "No monitor matches {selector:?}, the monitors are {:?}",
+            names
+                .iter()
+                .map(|name| name.as_deref().unwrap_or("unnamed"))
+                .collect::<Vec<_>>()
+        );
+    }
+    monitor
+}
+
+fn select_monitor(names: &[Option<String>], selector: &str) -> Option<usize> {
+    let selector = selector.trim();
+    if let Ok(number) = selector.parse::<usize>() {
+        return (1..=names.len()).contains(&number).then(|| number - 1);
+    }
+    let selector = selector.to_lowercase();
+    names.iter().position(|name| {
+        name.as_ref()
+            .is_some_and(|name| name.to_lowercase().contains(&selector))
+    })
+}
+
+/// The monitor a new window opens on, the one given by `--monitor` or the primary one.
+pub fn startup_monitor(event_loop: &EventLoop<UserEvent>) -> Option<MonitorHandle> {
+    match &SETTINGS.get::<CmdLineSettings>().monitor {
+        Some(selector) => find_monitor(event_loop.available_monitors(), selector),
+        None => event_loop
+            .primary_monitor()
+            .or_else(|| event_loop.available_monitors().next()),
+    }
+}
+
+/// The part of the monitor without the taskbar, which windows are placed in.
+#[cfg(target_os = "windows")]
+pub fn work_area(monitor: &MonitorHandle) -> (PhysicalPosition<i32>, PhysicalSize<u32>) {
+    use winapi::um::winuser::{GetMonitorInfoW, MONITORINFO};
+    use winit::platform::windows::MonitorHandleExtWindows;
+
+    let mut info: MONITORINFO = unsafe { std::mem::zeroed() };
+    info.cbSize = std::mem::size_of::<MONITORINFO>() as u32;
+    if unsafe { GetMonitorInfoW(monitor.hmonitor() as _, &mut info) } == 0 {
+        return (monitor.position(), monitor.size());
+    }
+    let work = info.rcWork;
+    (
+        PhysicalPosition::new(work.left, work.top),
+        PhysicalSize::new(
+            (work.right - work.left).max(0) as u32,
+            (work.bottom - work.top).max(0) as u32,
+        ),
+    )
+}
+
+/// The whole monitor, winit doesn't tell the part covered by panels and docks on the other
+/// platforms.
+#[cfg(not(target_os = "windows"))]
+pub fn work_area(monitor: &MonitorHandle) -> (PhysicalPosition<i32>, PhysicalSize<u32>) {
+    (monitor.position(), monitor.size())
+}
+
+/// The position that centers a window of the size on the work area of the monitor.
+pub fn centered_position(
+    monitor: &MonitorHandle,
+    size: PhysicalSize<u32>,
+) -> PhysicalPosition<i32> {
+    let (position, area_size) = work_area(monitor);
+    PhysicalPosition::new(
+        position.x + (area_size.width.saturating_sub(size.width) / 2) as i32,
+        position.y + (area_size.height.saturating_sub(size.height) / 2) as i32,
+    )
+}
+
+/// How far down and right a cascaded window is from the previous one.
+const CASCADE_STEP: i32 = 32;
+
+/// Where a new window is placed, given with `--position`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InitialPosition {
+    Center,
+    Mouse,
+    Cascade,
+    At(PhysicalPosition<i32>),
+}
+
+impl FromStr for InitialPosition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "center" => Ok(InitialPosition::Center),
+            "mouse" => Ok(InitialPosition::Mouse),
+            "cascade" => Ok(InitialPosition::Cascade),
+            position => parse_position(position)
+                .map(InitialPosition::At)
+                .ok_or_else(|| {
+                    format!(
+                        "Invalid position: {s}\nValid values: center, mouse, cascade or <x>,<y>"
+                    )
+                }),
+        }
+    }
+}
+
+fn parse_position(position: &str) -> Option<PhysicalPosition<i32>> {
+    let (x, y) = position.split_once(',')?;
+    Some(PhysicalPosition::new(
+        x.trim().parse().ok()?,
+        y.trim().parse().ok()?,
+    ))
+}
+
+/// Parses the hidden `--cascade-from X,Y`, the position of the window that started this one.
+pub fn parse_cascade_from(position: &str) -> Result<PhysicalPosition<i32>, String> {
+    parse_position(position).ok_or_else(|| format!("Invalid position: {position}"))
+}
+
+/// The outer position of a window of the size, placed on the monitor as given with `--position`.
+/// Cascading continues from `opened_from`, the window that started this one, or otherwise from
+/// `previous`, where the last window was.
+pub fn initial_position(
+    position: InitialPosition,
+    monitor: Option<&MonitorHandle>,
+    size: PhysicalSize<u32>,
+    opened_from: Option<PhysicalPosition<i32>>,
+    previous: Option<PhysicalPosition<i32>>,
+) -> Option<PhysicalPosition<i32>> {
+    let centered = || monitor.map(|monitor| centered_position(monitor, size));
+    match position {
+        InitialPosition::At(position) => Some(position),
+        InitialPosition::Center => centered(),
+        InitialPosition::Mouse => {
+            let Some(cursor) = cursor_position() else {
+                log::info!("The mouse position is unknown, centering the window instead");
+                return centered();
+            };
+            Some(match monitor {
+                Some(monitor) => clamp_to_area(cursor, size, work_area(monitor)),
+                None => cursor,
+            })
+        }
+        InitialPosition::Cascade => match (opened_from.or(previous), monitor) {
+            (Some(from), Some(monitor)) => Some(cascade(from, size, work_area(monitor))),
+            (Some(from), None) => Some(from),
+            (None, _) => centered(),
+        },
+    }
+}
+
+/// Moves the window down and right from `from`, and back to the top left corner of the area when
+/// it would no longer fit.
+fn cascade(
+    from: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    (area_position, area_size): (PhysicalPosition<i32>, PhysicalSize<u32>),
+) -> PhysicalPosition<i32> {
+    let next = PhysicalPosition::new(from.x + CASCADE_STEP, from.y + CASCADE_STEP);
+    let fits = next.x + size.width as i32 <= area_position.x + area_size.width as i32
+        && next.y + size.height as i32 <= area_position.y + area_size.height as i32;
+    if fits {
+        next
+    } else {
+        area_position
+    }
+}
+
+/// Keeps a window of the size inside the area, as far as it fits.
+fn clamp_to_area(
+    position: PhysicalPosition<i32>,
+    size: PhysicalSize<u32>,
+    (area_position, area_size): (PhysicalPosition<i32>, PhysicalSize<u32>),
+) -> PhysicalPosition<i32> {
+    let clamp = |position: i32, size: u32, start: i32, area: u32| {
+        let end = start + area.saturating_sub(size) as i32;
+        position.clamp(start, end)
+    };
+    PhysicalPosition::new(
+        clamp(position.x, size.width, area_position.x, area_size.width),
+        clamp(position.y, size.height, area_position.y, area_size.height),
+    )
+}
+
+#[cfg(target_os = "windows")]
+fn cursor_position() -> Option<PhysicalPosition<i32>> {
+    use winapi::{shared::windef::POINT, um::winuser::GetCursorPos};
+
+    let mut point = POINT { x: 0, y: 0 };
+    (unsafe { GetCursorPos(&mut point) } != 0).then(|| PhysicalPosition::new(point.x, point.y))
+}
+
+/// Winit only tells the position of the mouse over its own windows.
+#[cfg(not(target_os = "windows"))]
+fn cursor_position() -> Option<PhysicalPosition<i32>> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_select_monitor() {
+        let names = vec![
+            Some("\\\\.\\DISPLAY1".to_string()),
+            None,
+            Some("DELL U2720Q".to_string()),
+        ];
+        assert_eq!(select_monitor(&names, "1"), Some(0));
+        assert_eq!(select_monitor(&names, "3"), Some(2));
+        assert_eq!(select_monitor(&names, "0"), None);
+        assert_eq!(select_monitor(&names, "4"), None);
+        assert_eq!(select_monitor(&names, "dell"), Some(2));
+        assert_eq!(select_monitor(&names, "LG"), None);
+    }
+
+    #[test]
+    fn test_parse_initial_position() {
+        assert_eq!("cascade".parse(), Ok(InitialPosition::Cascade));
+        assert_eq!(
+            "-1920, 40".parse(),
+            Ok(InitialPosition::At(PhysicalPosition::new(-1920, 40)))
+        );
+        assert!("top".parse::<InitialPosition>().is_err());
+        assert!("10,".parse::<InitialPosition>().is_err());
+    }
+
+    #[test]
+    fn test_cascade() {
+        let area = (PhysicalPosition::new(0, 0), PhysicalSize::new(1920, 1080));
+        let size = PhysicalSize::new(800, 600);
+        assert_eq!(
+            cascade(PhysicalPosition::new(100, 100), size, area),
+            PhysicalPosition::new(132, 132)
+        );
+        // Back to the corner when the window would go past the bottom
+        assert_eq!(
+            cascade(PhysicalPosition::new(100, 470), size, area),
+            PhysicalPosition::new(0, 0)
+        );
+    }
+
+    #[test]
+    fn test_clamp_to_area() {
+        let area = (
+            PhysicalPosition::new(1920, 0),
+            PhysicalSize::new(1920, 1080),
+        );
+        let size = PhysicalSize::new(800, 600);
+        assert_eq!(
+            clamp_to_area(PhysicalPosition::new(3500, 900), size, area),
+            PhysicalPosition::new(3040, 480)
+        );
+        assert_eq!(
+            clamp_to_area(PhysicalPosition::new(2000, 100), size, area),
+            PhysicalPosition::new(2000, 100)
+        );
+    }
+}
@@ -11,12 +11,12 @@ use winit::{
         DeviceId, ElementState, Event, MouseButton, MouseScrollDelta, Touch, TouchPhase,
         WindowEvent,
     },
-    window::Window,
+    window::{CursorIcon, Window},
 };
 
 use crate::{
     bridge::{send_ui, SerialCommand},
-    renderer::{Renderer, WindowDrawDetails},
+    renderer::{link_safety, open_url, Hyperlink, LinkSafety, Renderer, WindowDrawDetails},
     settings::SETTINGS,
     window::keyboard_manager::KeyboardManager,
     window::{UserEvent, WindowSettings},
@@ -60,6 +60,30 @@ fn mouse_button_to_button_text(mouse_button: &MouseButton) -> Option<String> {
     }
 }
 
+/// Converts the shape names accepted by `neovide.set_mouse_shape` to a cursor icon.
+pub fn parse_mouse_shape(name: &str) -> Option<CursorIcon> {
+    Some(match name {
+        "default" | "arrow" => CursorIcon::Default,
+        "hand" | "pointer" => CursorIcon::Pointer,
+        "text" | "beam" => CursorIcon::Text,
+        "crosshair" => CursorIcon::Crosshair,
+        "move" => CursorIcon::Move,
+        "grab" => CursorIcon::Grab,
+        "grabbing" => CursorIcon::Grabbing,
+        "wait" | "busy" => CursorIcon::Wait,
+        "progress" => CursorIcon::Progress,
+        "help" => CursorIcon::Help,
+        "not_allowed" => CursorIcon::NotAllowed,
+        "resize_ns" | "updown" => CursorIcon::NsResize,
+        "resize_ew" | "leftright" => CursorIcon::EwResize,
+        "resize_nesw" => CursorIcon::NeswResize,
+        "resize_nwse" => CursorIcon::NwseResize,
+        "resize_col" => CursorIcon::ColResize,
+        "resize_row" => CursorIcon::RowResize,
+        _ => return None,
+    })
+}
+
 #[derive(Debug)]
 struct TouchTrace {
     start_time: Instant,
@@ -68,6 +92,14 @@ struct TouchTrace {
     left_deadzone_once: bool,
 }
 
+/// A native separator being dragged with the mouse.
+#[derive(Debug)]
+struct SeparatorDrag {
+    window_handle: u64,
+    start_x: f32,
+    columns: i64,
+}
+
 pub struct MouseManager {
     dragging: Option<String>,
     drag_position: PhysicalPosition<u32>,
@@ -83,6 +115,15 @@ pub struct MouseManager {
 
     window_details_under_mouse: Option<WindowDrawDetails>,
 
+    hovered_hyperlink: Option<Hyperlink>,
+    hyperlink_changed: bool,
+    opening_hyperlink: bool,
+    link_to_confirm: Option<String>,
+    hovered_separator: Option<u64>,
+    separator_drag: Option<SeparatorDrag>,
+    pixel_position: PhysicalPosition<f32>,
+    mouse_shape: CursorIcon,
+
     mouse_hidden: bool,
     pub enabled: bool,
 }
@@ -98,6 +139,14 @@ impl MouseManager {
             scroll_position: PhysicalPosition::new(0.0, 0.0),
             touch_position: HashMap::new(),
             window_details_under_mouse: None,
+            hovered_hyperlink: None,
+            hyperlink_changed: false,
+            opening_hyperlink: false,
+            link_to_confirm: None,
+            hovered_separator: None,
+            separator_drag: None,
+            pixel_position: PhysicalPosition::new(0.0, 0.0),
+            mouse_shape: CursorIcon::Default,
             mouse_hidden: false,
             enabled: true,
         }
@@ -108,7 +157,7 @@ impl MouseManager {
         x: i32,
         y: i32,
         keyboard_manager: &KeyboardManager,
-        renderer: &Renderer,
+        renderer: &mut Renderer,
         window: &Window,
     ) {
         let size = window.inner_size();
@@ -117,6 +166,23 @@ impl MouseManager {
         }
 
         let position: PhysicalPosition<f32> = PhysicalPosition::new(x as f32, y as f32);
+        self.pixel_position = position;
+
+        if let Some(drag) = &mut self.separator_drag {
+            let font_width = renderer.grid_renderer.font_dimensions.width as f32;
+            let columns = ((position.x - drag.start_x) / font_width).round() as i64;
+            if columns != drag.columns {
+                send_ui(SerialCommand::MoveSeparator {
+                    window: drag.window_handle,
+                    columns: columns - drag.columns,
+                });
+                drag.columns = columns;
+            }
+            return;
+        }
+        if self.dragging.is_none() {
+            self.update_hovered_separator(position, renderer, window);
+        }
 
         // If dragging, the relevant window (the one which we send all commands to) is the one
         // which the mouse drag started on. Otherwise its the top rendered window
@@ -152,6 +218,9 @@ impl MouseManager {
             global_bounds,
             renderer.grid_renderer.font_dimensions.into(),
         );
+        // The cells of zoomed windows are larger than the grid
+        let clamped_position = relevant_window_details
+            .map_or(clamped_position, |details| details.unzoom(clamped_position));
 
         self.position = to_grid_coords(
             clamped_position,
@@ -198,6 +267,74 @@ impl MouseManager {
             }
 
             self.has_moved = self.dragging.is_some() && (self.has_moved || has_moved);
+
+            let hovered_cell = (
+                relevant_window_details.id,
+                self.relative_position.x as u64,
+                self.relative_position.y as u64,
+            );
+            self.update_hovered_hyperlink(
+                self.dragging.is_none().then_some(hovered_cell),
+                renderer,
+                window,
+            );
+        }
+    }
+
+    fn update_hovered_separator(
+        &mut self,
+        position: PhysicalPosition<f32>,
+        renderer: &mut Renderer,
+        window: &Window,
+    ) {
+        let separator = renderer.separator_at((position.x, position.y).into());
+        if separator != self.hovered_separator {
+            window.set_cursor_icon(if separator.is_some() {
+                CursorIcon::ColResize
+            } else {
+                self.mouse_shape
+            });
+            renderer.set_hovered_separator(separator);
+            self.hovered_separator = separator;
+        }
+    }
+
+    fn update_hovered_hyperlink(
+        &mut self,
+        cell: Option<(u64, u64, u64)>,
+        renderer: &mut Renderer,
+        window: &Window,
+    ) {
+        let hyperlink =
+            cell.and_then(|(grid_id, column, row)| renderer.hyperlink_at(grid_id, column, row));
+        if hyperlink.is_some() != self.hovered_hyperlink.is_some() {
+            window.set_cursor_icon(if hyperlink.is_some() {
+                CursorIcon::Pointer
+            } else {
+                self.mouse_shape
+            });
+        }
+        self.hyperlink_changed |= renderer.set_hovered_cell(cell);
+        self.hovered_hyperlink = hyperlink;
+    }
+
+    /// Returns true once after the hovered hyperlink changed, so that its underline is redrawn.
+    pub fn take_hyperlink_changed(&mut self) -> bool {
+        std::mem::take(&mut self.hyperlink_changed)
+    }
+
+    /// Returns the clicked link that has to be confirmed before it's opened.
+    pub fn take_link_to_confirm(&mut self) -> Option<String> {
+        self.link_to_confirm.take()
+    }
+
+    fn open_hyperlink(&mut self, url: &str) {
+        match link_safety(url) {
+            LinkSafety::Trusted => open_url(url),
+            LinkSafety::NeedsConfirmation => self.link_to_confirm = Some(url.to_string()),
+            LinkSafety::Refused => {
+                log::warn!("Not opening {url}, only http, https, mailto and file links are opened")
+            }
         }
     }
 
@@ -210,6 +347,36 @@ impl MouseManager {
         // For some reason pointer down is handled differently from pointer up and drag.
         // Floating windows: relative coordinates are great.
         // Non floating windows: rather than global coordinates, relative are needed
+        // Ctrl/Cmd clicking a hyperlink opens it, and the click is not sent to Neovim
+        if *mouse_button == MouseButton::Left {
+            if down && keyboard_manager.is_hyperlink_modifier_pressed() {
+                if let Some(url) = self.hovered_hyperlink.as_ref().map(|link| link.url.clone()) {
+                    self.open_hyperlink(&url);
+                    self.opening_hyperlink = true;
+                    return;
+                }
+            } else if !down && self.opening_hyperlink {
+                self.opening_hyperlink = false;
+                return;
+            }
+        }
+
+        // Native separators are resized by Neovide instead of Neovim
+        if *mouse_button == MouseButton::Left {
+            if down && self.enabled {
+                if let Some(window_handle) = self.hovered_separator {
+                    self.separator_drag = Some(SeparatorDrag {
+                        window_handle,
+                        start_x: self.pixel_position.x,
+                        columns: 0,
+                    });
+                    return;
+                }
+            } else if !down && self.separator_drag.take().is_some() {
+                return;
+            }
+        }
+
         if self.enabled {
             if let Some(button_text) = mouse_button_to_button_text(mouse_button) {
                 if let Some(details) = &self.window_details_under_mouse {
@@ -247,6 +414,14 @@ impl MouseManager {
         }
     }
 
+    /// Sets the pointer shape requested by Neovim, hovered hyperlinks still show a pointer.
+    pub fn set_mouse_shape(&mut self, shape: CursorIcon, window: &Window) {
+        self.mouse_shape = shape;
+        if self.hovered_hyperlink.is_none() {
+            window.set_cursor_icon(shape);
+        }
+    }
+
     fn handle_line_scroll(&mut self, x: f32, y: f32, keyboard_manager: &KeyboardManager) {
         if !self.enabled {
             return;
@@ -321,7 +496,7 @@ impl MouseManager {
     fn handle_touch(
         &mut self,
         keyboard_manager: &KeyboardManager,
-        renderer: &Renderer,
+        renderer: &mut Renderer,
         window: &Window,
         finger_id: (DeviceId, u64),
         location: PhysicalPosition<f32>,
@@ -424,7 +599,7 @@ impl MouseManager {
         &mut self,
         event: &Event<UserEvent>,
         keyboard_manager: &KeyboardManager,
-        renderer: &Renderer,
+        renderer: &mut Renderer,
         window: &Window,
     ) {
         match event {
@@ -444,6 +619,10 @@ impl MouseManager {
                     self.mouse_hidden = false;
                 }
             }
+            Event::WindowEvent {
+                event: WindowEvent::CursorLeft { .. },
+                ..
+            } => self.update_hovered_hyperlink(None, renderer, window),
             Event::WindowEvent {
                 event:
                     WindowEvent::MouseWheel {
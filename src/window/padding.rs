@@ -0,0 +1,185 @@
+use rmpv::Value;
+
+use crate::renderer::animation_utils::{ease, ease_out_expo};
+
+/// The space between the edges of the window and the grid, in pixels. Negative padding lets the
+/// grid extend past the edges, for example to hide the outer columns in fullscreen.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct WindowPadding {
+    pub top: i32,
+    pub left: i32,
+    pub right: i32,
+    pub bottom: i32,
+}
+
+impl WindowPadding {
+    pub fn width(&self) -> i32 {
+        self.left + self.right
+    }
+
+    pub fn height(&self) -> i32 {
+        self.top + self.bottom
+    }
+
+    /// The same amount added to each side.
+    pub fn grow(self, amount: i32) -> Self {
+        Self {
+            top: self.top + amount,
+            left: self.left + amount,
+            right: self.right + amount,
+            bottom: self.bottom + amount,
+        }
+    }
+}
+
+/// The sides set with `neovide.set_padding`, which replace the ones of the settings.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct PaddingOverride {
+    pub top: Option<i32>,
+    pub left: Option<i32>,
+    pub right: Option<i32>,
+    pub bottom: Option<i32>,
+}
+
+impl PaddingOverride {
+    /// Parses a `{ top, left, right, bottom }` map, where every side is optional. Nil clears the
+    /// override.
+    pub fn parse(value: &Value) -> Result<Option<Self>, String> {
+        if value.is_nil() {
+            return Ok(None);
+        }
+        let map = value
+            .as_map()
+            .ok_or_else(|| format!("Expected a map of the sides, but received {value}"))?;
+        let mut padding = PaddingOverride::default();
+        for (key, side) in map {
+            let side_padding = side
+                .as_i64()
+                .and_then(|side_padding| i32::try_from(side_padding).ok())
+                .ok_or_else(|| format!("Expected the padding in pixels, but received {side}"))?;
+            let field = match key.as_str() {
+                Some("top") => &mut padding.top,
+                Some("left") => &mut padding.left,
+                Some("right") => &mut padding.right,
+                Some("bottom") => &mut padding.bottom,
+                _ => return Err(format!("Unknown side {key}")),
+            };
+            *field = Some(side_padding);
+        }
+        Ok(Some(padding))
+    }
+
+    pub fn apply(&self, padding: WindowPadding) -> WindowPadding {
+        WindowPadding {
+            top: self.top.unwrap_or(padding.top),
+            left: self.left.unwrap_or(padding.left),
+            right: self.right.unwrap_or(padding.right),
+            bottom: self.bottom.unwrap_or(padding.bottom),
+        }
+    }
+}
+
+/// Moves the padding smoothly to the one of the settings when they change.
+#[derive(Clone, Debug)]
+pub struct PaddingAnimation {
+    start: WindowPadding,
+    current: WindowPadding,
+    target: WindowPadding,
+    t: f32,
+}
+
+impl PaddingAnimation {
+    pub fn new(padding: WindowPadding) -> Self {
+        Self {
+            start: padding,
+            current: padding,
+            target: padding,
+            t: 1.0,
+        }
+    }
+
+    /// Starts moving from the current padding towards the new one.
+    pub fn set_target(&mut self, target: WindowPadding) {
+        if target != self.target {
+            self.start = self.current;
+            self.target = target;
+            self.t = 0.0;
+        }
+    }
+
+    /// Steps the animation and returns true while it's still in progress. Without a length, the
+    /// padding jumps to the target.
+    pub fn animate(&mut self, dt: f32, length: f32) -> bool {
+        if self.t >= 1.0 {
+            return false;
+        }
+        self.t = if length > 0.0 {
+            (self.t + dt / length).min(1.0)
+        } else {
+            1.0
+        };
+        let step = |start: i32, target: i32| {
+            ease(ease_out_expo, start as f32, target as f32, self.t).round() as i32
+        };
+        self.current = WindowPadding {
+            top: step(self.start.top, self.target.top),
+            left: step(self.start.left, self.target.left),
+            right: step(self.start.right, self.target.right),
+            bottom: step(self.start.bottom, self.target.bottom),
+        };
+        true
+    }
+
+    pub fn current(&self) -> WindowPadding {
+        self.current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn padding(amount: i32) -> WindowPadding {
+        WindowPadding::default().grow(amount)
+    }
+
+    #[test]
+    fn test_parse_padding_override() {
+        let value = Value::from(vec![
+            (Value::from("left"), Value::from(200)),
+            (Value::from("right"), Value::from(-10)),
+        ]);
+        let padding_override = PaddingOverride::parse(&value).unwrap().unwrap();
+        assert_eq!(
+            padding_override.apply(padding(5)),
+            WindowPadding {
+                top: 5,
+                left: 200,
+                right: -10,
+                bottom: 5,
+            }
+        );
+        assert_eq!(PaddingOverride::parse(&Value::Nil), Ok(None));
+        let value = Value::from(vec![(Value::from("middle"), Value::from(1))]);
+        assert!(PaddingOverride::parse(&value).is_err());
+        let value = Value::from(vec![(Value::from("top"), Value::from(i64::MAX))]);
+        assert!(PaddingOverride::parse(&value).is_err());
+    }
+
+    #[test]
+    fn test_padding_animation() {
+        let mut animation = PaddingAnimation::new(padding(0));
+        assert!(!animation.animate(0.1, 0.2));
+        animation.set_target(padding(100));
+        assert!(animation.animate(0.1, 0.2));
+        let halfway = animation.current().top;
+        assert!(halfway > 0 && halfway < 100);
+        assert!(animation.animate(0.1, 0.2));
+        assert_eq!(animation.current(), padding(100));
+        assert!(!animation.animate(0.1, 0.2));
+        // Without a length the padding changes right away
+        animation.set_target(padding(-20));
+        assert!(animation.animate(0.1, 0.0));
+        assert_eq!(animation.current(), padding(-20));
+    }
+}
@@ -0,0 +1,331 @@
+use std::path::Path;
+#[cfg(target_os = "linux")]
+use std::{
+    process::{Child, Command, Stdio},
+    sync::{Mutex, OnceLock},
+    thread::{self, JoinHandle},
+};
+
+use anyhow::{Context, Result};
+use image::{imageops::FilterType, RgbaImage};
+use log::error;
+use rmpv::Value;
+use winit::window::Window;
+
+#[cfg(target_os = "macos")]
+use super::macos::{set_dock_badge, set_dock_progress};
+use super::Progress;
+#[cfg(windows)]
+use crate::windows_utils::{set_taskbar_overlay, set_taskbar_progress, set_window_backdrop};
+use crate::{settings::ParseFromValue, utils::decode_base64};
+
+/// The size overlay images are scaled to, the taskbar scales them further down when needed.
+const OVERLAY_SIZE: u32 = 32;
+
+/// Shows the progress on the taskbar or the dock. Returns false when the platform has no native
+/// progress indicator, so that it's drawn in the window instead.
+#[allow(unused_variables)]
+pub fn set_progress(window: &Window, progress: Option<Progress>) -> bool {
+    #[cfg(windows)]
+    {
+        set_taskbar_progress(window, progress);
+        true
+    }
+    #[cfg(target_os = "macos")]
+    {
+        set_dock_progress(progress);
+        true
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        false
+    }
+}
+
+/// Shows a short text, like the number of diagnostics, as a badge on the dock icon or the
+/// launcher entry. `None` removes the badge.
+pub fn set_badge(badge: Option<&str>) {
+    #[cfg(target_os = "macos")]
+    set_dock_badge(badge);
+    #[cfg(target_os = "linux")]
+    set_launcher_count(badge);
+    #[cfg(windows)]
+    if badge.is_some() {
+        log::warn!("Badges are not supported on Windows, use an icon overlay instead");
+    }
+}
+
+/// Loads an image to show over the taskbar button.
+pub fn load_icon_overlay(path: &Path) -> Result<RgbaImage> {
+    let image = image::open(path).with_context(|| format!("Could not load {path:?}"))?;
+    Ok(image
+        .resize(OVERLAY_SIZE, OVERLAY_SIZE, FilterType::Triangle)
+        .to_rgba8())
+}
+
+/// Loads a window icon from an image file, or from base64 encoded image data, which can also be
+/// given as a `data:` URL.
+pub fn load_window_icon(source: &str) -> Result<RgbaImage> {
+    let path = Path::new(source);
+    let image = if path.is_file() {
+        image::open(path).with_context(|| format!("Could not load {path:?}"))?
+    } else {
+        let data = source
+            .rsplit_once("base64,")
+            .map_or(source, |(_, data)| data);
+        let bytes = decode_base64(data)
+            .context("Expected a path to an image, or base64 encoded image data")?;
+        image::load_from_memory(&bytes).context("Could not decode the icon")?
+    };
+    Ok(image.to_rgba8())
+}
+
+/// Shows the image over the corner of the taskbar button, `None` removes it.
+#[allow(unused_variables)]
+pub fn set_icon_overlay(window: &Window, overlay: Option<&RgbaImage>) {
+    #[cfg(windows)]
+    set_taskbar_overlay(window, overlay);
+    #[cfg(not(windows))]
+    if overlay.is_some() {
+        log::warn!("Icon overlays are only supported on Windows");
+    }
+}
+
+/// The material drawn by Windows 11 behind the transparent parts of the window.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum WindowsBackdrop {
+    #[default]
+    None,
+    /// The frosted glass of transient windows, which blurs everything behind the window.
+    Acrylic,
+    /// The desktop wallpaper tint of main windows.
+    Mica,
+    /// A variant of mica with a stronger tint, used by tabbed windows.
+    Tabbed,
+}
+
+impl ParseFromValue for WindowsBackdrop {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(backdrop) = value.as_str() {
+            *self = match backdrop {
+                "none" => WindowsBackdrop::None,
+                "acrylic" => WindowsBackdrop::Acrylic,
+                "mica" => WindowsBackdrop::Mica,
+                "tabbed" => WindowsBackdrop::Tabbed,
+                value => {
+                    error!(
+                        "Expected none, acrylic, mica or tabbed, but received {:?}",
+                        value
+                    );
+                    return;
+                }
+            };
+        } else {
+            error!("Expected a backdrop string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<WindowsBackdrop> for Value {
+    fn from(backdrop: WindowsBackdrop) -> Self {
+        match backdrop {
+            WindowsBackdrop::None => Value::from("none"),
+            WindowsBackdrop::Acrylic => Value::from("acrylic"),
+            WindowsBackdrop::Mica => Value::from("mica"),
+            WindowsBackdrop::Tabbed => Value::from("tabbed"),
+        }
+    }
+}
+
+/// Shows the backdrop material behind the window, it's only visible where the window is
+/// transparent.
+#[allow(unused_variables)]
+pub fn set_backdrop(window: &Window, backdrop: WindowsBackdrop) {
+    #[cfg(windows)]
+    set_window_backdrop(window, backdrop);
+    #[cfg(not(windows))]
+    if backdrop != WindowsBackdrop::None {
+        log::warn!("Backdrop materials are only supported on Windows 11");
+    }
+}
+
+/// Whether the user asked the OS to reduce motion, which turns the animations off by default.
+/// Only detected on macOS and Windows.
+pub fn prefers_reduced_motion() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        super::macos::prefers_reduced_motion()
+    }
+    #[cfg(windows)]
+    {
+        crate::windows_utils::prefers_reduced_motion()
+    }
+    #[cfg(not(any(windows, target_os = "macos")))]
+    {
+        false
+    }
+}
+
+/// Whether a high contrast theme is enabled in the accessibility settings of the OS, which turns
+/// the high contrast mode on by default. On Linux it's false until the settings read in the
+/// background are ready, see `wait_for_desktop_settings`.
+pub fn prefers_high_contrast() -> bool {
+    #[cfg(target_os = "macos")]
+    {
+        super::macos::prefers_high_contrast()
+    }
+    #[cfg(windows)]
+    {
+        crate::windows_utils::prefers_high_contrast()
+    }
+    #[cfg(target_os = "linux")]
+    {
+        DESKTOP_SETTINGS
+            .get()
+            .is_some_and(|settings| settings.high_contrast)
+    }
+    #[cfg(not(any(windows, target_os = "macos", target_os = "linux")))]
+    {
+        false
+    }
+}
+
+/// The text size chosen in the accessibility settings of the OS, relative to the normal size. It
+/// scales the default font size. macOS has no such setting.
+pub fn text_scale_factor() -> f32 {
+    #[cfg(windows)]
+    let factor = crate::windows_utils::text_scale_factor();
+    #[cfg(target_os = "linux")]
+    let factor = desktop_settings().text_scale_factor;
+    #[cfg(not(any(windows, target_os = "linux")))]
+    let factor = None;
+    factor
+        .filter(|factor| factor.is_finite() && *factor > 0.0)
+        .unwrap_or(1.0)
+}
+
+/// The accessibility settings of GNOME, which most other desktops also keep up to date for GTK
+/// applications.
+#[cfg(target_os = "linux")]
+#[derive(Clone, Copy, Debug, Default)]
+struct DesktopSettings {
+    high_contrast: bool,
+    text_scale_factor: Option<f32>,
+}
+
+#[cfg(target_os = "linux")]
+static DESKTOP_SETTINGS: OnceLock<DesktopSettings> = OnceLock::new();
+#[cfg(target_os = "linux")]
+static DESKTOP_SETTINGS_THREAD: Mutex<Option<JoinHandle<DesktopSettings>>> = Mutex::new(None);
+
+/// Starts reading the accessibility settings of the desktop in the background, since on Linux it
+/// takes starting `gsettings`, so that they are usually ready before the fonts are loaded.
+pub fn start_reading_desktop_settings() {
+    #[cfg(target_os = "linux")]
+    {
+        *DESKTOP_SETTINGS_THREAD.lock().unwrap() = Some(thread::spawn(read_desktop_settings));
+    }
+}
+
+/// Waits until the settings read in the background are ready. It returns at once on the other
+/// platforms, which read them directly.
+pub fn wait_for_desktop_settings() {
+    #[cfg(target_os = "linux")]
+    desktop_settings();
+}
+
+#[cfg(target_os = "linux")]
+fn desktop_settings() -> DesktopSettings {
+    *DESKTOP_SETTINGS.get_or_init(|| {
+        let thread = DESKTOP_SETTINGS_THREAD.lock().unwrap().take();
+        match thread {
+            Some(thread) => thread.join().unwrap_or_default(),
+            None => read_desktop_settings(),
+        }
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn read_desktop_settings() -> DesktopSettings {
+    // Both are read at the same time
+    let high_contrast = spawn_gsettings("org.gnome.desktop.a11y.interface", "high-contrast");
+    let text_scale_factor = spawn_gsettings("org.gnome.desktop.interface", "text-scaling-factor");
+    DesktopSettings {
+        high_contrast: gsettings_output(high_contrast).as_deref() == Some("true"),
+        text_scale_factor: gsettings_output(text_scale_factor)
+            .and_then(|factor| factor.parse::<f32>().ok()),
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn spawn_gsettings(schema: &str, key: &str) -> Option<Child> {
+    Command::new("gsettings")
+        .args(["get", schema, key])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .ok()
+}
+
+#[cfg(target_os = "linux")]
+fn gsettings_output(child: Option<Child>) -> Option<String> {
+    let output = child?.wait_with_output().ok()?;
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+/// Sets the count of the launcher entry through the Unity launcher API, which is supported by
+/// for example the Ubuntu dock, KDE Plasma and Dash to Dock. Only numbers can be shown.
+#[cfg(target_os = "linux")]
+fn set_launcher_count(badge: Option<&str>) {
+    let properties = match badge.map(|badge| badge.trim().parse::<i64>()) {
+        Some(Ok(count)) => format!("{{'count': <int64 {count}>, 'count-visible': <true>}}"),
+        Some(Err(_)) => {
+            log::warn!("The launcher badge can only show numbers, but received {badge:?}");
+            return;
+        }
+        None => "{'count-visible': <false>}".to_string(),
+    };
+    thread::spawn(move || {
+        let result = Command::new("gdbus")
+            .args([
+                "emit",
+                "--session",
+                "--object-path",
+                "/",
+                "--signal",
+                "com.canonical.Unity.LauncherEntry.Update",
+                "application://neovide.desktop",
+                &properties,
+            ])
+            .output();
+        if let Err(error) = result {
+            log::error!("Could not update the launcher badge: {error}");
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use image::{ImageFormat, Rgba};
+
+    use super::*;
+    use crate::utils::encode_base64;
+
+    #[test]
+    fn test_load_window_icon_from_base64() {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        let data = encode_base64(&png);
+
+        assert!(load_window_icon(&data).is_ok());
+        assert!(load_window_icon(&format!("data:image/png;base64,{data}")).is_ok());
+        assert!(load_window_icon("not an image").is_err());
+    }
+}
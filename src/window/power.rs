@@ -0,0 +1,216 @@
+//! Switches to a low-power profile on battery: the animations are turned off, frames are only
+//! rendered when something changes and the refresh rate is lowered.
+
+use std::{
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+    time::Duration,
+};
+
+use log::error;
+use rmpv::Value;
+
+use crate::{
+    bridge::{send_ui, ParallelCommand},
+    settings::{ParseFromValue, SETTINGS},
+    window::WindowSettings,
+};
+
+/// How often the power source is checked.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+/// The highest refresh rate while saving power.
+const POWER_SAVING_REFRESH_RATE: u64 = 30;
+
+static ON_BATTERY: AtomicBool = AtomicBool::new(false);
+
+/// When the low-power profile is used, set with `g:neovide_power_saving`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PowerSaving {
+    /// Only while running on battery.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ParseFromValue for PowerSaving {
+    fn parse_from_value(&mut self, value: Value) {
+        if let Some(mode) = value.as_str() {
+            *self = match mode {
+                "auto" => PowerSaving::Auto,
+                "always" => PowerSaving::Always,
+                "never" => PowerSaving::Never,
+                value => {
+                    error!("Expected auto, always or never, but received {:?}", value);
+                    return;
+                }
+            };
+        } else {
+            error!("Expected a power saving string, but received {:?}", value);
+        }
+    }
+}
+
+impl From<PowerSaving> for Value {
+    fn from(mode: PowerSaving) -> Self {
+        match mode {
+            PowerSaving::Auto => Value::from("auto"),
+            PowerSaving::Always => Value::from("always"),
+            PowerSaving::Never => Value::from("never"),
+        }
+    }
+}
+
+/// True while the low-power profile is used.
+pub fn power_saving() -> bool {
+    match SETTINGS.get::<WindowSettings>().power_saving {
+        PowerSaving::Auto => ON_BATTERY.load(Ordering::Relaxed),
+        PowerSaving::Always => true,
+        PowerSaving::Never => false,
+    }
+}
+
+/// The refresh rate, lowered while saving power.
+pub fn limit_refresh_rate(refresh_rate: u64) -> u64 {
+    if power_saving() {
+        refresh_rate.min(POWER_SAVING_REFRESH_RATE)
+    } else {
+        refresh_rate
+    }
+}
+
+/// Checks the power source in the background, and fires the `NeovidePowerChanged` autocommand
+/// when it changes.
+pub fn watch_power_source() {
+    let Some(on_battery) = is_on_battery() else {
+        log::info!("The power source is unknown, power saving is only used when always enabled");
+        return;
+    };
+    ON_BATTERY.store(on_battery, Ordering::Relaxed);
+    let spawned = thread::Builder::new()
+        .name("power_source".to_owned())
+        .spawn(|| loop {
+            thread::sleep(POLL_INTERVAL);
+            let Some(on_battery) = is_on_battery() else {
+                continue;
+            };
+            if ON_BATTERY.swap(on_battery, Ordering::Relaxed) != on_battery {
+                log::info!("Running on {}", if on_battery { "battery" } else { "AC" });
+                send_ui(ParallelCommand::PowerChanged {
+                    on_battery,
+                    power_saving: power_saving(),
+                });
+            }
+        });
+    if let Err(error) = spawned {
+        error!("Could not watch the power source: {error}");
+    }
+}
+
+/// A power supply from `/sys/class/power_supply`.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct PowerSupply {
+    kind: String,
+    online: bool,
+    /// `Device` for the batteries of peripherals, like wireless mice.
+    scope: String,
+}
+
+/// On battery when there's a system battery, and no power supply is online.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn on_battery_power(supplies: &[PowerSupply]) -> bool {
+    let has_battery = supplies
+        .iter()
+        .any(|supply| supply.kind == "Battery" && supply.scope != "Device");
+    has_battery
+        && !supplies
+            .iter()
+            .any(|supply| supply.kind != "Battery" && supply.online)
+}
+
+#[cfg(target_os = "linux")]
+fn is_on_battery() -> Option<bool> {
+    use std::fs;
+
+    let read = |path: &std::path::Path, name: &str| {
+        fs::read_to_string(path.join(name))
+            .map(|value| value.trim().to_string())
+            .unwrap_or_default()
+    };
+    let supplies = fs::read_dir("/sys/class/power_supply")
+        .ok()?
+        .flatten()
+        .map(|entry| {
+            let path = entry.path();
+            PowerSupply {
+                kind: read(&path, "type"),
+                online: read(&path, "online") == "1",
+                scope: read(&path, "scope"),
+            }
+        })
+        .collect::<Vec<_>>();
+    Some(on_battery_power(&supplies))
+}
+
+#[cfg(target_os = "windows")]
+fn is_on_battery() -> Option<bool> {
+    use winapi::um::winbase::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status: SYSTEM_POWER_STATUS = unsafe { std::mem::zeroed() };
+    if unsafe { GetSystemPowerStatus(&mut status) } == 0 {
+        return None;
+    }
+    match status.ACLineStatus {
+        0 => Some(true),
+        1 => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(target_os = "macos")]
+fn is_on_battery() -> Option<bool> {
+    #[link(name = "IOKit", kind = "framework")]
+    extern "C" {
+        fn IOPSGetTimeRemainingEstimate() -> f64;
+    }
+    // kIOPSTimeRemainingUnlimited, when connected to a power adapter
+    const TIME_REMAINING_UNLIMITED: f64 = -2.0;
+    Some(unsafe { IOPSGetTimeRemainingEstimate() } != TIME_REMAINING_UNLIMITED)
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "windows", target_os = "macos")))]
+fn is_on_battery() -> Option<bool> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn supply(kind: &str, online: bool, scope: &str) -> PowerSupply {
+        PowerSupply {
+            kind: kind.to_string(),
+            online,
+            scope: scope.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_on_battery_power() {
+        let battery = supply("Battery", false, "");
+        let mouse = supply("Battery", false, "Device");
+        assert!(on_battery_power(&[battery]));
+        assert!(!on_battery_power(&[
+            supply("Battery", false, ""),
+            supply("Mains", true, "")
+        ]));
+        assert!(on_battery_power(&[
+            supply("Battery", false, ""),
+            supply("Mains", false, ""),
+            supply("USB", false, "")
+        ]));
+        // Desktops without a battery, even with a wireless mouse
+        assert!(!on_battery_power(&[mouse]));
+        assert!(!on_battery_power(&[]));
+    }
+}
@@ -0,0 +1,54 @@
+use rmpv::Value;
+
+/// The progress of a long running task, set by plugins with `neovide.set_progress`. It's shown in
+/// the taskbar on Windows, in the dock on macOS and as a line at the top of the window elsewhere.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Progress {
+    Indeterminate,
+    /// The completed fraction, between 0 and 1.
+    Value(f32),
+}
+
+impl Progress {
+    /// Parses a percentage, `"indeterminate"` or `nil`, which clears the progress.
+    pub fn parse(value: &Value) -> Result<Option<Self>, String> {
+        match value {
+            Value::Nil => Ok(None),
+            Value::String(_) if value.as_str() == Some("indeterminate") => {
+                Ok(Some(Self::Indeterminate))
+            }
+            Value::Integer(_) | Value::F32(_) | Value::F64(_) => {
+                let percentage = value.as_f64().unwrap_or_default();
+                Ok(Some(Self::Value(
+                    (percentage / 100.0).clamp(0.0, 1.0) as f32
+                )))
+            }
+            _ => Err(format!(
+                "Expected a percentage, \"indeterminate\" or nil, but received {value}"
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(Progress::parse(&Value::Nil), Ok(None));
+        assert_eq!(
+            Progress::parse(&Value::from("indeterminate")),
+            Ok(Some(Progress::Indeterminate))
+        );
+        assert_eq!(
+            Progress::parse(&Value::from(50)),
+            Ok(Some(Progress::Value(0.5)))
+        );
+        assert_eq!(
+            Progress::parse(&Value::from(150.0)),
+            Ok(Some(Progress::Value(1.0)))
+        );
+        assert!(Progress::parse(&Value::from("done")).is_err());
+    }
+}
@@ -1,14 +1,29 @@
-use crate::{cmd_line::CmdLineSettings, settings::*};
+use crate::{
+    bridge::{FileOpenTarget, Osc52Access},
+    cmd_line::CmdLineSettings,
+    settings::*,
+    window::{
+        ComposeMode, FullscreenMode, OptionAsMeta, PowerSaving, Shortcuts, TerminalColors,
+        WindowsBackdrop,
+    },
+};
 
 #[derive(Clone, SettingGroup, PartialEq)]
 pub struct WindowSettings {
     pub refresh_rate: u64,
     pub refresh_rate_idle: u64,
     pub idle: bool,
+    pub power_saving: PowerSaving,
+    pub frame_skipping: bool,
+    pub max_frame_latency: u32,
+    #[range = "0.0..=1.0"]
     pub transparency: f32,
     pub window_blurred: bool,
+    pub windows_backdrop: WindowsBackdrop,
     pub scale_factor: f32,
     pub fullscreen: bool,
+    pub fullscreen_mode: FullscreenMode,
+    pub fullscreen_monitor: String,
     pub iso_layout: bool,
     pub remember_window_size: bool,
     pub remember_window_position: bool,
@@ -17,15 +32,44 @@ pub struct WindowSettings {
     pub touch_drag_timeout: f32,
     pub background_color: String,
     pub confirm_quit: bool,
-    pub padding_top: u32,
-    pub padding_left: u32,
-    pub padding_right: u32,
-    pub padding_bottom: u32,
+    pub padding_top: i32,
+    pub padding_left: i32,
+    pub padding_right: i32,
+    pub padding_bottom: i32,
+    pub padding_animation_length: f32,
+    pub padding_color: String,
+    pub padding_extend_background: bool,
     pub theme: String,
+    pub colorscheme_light: String,
+    pub colorscheme_dark: String,
+    pub terminal_colors: TerminalColors,
     pub input_macos_alt_is_meta: bool,
+    pub input_macos_option_key_is_meta: OptionAsMeta,
+    pub open_files_in: FileOpenTarget,
+    pub open_multiple_files_in: FileOpenTarget,
+    pub project_launcher: bool,
     pub input_ime: bool,
+    pub input_compose: ComposeMode,
+    pub input_instant_echo: bool,
+    pub input_raw_key_events: bool,
     pub unlink_border_highlights: bool,
     pub show_border: bool,
+    pub resize_animation_length: f32,
+    pub resize_preview: bool,
+    pub clipboard_osc52_read: Osc52Access,
+    pub clipboard_osc52_write: Osc52Access,
+    pub clipboard_osc52_max_size: u64,
+    pub shortcuts: Shortcuts,
+    pub zen_mode_scale: f32,
+    pub zen_mode_padding: u32,
+    pub zen_mode_dim_monitors: bool,
+    pub visual_bell_flash_taskbar: bool,
+    pub audio_bell_focused: bool,
+    pub audio_bell_unfocused: bool,
+    pub audio_bell_sound: String,
+    pub audio_bell_min_interval: f32,
+    pub busy_timeout: f32,
+    pub recover_on_crash: bool,
 
     #[option = "mousemoveevent"]
     pub mouse_move_event: bool,
@@ -40,12 +84,18 @@ impl Default for WindowSettings {
         Self {
             transparency: 1.0,
             window_blurred: false,
+            windows_backdrop: WindowsBackdrop::None,
             scale_factor: 1.0,
             fullscreen: false,
+            fullscreen_mode: FullscreenMode::Native,
+            fullscreen_monitor: "".to_string(),
             iso_layout: false,
             refresh_rate: 60,
             refresh_rate_idle: 5,
             idle: SETTINGS.get::<CmdLineSettings>().idle,
+            power_saving: PowerSaving::Auto,
+            frame_skipping: true,
+            max_frame_latency: 0,
             remember_window_size: true,
             remember_window_position: true,
             hide_mouse_when_typing: false,
@@ -57,14 +107,43 @@ impl Default for WindowSettings {
             padding_left: 0,
             padding_right: 0,
             padding_bottom: 0,
+            padding_animation_length: 0.15,
+            padding_color: "".to_string(),
+            padding_extend_background: false,
             theme: "".to_string(),
+            colorscheme_light: String::new(),
+            colorscheme_dark: String::new(),
+            terminal_colors: TerminalColors::default(),
             input_macos_alt_is_meta: false,
+            input_macos_option_key_is_meta: OptionAsMeta::None,
+            open_files_in: FileOpenTarget::Tabs,
+            open_multiple_files_in: FileOpenTarget::Tabs,
+            project_launcher: true,
             input_ime: true,
+            input_compose: ComposeMode::System,
+            input_instant_echo: false,
+            input_raw_key_events: false,
             mouse_move_event: false,
             observed_lines: None,
             observed_columns: None,
             unlink_border_highlights: true,
             show_border: false,
+            clipboard_osc52_read: Osc52Access::Confirm,
+            clipboard_osc52_write: Osc52Access::Allow,
+            clipboard_osc52_max_size: 1024 * 1024,
+            resize_animation_length: 0.15,
+            resize_preview: false,
+            shortcuts: Shortcuts::default(),
+            zen_mode_scale: 1.25,
+            zen_mode_padding: 100,
+            zen_mode_dim_monitors: false,
+            visual_bell_flash_taskbar: false,
+            audio_bell_focused: true,
+            audio_bell_unfocused: false,
+            audio_bell_sound: "".to_string(),
+            audio_bell_min_interval: 0.25,
+            busy_timeout: 5.0,
+            recover_on_crash: false,
         }
     }
 }
@@ -0,0 +1,258 @@
+use std::{collections::BTreeMap, env, process::Command};
+
+use log::error;
+use rmpv::Value;
+
+use crate::{
+    bridge::{send_ui, ParallelCommand, SerialCommand},
+    clipboard,
+    settings::{ParseFromValue, SETTINGS},
+    window::{WindowSettings, WINDOW_GEOMETRY},
+};
+
+/// The factor the scale factor is multiplied or divided by when zooming.
+const ZOOM_STEP: f64 = 1.1;
+
+/// A window level action that can be bound to a key chord with `g:neovide_shortcuts`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ShortcutAction {
+    ToggleFullscreen,
+    ZoomIn,
+    ZoomOut,
+    ZoomReset,
+    NewWindow,
+    Paste,
+    Quit,
+    CommandPalette,
+    Interrupt,
+    KillNeovim,
+}
+
+impl ShortcutAction {
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "toggle_fullscreen" => Some(ShortcutAction::ToggleFullscreen),
+            "zoom_in" => Some(ShortcutAction::ZoomIn),
+            "zoom_out" => Some(ShortcutAction::ZoomOut),
+            "zoom_reset" => Some(ShortcutAction::ZoomReset),
+            "new_window" => Some(ShortcutAction::NewWindow),
+            "paste" => Some(ShortcutAction::Paste),
+            "quit" => Some(ShortcutAction::Quit),
+            "command_palette" => Some(ShortcutAction::CommandPalette),
+            "interrupt" => Some(ShortcutAction::Interrupt),
+            "kill_neovim" => Some(ShortcutAction::KillNeovim),
+            _ => None,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ShortcutAction::ToggleFullscreen => "toggle_fullscreen",
+            ShortcutAction::ZoomIn => "zoom_in",
+            ShortcutAction::ZoomOut => "zoom_out",
+            ShortcutAction::ZoomReset => "zoom_reset",
+            ShortcutAction::NewWindow => "new_window",
+            ShortcutAction::Paste => "paste",
+            ShortcutAction::Quit => "quit",
+            ShortcutAction::CommandPalette => "command_palette",
+            ShortcutAction::Interrupt => "interrupt",
+            ShortcutAction::KillNeovim => "kill_neovim",
+        }
+    }
+}
+
+/// Multiplies `g:neovide_scale_factor` by `factor`, or resets it when `None`. The variable is
+/// changed through Neovim, so that both sides stay in sync.
+pub fn zoom(factor: Option<f64>) {
+    let scale_factor = match factor {
+        Some(factor) => SETTINGS.get::<WindowSettings>().scale_factor as f64 * factor,
+        None => 1.0,
+    };
+    send_ui(ParallelCommand::SetVariable {
+        name: "neovide_scale_factor".to_string(),
+        value: Value::from(scale_factor),
+    });
+}
+
+pub fn zoom_in() {
+    zoom(Some(ZOOM_STEP));
+}
+
+pub fn zoom_out() {
+    zoom(Some(1.0 / ZOOM_STEP));
+}
+
+/// Starts another Neovide instance.
+pub fn open_new_window() {
+    open_in_new_window(&[]);
+}
+
+/// Starts another Neovide instance with the files. It's kept from forwarding them back to this
+/// one, when started with `--single-instance`.
+pub fn open_in_new_window(files: &[String]) {
+    // Used by `--position cascade`
+    let cascade_from = WINDOW_GEOMETRY
+        .read()
+        .unwrap()
+        .position
+        .map(|position| format!("--cascade-from={},{}", position.x, position.y));
+    let result = env::current_exe().and_then(|exe| {
+        Command::new(exe)
+            .args(cascade_from)
+            .args(files)
+            .env("NEOVIDE_SINGLE_INSTANCE", "0")
+            .spawn()
+    });
+    if let Err(error) = result {
+        error!("Could not start a new Neovide window: {error}");
+    }
+}
+
+/// Pastes the system clipboard in any mode.
+pub fn paste_clipboard() {
+    match clipboard::get_contents("+") {
+        Ok(text) => send_ui(SerialCommand::Paste(text)),
+        Err(error) => error!("Could not read the clipboard: {error}"),
+    }
+}
+
+/// The key chords, in Neovim notation, bound to window actions. The chords are stored normalized,
+/// so that for example `<C-S-v>` and `<S-C-V>` are the same.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Shortcuts {
+    bindings: BTreeMap<String, (String, ShortcutAction)>,
+}
+
+impl Shortcuts {
+    pub fn get(&self, keys: &str) -> Option<ShortcutAction> {
+        self.bindings
+            .get(&normalize_chord(keys))
+            .map(|(_, action)| *action)
+    }
+}
+
+/// The chords handled by Neovide while Neovim is not responding, in addition to the shortcuts.
+pub fn busy_shortcut(keys: &str) -> Option<ShortcutAction> {
+    match normalize_chord(keys).as_str() {
+        "<C-c>" => Some(ShortcutAction::Interrupt),
+        "<S-C-k>" => Some(ShortcutAction::KillNeovim),
+        _ => None,
+    }
+}
+
+/// Orders the modifiers and lowercases the key names, the shift modifier is enough to distinguish
+/// upper case letters.
+fn normalize_chord(chord: &str) -> String {
+    let Some(inner) = chord
+        .strip_prefix('<')
+        .and_then(|chord| chord.strip_suffix('>'))
+        .filter(|inner| !inner.is_empty())
+    else {
+        return chord.to_string();
+    };
+
+    let mut modifiers = [false; 4];
+    let mut key = inner;
+    while key.len() > 2 && key.as_bytes()[1] == b'-' {
+        let index = match key.as_bytes()[0].to_ascii_uppercase() {
+            b'S' => 0,
+            b'C' => 1,
+            b'M' | b'A' => 2,
+            b'D' => 3,
+            _ => break,
+        };
+        modifiers[index] = true;
+        key = &key[2..];
+    }
+    let key = if key == "lt" { "<" } else { key };
+
+    let mut normalized = String::from("<");
+    for (enabled, name) in modifiers.iter().zip(["S-", "C-", "M-", "D-"]) {
+        if *enabled {
+            normalized += name;
+        }
+    }
+    normalized += &key.to_lowercase();
+    normalized += ">";
+    normalized
+}
+
+impl ParseFromValue for Shortcuts {
+    fn parse_from_value(&mut self, value: Value) {
+        let Value::Map(entries) = value else {
+            error!("Expected a map of shortcuts, but received {:?}", value);
+            return;
+        };
+        let mut bindings = BTreeMap::new();
+        for (chord, action) in entries {
+            let (Some(chord), Some(name)) = (chord.as_str(), action.as_str()) else {
+                error!("Expected a key chord and an action name, but received {chord} = {action}");
+                continue;
+            };
+            match ShortcutAction::parse(name) {
+                Some(action) => {
+                    bindings.insert(normalize_chord(chord), (chord.to_string(), action));
+                }
+                None => error!("Unknown shortcut action {:?} for {}", name, chord),
+            }
+        }
+        self.bindings = bindings;
+    }
+}
+
+impl From<Shortcuts> for Value {
+    fn from(shortcuts: Shortcuts) -> Self {
+        Value::Map(
+            shortcuts
+                .bindings
+                .into_values()
+                .map(|(chord, action)| (Value::from(chord), Value::from(action.name())))
+                .collect(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_chord() {
+        assert_eq!(normalize_chord("<C-S-v>"), normalize_chord("<S-C-V>"));
+        assert_eq!(normalize_chord("<D-=>"), "<D-=>");
+        assert_eq!(normalize_chord("<A-F11>"), "<M-f11>");
+        assert_eq!(normalize_chord("<C-->"), "<C-->");
+        assert_eq!(normalize_chord("<C-lt>"), "<C-<>");
+        assert_eq!(normalize_chord("a"), "a");
+    }
+
+    #[test]
+    fn test_busy_shortcuts() {
+        assert_eq!(busy_shortcut("<C-c>"), Some(ShortcutAction::Interrupt));
+        assert_eq!(busy_shortcut("<C-S-K>"), Some(ShortcutAction::KillNeovim));
+        assert_eq!(busy_shortcut("<C-k>"), None);
+    }
+
+    #[test]
+    fn test_parse_shortcuts() {
+        let mut shortcuts = Shortcuts::default();
+        shortcuts.parse_from_value(Value::Map(vec![
+            (Value::from("<C-S-v>"), Value::from("paste")),
+            (Value::from("<F11>"), Value::from("toggle_fullscreen")),
+            (Value::from("<C-q>"), Value::from("unknown")),
+        ]));
+        assert_eq!(shortcuts.get("<S-C-V>"), Some(ShortcutAction::Paste));
+        assert_eq!(
+            shortcuts.get("<F11>"),
+            Some(ShortcutAction::ToggleFullscreen)
+        );
+        assert_eq!(shortcuts.get("<C-q>"), None);
+        assert_eq!(
+            Value::from(shortcuts),
+            Value::Map(vec![
+                (Value::from("<C-S-v>"), Value::from("paste")),
+                (Value::from("<F11>"), Value::from("toggle_fullscreen")),
+            ])
+        );
+    }
+}
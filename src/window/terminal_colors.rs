@@ -0,0 +1,244 @@
+//! Sends the colors of the terminals to Neovim, derived from the colorscheme or given by the user,
+//! so that `:terminal` buffers match the rest of the window.
+
+use log::error;
+use rmpv::Value;
+use skia_safe::Color4f;
+
+use crate::{
+    bridge::{send_ui, ParallelCommand},
+    editor::Colors,
+    settings::{ParseFromValue, SETTINGS},
+    window::WindowSettings,
+};
+
+/// The hues of red, green, yellow, blue, magenta and cyan, the colors 1 to 6 of the palette.
+const HUES: [f32; 6] = [0.0, 120.0, 55.0, 220.0, 300.0, 185.0];
+/// How much of the foreground is mixed into the hues, so that they take the tint of the theme.
+const FOREGROUND_TINT: f32 = 0.2;
+
+/// Where the colors of the terminals come from, set with `g:neovide_terminal_colors`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub enum TerminalColors {
+    /// The ones set by the colorscheme, which Neovide leaves alone.
+    #[default]
+    Colorscheme,
+    /// Computed from the foreground and background of the colorscheme.
+    Derive,
+    /// The 16 colors given by the user, as `#rrggbb`.
+    Custom(Vec<String>),
+}
+
+impl ParseFromValue for TerminalColors {
+    fn parse_from_value(&mut self, value: Value) {
+        match &value {
+            Value::String(mode) => match mode.as_str() {
+                Some("colorscheme") => *self = TerminalColors::Colorscheme,
+                Some("derive") => *self = TerminalColors::Derive,
+                _ => error!(
+                    "Expected colorscheme, derive or a list of 16 colors, but received {:?}",
+                    value
+                ),
+            },
+            Value::Array(colors) => {
+                let parsed: Option<Vec<_>> = colors
+                    .iter()
+                    .map(|color| {
+                        let [r, g, b, _] = color
+                            .as_str()?
+                            .parse::<csscolorparser::Color>()
+                            .ok()?
+                            .to_rgba8();
+                        Some(format!("#{r:02x}{g:02x}{b:02x}"))
+                    })
+                    .collect();
+                match parsed {
+                    Some(colors) if colors.len() == 16 => *self = TerminalColors::Custom(colors),
+                    _ => error!("Expected a list of 16 colors, but received {:?}", value),
+                }
+            }
+            _ => error!(
+                "Expected a terminal colors string or list, but received {:?}",
+                value
+            ),
+        }
+    }
+}
+
+impl From<TerminalColors> for Value {
+    fn from(colors: TerminalColors) -> Self {
+        match colors {
+            TerminalColors::Colorscheme => Value::from("colorscheme"),
+            TerminalColors::Derive => Value::from("derive"),
+            TerminalColors::Custom(colors) => {
+                Value::Array(colors.into_iter().map(Value::from).collect())
+            }
+        }
+    }
+}
+
+/// Keeps `g:terminal_color_0` to `g:terminal_color_15` in sync with the colorscheme and the
+/// setting. Neovim reads them when a terminal is opened, so the ones already open keep their
+/// colors.
+#[derive(Default)]
+pub struct TerminalColorSync {
+    sent: Option<Vec<String>>,
+}
+
+impl TerminalColorSync {
+    /// Called after every `ColorScheme` event, since the colorscheme may have replaced the
+    /// variables even when the palette stays the same.
+    pub fn colorscheme_changed(&mut self, default_colors: &Colors) {
+        if self.sent.take().is_some() {
+            self.update(default_colors);
+        }
+    }
+
+    /// Called when the default colors or `g:neovide_terminal_colors` change.
+    pub fn update(&mut self, default_colors: &Colors) {
+        let palette = match SETTINGS.get::<WindowSettings>().terminal_colors {
+            TerminalColors::Colorscheme => {
+                // Give the variables back to the colorscheme
+                if self.sent.take().is_some() {
+                    send_ui(ParallelCommand::ExecLua {
+                        code: "for i = 0, 15 do vim.g['terminal_color_' .. i] = nil end"
+                            .to_string(),
+                        args: Vec::new(),
+                    });
+                }
+                return;
+            }
+            TerminalColors::Derive => {
+                let foreground = default_colors
+                    .foreground
+                    .unwrap_or(Color4f::new(1.0, 1.0, 1.0, 1.0));
+                let background = default_colors
+                    .background
+                    .unwrap_or(Color4f::new(0.0, 0.0, 0.0, 1.0));
+                derive_palette(foreground, background)
+                    .iter()
+                    .map(hex)
+                    .collect()
+            }
+            TerminalColors::Custom(colors) => colors,
+        };
+        if self.sent.as_ref() == Some(&palette) {
+            return;
+        }
+        for (index, color) in palette.iter().enumerate() {
+            send_ui(ParallelCommand::SetVariable {
+                name: format!("terminal_color_{index}"),
+                value: Value::from(color.as_str()),
+            });
+        }
+        self.sent = Some(palette);
+    }
+}
+
+/// The 16 colors of a terminal for the foreground and background. Black and white are shades
+/// between the darker and the lighter of them, and the other colors are lighter on dark
+/// backgrounds and darker on light ones, so that they can be read on the background.
+fn derive_palette(foreground: Color4f, background: Color4f) -> [Color4f; 16] {
+    let dark_background = luminance(background) < luminance(foreground);
+    let (dark, light) = if dark_background {
+        (background, foreground)
+    } else {
+        (foreground, background)
+    };
+    let hue = |hue: f32, bright: bool| {
+        let lightness = match (dark_background, bright) {
+            (true, false) => 0.6,
+            (true, true) => 0.72,
+            (false, false) => 0.4,
+            (false, true) => 0.3,
+        };
+        mix(hsl(hue, 0.65, lightness), foreground, FOREGROUND_TINT)
+    };
+    let mut palette = [dark; 16];
+    palette[0] = mix(dark, light, 0.1);
+    palette[7] = mix(light, dark, 0.15);
+    palette[8] = mix(dark, light, 0.4);
+    palette[15] = light;
+    for (index, hue_degrees) in HUES.iter().enumerate() {
+        palette[index + 1] = hue(*hue_degrees, false);
+        palette[index + 9] = hue(*hue_degrees, true);
+    }
+    palette
+}
+
+fn luminance(color: Color4f) -> f32 {
+    0.2126 * color.r + 0.7152 * color.g + 0.0722 * color.b
+}
+
+fn mix(a: Color4f, b: Color4f, amount: f32) -> Color4f {
+    Color4f::new(
+        a.r + (b.r - a.r) * amount,
+        a.g + (b.g - a.g) * amount,
+        a.b + (b.b - a.b) * amount,
+        1.0,
+    )
+}
+
+fn hsl(hue: f32, saturation: f32, lightness: f32) -> Color4f {
+    let chroma = (1.0 - (2.0 * lightness - 1.0).abs()) * saturation;
+    let sector = hue / 60.0;
+    let x = chroma * (1.0 - (sector % 2.0 - 1.0).abs());
+    let (r, g, b) = match sector as u32 {
+        0 => (chroma, x, 0.0),
+        1 => (x, chroma, 0.0),
+        2 => (0.0, chroma, x),
+        3 => (0.0, x, chroma),
+        4 => (x, 0.0, chroma),
+        _ => (chroma, 0.0, x),
+    };
+    let m = lightness - chroma / 2.0;
+    Color4f::new(r + m, g + m, b + m, 1.0)
+}
+
+fn hex(color: &Color4f) -> String {
+    let channel = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    format!(
+        "#{:02x}{:02x}{:02x}",
+        channel(color.r),
+        channel(color.g),
+        channel(color.b)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_terminal_colors() {
+        let mut colors = TerminalColors::default();
+        colors.parse_from_value(Value::from("derive"));
+        assert_eq!(colors, TerminalColors::Derive);
+        // Lists without 16 colors keep the previous value
+        colors.parse_from_value(Value::from(vec![Value::from("#ff0000")]));
+        assert_eq!(colors, TerminalColors::Derive);
+        let mut list = vec![Value::from("black"); 15];
+        list.push(Value::from("#FFFFFF"));
+        colors.parse_from_value(Value::from(list));
+        let TerminalColors::Custom(custom) = colors else {
+            panic!("Expected custom colors");
+        };
+        assert_eq!(custom[0], "#000000");
+        assert_eq!(custom[15], "#ffffff");
+    }
+
+    #[test]
+    fn test_derive_palette() {
+        let white = Color4f::new(1.0, 1.0, 1.0, 1.0);
+        let black = Color4f::new(0.0, 0.0, 0.0, 1.0);
+        let dark = derive_palette(white, black);
+        assert_eq!(hex(&dark[0]), "#1a1a1a");
+        assert_eq!(hex(&dark[15]), "#ffffff");
+        // Red is mostly red, and brighter on a dark background than on a light one
+        let light = derive_palette(black, white);
+        assert!(dark[1].r > dark[1].g && dark[1].r > dark[1].b);
+        assert!(luminance(dark[1]) > luminance(light[1]));
+        // Black stays the darker color on light backgrounds
+        assert_eq!(hex(&light[0]), "#1a1a1a");
+    }
+}
@@ -5,7 +5,9 @@ use winit::{
     event_loop::ControlFlow,
 };
 
-use super::{UserEvent, WindowSettings, WinitWindowWrapper};
+use super::{
+    limit_refresh_rate, power_saving, UserEvent, WindowSettings, WinitWindowWrapper, FRAME_TIMINGS,
+};
 use crate::{
     profiling::{tracy_plot, tracy_zone},
     settings::SETTINGS,
@@ -67,6 +69,11 @@ impl ShouldRender {
 }
 
 const MAX_ANIMATION_DT: f32 = 1.0 / 120.0;
+/// How many frames of lag the animations catch up in one frame. The rest is dropped, so that after
+/// a hitch the animations slow down for a moment instead of jumping.
+const MAX_CATCHUP_FRAMES: f64 = 3.0;
+/// A frame taking this many times its budget to render is slow enough to skip the next one.
+const SLOW_FRAME_FACTOR: f32 = 2.0;
 
 pub struct UpdateLoop {
     idle: bool,
@@ -79,6 +86,8 @@ pub struct UpdateLoop {
     pending_draw_commands: Vec<Event<UserEvent>>,
     animation_start: Instant,
     simulation_time: Duration,
+    last_render_duration: Duration,
+    skipped_last_frame: bool,
 }
 
 impl UpdateLoop {
@@ -104,6 +113,8 @@ impl UpdateLoop {
             pending_draw_commands,
             animation_start,
             simulation_time,
+            last_render_duration: Duration::ZERO,
+            skipped_last_frame: false,
         }
     }
 
@@ -112,7 +123,7 @@ impl UpdateLoop {
             // NOTE: Always wait for the idle refresh rate when winit throttling is used to avoid waking up too early
             // The winit redraw request will likely happen much before that and wake it up anyway
             FocusedState::Focused | FocusedState::UnfocusedNotDrawn => {
-                SETTINGS.get::<WindowSettings>().refresh_rate as f32
+                limit_refresh_rate(SETTINGS.get::<WindowSettings>().refresh_rate) as f32
             }
             _ => SETTINGS.get::<WindowSettings>().refresh_rate_idle as f32,
         }
@@ -143,7 +154,14 @@ impl UpdateLoop {
 
         let now = Instant::now();
         let animation_time = (now - self.animation_start).as_secs_f64();
-        let delta = animation_time - self.simulation_time.as_secs_f64();
+        let mut delta = animation_time - self.simulation_time.as_secs_f64();
+        let max_delta = MAX_CATCHUP_FRAMES * dt as f64;
+        if delta > max_delta {
+            // Compress the time when rendering can't keep up
+            tracy_plot!("Dropped animation time", delta - max_delta);
+            self.animation_start += Duration::from_secs_f64(delta - max_delta);
+            delta = max_delta;
+        }
         // Catchup immediately if the delta is more than one frame, otherwise smooth it over 10 frames
         let catchup = if delta >= dt as f64 {
             delta
@@ -166,7 +184,10 @@ impl UpdateLoop {
 
     pub fn render(&mut self, window_wrapper: &mut WinitWindowWrapper) {
         self.pending_render = false;
+        let render_start = Instant::now();
         window_wrapper.draw_frame(self.last_dt);
+        self.last_render_duration = render_start.elapsed();
+        self.skipped_last_frame = false;
 
         if let FocusedState::UnfocusedNotDrawn = self.focused {
             self.focused = FocusedState::Unfocused;
@@ -177,6 +198,32 @@ impl UpdateLoop {
         self.previous_frame_start = Instant::now();
     }
 
+    /// True when the last frame took so long to render that the next one should give the events
+    /// waiting in the queue, like input and draw commands, a chance to be handled first. Frames are
+    /// never skipped twice in a row.
+    fn should_skip_frame(&self, window_wrapper: &WinitWindowWrapper) -> bool {
+        if self.skipped_last_frame || !SETTINGS.get::<WindowSettings>().frame_skipping {
+            return false;
+        }
+        let frame_budget = window_wrapper
+            .vsync
+            .get_refresh_rate(window_wrapper.skia_renderer.window());
+        self.last_render_duration.as_secs_f32() > frame_budget * SLOW_FRAME_FACTOR
+    }
+
+    /// Presents a frame of the new size before the resize event returns to the OS, so that the
+    /// compositor never shows the stretched or uninitialized swap chain while dragging. Until Neovim
+    /// sends the resized grid, the last frame is stretched over the resized surface.
+    fn render_resized(&mut self, window_wrapper: &mut WinitWindowWrapper) {
+        if !window_wrapper.is_showing() {
+            return;
+        }
+        tracy_zone!("render (resized)");
+        self.should_render.update(window_wrapper.prepare_frame());
+        window_wrapper.animate_frame(0.0);
+        self.render(window_wrapper);
+    }
+
     pub fn step(
         &mut self,
         window_wrapper: &mut WinitWindowWrapper,
@@ -207,7 +254,7 @@ impl UpdateLoop {
                 if should_prepare {
                     self.should_render.update(window_wrapper.prepare_frame());
                     if self.should_render == ShouldRender::Immediately
-                        || !self.idle
+                        || (!self.idle && !power_saving())
                         || skipped_frame
                     {
                         self.should_render = ShouldRender::Wait;
@@ -227,11 +274,18 @@ impl UpdateLoop {
                                     .vsync
                                     .request_redraw(window_wrapper.skia_renderer.window());
                                 self.pending_render = true;
+                            } else if self.should_skip_frame(window_wrapper) {
+                                tracy_zone!("skipped frame");
+                                self.skipped_last_frame = true;
+                                self.should_render = ShouldRender::Immediately;
                             } else {
                                 self.render(window_wrapper);
                             }
                         }
                     } else {
+                        if self.num_consecutive_rendered > 0 {
+                            FRAME_TIMINGS.lock().unwrap().rendering_paused();
+                        }
                         self.num_consecutive_rendered = 0;
                         self.last_dt = self.previous_frame_start.elapsed().as_secs_f32();
                         self.previous_frame_start = Instant::now();
@@ -257,6 +311,13 @@ impl UpdateLoop {
             }
         }
 
+        let resized = matches!(
+            &event,
+            Ok(Event::WindowEvent {
+                event: WindowEvent::Resized(..),
+                ..
+            })
+        );
         if let Ok(event) = event {
             if self.pending_render
                 && matches!(&event, Event::UserEvent(UserEvent::DrawCommandBatch(_)))
@@ -266,6 +327,9 @@ impl UpdateLoop {
                 self.should_render = ShouldRender::Immediately;
             }
         }
+        if resized {
+            self.render_resized(window_wrapper);
+        }
         #[cfg(feature = "profiling")]
         self.should_render.plot_tracy();
 
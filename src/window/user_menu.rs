@@ -0,0 +1,99 @@
+use rmpv::Value;
+
+use crate::bridge::{send_ui, SerialCommand};
+
+/// A menu item defined from Lua.
+#[derive(Clone, Debug, PartialEq)]
+#[allow(dead_code)] // Only used on macOS
+pub struct UserMenuItem {
+    /// The menu bar menu the item is added to, for example `File`.
+    pub menu: Option<String>,
+    pub title: String,
+    /// The Ex command run when the item is selected, `None` for separators.
+    pub command: Option<String>,
+}
+
+/// Parses a list of `{ title = ..., command = ..., menu = ... }` tables. A title of `-` adds a
+/// separator.
+pub fn parse_menu_items(value: &Value) -> Result<Vec<UserMenuItem>, String> {
+    let Value::Array(items) = value else {
+        return Err(format!("Expected a list of menu items, got {value}"));
+    };
+    items
+        .iter()
+        .map(|item| {
+            let Value::Map(fields) = item else {
+                return Err(format!("Expected a menu item table, got {item}"));
+            };
+            let field = |name: &str| {
+                fields
+                    .iter()
+                    .find(|(key, _)| key.as_str() == Some(name))
+                    .and_then(|(_, value)| value.as_str())
+                    .map(str::to_string)
+            };
+            let title = field("title").ok_or_else(|| format!("Menu item without title: {item}"))?;
+            let command = match (title.as_str(), field("command")) {
+                ("-", _) => None,
+                (_, Some(command)) => Some(command),
+                (_, None) => return Err(format!("Menu item {title:?} has no command")),
+            };
+            Ok(UserMenuItem {
+                menu: field("menu"),
+                title,
+                command,
+            })
+        })
+        .collect()
+}
+
+/// Runs the command of a selected menu item. It's sent as keyboard input, so that it's ordered
+/// after the keys typed before the menu was opened.
+#[allow(dead_code)] // Not used on Linux
+pub fn run_menu_command(command: &str) {
+    send_ui(SerialCommand::Keyboard(format!(
+        "<Cmd>{}<CR>",
+        command.replace('<', "<lt>")
+    )));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(fields: &[(&str, &str)]) -> Value {
+        Value::Map(
+            fields
+                .iter()
+                .map(|(key, value)| (Value::from(*key), Value::from(*value)))
+                .collect(),
+        )
+    }
+
+    #[test]
+    fn test_parse_menu_items() {
+        let items = parse_menu_items(&Value::Array(vec![
+            table(&[("title", "Format"), ("command", "lua vim.lsp.buf.format()")]),
+            table(&[("title", "-"), ("menu", "Edit")]),
+        ]))
+        .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                UserMenuItem {
+                    menu: None,
+                    title: "Format".to_string(),
+                    command: Some("lua vim.lsp.buf.format()".to_string()),
+                },
+                UserMenuItem {
+                    menu: Some("Edit".to_string()),
+                    title: "-".to_string(),
+                    command: None,
+                },
+            ]
+        );
+
+        assert!(parse_menu_items(&Value::Array(vec![table(&[("title", "Format")])])).is_err());
+        assert!(parse_menu_items(&Value::from("Format")).is_err());
+    }
+}
@@ -1,19 +1,47 @@
+use std::{
+    mem,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+
 use super::{
-    KeyboardManager, MouseManager, UserEvent, WindowCommand, WindowSettings, WindowSettingsChanged,
+    accessibility::Accessibility, audio_bell::AudioBell, fullscreen::exclusive_video_mode,
+    fullscreen_to_restore, load_icon, monitors::find_monitor, platform_integration, shortcuts,
+    FullscreenMode, KeyboardManager, MouseManager, ResizeAnchor, ShortcutAction, UserEvent,
+    UserMenuItem, WindowCommand, WindowGeometry, WindowSettings, WindowSettingsChanged,
+    WindowsBackdrop, FRAME_TIMINGS, WINDOW_GEOMETRY,
 };
 
+use super::padding::{PaddingAnimation, PaddingOverride, WindowPadding};
+use super::terminal_colors::TerminalColorSync;
+use super::zen_mode::ZenMode;
 #[cfg(windows)]
-use crate::windows_utils::{register_right_click, unregister_right_click};
+use crate::windows_utils::{
+    cover_other_monitors, register_right_click, show_context_menu, unregister_right_click,
+    MonitorCover,
+};
 use crate::{
-    bridge::{send_ui, ParallelCommand, SerialCommand},
+    bridge::{
+        kill_embedded_neovim, restart_neovim, send_ui, FileOpenTarget, ParallelCommand,
+        SerialCommand,
+    },
     dimensions::Dimensions,
+    error_msg,
     profiling::{tracy_frame, tracy_gpu_collect, tracy_gpu_zone, tracy_plot, tracy_zone},
-    renderer::{create_skia_renderer, DrawCommand, Renderer, SkiaRenderer, VSync, WindowConfig},
+    renderer::{
+        animation_utils::{ease, ease_out_expo},
+        create_skia_renderer, max_frame_latency, open_url, CapturedFrame, CrashChoice, DrawCommand,
+        FrameRecorder, LinkChoice, PaletteAction, PaletteItem, PaletteItemKind, QuitChoice,
+        Renderer, RendererSettings, SkiaRenderer, SurfaceOptions, VSync, WindowConfig,
+        RENDERER_STATUS,
+    },
     running_tracker::RUNNING_TRACKER,
     settings::{
-        FontSettings, HotReloadConfigs, SettingsChanged, DEFAULT_GRID_SIZE, MIN_GRID_SIZE, SETTINGS,
+        load_last_window_settings, load_splash_color, windowed_settings, FontSettings,
+        HotReloadConfigs, PersistentWindowSettings, SettingsChanged, DEFAULT_GRID_SIZE,
+        MIN_GRID_SIZE, SETTINGS,
     },
-    window::{ShouldRender, WindowSize},
+    window::{power_saving, ShouldRender, WindowSize},
     CmdLineSettings,
 };
 
@@ -22,31 +50,72 @@ use super::macos::MacosWindowFeature;
 
 #[cfg(target_os = "macos")]
 use icrate::Foundation::MainThreadMarker;
+#[cfg(target_os = "macos")]
+use winit::platform::macos::WindowExtMacOS;
 
+use image::RgbaImage;
 use log::trace;
-use skia_safe::{scalar, Rect};
+use rmpv::Value;
+use skia_safe::{scalar, Color, Rect};
 use winit::{
     dpi::{PhysicalPosition, PhysicalSize, Position},
-    event::{Event, WindowEvent},
+    event::{ElementState, Event, MouseButton, WindowEvent},
     event_loop::EventLoopProxy,
-    window::{Fullscreen, Theme},
+    monitor::MonitorHandle,
+    window::{Fullscreen, Icon, Theme, UserAttentionType},
 };
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
-pub struct WindowPadding {
-    pub top: u32,
-    pub left: u32,
-    pub right: u32,
-    pub bottom: u32,
+/// The system theme is followed once it hasn't changed for this long, since some platforms send
+/// several changes when it's switched.
+const THEME_DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Sets `background` and the colorscheme of `g:neovide_colorscheme_light` or
+/// `g:neovide_colorscheme_dark` for the theme. When `follow` is false, only the autocommand is
+/// fired, for when `g:neovide_theme` isn't `auto`.
+fn apply_theme(theme: Theme, follow: bool, notify: bool) {
+    let settings = SETTINGS.get::<WindowSettings>();
+    let (name, colorscheme) = match theme {
+        Theme::Light => ("light", settings.colorscheme_light),
+        Theme::Dark => ("dark", settings.colorscheme_dark),
+    };
+    send_ui(ParallelCommand::ApplyTheme {
+        theme: name.to_string(),
+        set_background: follow,
+        colorscheme: (follow && !colorscheme.is_empty()).then_some(colorscheme),
+        notify,
+    });
 }
 
-pub fn set_background(background: &str) {
-    send_ui(ParallelCommand::SetBackground(background.to_string()));
+/// The grid is reflowed when the window hasn't been resized for this long while previewing.
+const RESIZE_SETTLE_TIME: Duration = Duration::from_millis(100);
+/// And at least this often while the window is still being dragged.
+const RESIZE_REFLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+/// How long the last frame is stretched over a resized window while waiting for Neovim to send the
+/// grid of the new size, in case it chooses another size, like when other UIs are attached.
+const GRID_RESIZE_TIMEOUT: Duration = Duration::from_millis(200);
+
+/// A resize of the window that is previewed with a stretched image of the last frame.
+struct LiveResize {
+    started: Instant,
+    last_change: Instant,
+    size: PhysicalSize<u32>,
+}
+
+/// An in progress resize of the window requested from Neovim, measured in grid cells.
+struct GridResizeAnimation {
+    start: Dimensions,
+    target: Dimensions,
+    current: Dimensions,
+    anchor: ResizeAnchor,
+    t: f32,
 }
 
 #[derive(PartialEq)]
 enum UIState {
     Initing, // Running init.vim/lua
+    Splash,  // Running init.vim/lua with the window already shown
+    Hidden,  // Ready as a --daemon, until a new invocation claims the window
     FirstFrame,
     Showing, // No pending resizes
 }
@@ -59,24 +128,92 @@ pub struct WinitWindowWrapper {
     keyboard_manager: KeyboardManager,
     mouse_manager: MouseManager,
     title: String,
-    fullscreen: bool,
+    // The mode the window is fullscreen in, kept to leave it the same way after the setting changes
+    fullscreen: Option<FullscreenMode>,
+    // How the window was before it entered fullscreen, saved with the fullscreen state
+    settings_before_fullscreen: Option<PersistentWindowSettings>,
+    // The monitor the window was fullscreen on when Neovide was closed, restored on the first frame
+    restore_fullscreen: Option<MonitorHandle>,
+    // The background shown until Neovim draws the first frame
+    splash_color: Color,
+    // Set for a --daemon until a new invocation claims it
+    waiting_daemon: bool,
     font_changed_last_frame: bool,
     saved_inner_size: PhysicalSize<u32>,
     saved_grid_size: Option<Dimensions>,
+    // The files dropped since the last event loop iteration, which are opened together
+    dropped_files: Vec<String>,
     ime_enabled: bool,
-    ime_position: PhysicalPosition<i32>,
+    ime_area: (PhysicalPosition<i32>, PhysicalSize<u32>),
     requested_columns: Option<u64>,
     requested_lines: Option<u64>,
+    resize_animation: Option<GridResizeAnimation>,
+    live_resize: Option<LiveResize>,
+    // The grid size requested from Neovim after the window was resized, and when to stop waiting
+    // for it
+    awaited_grid_size: Option<(Dimensions, Instant)>,
     ui_state: UIState,
     window_padding: WindowPadding,
+    // Moves the padding of the settings, or the one set with `neovide.set_padding`, smoothly
+    padding_animation: PaddingAnimation,
+    padding_override: Option<PaddingOverride>,
     initial_window_size: WindowSize,
     is_minimized: bool,
     theme: Option<Theme>,
+    // The theme of the system that was last followed, and the one it changed to, which is
+    // followed at the deadline unless it changes again
+    system_theme: Option<Theme>,
+    pending_system_theme: Option<(Theme, Instant)>,
+    context_menu: Vec<UserMenuItem>,
+    // Set while the right button press that opened the context menu hasn't been released
+    context_menu_pressed: bool,
+    // The last position of the mouse, which clicks on the palette are at
+    palette_mouse_position: (f32, f32),
+    terminal_colors: TerminalColorSync,
+    zen_mode: ZenMode,
+    #[cfg(windows)]
+    monitor_covers: Vec<MonitorCover>,
+    screenshot_paths: Vec<PathBuf>,
+    frame_recorder: Option<FrameRecorder>,
     pub vsync: VSync,
+    proxy: EventLoopProxy<UserEvent>,
+    accessibility: Accessibility,
+    audio_bell: AudioBell,
     #[cfg(target_os = "macos")]
     pub macos_feature: MacosWindowFeature,
 }
 
+/// Shows the window right away with the splash while Neovim starts, when its size doesn't depend
+/// on the grid size set in init.vim/lua. Returns true if it was shown.
+fn show_splash(
+    skia_renderer: &mut dyn SkiaRenderer,
+    initial_window_size: &WindowSize,
+    color: Color,
+) -> bool {
+    let window = skia_renderer.window();
+    match initial_window_size {
+        WindowSize::Size(..) => window.set_visible(true),
+        WindowSize::Maximized => {
+            window.set_visible(true);
+            window.set_maximized(true);
+        }
+        WindowSize::Grid(..) | WindowSize::NeovimGrid => return false,
+    }
+    skia_renderer.canvas().clear(color);
+    skia_renderer.flush();
+    skia_renderer.swap_buffers();
+    true
+}
+
+/// The alpha of the window background with `g:neovide_transparency`.
+fn window_alpha(opaque_surface: bool) -> u8 {
+    if opaque_surface {
+        255
+    } else {
+        (255.0 * SETTINGS.get::<WindowSettings>().transparency) as u8
+    }
+}
+
 impl WinitWindowWrapper {
     pub fn new(
         window: WindowConfig,
@@ -87,12 +224,31 @@ impl WinitWindowWrapper {
         let cmd_line_settings = SETTINGS.get::<CmdLineSettings>();
         let srgb = cmd_line_settings.srgb;
         let vsync_enabled = cmd_line_settings.vsync;
-        let skia_renderer = create_skia_renderer(window, srgb, vsync_enabled);
-        let window = skia_renderer.window();
+        let surface_options = SurfaceOptions::from_settings(&SETTINGS.get::<RendererSettings>());
+        let mut skia_renderer = create_skia_renderer(window, srgb, vsync_enabled, &surface_options);
+
+        // The splash is shown before the fonts are loaded, which can take a while on a cold start.
+        // It needs the GPU context, since a window shown without anything drawn on it flashes white
+        // or isn't mapped at all, depending on the platform.
+        let splash_color = load_splash_color().map_or(Color::BLACK, Color::new);
+        let waiting_daemon = cmd_line_settings.daemon;
+        let show_splash = !waiting_daemon
+            && show_splash(
+                skia_renderer.as_mut(),
+                &initial_window_size,
+                splash_color.with_a(window_alpha(surface_options.opaque())),
+            );
 
+        let window = skia_renderer.window();
         let scale_factor = skia_renderer.window().scale_factor();
         let renderer = Renderer::new(scale_factor, initial_font_settings);
         let saved_inner_size = window.inner_size();
+        let persistent_settings = load_last_window_settings().ok();
+        let restore_fullscreen = fullscreen_to_restore(window, persistent_settings.as_ref());
+        let settings_before_fullscreen = persistent_settings
+            .as_ref()
+            .and_then(PersistentWindowSettings::windowed)
+            .cloned();
 
         log::info!(
             "window created (scale_factor: {:.4}, font_dimensions: {:?})",
@@ -104,26 +260,34 @@ impl WinitWindowWrapper {
             input_ime,
             theme,
             window_blurred,
+            windows_backdrop,
             transparency,
+            max_frame_latency: frame_latency,
             ..
         } = SETTINGS.get::<WindowSettings>();
 
         skia_renderer
             .window()
-            .set_blur(window_blurred && transparency < 1.0);
-
-        match theme.as_str() {
-            "light" => set_background("light"),
-            "dark" => set_background("dark"),
-            "auto" => match window.theme() {
-                Some(Theme::Light) => set_background("light"),
-                Some(Theme::Dark) => set_background("dark"),
-                None => {}
-            },
-            _ => {}
+            .set_blur(window_blurred && transparency < 1.0 && !surface_options.opaque());
+        if windows_backdrop != WindowsBackdrop::None {
+            platform_integration::set_backdrop(skia_renderer.window(), windows_backdrop);
+        }
+
+        let system_theme = window.theme();
+        let initial_theme = match theme.as_str() {
+            "light" => Some(Theme::Light),
+            "dark" => Some(Theme::Dark),
+            "auto" => system_theme,
+            _ => None,
+        };
+        if let Some(initial_theme) = initial_theme {
+            apply_theme(initial_theme, true, false);
         }
 
-        let vsync = VSync::new(vsync_enabled, skia_renderer.as_ref(), proxy);
+        // The window is still hidden, as required by AccessKit
+        let accessibility = Accessibility::new(window, proxy.clone());
+        let vsync = VSync::new(vsync_enabled, skia_renderer.as_ref(), proxy.clone());
+        RENDERER_STATUS.write().unwrap().vsync = vsync.name().to_string();
 
         #[cfg(target_os = "macos")]
         let macos_feature = {
@@ -137,43 +301,176 @@ impl WinitWindowWrapper {
             keyboard_manager: KeyboardManager::new(),
             mouse_manager: MouseManager::new(),
             title: String::from("Neovide"),
-            fullscreen: false,
+            fullscreen: None,
+            settings_before_fullscreen,
+            restore_fullscreen,
+            splash_color,
+            waiting_daemon,
             font_changed_last_frame: false,
             saved_inner_size,
             saved_grid_size: None,
+            dropped_files: Vec::new(),
             ime_enabled: input_ime,
-            ime_position: PhysicalPosition::new(-1, -1),
+            ime_area: (PhysicalPosition::new(-1, -1), PhysicalSize::default()),
             requested_columns: None,
             requested_lines: None,
-            ui_state: UIState::Initing,
-            window_padding: WindowPadding {
-                left: 0,
-                right: 0,
-                top: 0,
-                bottom: 0,
+            resize_animation: None,
+            live_resize: None,
+            awaited_grid_size: None,
+            ui_state: if show_splash {
+                UIState::Splash
+            } else {
+                UIState::Initing
             },
+            window_padding: WindowPadding::default(),
+            padding_animation: PaddingAnimation::new(WindowPadding::default()),
+            padding_override: None,
             initial_window_size,
             is_minimized: false,
             theme: None,
+            system_theme,
+            pending_system_theme: None,
+            context_menu: Vec::new(),
+            context_menu_pressed: false,
+            palette_mouse_position: (0.0, 0.0),
+            terminal_colors: TerminalColorSync::default(),
+            zen_mode: ZenMode::default(),
+            #[cfg(windows)]
+            monitor_covers: Vec::new(),
+            screenshot_paths: Vec::new(),
+            frame_recorder: None,
             vsync,
+            proxy,
+            accessibility,
+            audio_bell: AudioBell::default(),
             #[cfg(target_os = "macos")]
             macos_feature,
         };
 
         wrapper.set_ime(input_ime);
         wrapper
+            .skia_renderer
+            .set_max_frame_latency(max_frame_latency(frame_latency));
+        wrapper.update_window_geometry();
+        if show_splash {
+            if let Some(monitor) = wrapper.restore_fullscreen.take() {
+                wrapper.restore_fullscreen(monitor);
+            }
+        }
+        wrapper
+    }
+
+    /// Enters fullscreen in the mode of `g:neovide_fullscreen_mode`, or leaves it.
+    fn set_fullscreen(&mut self, fullscreen: bool) {
+        let mode = fullscreen.then(|| SETTINGS.get::<WindowSettings>().fullscreen_mode);
+        if self.fullscreen == mode {
+            return;
+        }
+        // The windowed settings are only saved when entering from a window, so that switching
+        // between the modes doesn't replace them with the fullscreen size
+        match self.fullscreen.take() {
+            Some(previous) => self.leave_fullscreen(previous),
+            None => self.settings_before_fullscreen = Some(windowed_settings(self)),
+        }
+        if let Some(mode) = mode {
+            self.enter_fullscreen(mode, self.fullscreen_monitor());
+        }
+        self.fullscreen = mode;
+    }
+
+    /// Moves over to the mode and monitor of the settings when the window is already fullscreen.
+    /// The window isn't windowed in between, so the settings it's restored to when leaving
+    /// fullscreen are kept.
+    fn switch_fullscreen(&mut self) {
+        let Some(previous) = self.fullscreen else {
+            return;
+        };
+        let mode = SETTINGS.get::<WindowSettings>().fullscreen_mode;
+        self.leave_fullscreen(previous);
+        self.enter_fullscreen(mode, self.fullscreen_monitor());
+        self.fullscreen = Some(mode);
+    }
+
+    /// Makes the window fullscreen again on the monitor it was on when Neovide was closed.
+    fn restore_fullscreen(&mut self, monitor: MonitorHandle) {
+        let mode = SETTINGS.get::<WindowSettings>().fullscreen_mode;
+        self.enter_fullscreen(mode, Some(monitor));
+        self.fullscreen = Some(mode);
+        // Changed through the Neovim variable, so that it stays in sync
+        send_ui(ParallelCommand::SetVariable {
+            name: "neovide_fullscreen".to_string(),
+            value: Value::from(true),
+        });
     }
 
-    pub fn toggle_fullscreen(&mut self) {
+    fn update_terminal_colors(&mut self) {
+        let default_colors = &self.renderer.grid_renderer.default_style.colors;
+        self.terminal_colors.update(default_colors);
+    }
+
+    /// The default background color of Neovim, which is saved for the splash.
+    pub fn default_background(&self) -> Color {
+        self.renderer.grid_renderer.get_default_background()
+    }
+
+    pub fn is_fullscreen(&self) -> bool {
+        self.fullscreen.is_some()
+    }
+
+    /// The settings of the window from before it entered fullscreen.
+    pub fn settings_before_fullscreen(&self) -> Option<PersistentWindowSettings> {
+        self.settings_before_fullscreen.clone()
+    }
+
+    fn enter_fullscreen(&mut self, mode: FullscreenMode, monitor: Option<MonitorHandle>) {
         let window = self.skia_renderer.window();
-        if self.fullscreen {
-            window.set_fullscreen(None);
-        } else {
-            let handle = window.current_monitor();
-            window.set_fullscreen(Some(Fullscreen::Borderless(handle)));
+        match mode {
+            #[cfg(target_os = "macos")]
+            FullscreenMode::Simple | FullscreenMode::Borderless => {
+                // These cover the screen the window is on, so it's moved there first
+                if let Some(monitor) =
+                    monitor.filter(|monitor| window.current_monitor().as_ref() != Some(monitor))
+                {
+                    window.set_outer_position(monitor.position());
+                }
+                if mode == FullscreenMode::Simple {
+                    window.set_simple_fullscreen(true);
+                } else {
+                    self.macos_feature.set_borderless_fullscreen(true);
+                }
+            }
+            FullscreenMode::Exclusive => match monitor.as_ref().and_then(exclusive_video_mode) {
+                Some(video_mode) => window.set_fullscreen(Some(Fullscreen::Exclusive(video_mode))),
+                None => {
+                    log::warn!("No video mode for exclusive fullscreen, using borderless");
+                    window.set_fullscreen(Some(Fullscreen::Borderless(monitor)));
+                }
+            },
+            _ => window.set_fullscreen(Some(Fullscreen::Borderless(monitor))),
         }
+    }
 
-        self.fullscreen = !self.fullscreen;
+    /// The monitor of `g:neovide_fullscreen_monitor`, or the one the window is on.
+    fn fullscreen_monitor(&self) -> Option<MonitorHandle> {
+        let window = self.skia_renderer.window();
+        let selector = SETTINGS.get::<WindowSettings>().fullscreen_monitor;
+        if selector.is_empty() {
+            return window.current_monitor();
+        }
+        find_monitor(window.available_monitors(), &selector).or_else(|| window.current_monitor())
+    }
+
+    fn leave_fullscreen(&mut self, mode: FullscreenMode) {
+        let window = self.skia_renderer.window();
+        match mode {
+            #[cfg(target_os = "macos")]
+            FullscreenMode::Simple => {
+                window.set_simple_fullscreen(false);
+            }
+            #[cfg(target_os = "macos")]
+            FullscreenMode::Borderless => self.macos_feature.set_borderless_fullscreen(false),
+            _ => window.set_fullscreen(None),
+        }
     }
 
     pub fn minimize_window(&mut self) {
@@ -198,13 +495,134 @@ impl WinitWindowWrapper {
             WindowCommand::FocusWindow => {
                 self.skia_renderer.window().focus_window();
             }
+            WindowCommand::ShowDaemonWindow => {
+                // Still starting, then the window is shown when Neovim is ready
+                self.waiting_daemon = false;
+                // The invocation that claimed the window has its own files to show
+                self.close_command_palette();
+                if self.ui_state == UIState::Hidden {
+                    self.show_window();
+                }
+                self.skia_renderer.window().focus_window();
+            }
+            WindowCommand::RequestAttention(attention_type) => {
+                self.skia_renderer
+                    .window()
+                    .request_user_attention(Some(attention_type));
+            }
             WindowCommand::Minimize => {
                 self.minimize_window();
                 self.is_minimized = true;
             }
+            WindowCommand::SetPosition(position) => {
+                self.skia_renderer.window().set_outer_position(position);
+            }
+            WindowCommand::SetMouseShape(shape) => self
+                .mouse_manager
+                .set_mouse_shape(shape, self.skia_renderer.window()),
+            WindowCommand::ResizeWindow {
+                delta_cols,
+                delta_lines,
+                anchor,
+            } => self.handle_resize_window(delta_cols, delta_lines, anchor),
             WindowCommand::ThemeChanged(new_theme) => {
                 self.handle_theme_changed(new_theme);
             }
+            // Handled by the menu bar
+            WindowCommand::SetMenuItems(..) => {}
+            WindowCommand::SetContextMenu(items) => self.context_menu = items,
+            WindowCommand::SetZenMode(enabled) => self.set_zen_mode(enabled),
+            WindowCommand::SetPadding(padding_override) => self.padding_override = padding_override,
+            WindowCommand::Bell => self.audio_bell.ring(
+                &SETTINGS.get::<WindowSettings>(),
+                self.skia_renderer.window().has_focus(),
+            ),
+            WindowCommand::VisualBell => self.ring_visual_bell(),
+            WindowCommand::SetProgress(progress) => {
+                if !platform_integration::set_progress(self.skia_renderer.window(), progress) {
+                    self.renderer.set_progress(progress);
+                }
+            }
+            WindowCommand::SetBadge(badge) => platform_integration::set_badge(badge.as_deref()),
+            WindowCommand::SetWindowIcon(image) => self.set_window_icon(image),
+            WindowCommand::SetIconOverlay(overlay) => platform_integration::set_icon_overlay(
+                self.skia_renderer.window(),
+                overlay.as_ref(),
+            ),
+            WindowCommand::SetWindowLigatures(ligatures) => {
+                self.renderer.set_window_ligatures(ligatures)
+            }
+            WindowCommand::SetWindowIndents(indents) => self.renderer.set_window_indents(indents),
+            WindowCommand::SetWindowTrailingWhitespace(trailing_whitespace) => self
+                .renderer
+                .set_window_trailing_whitespace(trailing_whitespace),
+            WindowCommand::SetWindowZoom(window, zoom) => {
+                self.renderer.set_window_zoom(window, zoom)
+            }
+            WindowCommand::ScrollWindowColumns {
+                window,
+                cols,
+                text_left,
+            } => self.renderer.scroll_window_columns(window, cols, text_left),
+            WindowCommand::SetWindowSkipcol { window, skipcol } => {
+                self.renderer.set_window_skipcol(window, skipcol)
+            }
+            WindowCommand::ColorschemeChanged => {
+                let default_colors = &self.renderer.grid_renderer.default_style.colors;
+                self.terminal_colors.colorscheme_changed(default_colors);
+            }
+            WindowCommand::SetWindowHighlights(highlights) => {
+                self.renderer.set_window_highlights(highlights)
+            }
+            WindowCommand::OpenCommandPalette => self.open_command_palette(),
+            WindowCommand::OpenFilesInNewWindows(files) => {
+                for file in files {
+                    shortcuts::open_in_new_window(&[file]);
+                }
+            }
+            WindowCommand::ShowLogs { level, module } => {
+                self.renderer.command_palette.close();
+                self.renderer.log_viewer.open(&level, &module);
+                self.update_captured_keys();
+            }
+            WindowCommand::SetCommandPaletteItems(items) => {
+                self.renderer.command_palette.set_items(items)
+            }
+            // A daemon is claimed by an invocation that has its own files
+            WindowCommand::OpenProjectLauncher(..) if self.waiting_daemon => {}
+            WindowCommand::OpenProjectLauncher(items) => {
+                self.renderer.command_palette.open_with_items(items);
+                self.update_captured_keys();
+            }
+            WindowCommand::ConfirmQuit(modified_buffers) => self.open_quit_dialog(modified_buffers),
+            WindowCommand::NeovimCrashed { status, output } => {
+                self.renderer.quit_dialog.close();
+                self.renderer.command_palette.close();
+                self.renderer.crash_dialog.open(status, output);
+                self.update_captured_keys();
+            }
+            WindowCommand::NeovimRestarted => {
+                self.renderer.reset_windows();
+                self.keyboard_manager.set_neovim_busy(false);
+                // The new instance starts with the default size
+                self.saved_grid_size = None;
+                self.update_grid_size_from_window();
+            }
+            WindowCommand::SetNeovimBusy(busy) => {
+                let can_kill = SETTINGS.get::<CmdLineSettings>().server.is_none();
+                self.renderer.set_neovim_busy(busy, can_kill);
+                self.keyboard_manager.set_neovim_busy(busy);
+            }
+            WindowCommand::Screenshot(path) => self.screenshot_paths.push(path),
+            WindowCommand::RecordFrames {
+                directory,
+                duration,
+            } => match FrameRecorder::new(directory, duration) {
+                Ok(recorder) => self.frame_recorder = Some(recorder),
+                Err(error) => {
+                    error_msg!("Could not start recording: {error:?}");
+                }
+            },
             #[cfg(windows)]
             WindowCommand::RegisterRightClick => register_right_click(),
             #[cfg(windows)]
@@ -223,10 +641,12 @@ impl WinitWindowWrapper {
                 log::info!("lines changed");
                 self.requested_lines = lines;
             }
-            WindowSettingsChanged::Fullscreen(fullscreen) => {
-                if self.fullscreen != fullscreen {
-                    self.toggle_fullscreen();
-                }
+            WindowSettingsChanged::Fullscreen(fullscreen) => self.set_fullscreen(fullscreen),
+            WindowSettingsChanged::TerminalColors(..) => self.update_terminal_colors(),
+            // Switch over right away when the window is already fullscreen
+            WindowSettingsChanged::FullscreenMode(..)
+            | WindowSettingsChanged::FullscreenMonitor(..) => {
+                self.switch_fullscreen();
             }
             WindowSettingsChanged::InputIme(ime_enabled) => {
                 if self.ime_enabled != ime_enabled {
@@ -235,9 +655,16 @@ impl WinitWindowWrapper {
             }
             WindowSettingsChanged::WindowBlurred(blur) => {
                 let WindowSettings { transparency, .. } = SETTINGS.get::<WindowSettings>();
-                let transparent = transparency < 1.0;
+                let transparent = transparency < 1.0 && !self.renderer.opaque_surface;
                 self.skia_renderer.window().set_blur(blur && transparent);
             }
+            WindowSettingsChanged::WindowsBackdrop(backdrop) => {
+                platform_integration::set_backdrop(self.skia_renderer.window(), backdrop);
+            }
+            WindowSettingsChanged::MaxFrameLatency(latency) => {
+                self.skia_renderer
+                    .set_max_frame_latency(max_frame_latency(latency));
+            }
             _ => {}
         };
         #[cfg(target_os = "macos")]
@@ -245,9 +672,130 @@ impl WinitWindowWrapper {
             .handle_settings_changed(self.skia_renderer.window(), changed_setting);
     }
 
+    fn handle_resize_window(&mut self, delta_cols: i64, delta_lines: i64, anchor: ResizeAnchor) {
+        if self.fullscreen.is_some() || self.skia_renderer.window().is_maximized() {
+            log::info!("Ignoring window resize request while fullscreen or maximized");
+            return;
+        }
+
+        // Consecutive requests accumulate, so that holding down a mapping keeps growing the window
+        let (current, previous_target) = match &self.resize_animation {
+            Some(animation) => (animation.current, animation.target),
+            None => {
+                let grid_size = self.saved_grid_size.unwrap_or_else(|| self.get_grid_size());
+                (grid_size, grid_size)
+            }
+        };
+        let apply_delta = |size: u64, delta: i64, min: u64| {
+            (size as i64).saturating_add(delta).max(min as i64) as u64
+        };
+        let target = Dimensions {
+            width: apply_delta(previous_target.width, delta_cols, MIN_GRID_SIZE.width),
+            height: apply_delta(previous_target.height, delta_lines, MIN_GRID_SIZE.height),
+        };
+
+        log::info!("Resize window requested from {current:?} to {target:?} anchored {anchor:?}");
+        self.resize_animation = Some(GridResizeAnimation {
+            start: current,
+            target,
+            current,
+            anchor,
+            t: 0.0,
+        });
+    }
+
+    /// Steps the animated resize requested by `handle_resize_window` and returns true while it's
+    /// still in progress.
+    fn animate_window_resize(&mut self, dt: f32) -> bool {
+        let Some(animation) = self.resize_animation.as_mut() else {
+            return false;
+        };
+
+        let animation_length = SETTINGS.get::<WindowSettings>().resize_animation_length;
+        let animations_enabled =
+            SETTINGS.get::<RendererSettings>().animations_enabled && !power_saving();
+        animation.t = if animation_length > 0.0 && animations_enabled {
+            (animation.t + dt / animation_length).min(1.0)
+        } else {
+            1.0
+        };
+
+        let step = |start: u64, target: u64| {
+            ease(ease_out_expo, start as f32, target as f32, animation.t).round() as u64
+        };
+        let new_size = Dimensions {
+            width: step(animation.start.width, animation.target.width),
+            height: step(animation.start.height, animation.target.height),
+        };
+
+        if new_size != animation.current {
+            let font_dimensions = self.renderer.grid_renderer.font_dimensions;
+            let delta_x = (new_size.width as i64 - animation.current.width as i64)
+                * font_dimensions.width as i64;
+            let delta_y = (new_size.height as i64 - animation.current.height as i64)
+                * font_dimensions.height as i64;
+
+            // Move the window so that the anchored edges stay in place. This is not supported on
+            // all platforms, notably Wayland, in which case the window just grows from the top left.
+            let window = self.skia_renderer.window();
+            if animation.anchor != ResizeAnchor::NorthWest {
+                if let Ok(position) = window.outer_position() {
+                    let mut position = position;
+                    if animation.anchor.is_east() {
+                        position.x -= delta_x as i32;
+                    }
+                    if animation.anchor.is_south() {
+                        position.y -= delta_y as i32;
+                    }
+                    window.set_outer_position(position);
+                }
+            }
+
+            animation.current = new_size;
+            self.requested_columns = Some(new_size.width);
+            self.requested_lines = Some(new_size.height);
+        }
+
+        if animation.t >= 1.0 {
+            self.resize_animation = None;
+            false
+        } else {
+            true
+        }
+    }
+
+    fn update_window_geometry(&self) {
+        let window = self.skia_renderer.window();
+        *WINDOW_GEOMETRY.write().unwrap() = WindowGeometry {
+            position: window.outer_position().ok(),
+            outer_size: window.outer_size(),
+            inner_size: window.inner_size(),
+            scale_factor: window.scale_factor(),
+        };
+    }
+
     pub fn handle_title_changed(&mut self, new_title: String) {
         self.title = new_title;
         self.skia_renderer.window().set_title(&self.title);
+        self.accessibility.set_title(&self.title);
+    }
+
+    /// Follows the theme of the system once it has settled.
+    fn follow_system_theme(&mut self, should_render: &mut ShouldRender) {
+        let Some((theme, deadline)) = self.pending_system_theme else {
+            return;
+        };
+        if Instant::now() < deadline {
+            should_render.update(ShouldRender::Deadline(deadline));
+            return;
+        }
+        self.pending_system_theme = None;
+        if self.system_theme == Some(theme) {
+            return;
+        }
+        self.system_theme = Some(theme);
+        let follow = SETTINGS.get::<WindowSettings>().theme == "auto";
+        apply_theme(theme, follow, true);
     }
 
     pub fn handle_theme_changed(&mut self, new_theme: Option<Theme>) {
@@ -261,7 +809,11 @@ impl WinitWindowWrapper {
     }
 
     pub fn handle_quit(&mut self) {
-        if SETTINGS.get::<CmdLineSettings>().server.is_none() {
+        let settings = SETTINGS.get::<CmdLineSettings>();
+        // There's no Neovim to ask while the crash dialog is shown
+        if self.renderer.crash_dialog.is_open() {
+            RUNNING_TRACKER.quit("window closed");
+        } else if settings.server.is_none() && settings.replay.is_none() {
             send_ui(ParallelCommand::Quit);
         } else {
             RUNNING_TRACKER.quit("window closed");
@@ -274,6 +826,8 @@ impl WinitWindowWrapper {
 
     pub fn handle_focus_gained(&mut self) {
         send_ui(ParallelCommand::FocusGained);
+        // Not all window managers clear the X11 urgency hint by themselves
+        self.skia_renderer.window().request_user_attention(None);
         // Got focus back after being minimized previously
         if self.is_minimized {
             // Sending <NOP> after suspend triggers the `VimResume` AutoCmd
@@ -283,18 +837,360 @@ impl WinitWindowWrapper {
         }
     }
 
+    fn handle_shortcut(&mut self, action: ShortcutAction) {
+        match action {
+            ShortcutAction::ToggleFullscreen => {
+                // Changed through the Neovim variable, so that it stays in sync
+                send_ui(ParallelCommand::SetVariable {
+                    name: "neovide_fullscreen".to_string(),
+                    value: Value::from(self.fullscreen.is_none()),
+                });
+            }
+            ShortcutAction::ZoomIn => shortcuts::zoom_in(),
+            ShortcutAction::ZoomOut => shortcuts::zoom_out(),
+            ShortcutAction::ZoomReset => shortcuts::zoom(None),
+            ShortcutAction::NewWindow => shortcuts::open_new_window(),
+            ShortcutAction::Paste => shortcuts::paste_clipboard(),
+            ShortcutAction::Quit => self.handle_quit(),
+            ShortcutAction::Interrupt => send_ui(ParallelCommand::Interrupt),
+            ShortcutAction::KillNeovim => {
+                if !kill_embedded_neovim() {
+                    error_msg!("Only the embedded Neovim can be killed, not a server");
+                }
+            }
+            ShortcutAction::CommandPalette => {
+                if self.renderer.command_palette.is_open() {
+                    self.close_command_palette();
+                } else {
+                    self.open_command_palette();
+                }
+            }
+        }
+    }
+
+    /// Opens the palette with the items of the last time, and asks Neovim for the current ones.
+    fn open_command_palette(&mut self) {
+        if self.renderer.command_palette.is_open() {
+            return;
+        }
+        self.renderer.command_palette.open();
+        self.update_captured_keys();
+        send_ui(ParallelCommand::ExecLua {
+            code: "require('neovide')._command_palette_items()".to_string(),
+            args: vec![],
+        });
+    }
+
+    fn close_command_palette(&mut self) {
+        self.renderer.command_palette.close();
+        self.update_captured_keys();
+    }
+
+    /// The keys are kept from Neovim while one of the overlays is open.
+    fn update_captured_keys(&mut self) {
+        let captured = self.renderer.command_palette.is_open()
+            || self.renderer.log_viewer.is_open()
+            || self.renderer.quit_dialog.is_open()
+            || self.renderer.link_dialog.is_open()
+            || self.renderer.crash_dialog.is_open();
+        self.keyboard_manager.set_captured(captured);
+    }
+
+    fn open_quit_dialog(&mut self, modified_buffers: Vec<String>) {
+        self.renderer.quit_dialog.open(modified_buffers);
+        self.update_captured_keys();
+    }
+
+    fn handle_quit_choice(&mut self, choice: QuitChoice) {
+        self.renderer.quit_dialog.close();
+        self.update_captured_keys();
+        if choice != QuitChoice::Cancel {
+            send_ui(ParallelCommand::ExecLua {
+                code: "require('neovide')._quit_with(...)".to_string(),
+                args: vec![Value::from(choice.name())],
+            });
+        }
+    }
+
+    fn handle_captured_keys(&mut self) {
+        for key in self.keyboard_manager.take_captured_keys() {
+            if self.renderer.crash_dialog.is_open() {
+                match self.renderer.crash_dialog.handle_key(&key) {
+                    Some(CrashChoice::Restart) => {
+                        self.renderer.crash_dialog.close();
+                        self.update_captured_keys();
+                        restart_neovim();
+                    }
+                    Some(CrashChoice::Quit) => RUNNING_TRACKER.quit("neovim crashed"),
+                    None => {}
+                }
+                continue;
+            }
+            if self.renderer.link_dialog.is_open() {
+                if let Some(choice) = self.renderer.link_dialog.handle_key(&key) {
+                    let url = self.renderer.link_dialog.close();
+                    self.update_captured_keys();
+                    if let (LinkChoice::Open, Some(url)) = (choice, url) {
+                        open_url(&url);
+                    }
+                }
+                continue;
+            }
+            // The dialog is above the palette
+            if self.renderer.quit_dialog.is_open() {
+                if let Some(choice) = self.renderer.quit_dialog.handle_key(&key) {
+                    self.handle_quit_choice(choice);
+                }
+                continue;
+            }
+            if self.renderer.log_viewer.is_open() {
+                if !self.renderer.log_viewer.handle_key(&key) {
+                    self.renderer.log_viewer.close();
+                    self.update_captured_keys();
+                }
+                continue;
+            }
+            let action = self.renderer.command_palette.handle_key(&key);
+            self.handle_palette_action(action);
+        }
+    }
+
+    fn handle_palette_action(&mut self, action: PaletteAction) {
+        match action {
+            PaletteAction::None => {}
+            PaletteAction::Close => self.close_command_palette(),
+            PaletteAction::Run(item) => {
+                self.close_command_palette();
+                if let Some(command) = palette_window_command(&item) {
+                    self.handle_window_command(command);
+                    return;
+                }
+                send_ui(ParallelCommand::ExecLua {
+                    code: "require('neovide')._command_palette_run(...)".to_string(),
+                    args: vec![Value::from(item.kind.name()), Value::from(item.value)],
+                });
+            }
+        }
+    }
+
+    /// Selects and runs the items of the palette with the mouse. Returns true when the event
+    /// should not be sent to Neovim, which gets no mouse input while the palette is open.
+    fn handle_palette_mouse(&mut self, event: &Event<UserEvent>, changed: &mut bool) -> bool {
+        if !self.renderer.command_palette.is_open() {
+            return false;
+        }
+        let Event::WindowEvent { event, .. } = event else {
+            return false;
+        };
+        let size = self.skia_renderer.window().inner_size();
+        let size = (size.width as f32, size.height as f32);
+        let font_dimensions = self.renderer.grid_renderer.font_dimensions;
+        match event {
+            WindowEvent::CursorMoved { position, .. } => {
+                self.palette_mouse_position = (position.x as f32, position.y as f32);
+                let position = self.palette_mouse_position;
+                *changed = self
+                    .renderer
+                    .command_palette
+                    .hover(position, size, font_dimensions);
+                true
+            }
+            WindowEvent::MouseInput {
+                state: ElementState::Pressed,
+                button: MouseButton::Left,
+                ..
+            } => {
+                let position = self.palette_mouse_position;
+                let action = self
+                    .renderer
+                    .command_palette
+                    .click(position, size, font_dimensions);
+                *changed = action != PaletteAction::None;
+                self.handle_palette_action(action);
+                true
+            }
+            WindowEvent::MouseInput { .. } | WindowEvent::MouseWheel { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Opens the files dropped on the window. Several files dropped at once arrive as separate
+    /// events, before the event loop waits again.
+    fn open_dropped_files(&mut self) {
+        match self.dropped_files.len() {
+            0 => {}
+            // A single dropped file is always opened in a new tab
+            1 => send_ui(ParallelCommand::FileDrop {
+                path: self.dropped_files.remove(0),
+                target: FileOpenTarget::Tabs,
+            }),
+            _ => {
+                let files = mem::take(&mut self.dropped_files);
+                self.open_files(files);
+            }
+        }
+    }
+
+    /// Opens files from outside of Neovim, as configured by `g:neovide_open_files_in`, or
+    /// `g:neovide_open_multiple_files_in` when several arrive at once.
+    fn open_files(&mut self, paths: Vec<String>) {
+        let settings = SETTINGS.get::<WindowSettings>();
+        let target = if paths.len() > 1 {
+            settings.open_multiple_files_in
+        } else {
+            settings.open_files_in
+        };
+        for path in paths {
+            if target == FileOpenTarget::Windows {
+                shortcuts::open_in_new_window(&[path]);
+            } else {
+                send_ui(ParallelCommand::FileDrop { path, target });
+            }
+        }
+    }
+
+    fn set_zen_mode(&mut self, enabled: bool) {
+        self.zen_mode.set_enabled(enabled);
+        let dim_monitors = enabled && SETTINGS.get::<WindowSettings>().zen_mode_dim_monitors;
+        #[cfg(windows)]
+        {
+            self.monitor_covers = if dim_monitors {
+                cover_other_monitors(self.skia_renderer.window())
+            } else {
+                Vec::new()
+            };
+        }
+        #[cfg(target_os = "macos")]
+        self.macos_feature.set_other_screens_dimmed(dim_monitors);
+        #[cfg(not(any(windows, target_os = "macos")))]
+        if dim_monitors {
+            log::warn!("Dimming the other monitors is not supported on this platform");
+        }
+    }
+
+    fn ring_visual_bell(&mut self) {
+        self.renderer.ring_visual_bell();
+        let window = self.skia_renderer.window();
+        if SETTINGS.get::<WindowSettings>().visual_bell_flash_taskbar && !window.has_focus() {
+            window.request_user_attention(Some(UserAttentionType::Informational));
+        }
+    }
+
+    fn set_window_icon(&mut self, image: Option<RgbaImage>) {
+        let icon = image.and_then(|image| {
+            let (width, height) = image.dimensions();
+            Icon::from_rgba(image.into_raw(), width, height).ok()
+        });
+        self.skia_renderer
+            .window()
+            .set_window_icon(Some(icon.unwrap_or_else(load_icon)));
+    }
+
+    /// Steps the zen mode animation and returns true while it's still in progress.
+    fn animate_zen_mode(&mut self, dt: f32) -> bool {
+        // Without animations, zen mode is entered and left in one step
+        let dt = if SETTINGS.get::<RendererSettings>().animations_enabled && !power_saving() {
+            dt
+        } else {
+            f32::INFINITY
+        };
+        if !self.zen_mode.animate(dt) {
+            return false;
+        }
+        // Reloading the fonts on every frame would be too slow, so they are only reloaded at the end
+        let zoom = self
+            .zen_mode
+            .scale_factor(SETTINGS.get::<WindowSettings>().zen_mode_scale);
+        if self.zen_mode.is_settled() {
+            self.font_changed_last_frame |= self.renderer.set_zoom(zoom);
+        } else {
+            self.renderer.preview_zoom(zoom);
+        }
+        true
+    }
+
+    /// Shows the user defined context menu on right click. Returns true when the event should not
+    /// be sent to Neovim.
+    fn handle_context_menu(&mut self, event: &Event<UserEvent>) -> bool {
+        let Event::WindowEvent {
+            event:
+                WindowEvent::MouseInput {
+                    state,
+                    button: MouseButton::Right,
+                    ..
+                },
+            ..
+        } = event
+        else {
+            return false;
+        };
+        match state {
+            ElementState::Pressed if !self.context_menu.is_empty() => {
+                self.context_menu_pressed = self.show_context_menu();
+                self.context_menu_pressed
+            }
+            ElementState::Pressed => false,
+            ElementState::Released => std::mem::take(&mut self.context_menu_pressed),
+        }
+    }
+
+    /// Returns false when context menus are not supported on the platform.
+    #[cfg(windows)]
+    fn show_context_menu(&self) -> bool {
+        show_context_menu(self.skia_renderer.window(), &self.context_menu);
+        true
+    }
+
+    #[cfg(target_os = "macos")]
+    fn show_context_menu(&self) -> bool {
+        self.macos_feature.show_context_menu(&self.context_menu);
+        true
+    }
+
+    #[cfg(not(any(windows, target_os = "macos")))]
+    fn show_context_menu(&self) -> bool {
+        false
+    }
+
     /// Handles an event from winit and returns an boolean indicating if
     /// the window should be rendered.
     pub fn handle_event(&mut self, event: Event<UserEvent>) -> bool {
         tracy_zone!("handle_event", 0);
-        self.keyboard_manager.handle_event(&event);
-        self.mouse_manager.handle_event(
-            &event,
-            &self.keyboard_manager,
-            &self.renderer,
-            self.skia_renderer.window(),
-        );
-        let renderer_asks_to_be_rendered = self.renderer.handle_event(&event);
+        if let Event::WindowEvent { event, .. } = &event {
+            self.accessibility
+                .process_event(self.skia_renderer.window(), event);
+        }
+        let echoed = self
+            .keyboard_manager
+            .handle_event(&event)
+            .is_some_and(|text| self.renderer.handle_typed_text(&text));
+        let (preedit, selection) = self.keyboard_manager.ime_preedit();
+        let preedit_changed = self.renderer.set_ime_preedit(preedit, selection);
+        if let Some(action) = self.keyboard_manager.take_shortcut_action() {
+            self.handle_shortcut(action);
+        }
+        self.handle_captured_keys();
+        let mut palette_changed = false;
+        if !self.handle_palette_mouse(&event, &mut palette_changed)
+            && !self.handle_context_menu(&event)
+        {
+            self.mouse_manager.handle_event(
+                &event,
+                &self.keyboard_manager,
+                &mut self.renderer,
+                self.skia_renderer.window(),
+            );
+        }
+        if let Some(url) = self.mouse_manager.take_link_to_confirm() {
+            self.renderer.link_dialog.open(url);
+            self.update_captured_keys();
+        }
+        let hyperlink_changed = self.mouse_manager.take_hyperlink_changed();
+        let renderer_asks_to_be_rendered = self.renderer.handle_event(&event)
+            || echoed
+            || preedit_changed
+            || palette_changed
+            || hyperlink_changed;
         let mut should_render = true;
         match event {
             Event::Resumed => {
@@ -314,12 +1210,14 @@ impl WinitWindowWrapper {
             } => {
                 tracy_zone!("ScaleFactorChanged");
                 self.handle_scale_factor_update(scale_factor);
+                self.update_window_geometry();
             }
             Event::WindowEvent {
                 event: WindowEvent::Resized { .. },
                 ..
             } => {
                 self.skia_renderer.resize();
+                self.update_window_geometry();
                 #[cfg(target_os = "macos")]
                 self.macos_feature.handle_size_changed();
             }
@@ -329,8 +1227,9 @@ impl WinitWindowWrapper {
             } => {
                 tracy_zone!("DroppedFile");
                 let file_path = path.into_os_string().into_string().unwrap();
-                send_ui(ParallelCommand::FileDrop(file_path));
+                self.dropped_files.push(file_path);
             }
+
             Event::WindowEvent {
                 event: WindowEvent::Focused(focus),
                 ..
@@ -347,14 +1246,7 @@ impl WinitWindowWrapper {
                 ..
             } => {
                 tracy_zone!("ThemeChanged");
-                let settings = SETTINGS.get::<WindowSettings>();
-                if settings.theme.as_str() == "auto" {
-                    let background = match theme {
-                        Theme::Light => "light",
-                        Theme::Dark => "dark",
-                    };
-                    set_background(background);
-                }
+                self.pending_system_theme = Some((theme, Instant::now() + THEME_DEBOUNCE));
             }
             Event::WindowEvent {
                 event: WindowEvent::Moved(_),
@@ -362,6 +1254,7 @@ impl WinitWindowWrapper {
             } => {
                 tracy_zone!("Moved");
                 self.vsync.update(self.skia_renderer.window());
+                self.update_window_geometry();
             }
             Event::UserEvent(UserEvent::DrawCommandBatch(batch)) => {
                 self.handle_draw_commands(batch);
@@ -375,6 +1268,10 @@ impl WinitWindowWrapper {
             Event::UserEvent(UserEvent::ConfigsChanged(config)) => {
                 self.handle_config_changed(*config);
             }
+            Event::UserEvent(UserEvent::OpenFiles(paths)) => {
+                tracy_zone!("OpenFiles");
+                self.open_files(paths);
+            }
             _ => {
                 match event {
                     Event::WindowEvent { .. } => {
@@ -396,100 +1293,200 @@ impl WinitWindowWrapper {
                 should_render = renderer_asks_to_be_rendered;
             }
         }
-        self.ui_state != UIState::Initing && should_render
+        !matches!(self.ui_state, UIState::Initing | UIState::Hidden) && should_render
     }
 
     pub fn draw_frame(&mut self, dt: f32) {
         tracy_zone!("draw_frame");
-        self.renderer.draw_frame(self.skia_renderer.canvas(), dt);
+        if self.ui_state == UIState::Splash {
+            let color = self
+                .splash_color
+                .with_a(window_alpha(self.renderer.opaque_surface));
+            self.renderer
+                .draw_splash(self.skia_renderer.canvas(), color);
+        } else {
+            self.renderer.draw_frame(self.skia_renderer.canvas(), dt);
+        }
+        self.capture_frame();
         self.skia_renderer.flush();
         {
             tracy_gpu_zone!("wait for vsync");
             self.vsync.wait_for_vsync();
         }
         self.skia_renderer.swap_buffers();
+        FRAME_TIMINGS
+            .lock()
+            .unwrap()
+            .frame_presented(Instant::now());
         tracy_frame();
         tracy_gpu_collect();
     }
 
+    /// Saves the frame for the pending screenshots and the recording, if any.
+    fn capture_frame(&mut self) {
+        if self.screenshot_paths.is_empty() && self.frame_recorder.is_none() {
+            return;
+        }
+        tracy_zone!("capture_frame");
+        let Some(frame) = CapturedFrame::read(self.skia_renderer.canvas()) else {
+            error_msg!("Could not read back the rendered frame");
+            self.screenshot_paths.clear();
+            self.frame_recorder = None;
+            return;
+        };
+        for path in self.screenshot_paths.drain(..) {
+            frame.clone().save_png_in_background(path);
+        }
+        if let Some(recorder) = &mut self.frame_recorder {
+            if !recorder.add_frame(frame) {
+                self.frame_recorder = None;
+            }
+        }
+    }
+
     pub fn animate_frame(&mut self, dt: f32) -> bool {
         tracy_zone!("animate_frame", 0);
 
+        // Never wait for the frame producer, animate again once the latest frame is ready instead
+        let frame_pending = !self.renderer.receive_frame(false);
+        let resizing = self.animate_window_resize(dt);
+        let zen_mode_animating = self.animate_zen_mode(dt);
+        let padding_animating = self.animate_padding(dt);
         let res = self.renderer.animate_frame(
             &self.get_grid_size_from_window(0, 0),
             &self.padding_as_grid(),
             dt,
-        );
+        ) || resizing
+            || zen_mode_animating
+            || padding_animating
+            // Keep rendering until the frames to capture have been drawn
+            || !self.screenshot_paths.is_empty()
+            || self.frame_recorder.is_some();
+        let res = frame_pending || res;
         tracy_plot!("animate_frame", res as u8 as f64);
-        self.renderer.prepare_lines();
         #[allow(clippy::let_and_return)]
         res
     }
 
     fn handle_draw_commands(&mut self, batch: Vec<DrawCommand>) {
         tracy_zone!("handle_draw_commands");
+        FRAME_TIMINGS
+            .lock()
+            .unwrap()
+            .draw_commands_received(Instant::now());
+        let font_changing = batch
+            .iter()
+            .any(|command| matches!(command, DrawCommand::FontChanged(..)));
+        if font_changing && self.ui_state == UIState::Showing {
+            self.renderer
+                .begin_font_transition(self.skia_renderer.canvas());
+        }
+        let default_style_changed = batch
+            .iter()
+            .any(|command| matches!(command, DrawCommand::DefaultStyleChanged(..)));
+        self.accessibility.handle_draw_commands(&batch);
         let handle_draw_commands_result = self.renderer.handle_draw_commands(batch);
 
         self.font_changed_last_frame |= handle_draw_commands_result.font_changed;
+        if self
+            .awaited_grid_size
+            .is_some_and(|(grid_size, _)| grid_size == self.renderer.get_grid_size())
+        {
+            self.end_grid_resize_wait();
+        }
+        if default_style_changed {
+            self.update_terminal_colors();
+        }
 
-        if self.ui_state == UIState::Initing && handle_draw_commands_result.should_show {
-            log::info!("Showing the Window");
-            self.ui_state = UIState::FirstFrame;
+        if matches!(self.ui_state, UIState::Initing | UIState::Splash)
+            && handle_draw_commands_result.should_show
+        {
+            if self.waiting_daemon {
+                log::info!("Ready, waiting for a new invocation");
+                self.ui_state = UIState::Hidden;
+            } else {
+                self.show_window();
+            }
+        };
+    }
 
-            match self.initial_window_size {
-                WindowSize::Maximized => {
-                    self.skia_renderer.window().set_visible(true);
-                    self.skia_renderer.window().set_maximized(true);
-                }
-                WindowSize::Grid(Dimensions { width, height }) => {
-                    self.requested_columns = Some(width);
-                    self.requested_lines = Some(height);
-                    log::info!("Showing window {width}, {height}");
-                    // The visibility is changed after the size is adjusted
-                }
-                WindowSize::NeovimGrid => {
-                    let grid_size = self.renderer.get_grid_size();
-                    self.requested_columns = Some(grid_size.width);
-                    self.requested_lines = Some(grid_size.height);
-                }
-                WindowSize::Size(..) => {
-                    self.requested_columns = None;
-                    self.requested_lines = None;
-                    self.skia_renderer.window().set_visible(true);
-                }
+    fn show_window(&mut self) {
+        log::info!("Showing the Window");
+        self.ui_state = UIState::FirstFrame;
+        self.renderer.begin_startup_fade(self.splash_color);
+
+        match self.initial_window_size {
+            WindowSize::Maximized => {
+                self.skia_renderer.window().set_visible(true);
+                self.skia_renderer.window().set_maximized(true);
+            }
+            WindowSize::Grid(Dimensions { width, height }) => {
+                self.requested_columns = Some(width);
+                self.requested_lines = Some(height);
+                log::info!("Showing window {width}, {height}");
+                // The visibility is changed after the size is adjusted
+            }
+            WindowSize::NeovimGrid => {
+                let grid_size = self.renderer.get_grid_size();
+                self.requested_columns = Some(grid_size.width);
+                self.requested_lines = Some(grid_size.height);
+            }
+            WindowSize::Size(..) => {
+                self.requested_columns = None;
+                self.requested_lines = None;
+                self.skia_renderer.window().set_visible(true);
             }
+        }
 
-            // Ensure that the window has the correct IME state
-            self.set_ime(self.ime_enabled);
-        };
+        if let Some(monitor) = self.restore_fullscreen.take() {
+            self.restore_fullscreen(monitor);
+        }
+
+        // Ensure that the window has the correct IME state
+        self.set_ime(self.ime_enabled);
     }
 
     fn handle_config_changed(&mut self, config: HotReloadConfigs) {
         tracy_zone!("handle_config_changed");
+        if let HotReloadConfigs::VSync(enabled) = config {
+            self.set_vsync(enabled);
+            return;
+        }
         self.renderer.handle_config_changed(config);
         self.font_changed_last_frame = true;
     }
 
+    /// Switches vsync on or off, when it's changed in the config file or by a profile.
+    fn set_vsync(&mut self, enabled: bool) {
+        self.skia_renderer.set_vsync(enabled);
+        self.vsync = VSync::new(enabled, self.skia_renderer.as_ref(), self.proxy.clone());
+        RENDERER_STATUS.write().unwrap().vsync = self.vsync.name().to_string();
+    }
+
     pub fn prepare_frame(&mut self) -> ShouldRender {
         tracy_zone!("prepare_frame", 0);
+        self.open_dropped_files();
         let mut should_render = ShouldRender::Wait;
+        self.follow_system_theme(&mut should_render);
 
         let window_settings = SETTINGS.get::<WindowSettings>();
-        #[cfg(not(target_os = "macos"))]
-        let window_padding_top = window_settings.padding_top;
-        #[cfg(target_os = "macos")]
-        let window_padding_top =
-            window_settings.padding_top + self.macos_feature.extra_titlebar_height_in_pixels();
-        let window_padding = WindowPadding {
-            top: window_padding_top,
-            left: window_settings.padding_left,
-            right: window_settings.padding_right,
-            bottom: window_settings.padding_bottom,
-        };
+        let padding_target = self.padding_target(&window_settings);
+        if self.ui_state == UIState::Showing {
+            self.padding_animation.set_target(padding_target);
+        } else {
+            // The padding isn't animated before the window is shown
+            self.padding_animation = PaddingAnimation::new(padding_target);
+        }
+        // The grid is resized once to the target padding, and only the drawn offset is animated
+        let zen_mode_padding = self.zen_mode.padding(window_settings.zen_mode_padding);
+        let window_padding = padding_target.grow(zen_mode_padding as i32);
         let padding_changed = window_padding != self.window_padding;
 
         // Don't render until the UI is fully entered and the window is shown
-        if self.ui_state == UIState::Initing {
+        if matches!(
+            self.ui_state,
+            UIState::Initing | UIState::Splash | UIState::Hidden
+        ) {
             return ShouldRender::Wait;
         } else if self.ui_state == UIState::FirstFrame {
             should_render = ShouldRender::Immediately;
@@ -509,14 +1506,37 @@ impl WinitWindowWrapper {
             // NOTE: Only actually resize the grid when the window is not minimized
             // Some platforms return a zero size when that is the case, so we should not try to resize to that.
             let new_size = self.skia_renderer.window().inner_size();
-            if self.saved_inner_size != new_size || self.font_changed_last_frame || padding_changed
-            {
+            let size_changed = self.saved_inner_size != new_size;
+            let reflow = if size_changed && !self.font_changed_last_frame && !padding_changed {
+                self.preview_resize(new_size, &mut should_render)
+            } else {
+                size_changed || self.font_changed_last_frame || padding_changed
+            };
+            if reflow {
+                self.live_resize = None;
+                // Until Neovim sends the grid of the new size, the last frame is stretched over
+                // the window instead of drawing the old grid on it
+                if size_changed {
+                    self.renderer
+                        .begin_resize_preview(self.skia_renderer.canvas(), self.saved_inner_size);
+                }
                 self.window_padding = window_padding;
                 self.font_changed_last_frame = false;
                 self.saved_inner_size = new_size;
 
                 self.update_grid_size_from_window();
+                if self.awaited_grid_size.is_none() {
+                    self.renderer.end_resize_preview();
+                }
+                should_render = ShouldRender::Immediately;
+            }
+        }
+        if let Some((_, deadline)) = self.awaited_grid_size {
+            if Instant::now() >= deadline {
+                self.end_grid_resize_wait();
                 should_render = ShouldRender::Immediately;
+            } else {
+                should_render.update(ShouldRender::Deadline(deadline));
             }
         }
 
@@ -527,6 +1547,39 @@ impl WinitWindowWrapper {
         should_render
     }
 
+    /// The padding of the settings, with the sides set by `neovide.set_padding` replaced.
+    fn padding_target(&self, window_settings: &WindowSettings) -> WindowPadding {
+        #[cfg(not(target_os = "macos"))]
+        let padding_top = window_settings.padding_top;
+        #[cfg(target_os = "macos")]
+        let padding_top = window_settings.padding_top
+            + self.macos_feature.extra_titlebar_height_in_pixels() as i32;
+        let padding = WindowPadding {
+            top: padding_top,
+            left: window_settings.padding_left,
+            right: window_settings.padding_right,
+            bottom: window_settings.padding_bottom,
+        };
+        self.padding_override
+            .map_or(padding, |padding_override| padding_override.apply(padding))
+    }
+
+    /// Steps the padding animation and returns true while it's still in progress.
+    fn animate_padding(&mut self, dt: f32) -> bool {
+        let length = if SETTINGS.get::<RendererSettings>().animations_enabled && !power_saving() {
+            SETTINGS.get::<WindowSettings>().padding_animation_length
+        } else {
+            0.0
+        };
+        self.padding_animation.animate(dt, length)
+    }
+
+    /// True when the window shows the grids, and has a size they can be drawn at.
+    pub fn is_showing(&self) -> bool {
+        self.ui_state == UIState::Showing
+            && self.skia_renderer.window().is_minimized() != Some(true)
+    }
+
     pub fn get_grid_size(&self) -> Dimensions {
         self.renderer.get_grid_size()
     }
@@ -534,9 +1587,6 @@ impl WinitWindowWrapper {
     fn update_window_size_from_grid(&mut self, window_padding: &WindowPadding) {
         let window = self.skia_renderer.window();
 
-        let window_padding_width = window_padding.left + window_padding.right;
-        let window_padding_height = window_padding.top + window_padding.bottom;
-
         let grid_size = Dimensions {
             width: self.requested_columns.take().unwrap_or(
                 self.saved_grid_size
@@ -552,8 +1602,8 @@ impl WinitWindowWrapper {
             .renderer
             .grid_renderer
             .convert_grid_to_physical(grid_size);
-        new_size.width += window_padding_width;
-        new_size.height += window_padding_height;
+        new_size.width = (new_size.width as i64 + window_padding.width() as i64).max(1) as u32;
+        new_size.height = (new_size.height as i64 + window_padding.height() as i64).max(1) as u32;
         log::info!(
             "Resizing window based on grid. Grid Size: {:?}, Window Size {:?}",
             grid_size,
@@ -562,14 +1612,66 @@ impl WinitWindowWrapper {
         let _ = window.request_inner_size(new_size);
     }
 
+    /// With `g:neovide_resize_preview`, stretches the last frame over the window while it's being
+    /// resized. Returns true when the grid should be reflowed to the new size, either right away or
+    /// after the size settles.
+    fn preview_resize(
+        &mut self,
+        new_size: PhysicalSize<u32>,
+        should_render: &mut ShouldRender,
+    ) -> bool {
+        let window = self.skia_renderer.window();
+        if !SETTINGS.get::<WindowSettings>().resize_preview
+            || self.fullscreen.is_some()
+            || window.is_maximized()
+        {
+            return true;
+        }
+
+        let now = Instant::now();
+        let live_resize = match &mut self.live_resize {
+            Some(live_resize) => live_resize,
+            None => {
+                self.renderer
+                    .begin_resize_preview(self.skia_renderer.canvas(), self.saved_inner_size);
+                self.live_resize.insert(LiveResize {
+                    started: now,
+                    last_change: now,
+                    size: self.saved_inner_size,
+                })
+            }
+        };
+        if live_resize.size != new_size {
+            live_resize.size = new_size;
+            live_resize.last_change = now;
+            should_render.update(ShouldRender::Immediately);
+        }
+        let reflow_at = (live_resize.last_change + RESIZE_SETTLE_TIME)
+            .min(live_resize.started + RESIZE_REFLOW_INTERVAL);
+        if now >= reflow_at {
+            return true;
+        }
+        should_render.update(ShouldRender::Deadline(reflow_at));
+        false
+    }
+
+    /// Stops stretching the last frame once the grid of the new size has arrived, or Neovim didn't
+    /// send it in time.
+    fn end_grid_resize_wait(&mut self) {
+        self.awaited_grid_size = None;
+        if self.live_resize.is_none() {
+            self.renderer.end_resize_preview();
+        }
+    }
+
     fn get_grid_size_from_window(&self, min_width: u64, min_height: u64) -> Dimensions {
         let window_padding = self.window_padding;
-        let window_padding_width = window_padding.left + window_padding.right;
-        let window_padding_height = window_padding.top + window_padding.bottom;
-
+        // Negative padding makes the grid larger than the window
         let content_size = PhysicalSize {
-            width: self.saved_inner_size.width - window_padding_width,
-            height: self.saved_inner_size.height - window_padding_height,
+            width: (self.saved_inner_size.width as i64 - window_padding.width() as i64).max(0)
+                as u32,
+            height: (self.saved_inner_size.height as i64 - window_padding.height() as i64).max(0)
+                as u32,
         };
 
         let grid_size = self
@@ -593,6 +1695,7 @@ impl WinitWindowWrapper {
             return;
         }
         self.saved_grid_size = Some(grid_size);
+        self.awaited_grid_size = Some((grid_size, Instant::now() + GRID_RESIZE_TIMEOUT));
         log::info!(
             "Resizing grid based on window size. Grid Size: {:?}, Window Size {:?}",
             grid_size,
@@ -606,34 +1709,65 @@ impl WinitWindowWrapper {
 
     fn update_ime_position(&mut self) {
         let font_dimensions = self.renderer.grid_renderer.font_dimensions;
-        let cursor_position = self.renderer.get_cursor_position();
-        let position = PhysicalPosition::new(
-            cursor_position.x.round() as i32,
-            cursor_position.y.round() as i32 + font_dimensions.height as i32,
-        );
-        if position != self.ime_position {
-            self.ime_position = position;
-            self.skia_renderer.window().set_ime_cursor_area(
-                Position::Physical(position),
-                PhysicalSize::new(100, font_dimensions.height as u32),
-            );
+        // The candidates are shown for the clause being converted
+        let mut position = self.renderer.get_cursor_position();
+        position.x += (self.renderer.ime_selection_offset() as u64 * font_dimensions.width) as f32;
+        let area = self.keyboard_manager.ime_area(position, font_dimensions);
+        if area != self.ime_area {
+            self.ime_area = area;
+            let (position, size) = area;
+            self.skia_renderer
+                .window()
+                .set_ime_cursor_area(Position::Physical(position), size);
         }
     }
 
     fn handle_scale_factor_update(&mut self, scale_factor: f64) {
+        log::info!("Scale factor changed to {scale_factor}");
         #[cfg(target_os = "macos")]
         self.macos_feature.handle_scale_factor_update(scale_factor);
         self.renderer.handle_os_scale_factor_change(scale_factor);
         self.skia_renderer.resize();
     }
 
+    /// The padding the grids are drawn at, which follows the animation.
     fn padding_as_grid(&self) -> Rect {
         let font_dimensions = self.renderer.grid_renderer.font_dimensions;
+        let zen_mode_padding = self
+            .zen_mode
+            .padding(SETTINGS.get::<WindowSettings>().zen_mode_padding);
+        let padding = self
+            .padding_animation
+            .current()
+            .grow(zen_mode_padding as i32);
         Rect {
-            left: self.window_padding.left as scalar / font_dimensions.width as scalar,
-            right: self.window_padding.right as scalar / font_dimensions.width as scalar,
-            top: self.window_padding.top as scalar / font_dimensions.height as scalar,
-            bottom: self.window_padding.bottom as scalar / font_dimensions.height as scalar,
+            left: padding.left as scalar / font_dimensions.width as scalar,
+            right: padding.right as scalar / font_dimensions.width as scalar,
+            top: padding.top as scalar / font_dimensions.height as scalar,
+            bottom: padding.bottom as scalar / font_dimensions.height as scalar,
         }
     }
 }
+
+/// The Neovide commands of the palette that only act on the window. They are run directly instead
+/// of through Neovim, so that they also work while Neovim is busy or waiting at a prompt.
+fn palette_window_command(item: &PaletteItem) -> Option<WindowCommand> {
+    if item.kind != PaletteItemKind::Command {
+        return None;
+    }
+    match item.value.as_str() {
+        "NeovideFocus" => Some(WindowCommand::FocusWindow),
+        "NeovideLogs" => Some(WindowCommand::ShowLogs {
+            level: String::new(),
+            module: String::new(),
+        }),
+        "NeovideRequestAttention" => Some(WindowCommand::RequestAttention(
+            UserAttentionType::Informational,
+        )),
+        #[cfg(windows)]
+        "NeovideRegisterRightClick" => Some(WindowCommand::RegisterRightClick),
+        #[cfg(windows)]
+        "NeovideUnregisterRightClick" => Some(WindowCommand::UnregisterRightClick),
+        _ => None,
+    }
+}
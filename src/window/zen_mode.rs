@@ -0,0 +1,82 @@
+use crate::renderer::animation_utils::{ease, ease_in_out_quad};
+
+/// The time in seconds it takes to enter or leave zen mode.
+const ANIMATION_LENGTH: f32 = 0.3;
+
+/// The font scale and padding of zen mode, animated in and out with `:NeovideZenMode`. They are
+/// applied on top of the normal settings, so nothing needs to be saved and restored.
+#[derive(Clone, Debug, Default)]
+pub struct ZenMode {
+    enabled: bool,
+    t: f32,
+}
+
+impl ZenMode {
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    fn target(&self) -> f32 {
+        if self.enabled {
+            1.0
+        } else {
+            0.0
+        }
+    }
+
+    /// Returns true when the animation has reached the current state.
+    pub fn is_settled(&self) -> bool {
+        self.t == self.target()
+    }
+
+    /// Steps the animation towards the current state and returns true while it's in progress.
+    pub fn animate(&mut self, dt: f32) -> bool {
+        if self.is_settled() {
+            return false;
+        }
+        let step = dt / ANIMATION_LENGTH;
+        self.t = if self.enabled {
+            (self.t + step).min(1.0)
+        } else {
+            (self.t - step).max(0.0)
+        };
+        true
+    }
+
+    /// The factor the font size is multiplied with, `scale` when fully in zen mode.
+    pub fn scale_factor(&self, scale: f32) -> f64 {
+        ease(ease_in_out_quad, 1.0, scale, self.t) as f64
+    }
+
+    /// The padding added to each side of the window, `padding` pixels when fully in zen mode.
+    pub fn padding(&self, padding: u32) -> u32 {
+        ease(ease_in_out_quad, 0.0, padding as f32, self.t).round() as u32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zen_mode_animation() {
+        let mut zen_mode = ZenMode::default();
+        assert!(!zen_mode.animate(0.1));
+        assert_eq!(zen_mode.scale_factor(1.5), 1.0);
+
+        zen_mode.set_enabled(true);
+        assert!(zen_mode.animate(ANIMATION_LENGTH / 2.0));
+        assert!(!zen_mode.is_settled());
+        assert_eq!(zen_mode.padding(100), 50);
+        assert!(zen_mode.animate(ANIMATION_LENGTH));
+        assert!(zen_mode.is_settled());
+        assert!(!zen_mode.animate(0.1));
+        assert_eq!(zen_mode.scale_factor(1.5), 1.5);
+        assert_eq!(zen_mode.padding(100), 100);
+
+        zen_mode.set_enabled(false);
+        assert!(zen_mode.animate(ANIMATION_LENGTH));
+        assert_eq!(zen_mode.scale_factor(1.5), 1.0);
+        assert_eq!(zen_mode.padding(100), 0);
+    }
+}
@@ -1,22 +1,93 @@
 use std::{
-    ffi::CString,
+    ffi::{CString, OsStr, OsString},
+    io,
+    iter::once,
+    mem::size_of,
+    os::windows::{
+        ffi::OsStrExt,
+        io::{AsRawHandle, FromRawHandle, OwnedHandle, RawHandle},
+    },
+    path::Path,
     ptr::{null, null_mut},
+    slice,
 };
 
+use anyhow::{ensure, Result};
+use image::RgbaImage;
+use raw_window_handle::{HasRawWindowHandle, RawWindowHandle};
 use winapi::{
+    ctypes::c_void,
     shared::{
-        minwindef::{DWORD, HKEY, MAX_PATH},
-        windef::DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2,
+        minwindef::{BOOL, DWORD, FALSE, HKEY, HLOCAL, MAX_PATH, TRUE},
+        ntdef::LPWSTR,
+        sddl::{
+            ConvertSidToStringSidW, ConvertStringSecurityDescriptorToSecurityDescriptorW,
+            SDDL_REVISION_1,
+        },
+        windef::{DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, HICON, HWND, POINT},
+        winerror::{ERROR_FILE_NOT_FOUND, SUCCEEDED},
+        wtypesbase::CLSCTX_INPROC_SERVER,
     },
     um::{
+        combaseapi::CoCreateInstance,
+        dwmapi::{DwmExtendFrameIntoClientArea, DwmSetWindowAttribute},
         libloaderapi::GetModuleFileNameA,
-        winnt::{KEY_WRITE, REG_OPTION_NON_VOLATILE, REG_SZ},
-        winreg::{RegCloseKey, RegCreateKeyExA, RegDeleteTreeA, RegSetValueExA, HKEY_CURRENT_USER},
-        winuser::SetProcessDpiAwarenessContext,
+        minwinbase::SECURITY_ATTRIBUTES,
+        playsoundapi::{PlaySoundW, SND_ASYNC, SND_FILENAME, SND_NODEFAULT},
+        processthreadsapi::{
+            GetCurrentProcess, GetCurrentProcessId, OpenProcess, OpenProcessToken,
+            ProcessIdToSessionId,
+        },
+        securitybaseapi::{EqualSid, GetTokenInformation},
+        shobjidl_core::{
+            CLSID_TaskbarList, ITaskbarList3, TBPF_INDETERMINATE, TBPF_NOPROGRESS, TBPF_NORMAL,
+        },
+        uxtheme::MARGINS,
+        winbase::{GetNamedPipeServerProcessId, LocalFree},
+        wincon::{AttachConsole, ATTACH_PARENT_PROCESS},
+        wingdi::{CreateBitmap, DeleteObject},
+        winnt::{
+            TokenUser, HANDLE, KEY_WRITE, PROCESS_QUERY_LIMITED_INFORMATION, PSECURITY_DESCRIPTOR,
+            PSID, REG_OPTION_NON_VOLATILE, REG_SZ, TOKEN_QUERY, TOKEN_USER,
+        },
+        winreg::{
+            RegCloseKey, RegCreateKeyExA, RegCreateKeyExW, RegDeleteTreeA, RegDeleteTreeW,
+            RegGetValueA, RegSetValueExA, RegSetValueExW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD,
+        },
+        winuser::{
+            AppendMenuW, CreateIconIndirect, CreatePopupMenu, CreateWindowExW, DestroyIcon,
+            DestroyMenu, DestroyWindow, GetCursorPos, MessageBeep, SetLayeredWindowAttributes,
+            SetProcessDpiAwarenessContext, SystemParametersInfoW, TrackPopupMenu,
+            HCF_HIGHCONTRASTON, HIGHCONTRASTW, ICONINFO, LWA_ALPHA, MB_OK, MF_SEPARATOR, MF_STRING,
+            SPI_GETCLIENTAREAANIMATION, SPI_GETHIGHCONTRAST, SS_BLACKRECT, TPM_RETURNCMD,
+            TPM_RIGHTBUTTON, WS_EX_LAYERED, WS_EX_NOACTIVATE, WS_EX_TOOLWINDOW, WS_EX_TOPMOST,
+            WS_POPUP, WS_VISIBLE,
+        },
     },
 };
+use winit::window::Window;
+use wio::com::ComPtr;
+
+use crate::{
+    error_msg,
+    renderer::d3d::call_com_fn,
+    window::{run_menu_command, Progress, UserMenuItem, WindowsBackdrop},
+};
 
-use crate::error_msg;
+/// The opacity of the windows covering the other monitors in zen mode.
+const MONITOR_COVER_ALPHA: u8 = 200;
+
+/// `DWMWA_SYSTEMBACKDROP_TYPE`, available since Windows 11 22H2, and its `DWM_SYSTEMBACKDROP_TYPE`
+/// values, which are missing from winapi.
+const DWMWA_SYSTEMBACKDROP_TYPE: DWORD = 38;
+const DWMSBT_NONE: DWORD = 1;
+const DWMSBT_MAINWINDOW: DWORD = 2;
+const DWMSBT_TRANSIENTWINDOW: DWORD = 3;
+const DWMSBT_TABBEDWINDOW: DWORD = 4;
+
+fn to_wide(text: impl AsRef<OsStr>) -> Vec<u16> {
+    text.as_ref().encode_wide().chain(once(0)).collect()
+}
 
 fn get_binary_path() -> String {
     let mut buffer = vec![0u8; MAX_PATH];
@@ -238,8 +309,559 @@ pub fn unregister_right_click() {
     }
 }
 
+/// The key listing Neovide in the "Open With" menu of Explorer.
+const OPEN_WITH_KEY: &str = "Software\\Classes\\Applications\\neovide.exe";
+
+/// The extensions Neovide is suggested for, like the MIME types of the Linux desktop entry.
+const SUPPORTED_EXTENSIONS: &[&str] = &[
+    ".txt", ".md", ".log", ".ini", ".cfg", ".conf", ".toml", ".yaml", ".yml", ".json", ".xml",
+    ".c", ".h", ".cpp", ".hpp", ".cs", ".rs", ".go", ".java", ".py", ".rb", ".js", ".ts", ".lua",
+    ".vim", ".sh", ".ps1", ".bat", ".tex",
+];
+
+/// Creates the key with its default value, and the given named values.
+fn set_registry_values(path: &str, default: Option<&OsStr>, values: &[(&str, &str)]) -> bool {
+    let mut registry_key: HKEY = null_mut();
+    let registry_path = to_wide(path);
+    unsafe {
+        if RegCreateKeyExW(
+            HKEY_CURRENT_USER,
+            registry_path.as_ptr(),
+            0,
+            null_mut(),
+            REG_OPTION_NON_VOLATILE,
+            KEY_WRITE,
+            null_mut(),
+            &mut registry_key,
+            null_mut(),
+        ) != 0
+        {
+            return false;
+        }
+        let default = default.map(|value| (None, value));
+        let values = values
+            .iter()
+            .map(|&(name, value)| (Some(name), OsStr::new(value)));
+        let mut success = true;
+        for (name, value) in default.into_iter().chain(values) {
+            let wide_name = name.map(to_wide);
+            let wide_value = to_wide(value);
+            // The size is in bytes, including the terminating null
+            success &= RegSetValueExW(
+                registry_key,
+                wide_name.as_ref().map_or(null(), |name| name.as_ptr()),
+                0,
+                REG_SZ,
+                wide_value.as_ptr() as *const u8,
+                (wide_value.len() * size_of::<u16>()) as DWORD,
+            ) == 0;
+        }
+        RegCloseKey(registry_key);
+        success
+    }
+}
+
+/// Lists Neovide in the "Open With" menu of Explorer, returns the registry key. Explorer starts
+/// one process for each selected file, so they are gathered into a single window with
+/// `--single-instance`.
+pub fn register_open_with(neovide_path: &Path) -> Result<String> {
+    let mut command = OsString::from("\"");
+    command.push(neovide_path);
+    command.push("\" --single-instance \"%1\"");
+    let supported_types = SUPPORTED_EXTENSIONS
+        .iter()
+        .map(|extension| (*extension, ""))
+        .collect::<Vec<_>>();
+    let registered = set_registry_values(OPEN_WITH_KEY, None, &[("FriendlyAppName", "Neovide")])
+        && set_registry_values(
+            &format!("{OPEN_WITH_KEY}\\DefaultIcon"),
+            Some(neovide_path.as_os_str()),
+            &[],
+        )
+        && set_registry_values(
+            &format!("{OPEN_WITH_KEY}\\shell\\open\\command"),
+            Some(command.as_os_str()),
+            &[],
+        )
+        && set_registry_values(
+            &format!("{OPEN_WITH_KEY}\\SupportedTypes"),
+            None,
+            &supported_types,
+        );
+    ensure!(
+        registered,
+        "Could not write the registry key {OPEN_WITH_KEY}"
+    );
+    Ok(format!("HKEY_CURRENT_USER\\{OPEN_WITH_KEY}"))
+}
+
+/// Removes Neovide from the "Open With" menu of Explorer, returns the removed registry key.
+pub fn unregister_open_with() -> Result<String> {
+    let registry_path = to_wide(OPEN_WITH_KEY);
+    let status = unsafe { RegDeleteTreeW(HKEY_CURRENT_USER, registry_path.as_ptr()) };
+    ensure!(
+        status == 0 || status == ERROR_FILE_NOT_FOUND as i32,
+        "Could not remove the registry key {OPEN_WITH_KEY}"
+    );
+    Ok(format!("HKEY_CURRENT_USER\\{OPEN_WITH_KEY}"))
+}
+
+/// Whether "Animation effects" are turned off in the accessibility settings.
+pub fn prefers_reduced_motion() -> bool {
+    let mut animations: BOOL = TRUE;
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETCLIENTAREAANIMATION,
+            0,
+            &mut animations as *mut BOOL as *mut c_void,
+            0,
+        );
+    }
+    animations == FALSE
+}
+
+/// Whether a contrast theme is selected in the accessibility settings.
+pub fn prefers_high_contrast() -> bool {
+    let mut high_contrast = HIGHCONTRASTW {
+        cbSize: size_of::<HIGHCONTRASTW>() as u32,
+        dwFlags: 0,
+        lpszDefaultScheme: null_mut(),
+    };
+    unsafe {
+        SystemParametersInfoW(
+            SPI_GETHIGHCONTRAST,
+            high_contrast.cbSize,
+            &mut high_contrast as *mut HIGHCONTRASTW as *mut c_void,
+            0,
+        );
+    }
+    high_contrast.dwFlags & HCF_HIGHCONTRASTON != 0
+}
+
+/// The "Text size" of the accessibility settings, which is only stored in the registry when it's
+/// been changed from 100%.
+pub fn text_scale_factor() -> Option<f32> {
+    let key = CString::new("Software\\Microsoft\\Accessibility").unwrap();
+    let value = CString::new("TextScaleFactor").unwrap();
+    let mut percent: DWORD = 0;
+    let mut size = size_of::<DWORD>() as DWORD;
+    let status = unsafe {
+        RegGetValueA(
+            HKEY_CURRENT_USER,
+            key.as_ptr(),
+            value.as_ptr(),
+            RRF_RT_REG_DWORD,
+            null_mut(),
+            &mut percent as *mut DWORD as *mut c_void,
+            &mut size,
+        )
+    };
+    (status == 0).then_some(percent as f32 / 100.0)
+}
+
+/// Plays the sound file asynchronously, or the default beep when the path is empty.
+pub fn play_sound(sound: &str) {
+    unsafe {
+        if sound.is_empty() {
+            MessageBeep(MB_OK);
+        } else if PlaySoundW(
+            to_wide(sound).as_ptr(),
+            null_mut(),
+            SND_FILENAME | SND_ASYNC | SND_NODEFAULT,
+        ) == FALSE
+        {
+            log::warn!("Could not play {sound}");
+        }
+    }
+}
+
 pub fn windows_fix_dpi() {
     unsafe {
         SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
     }
 }
+
+/// Attaches to the console of the parent process, so that reports printed to stdout are visible
+/// even though Neovide is a GUI application.
+pub fn windows_attach_to_console() {
+    unsafe {
+        AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// Shows the user defined context menu at the mouse position, and runs the command of the selected
+/// item. The menu is modal, so this returns only after it's closed.
+pub fn show_context_menu(window: &Window, items: &[UserMenuItem]) {
+    let RawWindowHandle::Win32(handle) = window.raw_window_handle() else {
+        return;
+    };
+    let selected = unsafe {
+        let menu = CreatePopupMenu();
+        for (index, item) in items.iter().enumerate() {
+            if item.command.is_some() {
+                let title: Vec<u16> = OsStr::new(&item.title)
+                    .encode_wide()
+                    .chain(once(0))
+                    .collect();
+                // The ids start from 1, since 0 means that nothing was selected
+                AppendMenuW(menu, MF_STRING, index + 1, title.as_ptr());
+            } else {
+                AppendMenuW(menu, MF_SEPARATOR, 0, null());
+            }
+        }
+        let mut position = POINT { x: 0, y: 0 };
+        GetCursorPos(&mut position);
+        let selected = TrackPopupMenu(
+            menu,
+            TPM_RETURNCMD | TPM_RIGHTBUTTON,
+            position.x,
+            position.y,
+            0,
+            handle.hwnd as HWND,
+            null(),
+        );
+        DestroyMenu(menu);
+        selected
+    };
+    let command = (selected as usize)
+        .checked_sub(1)
+        .and_then(|index| items.get(index))
+        .and_then(|item| item.command.as_deref());
+    if let Some(command) = command {
+        run_menu_command(command);
+    }
+}
+
+/// A black, semi transparent window covering a monitor, destroyed when dropped.
+pub struct MonitorCover(HWND);
+
+impl Drop for MonitorCover {
+    fn drop(&mut self) {
+        unsafe {
+            DestroyWindow(self.0);
+        }
+    }
+}
+
+/// Dims all monitors except the one `window` is on, by covering them with a topmost window.
+pub fn cover_other_monitors(window: &Window) -> Vec<MonitorCover> {
+    let current_monitor = window.current_monitor();
+    // The predefined static control draws itself black with SS_BLACKRECT, so there's no need to
+    // register a window class
+    let class = to_wide("STATIC");
+    window
+        .available_monitors()
+        .filter(|monitor| Some(monitor) != current_monitor.as_ref())
+        .filter_map(|monitor| {
+            let position = monitor.position();
+            let size = monitor.size();
+            let hwnd = unsafe {
+                CreateWindowExW(
+                    WS_EX_LAYERED | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE | WS_EX_TOPMOST,
+                    class.as_ptr(),
+                    null(),
+                    WS_POPUP | WS_VISIBLE | SS_BLACKRECT,
+                    position.x,
+                    position.y,
+                    size.width as i32,
+                    size.height as i32,
+                    null_mut(),
+                    null_mut(),
+                    null_mut(),
+                    null_mut(),
+                )
+            };
+            if hwnd.is_null() {
+                log::error!("Could not create a window covering {:?}", monitor.name());
+                return None;
+            }
+            unsafe {
+                SetLayeredWindowAttributes(hwnd, 0, MONITOR_COVER_ALPHA, LWA_ALPHA);
+            }
+            Some(MonitorCover(hwnd))
+        })
+        .collect()
+}
+
+/// Creates the interface used to change the taskbar buttons.
+fn taskbar_list() -> Option<ComPtr<ITaskbarList3>> {
+    let taskbar = call_com_fn::<c_void, ITaskbarList3, _>(|taskbar, id| unsafe {
+        CoCreateInstance(
+            &CLSID_TaskbarList,
+            null_mut(),
+            CLSCTX_INPROC_SERVER,
+            id,
+            taskbar,
+        )
+    });
+    match taskbar {
+        Ok(taskbar) if unsafe { SUCCEEDED(taskbar.HrInit()) } => Some(taskbar),
+        _ => {
+            log::error!("Could not create the taskbar list");
+            None
+        }
+    }
+}
+
+/// Shows the progress on the taskbar button of the window, or removes it.
+pub fn set_taskbar_progress(window: &Window, progress: Option<Progress>) {
+    let RawWindowHandle::Win32(handle) = window.raw_window_handle() else {
+        return;
+    };
+    let hwnd = handle.hwnd as HWND;
+    let Some(taskbar) = taskbar_list() else {
+        return;
+    };
+    unsafe {
+        match progress {
+            None => taskbar.SetProgressState(hwnd, TBPF_NOPROGRESS),
+            Some(Progress::Indeterminate) => taskbar.SetProgressState(hwnd, TBPF_INDETERMINATE),
+            Some(Progress::Value(value)) => {
+                taskbar.SetProgressState(hwnd, TBPF_NORMAL);
+                taskbar.SetProgressValue(hwnd, (value * 1000.0).round() as u64, 1000)
+            }
+        };
+    }
+}
+
+/// Creates an icon from RGBA pixels. The mask is left empty, since the color bitmap has alpha.
+fn create_icon(image: &RgbaImage) -> HICON {
+    let (width, height) = image.dimensions();
+    let bgra: Vec<u8> = image
+        .pixels()
+        .flat_map(|pixel| {
+            let [r, g, b, a] = pixel.0;
+            [b, g, r, a]
+        })
+        .collect();
+    // The rows of monochrome bitmaps are aligned to 16 bits
+    let mask = vec![0u8; ((width as usize + 15) / 16 * 2) * height as usize];
+    unsafe {
+        let color = CreateBitmap(
+            width as i32,
+            height as i32,
+            1,
+            32,
+            bgra.as_ptr() as *const _,
+        );
+        let mask = CreateBitmap(width as i32, height as i32, 1, 1, mask.as_ptr() as *const _);
+        let mut icon_info = ICONINFO {
+            fIcon: TRUE,
+            xHotspot: 0,
+            yHotspot: 0,
+            hbmMask: mask,
+            hbmColor: color,
+        };
+        let icon = CreateIconIndirect(&mut icon_info);
+        DeleteObject(color as *mut _);
+        DeleteObject(mask as *mut _);
+        icon
+    }
+}
+
+/// Lets DWM draw the backdrop material behind the whole window. The client area is extended over
+/// by the frame, so the material shows through where the swap chain is transparent.
+pub fn set_window_backdrop(window: &Window, backdrop: WindowsBackdrop) {
+    let RawWindowHandle::Win32(handle) = window.raw_window_handle() else {
+        return;
+    };
+    let hwnd = handle.hwnd as HWND;
+    let backdrop_type = match backdrop {
+        WindowsBackdrop::None => DWMSBT_NONE,
+        WindowsBackdrop::Acrylic => DWMSBT_TRANSIENTWINDOW,
+        WindowsBackdrop::Mica => DWMSBT_MAINWINDOW,
+        WindowsBackdrop::Tabbed => DWMSBT_TABBEDWINDOW,
+    };
+    let extent = if backdrop == WindowsBackdrop::None {
+        0
+    } else {
+        -1
+    };
+    let margins = MARGINS {
+        cxLeftWidth: extent,
+        cxRightWidth: extent,
+        cyTopHeight: extent,
+        cyBottomHeight: extent,
+    };
+    unsafe {
+        DwmExtendFrameIntoClientArea(hwnd, &margins);
+        let result = DwmSetWindowAttribute(
+            hwnd,
+            DWMWA_SYSTEMBACKDROP_TYPE,
+            &backdrop_type as *const DWORD as *const c_void,
+            std::mem::size_of::<DWORD>() as DWORD,
+        );
+        if !SUCCEEDED(result) && backdrop != WindowsBackdrop::None {
+            log::warn!("Backdrop materials need Windows 11 version 22H2 or newer");
+        }
+    }
+}
+
+/// Shows a small image over the bottom right corner of the taskbar button, or removes it.
+pub fn set_taskbar_overlay(window: &Window, overlay: Option<&RgbaImage>) {
+    let RawWindowHandle::Win32(handle) = window.raw_window_handle() else {
+        return;
+    };
+    let Some(taskbar) = taskbar_list() else {
+        return;
+    };
+    let icon = overlay.map_or(null_mut(), create_icon);
+    let description = to_wide("Neovide");
+    unsafe {
+        taskbar.SetOverlayIcon(handle.hwnd as HWND, icon, description.as_ptr());
+        // The taskbar keeps its own copy of the icon
+        if !icon.is_null() {
+            DestroyIcon(icon);
+        }
+    }
+}
+
+/// The user a process runs as, kept as the buffer of its `TOKEN_USER`, which the SID points into.
+struct ProcessUser(Vec<u64>);
+
+impl ProcessUser {
+    fn of(process: HANDLE) -> Result<Self> {
+        let mut token = null_mut();
+        unsafe {
+            ensure!(
+                OpenProcessToken(process, TOKEN_QUERY, &mut token) != 0,
+                "Could not open the token of the process: {}",
+                io::Error::last_os_error()
+            );
+            let token = OwnedHandle::from_raw_handle(token as RawHandle);
+            let mut size = 0;
+            GetTokenInformation(
+                token.as_raw_handle() as HANDLE,
+                TokenUser,
+                null_mut(),
+                0,
+                &mut size,
+            );
+            // u64 keeps the buffer aligned for the structure
+            let mut buffer = vec![0u64; (size as usize).div_ceil(size_of::<u64>())];
+            ensure!(
+                GetTokenInformation(
+                    token.as_raw_handle() as HANDLE,
+                    TokenUser,
+                    buffer.as_mut_ptr() as *mut c_void,
+                    size,
+                    &mut size,
+                ) != 0,
+                "Could not read the user of the process: {}",
+                io::Error::last_os_error()
+            );
+            Ok(Self(buffer))
+        }
+    }
+
+    fn current() -> Result<Self> {
+        Self::of(unsafe { GetCurrentProcess() })
+    }
+
+    fn sid(&self) -> PSID {
+        unsafe { (*(self.0.as_ptr() as *const TOKEN_USER)).User.Sid }
+    }
+}
+
+/// The SID of the current user, like `S-1-5-21-...`, which unlike the user name can't be changed
+/// with the environment.
+pub fn current_user_sid() -> Result<String> {
+    let user = ProcessUser::current()?;
+    let mut string: LPWSTR = null_mut();
+    unsafe {
+        ensure!(
+            ConvertSidToStringSidW(user.sid(), &mut string) != 0,
+            "Could not convert the SID of the user: {}",
+            io::Error::last_os_error()
+        );
+        let length = (0..).take_while(|&i| *string.add(i) != 0).count();
+        let sid = String::from_utf16_lossy(slice::from_raw_parts(string, length));
+        LocalFree(string as HLOCAL);
+        Ok(sid)
+    }
+}
+
+/// The login session of the current process, the users of Remote Desktop each have their own.
+pub fn current_session_id() -> Result<u32> {
+    let mut session = 0;
+    ensure!(
+        unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) } != 0,
+        "Could not get the session of the process: {}",
+        io::Error::last_os_error()
+    );
+    Ok(session)
+}
+
+/// A security descriptor that grants access only to the current user, for the named pipes that
+/// other users mustn't connect to.
+pub struct UserOnlySecurity(PSECURITY_DESCRIPTOR);
+
+impl UserOnlySecurity {
+    pub fn new() -> Result<Self> {
+        // A protected DACL, which doesn't inherit other entries, with full access for the user
+        let sddl = to_wide(format!("D:P(A;;GA;;;{})", current_user_sid()?));
+        let mut descriptor = null_mut();
+        ensure!(
+            unsafe {
+                ConvertStringSecurityDescriptorToSecurityDescriptorW(
+                    sddl.as_ptr(),
+                    SDDL_REVISION_1 as DWORD,
+                    &mut descriptor,
+                    null_mut(),
+                )
+            } != 0,
+            "Could not create the security descriptor: {}",
+            io::Error::last_os_error()
+        );
+        Ok(Self(descriptor))
+    }
+
+    /// The attributes for creating an object with the descriptor, valid as long as it is.
+    pub fn attributes(&self) -> SECURITY_ATTRIBUTES {
+        SECURITY_ATTRIBUTES {
+            nLength: size_of::<SECURITY_ATTRIBUTES>() as DWORD,
+            lpSecurityDescriptor: self.0,
+            bInheritHandle: FALSE,
+        }
+    }
+}
+
+impl Drop for UserOnlySecurity {
+    fn drop(&mut self) {
+        unsafe {
+            LocalFree(self.0 as HLOCAL);
+        }
+    }
+}
+
+/// Checks that the server end of the named pipe is a process of the current user in the same
+/// session. Anyone can create a pipe whose name isn't taken yet, so nothing is sent before this.
+pub fn verify_pipe_server(pipe: &impl AsRawHandle) -> Result<()> {
+    let mut process_id = 0;
+    ensure!(
+        unsafe { GetNamedPipeServerProcessId(pipe.as_raw_handle() as HANDLE, &mut process_id) }
+            != 0,
+        "Could not get the server of the pipe: {}",
+        io::Error::last_os_error()
+    );
+    let mut session = 0;
+    ensure!(
+        unsafe { ProcessIdToSessionId(process_id, &mut session) } != 0
+            && session == current_session_id()?,
+        "The server of the pipe runs in another session"
+    );
+    let process = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, FALSE, process_id) };
+    ensure!(
+        !process.is_null(),
+        "Could not open the server of the pipe: {}",
+        io::Error::last_os_error()
+    );
+    let process = unsafe { OwnedHandle::from_raw_handle(process as RawHandle) };
+    let server = ProcessUser::of(process.as_raw_handle() as HANDLE)?;
+    let current = ProcessUser::current()?;
+    ensure!(
+        unsafe { EqualSid(server.sid(), current.sid()) } != 0,
+        "The server of the pipe runs as another user"
+    );
+    Ok(())
+}